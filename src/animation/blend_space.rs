@@ -0,0 +1,138 @@
+// clips placed along one parameter, like idle, walk and run by speed. the two clips around the parameter
+// value are blended, outside the range the nearest end plays alone
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlendSpace1d {
+    pub parameter: String,
+    // (position, clip), kept sorted by position
+    pub points: Vec<(f32, usize)>
+}
+
+impl BlendSpace1d {
+    pub fn new(parameter: &str, mut points: Vec<(f32, usize)>) -> BlendSpace1d {
+        points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        BlendSpace1d {
+            parameter: parameter.to_string(),
+            points
+        }
+    }
+
+    // (clip, weight) of every clip with a weight above 0
+    pub fn weights(&self, value: f32) -> Vec<(usize, f32)> {
+        let points = &self.points;
+        if points.is_empty() {
+            return vec![];
+        }
+        if value <= points[0].0 {
+            return vec![(points[0].1, 1.0)];
+        }
+        if value >= points[points.len() - 1].0 {
+            return vec![(points[points.len() - 1].1, 1.0)];
+        }
+
+        let upper = points.iter().position(|point| point.0 > value).unwrap_or(points.len() - 1);
+        let (a, b) = (&points[upper - 1], &points[upper]);
+        let t = (value - a.0) / (b.0 - a.0);
+        if t <= 0.0 {
+            vec![(a.1, 1.0)]
+        } else {
+            vec![(a.1, 1.0 - t), (b.1, t)]
+        }
+    }
+}
+
+// clips placed on a plane of two parameters, like strafing by velocity. weights use gradient band
+// interpolation: every clip's influence falls off towards each of the other clips, so a parameter on a
+// clip's position plays just that clip, and the points don't need to lie on a grid
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlendSpace2d {
+    pub x_parameter: String,
+    pub y_parameter: String,
+    // ([x, y], clip)
+    pub points: Vec<([f32; 2], usize)>
+}
+
+impl BlendSpace2d {
+    pub fn new(x_parameter: &str, y_parameter: &str, points: Vec<([f32; 2], usize)>) -> BlendSpace2d {
+        BlendSpace2d {
+            x_parameter: x_parameter.to_string(),
+            y_parameter: y_parameter.to_string(),
+            points
+        }
+    }
+
+    // (clip, weight) of every clip with a weight above 0, the weights add up to 1
+    pub fn weights(&self, x: f32, y: f32) -> Vec<(usize, f32)> {
+        let mut influences = vec![];
+        for (i, (position, clip)) in self.points.iter().enumerate() {
+            let to_sample = [x - position[0], y - position[1]];
+            let mut influence: f32 = 1.0;
+            for (j, (other, _)) in self.points.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                let to_other = [other[0] - position[0], other[1] - position[1]];
+                let length_squared = to_other[0] * to_other[0] + to_other[1] * to_other[1];
+                if length_squared <= 0.0 {
+                    continue;
+                }
+                let along = (to_sample[0] * to_other[0] + to_sample[1] * to_other[1]) / length_squared;
+                influence = influence.min((1.0 - along).max(0.0));
+            }
+            if influence > 0.0 {
+                influences.push((*clip, influence));
+            }
+        }
+
+        let total: f32 = influences.iter().map(|(_, influence)| influence).sum();
+        if total <= 0.0 {
+            // outside of every band, the closest clip plays alone
+            return self.points.iter()
+                .min_by(|a, b| {
+                    let distance = |p: &[f32; 2]| (p[0] - x).powi(2) + (p[1] - y).powi(2);
+                    distance(&a.0).partial_cmp(&distance(&b.0)).unwrap_or(std::cmp::Ordering::Equal)
+                })
+                .map(|(_, clip)| vec![(*clip, 1.0)])
+                .unwrap_or_default();
+        }
+        influences.into_iter().map(|(clip, influence)| (clip, influence / total)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_dimension_blends_the_two_points_around_the_value() {
+        let space = BlendSpace1d::new("speed", vec![(3.0, 2), (0.0, 0), (1.0, 1)]);
+        assert_eq!(space.weights(-1.0), vec![(0, 1.0)]);
+        assert_eq!(space.weights(0.5), vec![(0, 0.5), (1, 0.5)]);
+        assert_eq!(space.weights(2.5), vec![(1, 0.25), (2, 0.75)]);
+        assert_eq!(space.weights(1.0), vec![(1, 1.0)]);
+        assert_eq!(space.weights(7.0), vec![(2, 1.0)]);
+        assert!(BlendSpace1d::new("speed", vec![]).weights(1.0).is_empty());
+    }
+
+    #[test]
+    fn two_dimensions_play_a_clip_alone_on_its_point() {
+        let space = BlendSpace2d::new("x", "y", vec![([0.0, 0.0], 0), ([1.0, 0.0], 1), ([0.0, 1.0], 2), ([-1.0, 0.0], 3)]);
+        for (position, clip) in space.points.iter() {
+            assert_eq!(space.weights(position[0], position[1]), vec![(*clip, 1.0)]);
+        }
+    }
+
+    #[test]
+    fn two_dimensional_weights_add_up_to_one() {
+        let space = BlendSpace2d::new("x", "y", vec![([0.0, 0.0], 0), ([1.0, 0.0], 1), ([0.0, 1.0], 2), ([1.0, 1.0], 3)]);
+        for (x, y) in [(0.5, 0.5), (0.2, 0.7), (0.9, 0.1), (3.0, -2.0)].iter() {
+            let weights = space.weights(*x, *y);
+            assert!(!weights.is_empty());
+            assert!(weights.iter().all(|(_, weight)| *weight > 0.0));
+            let total: f32 = weights.iter().map(|(_, weight)| weight).sum();
+            assert!((total - 1.0).abs() < 1e-5);
+        }
+        // halfway between two points, both play equally
+        let weights = space.weights(0.5, 0.0);
+        assert_eq!(weights, vec![(0, 0.5), (1, 0.5)]);
+    }
+}
\ No newline at end of file
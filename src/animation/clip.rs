@@ -0,0 +1,303 @@
+use super::skeleton::{Pose, lerp};
+use crate::math::vec3::Vec3;
+use crate::math::quaternion::Quat;
+use crate::scene::gltf_import::{ImportedAnimation, ImportedSkin, ChannelProperty, ChannelInterpolation};
+
+use std::ops::{Add, Mul};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    // every keyframe has three values: in tangent, value and out tangent
+    CubicSpline
+}
+
+// values that can be keyframed
+pub trait Keyframe: Clone + Add<Output = Self> + Mul<f32, Output = Self> {
+    fn interpolate(&self, other: &Self, t: f32) -> Self;
+
+    // brings a cubic spline result back into shape
+    fn finish(self) -> Self {
+        self
+    }
+}
+
+impl Keyframe for Vec3 {
+    fn interpolate(&self, other: &Vec3, t: f32) -> Vec3 {
+        lerp(self, other, t)
+    }
+}
+
+impl Keyframe for Quat {
+    fn interpolate(&self, other: &Quat, t: f32) -> Quat {
+        self.slerp(other.clone(), t)
+    }
+
+    fn finish(self) -> Quat {
+        self.normalized()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframes<T: Keyframe> {
+    pub times: Vec<f32>,
+    pub values: Vec<T>,
+    pub interpolation: Interpolation
+}
+
+impl<T: Keyframe> Keyframes<T> {
+    pub fn new(times: Vec<f32>, values: Vec<T>, interpolation: Interpolation) -> Keyframes<T> {
+        Keyframes {
+            times,
+            values,
+            interpolation
+        }
+    }
+
+    // the value at time, held at the first and last keyframe outside of their range
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let count = self.times.len();
+        if count == 0 {
+            return None;
+        }
+        let value = |key: usize| match self.interpolation {
+            Interpolation::CubicSpline => self.values[key * 3 + 1].clone(),
+            _ => self.values[key].clone()
+        };
+        if time <= self.times[0] {
+            return Some(value(0));
+        }
+        if time >= self.times[count - 1] {
+            return Some(value(count - 1));
+        }
+
+        let key = self.times.iter().rposition(|key_time| *key_time <= time).unwrap_or(0);
+        let duration = self.times[key + 1] - self.times[key];
+        let t = if duration > 0.0 { (time - self.times[key]) / duration } else { 0.0 };
+        Some(match self.interpolation {
+            Interpolation::Step => value(key),
+            Interpolation::Linear => value(key).interpolate(&value(key + 1), t),
+            Interpolation::CubicSpline => {
+                // hermite spline with the tangents scaled by the keyframe distance, as gltf defines it
+                let (t2, t3) = (t * t, t * t * t);
+                let out_tangent = self.values[key * 3 + 2].clone() * duration;
+                let in_tangent = self.values[(key + 1) * 3].clone() * duration;
+                (value(key) * (2.0 * t3 - 3.0 * t2 + 1.0)
+                    + out_tangent * (t3 - 2.0 * t2 + t)
+                    + value(key + 1) * (-2.0 * t3 + 3.0 * t2)
+                    + in_tangent * (t3 - t2)).finish()
+            }
+        })
+    }
+}
+
+// the animated channels of one joint, the others keep the pose they're sampled into
+#[derive(Debug, Clone, PartialEq)]
+pub struct JointTrack {
+    pub joint: usize,
+    pub translation: Option<Keyframes<Vec3>>,
+    pub rotation: Option<Keyframes<Quat>>,
+    pub scale: Option<Keyframes<Vec3>>
+}
+
+impl JointTrack {
+    pub fn new(joint: usize) -> JointTrack {
+        JointTrack {
+            joint,
+            translation: None,
+            rotation: None,
+            scale: None
+        }
+    }
+}
+
+// named point in a clip for gameplay to react to, like a footstep
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipEvent {
+    pub time: f32,
+    pub name: String
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationClip {
+    pub name: String,
+    pub duration: f32,
+    pub tracks: Vec<JointTrack>,
+    pub events: Vec<ClipEvent>
+}
+
+impl AnimationClip {
+    // the duration is where the last keyframe of any track is
+    pub fn new(name: &str, tracks: Vec<JointTrack>) -> AnimationClip {
+        let mut duration: f32 = 0.0;
+        for track in tracks.iter() {
+            let ends = [
+                track.translation.as_ref().and_then(|keys| keys.times.last().cloned()),
+                track.rotation.as_ref().and_then(|keys| keys.times.last().cloned()),
+                track.scale.as_ref().and_then(|keys| keys.times.last().cloned())
+            ];
+            for end in ends.iter().filter_map(|end| *end) {
+                duration = duration.max(end);
+            }
+        }
+
+        AnimationClip {
+            name: name.to_string(),
+            duration,
+            tracks,
+            events: vec![]
+        }
+    }
+
+    pub fn add_event(&mut self, time: f32, name: &str) {
+        self.events.push(ClipEvent { time, name: name.to_string() });
+    }
+
+    // events passed when playback goes from start to end forwards, wrapping around the end of the clip
+    // when looping. an event exactly at start has fired already, one exactly at end fires now, so playback
+    // that just began passes a negative start
+    pub fn events_between(&self, start: f32, end: f32, looping: bool) -> Vec<&ClipEvent> {
+        let in_range = |from: f32, to: f32| self.events.iter().filter(move |event| event.time > from && event.time <= to);
+        if end >= start || !looping {
+            return in_range(start, end).collect();
+        }
+        // wrapped: the rest of this loop, then the start of the next. start at 0 includes events at 0
+        in_range(start, self.duration)
+            .chain(self.events.iter().filter(|event| event.time == 0.0))
+            .chain(in_range(0.0, end))
+            .collect()
+    }
+
+    // the channels of a gltf animation that target joints of the skin, morph weights are left out
+    pub fn from_gltf(animation: &ImportedAnimation, skin: &ImportedSkin) -> AnimationClip {
+        let mut tracks: Vec<JointTrack> = vec![];
+        for channel in animation.channels.iter() {
+            let joint = match skin.joints.iter().position(|node| *node == channel.node) {
+                Some(joint) => joint,
+                None => continue
+            };
+            let interpolation = match channel.interpolation {
+                ChannelInterpolation::Step => Interpolation::Step,
+                ChannelInterpolation::Linear => Interpolation::Linear,
+                ChannelInterpolation::CubicSpline => Interpolation::CubicSpline
+            };
+            let track_index = match tracks.iter().position(|track| track.joint == joint) {
+                Some(index) => index,
+                None => {
+                    tracks.push(JointTrack::new(joint));
+                    tracks.len() - 1
+                }
+            };
+
+            let values = channel.values.chunks(channel.components.max(1));
+            let vectors = || values.clone().map(|v| Vec3 { x: v[0], y: v[1], z: v[2] }).collect();
+            let track = &mut tracks[track_index];
+            match channel.property {
+                ChannelProperty::Translation =>
+                    track.translation = Some(Keyframes::new(channel.times.clone(), vectors(), interpolation)),
+                ChannelProperty::Scale =>
+                    track.scale = Some(Keyframes::new(channel.times.clone(), vectors(), interpolation)),
+                ChannelProperty::Rotation => {
+                    let rotations = values.clone().map(|r| Quat { x: r[0], y: r[1], z: r[2], w: r[3] }).collect();
+                    track.rotation = Some(Keyframes::new(channel.times.clone(), rotations, interpolation));
+                },
+                ChannelProperty::MorphWeights => ()
+            }
+        }
+
+        let mut ret = AnimationClip::new(&animation.name, tracks);
+        ret.duration = ret.duration.max(animation.duration);
+        ret
+    }
+
+    // overwrites the animated channels of pose with their values at time
+    pub fn sample(&self, time: f32, pose: &mut Pose) {
+        for track in self.tracks.iter() {
+            let local = match pose.locals.get_mut(track.joint) {
+                Some(local) => local,
+                None => continue
+            };
+            if let Some(translation) = track.translation.as_ref().and_then(|keys| keys.sample(time)) {
+                local.translation = translation;
+            }
+            if let Some(rotation) = track.rotation.as_ref().and_then(|keys| keys.sample(time)) {
+                local.rotation = rotation;
+            }
+            if let Some(scale) = track.scale.as_ref().and_then(|keys| keys.sample(time)) {
+                local.scale = scale;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::transform::Transform;
+
+    fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn turn(degrees: f32) -> Quat {
+        Quat::from_axis_angle(&vec3(0.0, 1.0, 0.0), degrees)
+    }
+
+    fn assert_same_rotation(a: &Quat, b: &Quat) {
+        assert!(a.dot(b.clone()).abs() > 0.9999, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn linear_rotations_are_slerped() {
+        let keys = Keyframes::new(vec![0.0, 1.0], vec![turn(0.0), turn(120.0)], Interpolation::Linear);
+        assert_same_rotation(&keys.sample(0.5).expect("failed to sample"), &turn(60.0));
+        assert_same_rotation(&keys.sample(0.25).expect("failed to sample"), &turn(30.0));
+        let halfway = keys.sample(0.5).expect("failed to sample");
+        assert!((halfway.lenght() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn slerp_takes_the_shorter_arc() {
+        // the same rotations as 0 and 160 degrees, with the second quaternion flipped
+        let keys = Keyframes::new(vec![0.0, 1.0], vec![turn(0.0), turn(160.0) * -1.0], Interpolation::Linear);
+        assert_same_rotation(&keys.sample(0.5).expect("failed to sample"), &turn(80.0));
+    }
+
+    #[test]
+    fn samples_are_held_outside_the_keyframes() {
+        let keys = Keyframes::new(vec![1.0, 2.0], vec![vec3(1.0, 0.0, 0.0), vec3(3.0, 0.0, 0.0)], Interpolation::Linear);
+        assert_eq!(keys.sample(0.0), Some(vec3(1.0, 0.0, 0.0)));
+        assert_eq!(keys.sample(5.0), Some(vec3(3.0, 0.0, 0.0)));
+        assert_eq!(Keyframes::<Vec3>::new(vec![], vec![], Interpolation::Linear).sample(0.0), None);
+
+        let step = Keyframes::new(vec![0.0, 1.0], vec![vec3(1.0, 0.0, 0.0), vec3(3.0, 0.0, 0.0)], Interpolation::Step);
+        assert_eq!(step.sample(0.9), Some(vec3(1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn cubic_spline_goes_through_the_keyframes() {
+        let zero = vec3(0.0, 0.0, 0.0);
+        let values = vec![zero.clone(), vec3(0.0, 0.0, 0.0), zero.clone(), zero.clone(), vec3(2.0, 0.0, 0.0), zero.clone()];
+        let keys = Keyframes::new(vec![0.0, 1.0], values, Interpolation::CubicSpline);
+        assert_eq!(keys.sample(1.0), Some(vec3(2.0, 0.0, 0.0)));
+        // flat tangents give smoothstep
+        let middle = keys.sample(0.5).expect("failed to sample");
+        assert!((middle.x - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn clips_overwrite_only_their_channels() {
+        let mut track = JointTrack::new(1);
+        track.rotation = Some(Keyframes::new(vec![0.0, 2.0], vec![turn(0.0), turn(90.0)], Interpolation::Linear));
+        let clip = AnimationClip::new("turn", vec![track]);
+        assert_eq!(clip.duration, 2.0);
+
+        let rest = Transform { translation: vec3(0.0, 1.0, 0.0), ..Transform::default() };
+        let mut pose = Pose { locals: vec![rest.clone(), rest.clone()] };
+        clip.sample(1.0, &mut pose);
+        assert_eq!(pose.locals[0], rest);
+        assert_eq!(pose.locals[1].translation, rest.translation);
+        assert_same_rotation(&pose.locals[1].rotation, &turn(45.0));
+    }
+}
\ No newline at end of file
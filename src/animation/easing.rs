@@ -0,0 +1,120 @@
+use std::f32::consts::PI;
+
+// maps linear progress from 0 to 1 onto a curve with the same ends. elastic ones overshoot on the way
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    ElasticIn,
+    ElasticOut,
+    ElasticInOut,
+    BounceIn,
+    BounceOut,
+    BounceInOut,
+    // css style cubic bezier from (0, 0) to (1, 1) with the control points (x1, y1) and (x2, y2),
+    // the x coordinates between 0 and 1
+    Bezier(f32, f32, f32, f32)
+}
+
+impl Easing {
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.max(0.0).min(1.0);
+        match *self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::QuadInOut => in_out(t, |t| t * t),
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut => in_out(t, |t| t * t * t),
+            Easing::ElasticIn => elastic_in(t),
+            Easing::ElasticOut => 1.0 - elastic_in(1.0 - t),
+            Easing::ElasticInOut => in_out(t, elastic_in),
+            Easing::BounceIn => 1.0 - bounce_out(1.0 - t),
+            Easing::BounceOut => bounce_out(t),
+            Easing::BounceInOut => in_out(t, |t| 1.0 - bounce_out(1.0 - t)),
+            Easing::Bezier(x1, y1, x2, y2) => bezier(t, x1, y1, x2, y2)
+        }
+    }
+}
+
+impl Default for Easing {
+    fn default() -> Easing {
+        Easing::Linear
+    }
+}
+
+// the in curve for the first half and its mirror for the second
+fn in_out<F: Fn(f32) -> f32>(t: f32, ease_in: F) -> f32 {
+    if t < 0.5 {
+        ease_in(t * 2.0) / 2.0
+    } else {
+        1.0 - ease_in((1.0 - t) * 2.0) / 2.0
+    }
+}
+
+fn elastic_in(t: f32) -> f32 {
+    if t <= 0.0 || t >= 1.0 {
+        return t;
+    }
+    // a decaying sine with a period of 0.3
+    let period = 0.3;
+    -(2.0f32.powf(10.0 * (t - 1.0))) * ((t - 1.0 - period / 4.0) * (2.0 * PI) / period).sin()
+}
+
+fn bounce_out(t: f32) -> f32 {
+    // four parabolas of shrinking height
+    let (n, d) = (7.5625, 2.75);
+    if t < 1.0 / d {
+        n * t * t
+    } else if t < 2.0 / d {
+        let t = t - 1.5 / d;
+        n * t * t + 0.75
+    } else if t < 2.5 / d {
+        let t = t - 2.25 / d;
+        n * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / d;
+        n * t * t + 0.984375
+    }
+}
+
+fn bezier(x: f32, x1: f32, y1: f32, x2: f32, y2: f32) -> f32 {
+    let curve = |s: f32, p1: f32, p2: f32| 3.0 * (1.0 - s).powi(2) * s * p1 + 3.0 * (1.0 - s) * s * s * p2 + s * s * s;
+    let slope = |s: f32, p1: f32, p2: f32| 3.0 * (1.0 - s).powi(2) * p1 + 6.0 * (1.0 - s) * s * (p2 - p1) + 3.0 * s * s * (1.0 - p2);
+
+    // newton's method for the curve parameter at x, bisection where the slope is too flat for it
+    let mut s = x;
+    for _ in 0..8 {
+        let error = curve(s, x1, x2) - x;
+        if error.abs() < 1e-6 {
+            return curve(s, y1, y2);
+        }
+        let derivative = slope(s, x1, x2);
+        if derivative.abs() < 1e-6 {
+            break;
+        }
+        s = (s - error / derivative).max(0.0).min(1.0);
+    }
+
+    let (mut low, mut high) = (0.0, 1.0);
+    s = x;
+    for _ in 0..32 {
+        let value = curve(s, x1, x2);
+        if (value - x).abs() < 1e-6 {
+            break;
+        }
+        if value < x {
+            low = s;
+        } else {
+            high = s;
+        }
+        s = (low + high) / 2.0;
+    }
+    curve(s, y1, y2)
+}
\ No newline at end of file
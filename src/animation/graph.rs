@@ -0,0 +1,629 @@
+use super::clip::AnimationClip;
+use super::skeleton::{Skeleton, Pose, blend_transforms, lerp};
+use super::blend_space::{BlendSpace1d, BlendSpace2d};
+use crate::math::vec3::Vec3;
+use crate::math::quaternion::Quat;
+
+use std::collections::{HashMap, HashSet};
+
+/*********************************
+*** PARAMETERS AND CONDITIONS
+*********************************/
+
+// what gameplay sets to drive the graph. unset floats read as 0 and unset bools as false
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Parameters {
+    floats: HashMap<String, f32>,
+    bools: HashMap<String, bool>,
+    // set until a transition uses them
+    triggers: HashSet<String>
+}
+
+impl Parameters {
+    pub fn new() -> Parameters {
+        Parameters::default()
+    }
+
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        self.floats.insert(name.to_string(), value);
+    }
+
+    pub fn float(&self, name: &str) -> f32 {
+        self.floats.get(name).cloned().unwrap_or(0.0)
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.bools.insert(name.to_string(), value);
+    }
+
+    pub fn bool(&self, name: &str) -> bool {
+        self.bools.get(name).cloned().unwrap_or(false)
+    }
+
+    pub fn set_trigger(&mut self, name: &str) {
+        self.triggers.insert(name.to_string());
+    }
+
+    pub fn reset_trigger(&mut self, name: &str) {
+        self.triggers.remove(name);
+    }
+
+    pub fn is_triggered(&self, name: &str) -> bool {
+        self.triggers.contains(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Greater(String, f32),
+    Less(String, f32),
+    IsTrue(String),
+    IsFalse(String),
+    Trigger(String),
+    // normalized time of the current state, 1 is the end of its first loop
+    ExitTime(f32)
+}
+
+impl Condition {
+    fn holds(&self, parameters: &Parameters, state_time: f32) -> bool {
+        match self {
+            Condition::Greater(name, value) => parameters.float(name) > *value,
+            Condition::Less(name, value) => parameters.float(name) < *value,
+            Condition::IsTrue(name) => parameters.bool(name),
+            Condition::IsFalse(name) => !parameters.bool(name),
+            Condition::Trigger(name) => parameters.is_triggered(name),
+            Condition::ExitTime(time) => state_time >= *time
+        }
+    }
+}
+
+/*********************************
+*** STATES AND LAYERS
+*********************************/
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Motion {
+    Clip(usize),
+    BlendSpace1d(BlendSpace1d),
+    BlendSpace2d(BlendSpace2d)
+}
+
+impl Motion {
+    // (clip, weight) for the current parameters
+    pub fn weights(&self, parameters: &Parameters) -> Vec<(usize, f32)> {
+        match self {
+            Motion::Clip(clip) => vec![(*clip, 1.0)],
+            Motion::BlendSpace1d(space) => space.weights(parameters.float(&space.parameter)),
+            Motion::BlendSpace2d(space) =>
+                space.weights(parameters.float(&space.x_parameter), parameters.float(&space.y_parameter))
+        }
+    }
+}
+
+// blend spaces play their clips in sync: they share a normalized time and the state lasts as long as the
+// weighted average of their durations
+#[derive(Debug, Clone, PartialEq)]
+pub struct State {
+    pub name: String,
+    pub motion: Motion,
+    pub speed: f32,
+    pub looping: bool
+}
+
+impl State {
+    pub fn new(name: &str, motion: Motion, looping: bool) -> State {
+        State {
+            name: name.to_string(),
+            motion,
+            speed: 1.0,
+            looping
+        }
+    }
+}
+
+// taken when all conditions hold, cross-fading over duration seconds. transitions without a from state
+// can be taken from any state but their target
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    pub from: Option<usize>,
+    pub to: usize,
+    pub conditions: Vec<Condition>,
+    pub duration: f32
+}
+
+impl Transition {
+    pub fn new(from: Option<usize>, to: usize, conditions: Vec<Condition>, duration: f32) -> Transition {
+        Transition {
+            from,
+            to,
+            conditions,
+            duration
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LayerBlend {
+    // replaces the pose of the layers below
+    Override,
+    // adds the difference between its pose and the rest pose on top of the layers below
+    Additive
+}
+
+// how much a layer affects each joint, from 0 to 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoneMask {
+    pub weights: Vec<f32>
+}
+
+impl BoneMask {
+    // joint and everything below it, like an upper body from the spine. joints whose parents go around in a
+    // cycle are left out
+    pub fn from_joint(skeleton: &Skeleton, joint: usize) -> BoneMask {
+        let weights = (0..skeleton.joints.len())
+            .map(|mut current| {
+                // a chain longer than the skeleton has gone around a cycle
+                for _ in 0..=skeleton.joints.len() {
+                    if current == joint {
+                        return 1.0;
+                    }
+                    match skeleton.joints[current].parent {
+                        Some(parent) => current = parent,
+                        None => return 0.0
+                    }
+                }
+                0.0
+            })
+            .collect();
+        BoneMask { weights }
+    }
+
+    fn weight(&self, joint: usize) -> f32 {
+        self.weights.get(joint).cloned().unwrap_or(0.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ActiveState {
+    state: usize,
+    // normalized, counts up past 1 while looping
+    time: f32,
+    weight: f32,
+    // weight change per second while cross-fading
+    fade_rate: f32,
+    // false until the first update, so events at the very start fire
+    started: bool
+}
+
+impl ActiveState {
+    fn new(state: usize, weight: f32, fade_rate: f32) -> ActiveState {
+        ActiveState {
+            state,
+            time: 0.0,
+            weight,
+            fade_rate,
+            started: false
+        }
+    }
+
+    fn phase(&self, looping: bool) -> f32 {
+        if looping { self.time.fract() } else { self.time.min(1.0) }
+    }
+}
+
+// a state machine. the current state plays along with the states it's still cross-fading from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layer {
+    pub name: String,
+    pub states: Vec<State>,
+    pub transitions: Vec<Transition>,
+    pub weight: f32,
+    pub blend: LayerBlend,
+    // every joint when None
+    pub mask: Option<BoneMask>,
+    // the current state last
+    active: Vec<ActiveState>
+}
+
+impl Layer {
+    pub fn new(name: &str, states: Vec<State>, initial_state: usize) -> Layer {
+        Layer {
+            name: name.to_string(),
+            states,
+            transitions: vec![],
+            weight: 1.0,
+            blend: LayerBlend::Override,
+            mask: None,
+            active: vec![ActiveState::new(initial_state, 1.0, 0.0)]
+        }
+    }
+
+    pub fn add_transition(&mut self, transition: Transition) {
+        self.transitions.push(transition);
+    }
+
+    pub fn state_index(&self, name: &str) -> Option<usize> {
+        self.states.iter().position(|state| state.name == name)
+    }
+
+    pub fn current_state(&self) -> usize {
+        self.active[self.active.len() - 1].state
+    }
+
+    // normalized time of the current state
+    pub fn state_time(&self) -> f32 {
+        self.active[self.active.len() - 1].time
+    }
+
+    pub fn is_transitioning(&self) -> bool {
+        self.active.len() > 1
+    }
+
+    // jumps to state without a cross-fade
+    pub fn set_state(&mut self, state: usize) {
+        self.active = vec![ActiveState::new(state, 1.0, 0.0)];
+    }
+
+    fn start_transition(&mut self, to: usize, duration: f32) {
+        if duration <= 0.0 {
+            self.set_state(to);
+            return;
+        }
+        for active in self.active.iter_mut() {
+            active.fade_rate = -active.weight / duration;
+        }
+        self.active.push(ActiveState::new(to, 0.0, 1.0 / duration));
+    }
+
+    fn update(&mut self, delta: f32, parameters: &mut Parameters, clips: &[AnimationClip], layer: usize,
+            events: &mut Vec<FiredEvent>) {
+        let current = self.current_state();
+        let state_time = self.state_time();
+        let taken = self.transitions.iter()
+            .find(|transition| match transition.from {
+                Some(from) => from == current,
+                None => transition.to != current
+            } && transition.conditions.iter().all(|condition| condition.holds(parameters, state_time)))
+            .cloned();
+        if let Some(transition) = taken {
+            for condition in transition.conditions.iter() {
+                if let Condition::Trigger(name) = condition {
+                    parameters.reset_trigger(name);
+                }
+            }
+            self.start_transition(transition.to, transition.duration);
+        }
+
+        for active in self.active.iter_mut() {
+            let state = &self.states[active.state];
+            let weights = state.motion.weights(parameters);
+            let duration: f32 = weights.iter().map(|(clip, weight)| clips[*clip].duration * weight).sum::<f32>()
+                / state.speed.abs().max(std::f32::EPSILON);
+
+            let previous_phase = if active.started { Some(active.phase(state.looping)) } else { None };
+            if duration > 0.0 {
+                active.time += delta / duration;
+            }
+            if !state.looping {
+                active.time = active.time.min(1.0);
+            }
+            active.started = true;
+
+            if active.fade_rate != 0.0 {
+                active.weight = (active.weight + active.fade_rate * delta).max(0.0).min(1.0);
+                if active.weight == 0.0 || active.weight == 1.0 {
+                    active.fade_rate = 0.0;
+                }
+            }
+
+            // events of the clips this state plays, as far as they're heard
+            if active.weight > 0.0 {
+                let phase = active.phase(state.looping);
+                for (clip, weight) in weights.iter().filter(|(_, weight)| *weight > 0.0) {
+                    let clip_duration = clips[*clip].duration;
+                    let start = previous_phase.map(|previous| previous * clip_duration).unwrap_or(-1.0);
+                    for event in clips[*clip].events_between(start, phase * clip_duration, state.looping) {
+                        events.push(FiredEvent {
+                            layer,
+                            state: active.state,
+                            clip: *clip,
+                            name: event.name.clone(),
+                            weight: active.weight * weight
+                        });
+                    }
+                }
+            }
+        }
+        let current_index = self.active.len() - 1;
+        let mut index = 0;
+        self.active.retain(|active| {
+            index += 1;
+            index - 1 == current_index || active.weight > 0.0
+        });
+    }
+
+    // the blend of every active state, None while nothing has weight yet
+    fn pose(&self, skeleton: &Skeleton, clips: &[AnimationClip], parameters: &Parameters) -> Option<Pose> {
+        let mut ret: Option<Pose> = None;
+        let mut total_weight = 0.0;
+        for active in self.active.iter().filter(|active| active.weight > 0.0) {
+            let state = &self.states[active.state];
+            let phase = active.phase(state.looping);
+            for (clip, clip_weight) in state.motion.weights(parameters) {
+                let weight = active.weight * clip_weight;
+                if weight <= 0.0 {
+                    continue;
+                }
+                let mut pose = skeleton.rest_pose();
+                clips[clip].sample(phase * clips[clip].duration, &mut pose);
+
+                total_weight += weight;
+                ret = Some(match ret {
+                    Some(blended) => blended.blend(&pose, weight / total_weight),
+                    None => pose
+                });
+            }
+        }
+        ret
+    }
+}
+
+/*********************************
+*** GRAPH
+*********************************/
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FiredEvent {
+    pub layer: usize,
+    pub state: usize,
+    pub clip: usize,
+    pub name: String,
+    // how much the clip contributed when the event fired, events of states fading out have less
+    pub weight: f32
+}
+
+// layers of state machines evaluated bottom up, everything advances only in update, so stepping it with
+// fixed deltas plays out the same every time
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AnimationGraph {
+    pub parameters: Parameters,
+    pub layers: Vec<Layer>
+}
+
+impl AnimationGraph {
+    pub fn new() -> AnimationGraph {
+        AnimationGraph::default()
+    }
+
+    pub fn add_layer(&mut self, layer: Layer) -> usize {
+        self.layers.push(layer);
+        self.layers.len() - 1
+    }
+
+    // takes at most one transition per layer, advances every state and returns the events passed
+    pub fn update(&mut self, delta: f32, clips: &[AnimationClip]) -> Vec<FiredEvent> {
+        let mut events = vec![];
+        for (index, layer) in self.layers.iter_mut().enumerate() {
+            layer.update(delta, &mut self.parameters, clips, index, &mut events);
+        }
+        events
+    }
+
+    pub fn pose(&self, skeleton: &Skeleton, clips: &[AnimationClip]) -> Pose {
+        let mut ret = skeleton.rest_pose();
+        for layer in self.layers.iter().filter(|layer| layer.weight > 0.0) {
+            let layer_pose = match layer.pose(skeleton, clips, &self.parameters) {
+                Some(pose) => pose,
+                None => continue
+            };
+
+            for (joint, local) in ret.locals.iter_mut().enumerate() {
+                let weight = layer.weight * layer.mask.as_ref().map(|mask| mask.weight(joint)).unwrap_or(1.0);
+                if weight <= 0.0 {
+                    continue;
+                }
+                let layer_local = &layer_pose.locals[joint];
+                match layer.blend {
+                    LayerBlend::Override => *local = blend_transforms(local, layer_local, weight),
+                    LayerBlend::Additive => {
+                        let rest = &skeleton.joints[joint].rest;
+                        let rotation = rest.rotation.conjugated().product(layer_local.rotation.clone());
+                        local.rotation = local.rotation.product(Quat::identity().slerp(rotation, weight)).normalized();
+                        local.translation += (layer_local.translation.clone() - rest.translation.clone()) * weight;
+                        // no factor scales up from a rest scale of 0, the axis is left alone then
+                        let ratio = |scale: f32, rest: f32| if rest != 0.0 { scale / rest } else { 1.0 };
+                        let scale = Vec3 {
+                            x: ratio(layer_local.scale.x, rest.scale.x),
+                            y: ratio(layer_local.scale.y, rest.scale.y),
+                            z: ratio(layer_local.scale.z, rest.scale.z)
+                        };
+                        local.scale = local.scale.clone() * lerp(&Vec3 { x: 1.0, y: 1.0, z: 1.0 }, &scale, weight);
+                    }
+                }
+            }
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::clip::{JointTrack, Keyframes, Interpolation};
+    use super::super::skeleton::Joint;
+    use crate::math::mat4::Mat4;
+    use crate::scene::transform::Transform;
+
+    fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    // a one second clip that holds joints at x
+    fn hold(joints: &[usize], x: f32) -> AnimationClip {
+        let tracks = joints.iter().map(|joint| {
+            let mut track = JointTrack::new(*joint);
+            let at = vec3(x, 0.0, 0.0);
+            track.translation = Some(Keyframes::new(vec![0.0, 1.0], vec![at.clone(), at], Interpolation::Linear));
+            track
+        }).collect();
+        AnimationClip::new("hold", tracks)
+    }
+
+    // parents of every joint
+    fn skeleton(parents: &[Option<usize>]) -> Skeleton {
+        let joints = parents.iter().enumerate()
+            .map(|(i, parent)| Joint::new(&format!("joint {}", i), *parent, Transform::default(), Mat4::identity()))
+            .collect();
+        Skeleton::new(joints, Mat4::identity())
+    }
+
+    fn idle_and_walk() -> AnimationGraph {
+        let states = vec![State::new("idle", Motion::Clip(0), true), State::new("walk", Motion::Clip(1), true)];
+        let mut layer = Layer::new("base", states, 0);
+        layer.add_transition(Transition::new(Some(0), 1, vec![Condition::Greater("speed".to_string(), 0.5)], 0.5));
+        let mut graph = AnimationGraph::new();
+        graph.add_layer(layer);
+        graph
+    }
+
+    #[test]
+    fn transitions_cross_fade_over_fixed_steps() {
+        let clips = vec![hold(&[0], 0.0), hold(&[0], 4.0)];
+        let skeleton = skeleton(&[None]);
+        let mut graph = idle_and_walk();
+
+        graph.update(0.125, &clips);
+        assert_eq!(graph.layers[0].current_state(), 0);
+
+        graph.parameters.set_float("speed", 1.0);
+        graph.update(0.125, &clips);
+        assert_eq!(graph.layers[0].current_state(), 1);
+        assert!(graph.layers[0].is_transitioning());
+        assert!((graph.pose(&skeleton, &clips).locals[0].translation.x - 1.0).abs() < 1e-5);
+
+        for _ in 0..3 {
+            graph.update(0.125, &clips);
+        }
+        assert!(!graph.layers[0].is_transitioning());
+        assert_eq!(graph.pose(&skeleton, &clips).locals[0].translation.x, 4.0);
+    }
+
+    #[test]
+    fn the_same_steps_give_the_same_result() {
+        let clips = vec![hold(&[0], 0.0), hold(&[0], 4.0)];
+        let skeleton = skeleton(&[None]);
+        let run = || {
+            let mut graph = idle_and_walk();
+            graph.parameters.set_float("speed", 1.0);
+            let poses: Vec<Pose> = (0..6).map(|_| {
+                graph.update(1.0 / 30.0, &clips);
+                graph.pose(&skeleton, &clips)
+            }).collect();
+            (poses, graph.layers[0].state_time())
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn triggers_are_used_up_by_their_transition() {
+        let clips = vec![hold(&[0], 0.0), hold(&[0], 1.0)];
+        let states = vec![State::new("idle", Motion::Clip(0), true), State::new("jump", Motion::Clip(1), false)];
+        let mut layer = Layer::new("base", states, 0);
+        layer.add_transition(Transition::new(None, 1, vec![Condition::Trigger("jump".to_string())], 0.0));
+        let mut graph = AnimationGraph::new();
+        graph.add_layer(layer);
+
+        graph.parameters.set_trigger("jump");
+        graph.update(0.1, &clips);
+        assert_eq!(graph.layers[0].current_state(), 1);
+        assert!(!graph.parameters.is_triggered("jump"));
+        assert!(!graph.layers[0].is_transitioning());
+    }
+
+    #[test]
+    fn exit_time_waits_for_the_end_of_the_state() {
+        let clips = vec![hold(&[0], 0.0), hold(&[0], 1.0)];
+        let states = vec![State::new("attack", Motion::Clip(0), false), State::new("idle", Motion::Clip(1), true)];
+        let mut layer = Layer::new("base", states, 0);
+        layer.add_transition(Transition::new(Some(0), 1, vec![Condition::ExitTime(1.0)], 0.0));
+        let mut graph = AnimationGraph::new();
+        graph.add_layer(layer);
+
+        graph.update(0.5, &clips);
+        graph.update(0.5, &clips);
+        assert_eq!(graph.layers[0].current_state(), 0);
+        assert_eq!(graph.layers[0].state_time(), 1.0);
+        // conditions are checked before the step
+        graph.update(0.5, &clips);
+        assert_eq!(graph.layers[0].current_state(), 1);
+    }
+
+    #[test]
+    fn events_fire_once_per_loop() {
+        let mut clip = hold(&[0], 0.0);
+        clip.add_event(0.0, "start");
+        clip.add_event(0.5, "middle");
+        let clips = vec![clip];
+        let mut graph = AnimationGraph::new();
+        graph.add_layer(Layer::new("base", vec![State::new("loop", Motion::Clip(0), true)], 0));
+
+        let mut fired = vec![];
+        for _ in 0..8 {
+            fired.extend(graph.update(0.25, &clips).into_iter().map(|event| event.name));
+        }
+        assert_eq!(fired.iter().filter(|name| *name == "start").count(), 3);
+        assert_eq!(fired.iter().filter(|name| *name == "middle").count(), 2);
+    }
+
+    #[test]
+    fn blend_space_states_follow_their_parameter() {
+        let clips = vec![hold(&[0], 0.0), hold(&[0], 2.0)];
+        let space = BlendSpace1d::new("speed", vec![(0.0, 0), (1.0, 1)]);
+        let mut graph = AnimationGraph::new();
+        graph.add_layer(Layer::new("base", vec![State::new("move", Motion::BlendSpace1d(space), true)], 0));
+        graph.parameters.set_float("speed", 0.25);
+        graph.update(0.1, &clips);
+        assert!((graph.pose(&skeleton(&[None]), &clips).locals[0].translation.x - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn bone_masks_cover_the_joint_and_its_children() {
+        // root, spine, arm on the spine and a leg on the root
+        let skeleton = skeleton(&[None, Some(0), Some(1), Some(0)]);
+        assert_eq!(BoneMask::from_joint(&skeleton, 1).weights, vec![0.0, 1.0, 1.0, 0.0]);
+
+        let clips = vec![hold(&[0, 1, 2, 3], 0.0), hold(&[0, 1, 2, 3], 1.0)];
+        let mut graph = AnimationGraph::new();
+        graph.add_layer(Layer::new("base", vec![State::new("legs", Motion::Clip(0), true)], 0));
+        let mut upper = Layer::new("upper", vec![State::new("wave", Motion::Clip(1), true)], 0);
+        upper.mask = Some(BoneMask::from_joint(&skeleton, 1));
+        graph.add_layer(upper);
+        graph.update(0.1, &clips);
+
+        let xs: Vec<f32> = graph.pose(&skeleton, &clips).locals.iter().map(|local| local.translation.x).collect();
+        assert_eq!(xs, vec![0.0, 1.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn bone_masks_end_on_cyclic_parents() {
+        let skeleton = skeleton(&[Some(1), Some(0), None]);
+        assert_eq!(BoneMask::from_joint(&skeleton, 2).weights, vec![0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn additive_layers_skip_axes_with_a_zero_rest_scale() {
+        let mut skeleton = skeleton(&[None]);
+        skeleton.joints[0].rest.scale = vec3(0.0, 1.0, 1.0);
+        let mut track = JointTrack::new(0);
+        track.scale = Some(Keyframes::new(vec![0.0, 1.0], vec![vec3(2.0, 2.0, 2.0), vec3(2.0, 2.0, 2.0)], Interpolation::Linear));
+        let clips = vec![AnimationClip::new("grow", vec![track])];
+        let mut layer = Layer::new("additive", vec![State::new("grow", Motion::Clip(0), true)], 0);
+        layer.blend = LayerBlend::Additive;
+        let mut graph = AnimationGraph::new();
+        graph.add_layer(layer);
+        graph.update(0.1, &clips);
+
+        let scale = &graph.pose(&skeleton, &clips).locals[0].scale;
+        assert_eq!(*scale, vec3(0.0, 2.0, 2.0));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,7 @@
+pub mod blend_space;
+pub mod clip;
+pub mod easing;
+pub mod graph;
+pub mod player;
+pub mod skeleton;
+pub mod tween;
\ No newline at end of file
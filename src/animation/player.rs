@@ -0,0 +1,226 @@
+use super::clip::AnimationClip;
+use super::skeleton::{Skeleton, Pose};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayingClip {
+    // index into the clips the player is updated with
+    pub clip: usize,
+    pub time: f32,
+    pub speed: f32,
+    pub looping: bool,
+    pub weight: f32,
+    // weight change per second while crossfading, 0 otherwise
+    fade_rate: f32
+}
+
+// plays clips on one skeleton. several clips can play at once, their poses are blended by weight, which
+// is how crossfades work: the new clip fades in while everything else fades out
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationPlayer {
+    // multiplies the speed of every clip
+    pub speed: f32,
+    playing: Vec<PlayingClip>
+}
+
+impl AnimationPlayer {
+    pub fn new() -> AnimationPlayer {
+        AnimationPlayer {
+            speed: 1.0,
+            playing: vec![]
+        }
+    }
+
+    // replaces whatever is playing
+    pub fn play(&mut self, clip: usize, looping: bool) {
+        self.playing = vec![PlayingClip {
+            clip,
+            time: 0.0,
+            speed: 1.0,
+            looping,
+            weight: 1.0,
+            fade_rate: 0.0
+        }];
+    }
+
+    // blends over to clip in duration seconds, the clips playing now fade out at the same rate
+    pub fn crossfade(&mut self, clip: usize, looping: bool, duration: f32) {
+        if duration <= 0.0 || self.playing.is_empty() {
+            self.play(clip, looping);
+            return;
+        }
+
+        for playing in self.playing.iter_mut() {
+            playing.fade_rate = -playing.weight / duration;
+        }
+        self.playing.push(PlayingClip {
+            clip,
+            time: 0.0,
+            speed: 1.0,
+            looping,
+            weight: 0.0,
+            fade_rate: 1.0 / duration
+        });
+    }
+
+    // adds a clip on top of the others with a fixed weight, for blends that aren't transitions
+    pub fn blend(&mut self, clip: usize, looping: bool, weight: f32) {
+        self.playing.push(PlayingClip {
+            clip,
+            time: 0.0,
+            speed: 1.0,
+            looping,
+            weight,
+            fade_rate: 0.0
+        });
+    }
+
+    pub fn stop(&mut self) {
+        self.playing.clear();
+    }
+
+    pub fn playing(&self) -> &[PlayingClip] {
+        &self.playing
+    }
+
+    pub fn playing_mut(&mut self) -> &mut [PlayingClip] {
+        &mut self.playing
+    }
+
+    // true once no clip is left or every one is a non looping clip at its end
+    pub fn is_finished(&self, clips: &[AnimationClip]) -> bool {
+        self.playing.iter().all(|playing| {
+            let duration = clips[playing.clip].duration;
+            !playing.looping && if playing.speed * self.speed >= 0.0 { playing.time >= duration } else { playing.time <= 0.0 }
+        })
+    }
+
+    // advances every clip and crossfade by delta seconds, faded out clips are dropped
+    pub fn update(&mut self, delta: f32, clips: &[AnimationClip]) {
+        for playing in self.playing.iter_mut() {
+            let duration = clips[playing.clip].duration;
+            playing.time += delta * playing.speed * self.speed;
+            playing.time = if playing.looping && duration > 0.0 {
+                playing.time.rem_euclid(duration)
+            } else {
+                playing.time.max(0.0).min(duration)
+            };
+
+            if playing.fade_rate != 0.0 {
+                playing.weight = (playing.weight + playing.fade_rate * delta).max(0.0).min(1.0);
+                if playing.weight == 0.0 || playing.weight == 1.0 {
+                    playing.fade_rate = 0.0;
+                }
+            }
+        }
+        self.playing.retain(|playing| playing.weight > 0.0 || playing.fade_rate > 0.0);
+    }
+
+    // the blended pose of every playing clip, the rest pose when nothing plays
+    pub fn pose(&self, skeleton: &Skeleton, clips: &[AnimationClip]) -> Pose {
+        let mut ret: Option<Pose> = None;
+        let mut total_weight = 0.0;
+        for playing in self.playing.iter().filter(|playing| playing.weight > 0.0) {
+            let mut pose = skeleton.rest_pose();
+            clips[playing.clip].sample(playing.time, &mut pose);
+
+            // running average, each clip gets its share of the weight seen so far
+            total_weight += playing.weight;
+            ret = Some(match ret {
+                Some(blended) => blended.blend(&pose, playing.weight / total_weight),
+                None => pose
+            });
+        }
+        ret.unwrap_or_else(|| skeleton.rest_pose())
+    }
+}
+
+impl Default for AnimationPlayer {
+    fn default() -> AnimationPlayer {
+        AnimationPlayer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::clip::{JointTrack, Keyframes, Interpolation};
+    use super::super::skeleton::Joint;
+    use crate::math::mat4::Mat4;
+    use crate::math::vec3::Vec3;
+    use crate::scene::transform::Transform;
+
+    fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    // a clip that holds the only joint at x for a second
+    fn hold(x: f32) -> AnimationClip {
+        let mut track = JointTrack::new(0);
+        let at = vec3(x, 0.0, 0.0);
+        track.translation = Some(Keyframes::new(vec![0.0, 1.0], vec![at.clone(), at], Interpolation::Linear));
+        AnimationClip::new("hold", vec![track])
+    }
+
+    fn skeleton() -> Skeleton {
+        Skeleton::new(vec![Joint::new("root", None, Transform::default(), Mat4::identity())], Mat4::identity())
+    }
+
+    fn weights(player: &AnimationPlayer) -> Vec<(usize, f32)> {
+        player.playing().iter().map(|playing| (playing.clip, playing.weight)).collect()
+    }
+
+    #[test]
+    fn crossfades_move_the_weight_over_at_a_steady_rate() {
+        let clips = vec![hold(0.0), hold(4.0)];
+        let mut player = AnimationPlayer::new();
+        player.play(0, true);
+        player.crossfade(1, true, 0.5);
+
+        // steps that add up exactly in floats
+        for step in 1..4 {
+            player.update(0.125, &clips);
+            let faded = step as f32 * 0.25;
+            let weights = weights(&player);
+            assert!((weights[0].1 - (1.0 - faded)).abs() < 1e-5);
+            assert!((weights[1].1 - faded).abs() < 1e-5);
+        }
+        player.update(0.125, &clips);
+        assert_eq!(weights(&player), vec![(1, 1.0)]);
+    }
+
+    #[test]
+    fn crossfaded_poses_blend_by_weight() {
+        let clips = vec![hold(0.0), hold(4.0)];
+        let mut player = AnimationPlayer::new();
+        player.play(0, true);
+        player.crossfade(1, true, 1.0);
+        player.update(0.25, &clips);
+
+        let pose = player.pose(&skeleton(), &clips);
+        assert!((pose.locals[0].translation.x - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn crossfading_from_nothing_plays_at_once() {
+        let clips = vec![hold(2.0)];
+        let mut player = AnimationPlayer::new();
+        assert_eq!(player.pose(&skeleton(), &clips), skeleton().rest_pose());
+        player.crossfade(0, false, 1.0);
+        assert_eq!(weights(&player), vec![(0, 1.0)]);
+    }
+
+    #[test]
+    fn looping_clips_wrap_and_others_stop_at_the_end() {
+        let clips = vec![hold(0.0)];
+        let mut player = AnimationPlayer::new();
+        player.play(0, true);
+        player.update(1.25, &clips);
+        assert!((player.playing()[0].time - 0.25).abs() < 1e-5);
+        assert!(!player.is_finished(&clips));
+
+        player.play(0, false);
+        player.update(1.25, &clips);
+        assert_eq!(player.playing()[0].time, 1.0);
+        assert!(player.is_finished(&clips));
+    }
+}
\ No newline at end of file
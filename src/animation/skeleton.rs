@@ -0,0 +1,303 @@
+use crate::math::vec3::Vec3;
+use crate::math::mat4::Mat4;
+use crate::scene::transform::Transform;
+use crate::scene::gltf_import::GltfScene;
+use crate::renderer::mesh::Mesh;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joint {
+    pub name: String,
+    // index into the skeleton's joints
+    pub parent: Option<usize>,
+    // local transform when nothing animates the joint
+    pub rest: Transform,
+    // from mesh space into the joint's space in the bind pose
+    pub inverse_bind: Mat4
+}
+
+impl Joint {
+    pub fn new(name: &str, parent: Option<usize>, rest: Transform, inverse_bind: Mat4) -> Joint {
+        Joint {
+            name: name.to_string(),
+            parent,
+            rest,
+            inverse_bind
+        }
+    }
+}
+
+// local transform of every joint, in the skeleton's joint order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pose {
+    pub locals: Vec<Transform>
+}
+
+impl Pose {
+    // weight 0 gives self and 1 other, rotations are slerped
+    pub fn blend(&self, other: &Pose, weight: f32) -> Pose {
+        Pose {
+            locals: self.locals.iter().zip(other.locals.iter())
+                .map(|(a, b)| blend_transforms(a, b, weight))
+                .collect()
+        }
+    }
+}
+
+pub fn blend_transforms(a: &Transform, b: &Transform, weight: f32) -> Transform {
+    Transform {
+        translation: lerp(&a.translation, &b.translation, weight),
+        rotation: a.rotation.slerp(b.rotation.clone(), weight),
+        scale: lerp(&a.scale, &b.scale, weight)
+    }
+}
+
+pub fn lerp(a: &Vec3, b: &Vec3, t: f32) -> Vec3 {
+    a.clone() * (1.0 - t) + b.clone() * t
+}
+
+// joint hierarchy of a skinned mesh. joints may be listed in any order, parents are resolved first
+#[derive(Debug, Clone, PartialEq)]
+pub struct Skeleton {
+    pub joints: Vec<Joint>,
+    // applied above the root joints, for whatever sits between them and the scene root
+    pub root: Mat4,
+    // joint indices with every parent before its children
+    order: Vec<usize>
+}
+
+impl Skeleton {
+    pub fn new(joints: Vec<Joint>, root: Mat4) -> Skeleton {
+        let mut order = vec![];
+        let mut visited = vec![false; joints.len()];
+        for joint in 0..joints.len() {
+            Self::visit(&joints, joint, &mut visited, &mut order);
+        }
+
+        Skeleton {
+            joints,
+            root,
+            order
+        }
+    }
+
+    fn visit(joints: &[Joint], joint: usize, visited: &mut [bool], order: &mut Vec<usize>) {
+        if visited[joint] {
+            return;
+        }
+        visited[joint] = true;
+        if let Some(parent) = joints[joint].parent {
+            Self::visit(joints, parent, visited, order);
+        }
+        order.push(joint);
+    }
+
+    // the skeleton of a gltf skin. joint parents are the nodes' parents when those are joints of the same
+    // skin, and the nodes above the topmost joint end up in root
+    pub fn from_gltf(scene: &GltfScene, skin: usize) -> Skeleton {
+        let skin = &scene.skins[skin];
+        let joint_of_node = |node: usize| skin.joints.iter().position(|joint| *joint == node);
+
+        let joints: Vec<Joint> = skin.joints.iter().zip(skin.inverse_bind_matrices.iter())
+            .map(|(node, inverse_bind)| {
+                let node_data = &scene.nodes[*node];
+                let parent = node_data.parent.and_then(|parent| joint_of_node(parent));
+                Joint::new(&node_data.name, parent, node_data.transform.clone(), inverse_bind.clone())
+            })
+            .collect();
+
+        let root = skin.joints.iter()
+            .find(|node| scene.nodes[**node].parent.and_then(|parent| joint_of_node(parent)).is_none())
+            .and_then(|node| scene.nodes[*node].parent)
+            .map(|parent| scene.world_matrix(parent))
+            .unwrap_or_else(Mat4::identity);
+
+        Skeleton::new(joints, root)
+    }
+
+    pub fn joint_index(&self, name: &str) -> Option<usize> {
+        self.joints.iter().position(|joint| joint.name == name)
+    }
+
+    pub fn rest_pose(&self) -> Pose {
+        Pose {
+            locals: self.joints.iter().map(|joint| joint.rest.clone()).collect()
+        }
+    }
+
+    // model space matrix of every joint
+    pub fn world_matrices(&self, pose: &Pose) -> Vec<Mat4> {
+        let mut ret = vec![Mat4::identity(); self.joints.len()];
+        for joint in self.order.iter().cloned() {
+            let local = pose.locals[joint].to_matrix();
+            ret[joint] = match self.joints[joint].parent {
+                Some(parent) => ret[parent].clone() * &local,
+                None => self.root.clone() * &local
+            };
+        }
+        ret
+    }
+
+    // what the skinning shader multiplies bind pose vertices by, one matrix per joint
+    pub fn skinning_matrices(&self, pose: &Pose) -> Vec<Mat4> {
+        self.world_matrices(pose).into_iter()
+            .zip(self.joints.iter())
+            .map(|(world, joint)| world * &joint.inverse_bind)
+            .collect()
+    }
+}
+
+// joints and weights of every vertex as the skinning shader gets them. a mesh without skinning data
+// follows the first joint, errors if the streams don't have one entry per vertex or name a joint past
+// joint_count
+pub fn vertex_influences(mesh: &Mesh, joint_count: usize) -> Result<Vec<([u16; 4], [f32; 4])>, String> {
+    if joint_count == 0 {
+        return Err("a skinned mesh needs at least one joint".to_string());
+    }
+    let vertex_count = mesh.vertices.len();
+    if mesh.joints.is_empty() && mesh.weights.is_empty() {
+        return Ok(vec![([0; 4], [1.0, 0.0, 0.0, 0.0]); vertex_count]);
+    }
+    if mesh.joints.len() != vertex_count || mesh.weights.len() != vertex_count {
+        return Err(format!("{} vertices with {} joints and {} weights", vertex_count, mesh.joints.len(), mesh.weights.len()));
+    }
+    if let Some(joint) = mesh.joints.iter().flat_map(|joints| joints.iter()).find(|joint| **joint as usize >= joint_count) {
+        return Err(format!("joint {} isn't in a skeleton of {} joints", joint, joint_count));
+    }
+    Ok(mesh.joints.iter().cloned().zip(mesh.weights.iter().cloned()).collect())
+}
+
+// the same blend of joint matrices the skinned vertex shader does, for checking poses without a gpu
+pub fn skin_mesh(mesh: &Mesh, skinning_matrices: &[Mat4]) -> Result<Mesh, String> {
+    let influences = vertex_influences(mesh, skinning_matrices.len())?;
+    let mut ret = mesh.clone();
+    for (i, (joints, weights)) in influences.iter().enumerate() {
+        let matrix = blended_matrix(joints, weights, skinning_matrices);
+        ret.vertices[i] = matrix.clone() * &mesh.vertices[i];
+    }
+    // normals can be indexed separately, they're skinned with the first vertex that uses them
+    let mut skinned = vec![false; mesh.normals.len()];
+    for (i, (joints, weights)) in influences.iter().enumerate() {
+        let normal_index = mesh.normal_indices.get(i).map(|n| *n as usize).unwrap_or(i);
+        if normal_index >= mesh.normals.len() || skinned[normal_index] {
+            continue;
+        }
+        let matrix = blended_matrix(joints, weights, skinning_matrices);
+        ret.normals[normal_index] = matrix.transform_vector(&mesh.normals[normal_index]).normalized();
+        skinned[normal_index] = true;
+    }
+    Ok(ret)
+}
+
+fn blended_matrix(joints: &[u16; 4], weights: &[f32; 4], skinning_matrices: &[Mat4]) -> Mat4 {
+    let mut ret = Mat4 { mat: [[0.0; 4]; 4] };
+    for (joint, weight) in joints.iter().zip(weights.iter()) {
+        for row in 0..4 {
+            for column in 0..4 {
+                ret[row][column] += skinning_matrices[*joint as usize][row][column] * weight;
+            }
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::quaternion::Quat;
+
+    fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn assert_near(a: &Vec3, b: &Vec3) {
+        assert!((a.clone() - b.clone()).lenght() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    fn translation(x: f32, y: f32, z: f32) -> Transform {
+        Transform { translation: vec3(x, y, z), ..Transform::default() }
+    }
+
+    // a root at x = 1 and a child 2 up from it, listed before its parent
+    fn arm() -> Skeleton {
+        let root = translation(1.0, 0.0, 0.0);
+        let child = translation(0.0, 2.0, 0.0);
+        let child_bind = (root.to_matrix() * &child.to_matrix()).invert().expect("failed to invert the child's bind pose");
+        let root_bind = root.to_matrix().invert().expect("failed to invert the root's bind pose");
+        Skeleton::new(vec![
+            Joint::new("child", Some(1), child, child_bind),
+            Joint::new("root", None, root, root_bind)
+        ], Mat4::identity())
+    }
+
+    fn triangle() -> Mesh {
+        Mesh::new(vec![vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0)], vec![0, 1, 2], vec![], vec![], vec![], vec![])
+    }
+
+    #[test]
+    fn world_matrices_apply_parents_first() {
+        let skeleton = arm();
+        let mut pose = skeleton.rest_pose();
+        pose.locals[1].rotation = Quat::from_axis_angle(&vec3(0.0, 0.0, 1.0), 90.0);
+        let world = skeleton.world_matrices(&pose);
+
+        assert_near(&(world[1].clone() * &vec3(0.0, 0.0, 0.0)), &vec3(1.0, 0.0, 0.0));
+        // the root's turn swings the child from +y over to -x
+        assert_near(&(world[0].clone() * &vec3(0.0, 0.0, 0.0)), &vec3(-1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn skinning_matrices_are_identity_in_the_rest_pose() {
+        let skeleton = arm();
+        for matrix in skeleton.skinning_matrices(&skeleton.rest_pose()) {
+            for row in 0..4 {
+                for column in 0..4 {
+                    let expected = if row == column { 1.0 } else { 0.0 };
+                    assert!((matrix[row][column] - expected).abs() < 1e-5);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn skin_mesh_blends_joints_by_weight() {
+        let skeleton = arm();
+        let mut pose = skeleton.rest_pose();
+        pose.locals[0].translation = vec3(0.0, 4.0, 0.0);
+        let mut mesh = triangle();
+        mesh.joints = vec![[0, 0, 0, 0], [1, 0, 0, 0], [0, 1, 0, 0]];
+        mesh.weights = vec![[1.0, 0.0, 0.0, 0.0], [1.0, 0.0, 0.0, 0.0], [0.5, 0.5, 0.0, 0.0]];
+
+        let skinned = skin_mesh(&mesh, &skeleton.skinning_matrices(&pose)).expect("failed to skin the mesh");
+        assert_near(&skinned.vertices[0], &vec3(0.0, 2.0, 0.0));
+        assert_near(&skinned.vertices[1], &vec3(1.0, 0.0, 0.0));
+        assert_near(&skinned.vertices[2], &vec3(0.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn meshes_without_skinning_data_follow_the_first_joint() {
+        let skeleton = arm();
+        let mut pose = skeleton.rest_pose();
+        pose.locals[0].translation = vec3(0.0, 3.0, 0.0);
+        let mesh = triangle();
+
+        let influences = vertex_influences(&mesh, skeleton.joints.len()).expect("failed to get the influences");
+        assert_eq!(influences, vec![([0; 4], [1.0, 0.0, 0.0, 0.0]); 3]);
+        let skinned = skin_mesh(&mesh, &skeleton.skinning_matrices(&pose)).expect("failed to skin the mesh");
+        assert_near(&skinned.vertices[2], &vec3(0.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn skinning_data_is_checked_against_the_mesh_and_skeleton() {
+        let matrices = arm().skinning_matrices(&arm().rest_pose());
+        let mut mesh = triangle();
+        mesh.joints = vec![[0; 4]; 3];
+        mesh.weights = vec![[1.0, 0.0, 0.0, 0.0]; 2];
+        assert!(skin_mesh(&mesh, &matrices).is_err());
+
+        mesh.weights.push([1.0, 0.0, 0.0, 0.0]);
+        mesh.joints[1] = [2, 0, 0, 0];
+        assert!(skin_mesh(&mesh, &matrices).is_err());
+
+        assert!(vertex_influences(&triangle(), 0).is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,653 @@
+use crate::math::vec2::Vec2;
+use crate::math::vec3::Vec3;
+use crate::math::quaternion::Quat;
+use super::easing::Easing;
+
+// a value that can be interpolated, t is the eased progress and can leave 0..1 for overshooting curves
+pub trait Tweenable: Clone {
+    fn tween(&self, to: &Self, t: f32) -> Self;
+}
+
+impl Tweenable for f32 {
+    fn tween(&self, to: &f32, t: f32) -> f32 {
+        self + (to - self) * t
+    }
+}
+
+impl Tweenable for Vec2 {
+    fn tween(&self, to: &Vec2, t: f32) -> Vec2 {
+        self.clone() + (to.clone() - self.clone()) * t
+    }
+}
+
+impl Tweenable for Vec3 {
+    fn tween(&self, to: &Vec3, t: f32) -> Vec3 {
+        self.clone() + (to.clone() - self.clone()) * t
+    }
+}
+
+impl Tweenable for Quat {
+    fn tween(&self, to: &Quat, t: f32) -> Quat {
+        self.slerp(to.clone(), t)
+    }
+}
+
+// rgba colors, channel by channel
+impl Tweenable for [f32; 4] {
+    fn tween(&self, to: &[f32; 4], t: f32) -> [f32; 4] {
+        let mut ret = *self;
+        for (channel, to) in ret.iter_mut().zip(to.iter()) {
+            *channel += (to - *channel) * t;
+        }
+        ret
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Repeat {
+    // plays once more than the count
+    Times(u32),
+    Forever
+}
+
+impl Repeat {
+    fn plays(&self) -> Option<u32> {
+        match *self {
+            Repeat::Times(times) => Some(times + 1),
+            Repeat::Forever => None
+        }
+    }
+}
+
+// anything the tweener and sequences can advance: tweens, waits, callbacks and sequences themselves
+pub trait Animated {
+    // advances by delta seconds and returns what is left of delta after finishing, 0 while still running
+    fn update(&mut self, delta: f32) -> f32;
+    fn is_finished(&self) -> bool;
+    // back to the start, so sequences can repeat their steps
+    fn reset(&mut self);
+}
+
+/*********************************
+*** TWEEN
+*********************************/
+
+// animates one value from one end to the other. the value is read with value() or handed to the update
+// callback every time it changes, which is how tweens drive properties they don't own
+pub struct Tween<T: Tweenable> {
+    pub from: T,
+    pub to: T,
+    // seconds for one play
+    pub duration: f32,
+    pub easing: Easing,
+    // seconds before the first play starts, not repeated
+    pub delay: f32,
+    pub repeat: Repeat,
+    // every second play runs backwards, from to back to from
+    pub yoyo: bool,
+    elapsed: f32,
+    value: T,
+    finished: bool,
+    on_update: Option<Box<dyn FnMut(&T)>>,
+    on_complete: Option<Box<dyn FnMut()>>
+}
+
+impl<T: Tweenable> Tween<T> {
+    pub fn new(from: T, to: T, duration: f32, easing: Easing) -> Tween<T> {
+        Tween {
+            value: from.clone(),
+            from,
+            to,
+            duration,
+            easing,
+            delay: 0.0,
+            repeat: Repeat::Times(0),
+            yoyo: false,
+            elapsed: 0.0,
+            finished: false,
+            on_update: None,
+            on_complete: None
+        }
+    }
+
+    pub fn set_on_update<F: FnMut(&T) + 'static>(&mut self, callback: F) {
+        self.on_update = Some(Box::new(callback));
+    }
+
+    pub fn set_on_complete<F: FnMut() + 'static>(&mut self, callback: F) {
+        self.on_complete = Some(Box::new(callback));
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    // seconds since the tween started, delay included
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    // the eased position between from and to after time seconds of playing, and the time left over once
+    // every play is done
+    fn progress(&self, time: f32) -> (f32, Option<f32>) {
+        let duration = self.duration.max(0.0);
+        let plays = self.repeat.plays();
+        let backwards = |play: u32| self.yoyo && play % 2 == 1;
+
+        if let Some(plays) = plays {
+            let total = duration * plays as f32;
+            if time >= total {
+                let end = if backwards(plays - 1) { 0.0 } else { 1.0 };
+                return (end, Some(time - total));
+            }
+        }
+        if duration <= 0.0 {
+            // repeating forever without a duration, rest at the end
+            return (1.0, None);
+        }
+
+        let play = (time / duration).floor();
+        let t = time / duration - play;
+        let t = if backwards(play as u32) { 1.0 - t } else { t };
+        (t, None)
+    }
+}
+
+impl<T: Tweenable> Animated for Tween<T> {
+    fn update(&mut self, delta: f32) -> f32 {
+        if self.finished {
+            return delta;
+        }
+        self.elapsed += delta;
+        if self.elapsed < self.delay {
+            return 0.0;
+        }
+
+        let (t, leftover) = self.progress(self.elapsed - self.delay);
+        self.value = self.from.tween(&self.to, self.easing.apply(t));
+        if let Some(on_update) = self.on_update.as_mut() {
+            on_update(&self.value);
+        }
+
+        match leftover {
+            Some(leftover) => {
+                self.finished = true;
+                if let Some(on_complete) = self.on_complete.as_mut() {
+                    on_complete();
+                }
+                // never more than this update's delta, the delay may have eaten into it
+                leftover.min(delta)
+            }
+            None => 0.0
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn reset(&mut self) {
+        self.elapsed = 0.0;
+        self.finished = false;
+        self.value = self.from.clone();
+    }
+}
+
+/*********************************
+*** STEPS
+*********************************/
+
+// does nothing for a while, to space out the steps of a sequence
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wait {
+    pub duration: f32,
+    elapsed: f32
+}
+
+impl Wait {
+    pub fn new(duration: f32) -> Wait {
+        Wait {
+            duration,
+            elapsed: 0.0
+        }
+    }
+}
+
+impl Animated for Wait {
+    fn update(&mut self, delta: f32) -> f32 {
+        self.elapsed += delta;
+        (self.elapsed - self.duration).max(0.0).min(delta)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    fn reset(&mut self) {
+        self.elapsed = 0.0;
+    }
+}
+
+// calls a function once when reached, without taking any time
+pub struct Call {
+    callback: Box<dyn FnMut()>,
+    called: bool
+}
+
+impl Call {
+    pub fn new<F: FnMut() + 'static>(callback: F) -> Call {
+        Call {
+            callback: Box::new(callback),
+            called: false
+        }
+    }
+}
+
+impl Animated for Call {
+    fn update(&mut self, delta: f32) -> f32 {
+        if !self.called {
+            (self.callback)();
+            self.called = true;
+        }
+        delta
+    }
+
+    fn is_finished(&self) -> bool {
+        self.called
+    }
+
+    fn reset(&mut self) {
+        self.called = false;
+    }
+}
+
+/*********************************
+*** SEQUENCE
+*********************************/
+
+// runs its steps one after another, or all at once when parallel. time left over by a finishing step
+// carries into the next, so a sequence keeps its timing at any frame rate
+pub struct Sequence {
+    pub steps: Vec<Box<dyn Animated>>,
+    pub parallel: bool,
+    pub repeat: Repeat,
+    current: usize,
+    plays: u32,
+    finished: bool,
+    on_complete: Option<Box<dyn FnMut()>>
+}
+
+impl Sequence {
+    pub fn new() -> Sequence {
+        Sequence {
+            steps: vec![],
+            parallel: false,
+            repeat: Repeat::Times(0),
+            current: 0,
+            plays: 0,
+            finished: false,
+            on_complete: None
+        }
+    }
+
+    // steps that all start together, finishing when the longest does
+    pub fn parallel() -> Sequence {
+        let mut ret = Sequence::new();
+        ret.parallel = true;
+        ret
+    }
+
+    pub fn push<A: Animated + 'static>(&mut self, step: A) {
+        self.steps.push(Box::new(step));
+    }
+
+    pub fn push_wait(&mut self, duration: f32) {
+        self.push(Wait::new(duration));
+    }
+
+    pub fn push_call<F: FnMut() + 'static>(&mut self, callback: F) {
+        self.push(Call::new(callback));
+    }
+
+    pub fn set_on_complete<F: FnMut() + 'static>(&mut self, callback: F) {
+        self.on_complete = Some(Box::new(callback));
+    }
+
+    // the step running now, in order
+    pub fn current_step(&self) -> usize {
+        self.current
+    }
+
+    // advances one play, returns the leftover time once it's done
+    fn update_play(&mut self, delta: f32) -> Option<f32> {
+        if self.parallel {
+            let leftover = self.steps.iter_mut()
+                .map(|step| if step.is_finished() { delta } else { step.update(delta) })
+                .fold(delta, f32::min);
+            return if self.steps.iter().all(|step| step.is_finished()) { Some(leftover) } else { None };
+        }
+
+        let mut delta = delta;
+        while self.current < self.steps.len() {
+            let step = &mut self.steps[self.current];
+            delta = step.update(delta);
+            if !step.is_finished() {
+                return None;
+            }
+            self.current += 1;
+        }
+        Some(delta)
+    }
+}
+
+impl Animated for Sequence {
+    fn update(&mut self, delta: f32) -> f32 {
+        if self.finished {
+            return delta;
+        }
+
+        let mut delta = delta;
+        loop {
+            let leftover = match self.update_play(delta) {
+                Some(leftover) => leftover,
+                None => return 0.0
+            };
+            self.plays += 1;
+            if self.repeat.plays().map(|plays| self.plays >= plays).unwrap_or(false) {
+                self.finished = true;
+                if let Some(on_complete) = self.on_complete.as_mut() {
+                    on_complete();
+                }
+                return leftover;
+            }
+
+            self.current = 0;
+            for step in self.steps.iter_mut() {
+                step.reset();
+            }
+            // a play that takes no time would repeat forever within this update
+            if leftover <= 0.0 || leftover >= delta {
+                return 0.0;
+            }
+            delta = leftover;
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn reset(&mut self) {
+        self.current = 0;
+        self.plays = 0;
+        self.finished = false;
+        for step in self.steps.iter_mut() {
+            step.reset();
+        }
+    }
+}
+
+impl Default for Sequence {
+    fn default() -> Sequence {
+        Sequence::new()
+    }
+}
+
+/*********************************
+*** TWEENER
+*********************************/
+
+// owns running animations and advances them every frame, dropping the ones that finish
+pub struct Tweener {
+    animations: Vec<(usize, Box<dyn Animated>)>,
+    next_id: usize
+}
+
+impl Tweener {
+    pub fn new() -> Tweener {
+        Tweener {
+            animations: vec![],
+            next_id: 0
+        }
+    }
+
+    // returns an id for cancelling the animation or checking on it
+    pub fn add<A: Animated + 'static>(&mut self, animation: A) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.animations.push((id, Box::new(animation)));
+        id
+    }
+
+    // stops the animation where it is, without completion callbacks
+    pub fn cancel(&mut self, id: usize) {
+        self.animations.retain(|(animation_id, _)| *animation_id != id);
+    }
+
+    pub fn is_active(&self, id: usize) -> bool {
+        self.animations.iter().any(|(animation_id, _)| *animation_id == id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.animations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.animations.is_empty()
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        for (_, animation) in self.animations.iter_mut() {
+            animation.update(delta);
+        }
+        self.animations.retain(|(_, animation)| !animation.is_finished());
+    }
+}
+
+impl Default for Tweener {
+    fn default() -> Tweener {
+        Tweener::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    // a tween from 0 to 1 over a second that reports its value into the returned cell
+    fn watched(duration: f32) -> (Tween<f32>, Rc<Cell<f32>>) {
+        let value = Rc::new(Cell::new(0.0));
+        let mut tween = Tween::new(0.0, 1.0, duration, Easing::Linear);
+        let seen = value.clone();
+        tween.set_on_update(move |v| seen.set(*v));
+        (tween, value)
+    }
+
+    fn counter() -> (Rc<Cell<u32>>, impl FnMut() + 'static) {
+        let count = Rc::new(Cell::new(0));
+        let counted = count.clone();
+        (count, move || counted.set(counted.get() + 1))
+    }
+
+    #[test]
+    fn every_easing_starts_at_from_and_ends_at_to() {
+        let easings = [
+            Easing::Linear, Easing::QuadIn, Easing::QuadOut, Easing::QuadInOut, Easing::CubicIn, Easing::CubicOut,
+            Easing::CubicInOut, Easing::ElasticIn, Easing::ElasticOut, Easing::ElasticInOut, Easing::BounceIn,
+            Easing::BounceOut, Easing::BounceInOut, Easing::Bezier(0.25, 0.1, 0.25, 1.0)
+        ];
+        for easing in easings.iter() {
+            assert_near(easing.apply(0.0), 0.0);
+            assert_near(easing.apply(1.0), 1.0);
+
+            let mut tween = Tween::new(2.0, 6.0, 1.0, *easing);
+            tween.update(0.0);
+            assert_near(*tween.value(), 2.0);
+            tween.update(1.0);
+            assert_near(*tween.value(), 6.0);
+            assert!(tween.is_finished(), "{:?}", easing);
+        }
+    }
+
+    #[test]
+    fn delay_holds_the_start() {
+        let mut tween = Tween::new(0.0, 10.0, 1.0, Easing::Linear);
+        tween.delay = 0.5;
+        assert_eq!(tween.update(0.25), 0.0);
+        assert_near(*tween.value(), 0.0);
+        tween.update(0.5);
+        assert_near(*tween.value(), 2.5);
+        assert_near(tween.elapsed(), 0.75);
+
+        // finishes 0.75 seconds into this update, the rest is handed back
+        assert_near(tween.update(1.0), 0.25);
+        assert!(tween.is_finished());
+        assert_near(*tween.value(), 10.0);
+        assert_eq!(tween.update(0.5), 0.5);
+    }
+
+    #[test]
+    fn repeats_play_again_and_yoyo_runs_back() {
+        let mut tween = Tween::new(0.0, 1.0, 1.0, Easing::Linear);
+        tween.repeat = Repeat::Times(1);
+        tween.update(1.25);
+        assert_near(*tween.value(), 0.25);
+        assert!(!tween.is_finished());
+        assert_near(tween.update(1.0), 0.25);
+        assert_near(*tween.value(), 1.0);
+
+        let mut tween = Tween::new(0.0, 1.0, 1.0, Easing::Linear);
+        tween.repeat = Repeat::Times(2);
+        tween.yoyo = true;
+        tween.update(0.25);
+        assert_near(*tween.value(), 0.25);
+        tween.update(1.0);
+        assert_near(*tween.value(), 0.75);
+        tween.update(1.0);
+        assert_near(*tween.value(), 0.25);
+        // three plays, the last forwards again
+        tween.update(1.0);
+        assert!(tween.is_finished());
+        assert_near(*tween.value(), 1.0);
+
+        let mut forever = Tween::new(0.0, 1.0, 1.0, Easing::Linear);
+        forever.repeat = Repeat::Forever;
+        forever.yoyo = true;
+        for _ in 0..100 {
+            assert_eq!(forever.update(0.7), 0.0);
+        }
+        assert!(!forever.is_finished());
+    }
+
+    #[test]
+    fn callbacks_see_every_value_and_completion_once() {
+        let (mut tween, value) = watched(1.0);
+        let (completed, on_complete) = counter();
+        tween.set_on_complete(on_complete);
+        tween.update(0.5);
+        assert_near(value.get(), 0.5);
+        assert_eq!(completed.get(), 0);
+        tween.update(1.0);
+        tween.update(1.0);
+        assert_near(value.get(), 1.0);
+        assert_eq!(completed.get(), 1);
+
+        tween.reset();
+        assert!(!tween.is_finished());
+        assert_near(*tween.value(), 0.0);
+    }
+
+    #[test]
+    fn sequences_carry_leftover_time_into_the_next_step() {
+        let (first, first_value) = watched(1.0);
+        let (second, second_value) = watched(1.0);
+        let (called, call) = counter();
+        let mut sequence = Sequence::new();
+        sequence.push(first);
+        sequence.push_wait(0.5);
+        sequence.push_call(call);
+        sequence.push(second);
+
+        sequence.update(1.25);
+        assert_near(first_value.get(), 1.0);
+        assert_eq!(sequence.current_step(), 1);
+        assert_eq!(called.get(), 0);
+
+        // the wait ends 0.25 in, the call takes no time and the second tween gets the rest
+        sequence.update(0.5);
+        assert_eq!(called.get(), 1);
+        assert_eq!(sequence.current_step(), 3);
+        assert_near(second_value.get(), 0.25);
+
+        assert_near(sequence.update(1.0), 0.25);
+        assert!(sequence.is_finished());
+        assert_near(second_value.get(), 1.0);
+        assert_eq!(called.get(), 1);
+    }
+
+    #[test]
+    fn repeating_sequences_restart_their_steps() {
+        let (tween, value) = watched(1.0);
+        let (called, call) = counter();
+        let (completed, on_complete) = counter();
+        let mut sequence = Sequence::new();
+        sequence.repeat = Repeat::Times(1);
+        sequence.push_call(call);
+        sequence.push(tween);
+        sequence.set_on_complete(on_complete);
+
+        sequence.update(1.5);
+        assert_eq!(called.get(), 2);
+        assert_near(value.get(), 0.5);
+        assert!(!sequence.is_finished());
+        sequence.update(1.0);
+        assert!(sequence.is_finished());
+        assert_eq!(completed.get(), 1);
+    }
+
+    #[test]
+    fn parallel_groups_finish_with_their_longest_step() {
+        let (short, short_value) = watched(1.0);
+        let (long, long_value) = watched(2.0);
+        let mut group = Sequence::parallel();
+        group.push(short);
+        group.push(long);
+
+        assert_eq!(group.update(1.5), 0.0);
+        assert_near(short_value.get(), 1.0);
+        assert_near(long_value.get(), 0.75);
+        assert!(!group.is_finished());
+        assert_near(group.update(1.0), 0.5);
+        assert!(group.is_finished());
+        assert_near(long_value.get(), 1.0);
+    }
+
+    #[test]
+    fn tweener_drops_finished_and_cancelled_animations() {
+        let mut tweener = Tweener::new();
+        let short = tweener.add(Tween::new(0.0, 1.0, 1.0, Easing::Linear));
+        let (completed, on_complete) = counter();
+        let mut long = Tween::new(0.0, 1.0, 2.0, Easing::Linear);
+        long.set_on_complete(on_complete);
+        let long = tweener.add(long);
+        assert_ne!(short, long);
+        assert_eq!(tweener.len(), 2);
+
+        tweener.update(1.5);
+        assert!(!tweener.is_active(short));
+        assert!(tweener.is_active(long));
+        assert_eq!(tweener.len(), 1);
+
+        tweener.cancel(long);
+        assert!(tweener.is_empty());
+        tweener.update(1.0);
+        assert_eq!(completed.get(), 0);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,58 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+// a typed reference to an asset in an AssetManager. every clone counts as a user of the asset, it stays
+// loaded until the last handle is dropped and unused assets are unloaded
+pub struct Handle<T> {
+    index: usize,
+    // the manager keeps one more, so the count of users is one less than the strong count
+    token: Arc<()>,
+    asset: PhantomData<fn() -> T>
+}
+
+impl<T> Handle<T> {
+    pub(crate) fn new(index: usize, token: &Arc<()>) -> Handle<T> {
+        Handle {
+            index,
+            token: token.clone(),
+            asset: PhantomData
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub(crate) fn token(&self) -> &Arc<()> {
+        &self.token
+    }
+}
+
+// derived impls would ask the same of T
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Handle<T> {
+        Handle::new(self.index, &self.token)
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Handle<T>) -> bool {
+        Arc::ptr_eq(&self.token, &other.token)
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Handle<{}>({})", std::any::type_name::<T>(), self.index)
+    }
+}
\ No newline at end of file
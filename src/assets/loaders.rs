@@ -0,0 +1,151 @@
+use super::manager::AssetManager;
+use crate::audio::sound::Sound;
+use crate::math::vec3::Vec3;
+use crate::renderer::mesh::Mesh;
+use crate::renderer::shader;
+use crate::renderer::spirv::{self, ShaderStage};
+use crate::renderer::texture::Texture;
+use crate::scene::gltf_import::{self, GltfScene};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+// spir-v ready to be made into a RuntimeShader on the device
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShaderCode {
+    pub stage: ShaderStage,
+    pub words: Vec<u32>
+}
+
+pub fn register_defaults(assets: &mut AssetManager) {
+    assets.register_loader(&["obj"], load_obj);
+    assets.register_loader(&["gltf", "glb"], load_gltf_mesh);
+    assets.register_loader(&["png", "jpg", "jpeg"], load_texture);
+    assets.register_loader(&["vert", "frag", "spv"], load_shader);
+    assets.register_loader(&["wav", "ogg", "flac"], |path: &Path| Ok(Sound::load(path)?));
+    assets.register_loader(&["gltf", "glb"], |path: &Path| -> Result<GltfScene, Box<dyn Error>> { Ok(gltf_import::import(path)?) });
+}
+
+// loaded as color, so sRGB. linear data like normal maps needs a loader of its own
+pub fn load_texture(path: &Path) -> Result<Texture, Box<dyn Error>> {
+    let image = image::open(path)?.to_rgba8();
+    let (width, height) = image.dimensions();
+    let pixels = image.pixels().map(|pixel| pixel.0).collect();
+    Ok(Texture::new(width, height, pixels, true)?)
+}
+
+// .vert and .frag are compiled as glsl, the stage of .spv comes from the module
+pub fn load_shader(path: &Path) -> Result<ShaderCode, Box<dyn Error>> {
+    let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase()).unwrap_or_default();
+    if extension == "spv" {
+        let words = spirv::words_from_bytes(&fs::read(path)?)?;
+        let stage = spirv::reflect(&words)?.stage;
+        return Ok(ShaderCode { stage, words });
+    }
+    let stage = if extension == "frag" { ShaderStage::Fragment } else { ShaderStage::Vertex };
+    Ok(ShaderCode { stage, words: shader::load_spirv(path, stage)? })
+}
+
+// the first mesh of the file, whole scenes are loaded as GltfScene
+pub fn load_gltf_mesh(path: &Path) -> Result<Mesh, Box<dyn Error>> {
+    let scene = gltf_import::import(path)?;
+    match scene.meshes.into_iter().next() {
+        Some(mesh) => Ok(mesh),
+        None => Err("the file has no meshes".into())
+    }
+}
+
+/*********************************
+*** OBJ
+*********************************/
+
+// positions, texture coordinates and normals of every object in the file as one mesh. polygons are made
+// into fans of triangles and a vertex is made for every different position, uv and normal triple
+pub fn load_obj(path: &Path) -> Result<Mesh, Box<dyn Error>> {
+    parse_obj(&fs::read_to_string(path)?).map_err(|e| e.into())
+}
+
+pub fn parse_obj(source: &str) -> Result<Mesh, String> {
+    let mut positions = vec![];
+    let mut uvs = vec![];
+    let mut normals = vec![];
+    let mut mesh = Mesh::new(vec![], vec![], vec![], vec![], vec![], vec![]);
+    let mut vertices: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let line_error = |message: &str| format!("line {}: {}", number + 1, message);
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => positions.push(parse_vec3(words).ok_or_else(|| line_error("bad position"))?),
+            Some("vt") => {
+                let mut uv = parse_vec3(words.chain(["0", "0"].iter().cloned())).ok_or_else(|| line_error("bad texture coordinate"))?;
+                // obj puts v = 0 at the bottom of the image
+                uv.y = 1.0 - uv.y;
+                uv.z = 0.0;
+                uvs.push(uv);
+            }
+            Some("vn") => normals.push(parse_vec3(words).ok_or_else(|| line_error("bad normal"))?),
+            Some("f") => {
+                let mut corners = vec![];
+                for corner in words {
+                    let key = parse_corner(corner, positions.len(), uvs.len(), normals.len())
+                        .ok_or_else(|| line_error(&format!("bad face corner {}", corner)))?;
+                    let next = mesh.vertices.len() as u32;
+                    let index = *vertices.entry(key).or_insert(next);
+                    if index == next {
+                        mesh.vertices.push(positions[key.0].clone());
+                        if let Some(uv) = key.1 {
+                            mesh.uvs.push(uvs[uv].clone());
+                        }
+                        if let Some(normal) = key.2 {
+                            mesh.normals.push(normals[normal].clone());
+                        }
+                    }
+                    corners.push(index);
+                }
+                if corners.len() < 3 {
+                    return Err(line_error("face with less than 3 corners"));
+                }
+                for i in 1..corners.len() - 1 {
+                    mesh.indices.extend_from_slice(&[corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // every vertex needs one or none of them
+    if mesh.uvs.len() != mesh.vertices.len() {
+        mesh.uvs.clear();
+    }
+    if mesh.normals.len() != mesh.vertices.len() {
+        mesh.normals.clear();
+    }
+    Ok(mesh)
+}
+
+fn parse_vec3<'a, I: Iterator<Item = &'a str>>(mut words: I) -> Option<Vec3> {
+    let mut next = || words.next().and_then(|word| word.parse::<f32>().ok());
+    Some(Vec3 { x: next()?, y: next()?, z: next()? })
+}
+
+// v, v/vt, v//vn or v/vt/vn, counted from 1 or from the end when negative
+fn parse_corner(corner: &str, positions: usize, uvs: usize, normals: usize) -> Option<(usize, Option<usize>, Option<usize>)> {
+    let mut parts = corner.split('/');
+    let index = |part: Option<&str>, count: usize| -> Option<Option<usize>> {
+        match part {
+            None | Some("") => Some(None),
+            Some(part) => {
+                let index = part.parse::<i64>().ok()?;
+                let index = if index < 0 { count as i64 + index } else { index - 1 };
+                if index < 0 || index as usize >= count { None } else { Some(Some(index as usize)) }
+            }
+        }
+    };
+    let position = index(parts.next(), positions)??;
+    let uv = index(parts.next(), uvs)?;
+    let normal = index(parts.next(), normals)?;
+    Some((position, uv, normal))
+}
\ No newline at end of file
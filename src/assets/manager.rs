@@ -0,0 +1,355 @@
+use super::handle::Handle;
+use super::loaders;
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub enum AssetError {
+    NoLoader { path: PathBuf, asset_type: &'static str },
+    // shared so the error can be both returned and kept in the manager's list
+    Load { path: PathBuf, error: Arc<dyn Error> }
+}
+
+impl AssetError {
+    pub fn path(&self) -> &Path {
+        match self {
+            AssetError::NoLoader { path, .. } => path,
+            AssetError::Load { path, .. } => path
+        }
+    }
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetError::NoLoader { path, asset_type } =>
+                write!(f, "{}: no loader for {} files as {}", path.display(), extension(path), asset_type),
+            AssetError::Load { path, error } => write!(f, "{}: {}", path.display(), error)
+        }
+    }
+}
+
+impl Error for AssetError {}
+
+type LoadFn = Box<dyn Fn(&Path) -> Result<Box<dyn Any>, Box<dyn Error>>>;
+
+struct Entry {
+    path: PathBuf,
+    asset: Box<dyn Any>,
+    token: Arc<()>
+}
+
+// loads assets by path through the loader registered for the extension and the type asked for, so the
+// same .gltf can be a scene or a mesh. loading a path again gives another handle to what's loaded
+pub struct AssetManager {
+    // relative paths are looked up from here
+    pub root: PathBuf,
+    entries: Vec<Option<Entry>>,
+    by_path: HashMap<(PathBuf, TypeId), usize>,
+    loaders: HashMap<(String, TypeId), LoadFn>,
+    // the last failure of every path that didn't load, cleared once it does
+    errors: HashMap<PathBuf, AssetError>
+}
+
+impl AssetManager {
+    // with the loaders for meshes, textures, shaders, sounds and scenes
+    pub fn new<P: AsRef<Path>>(root: P) -> AssetManager {
+        let mut ret = AssetManager::empty(root);
+        loaders::register_defaults(&mut ret);
+        ret
+    }
+
+    // without any loaders
+    pub fn empty<P: AsRef<Path>>(root: P) -> AssetManager {
+        AssetManager {
+            root: root.as_ref().to_path_buf(),
+            entries: vec![],
+            by_path: HashMap::new(),
+            loaders: HashMap::new(),
+            errors: HashMap::new()
+        }
+    }
+
+    // loads files with the extensions as T, replacing the loaders they had for T. extensions are matched
+    // without the dot and case
+    pub fn register_loader<T, F>(&mut self, extensions: &[&str], loader: F)
+        where T: 'static, F: Fn(&Path) -> Result<T, Box<dyn Error>> + Clone + 'static
+    {
+        for extension in extensions {
+            let loader = loader.clone();
+            self.loaders.insert((extension.to_lowercase(), TypeId::of::<T>()),
+                Box::new(move |path| loader(path).map(|asset| Box::new(asset) as Box<dyn Any>)));
+        }
+    }
+
+    pub fn has_loader<T: 'static>(&self, extension: &str) -> bool {
+        self.loaders.contains_key(&(extension.to_lowercase(), TypeId::of::<T>()))
+    }
+
+    /*********************************
+    *** LOADING
+    *********************************/
+
+    pub fn load<T: 'static, P: AsRef<Path>>(&mut self, path: P) -> Result<Handle<T>, AssetError> {
+        let path = self.resolve(path.as_ref());
+        let key = (path.clone(), TypeId::of::<T>());
+        if let Some(index) = self.by_path.get(&key) {
+            let entry = self.entries[*index].as_ref().expect("failed to find a loaded asset");
+            return Ok(Handle::new(*index, &entry.token));
+        }
+
+        let result = match self.loaders.get(&(extension(&path), TypeId::of::<T>())) {
+            Some(loader) => loader(&path).map_err(|error| AssetError::Load { path: path.clone(), error: Arc::from(error) }),
+            None => Err(AssetError::NoLoader { path: path.clone(), asset_type: std::any::type_name::<T>() })
+        };
+        match result {
+            Ok(asset) => {
+                self.errors.remove(&path);
+                Ok(self.add_entry(path, asset))
+            }
+            Err(error) => {
+                self.errors.insert(path, error.clone());
+                Err(error)
+            }
+        }
+    }
+
+    // loads every path, what failed is in the errors
+    pub fn load_all<T: 'static, P: AsRef<Path>>(&mut self, paths: &[P]) -> Vec<Result<Handle<T>, AssetError>> {
+        paths.iter().map(|path| self.load(path)).collect()
+    }
+
+    // adds an asset made in code, under a name in place of a path that load finds it by. an asset
+    // already under the name is replaced for new handles
+    pub fn insert<T: 'static>(&mut self, name: &str, asset: T) -> Handle<T> {
+        let path = self.resolve(Path::new(name));
+        self.by_path.remove(&(path.clone(), TypeId::of::<T>()));
+        self.add_entry(path, Box::new(asset))
+    }
+
+    pub fn errors(&self) -> &HashMap<PathBuf, AssetError> {
+        &self.errors
+    }
+
+    pub fn clear_errors(&mut self) {
+        self.errors.clear();
+    }
+
+    fn resolve(&self, path: &Path) -> PathBuf {
+        let path = if path.is_relative() { self.root.join(path) } else { path.to_path_buf() };
+        // the same file by different paths is loaded once, files that don't exist fail in their loader
+        fs::canonicalize(&path).unwrap_or(path)
+    }
+
+    fn add_entry<T: 'static>(&mut self, path: PathBuf, asset: Box<dyn Any>) -> Handle<T> {
+        let entry = Entry {
+            path: path.clone(),
+            asset,
+            token: Arc::new(())
+        };
+        let index = match self.entries.iter().position(|entry| entry.is_none()) {
+            Some(index) => index,
+            None => {
+                self.entries.push(None);
+                self.entries.len() - 1
+            }
+        };
+        let handle = Handle::new(index, &entry.token);
+        self.entries[index] = Some(entry);
+        self.by_path.insert((path, TypeId::of::<T>()), index);
+        handle
+    }
+
+    /*********************************
+    *** ACCESS
+    *********************************/
+
+    pub fn get<T: 'static>(&self, handle: &Handle<T>) -> &T {
+        self.entry(handle).asset.downcast_ref().expect("failed to find an asset of the handle's type")
+    }
+
+    pub fn get_mut<T: 'static>(&mut self, handle: &Handle<T>) -> &mut T {
+        let index = handle.index();
+        self.entries[index].as_mut().expect("failed to find the asset of a handle")
+            .asset.downcast_mut().expect("failed to find an asset of the handle's type")
+    }
+
+    pub fn path<T>(&self, handle: &Handle<T>) -> &Path {
+        &self.entry(handle).path
+    }
+
+    // handles out there, not counting the manager's
+    pub fn ref_count<T>(&self, handle: &Handle<T>) -> usize {
+        Arc::strong_count(handle.token()) - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_loaded<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = self.resolve(path.as_ref());
+        self.by_path.keys().any(|(loaded, _)| *loaded == path)
+    }
+
+    // a handle keeps its asset loaded, so the entry is always there
+    fn entry<T>(&self, handle: &Handle<T>) -> &Entry {
+        self.entries[handle.index()].as_ref().expect("failed to find the asset of a handle")
+    }
+
+    /*********************************
+    *** UNLOADING
+    *********************************/
+
+    // drops every asset no handle points to anymore, returns their paths
+    pub fn unload_unused(&mut self) -> Vec<PathBuf> {
+        let mut unloaded = vec![];
+        for slot in self.entries.iter_mut() {
+            let unused = slot.as_ref().map(|entry| Arc::strong_count(&entry.token) == 1).unwrap_or(false);
+            if unused {
+                unloaded.push(slot.take().expect("failed to unload an asset").path);
+            }
+        }
+        let entries = &self.entries;
+        self.by_path.retain(|_, index| entries[*index].is_some());
+        unloaded
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().map(|extension| extension.to_string_lossy().to_lowercase()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a directory of its own in the temp dir, so tests running at once don't share files
+    fn directory(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("assets_test_{}_{}", std::process::id(), name));
+        fs::create_dir_all(&path).expect("failed to create the test directory");
+        path
+    }
+
+    // text files load as strings and .num files as the number they hold
+    fn manager(root: &Path) -> AssetManager {
+        let mut assets = AssetManager::empty(root);
+        assets.register_loader(&["txt"], |path: &Path| -> Result<String, Box<dyn Error>> { Ok(fs::read_to_string(path)?) });
+        assets.register_loader(&["num"], |path: &Path| -> Result<i32, Box<dyn Error>> { Ok(fs::read_to_string(path)?.trim().parse()?) });
+        assets
+    }
+
+    #[test]
+    fn loading_a_path_again_gives_the_same_asset() {
+        let root = directory("same");
+        fs::write(root.join("hello.txt"), "hello").expect("failed to write the asset");
+        let mut assets = manager(&root);
+
+        let first: Handle<String> = assets.load("hello.txt").expect("failed to load");
+        let second: Handle<String> = assets.load(root.join("hello.txt")).expect("failed to load");
+        let third: Handle<String> = assets.load("./hello.txt").expect("failed to load");
+        assert_eq!(first, second);
+        assert_eq!(first, third);
+        assert_eq!(assets.len(), 1);
+        assert_eq!(assets.ref_count(&first), 3);
+        assert_eq!(assets.get(&second), "hello");
+        assert!(assets.is_loaded("hello.txt"));
+
+        assets.get_mut(&first).push_str(" there");
+        assert_eq!(assets.get(&third), "hello there");
+    }
+
+    #[test]
+    fn dropping_the_last_handle_unloads_the_asset() {
+        let root = directory("drop");
+        fs::write(root.join("kept.txt"), "kept").expect("failed to write the asset");
+        fs::write(root.join("dropped.txt"), "dropped").expect("failed to write the asset");
+        let mut assets = manager(&root);
+
+        let kept: Handle<String> = assets.load("kept.txt").expect("failed to load");
+        let dropped: Handle<String> = assets.load("dropped.txt").expect("failed to load");
+        let copy = dropped.clone();
+        assert_eq!(assets.ref_count(&dropped), 2);
+        drop(dropped);
+        assert!(assets.unload_unused().is_empty());
+
+        let path = assets.path(&copy).to_path_buf();
+        drop(copy);
+        assert_eq!(assets.unload_unused(), vec![path]);
+        assert!(!assets.is_loaded("dropped.txt"));
+        assert_eq!(assets.len(), 1);
+        assert_eq!(assets.get(&kept), "kept");
+
+        // loading it again reads the file again, into the freed slot
+        fs::write(root.join("dropped.txt"), "changed").expect("failed to write the asset");
+        let reloaded: Handle<String> = assets.load("dropped.txt").expect("failed to load");
+        assert_eq!(assets.get(&reloaded), "changed");
+        assert_eq!(assets.len(), 2);
+    }
+
+    #[test]
+    fn failed_assets_keep_their_error_without_affecting_the_others() {
+        let root = directory("errors");
+        fs::write(root.join("good.num"), "42").expect("failed to write the asset");
+        fs::write(root.join("bad.num"), "forty two").expect("failed to write the asset");
+        let mut assets = manager(&root);
+
+        let results: Vec<Result<Handle<i32>, AssetError>> = assets.load_all(&["good.num", "bad.num", "missing.num", "good.txt"]);
+        let good = results[0].as_ref().expect("failed to load");
+        assert_eq!(*assets.get(good), 42);
+        match &results[1] {
+            Err(AssetError::Load { path, .. }) => assert_eq!(path.file_name().and_then(|name| name.to_str()), Some("bad.num")),
+            other => panic!("expected a load error, got {:?}", other)
+        }
+        assert!(results[2].is_err());
+        match &results[3] {
+            Err(AssetError::NoLoader { .. }) => {}
+            other => panic!("expected a missing loader, got {:?}", other)
+        }
+        assert_eq!(assets.len(), 1);
+        assert_eq!(assets.errors().len(), 3);
+        assert!(assets.errors().keys().any(|path| path.ends_with("bad.num")));
+        assert!(!assets.errors().keys().any(|path| path.ends_with("good.num")));
+
+        // fixing the file and loading it again clears its error
+        fs::write(root.join("bad.num"), "7").expect("failed to write the asset");
+        let fixed: Handle<i32> = assets.load("bad.num").expect("failed to load");
+        assert_eq!(*assets.get(&fixed), 7);
+        assert_eq!(assets.errors().len(), 2);
+        assets.clear_errors();
+        assert!(assets.errors().is_empty());
+    }
+
+    #[test]
+    fn one_file_can_load_as_different_types() {
+        let root = directory("types");
+        fs::write(root.join("answer.num"), "42").expect("failed to write the asset");
+        let mut assets = manager(&root);
+        assets.register_loader(&["NUM"], |path: &Path| -> Result<String, Box<dyn Error>> { Ok(fs::read_to_string(path)?) });
+        assert!(assets.has_loader::<String>("num"));
+
+        let number: Handle<i32> = assets.load("answer.num").expect("failed to load");
+        let text: Handle<String> = assets.load("answer.num").expect("failed to load");
+        assert_ne!(number.index(), text.index());
+        assert_eq!(*assets.get(&number), 42);
+        assert_eq!(assets.get(&text), "42");
+
+        // made in code, and replaced under the same name for new handles
+        let first = assets.insert("generated", 1);
+        let second = assets.insert("generated", 2);
+        assert_ne!(first, second);
+        assert_eq!(*assets.get(&first), 1);
+        let loaded: Handle<i32> = assets.load("generated").expect("failed to load");
+        assert_eq!(*assets.get(&loaded), 2);
+    }
+}
\ No newline at end of file
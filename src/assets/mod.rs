@@ -0,0 +1,3 @@
+pub mod handle;
+pub mod loaders;
+pub mod manager;
\ No newline at end of file
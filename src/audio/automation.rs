@@ -0,0 +1,232 @@
+use crate::animation::easing::Easing;
+
+use super::mixer::Mixer;
+
+// something on a bus that can be automated or kept in a snapshot
+#[derive(Debug, Clone, PartialEq)]
+pub enum MixTarget {
+    Volume { bus: usize },
+    // a parameter of the effect at index effect on the bus
+    Parameter { bus: usize, effect: usize, name: String }
+}
+
+// moves a target from where it was to a value over time, advanced by the mixer as it mixes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Automation {
+    pub target: MixTarget,
+    pub from: f32,
+    pub to: f32,
+    // in seconds
+    pub duration: f32,
+    pub easing: Easing,
+    elapsed: f32
+}
+
+impl Automation {
+    pub fn new(target: MixTarget, from: f32, to: f32, duration: f32, easing: Easing) -> Automation {
+        Automation {
+            target,
+            from,
+            to,
+            duration,
+            easing,
+            elapsed: 0.0
+        }
+    }
+
+    // advances by delta seconds and returns the value to set
+    pub fn update(&mut self, delta: f32) -> f32 {
+        self.elapsed = (self.elapsed + delta).min(self.duration);
+        if self.duration <= 0.0 {
+            return self.to;
+        }
+        let t = self.easing.apply(self.elapsed / self.duration);
+        self.from + (self.to - self.from) * t
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+// a set of values for bus volumes and effect parameters, like an underwater mix with the music filtered
+// and the reverb up. snapshots can be blended with each other and transitioned to
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixSnapshot {
+    pub values: Vec<(MixTarget, f32)>
+}
+
+impl MixSnapshot {
+    pub fn new() -> MixSnapshot {
+        MixSnapshot {
+            values: vec![]
+        }
+    }
+
+    // the volume of every bus and every parameter of every effect as they are now
+    pub fn capture(mixer: &Mixer) -> MixSnapshot {
+        let mut snapshot = MixSnapshot::new();
+        let mut bus = 0;
+        while let Some(current) = mixer.bus(bus) {
+            snapshot.set(MixTarget::Volume { bus }, current.volume);
+            for (effect, processor) in current.effects.iter().enumerate() {
+                for name in processor.parameters() {
+                    if let Some(value) = processor.parameter(name) {
+                        snapshot.set(MixTarget::Parameter { bus, effect, name: name.to_string() }, value);
+                    }
+                }
+            }
+            bus += 1;
+        }
+        snapshot
+    }
+
+    // replaces the value if the target is already in the snapshot
+    pub fn set(&mut self, target: MixTarget, value: f32) {
+        match self.values.iter_mut().find(|(existing, _)| *existing == target) {
+            Some(entry) => entry.1 = value,
+            None => self.values.push((target, value))
+        }
+    }
+
+    pub fn get(&self, target: &MixTarget) -> Option<f32> {
+        self.values.iter().find(|(existing, _)| existing == target).map(|(_, value)| *value)
+    }
+
+    // t 0 is self and 1 is other, targets only one of them has keep their value
+    pub fn blend(&self, other: &MixSnapshot, t: f32) -> MixSnapshot {
+        let mut blended = self.clone();
+        for (target, value) in other.values.iter() {
+            match self.get(target) {
+                Some(from) => blended.set(target.clone(), from + (value - from) * t),
+                None => blended.set(target.clone(), *value)
+            }
+        }
+        blended
+    }
+}
+
+impl Default for MixSnapshot {
+    fn default() -> MixSnapshot {
+        MixSnapshot::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::effects::Filter;
+    use crate::audio::mixer::{VoiceSettings, MASTER_BUS};
+    use crate::audio::sound::Sound;
+
+    use std::sync::Arc;
+
+    // few frames a second keeps the times easy to follow
+    const RATE: u32 = 100;
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    fn cutoff(bus: usize) -> MixTarget {
+        MixTarget::Parameter { bus, effect: 0, name: "cutoff".to_string() }
+    }
+
+    // a music bus with a low pass, and a constant tone playing on the master bus
+    fn mixer() -> (Mixer, usize) {
+        let mut mixer = Mixer::new(RATE);
+        let music = mixer.add_bus("music", MASTER_BUS);
+        mixer.add_effect(music, Filter::low_pass(1000.0));
+        let tone = Arc::new(Sound::new(RATE, 1, vec![1.0; RATE as usize * 10]));
+        mixer.play(&tone, VoiceSettings::new());
+        (mixer, music)
+    }
+
+    #[test]
+    fn automations_ease_to_their_value() {
+        let mut automation = Automation::new(MixTarget::Volume { bus: 0 }, 1.0, 0.0, 1.0, Easing::Linear);
+        assert_near(automation.update(0.25), 0.75);
+        assert!(!automation.is_finished());
+        assert_near(automation.update(1.0), 0.0);
+        assert!(automation.is_finished());
+
+        let mut eased = Automation::new(MixTarget::Volume { bus: 0 }, 0.0, 1.0, 1.0, Easing::QuadIn);
+        assert_near(eased.update(0.5), 0.25);
+        assert_eq!(Automation::new(MixTarget::Volume { bus: 0 }, 0.0, 1.0, 0.0, Easing::Linear).update(0.0), 1.0);
+    }
+
+    #[test]
+    fn mixing_advances_automations() {
+        let (mut mixer, _) = mixer();
+        let master = MixTarget::Volume { bus: MASTER_BUS };
+        assert!(mixer.automate(master.clone(), 0.0, 1.0, Easing::Linear));
+
+        let mut out = vec![0.0; 50];
+        mixer.mix(&mut out);
+        assert_near(mixer.value(&master).expect("failed to find the master volume"), 0.75);
+        assert_near(out[49], 0.75);
+        assert!(mixer.is_automating(&master));
+
+        let mut out = vec![0.0; 200];
+        mixer.mix(&mut out);
+        assert_eq!(mixer.value(&master), Some(0.0));
+        assert!(!mixer.is_automating(&master));
+        assert_eq!(out, vec![0.0; 200]);
+    }
+
+    #[test]
+    fn missing_targets_are_refused() {
+        let (mut mixer, music) = mixer();
+        assert!(!mixer.automate(MixTarget::Volume { bus: 9 }, 0.0, 1.0, Easing::Linear));
+        assert!(!mixer.set_value(&MixTarget::Parameter { bus: music, effect: 0, name: "missing".to_string() }, 1.0));
+        assert!(!mixer.set_value(&cutoff(MASTER_BUS), 1.0));
+        assert_eq!(mixer.value(&cutoff(9)), None);
+    }
+
+    #[test]
+    fn snapshots_blend_what_they_share() {
+        let (mixer, music) = mixer();
+        let dry = MixSnapshot::capture(&mixer);
+        assert_eq!(dry.get(&MixTarget::Volume { bus: music }), Some(1.0));
+        assert_eq!(dry.get(&cutoff(music)), Some(1000.0));
+        assert_eq!(dry.get(&MixTarget::Parameter { bus: music, effect: 0, name: "q".to_string() }),
+                   Some(std::f32::consts::FRAC_1_SQRT_2));
+
+        let mut underwater = MixSnapshot::new();
+        underwater.set(MixTarget::Volume { bus: music }, 0.2);
+        underwater.set(cutoff(music), 500.0);
+        underwater.set(MixTarget::Volume { bus: 7 }, 0.5);
+
+        let blended = dry.blend(&underwater, 0.5);
+        assert_near(blended.get(&MixTarget::Volume { bus: music }).expect("failed to find the volume"), 0.6);
+        assert_near(blended.get(&cutoff(music)).expect("failed to find the cutoff"), 750.0);
+        // in one of them only, kept as it is
+        assert_eq!(blended.get(&MixTarget::Volume { bus: 7 }), Some(0.5));
+        assert_eq!(blended.get(&MixTarget::Volume { bus: MASTER_BUS }), Some(1.0));
+    }
+
+    #[test]
+    fn mixers_blend_and_transition_to_snapshots() {
+        let (mut mixer, music) = mixer();
+        let dry = MixSnapshot::capture(&mixer);
+        let mut underwater = MixSnapshot::new();
+        underwater.set(cutoff(music), 500.0);
+
+        // setting a blend stops automations of the same targets
+        mixer.automate(cutoff(music), 200.0, 10.0, Easing::Linear);
+        mixer.blend_snapshots(&dry, &underwater, 0.25);
+        assert!(!mixer.is_automating(&cutoff(music)));
+        assert_near(mixer.value(&cutoff(music)).expect("failed to find the cutoff"), 875.0);
+
+        mixer.transition(&underwater, 1.0, Easing::Linear);
+        let mut out = vec![0.0; 100];
+        mixer.mix(&mut out);
+        assert_near(mixer.value(&cutoff(music)).expect("failed to find the cutoff"), 687.5);
+        let mut out = vec![0.0; 100];
+        mixer.mix(&mut out);
+        assert_eq!(mixer.value(&cutoff(music)), Some(500.0));
+
+        mixer.set_snapshot(&dry);
+        assert_eq!(MixSnapshot::capture(&mixer), dry);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,674 @@
+use std::f32::consts::PI;
+
+// processes a bus in place. buffers are interleaved stereo, effects keep their own state between blocks
+pub trait Effect: Send {
+    // sidechain is the block of the bus set as the bus's sidechain, if there is one
+    fn process(&mut self, buffer: &mut [f32], sample_rate: u32, sidechain: Option<&[f32]>);
+
+    // names of what can be automated, see parameter and set_parameter
+    fn parameters(&self) -> &'static [&'static str];
+
+    fn parameter(&self, name: &str) -> Option<f32>;
+
+    // false if the effect has no such parameter
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool;
+
+    // forgets tails and envelopes, for when a bus is restarted
+    fn reset(&mut self) {}
+}
+
+fn db_to_gain(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
+
+fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.max(1e-6).log10()
+}
+
+// coefficient of a one pole smoother reaching ~63% of the way in time seconds
+fn smoothing(time: f32, sample_rate: u32) -> f32 {
+    if time <= 0.0 {
+        return 0.0;
+    }
+    (-1.0 / (time * sample_rate as f32)).exp()
+}
+
+/*********************************
+*** FILTER
+*********************************/
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterKind {
+    LowPass,
+    HighPass
+}
+
+// a 12 db per octave biquad
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    pub kind: FilterKind,
+    // in hz
+    pub cutoff: f32,
+    // resonance, 0.707 is flat
+    pub q: f32,
+    // x1, x2, y1, y2 for each channel
+    state: [[f32; 4]; 2]
+}
+
+impl Filter {
+    pub fn new(kind: FilterKind, cutoff: f32, q: f32) -> Filter {
+        Filter {
+            kind,
+            cutoff,
+            q,
+            state: [[0.0; 4]; 2]
+        }
+    }
+
+    pub fn low_pass(cutoff: f32) -> Filter {
+        Filter::new(FilterKind::LowPass, cutoff, std::f32::consts::FRAC_1_SQRT_2)
+    }
+
+    pub fn high_pass(cutoff: f32) -> Filter {
+        Filter::new(FilterKind::HighPass, cutoff, std::f32::consts::FRAC_1_SQRT_2)
+    }
+
+    // b0, b1, b2, a1, a2 normalized by a0, from the audio eq cookbook
+    fn coefficients(&self, sample_rate: u32) -> [f32; 5] {
+        let cutoff = self.cutoff.max(10.0).min(sample_rate as f32 * 0.49);
+        let w = 2.0 * PI * cutoff / sample_rate as f32;
+        let alpha = w.sin() / (2.0 * self.q.max(0.01));
+        let cos = w.cos();
+        let a0 = 1.0 + alpha;
+        let (b0, b1, b2) = match self.kind {
+            FilterKind::LowPass => ((1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0),
+            FilterKind::HighPass => ((1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0)
+        };
+        [b0 / a0, b1 / a0, b2 / a0, -2.0 * cos / a0, (1.0 - alpha) / a0]
+    }
+}
+
+impl Effect for Filter {
+    fn process(&mut self, buffer: &mut [f32], sample_rate: u32, _sidechain: Option<&[f32]>) {
+        let [b0, b1, b2, a1, a2] = self.coefficients(sample_rate);
+        for frame in buffer.chunks_mut(2) {
+            for (sample, state) in frame.iter_mut().zip(self.state.iter_mut()) {
+                let x = *sample;
+                let y = b0 * x + b1 * state[0] + b2 * state[1] - a1 * state[2] - a2 * state[3];
+                *state = [x, state[0], y, state[2]];
+                *sample = y;
+            }
+        }
+    }
+
+    fn parameters(&self) -> &'static [&'static str] {
+        &["cutoff", "q"]
+    }
+
+    fn parameter(&self, name: &str) -> Option<f32> {
+        match name {
+            "cutoff" => Some(self.cutoff),
+            "q" => Some(self.q),
+            _ => None
+        }
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "cutoff" => self.cutoff = value,
+            "q" => self.q = value,
+            _ => return false
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        self.state = [[0.0; 4]; 2];
+    }
+}
+
+/*********************************
+*** DELAY
+*********************************/
+
+// an echo, each repeat feedback times quieter than the one before
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delay {
+    // in seconds, up to max_time
+    pub time: f32,
+    pub feedback: f32,
+    // 0 dry to 1 only echoes
+    pub mix: f32,
+    max_time: f32,
+    // interleaved stereo ring buffer, allocated for the sample rate it first runs at
+    line: Vec<f32>,
+    sample_rate: u32,
+    position: usize
+}
+
+impl Delay {
+    pub fn new(time: f32, feedback: f32, mix: f32, max_time: f32) -> Delay {
+        Delay {
+            time,
+            feedback,
+            mix,
+            max_time: max_time.max(time),
+            line: vec![],
+            sample_rate: 0,
+            position: 0
+        }
+    }
+}
+
+impl Effect for Delay {
+    fn process(&mut self, buffer: &mut [f32], sample_rate: u32, _sidechain: Option<&[f32]>) {
+        if self.sample_rate != sample_rate {
+            self.sample_rate = sample_rate;
+            self.line = vec![0.0; ((self.max_time * sample_rate as f32) as usize + 1) * 2];
+            self.position = 0;
+        }
+        let frames = self.line.len() / 2;
+        let delay = ((self.time.max(0.0).min(self.max_time) * sample_rate as f32) as usize).max(1).min(frames - 1);
+        let feedback = self.feedback.max(0.0).min(0.99);
+        for frame in buffer.chunks_mut(2) {
+            let read = (self.position + frames - delay) % frames;
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                let echo = self.line[read * 2 + channel];
+                self.line[self.position * 2 + channel] = *sample + echo * feedback;
+                *sample = *sample * (1.0 - self.mix) + echo * self.mix;
+            }
+            self.position = (self.position + 1) % frames;
+        }
+    }
+
+    fn parameters(&self) -> &'static [&'static str] {
+        &["time", "feedback", "mix"]
+    }
+
+    fn parameter(&self, name: &str) -> Option<f32> {
+        match name {
+            "time" => Some(self.time),
+            "feedback" => Some(self.feedback),
+            "mix" => Some(self.mix),
+            _ => None
+        }
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "time" => self.time = value,
+            "feedback" => self.feedback = value,
+            "mix" => self.mix = value,
+            _ => return false
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        for sample in self.line.iter_mut() {
+            *sample = 0.0;
+        }
+    }
+}
+
+/*********************************
+*** REVERB
+*********************************/
+
+// delays in samples at 44100hz, the right channel's are longer by STEREO_SPREAD
+const COMB_TUNING: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNING: [usize; 4] = [556, 441, 341, 225];
+const STEREO_SPREAD: usize = 23;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Comb {
+    buffer: Vec<f32>,
+    position: usize,
+    // the low pass inside the feedback that makes the tail duller over time
+    filtered: f32
+}
+
+impl Comb {
+    fn process(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let output = self.buffer[self.position];
+        self.filtered = output * (1.0 - damping) + self.filtered * damping;
+        self.buffer[self.position] = input + self.filtered * feedback;
+        self.position = (self.position + 1) % self.buffer.len();
+        output
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Allpass {
+    buffer: Vec<f32>,
+    position: usize
+}
+
+impl Allpass {
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.buffer[self.position];
+        self.buffer[self.position] = input + delayed * 0.5;
+        self.position = (self.position + 1) % self.buffer.len();
+        delayed - input
+    }
+}
+
+// schroeder reverb with freeverb's tuning, parallel combs into serial allpasses per channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reverb {
+    // 0 to 1, how long the tail is
+    pub room_size: f32,
+    // 0 to 1, how fast the highs die out
+    pub damping: f32,
+    // 0 dry to 1 only the reverb
+    pub mix: f32,
+    combs: [Vec<Comb>; 2],
+    allpasses: [Vec<Allpass>; 2],
+    sample_rate: u32
+}
+
+impl Reverb {
+    pub fn new(room_size: f32, damping: f32, mix: f32) -> Reverb {
+        Reverb {
+            room_size,
+            damping,
+            mix,
+            combs: [vec![], vec![]],
+            allpasses: [vec![], vec![]],
+            sample_rate: 0
+        }
+    }
+
+    fn build(&mut self, sample_rate: u32) {
+        let scale = sample_rate as f32 / 44100.0;
+        let length = |samples: usize| ((samples as f32 * scale) as usize).max(1);
+        for channel in 0..2 {
+            let spread = channel * STEREO_SPREAD;
+            self.combs[channel] = COMB_TUNING.iter().map(|tuning| Comb {
+                buffer: vec![0.0; length(tuning + spread)],
+                position: 0,
+                filtered: 0.0
+            }).collect();
+            self.allpasses[channel] = ALLPASS_TUNING.iter().map(|tuning| Allpass {
+                buffer: vec![0.0; length(tuning + spread)],
+                position: 0
+            }).collect();
+        }
+        self.sample_rate = sample_rate;
+    }
+}
+
+impl Effect for Reverb {
+    fn process(&mut self, buffer: &mut [f32], sample_rate: u32, _sidechain: Option<&[f32]>) {
+        if self.sample_rate != sample_rate {
+            self.build(sample_rate);
+        }
+        let feedback = 0.7 + self.room_size.max(0.0).min(1.0) * 0.28;
+        let damping = self.damping.max(0.0).min(1.0) * 0.4;
+        let mix = self.mix.max(0.0).min(1.0);
+        for frame in buffer.chunks_mut(2) {
+            // both channels feed both tails, only the tunings differ
+            let input = (frame[0] + frame.get(1).unwrap_or(&frame[0])) * 0.015;
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                let mut wet = 0.0;
+                for comb in self.combs[channel].iter_mut() {
+                    wet += comb.process(input, feedback, damping);
+                }
+                for allpass in self.allpasses[channel].iter_mut() {
+                    wet = allpass.process(wet);
+                }
+                *sample = *sample * (1.0 - mix) + wet * mix;
+            }
+        }
+    }
+
+    fn parameters(&self) -> &'static [&'static str] {
+        &["room_size", "damping", "mix"]
+    }
+
+    fn parameter(&self, name: &str) -> Option<f32> {
+        match name {
+            "room_size" => Some(self.room_size),
+            "damping" => Some(self.damping),
+            "mix" => Some(self.mix),
+            _ => None
+        }
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "room_size" => self.room_size = value,
+            "damping" => self.damping = value,
+            "mix" => self.mix = value,
+            _ => return false
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        self.sample_rate = 0;
+    }
+}
+
+/*********************************
+*** COMPRESSOR
+*********************************/
+
+// turns down whatever goes over the threshold. with an infinite ratio and no attack it's a limiter
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compressor {
+    // in db
+    pub threshold: f32,
+    // 4 lets 1 db through for every 4 over the threshold
+    pub ratio: f32,
+    // in seconds
+    pub attack: f32,
+    pub release: f32,
+    // in db, added after compressing
+    pub makeup: f32,
+    // level the compressor is following, in db
+    envelope: f32
+}
+
+impl Compressor {
+    pub fn new(threshold: f32, ratio: f32, attack: f32, release: f32) -> Compressor {
+        Compressor {
+            threshold,
+            ratio,
+            attack,
+            release,
+            makeup: 0.0,
+            envelope: -120.0
+        }
+    }
+
+    // nothing gets over the threshold
+    pub fn limiter(threshold: f32) -> Compressor {
+        Compressor::new(threshold, std::f32::INFINITY, 0.0, 0.1)
+    }
+
+    // how much the last sample was turned down, in db
+    pub fn gain_reduction(&self) -> f32 {
+        (self.envelope - self.threshold).max(0.0) * (1.0 - 1.0 / self.ratio.max(1.0))
+    }
+}
+
+impl Effect for Compressor {
+    fn process(&mut self, buffer: &mut [f32], sample_rate: u32, _sidechain: Option<&[f32]>) {
+        let attack = smoothing(self.attack, sample_rate);
+        let release = smoothing(self.release, sample_rate);
+        let makeup = db_to_gain(self.makeup);
+        for frame in buffer.chunks_mut(2) {
+            let peak = frame.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+            let level = gain_to_db(peak);
+            let coefficient = if level > self.envelope { attack } else { release };
+            self.envelope = level + (self.envelope - level) * coefficient;
+            let gain = db_to_gain(-self.gain_reduction()) * makeup;
+            for sample in frame.iter_mut() {
+                *sample *= gain;
+            }
+        }
+    }
+
+    fn parameters(&self) -> &'static [&'static str] {
+        &["threshold", "ratio", "attack", "release", "makeup"]
+    }
+
+    fn parameter(&self, name: &str) -> Option<f32> {
+        match name {
+            "threshold" => Some(self.threshold),
+            "ratio" => Some(self.ratio),
+            "attack" => Some(self.attack),
+            "release" => Some(self.release),
+            "makeup" => Some(self.makeup),
+            _ => None
+        }
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "threshold" => self.threshold = value,
+            "ratio" => self.ratio = value,
+            "attack" => self.attack = value,
+            "release" => self.release = value,
+            "makeup" => self.makeup = value,
+            _ => return false
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        self.envelope = -120.0;
+    }
+}
+
+/*********************************
+*** DUCKER
+*********************************/
+
+// turns the bus down while its sidechain bus is louder than the threshold, like music under dialogue
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ducker {
+    // in db, of the sidechain
+    pub threshold: f32,
+    // in db, how far down the bus goes
+    pub depth: f32,
+    // in seconds
+    pub attack: f32,
+    pub release: f32,
+    // gain applied to the last sample
+    gain: f32
+}
+
+impl Ducker {
+    pub fn new(threshold: f32, depth: f32, attack: f32, release: f32) -> Ducker {
+        Ducker {
+            threshold,
+            depth,
+            attack,
+            release,
+            gain: 1.0
+        }
+    }
+
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+}
+
+impl Effect for Ducker {
+    fn process(&mut self, buffer: &mut [f32], sample_rate: u32, sidechain: Option<&[f32]>) {
+        let attack = smoothing(self.attack, sample_rate);
+        let release = smoothing(self.release, sample_rate);
+        let threshold = db_to_gain(self.threshold);
+        let ducked = db_to_gain(-self.depth.abs());
+        for (index, frame) in buffer.chunks_mut(2).enumerate() {
+            let key = sidechain.and_then(|sidechain| sidechain.get(index * 2..index * 2 + 2))
+                .map(|key| key[0].abs().max(key[1].abs())).unwrap_or(0.0);
+            let target = if key > threshold { ducked } else { 1.0 };
+            let coefficient = if target < self.gain { attack } else { release };
+            self.gain = target + (self.gain - target) * coefficient;
+            for sample in frame.iter_mut() {
+                *sample *= self.gain;
+            }
+        }
+    }
+
+    fn parameters(&self) -> &'static [&'static str] {
+        &["threshold", "depth", "attack", "release"]
+    }
+
+    fn parameter(&self, name: &str) -> Option<f32> {
+        match name {
+            "threshold" => Some(self.threshold),
+            "depth" => Some(self.depth),
+            "attack" => Some(self.attack),
+            "release" => Some(self.release),
+            _ => None
+        }
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "threshold" => self.threshold = value,
+            "depth" => self.depth = value,
+            "attack" => self.attack = value,
+            "release" => self.release = value,
+            _ => return false
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        self.gain = 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::mixer::{Mixer, VoiceSettings, MASTER_BUS};
+    use crate::audio::sound::Sound;
+
+    use std::sync::Arc;
+
+    const RATE: u32 = 48000;
+
+    // interleaved stereo, the same on both sides
+    fn sine(frequency: f32, amplitude: f32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .flat_map(|frame| {
+                let sample = (2.0 * PI * frequency * frame as f32 / RATE as f32).sin() * amplitude;
+                vec![sample, sample]
+            })
+            .collect()
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()))
+    }
+
+    // gain of the filter at frequency, measured once it has settled
+    fn response(filter: &mut Filter, frequency: f32) -> f32 {
+        let mut buffer = sine(frequency, 1.0, RATE as usize / 4);
+        filter.process(&mut buffer, RATE, None);
+        peak(&buffer[buffer.len() / 2..])
+    }
+
+    #[test]
+    fn low_pass_keeps_the_lows_and_cuts_the_highs() {
+        let mut filter = Filter::low_pass(1000.0);
+        assert!((response(&mut filter, 100.0) - 1.0).abs() < 0.02);
+        filter.reset();
+        assert!((response(&mut filter, 1000.0) - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.02);
+        filter.reset();
+        // 12 db per octave, so about -40 db a decade up
+        assert!(response(&mut filter, 10000.0) < 0.02);
+    }
+
+    #[test]
+    fn high_pass_keeps_the_highs_and_cuts_the_lows() {
+        let mut filter = Filter::high_pass(1000.0);
+        assert!((response(&mut filter, 10000.0) - 1.0).abs() < 0.02);
+        filter.reset();
+        assert!(response(&mut filter, 100.0) < 0.02);
+    }
+
+    #[test]
+    fn filters_keep_the_channels_apart() {
+        let mut filter = Filter::low_pass(1000.0);
+        let mut buffer: Vec<f32> = (0..1000).flat_map(|_| vec![1.0, 0.0]).collect();
+        filter.process(&mut buffer, RATE, None);
+        assert!((buffer[buffer.len() - 2] - 1.0).abs() < 1e-3);
+        assert_eq!(peak(&buffer.iter().skip(1).step_by(2).cloned().collect::<Vec<f32>>()), 0.0);
+    }
+
+    #[test]
+    fn the_limiter_holds_the_ceiling() {
+        let mut limiter = Compressor::limiter(-6.0);
+        let mut buffer = sine(440.0, 1.0, RATE as usize / 10);
+        limiter.process(&mut buffer, RATE, None);
+        assert!(peak(&buffer) <= db_to_gain(-6.0) + 1e-4);
+        assert!(peak(&buffer) > db_to_gain(-6.0) - 0.01);
+    }
+
+    #[test]
+    fn quiet_signals_pass_the_limiter_untouched() {
+        let mut limiter = Compressor::limiter(-6.0);
+        let input = sine(440.0, 0.1, 4800);
+        let mut buffer = input.clone();
+        limiter.process(&mut buffer, RATE, None);
+        for (out, input) in buffer.iter().zip(input.iter()) {
+            assert!((out - input).abs() < 1e-5);
+        }
+        assert_eq!(limiter.gain_reduction(), 0.0);
+    }
+
+    #[test]
+    fn the_ducker_goes_down_by_its_depth_and_comes_back() {
+        let mut ducker = Ducker::new(-20.0, 12.0, 0.01, 0.05);
+        let frames = RATE as usize / 2;
+        let mut buffer = vec![1.0; frames * 2];
+        ducker.process(&mut buffer, RATE, Some(&vec![0.5; frames * 2]));
+        assert!((ducker.gain() - db_to_gain(-12.0)).abs() < 1e-3);
+        assert!((buffer[buffer.len() - 1] - db_to_gain(-12.0)).abs() < 1e-3);
+
+        // a sidechain under the threshold lets go
+        let mut buffer = vec![1.0; frames * 2];
+        ducker.process(&mut buffer, RATE, Some(&vec![0.01; frames * 2]));
+        assert!((ducker.gain() - 1.0).abs() < 1e-3);
+
+        // no sidechain is silence
+        ducker.reset();
+        let mut buffer = vec![1.0; 20];
+        ducker.process(&mut buffer, RATE, None);
+        assert_eq!(buffer, vec![1.0; 20]);
+    }
+
+    #[test]
+    fn buses_duck_under_their_sidechain() {
+        let mut mixer = Mixer::new(1000);
+        let music = mixer.add_bus("music", MASTER_BUS);
+        let dialogue = mixer.add_bus("dialogue", MASTER_BUS);
+        mixer.add_effect(music, Ducker::new(-20.0, 12.0, 0.001, 0.01));
+        mixer.bus_mut(music).expect("failed to find the music bus").sidechain = Some(dialogue);
+        let tone = |level: f32| Arc::new(Sound::new(1000, 1, vec![level; 1000]));
+        mixer.play(&tone(1.0), VoiceSettings { bus: music, ..VoiceSettings::new() });
+        mixer.play(&tone(0.5), VoiceSettings { bus: dialogue, ..VoiceSettings::new() });
+
+        let mut out = vec![0.0; 400];
+        mixer.mix(&mut out);
+        assert!((out[398] - (db_to_gain(-12.0) + 0.5)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn delays_echo_after_their_time() {
+        let mut delay = Delay::new(0.001, 0.5, 1.0, 0.01);
+        let echo_frames = (0.001 * RATE as f32) as usize;
+        let mut buffer = vec![0.0; echo_frames * 2 * 3];
+        buffer[0] = 1.0;
+        delay.process(&mut buffer, RATE, None);
+        assert_eq!(buffer[echo_frames * 2], 1.0);
+        assert_eq!(buffer[echo_frames * 4], 0.5);
+        assert_eq!(peak(&buffer[..echo_frames * 2]), 0.0);
+    }
+
+    #[test]
+    fn parameters_are_found_by_name() {
+        let mut effects: Vec<Box<dyn Effect>> = vec![
+            Box::new(Filter::low_pass(1000.0)),
+            Box::new(Delay::new(0.1, 0.3, 0.5, 1.0)),
+            Box::new(Reverb::new(0.5, 0.5, 0.3)),
+            Box::new(Compressor::new(-10.0, 4.0, 0.01, 0.1)),
+            Box::new(Ducker::new(-30.0, 10.0, 0.01, 0.2))
+        ];
+        for effect in effects.iter_mut() {
+            for name in effect.parameters() {
+                assert!(effect.set_parameter(name, 0.25));
+                assert_eq!(effect.parameter(name), Some(0.25));
+            }
+            assert!(!effect.set_parameter("missing", 1.0));
+            assert_eq!(effect.parameter("missing"), None);
+        }
+    }
+}
\ No newline at end of file
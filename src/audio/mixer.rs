@@ -0,0 +1,560 @@
+use crate::animation::easing::Easing;
+
+use super::automation::{Automation, MixSnapshot, MixTarget};
+use super::effects::Effect;
+use super::sound::{Sound, SoundStream};
+use super::spatial::{self, Emitter, Listener};
+
+use std::sync::Arc;
+
+// the bus everything ends up in
+pub const MASTER_BUS: usize = 0;
+
+// how a voice plays, can be changed while it does
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoiceSettings {
+    pub volume: f32,
+    // playback rate, 2 is an octave up and twice as fast
+    pub pitch: f32,
+    // -1 left to 1 right, added to the pan from the emitter
+    pub pan: f32,
+    pub looping: bool,
+    // in seconds of the sound, looping voices go back to loop_start when they reach loop_end or, without
+    // one, the end of the sound
+    pub loop_start: f32,
+    pub loop_end: Option<f32>,
+    pub paused: bool,
+    pub bus: usize,
+    // placed in the world, the sound is heard in mono from where the emitter is
+    pub emitter: Option<Emitter>
+}
+
+impl VoiceSettings {
+    pub fn new() -> VoiceSettings {
+        VoiceSettings {
+            volume: 1.0,
+            pitch: 1.0,
+            pan: 0.0,
+            looping: false,
+            loop_start: 0.0,
+            loop_end: None,
+            paused: false,
+            bus: MASTER_BUS,
+            emitter: None
+        }
+    }
+
+    pub fn at(emitter: Emitter) -> VoiceSettings {
+        VoiceSettings {
+            emitter: Some(emitter),
+            ..VoiceSettings::new()
+        }
+    }
+}
+
+impl Default for VoiceSettings {
+    fn default() -> VoiceSettings {
+        VoiceSettings::new()
+    }
+}
+
+// names a playing voice. the slot of a voice that finished is reused under another generation, so old
+// handles don't reach the new voice
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VoiceHandle {
+    index: usize,
+    generation: u32
+}
+
+// voices play into buses, buses play into their parent bus and the master bus into the output. the
+// effects run in order on everything that reaches the bus, before its volume
+pub struct Bus {
+    pub name: String,
+    pub volume: f32,
+    pub muted: bool,
+    // none for the master bus
+    pub parent: Option<usize>,
+    pub effects: Vec<Box<dyn Effect>>,
+    // a bus whose output the effects can listen to, for ducking. buses after this one are heard in the
+    // same block, buses before it a block late
+    pub sidechain: Option<usize>,
+    buffer: Vec<f32>,
+    // the last block, kept for buses that sidechain from this one
+    last: Vec<f32>
+}
+
+impl Bus {
+    fn new(name: &str, parent: Option<usize>) -> Bus {
+        Bus {
+            name: name.to_string(),
+            volume: 1.0,
+            muted: false,
+            parent,
+            effects: vec![],
+            sidechain: None,
+            buffer: vec![],
+            last: vec![]
+        }
+    }
+}
+
+enum Source {
+    Sound(Arc<Sound>),
+    Stream {
+        stream: SoundStream,
+        // decoded samples from the frame start on, the ones played are dropped as it goes
+        buffer: Vec<f32>,
+        start: usize,
+        ended: bool
+    }
+}
+
+struct Voice {
+    settings: VoiceSettings,
+    source: Source,
+    // in source frames, between whole frames the samples are interpolated
+    cursor: f64,
+    // gains the last block ended with, the next one starts from them so changes don't click
+    gains: Option<(f32, f32)>,
+    // output frames to wait before playing
+    delay: usize,
+    // output frames left before the voice stops, if it was told to
+    remaining: Option<usize>,
+    generation: u32
+}
+
+// mixes every playing voice into interleaved stereo at one sample rate. sounds at other rates are
+// resampled, mono sounds play on both sides
+pub struct Mixer {
+    pub sample_rate: u32,
+    pub listener: Listener,
+    // in units per second
+    pub speed_of_sound: f32,
+    voices: Vec<Option<Voice>>,
+    // generation of the next voice in every slot
+    generations: Vec<u32>,
+    buses: Vec<Bus>,
+    automations: Vec<Automation>,
+    // output frames mixed so far
+    frames_mixed: u64
+}
+
+impl Mixer {
+    pub fn new(sample_rate: u32) -> Mixer {
+        Mixer {
+            sample_rate,
+            listener: Listener::new(),
+            speed_of_sound: 343.0,
+            voices: vec![],
+            generations: vec![],
+            buses: vec![Bus::new("master", None)],
+            automations: vec![],
+            frames_mixed: 0
+        }
+    }
+
+    /*********************************
+    *** VOICES
+    *********************************/
+
+    pub fn play(&mut self, sound: &Arc<Sound>, settings: VoiceSettings) -> VoiceHandle {
+        self.add_voice(Source::Sound(sound.clone()), settings)
+    }
+
+    // decodes the stream as it plays, looping streams start over from the file
+    pub fn play_stream(&mut self, stream: SoundStream, settings: VoiceSettings) -> VoiceHandle {
+        self.add_voice(Source::Stream { stream, buffer: vec![], start: 0, ended: false }, settings)
+    }
+
+    // holds the voice silent for seconds before it starts. set right after playing, voices started together
+    // this way line up to the sample
+    pub fn set_delay(&mut self, handle: VoiceHandle, seconds: f32) {
+        let frames = (seconds.max(0.0) * self.sample_rate as f32).round() as usize;
+        if let Some(voice) = self.voice_mut(handle) {
+            voice.delay = frames;
+        }
+    }
+
+    // stops the voice after seconds of mixing, to the sample
+    pub fn stop_after(&mut self, handle: VoiceHandle, seconds: f32) {
+        let frames = (seconds.max(0.0) * self.sample_rate as f32).round() as usize;
+        if let Some(voice) = self.voice_mut(handle) {
+            voice.remaining = Some(frames);
+        }
+    }
+
+    pub fn stop(&mut self, handle: VoiceHandle) {
+        if self.voice(handle).is_some() {
+            self.voices[handle.index] = None;
+        }
+    }
+
+    pub fn stop_all(&mut self) {
+        for voice in self.voices.iter_mut() {
+            *voice = None;
+        }
+    }
+
+    // false once the voice has finished or was stopped
+    pub fn is_playing(&self, handle: VoiceHandle) -> bool {
+        self.voice(handle).is_some()
+    }
+
+    pub fn settings(&self, handle: VoiceHandle) -> Option<&VoiceSettings> {
+        self.voice(handle).map(|voice| &voice.settings)
+    }
+
+    pub fn settings_mut(&mut self, handle: VoiceHandle) -> Option<&mut VoiceSettings> {
+        self.voice_mut(handle).map(|voice| &mut voice.settings)
+    }
+
+    // in seconds of the sound, 0 while the voice is delayed
+    pub fn position(&self, handle: VoiceHandle) -> Option<f32> {
+        self.voice(handle).map(|voice| {
+            let sample_rate = match &voice.source {
+                Source::Sound(sound) => sound.sample_rate,
+                Source::Stream { stream, .. } => stream.sample_rate
+            };
+            (voice.cursor / sample_rate as f64) as f32
+        })
+    }
+
+    // seconds of output mixed so far, the clock music and scheduled changes are timed against
+    pub fn time(&self) -> f64 {
+        self.frames_mixed as f64 / self.sample_rate as f64
+    }
+
+    pub fn voice_count(&self) -> usize {
+        self.voices.iter().filter(|voice| voice.is_some()).count()
+    }
+
+    fn voice(&self, handle: VoiceHandle) -> Option<&Voice> {
+        match self.voices.get(handle.index) {
+            Some(Some(voice)) if voice.generation == handle.generation => Some(voice),
+            _ => None
+        }
+    }
+
+    fn voice_mut(&mut self, handle: VoiceHandle) -> Option<&mut Voice> {
+        match self.voices.get_mut(handle.index) {
+            Some(Some(voice)) if voice.generation == handle.generation => Some(voice),
+            _ => None
+        }
+    }
+
+    fn add_voice(&mut self, source: Source, settings: VoiceSettings) -> VoiceHandle {
+        let index = match self.voices.iter().position(|voice| voice.is_none()) {
+            Some(index) => index,
+            None => {
+                self.voices.push(None);
+                self.generations.push(0);
+                self.voices.len() - 1
+            }
+        };
+        let generation = self.generations[index];
+        self.generations[index] = generation.wrapping_add(1);
+        self.voices[index] = Some(Voice {
+            settings,
+            source,
+            cursor: 0.0,
+            gains: None,
+            delay: 0,
+            remaining: None,
+            generation
+        });
+        VoiceHandle { index, generation }
+    }
+
+    /*********************************
+    *** BUSES
+    *********************************/
+
+    // returns the index of the bus, parents always come before their children
+    pub fn add_bus(&mut self, name: &str, parent: usize) -> usize {
+        let parent = if parent < self.buses.len() { parent } else { MASTER_BUS };
+        self.buses.push(Bus::new(name, Some(parent)));
+        self.buses.len() - 1
+    }
+
+    // returns the index of the effect on the bus, none if there's no such bus
+    pub fn add_effect<E: Effect + 'static>(&mut self, bus: usize, effect: E) -> Option<usize> {
+        let bus = self.buses.get_mut(bus)?;
+        bus.effects.push(Box::new(effect));
+        Some(bus.effects.len() - 1)
+    }
+
+    pub fn bus(&self, index: usize) -> Option<&Bus> {
+        self.buses.get(index)
+    }
+
+    pub fn bus_mut(&mut self, index: usize) -> Option<&mut Bus> {
+        self.buses.get_mut(index)
+    }
+
+    pub fn find_bus(&self, name: &str) -> Option<usize> {
+        self.buses.iter().position(|bus| bus.name == name)
+    }
+
+    /*********************************
+    *** AUTOMATION
+    *********************************/
+
+    pub fn value(&self, target: &MixTarget) -> Option<f32> {
+        match target {
+            MixTarget::Volume { bus } => self.buses.get(*bus).map(|bus| bus.volume),
+            MixTarget::Parameter { bus, effect, name } => {
+                self.buses.get(*bus)?.effects.get(*effect)?.parameter(name)
+            }
+        }
+    }
+
+    // false if the target doesn't exist
+    pub fn set_value(&mut self, target: &MixTarget, value: f32) -> bool {
+        match target {
+            MixTarget::Volume { bus } => match self.buses.get_mut(*bus) {
+                Some(bus) => {
+                    bus.volume = value;
+                    true
+                }
+                None => false
+            },
+            MixTarget::Parameter { bus, effect, name } => {
+                match self.buses.get_mut(*bus).and_then(|bus| bus.effects.get_mut(*effect)) {
+                    Some(effect) => effect.set_parameter(name, value),
+                    None => false
+                }
+            }
+        }
+    }
+
+    // moves the target from its current value to value over duration seconds of mixing, replacing any
+    // automation it already had. false if the target doesn't exist
+    pub fn automate(&mut self, target: MixTarget, value: f32, duration: f32, easing: Easing) -> bool {
+        let from = match self.value(&target) {
+            Some(from) => from,
+            None => return false
+        };
+        self.automations.retain(|automation| automation.target != target);
+        self.automations.push(Automation::new(target, from, value, duration, easing));
+        true
+    }
+
+    pub fn is_automating(&self, target: &MixTarget) -> bool {
+        self.automations.iter().any(|automation| automation.target == *target)
+    }
+
+    // sets every value of the snapshot right away
+    pub fn set_snapshot(&mut self, snapshot: &MixSnapshot) {
+        for (target, value) in snapshot.values.iter() {
+            self.automations.retain(|automation| automation.target != *target);
+            self.set_value(target, *value);
+        }
+    }
+
+    // automates every value of the snapshot
+    pub fn transition(&mut self, snapshot: &MixSnapshot, duration: f32, easing: Easing) {
+        for (target, value) in snapshot.values.iter() {
+            self.automate(target.clone(), *value, duration, easing);
+        }
+    }
+
+    // sets the blend of two snapshots, t 0 is from and 1 is to. called as t changes, like with the
+    // camera's depth under water
+    pub fn blend_snapshots(&mut self, from: &MixSnapshot, to: &MixSnapshot, t: f32) {
+        self.set_snapshot(&from.blend(to, t));
+    }
+
+    fn update_automations(&mut self, delta: f32) {
+        let mut automations = std::mem::replace(&mut self.automations, vec![]);
+        for automation in automations.iter_mut() {
+            let value = automation.update(delta);
+            self.set_value(&automation.target, value);
+        }
+        automations.retain(|automation| !automation.is_finished());
+        self.automations = automations;
+    }
+
+    /*********************************
+    *** MIXING
+    *********************************/
+
+    // fills out with interleaved stereo, voices that finish are removed
+    pub fn mix(&mut self, out: &mut [f32]) {
+        let frames = out.len() / 2;
+        self.update_automations(frames as f32 / self.sample_rate as f32);
+        self.frames_mixed += frames as u64;
+        for bus in self.buses.iter_mut() {
+            bus.buffer.clear();
+            bus.buffer.resize(frames * 2, 0.0);
+        }
+
+        for i in 0..self.voices.len() {
+            let finished = match self.voices[i].as_mut() {
+                Some(voice) => {
+                    let bus = if voice.settings.bus < self.buses.len() { voice.settings.bus } else { MASTER_BUS };
+                    mix_voice(voice, &mut self.buses[bus].buffer, self.sample_rate, &self.listener, self.speed_of_sound)
+                }
+                None => false
+            };
+            if finished {
+                self.voices[i] = None;
+            }
+        }
+
+        // children come after their parents, so going backwards every bus is complete before it's added
+        for index in (0..self.buses.len()).rev() {
+            let mut buffer = std::mem::replace(&mut self.buses[index].buffer, vec![]);
+            let mut effects = std::mem::replace(&mut self.buses[index].effects, vec![]);
+            let sidechain = match self.buses[index].sidechain {
+                Some(source) if source > index => self.buses.get(source).map(|bus| &bus.buffer[..]),
+                Some(source) if source < index => self.buses.get(source).map(|bus| &bus.last[..]),
+                _ => None
+            };
+            let sidechain = sidechain.filter(|sidechain| sidechain.len() == buffer.len());
+            for effect in effects.iter_mut() {
+                effect.process(&mut buffer, self.sample_rate, sidechain);
+            }
+            self.buses[index].effects = effects;
+
+            if self.buses.iter().any(|bus| bus.sidechain == Some(index)) {
+                let last = &mut self.buses[index].last;
+                last.clear();
+                last.extend_from_slice(&buffer);
+            }
+
+            let bus = &self.buses[index];
+            let gain = if bus.muted { 0.0 } else { bus.volume };
+            if let Some(parent) = bus.parent {
+                if gain > 0.0 {
+                    for (target, sample) in self.buses[parent].buffer.iter_mut().zip(buffer.iter()) {
+                        *target += sample * gain;
+                    }
+                }
+            }
+            self.buses[index].buffer = buffer;
+        }
+
+        let master = &self.buses[MASTER_BUS];
+        let gain = if master.muted { 0.0 } else { master.volume };
+        for (target, sample) in out.iter_mut().zip(master.buffer.iter()) {
+            *target = sample * gain;
+        }
+        for target in out[frames * 2..].iter_mut() {
+            *target = 0.0;
+        }
+    }
+}
+
+// adds a block of the voice to a bus buffer, returns whether the voice has finished
+fn mix_voice(voice: &mut Voice, buffer: &mut [f32], sample_rate: u32, listener: &Listener, speed_of_sound: f32) -> bool {
+    if voice.settings.paused {
+        return false;
+    }
+    let frames = buffer.len() / 2;
+    let (source_rate, channels) = match &voice.source {
+        Source::Sound(sound) => (sound.sample_rate, sound.channels as usize),
+        Source::Stream { stream, .. } => (stream.sample_rate, stream.channels as usize)
+    };
+
+    let settings = &voice.settings;
+    let spatialized = settings.emitter.as_ref().map(|emitter| spatial::spatialize(emitter, listener, speed_of_sound));
+    let (gain, pan, pitch) = match &spatialized {
+        Some(s) => (settings.volume * s.gain, settings.pan + s.pan, settings.pitch * s.pitch),
+        None => (settings.volume, settings.pan, settings.pitch)
+    };
+    let (left, right) = spatial::pan_gains(pan);
+    let target = (left * gain, right * gain);
+    let start = voice.gains.unwrap_or(target);
+    voice.gains = Some(target);
+    let mono = spatialized.is_some() || channels == 1;
+    let step = (pitch.max(0.0) as f64) * source_rate as f64 / sample_rate as f64;
+
+    // the loop in source frames, a stream's end isn't known until it's reached
+    let looping = settings.looping;
+    let loop_start = (settings.loop_start.max(0.0) as f64 * source_rate as f64) as usize;
+    let loop_end = match (settings.loop_end, &voice.source) {
+        (Some(end), _) => Some((end as f64 * source_rate as f64) as usize),
+        (None, Source::Sound(sound)) => Some(sound.samples.len() / channels),
+        (None, Source::Stream { .. }) => None
+    }.filter(|end| *end > loop_start);
+
+    for frame in 0..frames {
+        if voice.delay > 0 {
+            voice.delay -= 1;
+            continue;
+        }
+        match voice.remaining {
+            Some(0) => return true,
+            Some(remaining) => voice.remaining = Some(remaining - 1),
+            None => {}
+        }
+
+        let index = voice.cursor.floor() as usize;
+        let current = match frame_at(&mut voice.source, index, channels) {
+            Some(current) => current,
+            // the end of a stream without a loop end
+            None if looping && index > loop_start => {
+                voice.cursor = loop_start as f64;
+                seek(&mut voice.source, loop_start);
+                match frame_at(&mut voice.source, loop_start, channels) {
+                    Some(current) => current,
+                    None => return true
+                }
+            }
+            None => return true
+        };
+        let index = voice.cursor.floor() as usize;
+        let next = if looping && loop_end == Some(index + 1) { loop_start } else { index + 1 };
+        let next = frame_at(&mut voice.source, next, channels).unwrap_or(current);
+        let t = voice.cursor.fract() as f32;
+        let (l, r) = (current.0 + (next.0 - current.0) * t, current.1 + (next.1 - current.1) * t);
+
+        let (l, r) = if mono { ((l + r) * 0.5, (l + r) * 0.5) } else { (l, r) };
+        let t = frame as f32 / frames as f32;
+        buffer[frame * 2] += l * (start.0 + (target.0 - start.0) * t);
+        buffer[frame * 2 + 1] += r * (start.1 + (target.1 - start.1) * t);
+
+        voice.cursor += step;
+        if let (true, Some(end)) = (looping, loop_end) {
+            if voice.cursor >= end as f64 {
+                voice.cursor = loop_start as f64 + (voice.cursor - end as f64) % (end - loop_start) as f64;
+                seek(&mut voice.source, voice.cursor as usize);
+            }
+        }
+    }
+    false
+}
+
+// moves a stream back to a frame it has dropped already, sounds are all there
+fn seek(source: &mut Source, frame: usize) {
+    if let Source::Stream { stream, buffer, start, ended } = source {
+        if frame < *start {
+            *ended = stream.seek(frame, buffer).is_err();
+            *start = frame;
+        }
+    }
+}
+
+// a frame with mono sounds on both sides. streams decode up to it and drop what's well behind it, none
+// past the end or before what a stream still has
+fn frame_at(source: &mut Source, index: usize, channels: usize) -> Option<(f32, f32)> {
+    let samples = match source {
+        Source::Sound(sound) => sound.samples.get(index * channels..(index + 1) * channels)?,
+        Source::Stream { stream, buffer, start, ended } => {
+            if index < *start {
+                return None;
+            }
+            while buffer.len() / channels <= index - *start && !*ended {
+                // a stream that fails to decode ends there
+                *ended = !stream.read(buffer).unwrap_or(false);
+            }
+            let played = index - *start;
+            if played > 4096 {
+                buffer.drain(..played * channels);
+                *start = index;
+            }
+            buffer.get((index - *start) * channels..(index - *start + 1) * channels)?
+        }
+    };
+    Some((samples[0], samples[channels.min(2) - 1]))
+}
\ No newline at end of file
@@ -0,0 +1,7 @@
+pub mod automation;
+pub mod effects;
+pub mod mixer;
+pub mod music;
+pub mod output;
+pub mod sound;
+pub mod spatial;
\ No newline at end of file
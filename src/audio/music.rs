@@ -0,0 +1,529 @@
+use crate::animation::easing::Easing;
+
+use super::automation::{MixSnapshot, MixTarget};
+use super::mixer::{Mixer, VoiceHandle, VoiceSettings, MASTER_BUS};
+use super::sound::{AudioError, SoundStream};
+
+use std::path::{Path, PathBuf};
+
+// one stem of a track, heard depending on the intensity. it's silent up to enter and at full volume
+// from full on
+#[derive(Debug, Clone, PartialEq)]
+pub struct MusicLayer {
+    pub path: PathBuf,
+    pub enter: f32,
+    pub full: f32
+}
+
+impl MusicLayer {
+    pub fn new<P: AsRef<Path>>(path: P, enter: f32, full: f32) -> MusicLayer {
+        MusicLayer {
+            path: path.as_ref().to_path_buf(),
+            enter,
+            full
+        }
+    }
+
+    pub fn weight(&self, intensity: f32) -> f32 {
+        if self.full <= self.enter {
+            return if intensity >= self.enter { 1.0 } else { 0.0 };
+        }
+        ((intensity - self.enter) / (self.full - self.enter)).max(0.0).min(1.0)
+    }
+}
+
+// a piece of music made of stems played in sync. the stems are streamed and should share a sample rate
+// and length
+#[derive(Debug, Clone, PartialEq)]
+pub struct MusicTrack {
+    pub name: String,
+    pub layers: Vec<MusicLayer>,
+    pub bpm: f32,
+    pub beats_per_bar: u32,
+    // in seconds, where the first bar starts
+    pub offset: f32,
+    pub looping: bool,
+    // in seconds, so an intro plays once and the rest loops
+    pub loop_start: f32,
+    pub loop_end: Option<f32>
+}
+
+impl MusicTrack {
+    // a looping track of one stem always heard
+    pub fn new<P: AsRef<Path>>(name: &str, path: P, bpm: f32, beats_per_bar: u32) -> MusicTrack {
+        MusicTrack {
+            name: name.to_string(),
+            layers: vec![MusicLayer::new(path, 0.0, 0.0)],
+            bpm,
+            beats_per_bar,
+            offset: 0.0,
+            looping: true,
+            loop_start: 0.0,
+            loop_end: None
+        }
+    }
+
+    pub fn add_layer<P: AsRef<Path>>(&mut self, path: P, enter: f32, full: f32) {
+        self.layers.push(MusicLayer::new(path, enter, full));
+    }
+
+    // in seconds
+    pub fn beat_length(&self) -> f32 {
+        60.0 / self.bpm.max(1.0)
+    }
+
+    pub fn bar_length(&self) -> f32 {
+        self.beat_length() * self.beats_per_bar.max(1) as f32
+    }
+}
+
+// when a change of music happens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MusicSync {
+    Immediate,
+    NextBeat,
+    NextBar,
+    // when the current track reaches its loop end, or its end
+    LoopEnd
+}
+
+// a crossfade waiting for its sync point, handed to the mixer's automation once the time comes
+#[derive(Debug, Clone, PartialEq)]
+struct PendingFade {
+    // on the mixer's clock
+    start: f64,
+    duration: f32,
+    to: f32
+}
+
+// the buses a track plays through: one for the crossfade and one under it for every layer, whose volume
+// follows the intensity. the mixer's automation moves both, and they're reused once the track is done
+#[derive(Debug, Clone, PartialEq)]
+struct TrackBuses {
+    track: usize,
+    layers: Vec<usize>
+}
+
+struct PlayingTrack {
+    track: MusicTrack,
+    // one per layer
+    voices: Vec<VoiceHandle>,
+    buses: TrackBuses,
+    // the weight every layer was last automated to
+    weights: Vec<f32>,
+    fade: Option<PendingFade>
+}
+
+// plays music on a bus, crossfading from track to track in time with the beat and mixing stems by
+// intensity. needs update every frame
+pub struct MusicPlayer {
+    pub bus: usize,
+    pub volume: f32,
+    // what the layers follow, 0 calm to 1 intense
+    pub intensity: f32,
+    // seconds layers take to fade in or out fully when the intensity changes
+    pub layer_fade: f32,
+    current: Option<PlayingTrack>,
+    // tracks fading out, stopped by the mixer when they're done
+    outgoing: Vec<PlayingTrack>,
+    free_buses: Vec<TrackBuses>
+}
+
+impl MusicPlayer {
+    pub fn new(bus: usize) -> MusicPlayer {
+        MusicPlayer {
+            bus,
+            volume: 1.0,
+            intensity: 0.0,
+            layer_fade: 2.0,
+            current: None,
+            outgoing: vec![],
+            free_buses: vec![]
+        }
+    }
+
+    pub fn current(&self) -> Option<&MusicTrack> {
+        self.current.as_ref().map(|playing| &playing.track)
+    }
+
+    // seconds into the current track
+    pub fn position(&self, mixer: &Mixer) -> Option<f32> {
+        self.current.as_ref().and_then(|playing| mixer.position(playing.voices[0]))
+    }
+
+    // beats since the first bar of the current track, the fraction is how far into the beat it is
+    pub fn beat(&self, mixer: &Mixer) -> Option<f32> {
+        let track = self.current()?;
+        Some((self.position(mixer)? - track.offset) / track.beat_length())
+    }
+
+    // switches to the track, fading the current one out and the new one in over crossfade seconds from
+    // the moment sync picks
+    pub fn play(&mut self, mixer: &mut Mixer, track: &MusicTrack, crossfade: f32, sync: MusicSync) -> Result<(), AudioError> {
+        let mut streams = vec![];
+        for layer in track.layers.iter() {
+            streams.push(SoundStream::open(&layer.path)?);
+        }
+
+        let delay = self.time_to(mixer, sync);
+        let start = mixer.time() + delay as f64;
+        self.fade_out_current(mixer, delay, crossfade);
+
+        // the voices are silent until they start, without a crossfade they start at full volume
+        let buses = self.take_buses(mixer, track.layers.len());
+        let weights: Vec<f32> = track.layers.iter().map(|layer| layer.weight(self.intensity)).collect();
+        let mut volumes = MixSnapshot::new();
+        volumes.set(MixTarget::Volume { bus: buses.track }, if crossfade > 0.0 { 0.0 } else { 1.0 });
+        for (bus, weight) in buses.layers.iter().zip(weights.iter()) {
+            volumes.set(MixTarget::Volume { bus: *bus }, *weight);
+        }
+        mixer.set_snapshot(&volumes);
+
+        let mut voices = vec![];
+        for (stream, bus) in streams.into_iter().zip(buses.layers.iter()) {
+            let settings = VoiceSettings {
+                volume: self.volume,
+                looping: track.looping,
+                loop_start: track.loop_start,
+                loop_end: track.loop_end,
+                bus: *bus,
+                ..VoiceSettings::new()
+            };
+            let voice = mixer.play_stream(stream, settings);
+            mixer.set_delay(voice, delay);
+            voices.push(voice);
+        }
+        self.current = Some(PlayingTrack {
+            track: track.clone(),
+            voices,
+            buses,
+            weights,
+            fade: if crossfade > 0.0 { Some(PendingFade { start, duration: crossfade, to: 1.0 }) } else { None }
+        });
+        self.start_fades(mixer);
+        Ok(())
+    }
+
+    pub fn stop(&mut self, mixer: &mut Mixer, fade: f32, sync: MusicSync) {
+        let delay = self.time_to(mixer, sync);
+        self.fade_out_current(mixer, delay, fade);
+        self.start_fades(mixer);
+    }
+
+    pub fn update(&mut self, mixer: &mut Mixer) {
+        self.start_fades(mixer);
+
+        let (intensity, layer_fade) = (self.intensity, self.layer_fade.max(0.0));
+        if let Some(playing) = self.current.as_mut() {
+            let layers = playing.weights.iter_mut().zip(playing.track.layers.iter()).zip(playing.buses.layers.iter());
+            for ((weight, layer), bus) in layers {
+                let target = layer.weight(intensity);
+                if target != *weight {
+                    // at the same speed however far the layer has to go
+                    let volume = MixTarget::Volume { bus: *bus };
+                    let from = mixer.value(&volume).unwrap_or(*weight);
+                    mixer.automate(volume, target, layer_fade * (target - from).abs(), Easing::Linear);
+                    *weight = target;
+                }
+            }
+        }
+
+        // a track that doesn't loop ends by itself
+        let ended = self.current.as_ref().map(|playing| playing.voices.iter().all(|voice| !mixer.is_playing(*voice)));
+        if ended == Some(true) {
+            let playing = self.current.take().expect("failed to take the current track");
+            self.free_buses.push(playing.buses);
+        }
+        let (done, outgoing): (Vec<PlayingTrack>, Vec<PlayingTrack>) = std::mem::replace(&mut self.outgoing, vec![])
+            .into_iter()
+            .partition(|playing| playing.voices.iter().all(|voice| !mixer.is_playing(*voice)));
+        self.outgoing = outgoing;
+        self.free_buses.extend(done.into_iter().map(|playing| playing.buses));
+
+        for playing in self.current.iter().chain(self.outgoing.iter()) {
+            for voice in playing.voices.iter() {
+                if let Some(settings) = mixer.settings_mut(*voice) {
+                    settings.volume = self.volume;
+                }
+            }
+        }
+    }
+
+    // seconds until the current track gets to what sync waits for
+    fn time_to(&self, mixer: &Mixer, sync: MusicSync) -> f32 {
+        let (track, position) = match (self.current(), self.position(mixer)) {
+            (Some(track), Some(position)) => (track, position),
+            _ => return 0.0
+        };
+        let length = match sync {
+            MusicSync::Immediate => return 0.0,
+            MusicSync::NextBeat => track.beat_length(),
+            MusicSync::NextBar => track.bar_length(),
+            MusicSync::LoopEnd => {
+                // a stream's length isn't known, without a loop end this waits for the next bar
+                return match track.loop_end {
+                    Some(end) if end > position => end - position,
+                    _ => self.time_to(mixer, MusicSync::NextBar)
+                };
+            }
+        };
+        let mut next = track.offset + ((position - track.offset) / length).ceil() * length;
+        // loop ends are taken to be on a bar line
+        if let Some(end) = track.loop_end {
+            next = next.min(end);
+        }
+        (next - position).max(0.0)
+    }
+
+    fn fade_out_current(&mut self, mixer: &mut Mixer, delay: f32, duration: f32) {
+        if let Some(mut playing) = self.current.take() {
+            // replaces a fade in that hasn't started yet
+            playing.fade = Some(PendingFade { start: mixer.time() + delay as f64, duration, to: 0.0 });
+            for voice in playing.voices.iter() {
+                mixer.stop_after(*voice, delay + duration);
+            }
+            self.outgoing.push(playing);
+        }
+    }
+
+    // automates the crossfades whose time has come. one that starts between updates is shortened by how
+    // late it is, so it still ends on time
+    fn start_fades(&mut self, mixer: &mut Mixer) {
+        let time = mixer.time();
+        for playing in self.current.iter_mut().chain(self.outgoing.iter_mut()) {
+            let ready = playing.fade.as_ref().map(|fade| fade.start <= time).unwrap_or(false);
+            if ready {
+                let fade = playing.fade.take().expect("failed to take the fade");
+                let duration = (fade.duration - (time - fade.start) as f32).max(0.0);
+                mixer.automate(MixTarget::Volume { bus: playing.buses.track }, fade.to, duration, Easing::Linear);
+            }
+        }
+    }
+
+    // buses of a finished track under the same bus, or new ones
+    fn take_buses(&mut self, mixer: &mut Mixer, layers: usize) -> TrackBuses {
+        let bus = self.bus;
+        let free = self.free_buses.iter().position(|buses| mixer.bus(buses.track).and_then(|track| track.parent) == Some(bus));
+        let mut buses = match free {
+            Some(index) => self.free_buses.swap_remove(index),
+            None => TrackBuses { track: mixer.add_bus("music track", bus), layers: vec![] }
+        };
+        while buses.layers.len() < layers {
+            buses.layers.push(mixer.add_bus("music layer", buses.track));
+        }
+        buses
+    }
+}
+
+impl Default for MusicPlayer {
+    fn default() -> MusicPlayer {
+        MusicPlayer::new(MASTER_BUS)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hound::{SampleFormat, WavSpec, WavWriter};
+
+    // few frames a second keeps the times easy to follow
+    const RATE: u32 = 100;
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-3, "{} != {}", a, b);
+    }
+
+    // a mono tone of the given length in the temp dir, names must differ between tests running at once
+    fn stem(name: &str, seconds: f32) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("music_test_{}_{}.wav", std::process::id(), name));
+        let spec = WavSpec { channels: 1, sample_rate: RATE, bits_per_sample: 32, sample_format: SampleFormat::Float };
+        let mut writer = WavWriter::create(&path, spec).expect("failed to create the stem");
+        for _ in 0..(seconds * RATE as f32) as usize {
+            writer.write_sample(0.5f32).expect("failed to write the stem");
+        }
+        writer.finalize().expect("failed to finish the stem");
+        path
+    }
+
+    // mixes in tenths of a second, updating the player in between like a game would every frame
+    fn run(mixer: &mut Mixer, player: &mut MusicPlayer, seconds: f32) {
+        for _ in 0..(seconds * 10.0).round() as usize {
+            let mut out = vec![0.0; RATE as usize / 10 * 2];
+            mixer.mix(&mut out);
+            player.update(mixer);
+        }
+    }
+
+    fn volume(mixer: &Mixer, bus: usize) -> f32 {
+        mixer.value(&MixTarget::Volume { bus }).expect("failed to find the bus")
+    }
+
+    fn bus_count(mixer: &Mixer) -> usize {
+        (0..).take_while(|bus| mixer.bus(*bus).is_some()).count()
+    }
+
+    fn track_bus(player: &MusicPlayer) -> usize {
+        player.current.as_ref().expect("failed to find the current track").buses.track
+    }
+
+    #[test]
+    fn layers_fade_in_between_enter_and_full() {
+        let drums = MusicLayer::new("drums.ogg", 0.25, 0.75);
+        assert_eq!(drums.weight(0.0), 0.0);
+        assert_eq!(drums.weight(0.25), 0.0);
+        assert_near(drums.weight(0.5), 0.5);
+        assert_eq!(drums.weight(1.0), 1.0);
+
+        let switch = MusicLayer::new("choir.ogg", 0.5, 0.5);
+        assert_eq!(switch.weight(0.49), 0.0);
+        assert_eq!(switch.weight(0.5), 1.0);
+    }
+
+    #[test]
+    fn changes_wait_for_the_next_beat_or_bar() {
+        let mut mixer = Mixer::new(RATE);
+        let mut player = MusicPlayer::new(MASTER_BUS);
+        // a beat a second, four to the bar
+        let mut track = MusicTrack::new("march", stem("march", 20.0), 60.0, 4);
+        assert_eq!(player.time_to(&mixer, MusicSync::NextBar), 0.0);
+
+        player.play(&mut mixer, &track, 0.0, MusicSync::Immediate).expect("failed to play");
+        run(&mut mixer, &mut player, 1.5);
+        assert_near(player.position(&mixer).expect("failed to find the position"), 1.5);
+        assert_near(player.beat(&mixer).expect("failed to find the beat"), 1.5);
+        assert_eq!(player.time_to(&mixer, MusicSync::Immediate), 0.0);
+        assert_near(player.time_to(&mixer, MusicSync::NextBeat), 0.5);
+        assert_near(player.time_to(&mixer, MusicSync::NextBar), 2.5);
+        // no loop end, the bar it is
+        assert_near(player.time_to(&mixer, MusicSync::LoopEnd), 2.5);
+
+        // the first bar starting late moves the grid with it
+        track.offset = 0.25;
+        player.current.as_mut().expect("failed to find the current track").track = track.clone();
+        assert_near(player.time_to(&mixer, MusicSync::NextBeat), 0.75);
+        assert_near(player.time_to(&mixer, MusicSync::NextBar), 2.75);
+
+        // and loop ends cut bars short
+        track.loop_end = Some(3.0);
+        player.current.as_mut().expect("failed to find the current track").track = track;
+        assert_near(player.time_to(&mixer, MusicSync::NextBar), 1.5);
+        assert_near(player.time_to(&mixer, MusicSync::LoopEnd), 1.5);
+    }
+
+    #[test]
+    fn tracks_loop_between_their_loop_points() {
+        let mut mixer = Mixer::new(RATE);
+        let mut player = MusicPlayer::new(MASTER_BUS);
+        let mut track = MusicTrack::new("battle", stem("battle", 3.0), 120.0, 4);
+        track.loop_start = 1.0;
+        track.loop_end = Some(2.0);
+        player.play(&mut mixer, &track, 0.0, MusicSync::Immediate).expect("failed to play");
+
+        // the intro once, then round the loop
+        run(&mut mixer, &mut player, 2.5);
+        assert_near(player.position(&mixer).expect("failed to find the position"), 1.5);
+        run(&mut mixer, &mut player, 5.0);
+        assert_near(player.position(&mixer).expect("failed to find the position"), 1.5);
+
+        // without looping it plays to the end and is gone
+        let mut once = MusicTrack::new("sting", stem("sting", 1.0), 120.0, 4);
+        once.looping = false;
+        player.play(&mut mixer, &once, 0.0, MusicSync::Immediate).expect("failed to play");
+        run(&mut mixer, &mut player, 1.5);
+        assert!(player.current().is_none());
+    }
+
+    #[test]
+    fn crossfades_automate_the_track_buses() {
+        let mut mixer = Mixer::new(RATE);
+        let mut player = MusicPlayer::new(MASTER_BUS);
+        let calm = MusicTrack::new("calm", stem("calm", 20.0), 60.0, 4);
+        let tense = MusicTrack::new("tense", stem("tense", 20.0), 60.0, 4);
+
+        player.play(&mut mixer, &calm, 0.0, MusicSync::Immediate).expect("failed to play");
+        let calm_bus = track_bus(&player);
+        assert_eq!(volume(&mixer, calm_bus), 1.0);
+        run(&mut mixer, &mut player, 0.5);
+
+        player.play(&mut mixer, &tense, 1.0, MusicSync::Immediate).expect("failed to play");
+        let tense_bus = track_bus(&player);
+        assert_ne!(calm_bus, tense_bus);
+        assert!(mixer.is_automating(&MixTarget::Volume { bus: calm_bus }));
+        assert!(mixer.is_automating(&MixTarget::Volume { bus: tense_bus }));
+        run(&mut mixer, &mut player, 0.5);
+        assert_near(volume(&mixer, calm_bus), 0.5);
+        assert_near(volume(&mixer, tense_bus), 0.5);
+
+        run(&mut mixer, &mut player, 1.0);
+        assert_eq!(volume(&mixer, tense_bus), 1.0);
+        assert_eq!(player.current().map(|track| track.name.as_str()), Some("tense"));
+        assert!(player.outgoing.is_empty());
+
+        // the calm track's buses are free again and picked up by the next one
+        let buses = bus_count(&mixer);
+        player.play(&mut mixer, &calm, 0.0, MusicSync::Immediate).expect("failed to play");
+        assert_eq!(track_bus(&player), calm_bus);
+        assert_eq!(bus_count(&mixer), buses);
+    }
+
+    #[test]
+    fn synced_crossfades_start_on_the_bar() {
+        let mut mixer = Mixer::new(RATE);
+        let mut player = MusicPlayer::new(MASTER_BUS);
+        let calm = MusicTrack::new("calm bar", stem("calm_bar", 20.0), 60.0, 4);
+        let tense = MusicTrack::new("tense bar", stem("tense_bar", 20.0), 60.0, 4);
+        player.play(&mut mixer, &calm, 0.0, MusicSync::Immediate).expect("failed to play");
+        let calm_bus = track_bus(&player);
+        run(&mut mixer, &mut player, 1.5);
+
+        player.play(&mut mixer, &tense, 1.0, MusicSync::NextBar).expect("failed to play");
+        let tense_bus = track_bus(&player);
+        assert!(!mixer.is_automating(&MixTarget::Volume { bus: calm_bus }));
+        run(&mut mixer, &mut player, 2.0);
+        assert_eq!(volume(&mixer, calm_bus), 1.0);
+        assert_eq!(volume(&mixer, tense_bus), 0.0);
+
+        // the bar line is at 4 seconds
+        run(&mut mixer, &mut player, 1.0);
+        assert!(mixer.is_automating(&MixTarget::Volume { bus: calm_bus }));
+        assert_near(volume(&mixer, calm_bus), 0.5);
+        assert_near(volume(&mixer, tense_bus), 0.5);
+        assert_near(player.position(&mixer).expect("failed to find the position"), 0.5);
+    }
+
+    #[test]
+    fn layers_follow_the_intensity() {
+        let mut mixer = Mixer::new(RATE);
+        let mut player = MusicPlayer::new(MASTER_BUS);
+        player.layer_fade = 2.0;
+        let mut track = MusicTrack::new("explore", stem("explore_base", 20.0), 60.0, 4);
+        track.add_layer(stem("explore_drums", 20.0), 0.5, 1.0);
+        player.play(&mut mixer, &track, 0.0, MusicSync::Immediate).expect("failed to play");
+        let layers = player.current.as_ref().expect("failed to find the current track").buses.layers.clone();
+        assert_eq!(layers.len(), 2);
+        assert_eq!(volume(&mixer, layers[0]), 1.0);
+        assert_eq!(volume(&mixer, layers[1]), 0.0);
+
+        // all the way up takes layer_fade seconds
+        player.intensity = 1.0;
+        player.update(&mut mixer);
+        assert!(mixer.is_automating(&MixTarget::Volume { bus: layers[1] }));
+        run(&mut mixer, &mut player, 1.0);
+        assert_near(volume(&mixer, layers[1]), 0.5);
+
+        run(&mut mixer, &mut player, 1.0);
+        assert_eq!(volume(&mixer, layers[1]), 1.0);
+        assert!(!mixer.is_automating(&MixTarget::Volume { bus: layers[1] }));
+
+        // halfway back down takes half as long
+        player.intensity = 0.75;
+        player.update(&mut mixer);
+        run(&mut mixer, &mut player, 0.5);
+        assert_near(volume(&mixer, layers[1]), 0.75);
+        run(&mut mixer, &mut player, 0.5);
+        assert_eq!(volume(&mixer, layers[1]), 0.5);
+        assert_eq!(volume(&mixer, layers[0]), 1.0);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,240 @@
+use sdl2::Sdl;
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use hound::{WavWriter, WavSpec, SampleFormat};
+
+use super::mixer::Mixer;
+use super::sound::AudioError;
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+// the sdl device pulls from the mixer on its own thread
+struct MixerCallback {
+    mixer: Arc<Mutex<Mixer>>
+}
+
+impl AudioCallback for MixerCallback {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        match self.mixer.lock() {
+            Ok(mut mixer) => mixer.mix(out),
+            Err(_) => for sample in out.iter_mut() { *sample = 0.0 }
+        }
+    }
+}
+
+enum Output {
+    Device(AudioDevice<MixerCallback>),
+    // mixes only when rendered and throws the samples away
+    Null,
+    File(WavWriter<BufWriter<File>>)
+}
+
+// a mixer and where it plays to. the device output mixes by itself, the others mix when rendered, which is
+// what tests and offline bounces want
+pub struct AudioEngine {
+    mixer: Arc<Mutex<Mixer>>,
+    output: Output
+}
+
+impl AudioEngine {
+    // plays to the default device, the mixer runs at whatever rate the device opened with
+    pub fn new(sdl: &Sdl, sample_rate: u32) -> Result<AudioEngine, AudioError> {
+        let audio = sdl.audio().map_err(AudioError::Device)?;
+        let desired = AudioSpecDesired {
+            freq: Some(sample_rate as i32),
+            channels: Some(2),
+            samples: Some(1024)
+        };
+        let mixer = Arc::new(Mutex::new(Mixer::new(sample_rate)));
+        let device = audio.open_playback(None, &desired, |spec| {
+            mixer.lock().expect("failed to lock the mixer").sample_rate = spec.freq as u32;
+            MixerCallback { mixer: mixer.clone() }
+        }).map_err(AudioError::Device)?;
+        device.resume();
+
+        Ok(AudioEngine {
+            mixer,
+            output: Output::Device(device)
+        })
+    }
+
+    pub fn null(sample_rate: u32) -> AudioEngine {
+        AudioEngine {
+            mixer: Arc::new(Mutex::new(Mixer::new(sample_rate))),
+            output: Output::Null
+        }
+    }
+
+    // writes everything rendered to a 32 bit float stereo wav
+    pub fn to_file<P: AsRef<Path>>(path: P, sample_rate: u32) -> Result<AudioEngine, AudioError> {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float
+        };
+        Ok(AudioEngine {
+            mixer: Arc::new(Mutex::new(Mixer::new(sample_rate))),
+            output: Output::File(WavWriter::create(path, spec)?)
+        })
+    }
+
+    // the device thread waits while this is held, so don't keep it for long
+    pub fn mixer(&self) -> MutexGuard<'_, Mixer> {
+        self.mixer.lock().expect("failed to lock the mixer")
+    }
+
+    // mixes frames ahead and returns them, does nothing for the device output which mixes by itself
+    pub fn render(&mut self, frames: usize) -> Result<Vec<f32>, AudioError> {
+        let mut samples = vec![0.0; frames * 2];
+        match &mut self.output {
+            Output::Device(_) => return Ok(vec![]),
+            Output::Null => self.mixer.lock().expect("failed to lock the mixer").mix(&mut samples),
+            Output::File(writer) => {
+                self.mixer.lock().expect("failed to lock the mixer").mix(&mut samples);
+                for sample in samples.iter() {
+                    writer.write_sample(*sample)?;
+                }
+            }
+        }
+        Ok(samples)
+    }
+
+    // stops the device or finishes the file, dropping the engine does the same but can't report errors
+    pub fn finish(self) -> Result<(), AudioError> {
+        match self.output {
+            Output::File(writer) => Ok(writer.finalize()?),
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::mixer::VoiceSettings;
+    use crate::audio::sound::Sound;
+    use crate::audio::spatial::{Emitter, Attenuation};
+    use crate::math::vec3::Vec3;
+
+    fn left(samples: &[f32]) -> Vec<f32> {
+        samples.iter().step_by(2).cloned().collect()
+    }
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    // centered gains come out a rounding error off 1
+    fn assert_frames(samples: &[f32], expected: &[f32]) {
+        let left = left(samples);
+        assert_eq!(left.len(), expected.len());
+        for (sample, expected) in left.iter().zip(expected.iter()) {
+            assert_near(*sample, *expected);
+        }
+    }
+
+    // mono, every sample its frame number times step
+    fn ramp(sample_rate: u32, frames: usize, step: f32) -> Arc<Sound> {
+        Arc::new(Sound::new(sample_rate, 1, (0..frames).map(|frame| frame as f32 * step).collect()))
+    }
+
+    #[test]
+    fn renders_silence_without_voices() {
+        let mut engine = AudioEngine::null(48000);
+        let samples = engine.render(480).expect("failed to render");
+        assert_eq!(samples, vec![0.0; 960]);
+        assert_near(engine.mixer().time() as f32, 0.01);
+        engine.finish().expect("failed to finish");
+    }
+
+    #[test]
+    fn sounds_at_other_rates_are_resampled() {
+        let mut engine = AudioEngine::null(48000);
+        let handle = engine.mixer().play(&ramp(24000, 100, 0.01), VoiceSettings::new());
+
+        let samples = engine.render(150).expect("failed to render");
+        for (frame, sample) in left(&samples).iter().enumerate() {
+            assert_near(*sample, frame as f32 * 0.005);
+        }
+        // mono plays the same on both sides
+        assert_eq!(samples[2 * 99], samples[2 * 99 + 1]);
+        assert_near(engine.mixer().position(handle).expect("failed to find the voice"), 75.0 / 24000.0);
+
+        engine.render(100).expect("failed to render");
+        assert!(!engine.mixer().is_playing(handle));
+    }
+
+    #[test]
+    fn loops_go_back_to_the_loop_start_at_the_loop_end() {
+        let mut engine = AudioEngine::null(8);
+        let settings = VoiceSettings { looping: true, loop_start: 0.25, loop_end: Some(0.75), ..VoiceSettings::new() };
+        let handle = engine.mixer().play(&ramp(8, 10, 1.0), settings);
+
+        let samples = engine.render(14).expect("failed to render");
+        assert_frames(&samples, &[0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 2.0, 3.0, 4.0, 5.0, 2.0, 3.0, 4.0, 5.0]);
+        assert!(engine.mixer().is_playing(handle));
+
+        // without a loop end the whole sound after the loop start repeats
+        engine.mixer().settings_mut(handle).expect("failed to find the voice").loop_end = None;
+        let samples = engine.render(10).expect("failed to render");
+        assert_frames(&samples, &[2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn sounds_that_dont_loop_end() {
+        let mut engine = AudioEngine::null(8);
+        let handle = engine.mixer().play(&ramp(8, 4, 1.0), VoiceSettings::new());
+        let samples = engine.render(6).expect("failed to render");
+        assert_frames(&samples, &[0.0, 1.0, 2.0, 3.0, 0.0, 0.0]);
+        assert!(!engine.mixer().is_playing(handle));
+        assert_eq!(engine.mixer().voice_count(), 0);
+    }
+
+    #[test]
+    fn old_handles_dont_reach_the_voice_that_reused_their_slot() {
+        let mut engine = AudioEngine::null(8);
+        let sound = ramp(8, 2, 1.0);
+        let old = engine.mixer().play(&sound, VoiceSettings::new());
+        engine.render(4).expect("failed to render");
+        assert!(!engine.mixer().is_playing(old));
+
+        let new = engine.mixer().play(&sound, VoiceSettings::new());
+        assert_ne!(old, new);
+        assert!(!engine.mixer().is_playing(old));
+        assert!(engine.mixer().settings_mut(old).is_none());
+        assert!(engine.mixer().position(old).is_none());
+        engine.mixer().stop(old);
+        assert!(engine.mixer().is_playing(new));
+
+        engine.mixer().stop(new);
+        assert_eq!(engine.mixer().voice_count(), 0);
+    }
+
+    #[test]
+    fn placed_voices_are_attenuated_panned_and_pitched() {
+        let mut engine = AudioEngine::null(48000);
+        let mut emitter = Emitter::new(Vec3 { x: 2.0, y: 0.0, z: 0.0 });
+        emitter.attenuation = Attenuation::Inverse { reference: 1.0, rolloff: 1.0 };
+        let sound = Arc::new(Sound::new(48000, 1, vec![1.0; 4800]));
+        let handle = engine.mixer().play(&sound, VoiceSettings::at(emitter.clone()));
+
+        // all the way right at half the volume
+        let samples = engine.render(480).expect("failed to render");
+        assert_near(samples[20], 0.0);
+        assert_near(samples[21], 0.5 * std::f32::consts::SQRT_2);
+        assert_near(engine.mixer().position(handle).expect("failed to find the voice"), 0.01);
+
+        // coming closer at a tenth of the speed of sound
+        engine.mixer().stop(handle);
+        emitter.velocity = Vec3 { x: -34.3, y: 0.0, z: 0.0 };
+        let handle = engine.mixer().play(&sound, VoiceSettings::at(emitter));
+        engine.render(480).expect("failed to render");
+        let position = engine.mixer().position(handle).expect("failed to find the voice");
+        assert_near(position, 0.01 * 343.0 / (343.0 - 34.3));
+    }
+}
\ No newline at end of file
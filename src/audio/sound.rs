@@ -0,0 +1,223 @@
+use hound::{WavReader, SampleFormat};
+use lewton::inside_ogg::OggStreamReader;
+use claxon::FlacReader;
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum AudioError {
+    Io(io::Error),
+    Wav(hound::Error),
+    Vorbis(lewton::VorbisError),
+    Flac(claxon::Error),
+    UnknownFormat(PathBuf),
+    NoChannels(PathBuf),
+    Device(String)
+}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AudioError::Io(e) => write!(f, "io error: {}", e),
+            AudioError::Wav(e) => write!(f, "wav error: {}", e),
+            AudioError::Vorbis(e) => write!(f, "ogg vorbis error: {}", e),
+            AudioError::Flac(e) => write!(f, "flac error: {}", e),
+            AudioError::UnknownFormat(path) => write!(f, "{} isn't a wav, ogg vorbis or flac file", path.display()),
+            AudioError::NoChannels(path) => write!(f, "{} has no channels", path.display()),
+            AudioError::Device(e) => write!(f, "audio device error: {}", e)
+        }
+    }
+}
+
+impl Error for AudioError {}
+
+impl From<io::Error> for AudioError {
+    fn from(error: io::Error) -> AudioError {
+        AudioError::Io(error)
+    }
+}
+
+impl From<hound::Error> for AudioError {
+    fn from(error: hound::Error) -> AudioError {
+        AudioError::Wav(error)
+    }
+}
+
+impl From<lewton::VorbisError> for AudioError {
+    fn from(error: lewton::VorbisError) -> AudioError {
+        AudioError::Vorbis(error)
+    }
+}
+
+impl From<claxon::Error> for AudioError {
+    fn from(error: claxon::Error) -> AudioError {
+        AudioError::Flac(error)
+    }
+}
+
+// a whole sound decoded into memory, for short sounds played often
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sound {
+    pub sample_rate: u32,
+    pub channels: u16,
+    // interleaved, from -1 to 1
+    pub samples: Vec<f32>
+}
+
+impl Sound {
+    pub fn new(sample_rate: u32, channels: u16, samples: Vec<f32>) -> Sound {
+        Sound {
+            sample_rate,
+            channels,
+            samples
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Sound, AudioError> {
+        let mut stream = SoundStream::open(path)?;
+        let mut samples = vec![];
+        while stream.read(&mut samples)? {}
+        Ok(Sound::new(stream.sample_rate, stream.channels, samples))
+    }
+
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.frames() as f32 / self.sample_rate as f32
+    }
+}
+
+enum Decoder {
+    Wav(WavReader<BufReader<File>>),
+    Vorbis(OggStreamReader<BufReader<File>>),
+    Flac(FlacReader<BufReader<File>>)
+}
+
+// a sound decoded a block at a time while it plays, for music and long ambiences
+pub struct SoundStream {
+    pub sample_rate: u32,
+    pub channels: u16,
+    path: PathBuf,
+    decoder: Decoder
+}
+
+impl SoundStream {
+    // the format is told by the file's first bytes, not its extension
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<SoundStream, AudioError> {
+        let path = path.as_ref().to_path_buf();
+        let decoder = open_decoder(&path)?;
+        let (sample_rate, channels) = match &decoder {
+            Decoder::Wav(reader) => (reader.spec().sample_rate, reader.spec().channels),
+            Decoder::Vorbis(reader) => (reader.ident_hdr.audio_sample_rate, reader.ident_hdr.audio_channels as u16),
+            Decoder::Flac(reader) => (reader.streaminfo().sample_rate, reader.streaminfo().channels as u16)
+        };
+        if channels == 0 {
+            return Err(AudioError::NoChannels(path));
+        }
+        Ok(SoundStream {
+            sample_rate,
+            channels,
+            path,
+            decoder
+        })
+    }
+
+    // appends the next block of interleaved samples to out, false once the stream has ended
+    pub fn read(&mut self, out: &mut Vec<f32>) -> Result<bool, AudioError> {
+        match &mut self.decoder {
+            Decoder::Wav(reader) => {
+                let spec = reader.spec();
+                let block = 4096 * spec.channels as usize;
+                let start = out.len();
+                match spec.sample_format {
+                    SampleFormat::Float => {
+                        for sample in reader.samples::<f32>().take(block) {
+                            out.push(sample?);
+                        }
+                    }
+                    SampleFormat::Int => {
+                        let scale = 1.0 / (1u32 << (spec.bits_per_sample - 1)) as f32;
+                        for sample in reader.samples::<i32>().take(block) {
+                            out.push(sample? as f32 * scale);
+                        }
+                    }
+                }
+                Ok(out.len() > start)
+            }
+            Decoder::Vorbis(reader) => {
+                // packets can be empty without the stream having ended
+                match reader.read_dec_packet_itl()? {
+                    Some(packet) => {
+                        out.extend(packet.into_iter().map(|sample| sample as f32 / 32768.0));
+                        Ok(true)
+                    }
+                    None => Ok(false)
+                }
+            }
+            Decoder::Flac(reader) => {
+                let scale = 1.0 / (1u32 << (reader.streaminfo().bits_per_sample - 1)) as f32;
+                let mut blocks = reader.blocks();
+                match blocks.read_next_or_eof(vec![])? {
+                    Some(block) => {
+                        for frame in 0..block.duration() {
+                            for channel in 0..block.channels() {
+                                out.push(block.sample(channel, frame) as f32 * scale);
+                            }
+                        }
+                        Ok(true)
+                    }
+                    None => Ok(false)
+                }
+            }
+        }
+    }
+
+    // back to the start, for looping
+    pub fn rewind(&mut self) -> Result<(), AudioError> {
+        self.decoder = open_decoder(&self.path)?;
+        Ok(())
+    }
+
+    // moves to a frame from the start. wavs jump there, the others are decoded from the start up to it and
+    // what was decoded past the frame is put in out
+    pub fn seek(&mut self, frame: usize, out: &mut Vec<f32>) -> Result<(), AudioError> {
+        out.clear();
+        if let Decoder::Wav(reader) = &mut self.decoder {
+            let frames = reader.duration() as usize;
+            reader.seek(frame.min(frames) as u32)?;
+            return Ok(());
+        }
+
+        self.rewind()?;
+        let channels = self.channels as usize;
+        let mut decoded = 0;
+        while self.read(out)? {
+            let frames = out.len() / channels;
+            if decoded + frames > frame {
+                out.drain(..(frame - decoded) * channels);
+                return Ok(());
+            }
+            decoded += frames;
+            out.clear();
+        }
+        Ok(())
+    }
+}
+
+fn open_decoder(path: &Path) -> Result<Decoder, AudioError> {
+    let mut magic = [0u8; 4];
+    File::open(path)?.read_exact(&mut magic)?;
+    let reader = BufReader::new(File::open(path)?);
+    match &magic {
+        b"RIFF" => Ok(Decoder::Wav(WavReader::new(reader)?)),
+        b"OggS" => Ok(Decoder::Vorbis(OggStreamReader::new(reader)?)),
+        b"fLaC" => Ok(Decoder::Flac(FlacReader::new(reader)?)),
+        _ => Err(AudioError::UnknownFormat(path.to_path_buf()))
+    }
+}
\ No newline at end of file
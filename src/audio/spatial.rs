@@ -0,0 +1,211 @@
+use crate::math::vec3::Vec3;
+use crate::math::quaternion::Quat;
+
+use std::f32::consts::PI;
+
+// how loudness falls off with distance. sounds closer than the reference distance play at full volume
+#[derive(Debug, Clone, PartialEq)]
+pub enum Attenuation {
+    None,
+    // reference / distance, the physically right one
+    Inverse { reference: f32, rolloff: f32 },
+    // straight down to silence at max_distance
+    Linear { reference: f32, max_distance: f32 },
+    // (distance / reference) ^ -rolloff
+    Exponential { reference: f32, rolloff: f32 }
+}
+
+impl Attenuation {
+    pub fn gain(&self, distance: f32) -> f32 {
+        match self {
+            Attenuation::None => 1.0,
+            Attenuation::Inverse { reference, rolloff } => {
+                let distance = distance.max(*reference);
+                reference / (reference + rolloff * (distance - reference))
+            }
+            Attenuation::Linear { reference, max_distance } => {
+                if max_distance <= reference {
+                    return if distance <= *reference { 1.0 } else { 0.0 };
+                }
+                let distance = distance.max(*reference).min(*max_distance);
+                1.0 - (distance - reference) / (max_distance - reference)
+            }
+            Attenuation::Exponential { reference, rolloff } => {
+                (distance.max(*reference) / reference).powf(-rolloff)
+            }
+        }
+    }
+}
+
+// where sounds are heard from, usually the camera. -z is forward and +x is to the right
+#[derive(Debug, Clone, PartialEq)]
+pub struct Listener {
+    pub position: Vec3,
+    pub rotation: Quat,
+    // in units per second, for doppler
+    pub velocity: Vec3
+}
+
+impl Listener {
+    pub fn new() -> Listener {
+        Listener {
+            position: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+            rotation: Quat::identity(),
+            velocity: Vec3 { x: 0.0, y: 0.0, z: 0.0 }
+        }
+    }
+}
+
+impl Default for Listener {
+    fn default() -> Listener {
+        Listener::new()
+    }
+}
+
+// a sound placed in the world
+#[derive(Debug, Clone, PartialEq)]
+pub struct Emitter {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub attenuation: Attenuation,
+    // 0 turns doppler off for this sound, 1 is realistic
+    pub doppler_factor: f32,
+    // within this distance the sound spreads to both ears rather than coming from one side
+    pub spread_distance: f32
+}
+
+impl Emitter {
+    pub fn new(position: Vec3) -> Emitter {
+        Emitter {
+            position,
+            velocity: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+            attenuation: Attenuation::Inverse { reference: 1.0, rolloff: 1.0 },
+            doppler_factor: 1.0,
+            spread_distance: 0.5
+        }
+    }
+}
+
+// what placing a sound in the world does to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spatialized {
+    pub gain: f32,
+    // -1 left to 1 right
+    pub pan: f32,
+    // playback rate multiplier from doppler
+    pub pitch: f32
+}
+
+// gain from the distance, pan from the direction in the listener's space and pitch from how fast the
+// emitter and listener approach each other. speeds are clamped below the speed of sound
+pub fn spatialize(emitter: &Emitter, listener: &Listener, speed_of_sound: f32) -> Spatialized {
+    let offset = emitter.position.clone() - listener.position.clone();
+    let distance = offset.lenght();
+    let gain = emitter.attenuation.gain(distance);
+    if distance < 1e-6 {
+        return Spatialized { gain, pan: 0.0, pitch: 1.0 };
+    }
+
+    let local = listener.rotation.conjugated().rotate(&offset) / distance;
+    // close sounds are heard with both ears
+    let spread = if emitter.spread_distance > 0.0 { (distance / emitter.spread_distance).min(1.0) } else { 1.0 };
+    let pan = local.x * spread;
+
+    // doppler along the line from the emitter to the listener
+    let toward_listener = offset * (-1.0 / distance);
+    let limit = speed_of_sound * 0.99;
+    let listener_speed = (listener.velocity.dot(toward_listener.clone()) * emitter.doppler_factor).max(-limit).min(limit);
+    let emitter_speed = (emitter.velocity.dot(toward_listener) * emitter.doppler_factor).max(-limit).min(limit);
+    let pitch = (speed_of_sound - listener_speed) / (speed_of_sound - emitter_speed);
+
+    Spatialized { gain, pan, pitch }
+}
+
+// left and right gains of a constant power pan, both are 1 in the middle so centered sounds keep their
+// volume
+pub fn pan_gains(pan: f32) -> (f32, f32) {
+    let angle = (pan.max(-1.0).min(1.0) + 1.0) * PI / 4.0;
+    (angle.cos() * std::f32::consts::SQRT_2, angle.sin() * std::f32::consts::SQRT_2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn attenuation_is_full_inside_the_reference_distance() {
+        let curves = [
+            Attenuation::Inverse { reference: 2.0, rolloff: 1.0 },
+            Attenuation::Linear { reference: 2.0, max_distance: 10.0 },
+            Attenuation::Exponential { reference: 2.0, rolloff: 1.0 }
+        ];
+        for curve in curves.iter() {
+            assert_eq!(curve.gain(0.0), 1.0);
+            assert_eq!(curve.gain(2.0), 1.0);
+        }
+        assert_near(curves[0].gain(4.0), 0.5);
+        assert_near(curves[1].gain(6.0), 0.5);
+        assert_eq!(curves[1].gain(20.0), 0.0);
+        assert_near(curves[2].gain(8.0), 0.25);
+        assert_eq!(Attenuation::None.gain(1000.0), 1.0);
+    }
+
+    #[test]
+    fn pan_follows_the_listener_rotation() {
+        let emitter = Emitter::new(vec3(0.0, 0.0, -4.0));
+        let mut listener = Listener::new();
+        assert_near(spatialize(&emitter, &listener, 343.0).pan, 0.0);
+
+        // turned to face -x, the sound in front before is now on the right
+        listener.rotation = Quat::from_axis_angle(&vec3(0.0, 1.0, 0.0), 90.0);
+        let spatialized = spatialize(&emitter, &listener, 343.0);
+        assert_near(spatialized.pan, 1.0);
+        assert_near(spatialized.gain, 0.25);
+    }
+
+    #[test]
+    fn close_sounds_spread_to_both_ears() {
+        let mut emitter = Emitter::new(vec3(-0.25, 0.0, 0.0));
+        emitter.spread_distance = 0.5;
+        assert_near(spatialize(&emitter, &Listener::new(), 343.0).pan, -0.5);
+    }
+
+    #[test]
+    fn doppler_raises_approaching_sounds_and_lowers_receding_ones() {
+        let mut emitter = Emitter::new(vec3(10.0, 0.0, 0.0));
+        let mut listener = Listener::new();
+        emitter.velocity = vec3(-34.3, 0.0, 0.0);
+        assert_near(spatialize(&emitter, &listener, 343.0).pitch, 343.0 / (343.0 - 34.3));
+
+        emitter.velocity = vec3(0.0, 0.0, 0.0);
+        listener.velocity = vec3(-34.3, 0.0, 0.0);
+        assert_near(spatialize(&emitter, &listener, 343.0).pitch, (343.0 - 34.3) / 343.0);
+
+        // faster than sound is clamped rather than flipping the pitch
+        listener.velocity = vec3(0.0, 0.0, 0.0);
+        emitter.velocity = vec3(-1000.0, 0.0, 0.0);
+        assert!((spatialize(&emitter, &listener, 343.0).pitch - 100.0).abs() < 1e-2);
+
+        emitter.doppler_factor = 0.0;
+        assert_eq!(spatialize(&emitter, &listener, 343.0).pitch, 1.0);
+    }
+
+    #[test]
+    fn panning_keeps_the_power() {
+        assert_eq!(pan_gains(0.0).0, pan_gains(0.0).1);
+        for pan in [-1.0, -0.3, 0.0, 0.6, 1.0, 2.0].iter() {
+            let (left, right) = pan_gains(*pan);
+            assert_near(left * left + right * right, 2.0);
+        }
+        assert_near(pan_gains(1.0).0, 0.0);
+        assert_near(pan_gains(-1.0).1, 0.0);
+    }
+}
\ No newline at end of file
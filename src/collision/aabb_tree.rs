@@ -0,0 +1,397 @@
+use crate::math::aabb::Aabb;
+use crate::math::ray3d::Ray3d;
+
+#[derive(Debug, Clone, PartialEq)]
+struct TreeNode {
+    aabb: Aabb,
+    parent: Option<usize>,
+    // none for leaves
+    children: Option<[usize; 2]>,
+    // leaves are 0
+    height: i32,
+    // what the leaf stands for, usually an index into the caller's objects
+    data: usize
+}
+
+// bounding volume hierarchy for things that move. leaves hold boxes grown by margin, so small movements
+// don't touch the tree, and inserts pick the sibling that grows the tree's surface area least. the tree is
+// kept balanced with rotations like an avl tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct AabbTree {
+    // leaf boxes are grown by this much on every side
+    pub margin: f32,
+    nodes: Vec<TreeNode>,
+    free: Vec<usize>,
+    root: Option<usize>
+}
+
+impl AabbTree {
+    pub fn new(margin: f32) -> AabbTree {
+        AabbTree {
+            margin,
+            nodes: vec![],
+            free: vec![],
+            root: None
+        }
+    }
+
+    // returns the proxy that moves and removes the leaf
+    pub fn insert(&mut self, aabb: &Aabb, data: usize) -> usize {
+        let leaf = self.allocate(TreeNode {
+            aabb: aabb.expanded(self.margin),
+            parent: None,
+            children: None,
+            height: 0,
+            data
+        });
+        self.insert_leaf(leaf);
+        leaf
+    }
+
+    pub fn remove(&mut self, proxy: usize) {
+        self.remove_leaf(proxy);
+        self.free.push(proxy);
+    }
+
+    // moves a leaf, the tree only changes when the box left the grown one. returns whether it did
+    pub fn update(&mut self, proxy: usize, aabb: &Aabb) -> bool {
+        if self.nodes[proxy].aabb.contains(aabb) {
+            return false;
+        }
+        self.remove_leaf(proxy);
+        self.nodes[proxy].aabb = aabb.expanded(self.margin);
+        self.insert_leaf(proxy);
+        true
+    }
+
+    pub fn data(&self, proxy: usize) -> usize {
+        self.nodes[proxy].data
+    }
+
+    // the grown box of a leaf
+    pub fn fat_aabb(&self, proxy: usize) -> &Aabb {
+        &self.nodes[proxy].aabb
+    }
+
+    pub fn height(&self) -> i32 {
+        self.root.map(|root| self.nodes[root].height).unwrap_or(0)
+    }
+
+    // data of every leaf whose box overlaps aabb
+    pub fn query(&self, aabb: &Aabb) -> Vec<usize> {
+        let mut ret = vec![];
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            if !node.aabb.overlaps(aabb) {
+                continue;
+            }
+            match node.children {
+                Some(children) => stack.extend(children.iter()),
+                None => ret.push(node.data)
+            }
+        }
+        ret
+    }
+
+    // (data, distance the ray enters the box at) of every leaf the ray passes through, nearest first
+    pub fn raycast(&self, ray: &Ray3d) -> Vec<(usize, f32)> {
+        let mut ret = vec![];
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            let distance = match node.aabb.ray_intersection(ray) {
+                Some(distance) => distance,
+                None => continue
+            };
+            match node.children {
+                Some(children) => stack.extend(children.iter()),
+                None => ret.push((node.data, distance))
+            }
+        }
+        ret.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal).then(a.0.cmp(&b.0)));
+        ret
+    }
+
+    // every pair of leaves with overlapping boxes, as (smaller data, larger data), sorted
+    pub fn pairs(&self) -> Vec<(usize, usize)> {
+        let mut leaves = vec![];
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(node) = stack.pop() {
+            match self.nodes[node].children {
+                Some(children) => stack.extend(children.iter()),
+                None => leaves.push(node)
+            }
+        }
+
+        let mut ret = vec![];
+        for leaf in leaves.into_iter() {
+            let node = &self.nodes[leaf];
+            for other in self.query(&node.aabb) {
+                if other != node.data {
+                    ret.push((node.data.min(other), node.data.max(other)));
+                }
+            }
+        }
+        ret.sort();
+        ret.dedup();
+        ret
+    }
+
+    fn allocate(&mut self, node: TreeNode) -> usize {
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn insert_leaf(&mut self, leaf: usize) {
+        let mut index = match self.root {
+            Some(root) => root,
+            None => {
+                self.root = Some(leaf);
+                self.nodes[leaf].parent = None;
+                return;
+            }
+        };
+
+        // walk down to the sibling that costs least: the area the new parent adds, plus how much every
+        // ancestor on the way has to grow
+        let leaf_aabb = self.nodes[leaf].aabb.clone();
+        while let Some(children) = self.nodes[index].children {
+            let area = self.nodes[index].aabb.surface_area();
+            let combined_area = self.nodes[index].aabb.union(&leaf_aabb).surface_area();
+            let cost = 2.0 * combined_area;
+            let inherited = 2.0 * (combined_area - area);
+
+            let child_cost = |child: usize| {
+                let node = &self.nodes[child];
+                let grown = node.aabb.union(&leaf_aabb).surface_area();
+                match node.children {
+                    None => grown + inherited,
+                    Some(_) => grown - node.aabb.surface_area() + inherited
+                }
+            };
+            let (cost0, cost1) = (child_cost(children[0]), child_cost(children[1]));
+            if cost < cost0 && cost < cost1 {
+                break;
+            }
+            index = if cost0 < cost1 { children[0] } else { children[1] };
+        }
+
+        // a new parent for the sibling and the leaf
+        let sibling = index;
+        let old_parent = self.nodes[sibling].parent;
+        let parent = self.allocate(TreeNode {
+            aabb: self.nodes[sibling].aabb.union(&leaf_aabb),
+            parent: old_parent,
+            children: Some([sibling, leaf]),
+            height: self.nodes[sibling].height + 1,
+            data: 0
+        });
+        self.nodes[sibling].parent = Some(parent);
+        self.nodes[leaf].parent = Some(parent);
+        match old_parent {
+            Some(old_parent) => self.replace_child(old_parent, sibling, parent),
+            None => self.root = Some(parent)
+        }
+
+        self.refit(self.nodes[leaf].parent);
+    }
+
+    fn remove_leaf(&mut self, leaf: usize) {
+        if self.root == Some(leaf) {
+            self.root = None;
+            return;
+        }
+        let parent = self.nodes[leaf].parent.expect("failed to find the parent of a leaf");
+        let children = self.nodes[parent].children.expect("failed to find the children of a node");
+        let sibling = if children[0] == leaf { children[1] } else { children[0] };
+
+        // the sibling takes the parent's place
+        let grandparent = self.nodes[parent].parent;
+        self.nodes[sibling].parent = grandparent;
+        match grandparent {
+            Some(grandparent) => {
+                self.replace_child(grandparent, parent, sibling);
+                self.refit(Some(grandparent));
+            }
+            None => self.root = Some(sibling)
+        }
+        self.nodes[leaf].parent = None;
+        self.free.push(parent);
+    }
+
+    fn replace_child(&mut self, parent: usize, old: usize, new: usize) {
+        if let Some(children) = self.nodes[parent].children.as_mut() {
+            for child in children.iter_mut() {
+                if *child == old {
+                    *child = new;
+                }
+            }
+        }
+    }
+
+    // rebalances and resizes every node from index up to the root
+    fn refit(&mut self, mut index: Option<usize>) {
+        while let Some(node) = index {
+            let node = self.balance(node);
+            self.fit(node);
+            index = self.nodes[node].parent;
+        }
+    }
+
+    fn fit(&mut self, node: usize) {
+        if let Some([a, b]) = self.nodes[node].children {
+            self.nodes[node].aabb = self.nodes[a].aabb.union(&self.nodes[b].aabb);
+            self.nodes[node].height = 1 + self.nodes[a].height.max(self.nodes[b].height);
+        }
+    }
+
+    // when one child of a is more than one level taller than the other, the taller child is rotated up
+    // into a's place. returns the node now in that place
+    fn balance(&mut self, a: usize) -> usize {
+        let [b, c] = match self.nodes[a].children {
+            Some(children) if self.nodes[a].height >= 2 => children,
+            _ => return a
+        };
+        let difference = self.nodes[c].height - self.nodes[b].height;
+        if difference > 1 {
+            self.rotate(a, c, b)
+        } else if difference < -1 {
+            self.rotate(a, b, c)
+        } else {
+            a
+        }
+    }
+
+    // moves up the tall child of a, a keeps the short child and the shorter grandchild
+    fn rotate(&mut self, a: usize, tall: usize, short: usize) -> usize {
+        let [f, g] = self.nodes[tall].children.expect("failed to find the children of a node");
+
+        let parent = self.nodes[a].parent;
+        self.nodes[tall].parent = parent;
+        self.nodes[a].parent = Some(tall);
+        match parent {
+            Some(parent) => self.replace_child(parent, a, tall),
+            None => self.root = Some(tall)
+        }
+
+        let (kept, moved) = if self.nodes[f].height > self.nodes[g].height { (f, g) } else { (g, f) };
+        self.nodes[tall].children = Some([a, kept]);
+        self.nodes[a].children = Some([short, moved]);
+        self.nodes[moved].parent = Some(a);
+        self.fit(a);
+        self.fit(tall);
+        tall
+    }
+}
+
+impl Default for AabbTree {
+    fn default() -> AabbTree {
+        AabbTree::new(0.1)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::vec3::Vec3;
+
+    fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn unit_box(x: f32, y: f32, z: f32) -> Aabb {
+        Aabb::new(vec3(x, y, z), vec3(x + 1.0, y + 1.0, z + 1.0))
+    }
+
+    // a 5 x 5 x 5 grid of unit boxes with gaps of 1 between them, data is the index in the grid
+    fn grid() -> (AabbTree, Vec<Aabb>) {
+        let mut tree = AabbTree::new(0.1);
+        let mut boxes = vec![];
+        for i in 0..125 {
+            let aabb = unit_box((i % 5) as f32 * 2.0, (i / 5 % 5) as f32 * 2.0, (i / 25) as f32 * 2.0);
+            tree.insert(&aabb, i);
+            boxes.push(aabb);
+        }
+        (tree, boxes)
+    }
+
+    fn sorted(mut list: Vec<usize>) -> Vec<usize> {
+        list.sort();
+        list
+    }
+
+    #[test]
+    fn queries_match_testing_every_box() {
+        let (tree, boxes) = grid();
+        for query in [unit_box(0.5, 0.5, 0.5), unit_box(3.5, 1.5, 5.5), Aabb::new(vec3(-1.0, -1.0, -1.0), vec3(4.5, 0.5, 20.0))].iter() {
+            let expected: Vec<usize> = (0..boxes.len()).filter(|i| boxes[*i].expanded(0.1).overlaps(query)).collect();
+            assert_eq!(sorted(tree.query(query)), expected);
+        }
+        assert_eq!(tree.query(&unit_box(100.0, 0.0, 0.0)), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn the_tree_stays_balanced() {
+        let mut tree = AabbTree::new(0.0);
+        // inserted along a line, the worst case for a tree that isn't rebalanced
+        for i in 0..256 {
+            tree.insert(&unit_box(i as f32 * 2.0, 0.0, 0.0), i);
+        }
+        assert!(tree.height() <= 2 * 8, "height {}", tree.height());
+    }
+
+    #[test]
+    fn small_moves_stay_inside_the_grown_box() {
+        let mut tree = AabbTree::new(0.5);
+        let proxy = tree.insert(&unit_box(0.0, 0.0, 0.0), 7);
+        assert_eq!(tree.data(proxy), 7);
+        assert_eq!(tree.fat_aabb(proxy), &unit_box(0.0, 0.0, 0.0).expanded(0.5));
+        assert!(!tree.update(proxy, &unit_box(0.2, 0.0, -0.3)));
+        assert_eq!(tree.fat_aabb(proxy), &unit_box(0.0, 0.0, 0.0).expanded(0.5));
+
+        assert!(tree.update(proxy, &unit_box(3.0, 0.0, 0.0)));
+        assert_eq!(tree.query(&unit_box(3.0, 0.0, 0.0)), vec![7]);
+        assert_eq!(tree.query(&unit_box(-1.5, 0.0, 0.0)), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn removed_leaves_are_gone_and_their_slots_reused() {
+        let (mut tree, _) = grid();
+        let proxy = (0..125).find(|proxy| tree.data(*proxy) == 62).expect("failed to find the leaf");
+        tree.remove(proxy);
+        assert_eq!(tree.query(&unit_box(4.0, 4.0, 4.0)), Vec::<usize>::new());
+        assert_eq!(tree.insert(&unit_box(4.0, 4.0, 4.0), 200), proxy);
+        assert_eq!(tree.query(&unit_box(4.0, 4.0, 4.0)), vec![200]);
+    }
+
+    #[test]
+    fn pairs_come_out_once_and_sorted() {
+        let mut tree = AabbTree::new(0.0);
+        tree.insert(&unit_box(0.0, 0.0, 0.0), 3);
+        tree.insert(&unit_box(0.5, 0.5, 0.5), 1);
+        tree.insert(&unit_box(10.0, 0.0, 0.0), 2);
+        tree.insert(&unit_box(0.9, 0.0, 0.0), 0);
+        tree.insert(&unit_box(10.5, 0.0, 0.0), 4);
+        assert_eq!(tree.pairs(), vec![(0, 1), (0, 3), (1, 3), (2, 4)]);
+    }
+
+    #[test]
+    fn raycasts_come_out_nearest_first() {
+        let (tree, _) = grid();
+        let ray = Ray3d::new(vec3(-5.0, 0.5, 0.5), vec3(1.0, 0.0, 0.0), 100.0);
+        let hits = tree.raycast(&ray);
+        assert_eq!(hits.iter().map(|(data, _)| *data).collect::<Vec<usize>>(), vec![0, 1, 2, 3, 4]);
+        assert!((hits[0].1 - 4.9).abs() < 1e-4);
+        assert!(hits.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,402 @@
+use crate::math::vec3::Vec3;
+use crate::math::quaternion::Quat;
+use crate::math::ray3d::Ray3d;
+use super::contact;
+use super::shape::Shape;
+use super::world::CollisionWorld;
+
+// slides and step attempts per move before what's left of it is dropped
+const MAX_SLIDES: u32 = 4;
+// passes pushing the capsule out of what it overlaps
+const MAX_DEPENETRATIONS: u32 = 4;
+
+// something the character ran into or stands on
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharacterHit {
+    pub collider: usize,
+    pub point: Vec3,
+    // of the surface, towards the character
+    pub normal: Vec3
+}
+
+// what happened during an update
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharacterResult {
+    // standing on a surface no steeper than the max slope
+    pub grounded: bool,
+    pub ground: Option<CharacterHit>,
+    // everything run into while moving, in order, including the ground when landing
+    pub hits: Vec<CharacterHit>,
+    pub hit_wall: bool,
+    pub hit_ceiling: bool,
+    // climbed a ledge no higher than the step height
+    pub stepped: bool,
+    // how far the character moved, including being carried by a platform
+    pub displacement: Vec3
+}
+
+// the ground the character stood on after the last update, and where that was then. when it has moved
+// since, the character is carried along
+#[derive(Debug, Clone, PartialEq)]
+struct Platform {
+    collider: usize,
+    position: Vec3,
+    rotation: Quat
+}
+
+// an upright capsule moved by sweeping it through a collision world rather than by forces. it walks up
+// and down slopes up to max_slope, climbs ledges up to step_height, slides along walls, stays on the
+// ground going down slopes and stairs, and rides whatever it stands on. y is up
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharacterController {
+    // of the capsule's center
+    pub position: Vec3,
+    pub radius: f32,
+    // from the center to either cap's center
+    pub half_height: f32,
+    // steepest walkable slope, in radians
+    pub max_slope: f32,
+    pub step_height: f32,
+    // gap kept to everything, so the capsule never starts a sweep touching something
+    pub skin_width: f32,
+    // how far down the character is pulled to stay on the ground, going down slopes and steps
+    pub snap_distance: f32,
+    // downward acceleration while in the air, in units per second squared
+    pub gravity: f32,
+    pub jump_speed: f32,
+    // upwards, in units per second
+    pub vertical_velocity: f32,
+    // bits of the collision layers it collides with
+    pub mask: u32,
+    grounded: bool,
+    jumped: bool,
+    platform: Option<Platform>
+}
+
+impl CharacterController {
+    pub fn new(position: Vec3, radius: f32, half_height: f32) -> CharacterController {
+        CharacterController {
+            position,
+            radius,
+            half_height,
+            max_slope: 45f32.to_radians(),
+            step_height: 0.3,
+            skin_width: 0.02,
+            snap_distance: 0.3,
+            gravity: 9.81,
+            jump_speed: 5.0,
+            vertical_velocity: 0.0,
+            mask: std::u32::MAX,
+            grounded: false,
+            jumped: false,
+            platform: None
+        }
+    }
+
+    pub fn shape(&self) -> Shape {
+        Shape::Capsule { radius: self.radius, half_height: self.half_height }
+    }
+
+    // as of the last update
+    pub fn is_grounded(&self) -> bool {
+        self.grounded
+    }
+
+    // leaves the ground on the next update, returns false in the air
+    pub fn jump(&mut self) -> bool {
+        if !self.grounded {
+            return false;
+        }
+        self.vertical_velocity = self.jump_speed;
+        self.grounded = false;
+        self.jumped = true;
+        true
+    }
+
+    // teleports the character, it starts out falling
+    pub fn set_position(&mut self, position: Vec3) {
+        self.position = position;
+        self.vertical_velocity = 0.0;
+        self.grounded = false;
+        self.platform = None;
+    }
+
+    // moves the character for dt seconds, walking at walk units per second along the ground. walk's y is
+    // ignored, uphill and downhill walking keeps the horizontal speed
+    pub fn update(&mut self, world: &CollisionWorld, walk: &Vec3, dt: f32) -> CharacterResult {
+        let start = self.position.clone();
+        let mut result = CharacterResult {
+            grounded: false,
+            ground: None,
+            hits: vec![],
+            hit_wall: false,
+            hit_ceiling: false,
+            stepped: false,
+            displacement: Vec3 { x: 0.0, y: 0.0, z: 0.0 }
+        };
+
+        self.ride_platform(world);
+        self.depenetrate(world);
+
+        let was_grounded = self.grounded;
+        let jumped = std::mem::replace(&mut self.jumped, false);
+        let ground_normal = self.ground_normal(world);
+        if was_grounded && !jumped {
+            self.vertical_velocity = 0.0;
+        } else {
+            self.vertical_velocity -= self.gravity * dt;
+        }
+
+        // walking follows the ground's plane, so going downhill doesn't launch the character
+        let walk = Vec3 { x: walk.x * dt, y: 0.0, z: walk.z * dt };
+        let walk = match ground_normal.filter(|_| was_grounded && !jumped) {
+            Some(normal) => Vec3 { x: walk.x, y: -(normal.x * walk.x + normal.z * walk.z) / normal.y, z: walk.z },
+            None => walk
+        };
+        self.slide(world, walk, was_grounded && !jumped, &mut result);
+        self.slide(world, Vec3 { x: 0.0, y: self.vertical_velocity * dt, z: 0.0 }, false, &mut result);
+
+        // stays on the ground unless it left it on purpose, and lands right on it
+        if self.vertical_velocity <= 0.0 {
+            let probe = if was_grounded && !jumped { self.snap_distance } else { self.skin_width * 2.0 };
+            if let Some(ground) = self.find_ground(world, probe) {
+                self.position.y -= self.sweep_distance(world, &Vec3 { x: 0.0, y: -probe, z: 0.0 });
+                result.ground = Some(ground);
+            }
+        }
+
+        self.grounded = result.ground.is_some();
+        if self.grounded {
+            self.vertical_velocity = 0.0;
+        }
+        self.platform = result.ground.as_ref().and_then(|ground| {
+            world.collider(ground.collider).map(|collider| Platform {
+                collider: ground.collider,
+                position: collider.position.clone(),
+                rotation: collider.rotation.clone()
+            })
+        });
+        result.grounded = self.grounded;
+        result.displacement = self.position.clone() - start;
+        result
+    }
+
+    /*********************************
+    *** MOVING
+    *********************************/
+
+    // moves by motion, sliding along what's in the way. walking into a wall tries to step over it first
+    fn slide(&mut self, world: &CollisionWorld, motion: Vec3, walking: bool, result: &mut CharacterResult) {
+        let min_ground_y = self.max_slope.cos();
+        let mut remaining = motion;
+        for _ in 0..MAX_SLIDES {
+            let length = remaining.lenght();
+            if length < 1e-6 {
+                return;
+            }
+            let (collider, hit) = match world.sweep(&self.shape(), &self.position, &Quat::identity(), &remaining, self.mask) {
+                // moving away from what it's touching
+                Some((_, ref hit)) if hit.normal.dot(remaining.clone()) >= 0.0 => {
+                    self.position += remaining;
+                    return;
+                }
+                Some(found) => found,
+                None => {
+                    self.position += remaining;
+                    return;
+                }
+            };
+
+            let direction = remaining.clone() / length;
+            let travel = (hit.fraction * length - self.skin_width / (-direction.dot(hit.normal.clone())).max(0.1)).max(0.0);
+            self.position += direction * travel;
+            let left = remaining.clone() * (1.0 - travel / length);
+
+            let normal = hit.normal.clone();
+            let vertical = remaining.y.abs() > (remaining.x * remaining.x + remaining.z * remaining.z).sqrt();
+            result.hits.push(CharacterHit { collider, point: hit.point.clone(), normal: normal.clone() });
+            if normal.y >= min_ground_y {
+                // landed, or a walkable slope takes the motion along it
+                if vertical && remaining.y < 0.0 {
+                    return;
+                }
+                remaining = left.clone() - normal.clone() * left.dot(normal);
+                continue;
+            }
+            if normal.y < -1e-3 && remaining.y > 0.0 {
+                result.hit_ceiling = true;
+                self.vertical_velocity = self.vertical_velocity.min(0.0);
+            } else {
+                result.hit_wall = true;
+            }
+            if walking && self.try_step(world, &left) {
+                result.stepped = true;
+                return;
+            }
+
+            // walls and steep slopes only take walking sideways, never up them. falling slides down them
+            let normal = if !vertical && normal.y > -1e-3 {
+                let flat = Vec3 { x: normal.x, y: 0.0, z: normal.z };
+                if flat.lenght() > 1e-6 { flat.normalized() } else { normal }
+            } else {
+                normal
+            };
+            remaining = left.clone() - normal.clone() * left.dot(normal);
+        }
+    }
+
+    // up by the step height, across, and back down onto walkable ground. the character stays put if any
+    // of that fails
+    fn try_step(&mut self, world: &CollisionWorld, motion: &Vec3) -> bool {
+        let across = Vec3 { x: motion.x, y: 0.0, z: motion.z };
+        if self.step_height <= 0.0 || across.lenght() < 1e-6 {
+            return false;
+        }
+        let start = self.position.clone();
+
+        let up = self.sweep_distance(world, &Vec3 { x: 0.0, y: self.step_height, z: 0.0 });
+        self.position.y += up;
+        let moved = self.sweep_distance(world, &across);
+        if moved < 1e-4 {
+            self.position = start;
+            return false;
+        }
+        self.position += across.normalized() * moved;
+
+        let drop = up + self.skin_width;
+        if self.find_ground(world, drop).is_none() {
+            self.position = start;
+            return false;
+        }
+        self.position.y -= self.sweep_distance(world, &Vec3 { x: 0.0, y: -drop, z: 0.0 });
+        true
+    }
+
+    // how far the capsule can go along motion keeping the skin width to what's in the way
+    fn sweep_distance(&self, world: &CollisionWorld, motion: &Vec3) -> f32 {
+        let length = motion.lenght();
+        match world.sweep(&self.shape(), &self.position, &Quat::identity(), motion, self.mask) {
+            Some((_, hit)) if hit.normal.dot(motion.clone()) < 0.0 => {
+                let direction = motion.clone() / length;
+                (hit.fraction * length - self.skin_width / (-direction.dot(hit.normal)).max(0.1)).max(0.0)
+            }
+            _ => length
+        }
+    }
+
+    /*********************************
+    *** GROUND
+    *********************************/
+
+    // walkable ground at most distance below the capsule. resting on the edge of a ledge gives the rounded
+    // normal of the edge, so the surface just past the edge decides
+    fn find_ground(&self, world: &CollisionWorld, distance: f32) -> Option<CharacterHit> {
+        let min_ground_y = self.max_slope.cos();
+        let motion = Vec3 { x: 0.0, y: -distance, z: 0.0 };
+        let (collider, hit) = world.sweep(&self.shape(), &self.position, &Quat::identity(), &motion, self.mask)?;
+        if hit.normal.y >= min_ground_y {
+            return Some(CharacterHit { collider, point: hit.point, normal: hit.normal });
+        }
+
+        let outwards = Vec3 { x: hit.point.x - self.position.x, y: 0.0, z: hit.point.z - self.position.z };
+        if outwards.lenght() < 1e-6 {
+            return None;
+        }
+        let probe = self.skin_width * 2.0;
+        let origin = hit.point.clone() + outwards.normalized() * probe + Vec3 { x: 0.0, y: probe, z: 0.0 };
+        let (_, surface) = world.raycast(&Ray3d::new(origin, Vec3 { x: 0.0, y: -1.0, z: 0.0 }, probe * 2.0), self.mask)?;
+        // starting inside something means the steep surface goes on past the point, there's no edge
+        if surface.distance <= 0.0 || surface.normal.y < min_ground_y {
+            return None;
+        }
+        Some(CharacterHit { collider, point: hit.point, normal: surface.normal })
+    }
+
+    fn ground_normal(&self, world: &CollisionWorld) -> Option<Vec3> {
+        self.find_ground(world, self.skin_width * 2.0).map(|ground| ground.normal)
+    }
+
+    // follows the platform from where it was after the last update to where it is now
+    fn ride_platform(&mut self, world: &CollisionWorld) {
+        let platform = match self.platform.take() {
+            Some(platform) => platform,
+            None => return
+        };
+        if let Some(collider) = world.collider(platform.collider) {
+            let local = platform.rotation.conjugated().rotate(&(self.position.clone() - platform.position));
+            self.position = collider.rotation.rotate(&local) + collider.position.clone();
+        }
+    }
+
+    // pushes the capsule out of everything it overlaps, to the skin width
+    fn depenetrate(&mut self, world: &CollisionWorld) {
+        let shape = self.shape();
+        let rotation = Quat::identity();
+        for _ in 0..MAX_DEPENETRATIONS {
+            let aabb = shape.aabb(&self.position, &rotation).expanded(self.skin_width);
+            // the deepest overlap first, pushing out of it can take the capsule out of the others too
+            let mut deepest: Option<(Vec3, f32)> = None;
+            for index in world.overlap_aabb(&aabb, self.mask).into_iter() {
+                let collider = world.collider(index).expect("failed to find a collider");
+                let manifolds = contact::collide(&shape, &self.position, &rotation, &collider.shape, &collider.position,
+                                                 &collider.rotation, self.skin_width);
+                for manifold in manifolds.into_iter() {
+                    let depth = manifold.points.iter().map(|point| point.depth).fold(std::f32::MIN, f32::max) + self.skin_width;
+                    if depth > 1e-4 && deepest.as_ref().map(|(_, deepest)| depth > *deepest).unwrap_or(true) {
+                        deepest = Some((manifold.normal, depth));
+                    }
+                }
+            }
+            match deepest {
+                // normals point from the capsule to the collider
+                Some((normal, depth)) => self.position -= normal * depth,
+                None => return
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::world::Collider;
+
+    fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    // a floor with its top at y = 0 and a slope of degrees rising towards +x from x = 1
+    fn floor_and_slope(degrees: f32) -> CollisionWorld {
+        let mut world = CollisionWorld::new();
+        world.add(Collider::new(Shape::Box { half_extents: vec3(20.0, 0.5, 20.0) }, vec3(0.0, -0.5, 0.0), Quat::identity()));
+        let rotation = Quat::from_axis_angle(&vec3(0.0, 0.0, 1.0), degrees);
+        // the top face goes through (1, 0, 0)
+        let normal = rotation.rotate(&vec3(0.0, 1.0, 0.0));
+        world.add(Collider::new(Shape::Box { half_extents: vec3(5.0, 5.0, 5.0) }, vec3(1.0, 0.0, 0.0) - normal * 5.0, rotation));
+        world
+    }
+
+    fn walk_into_slope(degrees: f32) -> (CharacterController, f32) {
+        let world = floor_and_slope(degrees);
+        let mut character = CharacterController::new(vec3(0.0, 0.82, 0.0), 0.3, 0.5);
+        let start = character.position.y;
+        for _ in 0..120 {
+            character.update(&world, &vec3(2.0, 0.0, 0.0), 1.0 / 60.0);
+        }
+        (character, start)
+    }
+
+    #[test]
+    fn walks_up_walkable_slopes() {
+        let (character, start) = walk_into_slope(30.0);
+        assert!(character.position.y > start + 0.5, "stayed at {}", character.position.y);
+        assert!(character.is_grounded());
+    }
+
+    #[test]
+    fn steep_slopes_are_not_climbed() {
+        let (character, start) = walk_into_slope(60.0);
+        assert!(character.position.y < start + 0.1, "climbed to {}", character.position.y);
+        assert!(character.position.x < 1.0);
+    }
+}
\ No newline at end of file
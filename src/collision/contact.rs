@@ -0,0 +1,551 @@
+use crate::math::vec3::Vec3;
+use crate::math::quaternion::Quat;
+use super::shape::{Shape, Convex, ConvexHull, HullFace, TriangleMesh};
+use super::gjk;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContactPoint {
+    // halfway between the two surfaces
+    pub position: Vec3,
+    // overlap along the normal, negative while the shapes are still apart
+    pub depth: f32
+}
+
+// where two shapes touch, all points share one normal
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifold {
+    // from the first shape towards the second
+    pub normal: Vec3,
+    pub points: Vec<ContactPoint>
+}
+
+impl Manifold {
+    fn flipped(mut self) -> Manifold {
+        self.normal = self.normal * -1.0;
+        self
+    }
+}
+
+// contacts between two shapes placed in the world, normals point from a towards b. shapes less than margin
+// apart already get contacts, with a negative depth, so the solver can stop them before they overlap.
+// a triangle mesh gives a manifold for every triangle touched, two meshes never collide
+pub fn collide(a: &Shape, position_a: &Vec3, rotation_a: &Quat, b: &Shape, position_b: &Vec3, rotation_b: &Quat,
+               margin: f32) -> Vec<Manifold> {
+    match (a.convex(position_a, rotation_a), b.convex(position_b, rotation_b), a, b) {
+        (Some(convex_a), Some(convex_b), _, _) => collide_convex(&convex_a, &convex_b, margin).into_iter().collect(),
+        (Some(convex_a), None, _, Shape::TriangleMesh(mesh)) => collide_mesh(&convex_a, mesh, position_b, rotation_b, margin),
+        (None, Some(convex_b), Shape::TriangleMesh(mesh), _) => {
+            collide_mesh(&convex_b, mesh, position_a, rotation_a, margin).into_iter().map(Manifold::flipped).collect()
+        }
+        _ => vec![]
+    }
+}
+
+// spheres and capsules against each other have their own closed form, polyhedra against each other are
+// separating axis tests, and a round shape against a polyhedron goes through gjk, with epa once they overlap
+fn collide_convex(a: &Convex, b: &Convex, margin: f32) -> Option<Manifold> {
+    match (a, b) {
+        (Convex::Round(a0, a1, ra), Convex::Round(b0, b1, rb)) => round_round(a0, a1, *ra, b0, b1, *rb, margin),
+        (Convex::Hull(hull), Convex::Round(..)) => hull_round(hull, a, b, margin),
+        (Convex::Round(..), Convex::Hull(hull)) => hull_round(hull, b, a, margin).map(Manifold::flipped),
+        (Convex::Hull(hull_a), Convex::Hull(hull_b)) => hull_hull(hull_a, hull_b, margin)
+    }
+}
+
+// the contact of surface points on a and b along a normal from a to b
+fn contact(on_a: Vec3, on_b: Vec3, normal: &Vec3) -> ContactPoint {
+    ContactPoint {
+        depth: (on_a.clone() - on_b.clone()).dot(normal.clone()),
+        position: (on_a + on_b) * 0.5
+    }
+}
+
+/*********************************
+*** ROUND SHAPES
+*********************************/
+
+fn round_round(a0: &Vec3, a1: &Vec3, ra: f32, b0: &Vec3, b1: &Vec3, rb: f32, margin: f32) -> Option<Manifold> {
+    let (on_a, on_b) = closest_segments(a0, a1, b0, b1);
+    let delta = on_b.clone() - on_a.clone();
+    let distance = delta.lenght();
+    if distance > ra + rb + margin {
+        return None;
+    }
+
+    let normal = if distance > 1e-6 {
+        delta / distance
+    } else {
+        // the cores cross, push apart across both segments or between the centers
+        let center_delta = (b0.clone() + b1.clone() - a0.clone() - a1.clone()) * 0.5;
+        let across = (a1.clone() - a0.clone()).cross(b1.clone() - b0.clone());
+        if across.lenght() > 1e-6 {
+            let across = across.normalized();
+            if across.dot(center_delta) < 0.0 { across * -1.0 } else { across }
+        } else if center_delta.lenght() > 1e-6 {
+            center_delta.normalized()
+        } else {
+            Vec3 { x: 0.0, y: 1.0, z: 0.0 }
+        }
+    };
+
+    // capsules lying side by side touch along a line, its two ends keep them from rolling about one point
+    let mut points = vec![];
+    let (along_a, along_b) = (a1.clone() - a0.clone(), b1.clone() - b0.clone());
+    if along_a.lenght() > 1e-6 && along_b.lenght() > 1e-6 && along_a.normalized().dot(along_b.normalized()).abs() > 0.98 {
+        let axis = along_a.normalized();
+        let (t0, t1) = (axis.dot(b0.clone() - a0.clone()), axis.dot(b1.clone() - a0.clone()));
+        let (low, high) = (t0.min(t1).max(0.0), t0.max(t1).min(along_a.lenght()));
+        if high - low > 1e-4 {
+            for t in [low, high].iter() {
+                let p = a0.clone() + axis.clone() * *t;
+                let q = closest_point_segment(&p, b0, b1);
+                let point = contact(p + normal.clone() * ra, q - normal.clone() * rb, &normal);
+                if point.depth > -margin {
+                    points.push(point);
+                }
+            }
+        }
+    }
+    if points.is_empty() {
+        points.push(contact(on_a + normal.clone() * ra, on_b - normal.clone() * rb, &normal));
+    }
+
+    Some(Manifold { normal, points })
+}
+
+// a sphere or capsule against a polyhedron, the normal points from the polyhedron to the round shape
+fn hull_round(hull: &ConvexHull, hull_convex: &Convex, round: &Convex, margin: f32) -> Option<Manifold> {
+    let (p0, p1, radius) = match round {
+        Convex::Round(p0, p1, radius) => (p0, p1, *radius),
+        Convex::Hull(_) => return None
+    };
+
+    let separated = gjk::closest_points(hull_convex, round)
+        .map(|(on_hull, on_segment)| (on_hull.distance_to(on_segment.clone()), on_hull, on_segment))
+        .filter(|(distance, _, _)| *distance > 1e-6);
+    let (distance, on_hull, on_segment) = match separated {
+        Some(closest) => closest,
+        None => {
+            // the segment reaches into the hull, epa finds the way out and the separating axes stand in
+            // when the overlap is too flat for it
+            let normal = gjk::penetration(hull_convex, round)
+                .map(|(normal, ..)| normal)
+                .unwrap_or_else(|| deepest_axis(hull, p0, p1, radius));
+            return Some(deep_hull_round(hull, p0, p1, radius, normal));
+        }
+    };
+    if distance > radius + margin {
+        return None;
+    }
+    let normal = (on_segment.clone() - on_hull.clone()) / distance;
+
+    // a capsule lying flat on a face touches along a line, clipped to the face
+    let along = p1.clone() - p0.clone();
+    let face = hull.faces.iter().find(|face| normal.dot(face.normal.clone()) > 0.999);
+    if let Some(face) = face {
+        if along.lenght() > 1e-6 && along.normalized().dot(face.normal.clone()).abs() < 0.1 {
+            if let Some((q0, q1)) = clip_segment_to_face(hull, face, p0, p1) {
+                let points: Vec<ContactPoint> = [q0, q1].iter()
+                    .map(|q| {
+                        let separation = face.normal.dot(q.clone()) - face.distance;
+                        contact(q.clone() - face.normal.clone() * separation, q.clone() - face.normal.clone() * radius, &face.normal)
+                    })
+                    .filter(|point| point.depth > -margin)
+                    .collect();
+                if !points.is_empty() {
+                    return Some(Manifold { normal: face.normal.clone(), points });
+                }
+            }
+        }
+    }
+
+    Some(Manifold {
+        points: vec![contact(on_hull, on_segment - normal.clone() * radius, &normal)],
+        normal
+    })
+}
+
+// the axis of least overlap between a hull and a segment reaching into it, over the hull's faces and the
+// segment against its edges
+fn deepest_axis(hull: &ConvexHull, p0: &Vec3, p1: &Vec3, radius: f32) -> Vec3 {
+    let center = hull_center(hull);
+    let middle = (p0.clone() + p1.clone()) * 0.5;
+    let along = p1.clone() - p0.clone();
+
+    let mut axes: Vec<Vec3> = hull.faces.iter().map(|face| face.normal.clone()).collect();
+    if along.lenght() > 1e-6 {
+        for (a, b) in hull.edges.iter() {
+            let axis = along.cross(hull.vertices[*b].clone() - hull.vertices[*a].clone());
+            if axis.lenght() > 1e-6 {
+                let axis = axis.normalized();
+                axes.push(if axis.dot(middle.clone() - center.clone()) < 0.0 { axis * -1.0 } else { axis });
+            }
+        }
+    }
+
+    let mut best = (std::f32::MAX, Vec3 { x: 0.0, y: 1.0, z: 0.0 });
+    for axis in axes.into_iter() {
+        let hull_max = hull.vertices.iter().map(|v| axis.dot(v.clone())).fold(std::f32::MIN, f32::max);
+        let depth = hull_max - axis.dot(p0.clone()).min(axis.dot(p1.clone())) + radius;
+        if depth < best.0 {
+            best = (depth, axis);
+        }
+    }
+    best.1
+}
+
+// contacts of a segment reaching into a hull, pushed out along normal
+fn deep_hull_round(hull: &ConvexHull, p0: &Vec3, p1: &Vec3, radius: f32, normal: Vec3) -> Manifold {
+    let hull_max = hull.vertices.iter().map(|v| normal.dot(v.clone())).fold(std::f32::MIN, f32::max);
+    let along = p1.clone() - p0.clone();
+    let ends = if along.lenght() > 1e-6 { vec![p0.clone(), p1.clone()] } else { vec![p0.clone()] };
+    let mut points: Vec<ContactPoint> = ends.into_iter()
+        .map(|p| {
+            let on_round = p.clone() - normal.clone() * radius;
+            let depth = hull_max - normal.dot(p) + radius;
+            contact(on_round.clone() + normal.clone() * depth, on_round, &normal)
+        })
+        .filter(|point| point.depth > 0.0)
+        .collect();
+    if points.is_empty() {
+        let middle = (p0.clone() + p1.clone()) * 0.5;
+        let on_round = middle.clone() - normal.clone() * radius;
+        let depth = hull_max - normal.dot(middle) + radius;
+        points.push(contact(on_round.clone() + normal.clone() * depth, on_round, &normal));
+    }
+    Manifold { normal, points }
+}
+
+// the part of a segment above a face that's within the face's edges
+fn clip_segment_to_face(hull: &ConvexHull, face: &HullFace, p0: &Vec3, p1: &Vec3) -> Option<(Vec3, Vec3)> {
+    let (mut t0, mut t1) = (0.0f32, 1.0f32);
+    let along = p1.clone() - p0.clone();
+    let corners = &face.vertices;
+    for k in 0..corners.len() {
+        let (a, b) = (&hull.vertices[corners[k]], &hull.vertices[corners[(k + 1) % corners.len()]]);
+        let outwards = (b.clone() - a.clone()).cross(face.normal.clone());
+        let start = outwards.dot(p0.clone() - a.clone());
+        let rate = outwards.dot(along.clone());
+        if rate.abs() < 1e-9 {
+            if start > 0.0 {
+                return None;
+            }
+        } else if rate > 0.0 {
+            t1 = t1.min(-start / rate);
+        } else {
+            t0 = t0.max(-start / rate);
+        }
+    }
+    if t1 - t0 < 1e-4 {
+        return None;
+    }
+    Some((p0.clone() + along.clone() * t0, p0.clone() + along * t1))
+}
+
+/*********************************
+*** POLYHEDRA
+*********************************/
+
+// separating axis test over the face normals of both hulls and the cross products of their edges. the
+// axis of least overlap decides the contact: a face clips the other hull's most opposed face against its
+// edges, two edges touch where they pass closest
+fn hull_hull(a: &ConvexHull, b: &ConvexHull, margin: f32) -> Option<Manifold> {
+    let face_axis = |reference: &ConvexHull, incident: &ConvexHull| {
+        let mut best = (std::f32::MIN, 0);
+        for (i, face) in reference.faces.iter().enumerate() {
+            let separation = incident.vertices.iter().map(|v| face.normal.dot(v.clone())).fold(std::f32::MAX, f32::min) - face.distance;
+            if separation > best.0 {
+                best = (separation, i);
+            }
+        }
+        best
+    };
+
+    let face_a = face_axis(a, b);
+    if face_a.0 > margin {
+        return None;
+    }
+    let face_b = face_axis(b, a);
+    if face_b.0 > margin {
+        return None;
+    }
+
+    let (center_a, center_b) = (hull_center(a), hull_center(b));
+    let mut edge: (f32, Vec3, Vec3, Vec3) = (std::f32::MIN, center_a.clone(), center_a.clone(), center_a.clone());
+    for direction_a in edge_directions(a).iter() {
+        for direction_b in edge_directions(b).iter() {
+            let axis = direction_a.cross(direction_b.clone());
+            if axis.lenght() < 1e-5 {
+                continue;
+            }
+            let axis = axis.normalized();
+            let axis = if axis.dot(center_b.clone() - center_a.clone()) < 0.0 { axis * -1.0 } else { axis };
+            let max_a = a.vertices.iter().map(|v| axis.dot(v.clone())).fold(std::f32::MIN, f32::max);
+            let min_b = b.vertices.iter().map(|v| axis.dot(v.clone())).fold(std::f32::MAX, f32::min);
+            let separation = min_b - max_a;
+            if separation > margin {
+                return None;
+            }
+            if separation > edge.0 {
+                edge = (separation, axis, direction_a.clone(), direction_b.clone());
+            }
+        }
+    }
+
+    // faces give steadier contacts, edges only win by a clear distance
+    let best_face = face_a.0.max(face_b.0);
+    if edge.0 > best_face + 0.005 {
+        let (_, axis, direction_a, direction_b) = edge;
+        let (a0, a1) = support_edge(a, &direction_a, &axis);
+        let (b0, b1) = support_edge(b, &direction_b, &(axis.clone() * -1.0));
+        let (on_a, on_b) = closest_segments(&a0, &a1, &b0, &b1);
+        return Some(Manifold {
+            points: vec![contact(on_a, on_b, &axis)],
+            normal: axis
+        });
+    }
+
+    if face_b.0 > face_a.0 + 0.001 {
+        clip_faces(b, face_b.1, a, margin).map(Manifold::flipped)
+    } else {
+        clip_faces(a, face_a.1, b, margin)
+    }
+}
+
+// contacts of a reference face with the incident hull, the normal is the reference face's
+fn clip_faces(reference: &ConvexHull, face: usize, incident: &ConvexHull, margin: f32) -> Option<Manifold> {
+    let face = &reference.faces[face];
+    let normal = face.normal.clone();
+    let incident_face = incident.faces.iter()
+        .min_by(|a, b| a.normal.dot(normal.clone()).partial_cmp(&b.normal.dot(normal.clone())).unwrap_or(std::cmp::Ordering::Equal))
+        .expect("failed to find an incident face");
+
+    // sutherland hodgman against the planes through the reference face's edges
+    let mut polygon: Vec<Vec3> = incident_face.vertices.iter().map(|v| incident.vertices[*v].clone()).collect();
+    let corners = &face.vertices;
+    for k in 0..corners.len() {
+        let (a, b) = (&reference.vertices[corners[k]], &reference.vertices[corners[(k + 1) % corners.len()]]);
+        let outwards = (b.clone() - a.clone()).cross(normal.clone());
+        let offset = outwards.dot(a.clone());
+        let mut clipped = vec![];
+        for i in 0..polygon.len() {
+            let (p, q) = (&polygon[i], &polygon[(i + 1) % polygon.len()]);
+            let (dp, dq) = (outwards.dot(p.clone()) - offset, outwards.dot(q.clone()) - offset);
+            if dp <= 0.0 {
+                clipped.push(p.clone());
+            }
+            if (dp <= 0.0) != (dq <= 0.0) {
+                clipped.push(p.clone() + (q.clone() - p.clone()) * (dp / (dp - dq)));
+            }
+        }
+        polygon = clipped;
+        if polygon.is_empty() {
+            return None;
+        }
+    }
+
+    let points: Vec<ContactPoint> = polygon.into_iter()
+        .map(|p| {
+            let separation = normal.dot(p.clone()) - face.distance;
+            contact(p.clone() - normal.clone() * separation, p, &normal)
+        })
+        .filter(|point| point.depth > -margin)
+        .collect();
+    if points.is_empty() {
+        return None;
+    }
+    Some(Manifold {
+        points: reduce_points(points),
+        normal
+    })
+}
+
+// at most four points, the deepest and the ones spanning the largest area with it
+fn reduce_points(mut points: Vec<ContactPoint>) -> Vec<ContactPoint> {
+    if points.len() <= 4 {
+        return points;
+    }
+    let take_best = |points: &mut Vec<ContactPoint>, score: &dyn Fn(&ContactPoint) -> f32| {
+        let best = (0..points.len())
+            .max_by(|a, b| score(&points[*a]).partial_cmp(&score(&points[*b])).unwrap_or(std::cmp::Ordering::Equal))
+            .expect("failed to pick a contact point");
+        points.remove(best)
+    };
+
+    let first = take_best(&mut points, &|p| p.depth);
+    let second = take_best(&mut points, &|p| p.position.distance_to(first.position.clone()));
+    let third = take_best(&mut points, &|p| {
+        (second.position.clone() - first.position.clone()).cross(p.position.clone() - first.position.clone()).lenght()
+    });
+    let fourth = take_best(&mut points, &|p| {
+        p.position.distance_to(first.position.clone()) + p.position.distance_to(second.position.clone())
+            + p.position.distance_to(third.position.clone())
+    });
+    vec![first, second, third, fourth]
+}
+
+fn hull_center(hull: &ConvexHull) -> Vec3 {
+    let mut sum = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+    for v in hull.vertices.iter() {
+        sum += v.clone();
+    }
+    sum / hull.vertices.len().max(1) as f32
+}
+
+// unit direction of every edge, parallel edges only once
+fn edge_directions(hull: &ConvexHull) -> Vec<Vec3> {
+    let mut ret: Vec<Vec3> = vec![];
+    for (a, b) in hull.edges.iter() {
+        let direction = (hull.vertices[*b].clone() - hull.vertices[*a].clone()).normalized();
+        if !ret.iter().any(|d| d.dot(direction.clone()).abs() > 0.9999) {
+            ret.push(direction);
+        }
+    }
+    ret
+}
+
+// the edge along a direction that lies farthest along axis
+fn support_edge(hull: &ConvexHull, direction: &Vec3, axis: &Vec3) -> (Vec3, Vec3) {
+    hull.edges.iter()
+        .map(|(a, b)| (&hull.vertices[*a], &hull.vertices[*b]))
+        .filter(|(a, b)| ((*b).clone() - (*a).clone()).normalized().dot(direction.clone()).abs() > 0.9999)
+        .max_by(|x, y| {
+            let score = |e: &(&Vec3, &Vec3)| axis.dot(e.0.clone()).min(axis.dot(e.1.clone()));
+            score(x).partial_cmp(&score(y)).unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .map(|(a, b)| (a.clone(), b.clone()))
+        .expect("failed to find a supporting edge")
+}
+
+/*********************************
+*** TRIANGLE MESHES
+*********************************/
+
+// a convex shape against every triangle near it, normals point from the shape to the mesh
+fn collide_mesh(convex: &Convex, mesh: &TriangleMesh, position: &Vec3, rotation: &Quat, margin: f32) -> Vec<Manifold> {
+    mesh.triangles_near(&convex.aabb().expanded(margin), position, rotation)
+        .into_iter()
+        .filter_map(|triangle| collide_convex(convex, &Convex::Hull(triangle), margin))
+        .collect()
+}
+
+/*********************************
+*** CLOSEST POINTS
+*********************************/
+
+pub fn closest_point_segment(point: &Vec3, a: &Vec3, b: &Vec3) -> Vec3 {
+    let along = b.clone() - a.clone();
+    let length_squared = along.dot(along.clone());
+    if length_squared <= 1e-12 {
+        return a.clone();
+    }
+    let t = (along.dot(point.clone() - a.clone()) / length_squared).max(0.0).min(1.0);
+    a.clone() + along * t
+}
+
+// closest points between the segments p1 q1 and p2 q2, either can have both ends in the same place
+pub fn closest_segments(p1: &Vec3, q1: &Vec3, p2: &Vec3, q2: &Vec3) -> (Vec3, Vec3) {
+    let d1 = q1.clone() - p1.clone();
+    let d2 = q2.clone() - p2.clone();
+    let r = p1.clone() - p2.clone();
+    let a = d1.dot(d1.clone());
+    let e = d2.dot(d2.clone());
+    let f = d2.dot(r.clone());
+    let clamp = |x: f32| x.max(0.0).min(1.0);
+
+    let (s, t) = if a <= 1e-12 && e <= 1e-12 {
+        (0.0, 0.0)
+    } else if a <= 1e-12 {
+        (0.0, clamp(f / e))
+    } else {
+        let c = d1.dot(r);
+        if e <= 1e-12 {
+            (clamp(-c / a), 0.0)
+        } else {
+            let b = d1.dot(d2.clone());
+            let denominator = a * e - b * b;
+            let s = if denominator > 1e-12 { clamp((b * f - c * e) / denominator) } else { 0.0 };
+            let t = (b * s + f) / e;
+            if t < 0.0 {
+                (clamp(-c / a), 0.0)
+            } else if t > 1.0 {
+                (clamp((b - c) / a), 1.0)
+            } else {
+                (s, t)
+            }
+        }
+    };
+
+    (p1.clone() + d1 * s, p2.clone() + d2 * t)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn cube() -> Shape {
+        Shape::Box { half_extents: vec3(1.0, 1.0, 1.0) }
+    }
+
+    fn single(manifolds: Vec<Manifold>) -> Manifold {
+        assert_eq!(manifolds.len(), 1);
+        manifolds.into_iter().next().expect("failed to collide")
+    }
+
+    #[test]
+    fn a_box_resting_on_a_box_touches_at_its_corners() {
+        let identity = Quat::identity();
+        let manifold = single(collide(&cube(), &vec3(0.0, 0.0, 0.0), &identity, &cube(), &vec3(0.3, 1.9, 0.2), &identity, 0.0));
+        assert!((manifold.normal.clone() - vec3(0.0, 1.0, 0.0)).lenght() < 1e-4);
+        assert_eq!(manifold.points.len(), 4);
+        for point in manifold.points.iter() {
+            assert!((point.depth - 0.1).abs() < 1e-4);
+            assert!((point.position.y - 0.95).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn spheres_on_boxes_touch_at_one_point() {
+        let identity = Quat::identity();
+        let sphere = Shape::Sphere { radius: 0.5 };
+        let manifold = single(collide(&sphere, &vec3(0.2, 0.0, 1.4), &identity, &cube(), &vec3(0.0, 0.0, 0.0), &identity, 0.0));
+        // from the sphere to the box
+        assert!((manifold.normal.clone() - vec3(0.0, 0.0, -1.0)).lenght() < 1e-4);
+        assert_eq!(manifold.points.len(), 1);
+        assert!((manifold.points[0].depth - 0.1).abs() < 1e-4);
+    }
+
+    #[test]
+    fn capsules_side_by_side_touch_along_their_length() {
+        let identity = Quat::identity();
+        let capsule = Shape::Capsule { radius: 0.5, half_height: 1.0 };
+        let manifold = single(collide(&capsule, &vec3(0.0, 0.0, 0.0), &identity, &capsule, &vec3(0.9, 0.0, 0.0), &identity, 0.0));
+        assert!((manifold.normal.clone() - vec3(1.0, 0.0, 0.0)).lenght() < 1e-4);
+        assert_eq!(manifold.points.len(), 2);
+        for point in manifold.points.iter() {
+            assert!((point.depth - 0.1).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn the_margin_gives_contacts_before_touching() {
+        let identity = Quat::identity();
+        let sphere = Shape::Sphere { radius: 0.5 };
+        assert!(collide(&sphere, &vec3(0.0, 0.0, 0.0), &identity, &sphere, &vec3(1.05, 0.0, 0.0), &identity, 0.0).is_empty());
+        let manifold = single(collide(&sphere, &vec3(0.0, 0.0, 0.0), &identity, &sphere, &vec3(1.05, 0.0, 0.0), &identity, 0.1));
+        assert!((manifold.points[0].depth + 0.05).abs() < 1e-4);
+    }
+
+    #[test]
+    fn closest_points_between_segments() {
+        let (on_1, on_2) = closest_segments(&vec3(-1.0, 0.0, 0.0), &vec3(1.0, 0.0, 0.0), &vec3(0.5, 1.0, -1.0), &vec3(0.5, 1.0, 1.0));
+        assert!((on_1 - vec3(0.5, 0.0, 0.0)).lenght() < 1e-5);
+        assert!((on_2 - vec3(0.5, 1.0, 0.0)).lenght() < 1e-5);
+        assert!((closest_point_segment(&vec3(5.0, 1.0, 0.0), &vec3(-1.0, 0.0, 0.0), &vec3(1.0, 0.0, 0.0)) - vec3(1.0, 0.0, 0.0)).lenght() < 1e-5);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,481 @@
+use crate::math::vec3::Vec3;
+use super::shape::Convex;
+
+// a point of the minkowski difference a - b with the points of a and b it came from
+#[derive(Debug, Clone, PartialEq)]
+struct SimplexVertex {
+    a: Vec3,
+    b: Vec3,
+    w: Vec3
+}
+
+fn support(a: &Convex, b: &Convex, direction: &Vec3) -> SimplexVertex {
+    let on_a = a.core_support(direction);
+    let on_b = b.core_support(&(direction.clone() * -1.0));
+    SimplexVertex {
+        w: on_a.clone() - on_b.clone(),
+        a: on_a,
+        b: on_b
+    }
+}
+
+// closest points (on a, on b) between the cores of two convex shapes, none when the cores overlap. radii
+// are left to the caller, the surfaces are the radius further along the line between the points
+pub fn closest_points(a: &Convex, b: &Convex) -> Option<(Vec3, Vec3)> {
+    gjk(a, b).ok()
+}
+
+// how far two overlapping shapes reach into each other, radii included: (normal from a to b, depth,
+// deepest point of a, deepest point of b). none when they don't overlap or the overlap is too flat to
+// measure
+pub fn penetration(a: &Convex, b: &Convex) -> Option<(Vec3, f32, Vec3, Vec3)> {
+    let radius = a.radius() + b.radius();
+    match gjk(a, b) {
+        Ok((on_a, on_b)) => {
+            let delta = on_b.clone() - on_a.clone();
+            let distance = delta.lenght();
+            if distance >= radius || distance < 1e-9 {
+                return None;
+            }
+            let normal = delta / distance;
+            Some((normal.clone(), radius - distance, on_a + normal.clone() * a.radius(), on_b - normal * b.radius()))
+        }
+        Err(simplex) => {
+            let (normal, depth, on_a, on_b) = epa(a, b, simplex)?;
+            Some((normal.clone(), depth + radius, on_a + normal.clone() * a.radius(), on_b - normal * b.radius()))
+        }
+    }
+}
+
+// gilbert johnson keerthi: walks a simplex of the minkowski difference towards the origin. the closest
+// points when it can't get closer, the simplex around the origin when the cores overlap
+fn gjk(a: &Convex, b: &Convex) -> Result<(Vec3, Vec3), Vec<SimplexVertex>> {
+    let mut direction = b.center() - a.center();
+    if direction.lenght() < 1e-9 {
+        direction = Vec3 { x: 1.0, y: 0.0, z: 0.0 };
+    }
+    let mut simplex = vec![support(a, b, &(direction * -1.0))];
+    let mut weights = vec![1.0];
+
+    for _ in 0..64 {
+        let (closest, reduced) = closest_in_simplex(&simplex);
+        let kept: Vec<usize> = (0..simplex.len()).filter(|i| reduced[*i] > 0.0).collect();
+        simplex = kept.iter().map(|i| simplex[*i].clone()).collect();
+        weights = kept.iter().map(|i| reduced[*i]).collect();
+
+        let distance_squared = closest.dot(closest.clone());
+        if distance_squared < 1e-12 || simplex.len() == 4 {
+            return Err(simplex);
+        }
+
+        // the next point gets no closer, this is as close as it gets
+        let next = support(a, b, &(closest.clone() * -1.0));
+        if distance_squared - next.w.dot(closest.clone()) <= 1e-6 * distance_squared
+            || simplex.iter().any(|v| v.w.distance_to(next.w.clone()) < 1e-7) {
+            break;
+        }
+        simplex.push(next);
+    }
+
+    let mut on_a = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+    let mut on_b = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+    for (vertex, weight) in simplex.iter().zip(weights.iter()) {
+        on_a += vertex.a.clone() * *weight;
+        on_b += vertex.b.clone() * *weight;
+    }
+    Ok((on_a, on_b))
+}
+
+// the point of the simplex closest to the origin and the barycentric weight of every vertex for it,
+// vertices that don't contribute get 0
+fn closest_in_simplex(simplex: &[SimplexVertex]) -> (Vec3, Vec<f32>) {
+    let w: Vec<&Vec3> = simplex.iter().map(|v| &v.w).collect();
+    let weights = match simplex.len() {
+        1 => vec![1.0],
+        2 => {
+            let [u, v] = closest_on_segment(w[0], w[1]);
+            vec![u, v]
+        }
+        3 => closest_on_triangle(w[0], w[1], w[2]).to_vec(),
+        _ => closest_on_tetrahedron(w[0], w[1], w[2], w[3])
+    };
+
+    let mut point = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+    for (w, weight) in w.iter().zip(weights.iter()) {
+        point += (*w).clone() * *weight;
+    }
+    (point, weights)
+}
+
+fn closest_on_segment(a: &Vec3, b: &Vec3) -> [f32; 2] {
+    let along = b.clone() - a.clone();
+    let length_squared = along.dot(along.clone());
+    if length_squared < 1e-12 {
+        return [1.0, 0.0];
+    }
+    let t = -a.dot(along) / length_squared;
+    if t <= 0.0 {
+        [1.0, 0.0]
+    } else if t >= 1.0 {
+        [0.0, 1.0]
+    } else {
+        [1.0 - t, t]
+    }
+}
+
+// the voronoi regions of a triangle, as in real-time collision detection
+fn closest_on_triangle(a: &Vec3, b: &Vec3, c: &Vec3) -> [f32; 3] {
+    let ab = b.clone() - a.clone();
+    let ac = c.clone() - a.clone();
+    let (d1, d2) = (-ab.dot(a.clone()), -ac.dot(a.clone()));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return [1.0, 0.0, 0.0];
+    }
+    let (d3, d4) = (-ab.dot(b.clone()), -ac.dot(b.clone()));
+    if d3 >= 0.0 && d4 <= d3 {
+        return [0.0, 1.0, 0.0];
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        let v = d1 / (d1 - d3);
+        return [1.0 - v, v, 0.0];
+    }
+    let (d5, d6) = (-ab.dot(c.clone()), -ac.dot(c.clone()));
+    if d6 >= 0.0 && d5 <= d6 {
+        return [0.0, 0.0, 1.0];
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        let w = d2 / (d2 - d6);
+        return [1.0 - w, 0.0, w];
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return [0.0, 1.0 - w, w];
+    }
+    let denominator = va + vb + vc;
+    if denominator.abs() < 1e-12 {
+        // degenerate, fall back to the closest edge
+        let edges = [(0, 1), (1, 2), (0, 2)];
+        let points = [a, b, c];
+        let mut best = ([1.0, 0.0, 0.0], std::f32::MAX);
+        for (i, j) in edges.iter() {
+            let [u, v] = closest_on_segment(points[*i], points[*j]);
+            let point = points[*i].clone() * u + points[*j].clone() * v;
+            let distance = point.dot(point.clone());
+            if distance < best.1 {
+                let mut weights = [0.0; 3];
+                weights[*i] = u;
+                weights[*j] = v;
+                best = (weights, distance);
+            }
+        }
+        return best.0;
+    }
+    let v = vb / denominator;
+    let w = vc / denominator;
+    [1.0 - v - w, v, w]
+}
+
+// all zero when the origin is inside, otherwise the closest of the faces it's in front of
+fn closest_on_tetrahedron(a: &Vec3, b: &Vec3, c: &Vec3, d: &Vec3) -> Vec<f32> {
+    let points = [a, b, c, d];
+    let faces = [[0, 1, 2, 3], [0, 3, 1, 2], [0, 2, 3, 1], [1, 3, 2, 0]];
+    let mut best: Option<(Vec<f32>, f32)> = None;
+    for face in faces.iter() {
+        let (p, q, r, opposite) = (points[face[0]], points[face[1]], points[face[2]], points[face[3]]);
+        let normal = (q.clone() - p.clone()).cross(r.clone() - p.clone());
+        let origin_side = -normal.dot(p.clone());
+        let opposite_side = normal.dot(opposite.clone() - p.clone());
+        // the origin is on the face's side away from the fourth point
+        if origin_side * opposite_side >= 0.0 && opposite_side.abs() > 1e-12 {
+            continue;
+        }
+        let face_weights = closest_on_triangle(p, q, r);
+        let point = p.clone() * face_weights[0] + q.clone() * face_weights[1] + r.clone() * face_weights[2];
+        let distance = point.dot(point.clone());
+        if best.as_ref().map(|b| distance < b.1).unwrap_or(true) {
+            let mut weights = vec![0.0; 4];
+            for k in 0..3 {
+                weights[face[k]] = face_weights[k];
+            }
+            best = Some((weights, distance));
+        }
+    }
+    match best {
+        Some((weights, _)) => weights,
+        None => vec![0.25; 4]
+    }
+}
+
+/*********************************
+*** EPA
+*********************************/
+
+// expanding polytope: grows the simplex around the origin into a polytope that hugs the minkowski
+// difference until its face closest to the origin is on the difference's surface. that face's distance
+// is how far the cores overlap
+fn epa(a: &Convex, b: &Convex, simplex: Vec<SimplexVertex>) -> Option<(Vec3, f32, Vec3, Vec3)> {
+    let mut points = simplex;
+    let axes = [
+        Vec3 { x: 1.0, y: 0.0, z: 0.0 }, Vec3 { x: -1.0, y: 0.0, z: 0.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 }, Vec3 { x: 0.0, y: -1.0, z: 0.0 },
+        Vec3 { x: 0.0, y: 0.0, z: 1.0 }, Vec3 { x: 0.0, y: 0.0, z: -1.0 }
+    ];
+
+    // grow a smaller simplex into a tetrahedron with points off its line or plane
+    while points.len() < 4 {
+        let directions: Vec<Vec3> = match points.len() {
+            1 => axes.to_vec(),
+            2 => {
+                let along = points[1].w.clone() - points[0].w.clone();
+                let first = along.cross(axes.iter().min_by(|x, y| {
+                    x.dot(along.clone()).abs().partial_cmp(&y.dot(along.clone()).abs()).unwrap_or(std::cmp::Ordering::Equal)
+                }).expect("failed to pick an axis").clone()).normalized();
+                let second = along.normalized().cross(first.clone());
+                vec![first.clone(), first * -1.0, second.clone(), second * -1.0]
+            }
+            _ => {
+                let normal = (points[1].w.clone() - points[0].w.clone()).cross(points[2].w.clone() - points[0].w.clone()).normalized();
+                vec![normal.clone(), normal * -1.0]
+            }
+        };
+        let grows = |vertex: &SimplexVertex, points: &[SimplexVertex]| match points.len() {
+            1 => vertex.w.distance_to(points[0].w.clone()) > 1e-6,
+            2 => {
+                let along = (points[1].w.clone() - points[0].w.clone()).normalized();
+                along.cross(vertex.w.clone() - points[0].w.clone()).lenght() > 1e-6
+            }
+            _ => {
+                let normal = (points[1].w.clone() - points[0].w.clone()).cross(points[2].w.clone() - points[0].w.clone()).normalized();
+                normal.dot(vertex.w.clone() - points[0].w.clone()).abs() > 1e-6
+            }
+        };
+        let next = directions.iter().map(|direction| support(a, b, direction)).find(|vertex| grows(vertex, &points));
+        match next {
+            Some(vertex) => points.push(vertex),
+            // flat difference, there's no volume to measure
+            None => return None
+        }
+    }
+
+    let center = (points[0].w.clone() + points[1].w.clone() + points[2].w.clone() + points[3].w.clone()) * 0.25;
+    let mut faces: Vec<[usize; 3]> = vec![];
+    for face in [[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]].iter() {
+        let normal = (points[face[1]].w.clone() - points[face[0]].w.clone()).cross(points[face[2]].w.clone() - points[face[0]].w.clone());
+        faces.push(if normal.dot(points[face[0]].w.clone() - center.clone()) < 0.0 { [face[0], face[2], face[1]] } else { *face });
+    }
+
+    let face_plane = |points: &[SimplexVertex], face: &[usize; 3]| {
+        let (p, q, r) = (&points[face[0]].w, &points[face[1]].w, &points[face[2]].w);
+        let normal = (q.clone() - p.clone()).cross(r.clone() - p.clone()).normalized();
+        let distance = normal.dot(p.clone());
+        (normal, distance)
+    };
+
+    for _ in 0..64 {
+        let (closest, (normal, distance)) = faces.iter().enumerate()
+            .map(|(i, face)| (i, face_plane(&points, face)))
+            .min_by(|x, y| (x.1).1.partial_cmp(&(y.1).1).unwrap_or(std::cmp::Ordering::Equal))?;
+
+        let next = support(a, b, &normal);
+        if next.w.dot(normal.clone()) - distance < 1e-4 {
+            // the origin projected onto the closest face, carried over to a and b
+            let face = faces[closest];
+            let (p, q, r) = (&points[face[0]], &points[face[1]], &points[face[2]]);
+            let weights = barycentric(&(normal.clone() * distance), &p.w, &q.w, &r.w);
+            let on_a = p.a.clone() * weights[0] + q.a.clone() * weights[1] + r.a.clone() * weights[2];
+            let on_b = p.b.clone() * weights[0] + q.b.clone() * weights[1] + r.b.clone() * weights[2];
+            return Some((normal, distance, on_a, on_b));
+        }
+
+        // faces the new point sees make way for a fan from it to their outline
+        points.push(next);
+        let index = points.len() - 1;
+        let visible: Vec<bool> = faces.iter()
+            .map(|face| {
+                let (normal, distance) = face_plane(&points, face);
+                normal.dot(points[index].w.clone()) - distance > 1e-6
+            })
+            .collect();
+        let seen_edges: Vec<(usize, usize)> = faces.iter().zip(visible.iter())
+            .filter(|(_, visible)| **visible)
+            .flat_map(|(f, _)| vec![(f[0], f[1]), (f[1], f[2]), (f[2], f[0])])
+            .collect();
+        if seen_edges.is_empty() {
+            return None;
+        }
+        let mut kept: Vec<[usize; 3]> = faces.iter().zip(visible.iter())
+            .filter(|(_, visible)| !**visible)
+            .map(|(f, _)| *f)
+            .collect();
+        kept.extend(seen_edges.iter().filter(|(p, q)| !seen_edges.contains(&(*q, *p))).map(|(p, q)| [*p, *q, index]));
+        faces = kept;
+    }
+    None
+}
+
+// weights of a, b and c for a point on their plane
+fn barycentric(point: &Vec3, a: &Vec3, b: &Vec3, c: &Vec3) -> [f32; 3] {
+    let (v0, v1, v2) = (b.clone() - a.clone(), c.clone() - a.clone(), point.clone() - a.clone());
+    let (d00, d01, d11) = (v0.dot(v0.clone()), v0.dot(v1.clone()), v1.dot(v1.clone()));
+    let (d20, d21) = (v2.dot(v0), v2.dot(v1));
+    let denominator = d00 * d11 - d01 * d01;
+    if denominator.abs() < 1e-12 {
+        return [1.0, 0.0, 0.0];
+    }
+    let v = (d11 * d20 - d01 * d21) / denominator;
+    let w = (d00 * d21 - d01 * d20) / denominator;
+    [1.0 - v - w, v, w]
+}
+
+/*********************************
+*** SHAPE CASTS
+*********************************/
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CastHit {
+    // how much of the motion happens before the shapes touch, from 0 to 1
+    pub fraction: f32,
+    // at the touching point, from what was hit towards the moving shape
+    pub normal: Vec3,
+    pub point: Vec3
+}
+
+// first touch of a moving along motion with b standing still, by conservative advancement: a moves as far
+// as it surely can without touching b, until the gap closes. shapes that start overlapping hit at 0
+pub fn cast(a: &Convex, motion: &Vec3, b: &Convex) -> Option<CastHit> {
+    let radius = a.radius() + b.radius();
+    let tolerance = 1e-4;
+    let mut fraction = 0.0;
+    for _ in 0..32 {
+        let moved = a.translated(&(motion.clone() * fraction));
+        let (on_a, on_b) = match gjk(&moved, b) {
+            Ok(points) => points,
+            Err(_) => {
+                let (normal, _, on_a, _) = penetration(&moved, b).unwrap_or_else(|| {
+                    let normal = (b.center() - moved.center()).normalized();
+                    (normal, 0.0, moved.center(), b.center())
+                });
+                return Some(CastHit {
+                    fraction,
+                    normal: normal * -1.0,
+                    point: on_a
+                });
+            }
+        };
+
+        let delta = on_a.clone() - on_b.clone();
+        let distance = delta.lenght();
+        let normal = if distance > 1e-9 { delta / distance } else { motion.normalized() * -1.0 };
+        let gap = distance - radius;
+        if gap <= tolerance {
+            return Some(CastHit {
+                fraction,
+                point: on_b + normal.clone() * b.radius(),
+                normal
+            });
+        }
+
+        let approach = -motion.dot(normal);
+        if approach <= 1e-9 {
+            return None;
+        }
+        fraction += gap / approach;
+        if fraction > 1.0 {
+            return None;
+        }
+    }
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::quaternion::Quat;
+    use crate::collision::shape::Shape;
+
+    fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn placed(shape: Shape, position: Vec3) -> Convex {
+        shape.convex(&position, &Quat::identity()).expect("failed to make a convex shape")
+    }
+
+    fn cube(position: Vec3) -> Convex {
+        placed(Shape::Box { half_extents: vec3(1.0, 1.0, 1.0) }, position)
+    }
+
+    fn sphere(radius: f32, position: Vec3) -> Convex {
+        placed(Shape::Sphere { radius }, position)
+    }
+
+    fn assert_near(a: &Vec3, b: &Vec3) {
+        assert!((a.clone() - b.clone()).lenght() < 1e-3, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn closest_points_between_apart_shapes() {
+        let (on_a, on_b) = closest_points(&cube(vec3(0.0, 0.0, 0.0)), &sphere(0.5, vec3(3.0, 0.2, -0.4))).expect("failed to separate");
+        assert_near(&on_a, &vec3(1.0, 0.2, -0.4));
+        // a sphere's core is its center
+        assert_near(&on_b, &vec3(3.0, 0.2, -0.4));
+
+        // corner to corner
+        let (on_a, on_b) = closest_points(&cube(vec3(0.0, 0.0, 0.0)), &cube(vec3(3.0, 3.0, 3.0))).expect("failed to separate");
+        assert_near(&on_a, &vec3(1.0, 1.0, 1.0));
+        assert_near(&on_b, &vec3(2.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn overlapping_cores_have_no_closest_points() {
+        assert_eq!(closest_points(&cube(vec3(0.0, 0.0, 0.0)), &cube(vec3(1.5, 0.0, 0.0))), None);
+    }
+
+    #[test]
+    fn epa_finds_the_shallowest_way_out() {
+        let (normal, depth, on_a, on_b) = penetration(&cube(vec3(0.0, 0.0, 0.0)), &cube(vec3(0.2, 1.7, 0.1))).expect("failed to penetrate");
+        assert_near(&normal, &vec3(0.0, 1.0, 0.0));
+        assert!((depth - 0.3).abs() < 1e-3);
+        assert!((on_a.y - 1.0).abs() < 1e-3);
+        assert!((on_b.y - 0.7).abs() < 1e-3);
+    }
+
+    #[test]
+    fn penetration_includes_the_radius() {
+        // the cores are apart, the radii overlap
+        let (normal, depth, on_a, on_b) = penetration(&sphere(1.0, vec3(0.0, 0.0, 0.0)), &sphere(1.0, vec3(0.0, 0.0, 1.5))).expect("failed to penetrate");
+        assert_near(&normal, &vec3(0.0, 0.0, 1.0));
+        assert!((depth - 0.5).abs() < 1e-4);
+        assert_near(&on_a, &vec3(0.0, 0.0, 1.0));
+        assert_near(&on_b, &vec3(0.0, 0.0, 0.5));
+
+        // a sphere whose center is inside a box
+        let (normal, depth, _, _) = penetration(&cube(vec3(0.0, 0.0, 0.0)), &sphere(0.5, vec3(0.0, 0.0, -0.8))).expect("failed to penetrate");
+        assert_near(&normal, &vec3(0.0, 0.0, -1.0));
+        assert!((depth - 0.7).abs() < 1e-3);
+
+        assert_eq!(penetration(&sphere(1.0, vec3(0.0, 0.0, 0.0)), &sphere(1.0, vec3(0.0, 0.0, 2.5))), None);
+    }
+
+    #[test]
+    fn casts_stop_where_the_shapes_first_touch() {
+        let hit = cast(&sphere(0.5, vec3(-5.0, 0.0, 0.0)), &vec3(10.0, 0.0, 0.0), &cube(vec3(0.0, 0.0, 0.0))).expect("failed to hit");
+        assert!((hit.fraction - 0.35).abs() < 1e-3);
+        assert_near(&hit.normal, &vec3(-1.0, 0.0, 0.0));
+        assert_near(&hit.point, &vec3(-1.0, 0.0, 0.0));
+
+        // too short, and passing by
+        assert_eq!(cast(&sphere(0.5, vec3(-5.0, 0.0, 0.0)), &vec3(3.0, 0.0, 0.0), &cube(vec3(0.0, 0.0, 0.0))), None);
+        assert_eq!(cast(&sphere(0.5, vec3(-5.0, 2.0, 0.0)), &vec3(10.0, 0.0, 0.0), &cube(vec3(0.0, 0.0, 0.0))), None);
+    }
+
+    #[test]
+    fn casts_starting_inside_hit_at_zero() {
+        let hit = cast(&cube(vec3(0.5, 0.0, 0.0)), &vec3(0.0, -3.0, 0.0), &cube(vec3(0.0, 0.0, 0.0))).expect("failed to hit");
+        assert_eq!(hit.fraction, 0.0);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,7 @@
+pub mod aabb_tree;
+pub mod character;
+pub mod contact;
+pub mod gjk;
+pub mod raycast;
+pub mod shape;
+pub mod world;
\ No newline at end of file
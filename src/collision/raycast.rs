@@ -0,0 +1,203 @@
+use crate::math::vec3::Vec3;
+use crate::math::quaternion::Quat;
+use crate::math::ray3d::Ray3d;
+use super::shape::{Shape, ConvexHull, TriangleMesh, capsule_segment};
+use super::contact::closest_point_segment;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RayHit {
+    // along the ray, from its origin
+    pub distance: f32,
+    pub point: Vec3,
+    // of the surface that was hit, facing the ray
+    pub normal: Vec3
+}
+
+// where a ray first enters a shape placed in the world, within the ray's range. a ray starting inside a
+// solid shape hits it right away, with the normal against the ray. triangle meshes are hit from either side
+pub fn raycast(shape: &Shape, position: &Vec3, rotation: &Quat, ray: &Ray3d) -> Option<RayHit> {
+    let hit = match shape {
+        Shape::Sphere { radius } => round(position, position, *radius, ray),
+        Shape::Capsule { radius, half_height } => {
+            let (a, b) = capsule_segment(*half_height, position, rotation);
+            round(&a, &b, *radius, ray)
+        }
+        Shape::Box { half_extents } => {
+            // slabs in the box's space
+            let inverse = rotation.conjugated();
+            let origin = inverse.rotate(&(ray.origin.clone() - position.clone()));
+            let dir = inverse.rotate(&ray.dir);
+            cuboid(half_extents, &origin, &dir, ray.range).map(|(distance, normal)| (distance, rotation.rotate(&normal)))
+        }
+        Shape::ConvexHull(hull) => convex_hull(&hull.transformed(position, rotation), ray),
+        Shape::TriangleMesh(mesh) => triangle_mesh(mesh, position, rotation, ray)
+    };
+    hit.map(|(distance, normal)| RayHit {
+        point: ray.origin.clone() + ray.dir.clone() * distance,
+        distance,
+        normal
+    })
+}
+
+// a segment with a radius around it: the side of the cylinder, then the spheres at the ends
+fn round(a: &Vec3, b: &Vec3, radius: f32, ray: &Ray3d) -> Option<(f32, Vec3)> {
+    let inside = |point: &Vec3| closest_point_segment(point, a, b).distance_to(point.clone()) <= radius;
+    if inside(&ray.origin) {
+        return Some((0.0, ray.dir.clone() * -1.0));
+    }
+
+    let mut best: Option<(f32, Vec3)> = None;
+    let mut consider = |distance: f32, normal: Vec3| {
+        if distance >= 0.0 && distance <= ray.range && best.as_ref().map(|b| distance < b.0).unwrap_or(true) {
+            best = Some((distance, normal));
+        }
+    };
+
+    let along = b.clone() - a.clone();
+    let length = along.lenght();
+    if length > 1e-6 {
+        let axis = along / length;
+        // the ray and the offset from a with the parts along the axis taken out
+        let dir = ray.dir.clone() - axis.clone() * axis.dot(ray.dir.clone());
+        let offset = ray.origin.clone() - a.clone();
+        let offset = offset.clone() - axis.clone() * axis.dot(offset);
+        let qa = dir.dot(dir.clone());
+        let qb = offset.dot(dir);
+        let qc = offset.dot(offset.clone()) - radius * radius;
+        let discriminant = qb * qb - qa * qc;
+        if qa > 1e-12 && discriminant >= 0.0 {
+            let distance = (-qb - discriminant.sqrt()) / qa;
+            let point = ray.origin.clone() + ray.dir.clone() * distance;
+            let height = axis.dot(point.clone() - a.clone());
+            if height >= 0.0 && height <= length {
+                let normal = (point - (a.clone() + axis.clone() * height)).normalized();
+                consider(distance, normal);
+            }
+        }
+    }
+
+    for center in [a, b].iter() {
+        let to_origin = ray.origin.clone() - (*center).clone();
+        let qb = to_origin.dot(ray.dir.clone());
+        let discriminant = qb * qb - (to_origin.dot(to_origin.clone()) - radius * radius);
+        if discriminant >= 0.0 {
+            let distance = -qb - discriminant.sqrt();
+            let normal = (ray.origin.clone() + ray.dir.clone() * distance - (*center).clone()).normalized();
+            consider(distance, normal);
+        }
+    }
+    best
+}
+
+// a box centered on the origin, with the ray in the box's space
+fn cuboid(half_extents: &Vec3, origin: &Vec3, dir: &Vec3, range: f32) -> Option<(f32, Vec3)> {
+    let mut enter = std::f32::MIN;
+    let mut exit = range;
+    let mut normal = dir.clone() * -1.0;
+    for axis in 0..3 {
+        if dir[axis].abs() < 1e-12 {
+            if origin[axis].abs() > half_extents[axis] {
+                return None;
+            }
+            continue;
+        }
+        let inverse = 1.0 / dir[axis];
+        let t0 = (-half_extents[axis] - origin[axis]) * inverse;
+        let t1 = (half_extents[axis] - origin[axis]) * inverse;
+        let (near, far) = (t0.min(t1), t0.max(t1));
+        if near > enter {
+            enter = near;
+            normal = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+            normal[axis] = -dir[axis].signum();
+        }
+        exit = exit.min(far);
+        if enter > exit || exit < 0.0 {
+            return None;
+        }
+    }
+    if enter <= 0.0 {
+        return Some((0.0, dir.clone() * -1.0));
+    }
+    Some((enter, normal))
+}
+
+// cyrus beck: the ray enters through the last face it crosses going in, before any it crosses going out
+fn convex_hull(hull: &ConvexHull, ray: &Ray3d) -> Option<(f32, Vec3)> {
+    let mut enter = std::f32::MIN;
+    let mut exit = ray.range;
+    let mut normal = ray.dir.clone() * -1.0;
+    for face in hull.faces.iter() {
+        let start = face.normal.dot(ray.origin.clone()) - face.distance;
+        let rate = face.normal.dot(ray.dir.clone());
+        if rate.abs() < 1e-12 {
+            if start > 0.0 {
+                return None;
+            }
+            continue;
+        }
+        let t = -start / rate;
+        if rate < 0.0 {
+            if t > enter {
+                enter = t;
+                normal = face.normal.clone();
+            }
+        } else {
+            exit = exit.min(t);
+        }
+        if enter > exit || exit < 0.0 {
+            return None;
+        }
+    }
+    if enter <= 0.0 {
+        return Some((0.0, ray.dir.clone() * -1.0));
+    }
+    Some((enter, normal))
+}
+
+// the mesh's tree in its own space finds the triangles, moller trumbore hits them
+fn triangle_mesh(mesh: &TriangleMesh, position: &Vec3, rotation: &Quat, ray: &Ray3d) -> Option<(f32, Vec3)> {
+    let inverse = rotation.conjugated();
+    let local = Ray3d::new(inverse.rotate(&(ray.origin.clone() - position.clone())), inverse.rotate(&ray.dir), ray.range);
+
+    let mut best: Option<(f32, Vec3)> = None;
+    for (triangle, entry) in mesh.tree().raycast(&local).into_iter() {
+        if best.as_ref().map(|b| entry > b.0).unwrap_or(false) {
+            break;
+        }
+        let (a, b, c) = mesh.triangle(triangle);
+        if let Some((distance, normal)) = ray_triangle(&local, a, b, c) {
+            if best.as_ref().map(|b| distance < b.0).unwrap_or(true) {
+                best = Some((distance, normal));
+            }
+        }
+    }
+    best.map(|(distance, normal)| (distance, rotation.rotate(&normal)))
+}
+
+// a triangle hit from either side, the normal turned towards the ray
+fn ray_triangle(ray: &Ray3d, a: &Vec3, b: &Vec3, c: &Vec3) -> Option<(f32, Vec3)> {
+    let ab = b.clone() - a.clone();
+    let ac = c.clone() - a.clone();
+    let p = ray.dir.cross(ac.clone());
+    let determinant = ab.dot(p.clone());
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    let inverse = 1.0 / determinant;
+    let to_origin = ray.origin.clone() - a.clone();
+    let u = to_origin.dot(p) * inverse;
+    if u < 0.0 || u > 1.0 {
+        return None;
+    }
+    let q = to_origin.cross(ab.clone());
+    let v = ray.dir.dot(q.clone()) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let distance = ac.dot(q) * inverse;
+    if distance < 0.0 || distance > ray.range {
+        return None;
+    }
+    let normal = ab.cross(ac).normalized();
+    Some((distance, if normal.dot(ray.dir.clone()) > 0.0 { normal * -1.0 } else { normal }))
+}
\ No newline at end of file
@@ -0,0 +1,574 @@
+use crate::math::vec3::Vec3;
+use crate::math::mat3::Mat3;
+use crate::math::quaternion::Quat;
+use crate::math::aabb::Aabb;
+use crate::renderer::mesh::Mesh;
+use super::aabb_tree::AabbTree;
+
+use std::f32::consts::PI;
+
+// collision shape of a body, in the body's local space
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Sphere { radius: f32 },
+    Box { half_extents: Vec3 },
+    // rounded along the local y axis, half_height is the distance from the center to either cap's center
+    Capsule { radius: f32, half_height: f32 },
+    ConvexHull(ConvexHull),
+    // any triangle soup. meant for static and kinematic level geometry, two meshes never collide
+    TriangleMesh(TriangleMesh)
+}
+
+// mass, center of mass and inertia tensor around that center, all in local space
+#[derive(Debug, Clone, PartialEq)]
+pub struct MassProperties {
+    pub mass: f32,
+    pub center: Vec3,
+    pub inertia: Mat3
+}
+
+impl Shape {
+    // mass properties of the shape filled with a uniform density
+    pub fn mass_properties(&self, density: f32) -> MassProperties {
+        let zero = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+        match self {
+            Shape::Sphere { radius } => {
+                let mass = density * 4.0 / 3.0 * PI * radius.powi(3);
+                let i = 0.4 * mass * radius * radius;
+                MassProperties { mass, center: zero, inertia: Mat3::diagonal(&Vec3 { x: i, y: i, z: i }) }
+            }
+            Shape::Box { half_extents: h } => {
+                let mass = density * 8.0 * h.x * h.y * h.z;
+                let inertia = Vec3 {
+                    x: mass / 3.0 * (h.y * h.y + h.z * h.z),
+                    y: mass / 3.0 * (h.x * h.x + h.z * h.z),
+                    z: mass / 3.0 * (h.x * h.x + h.y * h.y)
+                };
+                MassProperties { mass, center: zero, inertia: Mat3::diagonal(&inertia) }
+            }
+            Shape::Capsule { radius: r, half_height } => {
+                // a cylinder and the two halves of a sphere, moved out to the ends by the parallel axis theorem
+                let h = half_height * 2.0;
+                let cylinder = density * PI * r * r * h;
+                let caps = density * 4.0 / 3.0 * PI * r.powi(3);
+                let axial = cylinder * r * r / 2.0 + caps * 0.4 * r * r;
+                let across = cylinder * (r * r / 4.0 + h * h / 12.0) + caps * (0.4 * r * r + h * h / 4.0 + 3.0 * h * r / 8.0);
+                MassProperties {
+                    mass: cylinder + caps,
+                    center: zero,
+                    inertia: Mat3::diagonal(&Vec3 { x: across, y: axial, z: across })
+                }
+            }
+            Shape::ConvexHull(hull) => {
+                let triangles = hull.faces.iter().flat_map(|face| {
+                    (1..face.vertices.len() - 1).map(move |i| [face.vertices[0], face.vertices[i], face.vertices[i + 1]])
+                });
+                polyhedron_mass(&hull.vertices, triangles, density)
+            }
+            // only right for closed meshes, open ones should be given a mass by hand
+            Shape::TriangleMesh(mesh) => polyhedron_mass(&mesh.vertices, mesh.triangles.iter().cloned(), density)
+        }
+    }
+
+    // world space bounding box of the shape at a position and rotation
+    pub fn aabb(&self, position: &Vec3, rotation: &Quat) -> Aabb {
+        match self {
+            Shape::Sphere { radius } => Aabb::new(position.clone(), position.clone()).expanded(*radius),
+            Shape::Box { half_extents } => {
+                let rotation = rotation.to_mat3();
+                let mut extents = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+                for i in 0..3 {
+                    for j in 0..3 {
+                        extents[i] += rotation[i][j].abs() * half_extents[j];
+                    }
+                }
+                Aabb::new(position.clone() - extents.clone(), position.clone() + extents)
+            }
+            Shape::Capsule { radius, half_height } => {
+                let (a, b) = capsule_segment(*half_height, position, rotation);
+                Aabb::from_points(&[a, b]).expanded(*radius)
+            }
+            Shape::ConvexHull(hull) => transformed_aabb(&hull.vertices, position, rotation),
+            Shape::TriangleMesh(mesh) => transformed_aabb(&mesh.vertices, position, rotation)
+        }
+    }
+
+    // the shape placed in the world, none for triangle meshes which aren't convex
+    pub fn convex(&self, position: &Vec3, rotation: &Quat) -> Option<Convex> {
+        match self {
+            Shape::Sphere { radius } => Some(Convex::Round(position.clone(), position.clone(), *radius)),
+            Shape::Capsule { radius, half_height } => {
+                let (a, b) = capsule_segment(*half_height, position, rotation);
+                Some(Convex::Round(a, b, *radius))
+            }
+            Shape::Box { half_extents } => Some(Convex::Hull(ConvexHull::cuboid(half_extents).transformed(position, rotation))),
+            Shape::ConvexHull(hull) => Some(Convex::Hull(hull.transformed(position, rotation))),
+            Shape::TriangleMesh(_) => None
+        }
+    }
+}
+
+fn transformed_aabb(vertices: &[Vec3], position: &Vec3, rotation: &Quat) -> Aabb {
+    let rotation = rotation.to_mat3();
+    let mut ret = Aabb::empty();
+    for vertex in vertices.iter() {
+        ret.extend(&(rotation.clone() * vertex + position.clone()));
+    }
+    ret
+}
+
+// a convex shape placed in the world the way the narrow phase sees it: a core and a radius around it.
+// spheres and capsules are a segment with a radius (a sphere's segment has both ends at its center),
+// boxes and hulls are a polyhedron without one
+#[derive(Debug, Clone, PartialEq)]
+pub enum Convex {
+    Round(Vec3, Vec3, f32),
+    Hull(ConvexHull)
+}
+
+impl Convex {
+    pub fn radius(&self) -> f32 {
+        match self {
+            Convex::Round(_, _, radius) => *radius,
+            Convex::Hull(_) => 0.0
+        }
+    }
+
+    // point of the core farthest along direction, the radius left out
+    pub fn core_support(&self, direction: &Vec3) -> Vec3 {
+        match self {
+            Convex::Round(a, b, _) => if a.dot(direction.clone()) >= b.dot(direction.clone()) { a.clone() } else { b.clone() },
+            Convex::Hull(hull) => hull.support(direction)
+        }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        match self {
+            Convex::Round(a, b, _) => (a.clone() + b.clone()) / 2.0,
+            Convex::Hull(hull) => Aabb::from_points(&hull.vertices).center()
+        }
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        match self {
+            Convex::Round(a, b, radius) => Aabb::from_points(&[a.clone(), b.clone()]).expanded(*radius),
+            Convex::Hull(hull) => Aabb::from_points(&hull.vertices)
+        }
+    }
+
+    pub fn translated(&self, offset: &Vec3) -> Convex {
+        match self {
+            Convex::Round(a, b, radius) => Convex::Round(a.clone() + offset.clone(), b.clone() + offset.clone(), *radius),
+            Convex::Hull(hull) => Convex::Hull(hull.transformed(offset, &Quat::identity()))
+        }
+    }
+}
+
+// world space ends of a capsule's inner segment
+pub fn capsule_segment(half_height: f32, position: &Vec3, rotation: &Quat) -> (Vec3, Vec3) {
+    let axis = rotation.rotate(&Vec3 { x: 0.0, y: half_height, z: 0.0 });
+    (position.clone() - axis.clone(), position.clone() + axis)
+}
+
+// mass properties of the volume enclosed by triangles that wind like mesh triangles. every triangle
+// makes a tetrahedron with the origin, their signed volumes and covariances add up to the solid's
+fn polyhedron_mass<I: Iterator<Item = [usize; 3]>>(vertices: &[Vec3], triangles: I, density: f32) -> MassProperties {
+    let mut volume = 0.0;
+    let mut centroid = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+    let mut covariance = [[0.0f32; 3]; 3];
+    for triangle in triangles {
+        let (a, b, c) = (&vertices[triangle[0]], &vertices[triangle[1]], &vertices[triangle[2]]);
+        let det = a.dot(b.cross(c.clone()));
+        volume += det / 6.0;
+        centroid += (a.clone() + b.clone() + c.clone()) * (det / 24.0);
+
+        // covariance of the tetrahedron: det / 120 * (sum of v v^T + (sum of v)(sum of v)^T)
+        let sum = a.clone() + b.clone() + c.clone();
+        for i in 0..3 {
+            for j in 0..3 {
+                covariance[i][j] += det / 120.0 * (a[i] * a[j] + b[i] * b[j] + c[i] * c[j] + sum[i] * sum[j]);
+            }
+        }
+    }
+
+    if volume.abs() <= std::f32::EPSILON {
+        return MassProperties { mass: 0.0, center: centroid, inertia: Mat3::diagonal(&Vec3 { x: 0.0, y: 0.0, z: 0.0 }) };
+    }
+    let mass = density * volume;
+    let center = centroid / volume;
+
+    // moved to the center of mass, then turned into the inertia tensor trace(C) I - C
+    let mut inertia = Mat3 { mat: [[0.0; 3]; 3] };
+    let mut shifted = [[0.0f32; 3]; 3];
+    for i in 0..3 {
+        for j in 0..3 {
+            shifted[i][j] = density * covariance[i][j] - mass * center[i] * center[j];
+        }
+    }
+    let trace = shifted[0][0] + shifted[1][1] + shifted[2][2];
+    for i in 0..3 {
+        for j in 0..3 {
+            inertia[i][j] = if i == j { trace } else { 0.0 } - shifted[i][j];
+        }
+    }
+
+    MassProperties { mass, center, inertia }
+}
+
+/*********************************
+*** CONVEX HULL
+*********************************/
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HullFace {
+    // corners in order, consecutive ones wind like mesh triangles so (b - a) x (c - a) points out
+    pub vertices: Vec<usize>,
+    pub normal: Vec3,
+    // normal . any corner
+    pub distance: f32
+}
+
+impl HullFace {
+    fn new(vertices: Vec<usize>, points: &[Vec3]) -> HullFace {
+        // newell's method, the sum of the corners' cross products holds up for polygons that are nearly flat
+        let mut normal = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+        for k in 0..vertices.len() {
+            normal += points[vertices[k]].cross(points[vertices[(k + 1) % vertices.len()]].clone());
+        }
+        let normal = normal.normalized();
+        HullFace {
+            distance: normal.dot(points[vertices[0]].clone()),
+            vertices,
+            normal
+        }
+    }
+}
+
+// convex polyhedron with polygonal faces, coplanar triangles are merged
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvexHull {
+    pub vertices: Vec<Vec3>,
+    pub faces: Vec<HullFace>,
+    // every edge once
+    pub edges: Vec<(usize, usize)>
+}
+
+impl ConvexHull {
+    // the hull around a point cloud, built by adding one point at a time and replacing the faces it sees.
+    // fails when the points don't span a volume
+    pub fn new(points: &[Vec3]) -> Result<ConvexHull, String> {
+        if points.len() < 4 {
+            return Err("a convex hull needs at least 4 points".to_string());
+        }
+        let scale = points.iter().map(|p| p.x.abs().max(p.y.abs()).max(p.z.abs())).fold(0.0, f32::max).max(1.0);
+        let epsilon = scale * 1e-5;
+
+        // starting tetrahedron from extreme points
+        let first = (0..points.len())
+            .min_by(|a, b| points[*a].x.partial_cmp(&points[*b].x).unwrap_or(std::cmp::Ordering::Equal))
+            .expect("failed to find an extreme point");
+        let farthest = |score: &dyn Fn(&Vec3) -> f32| (0..points.len())
+            .max_by(|a, b| score(&points[*a]).partial_cmp(&score(&points[*b])).unwrap_or(std::cmp::Ordering::Equal))
+            .expect("failed to find an extreme point");
+        let second = farthest(&|p| p.distance_to(points[first].clone()));
+        let line = points[second].clone() - points[first].clone();
+        let third = farthest(&|p| line.cross(p.clone() - points[first].clone()).lenght());
+        let plane = line.cross(points[third].clone() - points[first].clone());
+        let fourth = farthest(&|p| plane.dot(p.clone() - points[first].clone()).abs());
+        if line.lenght() <= epsilon || plane.lenght() <= epsilon * scale
+            || plane.normalized().dot(points[fourth].clone() - points[first].clone()).abs() <= epsilon {
+            return Err("the points of a convex hull don't span a volume".to_string());
+        }
+
+        let mut triangles = if plane.dot(points[fourth].clone() - points[first].clone()) > 0.0 {
+            // fourth is on the side (b - a) x (c - a) points to, so that face has to turn around
+            vec![[first, third, second], [first, second, fourth], [second, third, fourth], [third, first, fourth]]
+        } else {
+            vec![[first, second, third], [first, fourth, second], [second, fourth, third], [third, fourth, first]]
+        };
+
+        let outside = |triangle: &[usize; 3], point: &Vec3| {
+            let (a, b, c) = (&points[triangle[0]], &points[triangle[1]], &points[triangle[2]]);
+            let normal = (b.clone() - a.clone()).cross(c.clone() - a.clone()).normalized();
+            normal.dot(point.clone() - a.clone()) > epsilon
+        };
+        for (i, point) in points.iter().enumerate() {
+            if i == first || i == second || i == third || i == fourth {
+                continue;
+            }
+            let visible: Vec<bool> = triangles.iter().map(|triangle| outside(triangle, point)).collect();
+            if !visible.iter().any(|v| *v) {
+                continue;
+            }
+
+            // edges of seen faces whose neighbor isn't seen make the horizon the new faces fan out from
+            let seen_edges: Vec<(usize, usize)> = triangles.iter().zip(visible.iter())
+                .filter(|(_, visible)| **visible)
+                .flat_map(|(t, _)| vec![(t[0], t[1]), (t[1], t[2]), (t[2], t[0])])
+                .collect();
+            let horizon: Vec<(usize, usize)> = seen_edges.iter()
+                .filter(|(a, b)| !seen_edges.contains(&(*b, *a)))
+                .cloned()
+                .collect();
+
+            let mut kept: Vec<[usize; 3]> = triangles.iter().zip(visible.iter())
+                .filter(|(_, visible)| !**visible)
+                .map(|(t, _)| *t)
+                .collect();
+            kept.extend(horizon.iter().map(|(a, b)| [*a, *b, i]));
+            triangles = kept;
+        }
+
+        Ok(Self::from_triangles(points, &triangles))
+    }
+
+    // the hull around a mesh's vertices
+    pub fn from_mesh(mesh: &Mesh) -> Result<ConvexHull, String> {
+        ConvexHull::new(&mesh.vertices)
+    }
+
+    // merges coplanar neighbors into polygons and drops the points no face uses
+    fn from_triangles(points: &[Vec3], triangles: &[[usize; 3]]) -> ConvexHull {
+        let normal = |t: &[usize; 3]| {
+            (points[t[1]].clone() - points[t[0]].clone()).cross(points[t[2]].clone() - points[t[0]].clone()).normalized()
+        };
+
+        let mut grouped = vec![false; triangles.len()];
+        let mut polygons: Vec<Vec<usize>> = vec![];
+        for i in 0..triangles.len() {
+            if grouped[i] {
+                continue;
+            }
+            // flood fill over shared edges into neighbors facing the same way
+            let group_normal = normal(&triangles[i]);
+            let mut group = vec![i];
+            grouped[i] = true;
+            let mut next = 0;
+            while next < group.len() {
+                let t = triangles[group[next]];
+                next += 1;
+                for j in 0..triangles.len() {
+                    let u = triangles[j];
+                    let shares_edge = (0..3).any(|k| (0..3).any(|l| t[k] == u[(l + 1) % 3] && t[(k + 1) % 3] == u[l]));
+                    if !grouped[j] && shares_edge && normal(&u).dot(group_normal.clone()) > 1.0 - 1e-4 {
+                        grouped[j] = true;
+                        group.push(j);
+                    }
+                }
+            }
+
+            // the polygon is the loop of edges that only one triangle of the group has
+            let edges: Vec<(usize, usize)> = group.iter()
+                .flat_map(|j| {
+                    let t = triangles[*j];
+                    vec![(t[0], t[1]), (t[1], t[2]), (t[2], t[0])]
+                })
+                .collect();
+            let boundary: Vec<(usize, usize)> = edges.iter().filter(|(a, b)| !edges.contains(&(*b, *a))).cloned().collect();
+            let mut polygon = vec![boundary[0].0];
+            let mut current = boundary[0].1;
+            while current != polygon[0] && polygon.len() <= boundary.len() {
+                polygon.push(current);
+                current = match boundary.iter().find(|(a, _)| *a == current) {
+                    Some((_, b)) => *b,
+                    None => break
+                };
+            }
+
+            // corners on a straight line between their neighbors add nothing
+            let mut corners = vec![];
+            for k in 0..polygon.len() {
+                let previous = &points[polygon[(k + polygon.len() - 1) % polygon.len()]];
+                let corner = &points[polygon[k]];
+                let next = &points[polygon[(k + 1) % polygon.len()]];
+                let bend = (corner.clone() - previous.clone()).normalized().cross((next.clone() - corner.clone()).normalized());
+                if bend.lenght() > 1e-4 {
+                    corners.push(polygon[k]);
+                }
+            }
+            if corners.len() >= 3 {
+                polygons.push(corners);
+            }
+        }
+
+        // compact the vertices
+        let mut remap = vec![None; points.len()];
+        let mut vertices = vec![];
+        for polygon in polygons.iter_mut() {
+            for index in polygon.iter_mut() {
+                *index = *remap[*index].get_or_insert_with(|| {
+                    vertices.push(points[*index].clone());
+                    vertices.len() - 1
+                });
+            }
+        }
+        let faces: Vec<HullFace> = polygons.into_iter().map(|polygon| HullFace::new(polygon, &vertices)).collect();
+        Self::with_faces(vertices, faces)
+    }
+
+    fn with_faces(vertices: Vec<Vec3>, faces: Vec<HullFace>) -> ConvexHull {
+        let mut edges = vec![];
+        for face in faces.iter() {
+            for k in 0..face.vertices.len() {
+                let (a, b) = (face.vertices[k], face.vertices[(k + 1) % face.vertices.len()]);
+                let edge = (a.min(b), a.max(b));
+                if !edges.contains(&edge) {
+                    edges.push(edge);
+                }
+            }
+        }
+        ConvexHull {
+            vertices,
+            faces,
+            edges
+        }
+    }
+
+    // box centered on the origin
+    pub fn cuboid(half_extents: &Vec3) -> ConvexHull {
+        let vertices: Vec<Vec3> = (0..8)
+            .map(|i| Vec3 {
+                x: if i & 1 == 0 { -half_extents.x } else { half_extents.x },
+                y: if i & 2 == 0 { -half_extents.y } else { half_extents.y },
+                z: if i & 4 == 0 { -half_extents.z } else { half_extents.z }
+            })
+            .collect();
+
+        let mut faces = vec![];
+        for axis in 0..3 {
+            let (u, v) = (1 << ((axis + 1) % 3), 1 << ((axis + 2) % 3));
+            for side in 0..2 {
+                let base = side << axis;
+                let mut corners = vec![base, base | u, base | u | v, base | v];
+                if side == 0 {
+                    corners.reverse();
+                }
+                faces.push(HullFace::new(corners, &vertices));
+            }
+        }
+        Self::with_faces(vertices, faces)
+    }
+
+    // a single triangle as a flat hull with a face on either side
+    pub fn triangle(a: &Vec3, b: &Vec3, c: &Vec3) -> ConvexHull {
+        let vertices = vec![a.clone(), b.clone(), c.clone()];
+        let front = HullFace::new(vec![0, 1, 2], &vertices);
+        let back = HullFace::new(vec![0, 2, 1], &vertices);
+        ConvexHull {
+            vertices,
+            faces: vec![front, back],
+            edges: vec![(0, 1), (1, 2), (0, 2)]
+        }
+    }
+
+    // the same hull rotated, then moved
+    pub fn transformed(&self, position: &Vec3, rotation: &Quat) -> ConvexHull {
+        let rotation = rotation.to_mat3();
+        let vertices: Vec<Vec3> = self.vertices.iter().map(|v| rotation.clone() * v + position.clone()).collect();
+        let faces = self.faces.iter()
+            .map(|face| {
+                let normal = rotation.clone() * &face.normal;
+                HullFace {
+                    vertices: face.vertices.clone(),
+                    distance: normal.dot(vertices[face.vertices[0]].clone()),
+                    normal
+                }
+            })
+            .collect();
+        ConvexHull {
+            vertices,
+            faces,
+            edges: self.edges.clone()
+        }
+    }
+
+    // vertex farthest along direction
+    pub fn support(&self, direction: &Vec3) -> Vec3 {
+        self.vertices.iter()
+            .max_by(|a, b| a.dot(direction.clone()).partial_cmp(&b.dot(direction.clone())).unwrap_or(std::cmp::Ordering::Equal))
+            .cloned()
+            .unwrap_or(Vec3 { x: 0.0, y: 0.0, z: 0.0 })
+    }
+}
+
+/*********************************
+*** TRIANGLE MESH
+*********************************/
+
+// triangles with a bounding volume hierarchy over them, for level geometry. build a new one to change it
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriangleMesh {
+    vertices: Vec<Vec3>,
+    triangles: Vec<[usize; 3]>,
+    tree: AabbTree
+}
+
+impl TriangleMesh {
+    pub fn new(vertices: Vec<Vec3>, triangles: Vec<[usize; 3]>) -> TriangleMesh {
+        let mut tree = AabbTree::new(0.0);
+        for (i, t) in triangles.iter().enumerate() {
+            tree.insert(&Aabb::from_points(&[vertices[t[0]].clone(), vertices[t[1]].clone(), vertices[t[2]].clone()]), i);
+        }
+        TriangleMesh {
+            vertices,
+            triangles,
+            tree
+        }
+    }
+
+    // the triangles a mesh draws, degenerate ones are left out
+    pub fn from_mesh(mesh: &Mesh) -> TriangleMesh {
+        let list = mesh.triangle_list();
+        let triangles = list.chunks(3)
+            .filter(|t| t.len() == 3)
+            .map(|t| [t[0], t[1], t[2]])
+            .filter(|t| {
+                let (a, b, c) = (&mesh.vertices[t[0]], &mesh.vertices[t[1]], &mesh.vertices[t[2]]);
+                (b.clone() - a.clone()).cross(c.clone() - a.clone()).lenght() > 1e-12
+            })
+            .collect();
+        TriangleMesh::new(mesh.vertices.clone(), triangles)
+    }
+
+    pub fn vertices(&self) -> &[Vec3] {
+        &self.vertices
+    }
+
+    pub fn triangles(&self) -> &[[usize; 3]] {
+        &self.triangles
+    }
+
+    pub fn triangle(&self, index: usize) -> (&Vec3, &Vec3, &Vec3) {
+        let t = &self.triangles[index];
+        (&self.vertices[t[0]], &self.vertices[t[1]], &self.vertices[t[2]])
+    }
+
+    // the tree over the triangles, in the mesh's space
+    pub fn tree(&self) -> &AabbTree {
+        &self.tree
+    }
+
+    // the triangles near a world space box, as flat hulls in the world, for a mesh at position and rotation
+    pub fn triangles_near(&self, aabb: &Aabb, position: &Vec3, rotation: &Quat) -> Vec<ConvexHull> {
+        let inverse = rotation.conjugated();
+        let mut local = Aabb::empty();
+        for corner in 0..8 {
+            let world = Vec3 {
+                x: if corner & 1 == 0 { aabb.min.x } else { aabb.max.x },
+                y: if corner & 2 == 0 { aabb.min.y } else { aabb.max.y },
+                z: if corner & 4 == 0 { aabb.min.z } else { aabb.max.z }
+            };
+            local.extend(&inverse.rotate(&(world - position.clone())));
+        }
+
+        let mut near = self.tree.query(&local);
+        near.sort();
+        let world = |v: &Vec3| rotation.rotate(v) + position.clone();
+        near.into_iter()
+            .map(|i| {
+                let (a, b, c) = self.triangle(i);
+                ConvexHull::triangle(&world(a), &world(b), &world(c))
+            })
+            .collect()
+    }
+}
\ No newline at end of file
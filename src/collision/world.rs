@@ -0,0 +1,361 @@
+use crate::math::vec3::Vec3;
+use crate::math::quaternion::Quat;
+use crate::math::aabb::Aabb;
+use crate::math::ray3d::Ray3d;
+use super::aabb_tree::AabbTree;
+use super::contact::{self, Manifold};
+use super::gjk::{self, CastHit};
+use super::raycast::{self, RayHit};
+use super::shape::{Shape, Convex};
+
+// whether two things on these layers see each other, both have to be in the other's mask
+pub fn layers_collide(layer_a: u32, mask_a: u32, layer_b: u32, mask_b: u32) -> bool {
+    layer_a & mask_b != 0 && layer_b & mask_a != 0
+}
+
+// a shape placed in the world that doesn't move by itself
+#[derive(Debug, Clone, PartialEq)]
+pub struct Collider {
+    pub shape: Shape,
+    pub position: Vec3,
+    pub rotation: Quat,
+    // bits of the layers it's on
+    pub layer: u32,
+    // bits of the layers it collides with
+    pub mask: u32
+}
+
+impl Collider {
+    pub fn new(shape: Shape, position: Vec3, rotation: Quat) -> Collider {
+        Collider {
+            shape,
+            position,
+            rotation,
+            layer: 1,
+            mask: std::u32::MAX
+        }
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        self.shape.aabb(&self.position, &self.rotation)
+    }
+
+    pub fn collides_with(&self, other: &Collider) -> bool {
+        layers_collide(self.layer, self.mask, other.layer, other.mask)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CollisionEvent {
+    // the colliders started touching this update
+    Enter(usize, usize),
+    // they touched the last update and still do
+    Stay(usize, usize),
+    // they touched the last update and don't anymore, or one of them was removed
+    Exit(usize, usize)
+}
+
+// two colliders touching, a is the smaller index and the normals point from a to b
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContactPair {
+    pub a: usize,
+    pub b: usize,
+    pub manifolds: Vec<Manifold>
+}
+
+// colliders in a dynamic aabb tree, for overlap tests, raycasts and sweeps, and for finding which of them
+// touch. indices of removed colliders aren't reused
+pub struct CollisionWorld {
+    colliders: Vec<Option<Collider>>,
+    // tree leaf of every collider
+    proxies: Vec<Option<usize>>,
+    tree: AabbTree,
+    contacts: Vec<ContactPair>
+}
+
+impl CollisionWorld {
+    pub fn new() -> CollisionWorld {
+        CollisionWorld {
+            colliders: vec![],
+            proxies: vec![],
+            tree: AabbTree::default(),
+            contacts: vec![]
+        }
+    }
+
+    // returns the index of the collider
+    pub fn add(&mut self, collider: Collider) -> usize {
+        let index = self.colliders.len();
+        self.proxies.push(Some(self.tree.insert(&collider.aabb(), index)));
+        self.colliders.push(Some(collider));
+        index
+    }
+
+    // its contacts end with exit events on the next update
+    pub fn remove(&mut self, index: usize) -> Option<Collider> {
+        if let Some(proxy) = self.proxies.get_mut(index).and_then(|proxy| proxy.take()) {
+            self.tree.remove(proxy);
+        }
+        self.colliders.get_mut(index).and_then(|collider| collider.take())
+    }
+
+    pub fn collider(&self, index: usize) -> Option<&Collider> {
+        self.colliders.get(index).and_then(|collider| collider.as_ref())
+    }
+
+    // changes show in the tree on the next update
+    pub fn collider_mut(&mut self, index: usize) -> Option<&mut Collider> {
+        self.colliders.get_mut(index).and_then(|collider| collider.as_mut())
+    }
+
+    pub fn set_transform(&mut self, index: usize, position: Vec3, rotation: Quat) {
+        if let Some(collider) = self.colliders.get_mut(index).and_then(|collider| collider.as_mut()) {
+            collider.position = position;
+            collider.rotation = rotation;
+            if let Some(proxy) = self.proxies[index] {
+                self.tree.update(proxy, &collider.aabb());
+            }
+        }
+    }
+
+    // contacts found by the last update
+    pub fn contacts(&self) -> &[ContactPair] {
+        &self.contacts
+    }
+
+    // finds the colliders that touch and how that changed since the last update. events come out sorted
+    // by pair, exits last
+    pub fn update(&mut self) -> Vec<CollisionEvent> {
+        for (collider, proxy) in self.colliders.iter().zip(self.proxies.iter()) {
+            if let (Some(collider), Some(proxy)) = (collider, proxy) {
+                self.tree.update(*proxy, &collider.aabb());
+            }
+        }
+
+        let mut contacts = vec![];
+        for (a, b) in self.tree.pairs() {
+            let (collider_a, collider_b) = match (self.collider(a), self.collider(b)) {
+                (Some(collider_a), Some(collider_b)) if collider_a.collides_with(collider_b) => (collider_a, collider_b),
+                _ => continue
+            };
+            let manifolds: Vec<Manifold> = contact::collide(&collider_a.shape, &collider_a.position, &collider_a.rotation,
+                                                            &collider_b.shape, &collider_b.position, &collider_b.rotation, 0.0)
+                .into_iter()
+                .filter(|manifold| manifold.points.iter().any(|point| point.depth >= 0.0))
+                .collect();
+            if !manifolds.is_empty() {
+                contacts.push(ContactPair { a, b, manifolds });
+            }
+        }
+
+        let touched = |list: &[ContactPair], a: usize, b: usize| list.iter().any(|c| c.a == a && c.b == b);
+        let mut events: Vec<CollisionEvent> = contacts.iter()
+            .map(|c| if touched(&self.contacts, c.a, c.b) { CollisionEvent::Stay(c.a, c.b) } else { CollisionEvent::Enter(c.a, c.b) })
+            .collect();
+        events.extend(self.contacts.iter()
+            .filter(|c| !touched(&contacts, c.a, c.b))
+            .map(|c| CollisionEvent::Exit(c.a, c.b)));
+        self.contacts = contacts;
+        events
+    }
+
+    // colliders on a layer in mask whose bounds overlap aabb, sorted
+    pub fn overlap_aabb(&self, aabb: &Aabb, mask: u32) -> Vec<usize> {
+        let mut ret: Vec<usize> = self.tree.query(aabb).into_iter()
+            .filter(|index| {
+                self.collider(*index)
+                    .map(|collider| collider.layer & mask != 0 && collider.aabb().overlaps(aabb))
+                    .unwrap_or(false)
+            })
+            .collect();
+        ret.sort();
+        ret
+    }
+
+    // colliders on a layer in mask that a shape placed in the world touches, sorted
+    pub fn overlap_shape(&self, shape: &Shape, position: &Vec3, rotation: &Quat, mask: u32) -> Vec<usize> {
+        self.overlap_aabb(&shape.aabb(position, rotation), mask).into_iter()
+            .filter(|index| {
+                let collider = self.colliders[*index].as_ref().expect("failed to find a collider");
+                contact::collide(shape, position, rotation, &collider.shape, &collider.position, &collider.rotation, 0.0)
+                    .iter()
+                    .any(|manifold| manifold.points.iter().any(|point| point.depth >= 0.0))
+            })
+            .collect()
+    }
+
+    // the nearest collider on a layer in mask the ray hits
+    pub fn raycast(&self, ray: &Ray3d, mask: u32) -> Option<(usize, RayHit)> {
+        let mut best: Option<(usize, RayHit)> = None;
+        for (index, entry) in self.tree.raycast(ray).into_iter() {
+            // leaves come nearest first, none after this one can be closer
+            if best.as_ref().map(|(_, hit)| entry > hit.distance).unwrap_or(false) {
+                break;
+            }
+            if let Some(hit) = self.raycast_collider(index, ray, mask) {
+                if best.as_ref().map(|(_, best)| hit.distance < best.distance).unwrap_or(true) {
+                    best = Some((index, hit));
+                }
+            }
+        }
+        best
+    }
+
+    // every collider on a layer in mask the ray hits, nearest first
+    pub fn raycast_all(&self, ray: &Ray3d, mask: u32) -> Vec<(usize, RayHit)> {
+        let mut ret: Vec<(usize, RayHit)> = self.tree.raycast(ray).into_iter()
+            .filter_map(|(index, _)| self.raycast_collider(index, ray, mask).map(|hit| (index, hit)))
+            .collect();
+        ret.sort_by(|a, b| (a.1).distance.partial_cmp(&(b.1).distance).unwrap_or(std::cmp::Ordering::Equal).then(a.0.cmp(&b.0)));
+        ret
+    }
+
+    fn raycast_collider(&self, index: usize, ray: &Ray3d, mask: u32) -> Option<RayHit> {
+        let collider = self.collider(index).filter(|collider| collider.layer & mask != 0)?;
+        raycast::raycast(&collider.shape, &collider.position, &collider.rotation, ray)
+    }
+
+    // the first collider on a layer in mask a shape runs into when it moves by motion, with where along the
+    // motion that happens. triangle meshes can be hit but can't be swept
+    pub fn sweep(&self, shape: &Shape, position: &Vec3, rotation: &Quat, motion: &Vec3, mask: u32) -> Option<(usize, CastHit)> {
+        let moving = shape.convex(position, rotation)?;
+        let swept = moving.aabb().swept(motion);
+
+        let mut best: Option<(usize, CastHit)> = None;
+        for index in self.overlap_aabb(&swept, mask).into_iter() {
+            let collider = self.colliders[index].as_ref().expect("failed to find a collider");
+            let targets = match (collider.shape.convex(&collider.position, &collider.rotation), &collider.shape) {
+                (Some(convex), _) => vec![convex],
+                (None, Shape::TriangleMesh(mesh)) => {
+                    mesh.triangles_near(&swept, &collider.position, &collider.rotation).into_iter().map(Convex::Hull).collect()
+                }
+                _ => vec![]
+            };
+            for target in targets.iter() {
+                if let Some(hit) = gjk::cast(&moving, motion, target) {
+                    if best.as_ref().map(|(_, best)| hit.fraction < best.fraction).unwrap_or(true) {
+                        best = Some((index, hit));
+                    }
+                }
+            }
+        }
+        best
+    }
+}
+
+impl Default for CollisionWorld {
+    fn default() -> CollisionWorld {
+        CollisionWorld::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn ball(x: f32, y: f32, z: f32) -> Collider {
+        Collider::new(Shape::Sphere { radius: 0.5 }, vec3(x, y, z), Quat::identity())
+    }
+
+    #[test]
+    fn touching_starts_with_enter_then_stays_then_exits() {
+        let mut world = CollisionWorld::new();
+        let a = world.add(ball(0.0, 0.0, 0.0));
+        let b = world.add(ball(3.0, 0.0, 0.0));
+        assert_eq!(world.update(), vec![]);
+
+        world.set_transform(b, vec3(0.8, 0.0, 0.0), Quat::identity());
+        assert_eq!(world.update(), vec![CollisionEvent::Enter(a, b)]);
+        assert_eq!(world.contacts().len(), 1);
+        assert!((world.contacts()[0].manifolds[0].normal.x - 1.0).abs() < 1e-5);
+        assert_eq!(world.update(), vec![CollisionEvent::Stay(a, b)]);
+
+        world.set_transform(b, vec3(3.0, 0.0, 0.0), Quat::identity());
+        assert_eq!(world.update(), vec![CollisionEvent::Exit(a, b)]);
+        assert_eq!(world.update(), vec![]);
+    }
+
+    #[test]
+    fn removed_colliders_exit() {
+        let mut world = CollisionWorld::new();
+        let a = world.add(ball(0.0, 0.0, 0.0));
+        let b = world.add(ball(0.6, 0.0, 0.0));
+        let c = world.add(ball(-0.6, 0.0, 0.0));
+        assert_eq!(world.update(), vec![CollisionEvent::Enter(a, b), CollisionEvent::Enter(a, c)]);
+
+        assert!(world.remove(b).is_some());
+        assert!(world.collider(b).is_none());
+        assert_eq!(world.update(), vec![CollisionEvent::Stay(a, c), CollisionEvent::Exit(a, b)]);
+        // indices aren't reused
+        assert_eq!(world.add(ball(10.0, 0.0, 0.0)), 3);
+    }
+
+    #[test]
+    fn layers_filter_contacts_and_queries() {
+        let mut world = CollisionWorld::new();
+        let a = world.add(ball(0.0, 0.0, 0.0));
+        let mut ghost = ball(0.5, 0.0, 0.0);
+        ghost.layer = 2;
+        ghost.mask = 2;
+        let b = world.add(ghost);
+        let c = world.add(ball(-0.5, 0.0, 0.0));
+        // b doesn't see a or c, so neither touches it
+        assert_eq!(world.update(), vec![CollisionEvent::Enter(a, c)]);
+
+        assert!(layers_collide(1, 3, 2, 1));
+        assert!(!layers_collide(1, 3, 2, 2));
+        assert_eq!(world.overlap_shape(&Shape::Sphere { radius: 0.1 }, &vec3(0.3, 0.0, 0.0), &Quat::identity(), std::u32::MAX), vec![a, b]);
+        assert_eq!(world.overlap_shape(&Shape::Sphere { radius: 0.1 }, &vec3(0.3, 0.0, 0.0), &Quat::identity(), 2), vec![b]);
+        assert_eq!(world.overlap_aabb(&Aabb::new(vec3(-1.0, -1.0, -1.0), vec3(-0.9, 1.0, 1.0)), 2), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn raycasts_find_the_nearest_collider_on_the_mask() {
+        let mut world = CollisionWorld::new();
+        let near = world.add(ball(3.0, 0.0, 0.0));
+        let far = world.add(Collider::new(Shape::Box { half_extents: vec3(0.5, 2.0, 2.0) }, vec3(6.0, 0.0, 0.0), Quat::identity()));
+        world.collider_mut(near).expect("failed to find the collider").layer = 2;
+        world.update();
+
+        let ray = Ray3d::new(vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), 20.0);
+        let (index, hit) = world.raycast(&ray, std::u32::MAX).expect("failed to hit");
+        assert_eq!(index, near);
+        assert!((hit.distance - 2.5).abs() < 1e-4);
+        let (index, hit) = world.raycast(&ray, 1).expect("failed to hit");
+        assert_eq!(index, far);
+        assert!((hit.distance - 5.5).abs() < 1e-4);
+
+        let all = world.raycast_all(&ray, std::u32::MAX);
+        assert_eq!(all.iter().map(|(index, _)| *index).collect::<Vec<usize>>(), vec![near, far]);
+    }
+
+    #[test]
+    fn sweeps_stop_at_the_first_collider_in_the_way() {
+        let mut world = CollisionWorld::new();
+        let far = world.add(Collider::new(Shape::Box { half_extents: vec3(0.5, 0.5, 0.5) }, vec3(0.0, 0.0, 8.0), Quat::identity()));
+        let near = world.add(ball(0.0, 0.0, 4.0));
+        let mut ignored = ball(0.0, 0.0, 2.0);
+        ignored.layer = 2;
+        world.add(ignored);
+        world.update();
+
+        let capsule = Shape::Capsule { radius: 0.25, half_height: 0.5 };
+        let (index, hit) = world.sweep(&capsule, &vec3(0.0, 0.0, 0.0), &Quat::identity(), &vec3(0.0, 0.0, 10.0), 1)
+            .expect("failed to hit");
+        assert_eq!(index, near);
+        assert!((hit.fraction - 0.325).abs() < 1e-3);
+        assert!((hit.normal.z + 1.0).abs() < 1e-3);
+
+        world.remove(near);
+        let (index, hit) = world.sweep(&capsule, &vec3(0.0, 0.0, 0.0), &Quat::identity(), &vec3(0.0, 0.0, 10.0), 1)
+            .expect("failed to hit");
+        assert_eq!(index, far);
+        assert!((hit.fraction - 0.725).abs() < 1e-3);
+        assert!(world.sweep(&capsule, &vec3(0.0, 0.0, 0.0), &Quat::identity(), &vec3(0.0, 0.0, -10.0), 1).is_none());
+    }
+}
\ No newline at end of file
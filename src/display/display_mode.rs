@@ -0,0 +1,201 @@
+use sdl2::VideoSubsystem;
+use winit::MonitorId;
+
+// SDL reports dpi, winit reports a scale factor; 96 dpi is scale 1.0
+const BASE_DPI: f32 = 96.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoMode {
+    pub width: u32,
+    pub height: u32,
+    pub refresh_rate: u32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowMode {
+    Windowed,
+    // fullscreen-sized window without decorations, keeps the desktop video mode
+    Borderless,
+    // exclusive fullscreen, None keeps the monitor's current video mode
+    Fullscreen(Option<VideoMode>)
+}
+
+#[derive(Debug, Clone)]
+pub struct MonitorInfo {
+    pub index: usize,
+    pub name: String,
+    pub position: (i32, i32),
+    pub dimensions: (u32, u32),
+    pub scale_factor: f64,
+    pub video_modes: Vec<VideoMode>
+}
+
+// position and size the window had the last time it was in windowed mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowPlacement {
+    pub position: (i32, i32),
+    pub size: (u32, u32)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisplayEvent {
+    // new drawable size in physical pixels
+    Resized(u32, u32),
+    ScaleFactorChanged(f64),
+    ModeChanged(WindowMode)
+}
+
+impl MonitorInfo {
+    pub fn from_sdl(video_subsystem: &VideoSubsystem, display_index: i32) -> Result<MonitorInfo, String> {
+        let name = video_subsystem.display_name(display_index)?;
+        let bounds = video_subsystem.display_bounds(display_index)?;
+        let scale_factor = video_subsystem.display_dpi(display_index)
+            .map(|(_, hdpi, _)| f64::from(hdpi / BASE_DPI))
+            .unwrap_or(1.0);
+
+        let mut video_modes = vec![];
+        for mode_index in 0..video_subsystem.num_display_modes(display_index)? {
+            let mode = video_subsystem.display_mode(display_index, mode_index)?;
+            let video_mode = VideoMode {
+                width: mode.w as u32,
+                height: mode.h as u32,
+                refresh_rate: mode.refresh_rate as u32
+            };
+            // SDL lists the same resolution once per pixel format
+            if !video_modes.contains(&video_mode) {
+                video_modes.push(video_mode);
+            }
+        }
+
+        Ok(MonitorInfo {
+            index: display_index as usize,
+            name,
+            position: (bounds.x(), bounds.y()),
+            dimensions: (bounds.width(), bounds.height()),
+            scale_factor,
+            video_modes
+        })
+    }
+
+    // winit can't enumerate video modes, so the current one is the only mode reported
+    pub fn from_winit(monitor: &MonitorId, index: usize) -> MonitorInfo {
+        let position = monitor.get_position();
+        let dimensions = monitor.get_dimensions();
+        let current_mode = VideoMode {
+            width: dimensions.width as u32,
+            height: dimensions.height as u32,
+            refresh_rate: 0
+        };
+
+        MonitorInfo {
+            index,
+            name: monitor.get_name().unwrap_or_default(),
+            position: (position.x as i32, position.y as i32),
+            dimensions: (current_mode.width, current_mode.height),
+            scale_factor: monitor.get_hidpi_factor(),
+            video_modes: vec![current_mode]
+        }
+    }
+
+    // the supported mode closest to the one asked for, to pass on to WindowMode::Fullscreen. a resolution
+    // the monitor doesn't have falls back to the largest that fits in it, or to the smallest there is,
+    // and a refresh rate to the nearest one at that resolution. a refresh rate of 0 takes the highest.
+    // None when the monitor reports no modes
+    pub fn choose_video_mode(&self, wanted: VideoMode) -> Option<VideoMode> {
+        let area = |mode: &&VideoMode| u64::from(mode.width) * u64::from(mode.height);
+        let resolution = self.video_modes.iter()
+            .filter(|mode| mode.width <= wanted.width && mode.height <= wanted.height)
+            .max_by_key(area)
+            .or_else(|| self.video_modes.iter().min_by_key(area))?;
+
+        self.video_modes.iter()
+            .filter(|mode| mode.width == resolution.width && mode.height == resolution.height)
+            .min_by_key(|mode| {
+                let distance = if wanted.refresh_rate == 0 {
+                    0
+                } else {
+                    (i64::from(mode.refresh_rate) - i64::from(wanted.refresh_rate)).abs()
+                };
+                // the higher of two rates equally far away
+                (distance, std::cmp::Reverse(mode.refresh_rate))
+            })
+            .cloned()
+    }
+}
+
+pub fn enumerate_monitors(video_subsystem: &VideoSubsystem) -> Result<Vec<MonitorInfo>, String> {
+    let mut monitors = vec![];
+    for display_index in 0..video_subsystem.num_video_displays()? {
+        monitors.push(MonitorInfo::from_sdl(video_subsystem, display_index)?);
+    }
+
+    Ok(monitors)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mode(width: u32, height: u32, refresh_rate: u32) -> VideoMode {
+        VideoMode { width, height, refresh_rate }
+    }
+
+    fn monitor(video_modes: Vec<VideoMode>) -> MonitorInfo {
+        MonitorInfo {
+            index: 0,
+            name: "test".to_string(),
+            position: (0, 0),
+            dimensions: (1920, 1080),
+            scale_factor: 1.0,
+            video_modes
+        }
+    }
+
+    fn desktop() -> MonitorInfo {
+        monitor(vec![
+            mode(1920, 1080, 144), mode(1920, 1080, 60), mode(1920, 1080, 120),
+            mode(1280, 720, 60), mode(1600, 900, 60), mode(800, 600, 75)
+        ])
+    }
+
+    #[test]
+    fn supported_modes_are_chosen_as_they_are() {
+        let desktop = desktop();
+        for video_mode in desktop.video_modes.iter() {
+            assert_eq!(desktop.choose_video_mode(*video_mode), Some(*video_mode));
+        }
+    }
+
+    #[test]
+    fn missing_refresh_rates_fall_back_to_the_nearest() {
+        let desktop = desktop();
+        assert_eq!(desktop.choose_video_mode(mode(1920, 1080, 75)), Some(mode(1920, 1080, 60)));
+        assert_eq!(desktop.choose_video_mode(mode(1920, 1080, 100)), Some(mode(1920, 1080, 120)));
+        // halfway between 120 and 144 goes up
+        assert_eq!(desktop.choose_video_mode(mode(1920, 1080, 132)), Some(mode(1920, 1080, 144)));
+        assert_eq!(desktop.choose_video_mode(mode(1920, 1080, 240)), Some(mode(1920, 1080, 144)));
+        // no preference, the highest
+        assert_eq!(desktop.choose_video_mode(mode(1920, 1080, 0)), Some(mode(1920, 1080, 144)));
+    }
+
+    #[test]
+    fn missing_resolutions_fall_back_to_the_largest_that_fits() {
+        let desktop = desktop();
+        assert_eq!(desktop.choose_video_mode(mode(1700, 1000, 60)), Some(mode(1600, 900, 60)));
+        assert_eq!(desktop.choose_video_mode(mode(3840, 2160, 60)), Some(mode(1920, 1080, 60)));
+        // at the refresh rate nearest the one asked for
+        assert_eq!(desktop.choose_video_mode(mode(2560, 1440, 165)), Some(mode(1920, 1080, 144)));
+        assert_eq!(desktop.choose_video_mode(mode(1024, 768, 60)), Some(mode(800, 600, 75)));
+        // nothing fits, the smallest there is
+        assert_eq!(desktop.choose_video_mode(mode(640, 480, 60)), Some(mode(800, 600, 75)));
+    }
+
+    #[test]
+    fn monitors_without_modes_have_nothing_to_choose() {
+        assert_eq!(monitor(vec![]).choose_video_mode(mode(1920, 1080, 60)), None);
+        // what winit reports, the current mode without a refresh rate
+        let current = monitor(vec![mode(2560, 1440, 0)]);
+        assert_eq!(current.choose_video_mode(mode(1920, 1080, 60)), Some(mode(2560, 1440, 0)));
+    }
+}
\ No newline at end of file
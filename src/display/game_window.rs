@@ -1,15 +1,24 @@
 use sdl2::pixels::Color;
-use sdl2::event::Event;
+use sdl2::event::{Event, WindowEvent};
 use sdl2::{Sdl, VideoSubsystem, EventPump};
-use sdl2::video::{Window};
+use sdl2::video::{Window, WindowPos, FullscreenType, DisplayMode};
+use sdl2::pixels::PixelFormatEnum;
 use sdl2::render::{Canvas};
 use std::time::Duration;
 
+use super::display_mode::{self, WindowMode, MonitorInfo, WindowPlacement, DisplayEvent};
+
 pub struct GameWindow {
     sdl_context: Sdl,
     video_subsystem: VideoSubsystem,
     canvas: Canvas<Window>,
     event_pump: EventPump,
+
+    window_mode: WindowMode,
+    windowed_placement: WindowPlacement,
+    scale_factor: f64,
+    display_events: Vec<DisplayEvent>,
+
     pub closed: bool
 }
 
@@ -19,7 +28,11 @@ impl GameWindow {
         let video_subsystem = sdl_context.video().unwrap();
 
         let window = video_subsystem.window(name, width, height)
-            .position_centered().build().unwrap();
+            .position_centered().resizable().allow_highdpi().build().unwrap();
+        let windowed_placement = WindowPlacement {
+            position: window.position(),
+            size: window.size()
+        };
 
         let mut canvas = window.into_canvas().build().unwrap();
 
@@ -27,25 +40,143 @@ impl GameWindow {
         canvas.present();
         let mut event_pump = sdl_context.event_pump().unwrap();
 
-        GameWindow {
+        let mut ret = GameWindow {
             sdl_context: sdl_context,
             video_subsystem: video_subsystem,
             canvas: canvas,
             event_pump: event_pump,
+            window_mode: WindowMode::Windowed,
+            windowed_placement: windowed_placement,
+            scale_factor: 1.0,
+            display_events: vec![],
             closed: false
-        }
+        };
+        ret.scale_factor = ret.current_scale_factor();
+
+        ret
     }
 
     pub fn update(&mut self) {
         self.canvas.set_draw_color(Color::RGB(0, 0, 0));
         self.canvas.clear();
+        let mut moved = false;
         for event in self.event_pump.poll_iter() {
             match event {
                 Event::Quit {..} => { self.closed = true },
+                Event::Window { win_event: WindowEvent::Moved(x, y), .. } => {
+                    if self.window_mode == WindowMode::Windowed {
+                        self.windowed_placement.position = (x, y);
+                    }
+                    moved = true;
+                },
+                Event::Window { win_event: WindowEvent::SizeChanged(width, height), .. } => {
+                    if self.window_mode == WindowMode::Windowed {
+                        self.windowed_placement.size = (width as u32, height as u32);
+                    }
+                    let (drawable_width, drawable_height) = self.canvas.window().drawable_size();
+                    self.display_events.push(DisplayEvent::Resized(drawable_width, drawable_height));
+                },
                 _ => {}
             }
         }
 
+        // moving to another monitor is the only way the scale factor changes in SDL
+        if moved {
+            let scale_factor = self.current_scale_factor();
+            if scale_factor != self.scale_factor {
+                self.scale_factor = scale_factor;
+                self.display_events.push(DisplayEvent::ScaleFactorChanged(scale_factor));
+            }
+        }
+
         self.canvas.present();
     }
+
+    /*********************************
+    *** DISPLAY MODE FUNCTIONS
+    *********************************/
+
+    pub fn monitors(&self) -> Result<Vec<MonitorInfo>, String> {
+        display_mode::enumerate_monitors(&self.video_subsystem)
+    }
+
+    pub fn window_mode(&self) -> WindowMode {
+        self.window_mode
+    }
+
+    pub fn windowed_placement(&self) -> WindowPlacement {
+        self.windowed_placement
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    // returns and clears the display events gathered since the last call
+    pub fn poll_display_events(&mut self) -> Vec<DisplayEvent> {
+        self.display_events.drain(..).collect()
+    }
+
+    pub fn set_window_mode(&mut self, mode: WindowMode, monitor_index: usize) -> Result<(), String> {
+        let monitor = MonitorInfo::from_sdl(&self.video_subsystem, monitor_index as i32)?;
+        let window = self.canvas.window_mut();
+
+        if self.window_mode == WindowMode::Windowed {
+            self.windowed_placement = WindowPlacement {
+                position: window.position(),
+                size: window.size()
+            };
+        }
+
+        match mode {
+            WindowMode::Windowed => {
+                window.set_fullscreen(FullscreenType::Off)?;
+                window.set_bordered(true);
+                let (width, height) = self.windowed_placement.size;
+                window.set_size(width, height).map_err(|e| e.to_string())?;
+                let (x, y) = self.windowed_placement.position;
+                window.set_position(WindowPos::Positioned(x), WindowPos::Positioned(y));
+            },
+            WindowMode::Borderless => {
+                // the window has to be on the target monitor before SDL picks the desktop mode
+                window.set_position(WindowPos::Positioned(monitor.position.0), WindowPos::Positioned(monitor.position.1));
+                window.set_fullscreen(FullscreenType::Desktop)?;
+            },
+            WindowMode::Fullscreen(video_mode) => {
+                window.set_position(WindowPos::Positioned(monitor.position.0), WindowPos::Positioned(monitor.position.1));
+                let display_mode = match video_mode {
+                    Some(video_mode) => {
+                        if !monitor.video_modes.contains(&video_mode) {
+                            return Err(format!("video mode {:?} not supported by monitor {}", video_mode, monitor.name));
+                        }
+                        Some(DisplayMode::new(PixelFormatEnum::Unknown, video_mode.width as i32,
+                            video_mode.height as i32, video_mode.refresh_rate as i32))
+                    },
+                    None => None
+                };
+                window.set_display_mode(display_mode)?;
+                window.set_fullscreen(FullscreenType::True)?;
+            }
+        }
+
+        self.window_mode = mode;
+        self.display_events.push(DisplayEvent::ModeChanged(mode));
+        let (drawable_width, drawable_height) = self.canvas.window().drawable_size();
+        self.display_events.push(DisplayEvent::Resized(drawable_width, drawable_height));
+
+        let scale_factor = self.current_scale_factor();
+        if scale_factor != self.scale_factor {
+            self.scale_factor = scale_factor;
+            self.display_events.push(DisplayEvent::ScaleFactorChanged(scale_factor));
+        }
+
+        Ok(())
+    }
+
+    fn current_scale_factor(&self) -> f64 {
+        self.canvas.window().display_index()
+            .and_then(|index| MonitorInfo::from_sdl(&self.video_subsystem, index))
+            .map(|monitor| monitor.scale_factor)
+            .unwrap_or(1.0)
+    }
 }
\ No newline at end of file
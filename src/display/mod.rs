@@ -1 +1,2 @@
+pub mod display_mode;
 pub mod game_window;
\ No newline at end of file
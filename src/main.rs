@@ -5,26 +5,50 @@ extern crate vulkano_win;
 extern crate sdl2;
 extern crate winit;
 
+mod animation;
+mod assets;
+mod audio;
+mod collision;
 mod display;
 mod input;
 mod math;
+mod physics;
+mod physics2d;
 mod renderer;
+mod scene;
 
+use crate::assets::manager::AssetManager;
 use crate::renderer::core::Core;
+use crate::renderer::camera::Camera;
 use crate::renderer::mesh::Mesh;
+use crate::renderer::light::Light;
 use crate::math::vec3::Vec3;
 
 fn main() {
     let mut core_renderer = Core::new("KitsuneEngine test", 800, 600);
-    let triangle = Mesh::new(
+    let mut assets = AssetManager::new("assets");
+    let triangle = assets.insert("triangle", Mesh::new(
         vec![Vec3{x:-0.5, y:-0.5, z:0.0}, Vec3{x:0.0, y:0.5, z:0.0}, Vec3{x:0.5, y:-0.25, z:0.0}],
-        vec![0],
+        vec![0, 1, 2],
         vec![],
         vec![],
         vec![],
         vec![]
-    );
-    core_renderer.add_new(&triangle);
+    ));
+    core_renderer.add_new(assets.get(&triangle));
+    core_renderer.set_camera(Camera::new(
+        Vec3{x:0.0, y:0.0, z:1.5},
+        Vec3{x:0.0, y:0.0, z:-1.0},
+        Vec3{x:0.0, y:1.0, z:0.0},
+        60.0, 800.0 / 600.0, 0.1, 100.0
+    ));
+    core_renderer.add_light(Light::Ambient { color: Vec3{x:1.0, y:1.0, z:1.0}, intensity: 0.1 });
+    core_renderer.add_light(Light::Directional {
+        direction: Vec3{x:0.0, y:0.0, z:-1.0},
+        color: Vec3{x:1.0, y:1.0, z:1.0},
+        intensity: 1.0,
+        cast_shadows: true
+    });
     core_renderer.create_command_buffers();
     while !core_renderer.done {
         core_renderer.render();
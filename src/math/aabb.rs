@@ -0,0 +1,114 @@
+use super::vec3::Vec3;
+use super::ray3d::Ray3d;
+
+// axis aligned bounding box
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Aabb {
+        Aabb {
+            min,
+            max
+        }
+    }
+
+    // a box no point is in, grows from nothing with extend
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Vec3 { x: std::f32::MAX, y: std::f32::MAX, z: std::f32::MAX },
+            max: Vec3 { x: std::f32::MIN, y: std::f32::MIN, z: std::f32::MIN }
+        }
+    }
+
+    pub fn from_points(points: &[Vec3]) -> Aabb {
+        let mut ret = Aabb::empty();
+        for point in points.iter() {
+            ret.extend(point);
+        }
+        ret
+    }
+
+    pub fn extend(&mut self, point: &Vec3) {
+        for axis in 0..3 {
+            self.min[axis] = self.min[axis].min(point[axis]);
+            self.max[axis] = self.max[axis].max(point[axis]);
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        let mut ret = self.clone();
+        ret.extend(&other.min);
+        ret.extend(&other.max);
+        ret
+    }
+
+    // grown by margin on every side
+    pub fn expanded(&self, margin: f32) -> Aabb {
+        let margin = Vec3 { x: margin, y: margin, z: margin };
+        Aabb {
+            min: self.min.clone() - margin.clone(),
+            max: self.max.clone() + margin
+        }
+    }
+
+    // grown to also cover the box moved by offset
+    pub fn swept(&self, offset: &Vec3) -> Aabb {
+        let mut moved = self.clone();
+        moved.min += offset.clone();
+        moved.max += offset.clone();
+        self.union(&moved)
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min.clone() + self.max.clone()) / 2.0
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max.clone() - self.min.clone()) / 2.0
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let size = self.max.clone() - self.min.clone();
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.min[axis] <= other.max[axis] && other.min[axis] <= self.max[axis])
+    }
+
+    pub fn contains(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.min[axis] <= other.min[axis] && other.max[axis] <= self.max[axis])
+    }
+
+    pub fn contains_point(&self, point: &Vec3) -> bool {
+        (0..3).all(|axis| self.min[axis] <= point[axis] && point[axis] <= self.max[axis])
+    }
+
+    // distance along the ray to where it enters the box, 0 when it starts inside, none when it misses
+    // within its range
+    pub fn ray_intersection(&self, ray: &Ray3d) -> Option<f32> {
+        let mut enter = 0.0f32;
+        let mut exit = ray.range;
+        for axis in 0..3 {
+            if ray.dir[axis].abs() < 1e-12 {
+                if ray.origin[axis] < self.min[axis] || ray.origin[axis] > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let inverse = 1.0 / ray.dir[axis];
+            let t0 = (self.min[axis] - ray.origin[axis]) * inverse;
+            let t1 = (self.max[axis] - ray.origin[axis]) * inverse;
+            enter = enter.max(t0.min(t1));
+            exit = exit.min(t0.max(t1));
+            if enter > exit {
+                return None;
+            }
+        }
+        Some(enter)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,101 @@
+use super::vec2::Vec2;
+use super::ray2d::Ray2d;
+
+// axis aligned bounding rectangle
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aabb2d {
+    pub min: Vec2,
+    pub max: Vec2
+}
+
+impl Aabb2d {
+    pub fn new(min: Vec2, max: Vec2) -> Aabb2d {
+        Aabb2d {
+            min,
+            max
+        }
+    }
+
+    // a box no point is in, grows from nothing with extend
+    pub fn empty() -> Aabb2d {
+        Aabb2d {
+            min: Vec2 { x: std::f32::MAX, y: std::f32::MAX },
+            max: Vec2 { x: std::f32::MIN, y: std::f32::MIN }
+        }
+    }
+
+    pub fn from_points(points: &[Vec2]) -> Aabb2d {
+        let mut ret = Aabb2d::empty();
+        for point in points.iter() {
+            ret.extend(point);
+        }
+        ret
+    }
+
+    pub fn extend(&mut self, point: &Vec2) {
+        for axis in 0..2 {
+            self.min[axis] = self.min[axis].min(point[axis]);
+            self.max[axis] = self.max[axis].max(point[axis]);
+        }
+    }
+
+    pub fn union(&self, other: &Aabb2d) -> Aabb2d {
+        let mut ret = self.clone();
+        ret.extend(&other.min);
+        ret.extend(&other.max);
+        ret
+    }
+
+    // grown by margin on every side
+    pub fn expanded(&self, margin: f32) -> Aabb2d {
+        let margin = Vec2 { x: margin, y: margin };
+        Aabb2d {
+            min: self.min.clone() - margin.clone(),
+            max: self.max.clone() + margin
+        }
+    }
+
+    pub fn center(&self) -> Vec2 {
+        (self.min.clone() + self.max.clone()) / 2.0
+    }
+
+    pub fn half_extents(&self) -> Vec2 {
+        (self.max.clone() - self.min.clone()) / 2.0
+    }
+
+    pub fn overlaps(&self, other: &Aabb2d) -> bool {
+        (0..2).all(|axis| self.min[axis] <= other.max[axis] && other.min[axis] <= self.max[axis])
+    }
+
+    pub fn contains(&self, other: &Aabb2d) -> bool {
+        (0..2).all(|axis| self.min[axis] <= other.min[axis] && other.max[axis] <= self.max[axis])
+    }
+
+    pub fn contains_point(&self, point: &Vec2) -> bool {
+        (0..2).all(|axis| self.min[axis] <= point[axis] && point[axis] <= self.max[axis])
+    }
+
+    // distance along the ray to where it enters the box, 0 when it starts inside, none when it misses
+    // within its range
+    pub fn ray_intersection(&self, ray: &Ray2d) -> Option<f32> {
+        let mut enter = 0.0f32;
+        let mut exit = ray.range;
+        for axis in 0..2 {
+            if ray.dir[axis].abs() < 1e-12 {
+                if ray.origin[axis] < self.min[axis] || ray.origin[axis] > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let inverse = 1.0 / ray.dir[axis];
+            let t0 = (self.min[axis] - ray.origin[axis]) * inverse;
+            let t1 = (self.max[axis] - ray.origin[axis]) * inverse;
+            enter = enter.max(t0.min(t1));
+            exit = exit.min(t0.max(t1));
+            if enter > exit {
+                return None;
+            }
+        }
+        Some(enter)
+    }
+}
\ No newline at end of file
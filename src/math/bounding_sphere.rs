@@ -0,0 +1,91 @@
+use super::vec3::Vec3;
+use super::ray3d::Ray3d;
+use super::aabb::Aabb;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32
+}
+
+impl BoundingSphere {
+    pub fn new(center: Vec3, radius: f32) -> BoundingSphere {
+        BoundingSphere {
+            center,
+            radius
+        }
+    }
+
+    // ritter's sphere: a few percent larger than the smallest one, in two passes over the points
+    pub fn from_points(points: &[Vec3]) -> BoundingSphere {
+        if points.is_empty() {
+            return BoundingSphere::new(Vec3 { x: 0.0, y: 0.0, z: 0.0 }, 0.0);
+        }
+        let farthest_from = |from: &Vec3| {
+            points.iter()
+                .max_by(|a, b| a.distance_to(from.clone()).partial_cmp(&b.distance_to(from.clone())).unwrap_or(std::cmp::Ordering::Equal))
+                .cloned()
+                .expect("failed to find the farthest point")
+        };
+        let a = farthest_from(&points[0]);
+        let b = farthest_from(&a);
+        let mut ret = BoundingSphere::new((a.clone() + b.clone()) / 2.0, a.distance_to(b) / 2.0);
+        for point in points.iter() {
+            ret.extend(point);
+        }
+        ret
+    }
+
+    // grown just enough to hold point
+    pub fn extend(&mut self, point: &Vec3) {
+        let distance = point.distance_to(self.center.clone());
+        if distance <= self.radius {
+            return;
+        }
+        let radius = (self.radius + distance) / 2.0;
+        self.center += (point.clone() - self.center.clone()) * ((radius - self.radius) / distance);
+        self.radius = radius;
+    }
+
+    // the smallest sphere around both
+    pub fn union(&self, other: &BoundingSphere) -> BoundingSphere {
+        let distance = self.center.distance_to(other.center.clone());
+        if distance + other.radius <= self.radius {
+            return self.clone();
+        }
+        if distance + self.radius <= other.radius {
+            return other.clone();
+        }
+        let radius = (distance + self.radius + other.radius) / 2.0;
+        let center = self.center.clone() + (other.center.clone() - self.center.clone()) * ((radius - self.radius) / distance);
+        BoundingSphere::new(center, radius)
+    }
+
+    pub fn overlaps(&self, other: &BoundingSphere) -> bool {
+        self.center.distance_to(other.center.clone()) <= self.radius + other.radius
+    }
+
+    pub fn contains_point(&self, point: &Vec3) -> bool {
+        point.distance_to(self.center.clone()) <= self.radius
+    }
+
+    pub fn to_aabb(&self) -> Aabb {
+        Aabb::new(self.center.clone(), self.center.clone()).expanded(self.radius)
+    }
+
+    // distance along the ray to where it enters the sphere, 0 when it starts inside
+    pub fn ray_intersection(&self, ray: &Ray3d) -> Option<f32> {
+        let to_origin = ray.origin.clone() - self.center.clone();
+        let c = to_origin.dot(to_origin.clone()) - self.radius * self.radius;
+        if c <= 0.0 {
+            return Some(0.0);
+        }
+        let b = to_origin.dot(ray.dir.clone());
+        let discriminant = b * b - c;
+        if b > 0.0 || discriminant < 0.0 {
+            return None;
+        }
+        let t = -b - discriminant.sqrt();
+        if t <= ray.range { Some(t) } else { None }
+    }
+}
\ No newline at end of file
@@ -57,4 +57,41 @@ impl ops::Mul<&Vec2> for Mat2 {
 
         ret
     }
+}
+
+impl Mat2 {
+    pub fn identity() -> Mat2 {
+        Mat2 {
+            mat: [[1.0, 0.0], [0.0, 1.0]]
+        }
+    }
+
+    // counter-clockwise
+    pub fn rotation(radians: f32) -> Mat2 {
+        let (sin, cos) = radians.sin_cos();
+        Mat2 {
+            mat: [[cos, -sin], [sin, cos]]
+        }
+    }
+
+    pub fn transpose(&self) -> Mat2 {
+        Mat2 {
+            mat: [[self[0][0], self[1][0]], [self[0][1], self[1][1]]]
+        }
+    }
+
+    pub fn determinant(&self) -> f32 {
+        self[0][0] * self[1][1] - self[0][1] * self[1][0]
+    }
+
+    pub fn invert(&self) -> Option<Mat2> {
+        let determinant = self.determinant();
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+        let inverse = 1.0 / determinant;
+        Some(Mat2 {
+            mat: [[self[1][1] * inverse, -self[0][1] * inverse], [-self[1][0] * inverse, self[0][0] * inverse]]
+        })
+    }
 }
\ No newline at end of file
@@ -56,4 +56,83 @@ impl ops::Mul<&Vec3> for Mat3 {
 
         ret
     }
+}
+
+impl ops::Mul<f32> for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, other: f32) -> Mat3 {
+        let mut ret = self;
+        for row in ret.mat.iter_mut() {
+            for value in row.iter_mut() {
+                *value *= other;
+            }
+        }
+
+        ret
+    }
+}
+
+// other functions
+impl Mat3 {
+    pub fn identity() -> Mat3 {
+        Mat3 {
+            mat: [
+                [1.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [0.0, 0.0, 1.0]
+            ]
+        }
+    }
+
+    pub fn diagonal(d: &Vec3) -> Mat3 {
+        Mat3 {
+            mat: [
+                [d.x, 0.0, 0.0],
+                [0.0, d.y, 0.0],
+                [0.0, 0.0, d.z]
+            ]
+        }
+    }
+
+    pub fn transpose(&self) -> Mat3 {
+        let mut ret = self.clone();
+        for i in 0..3 {
+            for j in 0..3 {
+                ret[i][j] = self[j][i];
+            }
+        }
+
+        ret
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let m = &self.mat;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    // none for singular matrices, the determinant is compared relative to the size of the entries so
+    // small but well conditioned matrices, like the inertia of a light body, still invert
+    pub fn invert(&self) -> Option<Mat3> {
+        let det = self.determinant();
+        let size = self.mat.iter().flat_map(|row| row.iter()).fold(0.0f32, |size, v| size.max(v.abs()));
+        if det == 0.0 || det.abs() <= f32::EPSILON * size * size * size {
+            return None;
+        }
+
+        let m = &self.mat;
+        let mut ret = Mat3 { mat: [[0.0; 3]; 3] };
+        for i in 0..3 {
+            for j in 0..3 {
+                // cofactor of j, i over the determinant
+                let (r0, r1) = ((j + 1) % 3, (j + 2) % 3);
+                let (c0, c1) = ((i + 1) % 3, (i + 2) % 3);
+                ret[i][j] = (m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]) / det;
+            }
+        }
+
+        Some(ret)
+    }
 }
\ No newline at end of file
@@ -43,10 +43,89 @@ impl ops::Mul<&Mat4> for Mat4 {
     }
 }
 
+impl ops::Mul<&Vec3> for Mat4 {
+    type Output = Vec3;
+
+    // transforms a point, dividing by w
+    fn mul(self, other: &Vec3) -> Vec3 {
+        let v = [other.x, other.y, other.z, 1.0];
+        let mut ret = [0.0; 4];
+        for i in 0..4 {
+            for j in 0..4 {
+                ret[i] += self[i][j] * v[j];
+            }
+        }
+
+        Vec3 {
+            x: ret[0] / ret[3],
+            y: ret[1] / ret[3],
+            z: ret[2] / ret[3]
+        }
+    }
+}
+
 impl Mat4 {
-    // todo, only if needed later
-    pub fn invert(&self) {
+    pub fn identity() -> Mat4 {
+        Mat4 {
+            mat: [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0]
+            ]
+        }
+    }
+
+    // gauss-jordan elimination with partial pivoting, None if the matrix is singular
+    pub fn invert(&self) -> Option<Mat4> {
+        let mut a = self.mat;
+        let mut ret = Mat4::identity().mat;
+
+        for col in 0..4 {
+            let mut pivot = col;
+            for row in col + 1..4 {
+                if a[row][col].abs() > a[pivot][col].abs() {
+                    pivot = row;
+                }
+            }
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            ret.swap(col, pivot);
 
+            let inv_pivot = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= inv_pivot;
+                ret[col][j] *= inv_pivot;
+            }
+
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for j in 0..4 {
+                        a[row][j] -= factor * a[col][j];
+                        ret[row][j] -= factor * ret[col][j];
+                    }
+                }
+            }
+        }
+
+        Some(Mat4 { mat: ret })
+    }
+
+    // transforms a direction, ignoring translation
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        Vec3 {
+            x: self[0][0] * v.x + self[0][1] * v.y + self[0][2] * v.z,
+            y: self[1][0] * v.x + self[1][1] * v.y + self[1][2] * v.z,
+            z: self[2][0] * v.x + self[2][1] * v.y + self[2][2] * v.z
+        }
+    }
+
+    // rows laid out as glsl mat4 columns, ready to be copied into a uniform
+    pub fn to_gpu(&self) -> [[f32; 4]; 4] {
+        self.transpose().mat
     }
 
     pub fn transpose(&self) ->Mat4 {
@@ -189,6 +268,50 @@ impl Mat4 {
         p_mat * self
     }
 
+    // right handed view matrix looking from eye towards target
+    pub fn look_at(eye: &Vec3, target: &Vec3, up: &Vec3) -> Mat4 {
+        let f = (target.clone() - eye.clone()).normalized();
+        let s = f.cross(up.clone()).normalized();
+        let u = s.cross(f.clone());
+        Mat4 {
+            mat: [
+                [s.x, s.y, s.z, -s.dot(eye.clone())],
+                [u.x, u.y, u.z, -u.dot(eye.clone())],
+                [-f.x, -f.y, -f.z, f.dot(eye.clone())],
+                [0.0, 0.0, 0.0, 1.0]
+            ]
+        }
+    }
+
+    // vulkan orthographic projection matrix: depth mapped from 0 to 1
+    pub fn orthographic_proj_zo(&self, top: f32, bottom: f32, left: f32, right: f32, near: f32, far: f32) -> Mat4 {
+        let o_mat = Mat4 {
+            mat: [
+                [2.0/(right-left), 0.0, 0.0, -(right+left)/(right-left)],
+                [0.0, 2.0/(top-bottom), 0.0, -(top+bottom)/(top-bottom)],
+                [0.0, 0.0, -1.0/(far-near), -near/(far-near)],
+                [0.0, 0.0, 0.0, 1.0]
+            ]
+        };
+
+        o_mat * self
+    }
+
+    // vulkan perspective projection matrix from a vertical field of view: depth mapped from 0 to 1
+    pub fn perspective_fov_zo(&self, fov_y: f32, aspect: f32, near: f32, far: f32) -> Mat4 {
+        let f = 1.0 / (deg2rad(fov_y) / 2.0).tan();
+        let p_mat = Mat4 {
+            mat: [
+                [f/aspect, 0.0, 0.0, 0.0],
+                [0.0, f, 0.0, 0.0],
+                [0.0, 0.0, far/(near-far), far*near/(near-far)],
+                [0.0, 0.0, -1.0, 0.0]
+            ]
+        };
+
+        p_mat * self
+    }
+
     // todo: if needed
     pub fn slerp(&self, q: Quat, r: Quat, t: f32) {
 
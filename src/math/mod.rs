@@ -8,11 +8,14 @@ pub mod mat4;
 pub mod quaternion;
 pub mod ray2d;
 pub mod ray3d;
+pub mod aabb;
+pub mod aabb2d;
+pub mod bounding_sphere;
 
-fn deg2rad(degrees: f32) -> f32 {
+pub fn deg2rad(degrees: f32) -> f32 {
     degrees * (PI as f32 / 180.0)
 }
 
-fn rad2deg(radians: f32) -> f32 {
+pub fn rad2deg(radians: f32) -> f32 {
     radians * (180.0 / PI as f32)
 }
\ No newline at end of file
@@ -1,6 +1,9 @@
 use std::ops;
 use std::f32;
 use super::deg2rad;
+use super::mat3::Mat3;
+use super::mat4::Mat4;
+use super::vec3::Vec3;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Quat {
@@ -146,4 +149,86 @@ impl Quat {
             w: self.w
         }
     }
+
+    pub fn dot(&self, other: Quat) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    // hamilton product, the rotation other followed by self. the * operator works per component
+    pub fn product(&self, other: Quat) -> Quat {
+        Quat {
+            x: self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            y: self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            z: self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+            w: self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z
+        }
+    }
+
+    pub fn from_axis_angle(axis: &Vec3, degrees: f32) -> Quat {
+        let axis = axis.normalized();
+        let half = deg2rad(degrees) / 2.0;
+        Quat {
+            x: axis.x * half.sin(),
+            y: axis.y * half.sin(),
+            z: axis.z * half.sin(),
+            w: half.cos()
+        }
+    }
+
+    // spherical interpolation of unit quaternions along the shorter arc, t = 0 gives self
+    pub fn slerp(&self, other: Quat, t: f32) -> Quat {
+        let mut cos = self.dot(other.clone());
+        let other = if cos < 0.0 {
+            cos = -cos;
+            other * -1.0
+        } else {
+            other
+        };
+
+        // nearly the same rotation, lerping avoids the division by a tiny sine
+        if cos > 0.9995 {
+            return (self.clone() * (1.0 - t) + other * t).normalized();
+        }
+        let angle = cos.acos();
+        let sin = angle.sin();
+        self.clone() * (((1.0 - t) * angle).sin() / sin) + other * ((t * angle).sin() / sin)
+    }
+
+    pub fn identity() -> Quat {
+        Quat {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            w: 1.0
+        }
+    }
+
+    // v rotated by a unit quaternion
+    pub fn rotate(&self, v: &Vec3) -> Vec3 {
+        self.to_mat3() * v
+    }
+
+    pub fn to_mat3(&self) -> Mat3 {
+        let (x, y, z, w) = (self.x, self.y, self.z, self.w);
+        Mat3 {
+            mat: [
+                [1.0 - 2.0*(y*y + z*z), 2.0*(x*y - z*w), 2.0*(x*z + y*w)],
+                [2.0*(x*y + z*w), 1.0 - 2.0*(x*x + z*z), 2.0*(y*z - x*w)],
+                [2.0*(x*z - y*w), 2.0*(y*z + x*w), 1.0 - 2.0*(x*x + y*y)]
+            ]
+        }
+    }
+
+    // rotation matrix of a unit quaternion
+    pub fn to_mat4(&self) -> Mat4 {
+        let (x, y, z, w) = (self.x, self.y, self.z, self.w);
+        Mat4 {
+            mat: [
+                [1.0 - 2.0*(y*y + z*z), 2.0*(x*y - z*w), 2.0*(x*z + y*w), 0.0],
+                [2.0*(x*y + z*w), 1.0 - 2.0*(x*x + z*z), 2.0*(y*z - x*w), 0.0],
+                [2.0*(x*z - y*w), 2.0*(y*z + x*w), 1.0 - 2.0*(x*x + y*y), 0.0],
+                [0.0, 0.0, 0.0, 1.0]
+            ]
+        }
+    }
 }
\ No newline at end of file
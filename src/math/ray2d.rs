@@ -1,8 +1,27 @@
 use super::vec2;
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Ray2d {
-    range: f32,
-    dir: vec2::Vec2
+    pub origin: vec2::Vec2,
+    pub range: f32,
+    pub dir: vec2::Vec2
 }
 
-// todo
\ No newline at end of file
+impl Ray2d {
+    pub fn new(origin: vec2::Vec2, dir: vec2::Vec2, range: f32) -> Ray2d {
+        Ray2d {
+            origin,
+            range,
+            dir: dir.normalized()
+        }
+    }
+
+    // point at distance t along the ray
+    pub fn at(&self, t: f32) -> vec2::Vec2 {
+        self.origin.clone() + self.dir.clone() * t
+    }
+
+    pub fn end(&self) -> vec2::Vec2 {
+        self.at(self.range)
+    }
+}
\ No newline at end of file
@@ -1,8 +1,27 @@
 use super::vec3;
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Ray3d {
-    range: f32,
-    dir: vec3::Vec3
+    pub origin: vec3::Vec3,
+    pub range: f32,
+    pub dir: vec3::Vec3
 }
 
-// todo
\ No newline at end of file
+impl Ray3d {
+    pub fn new(origin: vec3::Vec3, dir: vec3::Vec3, range: f32) -> Ray3d {
+        Ray3d {
+            origin,
+            range,
+            dir: dir.normalized()
+        }
+    }
+
+    // point at distance t along the ray
+    pub fn at(&self, t: f32) -> vec3::Vec3 {
+        self.origin.clone() + self.dir.clone() * t
+    }
+
+    pub fn end(&self) -> vec3::Vec3 {
+        self.at(self.range)
+    }
+}
\ No newline at end of file
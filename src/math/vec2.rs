@@ -147,6 +147,19 @@ impl Vec2 {
         self.x * other.x + self.y * other.y
     }
 
+    // z of the 3d cross product, positive when other is counter-clockwise from self
+    pub fn cross(&self, other: Vec2) -> f32 {
+        self.x * other.y - self.y * other.x
+    }
+
+    // turned a quarter counter-clockwise
+    pub fn perpendicular(&self) -> Vec2 {
+        Vec2 {
+            x: -self.y,
+            y: self.x
+        }
+    }
+
     pub fn normalized(&self) -> Vec2 {
         self.clone() / self.clone().lenght()
     }
@@ -0,0 +1,249 @@
+use crate::math::vec3::Vec3;
+use crate::math::mat3::Mat3;
+use crate::math::quaternion::Quat;
+use crate::scene::transform::Transform;
+use crate::collision::shape::Shape;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BodyType {
+    // moved by gravity, forces and contacts
+    Dynamic,
+    // moved by its velocity or a target alone, pushes dynamic bodies without being pushed back
+    Kinematic,
+    // never moves
+    Static
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RigidBody {
+    pub body_type: BodyType,
+    pub shape: Shape,
+    // where the shape's origin is, the center of mass can sit elsewhere for hulls and meshes
+    pub position: Vec3,
+    pub rotation: Quat,
+    // of the center of mass, in units per second
+    pub linear_velocity: Vec3,
+    // radians per second around world axes
+    pub angular_velocity: Vec3,
+    pub friction: f32,
+    // bounciness, 0 stops dead and 1 bounces back as fast as it came
+    pub restitution: f32,
+    // fraction of velocity lost per second
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    pub gravity_scale: f32,
+    // bits of the collision layers it's on and of those it collides with
+    pub layer: u32,
+    pub mask: u32,
+    // scene node the world writes the transform of the body to
+    pub node: Option<usize>,
+    mass: f32,
+    inverse_mass: f32,
+    // in local space
+    center_of_mass: Vec3,
+    inverse_inertia: Mat3,
+    force: Vec3,
+    torque: Vec3,
+    sleeping: bool,
+    // seconds spent slow enough to sleep
+    pub(crate) sleep_time: f32,
+    // gravity it fell asleep under, scaled by gravity_scale. the world wakes it when that changes
+    pub(crate) sleep_gravity: Vec3,
+    kinematic_target: Option<(Vec3, Quat)>,
+    // the last step moved the body to a target, the velocity that took it there is cleared next step
+    moved_to_target: bool,
+    // transform before the last step, for interpolating between steps
+    previous_position: Vec3,
+    previous_rotation: Quat
+}
+
+impl RigidBody {
+    // a body at the origin with the mass of its shape filled with density. static and kinematic bodies
+    // have infinite mass whatever the density
+    pub fn new(body_type: BodyType, shape: Shape, density: f32) -> RigidBody {
+        let zero = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+        let mut ret = RigidBody {
+            body_type,
+            shape,
+            position: zero.clone(),
+            rotation: Quat::identity(),
+            linear_velocity: zero.clone(),
+            angular_velocity: zero.clone(),
+            friction: 0.5,
+            restitution: 0.0,
+            linear_damping: 0.01,
+            angular_damping: 0.05,
+            gravity_scale: 1.0,
+            layer: 1,
+            mask: std::u32::MAX,
+            node: None,
+            mass: 0.0,
+            inverse_mass: 0.0,
+            center_of_mass: zero.clone(),
+            inverse_inertia: Mat3::diagonal(&zero),
+            force: zero.clone(),
+            torque: zero.clone(),
+            sleeping: false,
+            sleep_time: 0.0,
+            sleep_gravity: zero.clone(),
+            kinematic_target: None,
+            moved_to_target: false,
+            previous_position: zero,
+            previous_rotation: Quat::identity()
+        };
+        let properties = ret.shape.mass_properties(density);
+        ret.center_of_mass = properties.center.clone();
+        ret.set_mass(properties.mass, &properties.inertia);
+        ret
+    }
+
+    // teleports the body, without interpolating from where it was
+    pub fn set_transform(&mut self, position: Vec3, rotation: Quat) {
+        self.previous_position = position.clone();
+        self.previous_rotation = rotation.clone();
+        self.position = position;
+        self.rotation = rotation;
+        self.wake();
+    }
+
+    // mass and the local inertia tensor around the center of mass, ignored unless the body is dynamic
+    pub fn set_mass(&mut self, mass: f32, inertia: &Mat3) {
+        if self.body_type != BodyType::Dynamic || mass <= 0.0 {
+            self.mass = 0.0;
+            self.inverse_mass = 0.0;
+            self.inverse_inertia = Mat3::diagonal(&Vec3 { x: 0.0, y: 0.0, z: 0.0 });
+            return;
+        }
+        self.mass = mass;
+        self.inverse_mass = 1.0 / mass;
+        self.inverse_inertia = inertia.invert().unwrap_or_else(|| Mat3::diagonal(&Vec3 { x: 0.0, y: 0.0, z: 0.0 }));
+    }
+
+    pub fn mass(&self) -> f32 {
+        self.mass
+    }
+
+    pub fn inverse_mass(&self) -> f32 {
+        self.inverse_mass
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.body_type == BodyType::Dynamic
+    }
+
+    // world space center of mass
+    pub fn center_of_mass(&self) -> Vec3 {
+        self.position.clone() + self.rotation.rotate(&self.center_of_mass)
+    }
+
+    pub fn local_center_of_mass(&self) -> &Vec3 {
+        &self.center_of_mass
+    }
+
+    // inverse inertia tensor rotated into world space
+    pub fn world_inverse_inertia(&self) -> Mat3 {
+        let rotation = self.rotation.to_mat3();
+        rotation.clone() * &self.inverse_inertia * &rotation.transpose()
+    }
+
+    // velocity of the body at a world space point
+    pub fn velocity_at(&self, point: &Vec3) -> Vec3 {
+        self.linear_velocity.clone() + self.angular_velocity.cross(point.clone() - self.center_of_mass())
+    }
+
+    // forces act over the next step, then they're cleared
+    pub fn apply_force(&mut self, force: &Vec3) {
+        self.force += force.clone();
+        self.wake();
+    }
+
+    pub fn apply_force_at(&mut self, force: &Vec3, point: &Vec3) {
+        self.torque += (point.clone() - self.center_of_mass()).cross(force.clone());
+        self.apply_force(force);
+    }
+
+    pub fn apply_torque(&mut self, torque: &Vec3) {
+        self.torque += torque.clone();
+        self.wake();
+    }
+
+    // instant change of momentum at a world space point
+    pub fn apply_impulse(&mut self, impulse: &Vec3, point: &Vec3) {
+        if !self.is_dynamic() {
+            return;
+        }
+        self.linear_velocity += impulse.clone() * self.inverse_mass;
+        let torque = (point.clone() - self.center_of_mass()).cross(impulse.clone());
+        self.angular_velocity += self.world_inverse_inertia() * &torque;
+        self.wake();
+    }
+
+    pub(crate) fn apply_impulse_unchecked(&mut self, impulse: &Vec3, offset: &Vec3, inverse_inertia: &Mat3) {
+        self.linear_velocity += impulse.clone() * self.inverse_mass;
+        self.angular_velocity += inverse_inertia.clone() * &offset.cross(impulse.clone());
+    }
+
+    pub(crate) fn take_force(&mut self) -> (Vec3, Vec3) {
+        let zero = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+        (std::mem::replace(&mut self.force, zero.clone()), std::mem::replace(&mut self.torque, zero))
+    }
+
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping
+    }
+
+    pub fn wake(&mut self) {
+        self.sleeping = false;
+        self.sleep_time = 0.0;
+    }
+
+    pub fn sleep(&mut self) {
+        if self.body_type == BodyType::Static {
+            return;
+        }
+        self.sleeping = true;
+        self.linear_velocity = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+        self.angular_velocity = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+    }
+
+    // moves a kinematic body to a transform over the next step, with the velocity that takes it there so
+    // whatever it touches gets carried along
+    pub fn set_kinematic_target(&mut self, position: Vec3, rotation: Quat) {
+        self.kinematic_target = Some((position, rotation));
+        self.wake();
+    }
+
+    // the target of this step. a body that reached its last target and has no new one stops there
+    pub(crate) fn take_kinematic_target(&mut self) -> Option<(Vec3, Quat)> {
+        let target = self.kinematic_target.take();
+        if target.is_none() && self.moved_to_target {
+            self.linear_velocity = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+            self.angular_velocity = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+        }
+        self.moved_to_target = target.is_some();
+        target
+    }
+
+    pub(crate) fn store_previous(&mut self) {
+        self.previous_position = self.position.clone();
+        self.previous_rotation = self.rotation.clone();
+    }
+
+    // rotation and translation of the body, with unit scale
+    pub fn transform(&self) -> Transform {
+        Transform {
+            translation: self.position.clone(),
+            rotation: self.rotation.clone(),
+            scale: Vec3 { x: 1.0, y: 1.0, z: 1.0 }
+        }
+    }
+
+    // between the transform before the last step at 0 and the current one at 1
+    pub fn interpolated_transform(&self, alpha: f32) -> Transform {
+        Transform {
+            translation: self.previous_position.clone() * (1.0 - alpha) + self.position.clone() * alpha,
+            rotation: self.previous_rotation.slerp(self.rotation.clone(), alpha),
+            scale: Vec3 { x: 1.0, y: 1.0, z: 1.0 }
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,564 @@
+use crate::math::vec3::Vec3;
+use crate::math::mat3::Mat3;
+use crate::math::quaternion::Quat;
+use super::body::RigidBody;
+use super::world::{tangent_basis, angular_velocity_between};
+
+use std::f32::consts::PI;
+
+// how one axis of a generic joint moves, translations are in units and rotations in radians
+#[derive(Debug, Clone, PartialEq)]
+pub enum Dof {
+    Free,
+    Locked,
+    Limited { lower: f32, upper: f32 },
+    // pulled back to 0, frequency is in oscillations per second and a damping_ratio of 1 stops it without
+    // overshooting
+    Spring { frequency: f32, damping_ratio: f32 }
+}
+
+// axes are in the space of body a. angles are those of b turned relative to a, starting from how they
+// were turned when the joint was made
+#[derive(Debug, Clone, PartialEq)]
+pub enum JointKind {
+    // glues the bodies together at the anchors
+    Fixed,
+    // pins the anchors together and lets b turn around axis alone. the motor turns b relative to a at
+    // motor_speed radians per second with up to max_motor_torque, 0 turns it off
+    Hinge { axis: Vec3, limits: Option<(f32, f32)>, motor_speed: f32, max_motor_torque: f32 },
+    // pins the anchors together and lets b turn any way. b's copy of axis stays within cone_limit radians
+    // of a's, twist_limits are on b turning around it
+    BallSocket { axis: Vec3, cone_limit: Option<f32>, twist_limits: Option<(f32, f32)> },
+    // lets b slide along axis through a's anchor, without turning. limits are on how far b's anchor is along
+    // the axis
+    Slider { axis: Vec3, limits: Option<(f32, f32)>, motor_speed: f32, max_motor_force: f32 },
+    // keeps the anchors length apart. a frequency above 0 makes it a spring
+    Distance { length: f32, frequency: f32, damping_ratio: f32 },
+    // every axis of a frame set on its own. the frame turns a's space, translations are of b's anchor
+    // from a's along the frame's axes and rotations around them
+    Generic { frame: Quat, linear: [Dof; 3], angular: [Dof; 3] }
+}
+
+// a constraint between the bodies a and b, solved together with the contacts
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joint {
+    pub a: usize,
+    pub b: usize,
+    // in the space of a and of b
+    pub anchor_a: Vec3,
+    pub anchor_b: Vec3,
+    // how b is turned relative to a when the joint is at rest
+    pub reference: Quat,
+    // whether a and b still collide with each other
+    pub collide_connected: bool,
+    // the joint breaks when it has to hold the bodies together harder than this, in newtons and newton
+    // meters. broken joints stay in the world and are skipped
+    pub break_force: f32,
+    pub break_torque: f32,
+    // velocity iterations for this joint, more than the world's stiffen long chains
+    pub iterations: Option<u32>,
+    pub kind: JointKind,
+    broken: bool,
+    // force and torque holding the bodies together in the last step
+    reaction: (f32, f32),
+    // of the last step, their impulses warm start the next one
+    rows: Vec<Row>
+}
+
+// one velocity constraint, the velocity along it is linear . (vb - va) + angular_b . wb - angular_a . wa.
+// the impulse accumulated over the step is kept between min and max
+#[derive(Debug, Clone, PartialEq)]
+struct Row {
+    linear: Vec3,
+    angular_a: Vec3,
+    angular_b: Vec3,
+    // changes of the bodies' angular velocities per unit of impulse
+    turn_a: Vec3,
+    turn_b: Vec3,
+    inverse_mass_a: f32,
+    inverse_mass_b: f32,
+    mass: f32,
+    bias: f32,
+    // softness of springs, 0 for rigid rows
+    gamma: f32,
+    min: f32,
+    max: f32,
+    impulse: f32
+}
+
+// what prepare needs of each body
+struct Side<'a> {
+    body: &'a RigidBody,
+    inverse_inertia: Mat3,
+    // from the center of mass to the anchor
+    offset: Vec3
+}
+
+impl Joint {
+    pub fn new(a: usize, b: usize, anchor_a: Vec3, anchor_b: Vec3, kind: JointKind) -> Joint {
+        Joint {
+            a,
+            b,
+            anchor_a,
+            anchor_b,
+            reference: Quat::identity(),
+            collide_connected: false,
+            break_force: std::f32::MAX,
+            break_torque: std::f32::MAX,
+            iterations: None,
+            kind,
+            broken: false,
+            reaction: (0.0, 0.0),
+            rows: vec![]
+        }
+    }
+
+    // a joint holding the bodies as they are now, at a world space anchor
+    pub fn at(a: usize, body_a: &RigidBody, b: usize, body_b: &RigidBody, anchor: &Vec3, kind: JointKind) -> Joint {
+        let local = |body: &RigidBody| body.rotation.conjugated().rotate(&(anchor.clone() - body.position.clone()));
+        let mut ret = Joint::new(a, b, local(body_a), local(body_b), kind);
+        ret.reference = body_a.rotation.conjugated().product(body_b.rotation.clone());
+        ret
+    }
+
+    pub fn is_broken(&self) -> bool {
+        self.broken
+    }
+
+    // puts a broken joint back together
+    pub fn repair(&mut self) {
+        self.broken = false;
+        self.rows.clear();
+    }
+
+    // force and torque the joint held the bodies together with in the last step
+    pub fn reaction(&self) -> (f32, f32) {
+        self.reaction
+    }
+
+    pub(crate) fn iterations(&self, default: u32) -> u32 {
+        self.iterations.unwrap_or(default)
+    }
+
+    // rows from where the bodies are now, then the impulses of the last step again
+    pub(crate) fn prepare(&mut self, bodies: &mut [RigidBody], dt: f32, baumgarte: f32) {
+        let mut rows = {
+            let a = Side::new(&bodies[self.a], &self.anchor_a);
+            let b = Side::new(&bodies[self.b], &self.anchor_b);
+            self.build(&a, &b, dt, baumgarte / dt)
+        };
+
+        // the same kind of joint always has the same rows, so the impulses line up
+        if rows.len() == self.rows.len() {
+            for (row, old) in rows.iter_mut().zip(self.rows.iter()) {
+                row.impulse = old.impulse;
+            }
+        }
+        for row in rows.iter() {
+            row.apply(bodies, self.a, self.b, row.impulse);
+        }
+        self.rows = rows;
+    }
+
+    // one velocity iteration
+    pub(crate) fn solve(&mut self, bodies: &mut [RigidBody]) {
+        for row in self.rows.iter_mut() {
+            let velocity = row.velocity(&bodies[self.a], &bodies[self.b]);
+            let impulse = -row.mass * (velocity + row.bias + row.gamma * row.impulse);
+            let total = (row.impulse + impulse).max(row.min).min(row.max);
+            row.apply(bodies, self.a, self.b, total - row.impulse);
+            row.impulse = total;
+        }
+    }
+
+    // measures what the joint held after the step's iterations and breaks it if that was too much
+    pub(crate) fn finish(&mut self, dt: f32) -> bool {
+        let zero = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+        let (mut force, mut torque) = (zero.clone(), zero);
+        for row in self.rows.iter() {
+            if row.linear.lenght() > 0.0 {
+                force += row.linear.clone() * row.impulse;
+            } else {
+                torque += row.angular_b.clone() * row.impulse;
+            }
+        }
+        self.reaction = (force.lenght() / dt, torque.lenght() / dt);
+        if self.reaction.0 > self.break_force || self.reaction.1 > self.break_torque {
+            self.broken = true;
+            self.rows.clear();
+        }
+        self.broken
+    }
+
+    fn build(&self, a: &Side, b: &Side, dt: f32, correction: f32) -> Vec<Row> {
+        let rotation_a = &a.body.rotation;
+        let anchor_a = a.body.center_of_mass() + a.offset.clone();
+        let anchor_b = b.body.center_of_mass() + b.offset.clone();
+        let separation = anchor_b - anchor_a;
+        // how far b is turned from where the joint holds it, as a rotation vector in world space
+        let turned = angular_velocity_between(rotation_a, &b.body.rotation.product(self.reference.conjugated()), 1.0);
+        let limit_bias = |error: f32| if error > 0.0 { error / dt } else { error * correction };
+        let axes = |rotation: &Quat| [
+            rotation.rotate(&Vec3 { x: 1.0, y: 0.0, z: 0.0 }),
+            rotation.rotate(&Vec3 { x: 0.0, y: 1.0, z: 0.0 }),
+            rotation.rotate(&Vec3 { x: 0.0, y: 0.0, z: 1.0 })
+        ];
+
+        let mut rows = vec![];
+        // equal rows hold at 0, limits push one way alone
+        let equal = |row: Row, error: f32| row.bounded(error * correction, std::f32::MIN, std::f32::MAX);
+        let limit = |row: Row, lower: f32, upper: f32, value: f32| {
+            vec![row.clone().bounded(limit_bias(value - lower), 0.0, std::f32::MAX),
+                 row.negated().bounded(limit_bias(upper - value), 0.0, std::f32::MAX)]
+        };
+        let motor = |row: Row, speed: f32, max: f32| row.bounded(-speed, -max * dt, max * dt);
+        let point = |rows: &mut Vec<Row>| {
+            for axis in axes(&Quat::identity()).iter() {
+                rows.push(equal(Row::linear(axis, a, b, &Vec3 { x: 0.0, y: 0.0, z: 0.0 }), separation.dot(axis.clone())));
+            }
+        };
+        let lock = |rows: &mut Vec<Row>| {
+            for axis in axes(&Quat::identity()).iter() {
+                rows.push(equal(Row::angular(axis, a, b), turned.dot(axis.clone())));
+            }
+        };
+
+        match &self.kind {
+            JointKind::Fixed => {
+                point(&mut rows);
+                lock(&mut rows);
+            }
+            JointKind::Hinge { axis, limits, motor_speed, max_motor_torque } => {
+                point(&mut rows);
+                let along = rotation_a.rotate(&axis.normalized());
+                let along_b = b.body.rotation.product(self.reference.conjugated()).rotate(&axis.normalized());
+                // b's axis kept on a's, the angle between them is their cross product for small angles
+                let misaligned = along.cross(along_b);
+                for across in tangent_basis(&along).iter() {
+                    rows.push(equal(Row::angular(across, a, b), misaligned.dot(across.clone())));
+                }
+                let angle = turned.dot(along.clone());
+                if let Some((lower, upper)) = limits {
+                    rows.extend(limit(Row::angular(&along, a, b), *lower, *upper, angle));
+                }
+                if *max_motor_torque > 0.0 {
+                    rows.push(motor(Row::angular(&along, a, b), *motor_speed, *max_motor_torque));
+                }
+            }
+            JointKind::BallSocket { axis, cone_limit, twist_limits } => {
+                point(&mut rows);
+                let along = rotation_a.rotate(&axis.normalized());
+                let along_b = b.body.rotation.product(self.reference.conjugated()).rotate(&axis.normalized());
+                if let Some(cone) = cone_limit {
+                    // the swing turns around the axis both copies of axis are at right angles to
+                    let swing = along.cross(along_b.clone());
+                    let swing = if swing.lenght() > 1e-6 { swing.normalized() } else { tangent_basis(&along)[0].clone() };
+                    let angle = along.dot(along_b.clone()).max(-1.0).min(1.0).acos();
+                    rows.push(Row::angular(&(swing * -1.0), a, b).bounded(limit_bias(cone - angle), 0.0, std::f32::MAX));
+                }
+                if let Some((lower, upper)) = twist_limits {
+                    let middle = (along.clone() + along_b).normalized();
+                    let angle = turned.dot(middle.clone());
+                    rows.extend(limit(Row::angular(&middle, a, b), *lower, *upper, angle));
+                }
+            }
+            JointKind::Slider { axis, limits, motor_speed, max_motor_force } => {
+                lock(&mut rows);
+                let along = rotation_a.rotate(&axis.normalized());
+                for across in tangent_basis(&along).iter() {
+                    rows.push(equal(Row::linear(across, a, b, &separation), separation.dot(across.clone())));
+                }
+                let translation = separation.dot(along.clone());
+                if let Some((lower, upper)) = limits {
+                    rows.extend(limit(Row::linear(&along, a, b, &separation), *lower, *upper, translation));
+                }
+                if *max_motor_force > 0.0 {
+                    rows.push(motor(Row::linear(&along, a, b, &separation), *motor_speed, *max_motor_force));
+                }
+            }
+            JointKind::Distance { length, frequency, damping_ratio } => {
+                let distance = separation.lenght();
+                let direction = if distance > 1e-6 { separation.clone() / distance } else { Vec3 { x: 0.0, y: 1.0, z: 0.0 } };
+                let row = Row::linear(&direction, a, b, &Vec3 { x: 0.0, y: 0.0, z: 0.0 });
+                let error = distance - length;
+                rows.push(if *frequency > 0.0 { row.soft(error, *frequency, *damping_ratio, dt) } else { equal(row, error) });
+            }
+            JointKind::Generic { frame, linear, angular } => {
+                let frame_axes = axes(&rotation_a.product(frame.clone()));
+                for (axis, dof) in frame_axes.iter().zip(linear.iter()) {
+                    let row = Row::linear(axis, a, b, &separation);
+                    rows.extend(dof.rows(row, separation.dot(axis.clone()), dt, correction, &limit));
+                }
+                for (axis, dof) in frame_axes.iter().zip(angular.iter()) {
+                    let row = Row::angular(axis, a, b);
+                    rows.extend(dof.rows(row, turned.dot(axis.clone()), dt, correction, &limit));
+                }
+            }
+        }
+        rows
+    }
+}
+
+impl<'a> Side<'a> {
+    fn new(body: &'a RigidBody, anchor: &Vec3) -> Side<'a> {
+        Side {
+            body,
+            inverse_inertia: body.world_inverse_inertia(),
+            offset: body.rotation.rotate(&(anchor.clone() - body.local_center_of_mass().clone()))
+        }
+    }
+}
+
+impl Dof {
+    fn rows(&self, row: Row, value: f32, dt: f32, correction: f32, limit: &dyn Fn(Row, f32, f32, f32) -> Vec<Row>) -> Vec<Row> {
+        match self {
+            Dof::Free => vec![],
+            Dof::Locked => vec![row.bounded(value * correction, std::f32::MIN, std::f32::MAX)],
+            Dof::Limited { lower, upper } => limit(row, *lower, *upper, value),
+            Dof::Spring { frequency, damping_ratio } => vec![row.soft(value, *frequency, *damping_ratio, dt)]
+        }
+    }
+}
+
+impl Row {
+    fn new(linear: Vec3, angular_a: Vec3, angular_b: Vec3, a: &Side, b: &Side) -> Row {
+        let turn_a = a.inverse_inertia.clone() * &angular_a;
+        let turn_b = b.inverse_inertia.clone() * &angular_b;
+        let (inverse_mass_a, inverse_mass_b) = (a.body.inverse_mass(), b.body.inverse_mass());
+        let k = linear.dot(linear.clone()) * (inverse_mass_a + inverse_mass_b)
+            + angular_a.dot(turn_a.clone()) + angular_b.dot(turn_b.clone());
+        Row {
+            linear,
+            angular_a,
+            angular_b,
+            turn_a,
+            turn_b,
+            inverse_mass_a,
+            inverse_mass_b,
+            mass: if k > 0.0 { 1.0 / k } else { 0.0 },
+            bias: 0.0,
+            gamma: 0.0,
+            min: std::f32::MIN,
+            max: std::f32::MAX,
+            impulse: 0.0
+        }
+    }
+
+    // b's anchor moving along direction relative to a's. a's lever reaches past its anchor by separation,
+    // to where b's anchor is
+    fn linear(direction: &Vec3, a: &Side, b: &Side, separation: &Vec3) -> Row {
+        let lever_a = a.offset.clone() + separation.clone();
+        Row::new(direction.clone(), lever_a.cross(direction.clone()), b.offset.cross(direction.clone()), a, b)
+    }
+
+    // b turning around axis relative to a
+    fn angular(axis: &Vec3, a: &Side, b: &Side) -> Row {
+        Row::new(Vec3 { x: 0.0, y: 0.0, z: 0.0 }, axis.clone(), axis.clone(), a, b)
+    }
+
+    fn negated(&self) -> Row {
+        Row {
+            linear: self.linear.clone() * -1.0,
+            angular_a: self.angular_a.clone() * -1.0,
+            angular_b: self.angular_b.clone() * -1.0,
+            turn_a: self.turn_a.clone() * -1.0,
+            turn_b: self.turn_b.clone() * -1.0,
+            ..self.clone()
+        }
+    }
+
+    fn bounded(mut self, bias: f32, min: f32, max: f32) -> Row {
+        self.bias = bias;
+        self.min = min;
+        self.max = max;
+        self
+    }
+
+    // a spring and damper turned into a bias and a softness
+    fn soft(mut self, error: f32, frequency: f32, damping_ratio: f32, dt: f32) -> Row {
+        if self.mass <= 0.0 || frequency <= 0.0 {
+            return self;
+        }
+        let omega = 2.0 * PI * frequency;
+        let damping = 2.0 * self.mass * damping_ratio * omega;
+        let stiffness = self.mass * omega * omega;
+        let gamma = dt * (damping + dt * stiffness);
+        let gamma = if gamma > 0.0 { 1.0 / gamma } else { 0.0 };
+        self.mass = 1.0 / (1.0 / self.mass + gamma);
+        self.bias = error * dt * stiffness * gamma;
+        self.gamma = gamma;
+        self
+    }
+
+    fn velocity(&self, body_a: &RigidBody, body_b: &RigidBody) -> f32 {
+        self.linear.dot(body_b.linear_velocity.clone() - body_a.linear_velocity.clone())
+            + self.angular_b.dot(body_b.angular_velocity.clone()) - self.angular_a.dot(body_a.angular_velocity.clone())
+    }
+
+    fn apply(&self, bodies: &mut [RigidBody], a: usize, b: usize, impulse: f32) {
+        bodies[a].linear_velocity -= self.linear.clone() * (impulse * self.inverse_mass_a);
+        bodies[a].angular_velocity -= self.turn_a.clone() * impulse;
+        bodies[b].linear_velocity += self.linear.clone() * (impulse * self.inverse_mass_b);
+        bodies[b].angular_velocity += self.turn_b.clone() * impulse;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collision::shape::Shape;
+    use crate::physics::body::BodyType;
+    use crate::physics::world::PhysicsWorld;
+
+    fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn ball(body_type: BodyType, position: Vec3) -> RigidBody {
+        let mut ret = RigidBody::new(body_type, Shape::Sphere { radius: 0.1 }, 1.0);
+        ret.set_transform(position, Quat::identity());
+        ret
+    }
+
+    // a ball joined at an anchor to a static ball, the joint is made where the balls are now
+    fn hang(position: Vec3, anchor: Vec3, kind: JointKind) -> (PhysicsWorld, usize, usize) {
+        let mut world = PhysicsWorld::new();
+        let a = world.add_body(ball(BodyType::Static, anchor.clone()));
+        let b = world.add_body(ball(BodyType::Dynamic, position));
+        let joint = Joint::at(a, world.body(a), b, world.body(b), &anchor, kind);
+        let index = world.add_joint(joint);
+        (world, b, index)
+    }
+
+    // rotation of the ball around z, in radians
+    fn angle_around_z(body: &RigidBody) -> f32 {
+        2.0 * body.rotation.z.atan2(body.rotation.w)
+    }
+
+    #[test]
+    fn distance_joints_keep_their_length() {
+        let mut world = PhysicsWorld::new();
+        let a = world.add_body(ball(BodyType::Static, vec3(0.0, 0.0, 0.0)));
+        let b = world.add_body(ball(BodyType::Dynamic, vec3(2.0, 0.0, 0.0)));
+        let kind = JointKind::Distance { length: 2.0, frequency: 0.0, damping_ratio: 0.0 };
+        world.add_joint(Joint::new(a, b, vec3(0.0, 0.0, 0.0), vec3(0.0, 0.0, 0.0), kind));
+        let mut lowest = 0.0f32;
+        for _ in 0..120 {
+            world.step();
+            assert!((world.body(b).position.lenght() - 2.0).abs() < 0.05);
+            lowest = lowest.min(world.body(b).position.y);
+        }
+        // and it swung through the bottom
+        assert!(lowest < -1.95);
+    }
+
+    #[test]
+    fn ball_sockets_pin_the_anchors_together() {
+        let kind = JointKind::BallSocket { axis: vec3(0.0, 1.0, 0.0), cone_limit: None, twist_limits: None };
+        let (mut world, b, _) = hang(vec3(1.0, 0.0, 0.0), vec3(0.0, 0.0, 0.0), kind);
+        for _ in 0..120 {
+            world.step();
+        }
+        let anchor = world.body(b).position.clone() + world.body(b).rotation.rotate(&vec3(-1.0, 0.0, 0.0));
+        assert!(anchor.lenght() < 0.05);
+    }
+
+    #[test]
+    fn hinge_limits_stop_the_swing() {
+        let kind = JointKind::Hinge { axis: vec3(0.0, 0.0, 1.0), limits: Some((-0.5, 0.5)), motor_speed: 0.0, max_motor_torque: 0.0 };
+        let (mut world, b, _) = hang(vec3(1.0, 0.0, 0.0), vec3(0.0, 0.0, 0.0), kind);
+        for _ in 0..120 {
+            world.step();
+            assert!(angle_around_z(world.body(b)) > -0.55);
+        }
+        // resting on the lower limit, it would hang straight down without it
+        assert!((angle_around_z(world.body(b)) + 0.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn hinge_motors_turn_at_their_speed() {
+        let kind = JointKind::Hinge { axis: vec3(0.0, 0.0, 1.0), limits: None, motor_speed: 2.0, max_motor_torque: 100.0 };
+        let (mut world, b, _) = hang(vec3(0.0, 0.0, 0.0), vec3(0.0, 0.0, 0.0), kind);
+        world.gravity = vec3(0.0, 0.0, 0.0);
+        world.body_mut(b).angular_damping = 0.0;
+        for _ in 0..30 {
+            world.step();
+        }
+        assert!((world.body(b).angular_velocity.z - 2.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn slider_limits_stop_the_slide() {
+        let kind = JointKind::Slider { axis: vec3(0.0, 1.0, 0.0), limits: Some((-1.0, 0.0)), motor_speed: 0.0, max_motor_force: 0.0 };
+        let (mut world, b, _) = hang(vec3(0.0, 0.0, 0.0), vec3(0.0, 0.0, 0.0), kind);
+        world.body_mut(b).apply_force(&vec3(1.0, 0.0, 0.0));
+        for _ in 0..120 {
+            world.step();
+            assert!(world.body(b).position.y > -1.05);
+        }
+        assert!((world.body(b).position.y + 1.0).abs() < 0.05);
+        // it only slides along the axis
+        assert!(world.body(b).position.x.abs() < 1e-2);
+    }
+
+    #[test]
+    fn more_iterations_stretch_a_chain_less() {
+        let stretch = |iterations: Option<u32>| {
+            let mut world = PhysicsWorld::new();
+            let mut previous = world.add_body(ball(BodyType::Static, vec3(0.0, 0.0, 0.0)));
+            for i in 1..=10 {
+                let link = world.add_body(ball(BodyType::Dynamic, vec3(0.0, -0.5 * i as f32, 0.0)));
+                let anchor = vec3(0.0, -0.5 * i as f32 + 0.5, 0.0);
+                let kind = JointKind::BallSocket { axis: vec3(0.0, 1.0, 0.0), cone_limit: None, twist_limits: None };
+                let mut joint = Joint::at(previous, world.body(previous), link, world.body(link), &anchor, kind);
+                joint.iterations = iterations;
+                world.add_joint(joint);
+                previous = link;
+            }
+            for _ in 0..30 {
+                world.step();
+            }
+            -world.body(previous).position.y - 5.0
+        };
+        assert_eq!(Joint::new(0, 1, vec3(0.0, 0.0, 0.0), vec3(0.0, 0.0, 0.0), JointKind::Fixed).iterations(8), 8);
+        assert!(stretch(Some(40)) < stretch(None));
+    }
+
+    #[test]
+    fn joints_break_past_their_force() {
+        let kind = JointKind::BallSocket { axis: vec3(0.0, 1.0, 0.0), cone_limit: None, twist_limits: None };
+        let (mut world, b, joint) = hang(vec3(0.0, -1.0, 0.0), vec3(0.0, 0.0, 0.0), kind);
+        let weight = world.body(b).mass() * 9.81;
+        world.joint_mut(joint).break_force = weight * 2.0;
+        for _ in 0..10 {
+            world.step();
+        }
+        assert!(!world.joint(joint).is_broken());
+        assert!((world.joint(joint).reaction().0 - weight).abs() < weight * 0.05);
+
+        world.body_mut(b).gravity_scale = 3.0;
+        world.step();
+        assert!(world.joint(joint).is_broken());
+        for _ in 0..10 {
+            world.step();
+        }
+        assert!(world.body(b).position.y < -1.1);
+    }
+
+    #[test]
+    fn sleeping_bodies_wake_when_their_load_changes() {
+        let kind = JointKind::BallSocket { axis: vec3(0.0, 1.0, 0.0), cone_limit: None, twist_limits: None };
+        let (mut world, b, joint) = hang(vec3(0.0, -1.0, 0.0), vec3(0.0, 0.0, 0.0), kind);
+        world.joint_mut(joint).break_force = world.body(b).mass() * 9.81 * 2.0;
+        for _ in 0..60 {
+            world.step();
+        }
+        assert!(world.body(b).is_sleeping());
+
+        world.body_mut(b).gravity_scale = 3.0;
+        world.step();
+        assert!(!world.body(b).is_sleeping());
+        assert!(world.joint(joint).is_broken());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,3 @@
+pub mod body;
+pub mod joint;
+pub mod world;
\ No newline at end of file
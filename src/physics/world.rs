@@ -0,0 +1,691 @@
+use crate::math::vec3::Vec3;
+use crate::math::mat3::Mat3;
+use crate::math::quaternion::Quat;
+use crate::scene::node::Node;
+use super::body::{RigidBody, BodyType};
+use super::joint::Joint;
+use crate::collision::aabb_tree::AabbTree;
+use crate::collision::contact::{self, Manifold};
+use crate::collision::world::layers_collide;
+
+// two bodies touching after a step, with the impulses the solver pushed them apart with
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contact {
+    pub a: usize,
+    pub b: usize,
+    // normal from a to b
+    pub manifold: Manifold,
+    // per point, accumulated over the step and reused to warm start the next one
+    pub normal_impulses: Vec<f32>,
+    tangent_impulses: Vec<[f32; 2]>,
+    // points in a's local space, to recognize them next step
+    anchors: Vec<Vec3>
+}
+
+// what the solver keeps per contact point while iterating
+struct SolverPoint {
+    // from the centers of mass to the point
+    offset_a: Vec3,
+    offset_b: Vec3,
+    normal_mass: f32,
+    tangent_mass: [f32; 2],
+    // separating velocity the point should reach, from restitution and overlap
+    bias: f32
+}
+
+struct SolverContact {
+    tangents: [Vec3; 2],
+    friction: f32,
+    points: Vec<SolverPoint>
+}
+
+// rigid body simulation on a fixed step. contacts and joints are solved together with sequential impulses,
+// bodies that come to rest sleep together with everything they touch or are joined to. the same bodies and
+// inputs always give the same results
+pub struct PhysicsWorld {
+    pub gravity: Vec3,
+    // seconds per step
+    pub fixed_step: f32,
+    // most steps an update takes, time beyond that is dropped so one slow frame doesn't cause more
+    pub max_steps: u32,
+    pub velocity_iterations: u32,
+    // bodies this close get contacts before they touch
+    pub contact_margin: f32,
+    // overlap that's left alone, so resting contacts don't jitter
+    pub penetration_slop: f32,
+    // fraction of the overlap and of joint errors pushed out per step
+    pub baumgarte: f32,
+    // approach speeds below this don't bounce
+    pub restitution_threshold: f32,
+    // bodies slower than these for sleep_delay seconds fall asleep
+    pub sleep_linear_velocity: f32,
+    pub sleep_angular_velocity: f32,
+    pub sleep_delay: f32,
+    bodies: Vec<RigidBody>,
+    joints: Vec<Joint>,
+    // tree leaf of every body
+    proxies: Vec<usize>,
+    tree: AabbTree,
+    contacts: Vec<Contact>,
+    // simulated time owed to the frame
+    accumulator: f32
+}
+
+impl PhysicsWorld {
+    pub fn new() -> PhysicsWorld {
+        PhysicsWorld {
+            gravity: Vec3 { x: 0.0, y: -9.81, z: 0.0 },
+            fixed_step: 1.0 / 60.0,
+            max_steps: 8,
+            velocity_iterations: 10,
+            contact_margin: 0.02,
+            penetration_slop: 0.005,
+            baumgarte: 0.2,
+            restitution_threshold: 1.0,
+            sleep_linear_velocity: 0.05,
+            sleep_angular_velocity: 0.05,
+            sleep_delay: 0.5,
+            bodies: vec![],
+            joints: vec![],
+            proxies: vec![],
+            tree: AabbTree::default(),
+            contacts: vec![],
+            accumulator: 0.0
+        }
+    }
+
+    // returns the index of the body
+    pub fn add_body(&mut self, body: RigidBody) -> usize {
+        let index = self.bodies.len();
+        self.proxies.push(self.tree.insert(&body.shape.aabb(&body.position, &body.rotation), index));
+        self.bodies.push(body);
+        index
+    }
+
+    pub fn body(&self, index: usize) -> &RigidBody {
+        &self.bodies[index]
+    }
+
+    pub fn body_mut(&mut self, index: usize) -> &mut RigidBody {
+        &mut self.bodies[index]
+    }
+
+    pub fn bodies(&self) -> &[RigidBody] {
+        &self.bodies
+    }
+
+    // returns the index of the joint
+    pub fn add_joint(&mut self, joint: Joint) -> usize {
+        self.bodies[joint.a].wake();
+        self.bodies[joint.b].wake();
+        self.joints.push(joint);
+        self.joints.len() - 1
+    }
+
+    pub fn joint(&self, index: usize) -> &Joint {
+        &self.joints[index]
+    }
+
+    // wakes the joined bodies, the joint may no longer hold them the way they fell asleep
+    pub fn joint_mut(&mut self, index: usize) -> &mut Joint {
+        self.bodies[self.joints[index].a].wake();
+        self.bodies[self.joints[index].b].wake();
+        &mut self.joints[index]
+    }
+
+    pub fn joints(&self) -> &[Joint] {
+        &self.joints
+    }
+
+    // contacts of the last step, sleeping bodies have none
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    // runs as many fixed steps as fit into the time passed, returns how many
+    pub fn update(&mut self, delta: f32) -> u32 {
+        self.accumulator += delta;
+        let mut steps = 0;
+        while self.accumulator >= self.fixed_step && steps < self.max_steps {
+            self.step();
+            self.accumulator -= self.fixed_step;
+            steps += 1;
+        }
+        if steps == self.max_steps {
+            self.accumulator = self.accumulator.min(self.fixed_step);
+        }
+        steps
+    }
+
+    // how far the time passed is between the last step and the next one, for interpolating transforms
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.fixed_step).max(0.0).min(1.0)
+    }
+
+    // writes the interpolated transform of every body with a node into that node, keeping its scale. the
+    // nodes should be at the root of their scene, their transforms are taken as world transforms
+    pub fn sync_nodes(&self, nodes: &mut [Node]) {
+        let alpha = self.alpha();
+        for body in self.bodies.iter() {
+            if let Some(node) = body.node.and_then(|node| nodes.get_mut(node)) {
+                let transform = body.interpolated_transform(alpha);
+                node.transform.translation = transform.translation;
+                node.transform.rotation = transform.rotation;
+            }
+        }
+    }
+
+    pub fn step(&mut self) {
+        let dt = self.fixed_step;
+        let targets = self.apply_forces(dt);
+        let pairs = self.broad_phase();
+        let contacts = self.narrow_phase(&pairs);
+        let contacts = self.solve(contacts, dt);
+        self.integrate(&targets, dt);
+        self.update_sleep(&contacts, dt);
+        self.contacts = contacts;
+    }
+
+    // gravity, forces and damping go into the velocities before contacts are solved, that's what makes the
+    // integration semi-implicit. kinematic targets turn into the velocities that reach them. a sleeping body
+    // whose gravity changed wakes, the joints and contacts then wake whatever it rests on
+    fn apply_forces(&mut self, dt: f32) -> Vec<Option<(Vec3, Quat)>> {
+        let gravity = self.gravity.clone();
+        self.bodies.iter_mut()
+            .map(|body| {
+                body.store_previous();
+                if body.is_sleeping() && gravity.clone() * body.gravity_scale != body.sleep_gravity {
+                    body.wake();
+                }
+                let (force, torque) = body.take_force();
+                match body.body_type {
+                    BodyType::Dynamic if !body.is_sleeping() => {
+                        let acceleration = gravity.clone() * body.gravity_scale + force * body.inverse_mass();
+                        body.linear_velocity += acceleration * dt;
+                        body.angular_velocity += body.world_inverse_inertia() * &torque * dt;
+                        body.linear_velocity *= 1.0 / (1.0 + dt * body.linear_damping);
+                        body.angular_velocity *= 1.0 / (1.0 + dt * body.angular_damping);
+                        None
+                    }
+                    BodyType::Kinematic => {
+                        let target = body.take_kinematic_target();
+                        if let Some((position, rotation)) = target.as_ref() {
+                            body.linear_velocity = (position.clone() - body.position.clone()) / dt;
+                            body.angular_velocity = angular_velocity_between(&body.rotation, rotation, dt);
+                        }
+                        target
+                    }
+                    _ => None
+                }
+            })
+            .collect()
+    }
+
+    // moves the bodies' leaves in the tree, which then gives the pairs whose boxes overlap. pairs come out
+    // sorted so the solver order never changes
+    fn broad_phase(&mut self) -> Vec<(usize, usize)> {
+        for (body, proxy) in self.bodies.iter().zip(self.proxies.iter()) {
+            let aabb = body.shape.aabb(&body.position, &body.rotation).expanded(self.contact_margin);
+            self.tree.update(*proxy, &aabb);
+        }
+
+        let awake = |body: &RigidBody| body.body_type != BodyType::Static && !body.is_sleeping();
+        // bodies held by a joint leave each other alone unless the joint says otherwise
+        let jointed = |a: usize, b: usize| {
+            self.joints.iter().any(|j| !j.collide_connected && !j.is_broken() && (j.a.min(j.b), j.a.max(j.b)) == (a, b))
+        };
+        self.tree.pairs().into_iter()
+            .filter(|(i, j)| {
+                let (a, b) = (&self.bodies[*i], &self.bodies[*j]);
+                let aabb_a = a.shape.aabb(&a.position, &a.rotation).expanded(self.contact_margin);
+                let aabb_b = b.shape.aabb(&b.position, &b.rotation).expanded(self.contact_margin);
+                (a.is_dynamic() || b.is_dynamic()) && (awake(a) || awake(b))
+                    && layers_collide(a.layer, a.mask, b.layer, b.mask) && aabb_a.overlaps(&aabb_b) && !jointed(*i, *j)
+            })
+            .collect()
+    }
+
+    fn narrow_phase(&mut self, pairs: &[(usize, usize)]) -> Vec<Contact> {
+        let mut ret = vec![];
+        for (a, b) in pairs.iter().cloned() {
+            let manifolds = {
+                let (body_a, body_b) = (&self.bodies[a], &self.bodies[b]);
+                contact::collide(&body_a.shape, &body_a.position, &body_a.rotation,
+                                 &body_b.shape, &body_b.position, &body_b.rotation, self.contact_margin)
+            };
+            if manifolds.is_empty() {
+                continue;
+            }
+
+            // a sleeping body is woken by anything awake that touches it
+            for (sleeper, other) in [(a, b), (b, a)].iter() {
+                let other = &self.bodies[*other];
+                if self.bodies[*sleeper].is_sleeping() && !other.is_sleeping() && other.body_type != BodyType::Static {
+                    self.bodies[*sleeper].wake();
+                }
+            }
+
+            let inverse_rotation = self.bodies[a].rotation.conjugated();
+            for manifold in manifolds.into_iter() {
+                let anchors: Vec<Vec3> = manifold.points.iter()
+                    .map(|point| inverse_rotation.rotate(&(point.position.clone() - self.bodies[a].position.clone())))
+                    .collect();
+
+                // warm start from the points of the last step that are still in the same place
+                let previous = self.contacts.iter()
+                    .find(|c| c.a == a && c.b == b && c.manifold.normal.dot(manifold.normal.clone()) > 0.95);
+                let mut normal_impulses = vec![0.0; anchors.len()];
+                let mut tangent_impulses = vec![[0.0; 2]; anchors.len()];
+                if let Some(previous) = previous {
+                    for (i, anchor) in anchors.iter().enumerate() {
+                        let matching = previous.anchors.iter()
+                            .position(|old| old.distance_to(anchor.clone()) < 0.05);
+                        if let Some(j) = matching {
+                            normal_impulses[i] = previous.normal_impulses[j];
+                            tangent_impulses[i] = previous.tangent_impulses[j];
+                        }
+                    }
+                }
+
+                ret.push(Contact {
+                    a,
+                    b,
+                    manifold,
+                    normal_impulses,
+                    tangent_impulses,
+                    anchors
+                });
+            }
+        }
+        ret
+    }
+
+    fn solve(&mut self, mut contacts: Vec<Contact>, dt: f32) -> Vec<Contact> {
+        let inverse_inertias: Vec<Mat3> = self.bodies.iter().map(|body| body.world_inverse_inertia()).collect();
+        let centers: Vec<Vec3> = self.bodies.iter().map(|body| body.center_of_mass()).collect();
+
+        // masses along the normal and tangents, and the velocity each point has to reach
+        let mut solver_contacts = vec![];
+        for c in contacts.iter() {
+            let (body_a, body_b) = (&self.bodies[c.a], &self.bodies[c.b]);
+            let normal = &c.manifold.normal;
+            let tangents = tangent_basis(normal);
+            let effective_mass = |offset_a: &Vec3, offset_b: &Vec3, axis: &Vec3| {
+                let angular_a = (inverse_inertias[c.a].clone() * &offset_a.cross(axis.clone())).cross(offset_a.clone());
+                let angular_b = (inverse_inertias[c.b].clone() * &offset_b.cross(axis.clone())).cross(offset_b.clone());
+                let k = body_a.inverse_mass() + body_b.inverse_mass() + axis.dot(angular_a + angular_b);
+                if k > 0.0 { 1.0 / k } else { 0.0 }
+            };
+
+            let restitution = body_a.restitution.max(body_b.restitution);
+            let points = c.manifold.points.iter()
+                .map(|point| {
+                    let offset_a = point.position.clone() - centers[c.a].clone();
+                    let offset_b = point.position.clone() - centers[c.b].clone();
+                    let approach = (body_b.velocity_at(&point.position) - body_a.velocity_at(&point.position)).dot(normal.clone());
+
+                    // still apart: the gap may close within this step but no further. overlapping: push out
+                    let mut bias = if point.depth < 0.0 {
+                        point.depth / dt
+                    } else {
+                        self.baumgarte / dt * (point.depth - self.penetration_slop).max(0.0)
+                    };
+                    if approach < -self.restitution_threshold {
+                        bias = bias.max(-restitution * approach);
+                    }
+
+                    SolverPoint {
+                        normal_mass: effective_mass(&offset_a, &offset_b, normal),
+                        tangent_mass: [effective_mass(&offset_a, &offset_b, &tangents[0]), effective_mass(&offset_a, &offset_b, &tangents[1])],
+                        offset_a,
+                        offset_b,
+                        bias
+                    }
+                })
+                .collect();
+
+            solver_contacts.push(SolverContact {
+                tangents,
+                friction: (body_a.friction * body_b.friction).sqrt(),
+                points
+            });
+        }
+
+        // warm start
+        for (c, solver) in contacts.iter().zip(solver_contacts.iter()) {
+            for (i, point) in solver.points.iter().enumerate() {
+                let impulse = c.manifold.normal.clone() * c.normal_impulses[i]
+                    + solver.tangents[0].clone() * c.tangent_impulses[i][0]
+                    + solver.tangents[1].clone() * c.tangent_impulses[i][1];
+                self.apply_pair_impulse(c.a, c.b, &impulse, point, &inverse_inertias);
+            }
+        }
+
+        // a joint wakes what it holds when either end is awake, and is left alone when both sleep
+        let mut active = vec![];
+        for (index, joint) in self.joints.iter().enumerate() {
+            let (a, b) = (&self.bodies[joint.a], &self.bodies[joint.b]);
+            let moving = |body: &RigidBody| body.is_dynamic() && !body.is_sleeping();
+            if joint.is_broken() || !(a.is_dynamic() || b.is_dynamic()) || !(moving(a) || moving(b)) {
+                continue;
+            }
+            active.push(index);
+        }
+        for index in active.iter() {
+            for body in [self.joints[*index].a, self.joints[*index].b].iter() {
+                if self.bodies[*body].is_sleeping() {
+                    self.bodies[*body].wake();
+                }
+            }
+        }
+        for index in active.iter() {
+            self.joints[*index].prepare(&mut self.bodies, dt, self.baumgarte);
+        }
+
+        // joints can ask for more iterations than the contacts get
+        let iterations = active.iter()
+            .map(|index| self.joints[*index].iterations(self.velocity_iterations))
+            .fold(self.velocity_iterations, u32::max);
+        for iteration in 0..iterations {
+            for index in active.iter() {
+                if iteration < self.joints[*index].iterations(self.velocity_iterations) {
+                    self.joints[*index].solve(&mut self.bodies);
+                }
+            }
+            if iteration >= self.velocity_iterations {
+                continue;
+            }
+            for (c, solver) in contacts.iter_mut().zip(solver_contacts.iter()) {
+                let normal = c.manifold.normal.clone();
+                for (i, point) in solver.points.iter().enumerate() {
+                    // friction first, limited by the normal impulse so far
+                    for (axis, tangent) in solver.tangents.iter().enumerate() {
+                        let speed = self.relative_velocity(c.a, c.b, point).dot(tangent.clone());
+                        let limit = solver.friction * c.normal_impulses[i];
+                        let old = c.tangent_impulses[i][axis];
+                        let new = (old - speed * point.tangent_mass[axis]).max(-limit).min(limit);
+                        c.tangent_impulses[i][axis] = new;
+                        self.apply_pair_impulse(c.a, c.b, &(tangent.clone() * (new - old)), point, &inverse_inertias);
+                    }
+
+                    // contacts push, never pull
+                    let speed = self.relative_velocity(c.a, c.b, point).dot(normal.clone());
+                    let old = c.normal_impulses[i];
+                    let new = (old - (speed - point.bias) * point.normal_mass).max(0.0);
+                    c.normal_impulses[i] = new;
+                    self.apply_pair_impulse(c.a, c.b, &(normal.clone() * (new - old)), point, &inverse_inertias);
+                }
+            }
+        }
+
+        for index in active.iter() {
+            self.joints[*index].finish(dt);
+        }
+        contacts
+    }
+
+    // velocity of b relative to a at a contact point
+    fn relative_velocity(&self, a: usize, b: usize, point: &SolverPoint) -> Vec3 {
+        let (body_a, body_b) = (&self.bodies[a], &self.bodies[b]);
+        body_b.linear_velocity.clone() + body_b.angular_velocity.cross(point.offset_b.clone())
+            - body_a.linear_velocity.clone() - body_a.angular_velocity.cross(point.offset_a.clone())
+    }
+
+    // pushes b along the impulse and a the other way
+    fn apply_pair_impulse(&mut self, a: usize, b: usize, impulse: &Vec3, point: &SolverPoint, inverse_inertias: &[Mat3]) {
+        if self.bodies[a].is_dynamic() {
+            self.bodies[a].apply_impulse_unchecked(&(impulse.clone() * -1.0), &point.offset_a, &inverse_inertias[a]);
+        }
+        if self.bodies[b].is_dynamic() {
+            self.bodies[b].apply_impulse_unchecked(impulse, &point.offset_b, &inverse_inertias[b]);
+        }
+    }
+
+    // moves the centers of mass by the solved velocities and turns the bodies around them
+    fn integrate(&mut self, targets: &[Option<(Vec3, Quat)>], dt: f32) {
+        for (body, target) in self.bodies.iter_mut().zip(targets.iter()) {
+            if body.body_type == BodyType::Static || body.is_sleeping() {
+                continue;
+            }
+            // kinematic targets are met exactly, whatever the integration would round to
+            if let Some((position, rotation)) = target {
+                body.position = position.clone();
+                body.rotation = rotation.clone();
+                continue;
+            }
+
+            let center = body.center_of_mass() + body.linear_velocity.clone() * dt;
+            let w = &body.angular_velocity;
+            let spin = Quat { x: w.x, y: w.y, z: w.z, w: 0.0 }.product(body.rotation.clone());
+            body.rotation = (body.rotation.clone() + spin * (0.5 * dt)).normalized();
+            body.position = center - body.rotation.rotate(body.local_center_of_mass());
+        }
+    }
+
+    // bodies touching or joined to each other form islands, an island sleeps once all of its bodies have
+    // been slow for sleep_delay seconds
+    fn update_sleep(&mut self, contacts: &[Contact], dt: f32) {
+        let mut parents: Vec<usize> = (0..self.bodies.len()).collect();
+        fn root(parents: &mut Vec<usize>, mut i: usize) -> usize {
+            while parents[i] != i {
+                parents[i] = parents[parents[i]];
+                i = parents[i];
+            }
+            i
+        }
+
+        let links = contacts.iter().map(|c| (c.a, c.b))
+            .chain(self.joints.iter().filter(|joint| !joint.is_broken()).map(|joint| (joint.a, joint.b)));
+        for (a, b) in links {
+            if self.bodies[a].is_dynamic() && self.bodies[b].is_dynamic() {
+                let (root_a, root_b) = (root(&mut parents, a), root(&mut parents, b));
+                parents[root_a.max(root_b)] = root_a.min(root_b);
+            }
+        }
+
+        let linear = self.sleep_linear_velocity.powi(2);
+        let angular = self.sleep_angular_velocity.powi(2);
+        for body in self.bodies.iter_mut().filter(|body| body.is_dynamic() && !body.is_sleeping()) {
+            let slow = body.linear_velocity.dot(body.linear_velocity.clone()) < linear
+                && body.angular_velocity.dot(body.angular_velocity.clone()) < angular;
+            body.sleep_time = if slow { body.sleep_time + dt } else { 0.0 };
+        }
+        // riding on something that moves is never resting
+        for c in contacts.iter() {
+            for (rider, carrier) in [(c.a, c.b), (c.b, c.a)].iter() {
+                let carrier = &self.bodies[*carrier];
+                let moving = carrier.linear_velocity.lenght() > 0.0 || carrier.angular_velocity.lenght() > 0.0;
+                if carrier.body_type == BodyType::Kinematic && moving {
+                    self.bodies[*rider].sleep_time = 0.0;
+                }
+            }
+        }
+
+        let mut island_time = vec![std::f32::MAX; self.bodies.len()];
+        for i in 0..self.bodies.len() {
+            if self.bodies[i].is_dynamic() && !self.bodies[i].is_sleeping() {
+                let island = root(&mut parents, i);
+                island_time[island] = island_time[island].min(self.bodies[i].sleep_time);
+            }
+        }
+        for i in 0..self.bodies.len() {
+            if self.bodies[i].is_dynamic() && !self.bodies[i].is_sleeping() {
+                let island = root(&mut parents, i);
+                if island_time[island] >= self.sleep_delay {
+                    self.bodies[i].sleep_gravity = self.gravity.clone() * self.bodies[i].gravity_scale;
+                    self.bodies[i].sleep();
+                }
+            }
+        }
+    }
+}
+
+impl Default for PhysicsWorld {
+    fn default() -> PhysicsWorld {
+        PhysicsWorld::new()
+    }
+}
+
+// two unit vectors at right angles to the normal and each other
+pub fn tangent_basis(normal: &Vec3) -> [Vec3; 2] {
+    let helper = if normal.x.abs() < 0.57 {
+        Vec3 { x: 1.0, y: 0.0, z: 0.0 }
+    } else {
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 }
+    };
+    let first = normal.cross(helper).normalized();
+    let second = normal.cross(first.clone());
+    [first, second]
+}
+
+// angular velocity that turns from one rotation to another in dt seconds
+pub fn angular_velocity_between(from: &Quat, to: &Quat, dt: f32) -> Vec3 {
+    let mut delta = to.product(from.conjugated());
+    if delta.w < 0.0 {
+        delta = delta * -1.0;
+    }
+    let sin = (delta.x * delta.x + delta.y * delta.y + delta.z * delta.z).sqrt();
+    if sin < 1e-6 {
+        return Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+    }
+    let angle = 2.0 * sin.atan2(delta.w);
+    Vec3 { x: delta.x, y: delta.y, z: delta.z } * (angle / (sin * dt))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collision::shape::Shape;
+    use crate::scene::transform::Transform;
+
+    fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn zero() -> Vec3 {
+        vec3(0.0, 0.0, 0.0)
+    }
+
+    fn body_at(body_type: BodyType, shape: Shape, position: Vec3) -> RigidBody {
+        let mut ret = RigidBody::new(body_type, shape, 1.0);
+        ret.set_transform(position, Quat::identity());
+        ret.wake();
+        ret
+    }
+
+    // a box dropped on a floor
+    fn drop_box() -> PhysicsWorld {
+        let mut world = PhysicsWorld::new();
+        world.add_body(body_at(BodyType::Static, Shape::Box { half_extents: vec3(5.0, 0.5, 5.0) }, vec3(0.0, -0.5, 0.0)));
+        let mut falling = body_at(BodyType::Dynamic, Shape::Box { half_extents: vec3(0.5, 0.5, 0.5) }, vec3(0.0, 2.0, 0.0));
+        falling.rotation = Quat::from_axis_angle(&vec3(1.0, 0.0, 1.0), 30.0);
+        world.add_body(falling);
+        world
+    }
+
+    #[test]
+    fn kinematic_targets_are_reached_in_one_step_and_then_held() {
+        let mut world = PhysicsWorld::new();
+        let platform = world.add_body(body_at(BodyType::Kinematic, Shape::Sphere { radius: 0.5 }, zero()));
+        let rotation = Quat::from_axis_angle(&vec3(0.0, 1.0, 0.0), 30.0);
+        world.body_mut(platform).set_kinematic_target(vec3(1.0, 0.0, 0.0), rotation.clone());
+
+        world.step();
+        assert_eq!(world.body(platform).position, vec3(1.0, 0.0, 0.0));
+        assert_eq!(world.body(platform).rotation, rotation);
+        assert!((world.body(platform).linear_velocity.x - 1.0 / world.fixed_step).abs() < 1e-2);
+
+        for _ in 0..3 {
+            world.step();
+        }
+        assert_eq!(world.body(platform).position, vec3(1.0, 0.0, 0.0));
+        // renormalized every step
+        assert!(world.body(platform).rotation.dot(rotation) > 0.99999);
+        assert_eq!(world.body(platform).linear_velocity, zero());
+        assert_eq!(world.body(platform).angular_velocity, zero());
+    }
+
+    #[test]
+    fn kinematic_velocities_without_a_target_are_kept() {
+        let mut world = PhysicsWorld::new();
+        let platform = world.add_body(body_at(BodyType::Kinematic, Shape::Sphere { radius: 0.5 }, zero()));
+        world.body_mut(platform).linear_velocity = vec3(0.0, 0.0, 6.0);
+        for _ in 0..10 {
+            world.step();
+        }
+        assert!((world.body(platform).position.z - 1.0).abs() < 1e-4);
+        assert_eq!(world.body(platform).linear_velocity, vec3(0.0, 0.0, 6.0));
+    }
+
+    #[test]
+    fn updates_run_whole_fixed_steps() {
+        let mut world = drop_box();
+        world.fixed_step = 0.25;
+        assert_eq!(world.update(0.2), 0);
+        assert_eq!(world.update(0.2), 1);
+        assert!((world.alpha() - 0.6).abs() < 1e-5);
+        assert_eq!(world.update(0.1), 1);
+        assert!(world.alpha().abs() < 1e-5);
+
+        // a long frame is cut to max_steps and the rest dropped
+        assert_eq!(world.update(10.0), world.max_steps);
+        assert!(world.alpha() <= 1.0);
+    }
+
+    #[test]
+    fn the_same_frames_give_the_same_bodies() {
+        let run = |frames: &[f32]| {
+            let mut world = drop_box();
+            for delta in frames.iter() {
+                world.update(*delta);
+            }
+            world.bodies().to_vec()
+        };
+        let frames: Vec<f32> = (0..120).map(|i| if i % 3 == 0 { 1.0 / 30.0 } else { 1.0 / 60.0 }).collect();
+        let bodies = run(&frames);
+        assert_eq!(bodies, run(&frames));
+        // it came to rest on the floor
+        assert!(bodies[1].position.y > 0.4 && bodies[1].position.y < 1.0);
+    }
+
+    #[test]
+    fn nodes_get_the_interpolated_transform() {
+        let mut world = PhysicsWorld::new();
+        world.gravity = zero();
+        world.fixed_step = 0.5;
+        let mut moving = body_at(BodyType::Dynamic, Shape::Sphere { radius: 0.5 }, zero());
+        moving.linear_velocity = vec3(2.0, 0.0, 0.0);
+        moving.linear_damping = 0.0;
+        moving.node = Some(1);
+        world.add_body(moving);
+        world.add_body(body_at(BodyType::Dynamic, Shape::Sphere { radius: 0.5 }, vec3(0.0, 5.0, 0.0)));
+
+        let scale = vec3(2.0, 2.0, 2.0);
+        let mut nodes = vec![
+            Node::new("untouched", Transform::default()),
+            Node::new("ball", Transform { scale: scale.clone(), ..Transform::default() })
+        ];
+        world.update(0.75);
+        world.sync_nodes(&mut nodes);
+
+        // half way between x = 0 and x = 1
+        assert!((nodes[1].transform.translation.x - 0.5).abs() < 1e-5);
+        assert_eq!(nodes[1].transform.scale, scale);
+        assert_eq!(nodes[0].transform, Transform::default());
+    }
+
+    #[test]
+    fn small_light_bodies_still_rotate() {
+        let mut world = PhysicsWorld::new();
+        world.gravity = zero();
+        // radius 0.25 at density 1, the inertia tensor's determinant is around 4e-9
+        let spinning = world.add_body(body_at(BodyType::Dynamic, Shape::Sphere { radius: 0.25 }, zero()));
+        assert!(world.body(spinning).world_inverse_inertia()[1][1] > 0.0);
+
+        world.body_mut(spinning).apply_torque(&vec3(0.0, 0.001, 0.0));
+        world.step();
+        assert!(world.body(spinning).angular_velocity.y > 0.0);
+        world.step();
+        assert!(world.body(spinning).rotation.y > 0.0);
+    }
+}
\ No newline at end of file
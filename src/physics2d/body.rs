@@ -0,0 +1,195 @@
+use crate::math::vec2::Vec2;
+use crate::math::mat2::Mat2;
+use super::shape::Shape;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BodyType {
+    // moved by gravity, forces, contacts and joints
+    Dynamic,
+    // moved by its velocity alone, pushes dynamic bodies without being pushed back
+    Kinematic,
+    // never moves
+    Static
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RigidBody {
+    pub body_type: BodyType,
+    pub shape: Shape,
+    // where the shape's origin is, the center of mass can sit elsewhere for polygons and segments
+    pub position: Vec2,
+    // radians counter-clockwise
+    pub angle: f32,
+    // of the center of mass, in units per second
+    pub linear_velocity: Vec2,
+    // radians per second counter-clockwise
+    pub angular_velocity: f32,
+    pub friction: f32,
+    // bounciness, 0 stops dead and 1 bounces back as fast as it came
+    pub restitution: f32,
+    // fraction of velocity lost per second
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    pub gravity_scale: f32,
+    // bits of the collision layers it's on and of those it collides with
+    pub layer: u32,
+    pub mask: u32,
+    mass: f32,
+    inverse_mass: f32,
+    inverse_inertia: f32,
+    // in local space
+    center_of_mass: Vec2,
+    force: Vec2,
+    torque: f32,
+    // transform before the last step, for interpolating between steps
+    previous_position: Vec2,
+    previous_angle: f32
+}
+
+impl RigidBody {
+    // a body at the origin with the mass of its shape filled with density. static and kinematic bodies
+    // have infinite mass whatever the density
+    pub fn new(body_type: BodyType, shape: Shape, density: f32) -> RigidBody {
+        let zero = Vec2 { x: 0.0, y: 0.0 };
+        let mut ret = RigidBody {
+            body_type,
+            shape,
+            position: zero.clone(),
+            angle: 0.0,
+            linear_velocity: zero.clone(),
+            angular_velocity: 0.0,
+            friction: 0.5,
+            restitution: 0.0,
+            linear_damping: 0.01,
+            angular_damping: 0.05,
+            gravity_scale: 1.0,
+            layer: 1,
+            mask: std::u32::MAX,
+            mass: 0.0,
+            inverse_mass: 0.0,
+            inverse_inertia: 0.0,
+            center_of_mass: zero.clone(),
+            force: zero.clone(),
+            torque: 0.0,
+            previous_position: zero,
+            previous_angle: 0.0
+        };
+        let properties = ret.shape.mass_properties(density);
+        ret.center_of_mass = properties.center.clone();
+        ret.set_mass(properties.mass, properties.inertia);
+        ret
+    }
+
+    // teleports the body, without interpolating from where it was
+    pub fn set_transform(&mut self, position: Vec2, angle: f32) {
+        self.previous_position = position.clone();
+        self.previous_angle = angle;
+        self.position = position;
+        self.angle = angle;
+    }
+
+    // mass and the inertia around the center of mass, ignored unless the body is dynamic. an inertia of 0
+    // keeps the body from turning
+    pub fn set_mass(&mut self, mass: f32, inertia: f32) {
+        if self.body_type != BodyType::Dynamic || mass <= 0.0 {
+            self.mass = 0.0;
+            self.inverse_mass = 0.0;
+            self.inverse_inertia = 0.0;
+            return;
+        }
+        self.mass = mass;
+        self.inverse_mass = 1.0 / mass;
+        self.inverse_inertia = if inertia > 0.0 { 1.0 / inertia } else { 0.0 };
+    }
+
+    pub fn mass(&self) -> f32 {
+        self.mass
+    }
+
+    pub fn inverse_mass(&self) -> f32 {
+        self.inverse_mass
+    }
+
+    pub fn inverse_inertia(&self) -> f32 {
+        self.inverse_inertia
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.body_type == BodyType::Dynamic
+    }
+
+    // world space center of mass
+    pub fn center_of_mass(&self) -> Vec2 {
+        self.world_point(&self.center_of_mass)
+    }
+
+    pub fn local_center_of_mass(&self) -> &Vec2 {
+        &self.center_of_mass
+    }
+
+    // a point in the body's space moved into the world
+    pub fn world_point(&self, local: &Vec2) -> Vec2 {
+        Mat2::rotation(self.angle) * local + self.position.clone()
+    }
+
+    // a world space point in the body's space
+    pub fn local_point(&self, world: &Vec2) -> Vec2 {
+        Mat2::rotation(-self.angle) * &(world.clone() - self.position.clone())
+    }
+
+    // velocity of the body at a world space point
+    pub fn velocity_at(&self, point: &Vec2) -> Vec2 {
+        self.linear_velocity.clone() + (point.clone() - self.center_of_mass()).perpendicular() * self.angular_velocity
+    }
+
+    // forces act over the next step, then they're cleared
+    pub fn apply_force(&mut self, force: &Vec2) {
+        self.force += force.clone();
+    }
+
+    pub fn apply_force_at(&mut self, force: &Vec2, point: &Vec2) {
+        self.torque += (point.clone() - self.center_of_mass()).cross(force.clone());
+        self.apply_force(force);
+    }
+
+    pub fn apply_torque(&mut self, torque: f32) {
+        self.torque += torque;
+    }
+
+    // instant change of momentum at a world space point
+    pub fn apply_impulse(&mut self, impulse: &Vec2, point: &Vec2) {
+        if !self.is_dynamic() {
+            return;
+        }
+        let offset = point.clone() - self.center_of_mass();
+        self.apply_impulse_unchecked(impulse, &offset);
+    }
+
+    pub fn apply_angular_impulse(&mut self, impulse: f32) {
+        if self.is_dynamic() {
+            self.angular_velocity += impulse * self.inverse_inertia;
+        }
+    }
+
+    // offset from the center of mass to where the impulse acts, bodies with infinite mass stay unchanged
+    pub(crate) fn apply_impulse_unchecked(&mut self, impulse: &Vec2, offset: &Vec2) {
+        self.linear_velocity += impulse.clone() * self.inverse_mass;
+        self.angular_velocity += offset.cross(impulse.clone()) * self.inverse_inertia;
+    }
+
+    pub(crate) fn take_force(&mut self) -> (Vec2, f32) {
+        let force = std::mem::replace(&mut self.force, Vec2 { x: 0.0, y: 0.0 });
+        (force, std::mem::replace(&mut self.torque, 0.0))
+    }
+
+    pub(crate) fn store_previous(&mut self) {
+        self.previous_position = self.position.clone();
+        self.previous_angle = self.angle;
+    }
+
+    // position and angle between the ones before the last step at 0 and the current ones at 1
+    pub fn interpolated_transform(&self, alpha: f32) -> (Vec2, f32) {
+        (self.previous_position.clone() * (1.0 - alpha) + self.position.clone() * alpha,
+         self.previous_angle * (1.0 - alpha) + self.angle * alpha)
+    }
+}
\ No newline at end of file
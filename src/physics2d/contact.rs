@@ -0,0 +1,270 @@
+use crate::math::vec2::Vec2;
+use super::shape::{Shape, Convex};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContactPoint {
+    // halfway between the two surfaces
+    pub position: Vec2,
+    // overlap along the normal, negative while the shapes are still apart
+    pub depth: f32
+}
+
+// where two shapes touch, all points share one normal
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifold {
+    // from the first shape towards the second
+    pub normal: Vec2,
+    pub points: Vec<ContactPoint>
+}
+
+impl Manifold {
+    fn flipped(mut self) -> Manifold {
+        self.normal = self.normal * -1.0;
+        self
+    }
+}
+
+// contacts between two shapes placed in the world, the normal points from a towards b. shapes less than
+// margin apart already get contacts, with a negative depth, so the solver can stop them before they overlap
+pub fn collide(a: &Shape, position_a: &Vec2, angle_a: f32, b: &Shape, position_b: &Vec2, angle_b: f32, margin: f32) -> Option<Manifold> {
+    collide_convex(&a.convex(position_a, angle_a), &b.convex(position_b, angle_b), margin)
+}
+
+pub fn collide_convex(a: &Convex, b: &Convex, margin: f32) -> Option<Manifold> {
+    match (a.vertices.len(), b.vertices.len()) {
+        (1, 1) => circles(a, b, margin),
+        (_, 1) => polygon_circle(a, b, margin),
+        (1, _) => polygon_circle(b, a, margin).map(Manifold::flipped),
+        _ => polygons(a, b, margin)
+    }
+}
+
+// the contact of surface points on a and b along a normal from a to b
+fn contact(on_a: Vec2, on_b: Vec2, normal: &Vec2) -> ContactPoint {
+    ContactPoint {
+        depth: (on_a.clone() - on_b.clone()).dot(normal.clone()),
+        position: (on_a + on_b) * 0.5
+    }
+}
+
+fn circles(a: &Convex, b: &Convex, margin: f32) -> Option<Manifold> {
+    let (center_a, center_b) = (&a.vertices[0], &b.vertices[0]);
+    let delta = center_b.clone() - center_a.clone();
+    let distance = delta.lenght();
+    if distance > a.radius + b.radius + margin {
+        return None;
+    }
+    let normal = if distance > 1e-6 { delta / distance } else { Vec2 { x: 0.0, y: 1.0 } };
+    Some(Manifold {
+        points: vec![contact(center_a.clone() + normal.clone() * a.radius, center_b.clone() - normal.clone() * b.radius, &normal)],
+        normal
+    })
+}
+
+// the normal points from the polygon to the circle. the circle's center is either in front of the face it's
+// farthest out of, or past one of its ends where the corner is closest
+fn polygon_circle(polygon: &Convex, circle: &Convex, margin: f32) -> Option<Manifold> {
+    let center = &circle.vertices[0];
+    let radius = polygon.radius + circle.radius;
+    let count = polygon.vertices.len();
+
+    let (face, separation) = (0..count)
+        .map(|i| (i, polygon.normals[i].dot(center.clone() - polygon.vertices[i].clone())))
+        .fold((0, std::f32::MIN), |best, (i, s)| if s > best.1 { (i, s) } else { best });
+    if separation > radius + margin {
+        return None;
+    }
+
+    let (v1, v2) = (&polygon.vertices[face], &polygon.vertices[(face + 1) % count]);
+    let (normal, separation) = if separation < 1e-6 {
+        (polygon.normals[face].clone(), separation)
+    } else if (center.clone() - v1.clone()).dot(v2.clone() - v1.clone()) <= 0.0 {
+        let delta = center.clone() - v1.clone();
+        (delta.normalized(), delta.lenght())
+    } else if (center.clone() - v2.clone()).dot(v1.clone() - v2.clone()) <= 0.0 {
+        let delta = center.clone() - v2.clone();
+        (delta.normalized(), delta.lenght())
+    } else {
+        (polygon.normals[face].clone(), separation)
+    };
+    if separation > radius + margin {
+        return None;
+    }
+
+    let on_polygon = center.clone() - normal.clone() * (separation - polygon.radius);
+    let on_circle = center.clone() - normal.clone() * circle.radius;
+    Some(Manifold {
+        points: vec![contact(on_polygon, on_circle, &normal)],
+        normal
+    })
+}
+
+/*********************************
+*** POLYGONS
+*********************************/
+
+// separating axis test over the face normals of both. the face the other polygon is farthest out of is the
+// reference, the other polygon's face most opposed to it is clipped against its sides
+fn polygons(a: &Convex, b: &Convex, margin: f32) -> Option<Manifold> {
+    let radius = a.radius + b.radius;
+    let (face_a, separation_a) = max_separation(a, b);
+    if separation_a > radius + margin {
+        return None;
+    }
+    let (face_b, separation_b) = max_separation(b, a);
+    if separation_b > radius + margin {
+        return None;
+    }
+
+    // a's faces win ties so the choice doesn't flip between steps
+    if separation_b > separation_a + 1e-3 {
+        clip(b, face_b, a, margin).map(Manifold::flipped)
+    } else {
+        clip(a, face_a, b, margin)
+    }
+}
+
+// the face of reference the other polygon is farthest out of, and how far
+fn max_separation(reference: &Convex, other: &Convex) -> (usize, f32) {
+    let mut best = (0, std::f32::MIN);
+    for (i, normal) in reference.normals.iter().enumerate() {
+        let separation = other.vertices.iter()
+            .map(|v| normal.dot(v.clone() - reference.vertices[i].clone()))
+            .fold(std::f32::MAX, f32::min);
+        if separation > best.1 {
+            best = (i, separation);
+        }
+    }
+    best
+}
+
+fn clip(reference: &Convex, face: usize, incident: &Convex, margin: f32) -> Option<Manifold> {
+    let count = reference.vertices.len();
+    let normal = reference.normals[face].clone();
+    let (v1, v2) = (&reference.vertices[face], &reference.vertices[(face + 1) % count]);
+
+    let incident_face = (0..incident.normals.len())
+        .min_by(|i, j| {
+            incident.normals[*i].dot(normal.clone()).partial_cmp(&incident.normals[*j].dot(normal.clone())).unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .expect("failed to find an incident face");
+    let mut points = vec![
+        incident.vertices[incident_face].clone(),
+        incident.vertices[(incident_face + 1) % incident.vertices.len()].clone()
+    ];
+
+    // keep the part of the incident face between the planes through the reference face's ends
+    let tangent = (v2.clone() - v1.clone()).normalized();
+    for (side, offset) in [(tangent.clone() * -1.0, -tangent.dot(v1.clone())), (tangent.clone(), tangent.dot(v2.clone()))].iter() {
+        let (d0, d1) = (side.dot(points[0].clone()) - offset, side.dot(points[1].clone()) - offset);
+        if d0 > 0.0 && d1 > 0.0 {
+            return None;
+        }
+        if d0 > 0.0 {
+            points[0] = points[0].clone() + (points[1].clone() - points[0].clone()) * (d0 / (d0 - d1));
+        } else if d1 > 0.0 {
+            points[1] = points[1].clone() + (points[0].clone() - points[1].clone()) * (d1 / (d1 - d0));
+        }
+    }
+
+    let points: Vec<ContactPoint> = points.into_iter()
+        .map(|p| {
+            let separation = normal.dot(p.clone() - v1.clone());
+            let on_reference = p.clone() - normal.clone() * (separation - reference.radius);
+            contact(on_reference, p - normal.clone() * incident.radius, &normal)
+        })
+        .filter(|point| point.depth > -margin)
+        .collect();
+    if points.is_empty() {
+        return None;
+    }
+    Some(Manifold { normal, points })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    fn vec2(x: f32, y: f32) -> Vec2 {
+        Vec2 { x, y }
+    }
+
+    fn square() -> Shape {
+        Shape::Box { half_extents: vec2(1.0, 1.0) }
+    }
+
+    fn assert_near(a: &Vec2, b: &Vec2) {
+        assert!((a.clone() - b.clone()).lenght() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn overlapping_boxes_touch_along_the_shallowest_axis() {
+        let manifold = collide(&square(), &vec2(0.0, 0.0), 0.0, &square(), &vec2(1.5, 0.2), 0.0, 0.0).expect("failed to collide");
+        assert_near(&manifold.normal, &vec2(1.0, 0.0));
+        assert_eq!(manifold.points.len(), 2);
+        for point in manifold.points.iter() {
+            assert!((point.depth - 0.5).abs() < 1e-4);
+            assert!((point.position.x - 0.75).abs() < 1e-4);
+        }
+        // the incident face is clipped to the reference face's ends
+        let mut ys: Vec<f32> = manifold.points.iter().map(|point| point.position.y).collect();
+        ys.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert!((ys[0] + 0.8).abs() < 1e-4 && (ys[1] - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn the_normal_points_from_the_first_shape_to_the_second() {
+        let manifold = collide(&square(), &vec2(0.0, 1.5), 0.0, &square(), &vec2(0.0, 0.0), 0.0, 0.0).expect("failed to collide");
+        assert_near(&manifold.normal, &vec2(0.0, -1.0));
+
+        let circle = Shape::Circle { radius: 0.5 };
+        let manifold = collide(&circle, &vec2(-1.25, 0.0), 0.0, &square(), &vec2(0.0, 0.0), 0.0, 0.0).expect("failed to collide");
+        assert_near(&manifold.normal, &vec2(1.0, 0.0));
+        assert!((manifold.points[0].depth - 0.25).abs() < 1e-4);
+    }
+
+    #[test]
+    fn a_corner_on_a_face_touches_at_one_point() {
+        let manifold = collide(&square(), &vec2(0.0, 0.0), 0.0, &square(), &vec2(0.0, 2.3), PI / 4.0, 0.0).expect("failed to collide");
+        assert_near(&manifold.normal, &vec2(0.0, 1.0));
+        assert_eq!(manifold.points.len(), 1);
+        let depth = 1.0 + 2.0f32.sqrt() - 2.3;
+        assert!((manifold.points[0].depth - depth).abs() < 1e-4);
+        assert!(manifold.points[0].position.x.abs() < 1e-4);
+    }
+
+    #[test]
+    fn circles_touch_along_the_line_between_their_centers() {
+        let circle = Shape::Circle { radius: 1.0 };
+        let manifold = collide(&circle, &vec2(0.0, 0.0), 0.0, &circle, &vec2(1.2, 1.6), 0.0, 0.0).expect("failed to collide");
+        assert_near(&manifold.normal, &vec2(0.6, 0.8));
+        assert!((manifold.points[0].depth - 0.0).abs() < 1e-4);
+        assert_near(&manifold.points[0].position, &vec2(0.6, 0.8));
+    }
+
+    #[test]
+    fn shapes_within_the_margin_get_contacts_with_a_negative_depth() {
+        let apart = vec2(2.1, 0.0);
+        assert_eq!(collide(&square(), &vec2(0.0, 0.0), 0.0, &square(), &apart, 0.0, 0.05), None);
+
+        let manifold = collide(&square(), &vec2(0.0, 0.0), 0.0, &square(), &apart, 0.0, 0.2).expect("failed to collide");
+        for point in manifold.points.iter() {
+            assert!((point.depth + 0.1).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn segments_hold_up_circles_and_boxes() {
+        let ground = Shape::Segment { a: vec2(-5.0, 0.0), b: vec2(5.0, 0.0) };
+        let manifold = collide(&ground, &vec2(0.0, 0.0), 0.0, &Shape::Circle { radius: 0.5 }, &vec2(1.0, 0.4), 0.0, 0.0)
+            .expect("failed to collide");
+        assert_near(&manifold.normal, &vec2(0.0, 1.0));
+        assert!((manifold.points[0].depth - 0.1).abs() < 1e-4);
+
+        let manifold = collide(&ground, &vec2(0.0, 0.0), 0.0, &square(), &vec2(0.0, 0.9), 0.0, 0.0).expect("failed to collide");
+        assert_near(&manifold.normal, &vec2(0.0, 1.0));
+        assert_eq!(manifold.points.len(), 2);
+    }
+}
\ No newline at end of file
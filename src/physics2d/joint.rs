@@ -0,0 +1,393 @@
+use crate::math::vec2::Vec2;
+use crate::math::mat2::Mat2;
+use super::body::RigidBody;
+
+use std::f32::consts::PI;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JointKind {
+    // keeps the anchors length apart. a frequency above 0 makes it a spring that oscillates that many times
+    // per second, damping_ratio 1 stops it without overshooting
+    Distance { length: f32, frequency: f32, damping_ratio: f32 },
+    // pins the anchors together and lets the bodies turn around them. limits are on the angle of b minus
+    // the angle of a minus reference_angle, the motor turns b relative to a at motor_speed radians per
+    // second with up to max_motor_torque, 0 turns it off
+    Revolute { reference_angle: f32, limits: Option<(f32, f32)>, motor_speed: f32, max_motor_torque: f32 },
+    // lets b slide along an axis in a's space through a's anchor, without turning. limits are on how far
+    // b's anchor is along the axis
+    Prismatic { axis: Vec2, reference_angle: f32, limits: Option<(f32, f32)>, motor_speed: f32, max_motor_force: f32 },
+    // glues the bodies together at the anchors
+    Weld { reference_angle: f32 }
+}
+
+// a constraint between the bodies a and b, solved together with the contacts
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joint {
+    pub a: usize,
+    pub b: usize,
+    // in the space of a and of b
+    pub anchor_a: Vec2,
+    pub anchor_b: Vec2,
+    // whether a and b still collide with each other
+    pub collide_connected: bool,
+    pub kind: JointKind,
+    // accumulated over the step and reused to warm start the next one
+    point_impulse: Vec2,
+    axis_impulse: f32,
+    angular_impulse: f32,
+    motor_impulse: f32,
+    lower_impulse: f32,
+    upper_impulse: f32,
+    prepared: Option<Prepared>
+}
+
+// a velocity constraint along a direction, with the lever arms it has on a and b. the velocity along it is
+// direction . (vb - va) + arm_b wb - arm_a wa
+#[derive(Debug, Clone, PartialEq)]
+struct Axis {
+    direction: Vec2,
+    arm_a: f32,
+    arm_b: f32,
+    mass: f32
+}
+
+// what the solver keeps per joint while iterating
+#[derive(Debug, Clone, PartialEq)]
+struct Prepared {
+    // from the centers of mass to the anchors
+    offset_a: Vec2,
+    offset_b: Vec2,
+    // inverse of the effective mass of the anchors' relative velocity, and the velocity that closes their gap
+    point: Option<(Mat2, Vec2)>,
+    // axis, velocity bias and softness
+    axis: Option<(Axis, f32, f32)>,
+    angular: Option<(Axis, f32)>,
+    // axis and the biases of the lower and the upper limit
+    limit: Option<(Axis, f32, f32)>,
+    // axis, speed and most impulse per step
+    motor: Option<(Axis, f32, f32)>
+}
+
+impl Joint {
+    pub fn new(a: usize, b: usize, anchor_a: Vec2, anchor_b: Vec2, kind: JointKind) -> Joint {
+        Joint {
+            a,
+            b,
+            anchor_a,
+            anchor_b,
+            collide_connected: false,
+            kind,
+            point_impulse: Vec2 { x: 0.0, y: 0.0 },
+            axis_impulse: 0.0,
+            angular_impulse: 0.0,
+            motor_impulse: 0.0,
+            lower_impulse: 0.0,
+            upper_impulse: 0.0,
+            prepared: None
+        }
+    }
+
+    // masses and biases from where the bodies are now, then the impulses of the last step again
+    pub(crate) fn prepare(&mut self, bodies: &mut [RigidBody], dt: f32, baumgarte: f32) {
+        let (body_a, body_b) = (&bodies[self.a], &bodies[self.b]);
+        let offset_a = Mat2::rotation(body_a.angle) * &(self.anchor_a.clone() - body_a.local_center_of_mass().clone());
+        let offset_b = Mat2::rotation(body_b.angle) * &(self.anchor_b.clone() - body_b.local_center_of_mass().clone());
+        let (anchor_a, anchor_b) = (body_a.world_point(&self.anchor_a), body_b.world_point(&self.anchor_b));
+        let separation = anchor_b.clone() - anchor_a.clone();
+        let correction = baumgarte / dt;
+
+        let mut prepared = Prepared {
+            offset_a: offset_a.clone(),
+            offset_b: offset_b.clone(),
+            point: None,
+            axis: None,
+            angular: None,
+            limit: None,
+            motor: None
+        };
+        let point = || {
+            let (ma, mb, ia, ib) = (body_a.inverse_mass(), body_b.inverse_mass(), body_a.inverse_inertia(), body_b.inverse_inertia());
+            let (ra, rb) = (&offset_a, &offset_b);
+            let k = Mat2 {
+                mat: [[ma + mb + ia * ra.y * ra.y + ib * rb.y * rb.y, -ia * ra.x * ra.y - ib * rb.x * rb.y],
+                      [-ia * ra.x * ra.y - ib * rb.x * rb.y, ma + mb + ia * ra.x * ra.x + ib * rb.x * rb.x]]
+            };
+            k.invert().map(|mass| (mass, separation.clone() * correction))
+        };
+        let limit_bias = |error: f32| if error > 0.0 { error / dt } else { error * correction };
+
+        match &self.kind {
+            JointKind::Distance { length, frequency, damping_ratio } => {
+                let distance = separation.lenght();
+                let direction = if distance > 1e-6 { separation.clone() / distance } else { Vec2 { x: 1.0, y: 0.0 } };
+                let mut axis = Axis::new(direction.clone(), offset_a.cross(direction.clone()), offset_b.cross(direction), body_a, body_b);
+                let error = distance - length;
+                if *frequency > 0.0 && axis.mass > 0.0 {
+                    // soft constraint, the spring and damper turned into a bias and a softness
+                    let omega = 2.0 * PI * frequency;
+                    let damping = 2.0 * axis.mass * damping_ratio * omega;
+                    let stiffness = axis.mass * omega * omega;
+                    let gamma = dt * (damping + dt * stiffness);
+                    let gamma = if gamma > 0.0 { 1.0 / gamma } else { 0.0 };
+                    axis.mass = 1.0 / (1.0 / axis.mass + gamma);
+                    prepared.axis = Some((axis, error * dt * stiffness * gamma, gamma));
+                } else {
+                    prepared.axis = Some((axis, error * correction, 0.0));
+                }
+            }
+            JointKind::Revolute { reference_angle, limits, motor_speed, max_motor_torque } => {
+                prepared.point = point();
+                let angle = body_b.angle - body_a.angle - reference_angle;
+                if let Some((lower, upper)) = limits {
+                    prepared.limit = Some((Axis::angular(body_a, body_b), limit_bias(angle - lower), limit_bias(upper - angle)));
+                }
+                if *max_motor_torque > 0.0 {
+                    prepared.motor = Some((Axis::angular(body_a, body_b), *motor_speed, max_motor_torque * dt));
+                }
+            }
+            JointKind::Prismatic { axis, reference_angle, limits, motor_speed, max_motor_force } => {
+                let along = Mat2::rotation(body_a.angle) * &axis.normalized();
+                let across = along.perpendicular();
+                let lever = separation.clone() + offset_a.clone();
+                let perpendicular = Axis::new(across.clone(), lever.cross(across.clone()), offset_b.cross(across.clone()), body_a, body_b);
+                prepared.axis = Some((perpendicular, across.dot(separation.clone()) * correction, 0.0));
+                let angle = body_b.angle - body_a.angle - reference_angle;
+                prepared.angular = Some((Axis::angular(body_a, body_b), angle * correction));
+
+                let sliding = Axis::new(along.clone(), lever.cross(along.clone()), offset_b.cross(along.clone()), body_a, body_b);
+                let translation = along.dot(separation.clone());
+                if let Some((lower, upper)) = limits {
+                    prepared.limit = Some((sliding.clone(), limit_bias(translation - lower), limit_bias(upper - translation)));
+                }
+                if *max_motor_force > 0.0 {
+                    prepared.motor = Some((sliding, *motor_speed, max_motor_force * dt));
+                }
+            }
+            JointKind::Weld { reference_angle } => {
+                prepared.point = point();
+                let angle = body_b.angle - body_a.angle - reference_angle;
+                prepared.angular = Some((Axis::angular(body_a, body_b), angle * correction));
+            }
+        }
+
+        // warm start, impulses for parts the joint lost are dropped
+        if prepared.point.is_none() {
+            self.point_impulse = Vec2 { x: 0.0, y: 0.0 };
+        }
+        if prepared.axis.is_none() {
+            self.axis_impulse = 0.0;
+        }
+        if prepared.angular.is_none() {
+            self.angular_impulse = 0.0;
+        }
+        if prepared.limit.is_none() {
+            self.lower_impulse = 0.0;
+            self.upper_impulse = 0.0;
+        }
+        if prepared.motor.is_none() {
+            self.motor_impulse = 0.0;
+        }
+        apply_point(bodies, self.a, self.b, &prepared, &self.point_impulse);
+        if let Some((axis, _, _)) = &prepared.axis {
+            axis.apply(bodies, self.a, self.b, self.axis_impulse);
+        }
+        if let Some((axis, _)) = &prepared.angular {
+            axis.apply(bodies, self.a, self.b, self.angular_impulse);
+        }
+        if let Some((axis, _, _)) = &prepared.limit {
+            axis.apply(bodies, self.a, self.b, self.lower_impulse - self.upper_impulse);
+        }
+        if let Some((axis, _, _)) = &prepared.motor {
+            axis.apply(bodies, self.a, self.b, self.motor_impulse);
+        }
+        self.prepared = Some(prepared);
+    }
+
+    // one velocity iteration: motor, limits, then the parts that always hold
+    pub(crate) fn solve(&mut self, bodies: &mut [RigidBody]) {
+        let prepared = match self.prepared.as_ref() {
+            Some(prepared) => prepared,
+            None => return
+        };
+        let (a, b) = (self.a, self.b);
+
+        if let Some((axis, speed, max_impulse)) = &prepared.motor {
+            let impulse = -axis.mass * (axis.velocity(&bodies[a], &bodies[b]) - speed);
+            let total = (self.motor_impulse + impulse).max(-max_impulse).min(*max_impulse);
+            axis.apply(bodies, a, b, total - self.motor_impulse);
+            self.motor_impulse = total;
+        }
+
+        if let Some((axis, lower_bias, upper_bias)) = &prepared.limit {
+            // each limit only pushes away from its end, the upper one along the axis turned around
+            solve_limit(axis, *lower_bias, &mut self.lower_impulse, bodies, a, b);
+            solve_limit(&axis.negated(), *upper_bias, &mut self.upper_impulse, bodies, a, b);
+        }
+
+        if let Some((axis, bias, gamma)) = &prepared.axis {
+            let impulse = -axis.mass * (axis.velocity(&bodies[a], &bodies[b]) + bias + gamma * self.axis_impulse);
+            axis.apply(bodies, a, b, impulse);
+            self.axis_impulse += impulse;
+        }
+
+        if let Some((axis, bias)) = &prepared.angular {
+            let impulse = -axis.mass * (axis.velocity(&bodies[a], &bodies[b]) + bias);
+            axis.apply(bodies, a, b, impulse);
+            self.angular_impulse += impulse;
+        }
+
+        if let Some((mass, bias)) = &prepared.point {
+            let (body_a, body_b) = (&bodies[a], &bodies[b]);
+            let velocity = body_b.linear_velocity.clone() + prepared.offset_b.perpendicular() * body_b.angular_velocity
+                - body_a.linear_velocity.clone() - prepared.offset_a.perpendicular() * body_a.angular_velocity;
+            let impulse = mass.clone() * &(velocity + bias.clone()) * -1.0;
+            apply_point(bodies, a, b, prepared, &impulse);
+            self.point_impulse += impulse;
+        }
+    }
+}
+
+fn solve_limit(axis: &Axis, bias: f32, accumulated: &mut f32, bodies: &mut [RigidBody], a: usize, b: usize) {
+    let impulse = -axis.mass * (axis.velocity(&bodies[a], &bodies[b]) + bias);
+    let total = (*accumulated + impulse).max(0.0);
+    axis.apply(bodies, a, b, total - *accumulated);
+    *accumulated = total;
+}
+
+// an impulse on b at its anchor and the opposite one on a at its anchor
+fn apply_point(bodies: &mut [RigidBody], a: usize, b: usize, prepared: &Prepared, impulse: &Vec2) {
+    bodies[a].apply_impulse_unchecked(&(impulse.clone() * -1.0), &prepared.offset_a);
+    bodies[b].apply_impulse_unchecked(impulse, &prepared.offset_b);
+}
+
+impl Axis {
+    fn new(direction: Vec2, arm_a: f32, arm_b: f32, body_a: &RigidBody, body_b: &RigidBody) -> Axis {
+        let k = direction.dot(direction.clone()) * (body_a.inverse_mass() + body_b.inverse_mass())
+            + body_a.inverse_inertia() * arm_a * arm_a + body_b.inverse_inertia() * arm_b * arm_b;
+        Axis {
+            direction,
+            arm_a,
+            arm_b,
+            mass: if k > 0.0 { 1.0 / k } else { 0.0 }
+        }
+    }
+
+    // the relative angular velocity of the bodies
+    fn angular(body_a: &RigidBody, body_b: &RigidBody) -> Axis {
+        Axis::new(Vec2 { x: 0.0, y: 0.0 }, 1.0, 1.0, body_a, body_b)
+    }
+
+    fn negated(&self) -> Axis {
+        Axis {
+            direction: self.direction.clone() * -1.0,
+            arm_a: -self.arm_a,
+            arm_b: -self.arm_b,
+            mass: self.mass
+        }
+    }
+
+    fn velocity(&self, body_a: &RigidBody, body_b: &RigidBody) -> f32 {
+        self.direction.dot(body_b.linear_velocity.clone() - body_a.linear_velocity.clone())
+            + self.arm_b * body_b.angular_velocity - self.arm_a * body_a.angular_velocity
+    }
+
+    fn apply(&self, bodies: &mut [RigidBody], a: usize, b: usize, impulse: f32) {
+        for (body, sign, arm) in [(a, -1.0, self.arm_a), (b, 1.0, self.arm_b)].iter() {
+            let body = &mut bodies[*body];
+            let (inverse_mass, inverse_inertia) = (body.inverse_mass(), body.inverse_inertia());
+            body.linear_velocity += self.direction.clone() * (sign * impulse * inverse_mass);
+            body.angular_velocity += sign * impulse * arm * inverse_inertia;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics2d::body::BodyType;
+    use crate::physics2d::shape::Shape;
+    use crate::physics2d::world::PhysicsWorld;
+
+    fn vec2(x: f32, y: f32) -> Vec2 {
+        Vec2 { x, y }
+    }
+
+    // a bar from the origin to x = 2 on a static body at the origin
+    fn bar(kind: JointKind) -> (PhysicsWorld, usize) {
+        let mut world = PhysicsWorld::new();
+        let a = world.add_body(RigidBody::new(BodyType::Static, Shape::Circle { radius: 0.1 }, 1.0));
+        let mut bar = RigidBody::new(BodyType::Dynamic, Shape::Box { half_extents: vec2(1.0, 0.1) }, 1.0);
+        bar.set_transform(vec2(1.0, 0.0), 0.0);
+        let b = world.add_body(bar);
+        world.add_joint(Joint::new(a, b, vec2(0.0, 0.0), vec2(-1.0, 0.0), kind));
+        (world, b)
+    }
+
+    fn pin_gap(world: &PhysicsWorld, b: usize) -> f32 {
+        world.body(b).world_point(&vec2(-1.0, 0.0)).lenght()
+    }
+
+    #[test]
+    fn revolute_joints_pin_the_anchors_together() {
+        let (mut world, b) = bar(JointKind::Revolute { reference_angle: 0.0, limits: None, motor_speed: 0.0, max_motor_torque: 0.0 });
+        let mut lowest = 0.0f32;
+        for _ in 0..120 {
+            world.step();
+            assert!(pin_gap(&world, b) < 0.02);
+            lowest = lowest.min(world.body(b).angle);
+        }
+        // it swung down past hanging straight
+        assert!(lowest < -PI / 2.0 + 0.05);
+    }
+
+    #[test]
+    fn revolute_limits_stop_the_swing() {
+        let (mut world, b) = bar(JointKind::Revolute { reference_angle: 0.0, limits: Some((-0.5, 0.5)), motor_speed: 0.0, max_motor_torque: 0.0 });
+        for _ in 0..120 {
+            world.step();
+            assert!(world.body(b).angle > -0.55);
+            assert!(pin_gap(&world, b) < 0.02);
+        }
+        assert!((world.body(b).angle + 0.5).abs() < 0.02);
+        assert!(world.body(b).angular_velocity.abs() < 0.1);
+    }
+
+    #[test]
+    fn revolute_motors_turn_at_their_speed() {
+        let (mut world, b) = bar(JointKind::Revolute { reference_angle: 0.0, limits: None, motor_speed: 1.5, max_motor_torque: 1000.0 });
+        world.gravity = vec2(0.0, 0.0);
+        world.body_mut(b).angular_damping = 0.0;
+        for _ in 0..30 {
+            world.step();
+        }
+        assert!((world.body(b).angular_velocity - 1.5).abs() < 1e-2);
+    }
+
+    #[test]
+    fn prismatic_limits_stop_the_slide() {
+        let kind = JointKind::Prismatic { axis: vec2(0.0, 1.0), reference_angle: 0.0, limits: Some((-1.0, 0.0)), motor_speed: 0.0, max_motor_force: 0.0 };
+        let (mut world, b) = bar(kind);
+        for _ in 0..120 {
+            world.step();
+        }
+        let anchor = world.body(b).world_point(&vec2(-1.0, 0.0));
+        assert!((anchor.y + 1.0).abs() < 0.02);
+        assert!(anchor.x.abs() < 0.02);
+        assert!(world.body(b).angle.abs() < 0.02);
+    }
+
+    #[test]
+    fn distance_joints_keep_their_length() {
+        let mut world = PhysicsWorld::new();
+        let a = world.add_body(RigidBody::new(BodyType::Static, Shape::Circle { radius: 0.1 }, 1.0));
+        let mut ball = RigidBody::new(BodyType::Dynamic, Shape::Circle { radius: 0.1 }, 1.0);
+        ball.set_transform(vec2(2.0, 0.0), 0.0);
+        let b = world.add_body(ball);
+        world.add_joint(Joint::new(a, b, vec2(0.0, 0.0), vec2(0.0, 0.0), JointKind::Distance { length: 2.0, frequency: 0.0, damping_ratio: 0.0 }));
+        for _ in 0..120 {
+            world.step();
+            assert!((world.body(b).position.lenght() - 2.0).abs() < 0.05);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,7 @@
+pub mod body;
+pub mod contact;
+pub mod joint;
+pub mod raycast;
+pub mod shape;
+pub mod spatial_hash;
+pub mod world;
\ No newline at end of file
@@ -0,0 +1,161 @@
+use crate::math::vec2::Vec2;
+use crate::math::ray2d::Ray2d;
+use super::shape::{Shape, Convex};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RayHit {
+    // along the ray, from its origin
+    pub distance: f32,
+    pub point: Vec2,
+    // of the surface that was hit, facing the ray
+    pub normal: Vec2
+}
+
+// where a ray first enters a shape placed in the world, within the ray's range. a ray starting inside a
+// circle, box or polygon hits it right away, with the normal against the ray. segments are hit from
+// either side
+pub fn raycast(shape: &Shape, position: &Vec2, angle: f32, ray: &Ray2d) -> Option<RayHit> {
+    let convex = shape.convex(position, angle);
+    let hit = match shape {
+        Shape::Circle { radius } => circle(position, *radius, ray),
+        Shape::Segment { .. } => segment(&convex.vertices[0], &convex.vertices[1], ray),
+        _ => polygon(&convex, ray)
+    };
+    hit.map(|(distance, normal)| RayHit {
+        point: ray.at(distance),
+        distance,
+        normal
+    })
+}
+
+fn circle(center: &Vec2, radius: f32, ray: &Ray2d) -> Option<(f32, Vec2)> {
+    let to_origin = ray.origin.clone() - center.clone();
+    let c = to_origin.dot(to_origin.clone()) - radius * radius;
+    if c <= 0.0 {
+        return Some((0.0, ray.dir.clone() * -1.0));
+    }
+    let b = to_origin.dot(ray.dir.clone());
+    let discriminant = b * b - c;
+    if b > 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    let distance = -b - discriminant.sqrt();
+    if distance > ray.range {
+        return None;
+    }
+    Some((distance, (ray.at(distance) - center.clone()).normalized()))
+}
+
+// cyrus beck: the ray enters through the last edge it crosses going in, before any it crosses going out
+fn polygon(polygon: &Convex, ray: &Ray2d) -> Option<(f32, Vec2)> {
+    let mut enter = std::f32::MIN;
+    let mut exit = ray.range;
+    let mut normal = ray.dir.clone() * -1.0;
+    for (vertex, edge_normal) in polygon.vertices.iter().zip(polygon.normals.iter()) {
+        let start = edge_normal.dot(ray.origin.clone() - vertex.clone());
+        let rate = edge_normal.dot(ray.dir.clone());
+        if rate.abs() < 1e-12 {
+            if start > 0.0 {
+                return None;
+            }
+            continue;
+        }
+        let t = -start / rate;
+        if rate < 0.0 {
+            if t > enter {
+                enter = t;
+                normal = edge_normal.clone();
+            }
+        } else {
+            exit = exit.min(t);
+        }
+        if enter > exit || exit < 0.0 {
+            return None;
+        }
+    }
+    if enter <= 0.0 {
+        return Some((0.0, ray.dir.clone() * -1.0));
+    }
+    Some((enter, normal))
+}
+
+fn segment(a: &Vec2, b: &Vec2, ray: &Ray2d) -> Option<(f32, Vec2)> {
+    let along = b.clone() - a.clone();
+    let denominator = ray.dir.cross(along.clone());
+    if denominator.abs() < 1e-12 {
+        return None;
+    }
+    let to_a = a.clone() - ray.origin.clone();
+    let distance = to_a.cross(along.clone()) / denominator;
+    let fraction = to_a.cross(ray.dir.clone()) / denominator;
+    if distance < 0.0 || distance > ray.range || fraction < 0.0 || fraction > 1.0 {
+        return None;
+    }
+    let normal = along.perpendicular().normalized();
+    Some((distance, if normal.dot(ray.dir.clone()) > 0.0 { normal * -1.0 } else { normal }))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vec2(x: f32, y: f32) -> Vec2 {
+        Vec2 { x, y }
+    }
+
+    fn ray(origin: Vec2, dir: Vec2) -> Ray2d {
+        Ray2d::new(origin, dir, 10.0)
+    }
+
+    #[test]
+    fn rays_enter_circles_on_the_near_side() {
+        let circle = Shape::Circle { radius: 1.0 };
+        let hit = raycast(&circle, &vec2(5.0, 0.0), 0.0, &ray(vec2(0.0, 0.0), vec2(1.0, 0.0))).expect("failed to hit");
+        assert!((hit.distance - 4.0).abs() < 1e-4);
+        assert_eq!(hit.normal, vec2(-1.0, 0.0));
+        assert_eq!(raycast(&circle, &vec2(5.0, 2.0), 0.0, &ray(vec2(0.0, 0.0), vec2(1.0, 0.0))), None);
+        // behind the origin
+        assert_eq!(raycast(&circle, &vec2(-5.0, 0.0), 0.0, &ray(vec2(0.0, 0.0), vec2(1.0, 0.0))), None);
+    }
+
+    #[test]
+    fn rays_hit_boxes_on_the_face_they_cross() {
+        let square = Shape::Box { half_extents: vec2(1.0, 1.0) };
+        let hit = raycast(&square, &vec2(0.0, 5.0), 0.0, &ray(vec2(0.5, 0.0), vec2(0.0, 1.0))).expect("failed to hit");
+        assert!((hit.distance - 4.0).abs() < 1e-4);
+        assert!((hit.normal.clone() - vec2(0.0, -1.0)).lenght() < 1e-4);
+        assert!((hit.point.clone() - vec2(0.5, 4.0)).lenght() < 1e-4);
+
+        // turned 45 degrees, the corner is nearest
+        let hit = raycast(&square, &vec2(5.0, 0.0), std::f32::consts::PI / 4.0, &ray(vec2(0.0, 0.0), vec2(1.0, 0.0))).expect("failed to hit");
+        assert!((hit.distance - (5.0 - 2.0f32.sqrt())).abs() < 1e-4);
+    }
+
+    #[test]
+    fn rays_starting_inside_hit_right_away() {
+        let square = Shape::Box { half_extents: vec2(1.0, 1.0) };
+        let hit = raycast(&square, &vec2(0.0, 0.0), 0.0, &ray(vec2(0.0, 0.0), vec2(1.0, 0.0))).expect("failed to hit");
+        assert_eq!(hit.distance, 0.0);
+        assert_eq!(hit.normal, vec2(-1.0, 0.0));
+    }
+
+    #[test]
+    fn rays_stop_at_their_range() {
+        let circle = Shape::Circle { radius: 1.0 };
+        let short = Ray2d::new(vec2(0.0, 0.0), vec2(1.0, 0.0), 3.0);
+        assert_eq!(raycast(&circle, &vec2(5.0, 0.0), 0.0, &short), None);
+    }
+
+    #[test]
+    fn segments_are_hit_from_either_side() {
+        let wall = Shape::Segment { a: vec2(0.0, -1.0), b: vec2(0.0, 1.0) };
+        let hit = raycast(&wall, &vec2(3.0, 0.0), 0.0, &ray(vec2(0.0, 0.0), vec2(1.0, 0.0))).expect("failed to hit");
+        assert!((hit.distance - 3.0).abs() < 1e-4);
+        assert!((hit.normal.clone() - vec2(-1.0, 0.0)).lenght() < 1e-4);
+
+        let hit = raycast(&wall, &vec2(3.0, 0.0), 0.0, &ray(vec2(6.0, 0.5), vec2(-1.0, 0.0))).expect("failed to hit");
+        assert!((hit.distance - 3.0).abs() < 1e-4);
+        assert!((hit.normal.clone() - vec2(1.0, 0.0)).lenght() < 1e-4);
+    }
+}
\ No newline at end of file
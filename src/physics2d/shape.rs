@@ -0,0 +1,208 @@
+use crate::math::vec2::Vec2;
+use crate::math::mat2::Mat2;
+use crate::math::aabb2d::Aabb2d;
+
+use std::f32::consts::PI;
+
+// in the body's space, around the body's position
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Circle { radius: f32 },
+    Box { half_extents: Vec2 },
+    Polygon(Polygon),
+    // a line without thickness, for ground and walls. a dynamic one weighs density per unit of length
+    Segment { a: Vec2, b: Vec2 }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MassProperties {
+    pub mass: f32,
+    // in the shape's space
+    pub center: Vec2,
+    // around the center of mass
+    pub inertia: f32
+}
+
+impl Shape {
+    pub fn mass_properties(&self, density: f32) -> MassProperties {
+        let zero = Vec2 { x: 0.0, y: 0.0 };
+        match self {
+            Shape::Circle { radius } => {
+                let mass = density * PI * radius * radius;
+                MassProperties {
+                    mass,
+                    center: zero,
+                    inertia: mass * radius * radius / 2.0
+                }
+            }
+            Shape::Box { half_extents } => {
+                let mass = density * 4.0 * half_extents.x * half_extents.y;
+                MassProperties {
+                    mass,
+                    center: zero,
+                    inertia: mass * (half_extents.x * half_extents.x + half_extents.y * half_extents.y) / 3.0
+                }
+            }
+            Shape::Polygon(polygon) => polygon.mass_properties(density),
+            Shape::Segment { a, b } => {
+                let length = a.distance_to(b.clone());
+                let mass = density * length;
+                MassProperties {
+                    mass,
+                    center: (a.clone() + b.clone()) / 2.0,
+                    inertia: mass * length * length / 12.0
+                }
+            }
+        }
+    }
+
+    pub fn aabb(&self, position: &Vec2, angle: f32) -> Aabb2d {
+        let convex = self.convex(position, angle);
+        Aabb2d::from_points(&convex.vertices).expanded(convex.radius)
+    }
+
+    // the shape placed in the world, rotated by angle radians counter-clockwise, then moved to position
+    pub fn convex(&self, position: &Vec2, angle: f32) -> Convex {
+        let rotation = Mat2::rotation(angle);
+        let place = |v: &Vec2| rotation.clone() * v + position.clone();
+        match self {
+            Shape::Circle { radius } => Convex {
+                vertices: vec![position.clone()],
+                normals: vec![],
+                radius: *radius
+            },
+            Shape::Box { half_extents } => Polygon::rectangle(half_extents).placed(position, angle),
+            Shape::Polygon(polygon) => polygon.placed(position, angle),
+            Shape::Segment { a, b } => {
+                let (a, b) = (place(a), place(b));
+                let normal = (b.clone() - a.clone()).perpendicular().normalized() * -1.0;
+                Convex {
+                    vertices: vec![a, b],
+                    normals: vec![normal.clone(), normal * -1.0],
+                    radius: 0.0
+                }
+            }
+        }
+    }
+}
+
+// what collision works with: a convex polygon in world space with a radius around it. circles are one
+// vertex, segments two with a normal for either side
+#[derive(Debug, Clone, PartialEq)]
+pub struct Convex {
+    // counter-clockwise
+    pub vertices: Vec<Vec2>,
+    // outwards, normals[i] belongs to the edge from vertices[i] to the next one
+    pub normals: Vec<Vec2>,
+    pub radius: f32
+}
+
+impl Convex {
+    pub fn centroid(&self) -> Vec2 {
+        let mut sum = Vec2 { x: 0.0, y: 0.0 };
+        for v in self.vertices.iter() {
+            sum += v.clone();
+        }
+        sum / self.vertices.len().max(1) as f32
+    }
+}
+
+// a convex polygon, counter-clockwise
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    pub vertices: Vec<Vec2>,
+    pub normals: Vec<Vec2>
+}
+
+impl Polygon {
+    // the convex hull of the points, by andrew's monotone chain. fails when they don't span an area
+    pub fn new(points: &[Vec2]) -> Result<Polygon, String> {
+        let mut sorted = points.to_vec();
+        sorted.sort_by(|a, b| {
+            a.x.partial_cmp(&b.x).unwrap_or(std::cmp::Ordering::Equal)
+                .then(a.y.partial_cmp(&b.y).unwrap_or(std::cmp::Ordering::Equal))
+        });
+        sorted.dedup();
+
+        // lower then upper chain, points that don't turn left are dropped
+        let mut hull: Vec<Vec2> = vec![];
+        for pass in 0..2 {
+            let start = hull.len();
+            let order: Vec<&Vec2> = if pass == 0 { sorted.iter().collect() } else { sorted.iter().rev().collect() };
+            for point in order.into_iter() {
+                while hull.len() >= start + 2 {
+                    let (a, b) = (&hull[hull.len() - 2], &hull[hull.len() - 1]);
+                    if (b.clone() - a.clone()).cross(point.clone() - a.clone()) > 1e-6 {
+                        break;
+                    }
+                    hull.pop();
+                }
+                hull.push(point.clone());
+            }
+            hull.pop();
+        }
+
+        if hull.len() < 3 {
+            return Err("the points of a polygon don't span an area".to_string());
+        }
+        Ok(Polygon::from_hull(hull))
+    }
+
+    // rectangle centered on the origin
+    pub fn rectangle(half_extents: &Vec2) -> Polygon {
+        let (x, y) = (half_extents.x, half_extents.y);
+        Polygon::from_hull(vec![Vec2 { x: -x, y: -y }, Vec2 { x, y: -y }, Vec2 { x, y }, Vec2 { x: -x, y }])
+    }
+
+    fn from_hull(vertices: Vec<Vec2>) -> Polygon {
+        let normals = (0..vertices.len())
+            .map(|i| {
+                let edge = vertices[(i + 1) % vertices.len()].clone() - vertices[i].clone();
+                edge.perpendicular().normalized() * -1.0
+            })
+            .collect();
+        Polygon {
+            vertices,
+            normals
+        }
+    }
+
+    // area, centroid and inertia from the triangles fanning out from the first vertex
+    pub fn mass_properties(&self, density: f32) -> MassProperties {
+        let origin = self.vertices[0].clone();
+        let mut area = 0.0;
+        let mut center = Vec2 { x: 0.0, y: 0.0 };
+        let mut inertia = 0.0;
+        for i in 1..self.vertices.len() - 1 {
+            let e1 = self.vertices[i].clone() - origin.clone();
+            let e2 = self.vertices[i + 1].clone() - origin.clone();
+            let d = e1.cross(e2.clone());
+            let triangle_area = d / 2.0;
+            area += triangle_area;
+            center += (e1.clone() + e2.clone()) * (triangle_area / 3.0);
+            let int_x = e1.x * e1.x + e2.x * e1.x + e2.x * e2.x;
+            let int_y = e1.y * e1.y + e2.y * e1.y + e2.y * e2.y;
+            inertia += (d / 12.0) * (int_x + int_y);
+        }
+
+        let mass = density * area;
+        let local_center = center / area;
+        // inertia was around the fan's origin, moved to the centroid
+        let inertia = density * inertia - mass * local_center.dot(local_center.clone());
+        MassProperties {
+            mass,
+            center: local_center + origin,
+            inertia
+        }
+    }
+
+    // rotated by angle radians, then moved to position
+    pub fn placed(&self, position: &Vec2, angle: f32) -> Convex {
+        let rotation = Mat2::rotation(angle);
+        Convex {
+            vertices: self.vertices.iter().map(|v| rotation.clone() * v + position.clone()).collect(),
+            normals: self.normals.iter().map(|n| rotation.clone() * n).collect(),
+            radius: 0.0
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,234 @@
+use crate::math::vec2::Vec2;
+use crate::math::aabb2d::Aabb2d;
+use crate::math::ray2d::Ray2d;
+
+use std::collections::HashMap;
+
+// boxes spanning more cells than this are kept aside and tested against everything
+const MAX_CELLS: i64 = 256;
+
+// uniform grid of square cells, only the cells something is in are stored. it's rebuilt whenever things
+// move, which is cheap as long as the cells are about the size of the things in them. results come out
+// sorted, never in the map's order
+#[derive(Debug, Clone)]
+pub struct SpatialHash {
+    pub cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    // data and box of everything inserted, cells hold indices into it
+    items: Vec<(usize, Aabb2d)>,
+    large: Vec<usize>
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> SpatialHash {
+        SpatialHash {
+            cell_size,
+            cells: HashMap::new(),
+            items: vec![],
+            large: vec![]
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.items.clear();
+        self.large.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn insert(&mut self, aabb: &Aabb2d, data: usize) {
+        let item = self.items.len();
+        let (min, max) = (self.cell(&aabb.min), self.cell(&aabb.max));
+        if (max.0 - min.0 + 1) as i64 * (max.1 - min.1 + 1) as i64 > MAX_CELLS {
+            self.large.push(item);
+        } else {
+            for x in min.0..=max.0 {
+                for y in min.1..=max.1 {
+                    self.cells.entry((x, y)).or_insert_with(Vec::new).push(item);
+                }
+            }
+        }
+        self.items.push((data, aabb.clone()));
+    }
+
+    // data of everything whose box overlaps aabb, sorted
+    pub fn query(&self, aabb: &Aabb2d) -> Vec<usize> {
+        let mut found = self.large.clone();
+        let (min, max) = (self.cell(&aabb.min), self.cell(&aabb.max));
+        if (max.0 - min.0 + 1) as i64 * (max.1 - min.1 + 1) as i64 > MAX_CELLS {
+            found = (0..self.items.len()).collect();
+        } else {
+            for x in min.0..=max.0 {
+                for y in min.1..=max.1 {
+                    if let Some(cell) = self.cells.get(&(x, y)) {
+                        found.extend(cell.iter());
+                    }
+                }
+            }
+        }
+
+        let mut ret: Vec<usize> = found.into_iter()
+            .filter(|item| self.items[*item].1.overlaps(aabb))
+            .map(|item| self.items[item].0)
+            .collect();
+        ret.sort();
+        ret.dedup();
+        ret
+    }
+
+    // every pair with overlapping boxes, as (smaller data, larger data), sorted
+    pub fn pairs(&self) -> Vec<(usize, usize)> {
+        let mut ret = vec![];
+        let mut push = |i: usize, j: usize| {
+            let ((a, aabb_a), (b, aabb_b)) = (&self.items[i], &self.items[j]);
+            if a != b && aabb_a.overlaps(aabb_b) {
+                ret.push((*a.min(b), *a.max(b)));
+            }
+        };
+        for cell in self.cells.values() {
+            for (k, i) in cell.iter().enumerate() {
+                for j in cell[k + 1..].iter() {
+                    push(*i, *j);
+                }
+            }
+        }
+        for (k, i) in self.large.iter().enumerate() {
+            for j in 0..self.items.len() {
+                if !self.large[..=k].contains(&j) {
+                    push(*i, j);
+                }
+            }
+        }
+        ret.sort();
+        ret.dedup();
+        ret
+    }
+
+    // (data, distance the ray enters the box at) of everything the ray passes through, nearest first. the
+    // cells are walked along the ray
+    pub fn raycast(&self, ray: &Ray2d) -> Vec<(usize, f32)> {
+        let mut found = self.large.clone();
+        let mut cell = self.cell(&ray.origin);
+        let end = self.cell(&ray.end());
+        let step = (ray.dir.x.signum() as i32, ray.dir.y.signum() as i32);
+
+        // distance along the ray to the next cell border on either axis, and between borders
+        let border = |axis: usize, cell: i32| {
+            if ray.dir[axis].abs() < 1e-12 {
+                return std::f32::MAX;
+            }
+            let next = (if ray.dir[axis] > 0.0 { cell + 1 } else { cell }) as f32 * self.cell_size;
+            (next - ray.origin[axis]) / ray.dir[axis]
+        };
+        let delta = |axis: usize| if ray.dir[axis].abs() < 1e-12 { std::f32::MAX } else { self.cell_size / ray.dir[axis].abs() };
+        let mut next = (border(0, cell.0), border(1, cell.1));
+
+        loop {
+            if let Some(items) = self.cells.get(&cell) {
+                found.extend(items.iter());
+            }
+            if cell == end || next.0.min(next.1) > ray.range {
+                break;
+            }
+            if next.0 < next.1 {
+                cell.0 += step.0;
+                next.0 += delta(0);
+            } else {
+                cell.1 += step.1;
+                next.1 += delta(1);
+            }
+        }
+
+        found.sort();
+        found.dedup();
+        let mut ret: Vec<(usize, f32)> = found.into_iter()
+            .filter_map(|item| self.items[item].1.ray_intersection(ray).map(|distance| (self.items[item].0, distance)))
+            .collect();
+        ret.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal).then(a.0.cmp(&b.0)));
+        ret
+    }
+
+    fn cell(&self, point: &Vec2) -> (i32, i32) {
+        ((point.x / self.cell_size).floor() as i32, (point.y / self.cell_size).floor() as i32)
+    }
+}
+
+impl Default for SpatialHash {
+    fn default() -> SpatialHash {
+        SpatialHash::new(2.0)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aabb(min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> Aabb2d {
+        Aabb2d::new(Vec2 { x: min_x, y: min_y }, Vec2 { x: max_x, y: max_y })
+    }
+
+    // three boxes in a row, a huge one under all of them and one far away
+    fn filled() -> SpatialHash {
+        let mut hash = SpatialHash::new(1.0);
+        hash.insert(&aabb(0.0, 0.0, 0.9, 0.9), 10);
+        hash.insert(&aabb(0.5, 0.5, 1.5, 1.5), 11);
+        hash.insert(&aabb(3.0, 0.0, 3.5, 0.5), 12);
+        hash.insert(&aabb(-100.0, -100.0, 100.0, -50.0), 13);
+        hash.insert(&aabb(-20.0, 20.0, -19.0, 21.0), 14);
+        hash
+    }
+
+    #[test]
+    fn queries_find_the_overlapping_boxes_sorted() {
+        let hash = filled();
+        assert_eq!(hash.len(), 5);
+        assert_eq!(hash.query(&aabb(0.8, 0.8, 1.0, 1.0)), vec![10, 11]);
+        assert_eq!(hash.query(&aabb(3.2, 0.2, 3.3, 0.3)), vec![12]);
+        assert_eq!(hash.query(&aabb(2.0, 2.0, 2.5, 2.5)), Vec::<usize>::new());
+        // boxes over many cells are kept aside and still found
+        assert_eq!(hash.query(&aabb(70.0, -60.0, 71.0, -59.0)), vec![13]);
+        // so are small ones with a query spanning many cells
+        assert_eq!(hash.query(&aabb(-50.0, 0.0, 50.0, 50.0)), vec![10, 11, 12, 14]);
+    }
+
+    #[test]
+    fn pairs_come_out_once_and_sorted() {
+        let mut hash = filled();
+        hash.insert(&aabb(0.2, 0.2, 0.3, 0.3), 15);
+        hash.insert(&aabb(3.4, -51.0, 3.6, 0.1), 16);
+        assert_eq!(hash.pairs(), vec![(10, 11), (10, 15), (12, 16), (13, 16)]);
+    }
+
+    #[test]
+    fn raycasts_walk_the_cells_nearest_first() {
+        let hash = filled();
+        let ray = Ray2d::new(Vec2 { x: -1.0, y: 0.25 }, Vec2 { x: 1.0, y: 0.0 }, 10.0);
+        let hits = hash.raycast(&ray);
+        assert_eq!(hits.iter().map(|(data, _)| *data).collect::<Vec<usize>>(), vec![10, 12]);
+        assert!((hits[0].1 - 1.0).abs() < 1e-5);
+        assert!((hits[1].1 - 4.0).abs() < 1e-5);
+
+        let short = Ray2d::new(Vec2 { x: -1.0, y: 0.25 }, Vec2 { x: 1.0, y: 0.0 }, 2.0);
+        assert_eq!(hash.raycast(&short).len(), 1);
+        // straight down through the huge box
+        let down = Ray2d::new(Vec2 { x: 10.0, y: 0.0 }, Vec2 { x: 0.0, y: -1.0 }, 60.0);
+        assert_eq!(hash.raycast(&down), vec![(13, 50.0)]);
+    }
+
+    #[test]
+    fn clearing_empties_it() {
+        let mut hash = filled();
+        hash.clear();
+        assert!(hash.is_empty());
+        assert_eq!(hash.query(&aabb(-100.0, -100.0, 100.0, 100.0)), Vec::<usize>::new());
+        assert_eq!(hash.pairs(), vec![]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,525 @@
+use crate::math::vec2::Vec2;
+use crate::math::aabb2d::Aabb2d;
+use crate::math::ray2d::Ray2d;
+use super::body::{RigidBody, BodyType};
+use super::contact::{self, Manifold};
+use super::joint::Joint;
+use super::shape::Shape;
+use super::raycast::{self, RayHit};
+use super::spatial_hash::SpatialHash;
+
+// whether two things on these layers see each other, both have to be in the other's mask
+pub fn layers_collide(layer_a: u32, mask_a: u32, layer_b: u32, mask_b: u32) -> bool {
+    layer_a & mask_b != 0 && layer_b & mask_a != 0
+}
+
+// two bodies touching after a step, with the impulses the solver pushed them apart with
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contact {
+    pub a: usize,
+    pub b: usize,
+    // normal from a to b
+    pub manifold: Manifold,
+    // per point, accumulated over the step and reused to warm start the next one
+    pub normal_impulses: Vec<f32>,
+    tangent_impulses: Vec<f32>,
+    // points in a's local space, to recognize them next step
+    anchors: Vec<Vec2>
+}
+
+// what the solver keeps per contact point while iterating
+struct SolverPoint {
+    // from the centers of mass to the point
+    offset_a: Vec2,
+    offset_b: Vec2,
+    normal_mass: f32,
+    tangent_mass: f32,
+    // separating velocity the point should reach, from restitution and overlap
+    bias: f32
+}
+
+struct SolverContact {
+    tangent: Vec2,
+    friction: f32,
+    points: Vec<SolverPoint>
+}
+
+// 2d rigid body simulation on a fixed step, contacts and joints are solved together with sequential
+// impulses. the same bodies and inputs always give the same results
+pub struct PhysicsWorld {
+    pub gravity: Vec2,
+    // seconds per step
+    pub fixed_step: f32,
+    // most steps an update takes, time beyond that is dropped so one slow frame doesn't cause more
+    pub max_steps: u32,
+    pub velocity_iterations: u32,
+    // bodies this close get contacts before they touch
+    pub contact_margin: f32,
+    // overlap that's left alone, so resting contacts don't jitter
+    pub penetration_slop: f32,
+    // fraction of the overlap and of joint errors pushed out per step
+    pub baumgarte: f32,
+    // approach speeds below this don't bounce
+    pub restitution_threshold: f32,
+    bodies: Vec<RigidBody>,
+    joints: Vec<Joint>,
+    contacts: Vec<Contact>,
+    // broad phase, rebuilt every step
+    hash: SpatialHash,
+    // simulated time owed to the frame
+    accumulator: f32
+}
+
+impl PhysicsWorld {
+    pub fn new() -> PhysicsWorld {
+        PhysicsWorld {
+            gravity: Vec2 { x: 0.0, y: -9.81 },
+            fixed_step: 1.0 / 60.0,
+            max_steps: 8,
+            velocity_iterations: 10,
+            contact_margin: 0.02,
+            penetration_slop: 0.005,
+            baumgarte: 0.2,
+            restitution_threshold: 1.0,
+            bodies: vec![],
+            joints: vec![],
+            contacts: vec![],
+            hash: SpatialHash::default(),
+            accumulator: 0.0
+        }
+    }
+
+    // returns the index of the body
+    pub fn add_body(&mut self, body: RigidBody) -> usize {
+        let index = self.bodies.len();
+        self.hash.insert(&body.shape.aabb(&body.position, body.angle).expanded(self.contact_margin), index);
+        self.bodies.push(body);
+        index
+    }
+
+    pub fn body(&self, index: usize) -> &RigidBody {
+        &self.bodies[index]
+    }
+
+    pub fn body_mut(&mut self, index: usize) -> &mut RigidBody {
+        &mut self.bodies[index]
+    }
+
+    pub fn bodies(&self) -> &[RigidBody] {
+        &self.bodies
+    }
+
+    // returns the index of the joint
+    pub fn add_joint(&mut self, joint: Joint) -> usize {
+        self.joints.push(joint);
+        self.joints.len() - 1
+    }
+
+    pub fn joint(&self, index: usize) -> &Joint {
+        &self.joints[index]
+    }
+
+    pub fn joint_mut(&mut self, index: usize) -> &mut Joint {
+        &mut self.joints[index]
+    }
+
+    pub fn joints(&self) -> &[Joint] {
+        &self.joints
+    }
+
+    // the broad phase's cells should be about the size of the bodies
+    pub fn set_cell_size(&mut self, cell_size: f32) {
+        self.hash.cell_size = cell_size;
+    }
+
+    // contacts of the last step
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    // runs as many fixed steps as fit into the time passed, returns how many
+    pub fn update(&mut self, delta: f32) -> u32 {
+        self.accumulator += delta;
+        let mut steps = 0;
+        while self.accumulator >= self.fixed_step && steps < self.max_steps {
+            self.step();
+            self.accumulator -= self.fixed_step;
+            steps += 1;
+        }
+        if steps == self.max_steps {
+            self.accumulator = self.accumulator.min(self.fixed_step);
+        }
+        steps
+    }
+
+    // how far the time passed is between the last step and the next one, for interpolating transforms
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.fixed_step).max(0.0).min(1.0)
+    }
+
+    pub fn step(&mut self) {
+        let dt = self.fixed_step;
+        self.apply_forces(dt);
+        let pairs = self.broad_phase();
+        let contacts = self.narrow_phase(&pairs);
+        let contacts = self.solve(contacts, dt);
+        self.integrate(dt);
+        self.contacts = contacts;
+        self.rebuild_hash();
+    }
+
+    // queries find bodies moved by hand since the last step where they were
+
+    // the nearest body on a layer in mask the ray hits
+    pub fn raycast(&self, ray: &Ray2d, mask: u32) -> Option<(usize, RayHit)> {
+        let mut best: Option<(usize, RayHit)> = None;
+        for (index, entry) in self.hash.raycast(ray).into_iter() {
+            // boxes come nearest first, none after this one can be closer
+            if best.as_ref().map(|(_, hit)| entry > hit.distance).unwrap_or(false) {
+                break;
+            }
+            let body = &self.bodies[index];
+            if body.layer & mask == 0 {
+                continue;
+            }
+            if let Some(hit) = raycast::raycast(&body.shape, &body.position, body.angle, ray) {
+                if best.as_ref().map(|(_, best)| hit.distance < best.distance).unwrap_or(true) {
+                    best = Some((index, hit));
+                }
+            }
+        }
+        best
+    }
+
+    // bodies on a layer in mask whose bounds overlap aabb, sorted
+    pub fn query_aabb(&self, aabb: &Aabb2d, mask: u32) -> Vec<usize> {
+        self.hash.query(aabb).into_iter()
+            .filter(|index| self.bodies[*index].layer & mask != 0)
+            .collect()
+    }
+
+    // bodies on a layer in mask with a point inside their shape, sorted
+    pub fn query_point(&self, point: &Vec2, mask: u32) -> Vec<usize> {
+        let probe = Shape::Circle { radius: 0.0 };
+        self.query_aabb(&Aabb2d::new(point.clone(), point.clone()), mask).into_iter()
+            .filter(|index| {
+                let body = &self.bodies[*index];
+                contact::collide(&probe, point, 0.0, &body.shape, &body.position, body.angle, 0.0)
+                    .map(|manifold| manifold.points.iter().any(|point| point.depth >= 0.0))
+                    .unwrap_or(false)
+            })
+            .collect()
+    }
+
+    // gravity, forces and damping go into the velocities before contacts are solved, that's what makes the
+    // integration semi-implicit
+    fn apply_forces(&mut self, dt: f32) {
+        let gravity = self.gravity.clone();
+        for body in self.bodies.iter_mut() {
+            body.store_previous();
+            let (force, torque) = body.take_force();
+            if body.is_dynamic() {
+                let acceleration = gravity.clone() * body.gravity_scale + force * body.inverse_mass();
+                body.linear_velocity += acceleration * dt;
+                body.angular_velocity += torque * body.inverse_inertia() * dt;
+                body.linear_velocity *= 1.0 / (1.0 + dt * body.linear_damping);
+                body.angular_velocity *= 1.0 / (1.0 + dt * body.angular_damping);
+            }
+        }
+    }
+
+    fn rebuild_hash(&mut self) {
+        self.hash.clear();
+        for (i, body) in self.bodies.iter().enumerate() {
+            self.hash.insert(&body.shape.aabb(&body.position, body.angle).expanded(self.contact_margin), i);
+        }
+    }
+
+    // pairs of bodies whose bounds overlap in the hash, sorted so the solver order never changes
+    fn broad_phase(&mut self) -> Vec<(usize, usize)> {
+        self.rebuild_hash();
+
+        // bodies held by a joint leave each other alone unless the joint says otherwise
+        let jointed = |a: usize, b: usize| self.joints.iter().any(|j| !j.collide_connected && (j.a.min(j.b), j.a.max(j.b)) == (a, b));
+        self.hash.pairs().into_iter()
+            .filter(|(i, j)| {
+                let (a, b) = (&self.bodies[*i], &self.bodies[*j]);
+                (a.is_dynamic() || b.is_dynamic()) && layers_collide(a.layer, a.mask, b.layer, b.mask) && !jointed(*i, *j)
+            })
+            .collect()
+    }
+
+    fn narrow_phase(&self, pairs: &[(usize, usize)]) -> Vec<Contact> {
+        let mut ret = vec![];
+        for (a, b) in pairs.iter().cloned() {
+            let (body_a, body_b) = (&self.bodies[a], &self.bodies[b]);
+            let manifold = match contact::collide(&body_a.shape, &body_a.position, body_a.angle,
+                                                  &body_b.shape, &body_b.position, body_b.angle, self.contact_margin) {
+                Some(manifold) => manifold,
+                None => continue
+            };
+
+            let anchors: Vec<Vec2> = manifold.points.iter().map(|point| body_a.local_point(&point.position)).collect();
+
+            // warm start from the points of the last step that are still in the same place
+            let previous = self.contacts.iter()
+                .find(|c| c.a == a && c.b == b && c.manifold.normal.dot(manifold.normal.clone()) > 0.95);
+            let mut normal_impulses = vec![0.0; anchors.len()];
+            let mut tangent_impulses = vec![0.0; anchors.len()];
+            if let Some(previous) = previous {
+                for (i, anchor) in anchors.iter().enumerate() {
+                    let matching = previous.anchors.iter().position(|old| old.distance_to(anchor.clone()) < 0.05);
+                    if let Some(j) = matching {
+                        normal_impulses[i] = previous.normal_impulses[j];
+                        tangent_impulses[i] = previous.tangent_impulses[j];
+                    }
+                }
+            }
+
+            ret.push(Contact {
+                a,
+                b,
+                manifold,
+                normal_impulses,
+                tangent_impulses,
+                anchors
+            });
+        }
+        ret
+    }
+
+    fn solve(&mut self, mut contacts: Vec<Contact>, dt: f32) -> Vec<Contact> {
+        let centers: Vec<Vec2> = self.bodies.iter().map(|body| body.center_of_mass()).collect();
+
+        // masses along the normal and tangent, and the velocity each point has to reach
+        let mut solver_contacts = vec![];
+        for c in contacts.iter() {
+            let (body_a, body_b) = (&self.bodies[c.a], &self.bodies[c.b]);
+            let normal = &c.manifold.normal;
+            let tangent = normal.perpendicular() * -1.0;
+            let effective_mass = |offset_a: &Vec2, offset_b: &Vec2, axis: &Vec2| {
+                let (arm_a, arm_b) = (offset_a.cross(axis.clone()), offset_b.cross(axis.clone()));
+                let k = body_a.inverse_mass() + body_b.inverse_mass()
+                    + body_a.inverse_inertia() * arm_a * arm_a + body_b.inverse_inertia() * arm_b * arm_b;
+                if k > 0.0 { 1.0 / k } else { 0.0 }
+            };
+
+            let restitution = body_a.restitution.max(body_b.restitution);
+            // bounces come from the speed the bodies arrived with, without this step's gravity, or a ball
+            // caught on the contact margin keeps gaining enough to bounce forever
+            let gravity = |body: &RigidBody| if body.is_dynamic() { body.gravity_scale } else { 0.0 };
+            let gravity_approach = self.gravity.dot(normal.clone()) * (gravity(body_b) - gravity(body_a)) * dt;
+            let points = c.manifold.points.iter()
+                .map(|point| {
+                    let offset_a = point.position.clone() - centers[c.a].clone();
+                    let offset_b = point.position.clone() - centers[c.b].clone();
+                    let approach = (body_b.velocity_at(&point.position) - body_a.velocity_at(&point.position)).dot(normal.clone());
+
+                    // still apart: the gap may close within this step but no further. overlapping: push out
+                    let mut bias = if point.depth < 0.0 {
+                        point.depth / dt
+                    } else {
+                        self.baumgarte / dt * (point.depth - self.penetration_slop).max(0.0)
+                    };
+                    let arrival = approach - gravity_approach;
+                    if arrival < -self.restitution_threshold {
+                        bias = bias.max(-restitution * arrival);
+                    }
+
+                    SolverPoint {
+                        normal_mass: effective_mass(&offset_a, &offset_b, normal),
+                        tangent_mass: effective_mass(&offset_a, &offset_b, &tangent),
+                        offset_a,
+                        offset_b,
+                        bias
+                    }
+                })
+                .collect();
+
+            solver_contacts.push(SolverContact {
+                tangent,
+                friction: (body_a.friction * body_b.friction).sqrt(),
+                points
+            });
+        }
+
+        // warm start
+        for (c, solver) in contacts.iter().zip(solver_contacts.iter()) {
+            for (i, point) in solver.points.iter().enumerate() {
+                let impulse = c.manifold.normal.clone() * c.normal_impulses[i] + solver.tangent.clone() * c.tangent_impulses[i];
+                self.apply_pair_impulse(c.a, c.b, &impulse, point);
+            }
+        }
+        for joint in self.joints.iter_mut() {
+            joint.prepare(&mut self.bodies, dt, self.baumgarte);
+        }
+
+        for _ in 0..self.velocity_iterations {
+            for joint in self.joints.iter_mut() {
+                joint.solve(&mut self.bodies);
+            }
+
+            for (c, solver) in contacts.iter_mut().zip(solver_contacts.iter()) {
+                let normal = c.manifold.normal.clone();
+                for (i, point) in solver.points.iter().enumerate() {
+                    // friction first, limited by the normal impulse so far
+                    let speed = self.relative_velocity(c.a, c.b, point).dot(solver.tangent.clone());
+                    let limit = solver.friction * c.normal_impulses[i];
+                    let old = c.tangent_impulses[i];
+                    let new = (old - speed * point.tangent_mass).max(-limit).min(limit);
+                    c.tangent_impulses[i] = new;
+                    self.apply_pair_impulse(c.a, c.b, &(solver.tangent.clone() * (new - old)), point);
+
+                    // contacts push, never pull
+                    let speed = self.relative_velocity(c.a, c.b, point).dot(normal.clone());
+                    let old = c.normal_impulses[i];
+                    let new = (old - (speed - point.bias) * point.normal_mass).max(0.0);
+                    c.normal_impulses[i] = new;
+                    self.apply_pair_impulse(c.a, c.b, &(normal.clone() * (new - old)), point);
+                }
+            }
+        }
+        contacts
+    }
+
+    // velocity of b relative to a at a contact point
+    fn relative_velocity(&self, a: usize, b: usize, point: &SolverPoint) -> Vec2 {
+        let (body_a, body_b) = (&self.bodies[a], &self.bodies[b]);
+        body_b.linear_velocity.clone() + point.offset_b.perpendicular() * body_b.angular_velocity
+            - body_a.linear_velocity.clone() - point.offset_a.perpendicular() * body_a.angular_velocity
+    }
+
+    // pushes b along the impulse and a the other way
+    fn apply_pair_impulse(&mut self, a: usize, b: usize, impulse: &Vec2, point: &SolverPoint) {
+        self.bodies[a].apply_impulse_unchecked(&(impulse.clone() * -1.0), &point.offset_a);
+        self.bodies[b].apply_impulse_unchecked(impulse, &point.offset_b);
+    }
+
+    // moves the centers of mass by the solved velocities and turns the bodies around them
+    fn integrate(&mut self, dt: f32) {
+        for body in self.bodies.iter_mut().filter(|body| body.body_type != BodyType::Static) {
+            let center = body.center_of_mass() + body.linear_velocity.clone() * dt;
+            body.angle += body.angular_velocity * dt;
+            let offset = body.world_point(body.local_center_of_mass()) - body.position.clone();
+            body.position = center - offset;
+        }
+    }
+}
+
+impl Default for PhysicsWorld {
+    fn default() -> PhysicsWorld {
+        PhysicsWorld::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics2d::joint::JointKind;
+    use crate::physics2d::shape::Polygon;
+
+    fn vec2(x: f32, y: f32) -> Vec2 {
+        Vec2 { x, y }
+    }
+
+    fn body_at(body_type: BodyType, shape: Shape, position: Vec2, angle: f32) -> RigidBody {
+        let mut ret = RigidBody::new(body_type, shape, 1.0);
+        ret.set_transform(position, angle);
+        ret
+    }
+
+    // a floor, a stack of boxes, a tumbling polygon and a pendulum knocking into them
+    fn scene() -> PhysicsWorld {
+        let mut world = PhysicsWorld::new();
+        world.add_body(body_at(BodyType::Static, Shape::Segment { a: vec2(-10.0, 0.0), b: vec2(10.0, 0.0) }, vec2(0.0, 0.0), 0.0));
+        for i in 0..4 {
+            world.add_body(body_at(BodyType::Dynamic, Shape::Box { half_extents: vec2(0.5, 0.5) }, vec2(0.05 * i as f32, 0.5 + i as f32), 0.0));
+        }
+        let triangle = Polygon::new(&[vec2(-0.5, 0.0), vec2(0.5, 0.0), vec2(0.0, 0.8)]).expect("failed to make a triangle");
+        world.add_body(body_at(BodyType::Dynamic, Shape::Polygon(triangle), vec2(2.0, 3.0), 0.7));
+        let pivot = world.add_body(body_at(BodyType::Static, Shape::Circle { radius: 0.1 }, vec2(-3.0, 5.0), 0.0));
+        let bob = world.add_body(body_at(BodyType::Dynamic, Shape::Circle { radius: 0.4 }, vec2(-6.0, 5.0), 0.0));
+        world.add_joint(Joint::new(pivot, bob, vec2(0.0, 0.0), vec2(0.0, 0.0), JointKind::Distance { length: 3.0, frequency: 0.0, damping_ratio: 0.0 }));
+        world
+    }
+
+    fn bits(world: &PhysicsWorld) -> Vec<u32> {
+        world.bodies().iter()
+            .flat_map(|body| vec![body.position.x, body.position.y, body.angle, body.linear_velocity.x, body.linear_velocity.y, body.angular_velocity])
+            .map(f32::to_bits)
+            .collect()
+    }
+
+    #[test]
+    fn identical_runs_end_bit_for_bit_the_same() {
+        let (mut first, mut second) = (scene(), scene());
+        for _ in 0..300 {
+            first.step();
+            second.step();
+        }
+        assert_eq!(bits(&first), bits(&second));
+        // and something happened
+        assert!(bits(&first) != bits(&scene()));
+    }
+
+    #[test]
+    fn boxes_come_to_rest_stacked_on_the_floor() {
+        let mut world = PhysicsWorld::new();
+        world.add_body(body_at(BodyType::Static, Shape::Segment { a: vec2(-10.0, 0.0), b: vec2(10.0, 0.0) }, vec2(0.0, 0.0), 0.0));
+        for i in 0..4 {
+            world.add_body(body_at(BodyType::Dynamic, Shape::Box { half_extents: vec2(0.5, 0.5) }, vec2(0.0, 0.5 + i as f32), 0.0));
+        }
+        for _ in 0..240 {
+            world.step();
+        }
+        for i in 0..4 {
+            let body = world.body(1 + i);
+            assert!((body.position.y - (0.5 + i as f32)).abs() < 0.05, "box {} at {:?}", i, body.position);
+            assert!(body.linear_velocity.lenght() < 0.05);
+        }
+    }
+
+    #[test]
+    fn raycasts_find_the_nearest_body_on_the_mask() {
+        let mut world = PhysicsWorld::new();
+        let near = world.add_body(body_at(BodyType::Static, Shape::Circle { radius: 0.5 }, vec2(3.0, 0.0), 0.0));
+        let far = world.add_body(body_at(BodyType::Static, Shape::Box { half_extents: vec2(0.5, 2.0) }, vec2(6.0, 0.0), 0.0));
+        world.body_mut(near).layer = 2;
+        world.step();
+
+        let ray = Ray2d::new(vec2(0.0, 0.0), vec2(1.0, 0.0), 20.0);
+        let (index, hit) = world.raycast(&ray, std::u32::MAX).expect("failed to hit");
+        assert_eq!(index, near);
+        assert!((hit.distance - 2.5).abs() < 1e-4);
+        let (index, hit) = world.raycast(&ray, 1).expect("failed to hit");
+        assert_eq!(index, far);
+        assert!((hit.distance - 5.5).abs() < 1e-4);
+        assert!(world.raycast(&Ray2d::new(vec2(0.0, 0.0), vec2(-1.0, 0.0), 20.0), std::u32::MAX).is_none());
+    }
+
+    #[test]
+    fn point_queries_test_the_shapes_not_just_their_bounds() {
+        let mut world = PhysicsWorld::new();
+        let circle = world.add_body(body_at(BodyType::Static, Shape::Circle { radius: 1.0 }, vec2(0.0, 0.0), 0.0));
+        assert_eq!(world.query_point(&vec2(0.5, 0.5), std::u32::MAX), vec![circle]);
+        // inside the box around the circle, outside the circle
+        assert_eq!(world.query_point(&vec2(0.9, 0.9), std::u32::MAX), Vec::<usize>::new());
+        assert_eq!(world.query_aabb(&Aabb2d::new(vec2(0.8, 0.8), vec2(0.9, 0.9)), std::u32::MAX), vec![circle]);
+        assert_eq!(world.query_aabb(&Aabb2d::new(vec2(0.8, 0.8), vec2(0.9, 0.9)), 2), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn updates_run_whole_fixed_steps() {
+        let mut world = scene();
+        world.fixed_step = 0.25;
+        world.max_steps = 2;
+        assert_eq!(world.update(0.2), 0);
+        assert_eq!(world.update(0.2), 1);
+        assert!((world.alpha() - 0.6).abs() < 1e-5);
+        // a long frame is cut to max_steps, and what's owed after it to one step
+        assert_eq!(world.update(5.0), 2);
+        assert_eq!(world.update(0.0), 1);
+        assert_eq!(world.update(0.0), 0);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,73 @@
+use crate::math::vec3::Vec3;
+use crate::math::mat4::Mat4;
+use crate::math::deg2rad;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Camera {
+    pub position: Vec3,
+    pub forward: Vec3,
+    pub up: Vec3,
+    // vertical field of view in degrees
+    pub fov_y: f32,
+    pub aspect: f32,
+    pub near: f32,
+    pub far: f32
+}
+
+impl Camera {
+    pub fn new(position: Vec3, forward: Vec3, up: Vec3, fov_y: f32, aspect: f32, near: f32, far: f32) -> Camera {
+        Camera {
+            position,
+            forward: forward.normalized(),
+            up: up.normalized(),
+            fov_y,
+            aspect,
+            near,
+            far
+        }
+    }
+
+    pub fn view_matrix(&self) -> Mat4 {
+        Mat4::look_at(&self.position, &(self.position.clone() + self.forward.clone()), &self.up)
+    }
+
+    pub fn projection_matrix(&self) -> Mat4 {
+        Mat4::identity().perspective_fov_zo(self.fov_y, self.aspect, self.near, self.far)
+    }
+
+    pub fn view_projection_matrix(&self) -> Mat4 {
+        self.projection_matrix() * &self.view_matrix()
+    }
+
+    // world space corners of the frustum between two view distances,
+    // near plane first, in the order bottom left, bottom right, top right, top left
+    pub fn frustum_slice_corners(&self, near: f32, far: f32) -> [Vec3; 8] {
+        let forward = self.forward.normalized();
+        let right = forward.cross(self.up.clone()).normalized();
+        let up = right.cross(forward.clone());
+        let tan_half_fov = (deg2rad(self.fov_y) / 2.0).tan();
+
+        let corner = |distance: f32, sx: f32, sy: f32| {
+            let half_height = distance * tan_half_fov;
+            let half_width = half_height * self.aspect;
+            self.position.clone() + forward.clone() * distance
+                + right.clone() * (sx * half_width) + up.clone() * (sy * half_height)
+        };
+
+        [
+            corner(near, -1.0, -1.0), corner(near, 1.0, -1.0), corner(near, 1.0, 1.0), corner(near, -1.0, 1.0),
+            corner(far, -1.0, -1.0), corner(far, 1.0, -1.0), corner(far, 1.0, 1.0), corner(far, -1.0, 1.0)
+        ]
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Camera {
+        Camera::new(
+            Vec3 { x: 0.0, y: 0.0, z: -1.0 },
+            Vec3 { x: 0.0, y: 0.0, z: 1.0 },
+            Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+            60.0, 4.0 / 3.0, 0.1, 100.0
+        )
+    }
+}
\ No newline at end of file
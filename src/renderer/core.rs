@@ -1,10 +1,31 @@
 use super::mesh::Mesh;
+use super::light::{Light, LightData, NO_SHADOW, pack_lights};
+use super::camera::Camera;
+use super::shadow::{self, ShadowSettings, ShadowFlags, ShadowView, ShadowData};
+use super::shadow_map::{self, ShadowAtlas};
+use super::material::{Material, PbrMaterial};
+use super::texture::Texture;
+use super::ibl::IblMaps;
+use super::pbr::{self, PbrVertex, GpuEnvironment};
+use super::shader::{ShaderProgram, ShaderTarget, RuntimeShader};
+use super::spirv::ShaderStage;
+use super::render_graph::{RenderGraph, CompiledGraph, PassId, ResourceId, ResourceUsage, ImageDesc, ImageSize};
+use super::post::PostSettings;
+use super::post_process::{PostProcessor, CompositeTarget, HDR_FORMAT, LDR_FORMAT};
+use super::instancing::{InstanceData, InstanceId, InstanceBatches};
+use super::debug_draw::{self, DebugDraw};
+use super::debug_render::DebugRenderer;
+use super::lod::{LodGroup, LodSettings, LodDither};
+use crate::animation::skeleton::{Skeleton, Pose, vertex_influences};
 use crate::math::vec3::Vec3;
+use crate::math::mat4::Mat4;
+use crate::display::display_mode::{WindowMode, MonitorInfo, WindowPlacement, DisplayEvent};
 
 use std::sync::Arc;
 use std::collections::HashSet;
+use std::path::Path;
 
-use winit::{EventsLoop, WindowBuilder, Window, dpi::LogicalSize, Event, WindowEvent};
+use winit::{EventsLoop, WindowBuilder, Window, dpi::LogicalSize, dpi::LogicalPosition, Event, WindowEvent};
 use vulkano_win::VkSurfaceBuild;
 
 use vulkano::instance::{
@@ -29,7 +50,8 @@ use vulkano::swapchain::{
     AcquireError
 };
 use vulkano::format::Format;
-use vulkano::image::{ImageUsage, swapchain::SwapchainImage};
+use vulkano::image::{ImageUsage, ImageLayout, ImmutableImage, AttachmentImage, swapchain::SwapchainImage};
+use vulkano::sampler::Sampler;
 use vulkano::sync::{self, SharingMode, GpuFuture};
 use vulkano::pipeline::{
     GraphicsPipeline,
@@ -38,7 +60,7 @@ use vulkano::pipeline::{
     vertex::BufferlessVertices,
     viewport::Viewport,
 };
-use vulkano::pipeline::vertex::SingleBufferDefinition;
+use vulkano::pipeline::vertex::{SingleBufferDefinition, OneVertexOneInstanceDefinition};
 use vulkano::framebuffer::{
     RenderPassAbstract,
     Subpass,
@@ -51,7 +73,9 @@ use vulkano::command_buffer::{
     AutoCommandBufferBuilder,
     DynamicState,
 };
+use vulkano::format::ClearValue;
 use vulkano::buffer::{BufferUsage, BufferAccess, CpuAccessibleBuffer};
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
 
 mod vs {
     vulkano_shaders::shader!{
@@ -61,33 +85,222 @@ mod vs {
         #extension GL_ARB_separate_shader_objects : enable
 
         layout(location = 0) in vec3 position;
+        layout(location = 1) in vec3 normal;
 
-        layout(location = 0) out vec3 pre_color;
+        layout(location = 0) out vec3 world_position;
+        layout(location = 1) out vec3 world_normal;
+        layout(location = 2) out vec3 tint;
+
+        layout(set = 0, binding = 0) uniform FrameData {
+            mat4 view_proj;
+        } frame;
 
         void main() {
-            gl_Position = vec4(position.x, -position.y, position.z, 1.0);
-            pre_color = vec3(position.x, position.y, 1.0);
+            // vulkan's clip space y points down
+            vec4 clip = frame.view_proj * vec4(position, 1.0);
+            gl_Position = vec4(clip.x, -clip.y, clip.z, clip.w);
+            world_position = position;
+            world_normal = normal;
+            tint = vec3(1.0);
         }"
     }
 }
 
-mod fs {
+// the lit vertex shader for instanced meshes, the second binding holds the model matrix and tint
+// of each instance
+mod instanced_vs {
     vulkano_shaders::shader!{
-        ty: "fragment",
+        ty: "vertex",
         src: "
         #version 450
         #extension GL_ARB_separate_shader_objects : enable
 
-        layout(location = 0) in vec3 pre_color;
+        layout(location = 0) in vec3 position;
+        layout(location = 1) in vec3 normal;
+        layout(location = 2) in vec4 model_x;
+        layout(location = 3) in vec4 model_y;
+        layout(location = 4) in vec4 model_z;
+        layout(location = 5) in vec4 model_w;
+        layout(location = 6) in vec4 color;
 
-        layout(location = 0) out vec4 f_color;
+        layout(location = 0) out vec3 world_position;
+        layout(location = 1) out vec3 world_normal;
+        layout(location = 2) out vec3 tint;
+
+        layout(set = 0, binding = 0) uniform FrameData {
+            mat4 view_proj;
+        } frame;
 
         void main() {
-            f_color = vec4(pre_color, 1.0);
+            mat4 model = mat4(model_x, model_y, model_z, model_w);
+            vec4 world = model * vec4(position, 1.0);
+            vec4 clip = frame.view_proj * world;
+            gl_Position = vec4(clip.x, -clip.y, clip.z, clip.w);
+            world_position = world.xyz;
+            world_normal = transpose(inverse(mat3(model))) * normal;
+            tint = color.rgb;
         }"
     }
 }
 
+// the lit vertex shader for skinned meshes, vertices are moved by the weighted joint matrices of the
+// mesh's current pose
+mod skinned_vs {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        src: "
+        #version 450
+        #extension GL_ARB_separate_shader_objects : enable
+
+        layout(location = 0) in vec3 position;
+        layout(location = 1) in vec3 normal;
+        layout(location = 2) in uvec4 joints;
+        layout(location = 3) in vec4 weights;
+
+        layout(location = 0) out vec3 world_position;
+        layout(location = 1) out vec3 world_normal;
+        layout(location = 2) out vec3 tint;
+
+        layout(set = 0, binding = 0) uniform FrameData {
+            mat4 view_proj;
+        } frame;
+
+        layout(set = 0, binding = 4) readonly buffer JointMatrices {
+            mat4 matrices[];
+        } joint_matrices;
+
+        void main() {
+            mat4 skin = weights.x * joint_matrices.matrices[joints.x]
+                + weights.y * joint_matrices.matrices[joints.y]
+                + weights.z * joint_matrices.matrices[joints.z]
+                + weights.w * joint_matrices.matrices[joints.w];
+            vec4 world = skin * vec4(position, 1.0);
+            vec4 clip = frame.view_proj * world;
+            gl_Position = vec4(clip.x, -clip.y, clip.z, clip.w);
+            world_position = world.xyz;
+            world_normal = mat3(skin) * normal;
+            tint = vec3(1.0);
+        }"
+    }
+}
+
+// compiled when the renderer starts, so it can include the shadow sampling it shares with the pbr shader
+const FS_GLSL: &str = r#"
+        #version 450
+        #extension GL_ARB_separate_shader_objects : enable
+
+        const uint DIRECTIONAL_LIGHT = 0;
+        const uint POINT_LIGHT = 1;
+        const uint SPOT_LIGHT = 2;
+
+        struct Light {
+            vec4 position_kind;
+            vec4 direction;
+            vec4 color_intensity;
+            vec4 attenuation; // constant, linear, quadratic, range
+            vec4 cone; // cos inner, cos outer
+            vec4 shadow; // first shadow, shadow count
+        };
+
+        struct Shadow {
+            mat4 view_proj;
+            vec4 atlas_rect; // uv offset, uv scale
+            vec4 params; // split far, constant bias, slope bias, normal offset
+        };
+
+        layout(location = 0) in vec3 world_position;
+        layout(location = 1) in vec3 world_normal;
+        layout(location = 2) in vec3 tint;
+
+        layout(location = 0) out vec4 f_color;
+
+        layout(set = 0, binding = 0) uniform FrameData {
+            mat4 view_proj;
+            vec4 camera_position;
+            vec4 camera_forward;
+            vec4 ambient;
+            uvec4 light_count;
+            vec4 shadow_params; // pcf radius, atlas texel size
+        } frame;
+
+        layout(set = 0, binding = 1) readonly buffer LightBuffer {
+            Light lights[];
+        } light_buffer;
+
+        layout(set = 0, binding = 2) readonly buffer ShadowBuffer {
+            Shadow shadows[];
+        } shadow_buffer;
+
+        layout(set = 0, binding = 3) uniform sampler2D shadow_atlas;
+
+        layout(push_constant) uniform MaterialConstants {
+            vec4 diffuse; // rgb + opacity
+            vec4 specular; // rgb + shininess
+            vec4 flags; // receives shadows, lod fade, lod fading out
+        } material;
+
+        const float BAYER[16] = float[](
+            0.0, 8.0, 2.0, 10.0,
+            12.0, 4.0, 14.0, 6.0,
+            3.0, 11.0, 1.0, 9.0,
+            15.0, 7.0, 13.0, 5.0
+        );
+
+        #include "shadow.glsl"
+
+        void main() {
+            // screen door cross-fade between lod levels, the level fading out keeps the pixels the other one drops
+            if (material.flags.y > 0.0) {
+                ivec2 cell = ivec2(gl_FragCoord.xy) % 4;
+                float threshold = (BAYER[cell.y * 4 + cell.x] + 0.5) / 16.0;
+                bool fading_out = material.flags.z > 0.0;
+                if (fading_out == (threshold < material.flags.y)) {
+                    discard;
+                }
+            }
+
+            vec3 n = normalize(world_normal);
+            vec3 v = normalize(frame.camera_position.xyz - world_position);
+            vec3 diffuse = material.diffuse.rgb * tint;
+            vec3 color = frame.ambient.rgb * diffuse;
+
+            for (uint i = 0; i < frame.light_count.x; i++) {
+                Light light = light_buffer.lights[i];
+                uint kind = uint(light.position_kind.w);
+
+                vec3 l;
+                float attenuation = 1.0;
+                if (kind == DIRECTIONAL_LIGHT) {
+                    l = normalize(-light.direction.xyz);
+                } else {
+                    vec3 to_light = light.position_kind.xyz - world_position;
+                    float d = length(to_light);
+                    l = to_light / d;
+                    attenuation = 1.0 / (light.attenuation.x + light.attenuation.y * d + light.attenuation.z * d * d);
+                    if (light.attenuation.w > 0.0 && d > light.attenuation.w) {
+                        attenuation = 0.0;
+                    }
+                    if (kind == SPOT_LIGHT) {
+                        float theta = dot(-l, light.direction.xyz);
+                        float epsilon = max(light.cone.x - light.cone.y, 0.0001);
+                        attenuation *= clamp((theta - light.cone.y) / epsilon, 0.0, 1.0);
+                    }
+                }
+
+                if (material.flags.x > 0.0) {
+                    attenuation *= shadow_factor(light, world_position, n, l);
+                }
+
+                vec3 radiance = light.color_intensity.rgb * light.color_intensity.w * attenuation;
+                float n_dot_l = max(dot(n, l), 0.0);
+                vec3 h = normalize(l + v);
+                float specular = n_dot_l > 0.0 ? pow(max(dot(n, h), 0.0), material.specular.w) : 0.0;
+                color += radiance * (diffuse * n_dot_l + material.specular.rgb * specular);
+            }
+
+            f_color = vec4(color, material.diffuse.a);
+        }"#;
+
 fn device_extensions() -> DeviceExtensions {
     DeviceExtensions {
         khr_swapchain: true,
@@ -104,6 +317,8 @@ const VALIDATION_LAYERS: &[&str] =  &[
     "VK_LAYER_LUNARG_standard_validation"
 ];
 
+const DEPTH_FORMAT: Format = Format::D32Sfloat;
+
 struct QueueFamilyIndices {
     graphics_family: i32,
     present_family: i32
@@ -124,24 +339,178 @@ impl QueueFamilyIndices {
 
 #[derive(Copy, Clone)]
 struct Vertex {
-    position: [f32; 3]
+    position: [f32; 3],
+    normal: [f32; 3]
+}
+impl_vertex!(Vertex, position, normal);
+
+struct PbrDrawable {
+    vertex_buffer: Arc<BufferAccess + Send + Sync>,
+    material: PbrMaterial,
+    shadows: ShadowFlags,
+    // base color, metallic-roughness, normal, occlusion, emissive
+    maps: [Arc<ImmutableImage<Format>>; 5]
+}
+
+// a mesh registered for instancing, its instances live in Core::instances
+struct InstancedMesh {
+    vertex_buffer: Arc<BufferAccess + Send + Sync>,
+    shadows: ShadowFlags
+}
+
+#[derive(Default, Copy, Clone)]
+struct SkinnedVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+    joints: [u32; 4],
+    weights: [f32; 4]
+}
+impl_vertex!(SkinnedVertex, position, normal, joints, weights);
+
+// a lit mesh deformed by a skeleton, drawn in whatever pose was set last
+struct SkinnedMesh {
+    vertex_buffer: Arc<BufferAccess + Send + Sync>,
+    material: Material,
+    shadows: ShadowFlags,
+    skeleton: Skeleton,
+    skinning_matrices: Vec<Mat4>
+}
+
+// a lit mesh with several levels of detail, one vertex buffer per level
+struct LodMesh {
+    levels: Vec<Arc<BufferAccess + Send + Sync>>,
+    material: Material,
+    shadows: ShadowFlags,
+    group: LodGroup
+}
+
+// std140 layout of the FrameData uniform block
+#[derive(Copy, Clone)]
+struct FrameData {
+    view_proj: [[f32; 4]; 4],
+    camera_position: [f32; 4],
+    camera_forward: [f32; 4],
+    ambient: [f32; 4],
+    light_count: [u32; 4],
+    shadow_params: [f32; 4]
+}
+
+// std140 layout of the MaterialConstants push constant block
+#[derive(Copy, Clone)]
+struct MaterialConstants {
+    diffuse: [f32; 4],
+    specular: [f32; 4],
+    flags: [f32; 4]
+}
+
+// buffers shared by the passes of a frame
+struct FrameBuffers {
+    frame: Arc<CpuAccessibleBuffer<FrameData>>,
+    lights: Arc<CpuAccessibleBuffer<[LightData]>>,
+    shadows: Arc<CpuAccessibleBuffer<[ShadowData]>>,
+    // instance data of the non empty batches, with the index of their batch
+    instances: Vec<(usize, Arc<BufferAccess + Send + Sync>)>,
+    // joint matrices of every skinned mesh
+    joints: Vec<Arc<CpuAccessibleBuffer<[[[f32; 4]; 4]]>>>
+}
+
+// passes of the frame graph, recorded in the order the compiled graph runs them
+struct FramePasses {
+    shadows: PassId,
+    main: PassId,
+    // (pass, source, target) of each bloom blur step
+    bloom_downsamples: Vec<(PassId, ResourceId, ResourceId)>,
+    bloom_upsamples: Vec<(PassId, ResourceId, ResourceId)>,
+    composite: PassId,
+    fxaa: Option<PassId>,
+    debug: PassId,
+
+    hdr: ResourceId,
+    depth: ResourceId,
+    // the largest bloom mip, which ends up holding the whole blur
+    bloom: Option<ResourceId>,
+    // composite output when fxaa runs after it
+    ldr: Option<ResourceId>
 }
-impl_vertex!(Vertex, position);
 
 pub struct Core<'a> {
     meshes: Vec<&'a Mesh>,
-    // todo add materials assigned to each mesh
+    materials: Vec<Material>,
+    shadow_flags: Vec<ShadowFlags>,
+    lights: Vec<Light>,
+    camera: Camera,
 
     // shaders
-    fragment_shader: fs::Shader,
+    fragment_shader: RuntimeShader,
     vertex_shader: vs::Shader,
+    // runtime loaded replacements for the built-in shaders
+    lit_program: Option<ShaderProgram>,
+    pbr_program: Option<ShaderProgram>,
+    shader_hot_reload: bool,
+    // reload failures of the hot reload in render(), until they're polled
+    shader_errors: Vec<(ShaderTarget, String)>,
 
     // VAO & VBO
     vertex_buffers: Vec<Arc<BufferAccess + Send + Sync>>,
 
+    // instanced rendering
+    instanced_vertex_shader: instanced_vs::Shader,
+    instanced_pipeline: Arc<GraphicsPipelineAbstract + Send + Sync>,
+    instanced_meshes: Vec<InstancedMesh>,
+    instances: InstanceBatches,
+
+    // skeletal animation
+    skinned_vertex_shader: skinned_vs::Shader,
+    skinned_pipeline: Arc<GraphicsPipelineAbstract + Send + Sync>,
+    skinned_meshes: Vec<SkinnedMesh>,
+
+    // level of detail
+    lod_meshes: Vec<LodMesh>,
+    lod_settings: LodSettings,
+
+    // physically based rendering
+    pbr_fragment_shader: RuntimeShader,
+    pbr_vertex_shader: pbr::vs::Shader,
+    pbr_pipeline: Arc<GraphicsPipelineAbstract + Send + Sync>,
+    pbr_meshes: Vec<PbrDrawable>,
+    environment: GpuEnvironment,
+    material_sampler: Arc<Sampler>,
+    default_maps: [Arc<ImmutableImage<Format>>; 5],
+
+    // shadow mapping
+    shadow_settings: ShadowSettings,
+    shadow_fragment_shader: shadow_map::fs::Shader,
+    shadow_vertex_shader: shadow_map::vs::Shader,
+    shadow_render_pass: Arc<RenderPassAbstract + Send + Sync>,
+    shadow_pipeline: Arc<GraphicsPipelineAbstract + Send + Sync>,
+    pbr_shadow_pipeline: Arc<GraphicsPipelineAbstract + Send + Sync>,
+    instanced_shadow_vertex_shader: shadow_map::instanced_vs::Shader,
+    instanced_shadow_pipeline: Arc<GraphicsPipelineAbstract + Send + Sync>,
+    skinned_shadow_vertex_shader: shadow_map::skinned_vs::Shader,
+    skinned_shadow_pipeline: Arc<GraphicsPipelineAbstract + Send + Sync>,
+    shadow_atlas: ShadowAtlas,
+
+    // frame composition
+    frame_graph: CompiledGraph,
+    frame_passes: FramePasses,
+    // backing images of the graph's transient resources, indexed like frame_graph.images
+    graph_images: Vec<Arc<AttachmentImage<Format>>>,
+
+    // post-processing
+    post_settings: PostSettings,
+    post_processor: PostProcessor,
+
+    debug_renderer: DebugRenderer,
+
     width: u32,
     height: u32,
 
+    // display state
+    window_mode: WindowMode,
+    windowed_placement: WindowPlacement,
+    scale_factor: f64,
+    display_events: Vec<DisplayEvent>,
+
     // vulkan structures
     instance: Arc<Instance>,
     debug_callback: Option<DebugCallback>,
@@ -177,33 +546,137 @@ impl<'a> Core<'a> {
         let instance = Self::create_instance();
         let debug_callback = Self::setup_debug_callback(&instance);
         let (events_loop, surface) = Self::create_surface(&instance, name, width, height);
+        let scale_factor = surface.window().get_hidpi_factor();
+        let windowed_placement = Self::current_placement(surface.window());
+        // the swap chain extent is in physical pixels
+        let (width, height) = (
+            (f64::from(width) * scale_factor).round() as u32,
+            (f64::from(height) * scale_factor).round() as u32
+        );
 
         let physical_device_index = Self::pick_physical_device(&instance, &surface);
         let (device, graphics_queue, present_queue) = Self::create_logical_device(
             &instance, &surface, physical_device_index);
 
         // initializing shader modules
-        let fragment_shader = fs::Shader::load(device.clone()).expect("failed to create shader module");
+        let fragment_shader = RuntimeShader::from_glsl(&device, FS_GLSL, ShaderStage::Fragment, "lit.frag")
+            .expect("failed to create shader module");
         let vertex_shader = vs::Shader::load(device.clone()).expect("failed to create shader module");
+        let instanced_vertex_shader = instanced_vs::Shader::load(device.clone()).expect("failed to create shader module");
+        let pbr_fragment_shader = RuntimeShader::from_glsl(&device, pbr::FS_GLSL, ShaderStage::Fragment, "pbr.frag")
+            .expect("failed to create shader module");
+        let pbr_vertex_shader = pbr::vs::Shader::load(device.clone()).expect("failed to create shader module");
+        let shadow_fragment_shader = shadow_map::fs::Shader::load(device.clone()).expect("failed to create shader module");
+        let shadow_vertex_shader = shadow_map::vs::Shader::load(device.clone()).expect("failed to create shader module");
+        let instanced_shadow_vertex_shader = shadow_map::instanced_vs::Shader::load(device.clone())
+            .expect("failed to create shader module");
+        let skinned_vertex_shader = skinned_vs::Shader::load(device.clone()).expect("failed to create shader module");
+        let skinned_shadow_vertex_shader = shadow_map::skinned_vs::Shader::load(device.clone())
+            .expect("failed to create shader module");
 
         let (swap_chain, swap_chain_images) = Self::create_swap_chain(&instance, &surface, physical_device_index,
             &device, &graphics_queue, &present_queue, width, height, None);
         
-        let render_pass = Self::create_render_pass(&device, swap_chain.format());
+        let render_pass = Self::create_render_pass(&device, HDR_FORMAT);
         let graphics_pipeline = Self::create_graphics_pipeline(&device, swap_chain.dimensions(), &render_pass, &fragment_shader, &vertex_shader);
-
-        let swap_chain_framebuffers = Self::create_framebuffers(&swap_chain_images, &render_pass);
+        let pbr_pipeline = Self::create_pbr_pipeline(&device, swap_chain.dimensions(), &render_pass,
+            &pbr_fragment_shader, &pbr_vertex_shader);
+        let instanced_pipeline = Self::create_instanced_pipeline(&device, swap_chain.dimensions(), &render_pass,
+            &fragment_shader, &instanced_vertex_shader);
+        let skinned_pipeline = Self::create_skinned_pipeline(&device, swap_chain.dimensions(), &render_pass,
+            &fragment_shader, &skinned_vertex_shader);
+
+        let environment = GpuEnvironment::neutral(&device, &graphics_queue);
+        let material_sampler = Sampler::simple_repeat_linear(device.clone());
+        let default_maps = Self::create_default_maps(&graphics_queue);
+
+        let shadow_settings = ShadowSettings::default();
+        let shadow_render_pass = shadow_map::create_render_pass(&device);
+        let shadow_pipeline = shadow_map::create_pipeline::<Vertex>(&device, &shadow_render_pass,
+            &shadow_fragment_shader, &shadow_vertex_shader);
+        let pbr_shadow_pipeline = shadow_map::create_pipeline::<PbrVertex>(&device, &shadow_render_pass,
+            &shadow_fragment_shader, &shadow_vertex_shader);
+        let instanced_shadow_pipeline = shadow_map::create_instanced_pipeline::<Vertex, InstanceData>(&device,
+            &shadow_render_pass, &shadow_fragment_shader, &instanced_shadow_vertex_shader);
+        let skinned_shadow_pipeline = shadow_map::create_skinned_pipeline::<SkinnedVertex>(&device,
+            &shadow_render_pass, &shadow_fragment_shader, &skinned_shadow_vertex_shader);
+        let shadow_atlas = ShadowAtlas::new(&device, &shadow_render_pass, 1, shadow_settings.map_size);
+
+        let post_settings = PostSettings::default();
+        let mut post_processor = PostProcessor::new(&device, &graphics_queue, swap_chain.format());
+        post_processor.update_lut(&graphics_queue, &post_settings);
+        let debug_renderer = DebugRenderer::new(&device, swap_chain.format(), DEPTH_FORMAT);
+        let (frame_graph, frame_passes) = Self::create_frame_graph(&post_settings);
+        let graph_images = Self::create_graph_images(&device, &frame_graph, swap_chain.dimensions());
+
+        // the last post-processing step writes the swap chain image
+        let swap_chain_framebuffers = Self::create_framebuffers(&swap_chain_images, &post_processor.output_render_pass);
 
         let previous_frame_end = Some(Self::create_sync_objects(&device));
 
         let mut ret = Core {
             meshes: Vec::new(),
+            materials: Vec::new(),
+            shadow_flags: Vec::new(),
+            lights: Vec::new(),
+            camera: Camera::default(),
             fragment_shader,
             vertex_shader,
+            lit_program: None,
+            pbr_program: None,
+            shader_hot_reload: false,
+            shader_errors: vec![],
             vertex_buffers: vec![],
+
+            instanced_vertex_shader,
+            instanced_pipeline,
+            instanced_meshes: vec![],
+            instances: InstanceBatches::new(),
+
+            skinned_vertex_shader,
+            skinned_pipeline,
+            skinned_meshes: vec![],
+
+            lod_meshes: vec![],
+            lod_settings: LodSettings::default(),
+
+            pbr_fragment_shader,
+            pbr_vertex_shader,
+            pbr_pipeline,
+            pbr_meshes: vec![],
+            environment,
+            material_sampler,
+            default_maps,
+
+            shadow_settings,
+            shadow_fragment_shader,
+            shadow_vertex_shader,
+            shadow_render_pass,
+            shadow_pipeline,
+            pbr_shadow_pipeline,
+            instanced_shadow_vertex_shader,
+            instanced_shadow_pipeline,
+            skinned_shadow_vertex_shader,
+            skinned_shadow_pipeline,
+            shadow_atlas,
+
+            frame_graph,
+            frame_passes,
+            graph_images,
+
+            post_settings,
+            post_processor,
+
+            debug_renderer,
+
             width,
             height,
 
+            window_mode: WindowMode::Windowed,
+            windowed_placement,
+            scale_factor,
+            display_events: vec![],
+
             instance,
             debug_callback,
 
@@ -476,6 +949,7 @@ impl<'a> Core<'a> {
         (swap_chain, images)
     }
 
+    // the depth is stored for the debug lines drawn after post-processing
     fn create_render_pass(device: &Arc<Device>, color_format: Format) -> Arc<RenderPassAbstract + Send + Sync> {
         Arc::new(single_pass_renderpass!(device.clone(),
             attachments: {
@@ -484,20 +958,121 @@ impl<'a> Core<'a> {
                     store: Store,
                     format: color_format,
                     samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: Store,
+                    format: DEPTH_FORMAT,
+                    samples: 1,
                 }
             },
             pass: {
                 color: [color],
-                depth_stencil: {}
+                depth_stencil: {depth}
             }
         ).unwrap())
     }
 
+    // the scene is drawn to an HDR image, the post-processing passes turn it into the swap chain image
+    fn create_frame_graph(post_settings: &PostSettings) -> (CompiledGraph, FramePasses) {
+        let mut graph = RenderGraph::new();
+        let swap_chain_image = graph.import_image("swap chain image", ImageLayout::Undefined, ImageLayout::PresentSrc);
+        let shadow_atlas = graph.import_image("shadow atlas", ImageLayout::Undefined, ImageLayout::ShaderReadOnlyOptimal);
+        let hdr = graph.create_image("hdr", ImageDesc { format: HDR_FORMAT, size: ImageSize::SwapchainRelative(1.0) });
+        let depth = graph.create_image("depth", ImageDesc { format: DEPTH_FORMAT, size: ImageSize::SwapchainRelative(1.0) });
+
+        // the shadow pass always runs so the atlas is cleared even when nothing casts
+        let shadows = graph.add_pass("shadows");
+        graph.clear(shadows, shadow_atlas, ResourceUsage::DepthStencilAttachment);
+
+        let main = graph.add_pass("main");
+        graph.read(main, shadow_atlas, ResourceUsage::Sampled);
+        graph.clear(main, hdr, ResourceUsage::ColorAttachment);
+        graph.clear(main, depth, ResourceUsage::DepthStencilAttachment);
+
+        // bloom halves the resolution down a chain of mips, then adds each mip back onto the next larger one
+        let mut bloom_downsamples = vec![];
+        let mut bloom_upsamples = vec![];
+        let mut bloom = None;
+        if post_settings.bloom.enabled && post_settings.bloom.mip_count > 0 {
+            let mips: Vec<ResourceId> = (0..post_settings.bloom.mip_count)
+                .map(|mip| graph.create_image(&format!("bloom mip {}", mip), ImageDesc {
+                    format: HDR_FORMAT,
+                    size: ImageSize::SwapchainRelative(0.5f32.powi(mip as i32 + 1))
+                }))
+                .collect();
+
+            let mut source = hdr;
+            for (mip, target) in mips.iter().cloned().enumerate() {
+                let pass = graph.add_pass(&format!("bloom downsample {}", mip));
+                graph.read(pass, source, ResourceUsage::Sampled);
+                graph.clear(pass, target, ResourceUsage::ColorAttachment);
+                bloom_downsamples.push((pass, source, target));
+                source = target;
+            }
+            for mip in (1..mips.len()).rev() {
+                let pass = graph.add_pass(&format!("bloom upsample {}", mip - 1));
+                graph.read(pass, mips[mip], ResourceUsage::Sampled);
+                graph.write(pass, mips[mip - 1], ResourceUsage::ColorAttachment);
+                bloom_upsamples.push((pass, mips[mip], mips[mip - 1]));
+            }
+            bloom = Some(mips[0]);
+        }
+
+        let composite = graph.add_pass("composite");
+        graph.read(composite, hdr, ResourceUsage::Sampled);
+        if let Some(bloom) = bloom {
+            graph.read(composite, bloom, ResourceUsage::Sampled);
+        }
+
+        // fxaa works on tonemapped colors, so with it enabled the composite goes to an intermediate image
+        let (fxaa, ldr) = if post_settings.fxaa {
+            let ldr = graph.create_image("ldr", ImageDesc { format: LDR_FORMAT, size: ImageSize::SwapchainRelative(1.0) });
+            graph.clear(composite, ldr, ResourceUsage::ColorAttachment);
+
+            let fxaa = graph.add_pass("fxaa");
+            graph.read(fxaa, ldr, ResourceUsage::Sampled);
+            graph.clear(fxaa, swap_chain_image, ResourceUsage::ColorAttachment);
+            (Some(fxaa), Some(ldr))
+        } else {
+            graph.clear(composite, swap_chain_image, ResourceUsage::ColorAttachment);
+            (None, None)
+        };
+
+        // debug lines go on top of the finished image, so post-processing doesn't change their colors
+        let debug = graph.add_pass("debug lines");
+        graph.read(debug, depth, ResourceUsage::DepthStencilRead);
+        graph.write(debug, swap_chain_image, ResourceUsage::ColorAttachment);
+
+        let compiled = graph.compile().expect("invalid frame graph");
+        (compiled, FramePasses {
+            shadows,
+            main,
+            bloom_downsamples,
+            bloom_upsamples,
+            composite,
+            fxaa,
+            debug,
+            hdr,
+            depth,
+            bloom,
+            ldr
+        })
+    }
+
+    fn create_graph_images(device: &Arc<Device>, graph: &CompiledGraph, swap_chain_extent: [u32; 2])
+            -> Vec<Arc<AttachmentImage<Format>>> {
+        graph.images.iter()
+            .map(|image| AttachmentImage::with_usage(device.clone(), image.desc.size.resolve(swap_chain_extent),
+                image.desc.format, image.usage).expect("failed to create render graph image"))
+            .collect()
+    }
+
     fn create_graphics_pipeline(
         device: &Arc<Device>,
         swap_chain_extent: [u32; 2],
         render_pass: &Arc<RenderPassAbstract + Send + Sync>,
-        frag_shader_module: &fs::Shader,
+        frag_shader_module: &RuntimeShader,
         vert_shader_module: &vs::Shader
     ) -> Arc<GraphicsPipelineAbstract + Send + Sync> {
         let dimensions = [swap_chain_extent[0] as f32, swap_chain_extent[1] as f32];
@@ -513,7 +1088,7 @@ impl<'a> Core<'a> {
             .triangle_list()
             .primitive_restart(false)
             .viewports(vec![viewport]) // NOTE: also sets scissor to cover whole viewport
-            .fragment_shader(frag_shader_module.main_entry_point(), ())
+            .fragment_shader(frag_shader_module.entry_point(), ())
             .depth_clamp(false)
             // NOTE: there's an outcommented .rasterizer_discard() in Vulkano...
             .polygon_mode_fill() // = default
@@ -521,6 +1096,7 @@ impl<'a> Core<'a> {
             .cull_mode_back()
             .front_face_clockwise()
             // NOTE: no depth_bias here, but on pipeline::raster::Rasterization
+            .depth_stencil_simple_depth()
             .blend_pass_through() // = default
             .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
             .build(device.clone())
@@ -529,6 +1105,105 @@ impl<'a> Core<'a> {
         pipeline
     }
 
+    fn create_pbr_pipeline(
+        device: &Arc<Device>,
+        swap_chain_extent: [u32; 2],
+        render_pass: &Arc<RenderPassAbstract + Send + Sync>,
+        frag_shader_module: &RuntimeShader,
+        vert_shader_module: &pbr::vs::Shader
+    ) -> Arc<GraphicsPipelineAbstract + Send + Sync> {
+        let dimensions = [swap_chain_extent[0] as f32, swap_chain_extent[1] as f32];
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions,
+            depth_range: 0.0 .. 1.0,
+        };
+
+        Arc::new(GraphicsPipeline::start()
+            .vertex_input(SingleBufferDefinition::<PbrVertex>::new())
+            .vertex_shader(vert_shader_module.main_entry_point(), ())
+            .triangle_list()
+            .viewports(vec![viewport])
+            .fragment_shader(frag_shader_module.entry_point(), ())
+            .cull_mode_back()
+            .front_face_clockwise()
+            .depth_stencil_simple_depth()
+            .blend_pass_through()
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .unwrap())
+    }
+
+    // the lit pipeline with a second, per instance vertex buffer binding
+    fn create_instanced_pipeline(
+        device: &Arc<Device>,
+        swap_chain_extent: [u32; 2],
+        render_pass: &Arc<RenderPassAbstract + Send + Sync>,
+        frag_shader_module: &RuntimeShader,
+        vert_shader_module: &instanced_vs::Shader
+    ) -> Arc<GraphicsPipelineAbstract + Send + Sync> {
+        let dimensions = [swap_chain_extent[0] as f32, swap_chain_extent[1] as f32];
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions,
+            depth_range: 0.0 .. 1.0,
+        };
+
+        Arc::new(GraphicsPipeline::start()
+            .vertex_input(OneVertexOneInstanceDefinition::<Vertex, InstanceData>::new())
+            .vertex_shader(vert_shader_module.main_entry_point(), ())
+            .triangle_list()
+            .viewports(vec![viewport])
+            .fragment_shader(frag_shader_module.entry_point(), ())
+            .cull_mode_back()
+            .front_face_clockwise()
+            .depth_stencil_simple_depth()
+            .blend_pass_through()
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .unwrap())
+    }
+
+    fn create_skinned_pipeline(
+        device: &Arc<Device>,
+        swap_chain_extent: [u32; 2],
+        render_pass: &Arc<RenderPassAbstract + Send + Sync>,
+        frag_shader_module: &RuntimeShader,
+        vert_shader_module: &skinned_vs::Shader
+    ) -> Arc<GraphicsPipelineAbstract + Send + Sync> {
+        let dimensions = [swap_chain_extent[0] as f32, swap_chain_extent[1] as f32];
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions,
+            depth_range: 0.0 .. 1.0,
+        };
+
+        Arc::new(GraphicsPipeline::start()
+            .vertex_input(SingleBufferDefinition::<SkinnedVertex>::new())
+            .vertex_shader(vert_shader_module.main_entry_point(), ())
+            .triangle_list()
+            .viewports(vec![viewport])
+            .fragment_shader(frag_shader_module.entry_point(), ())
+            .cull_mode_back()
+            .front_face_clockwise()
+            .depth_stencil_simple_depth()
+            .blend_pass_through()
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .unwrap())
+    }
+
+    // stand-ins for missing material maps, neutral under the multiplication by the material factors
+    fn create_default_maps(queue: &Arc<Queue>) -> [Arc<ImmutableImage<Format>>; 5] {
+        [
+            pbr::upload_texture(queue, &Texture::solid([255, 255, 255, 255], true)),
+            pbr::upload_texture(queue, &Texture::solid([255, 255, 255, 255], false)),
+            pbr::upload_texture(queue, &Texture::solid([128, 128, 255, 255], false)),
+            pbr::upload_texture(queue, &Texture::solid([255, 255, 255, 255], false)),
+            pbr::upload_texture(queue, &Texture::solid([255, 255, 255, 255], true))
+        ]
+    }
+
     fn create_framebuffers(
         swap_chain_images: &[Arc<SwapchainImage<Window>>],
         render_pass: &Arc<RenderPassAbstract + Send + Sync>
@@ -552,21 +1227,350 @@ impl<'a> Core<'a> {
     *********************************/
 
     pub fn create_command_buffers(&mut self) {
+        self.command_buffers = vec![];
+        for image_index in 0..self.swap_chain_framebuffers.len() {
+            let command_buffer = self.create_command_buffer(image_index);
+            self.command_buffers.push(command_buffer);
+        }
+    }
+
+    // gives every shadow casting light its range of shadow views and fills in its shadow indices
+    fn assign_shadows(&self, light_data: &mut [LightData]) -> Vec<ShadowView> {
+        let mut views = vec![];
+        let casting_lights = self.lights.iter().filter(|light| match light {
+            Light::Ambient { .. } => false,
+            _ => true
+        }).take(light_data.len());
+
+        // light_data has the same order as the non ambient lights up to MAX_LIGHTS, see pack_lights
+        for (data_index, light) in casting_lights.enumerate() {
+            let first = views.len();
+            match light {
+                Light::Directional { direction, cast_shadows: true, .. } => {
+                    views.extend(shadow::directional_cascades(&self.camera, direction, &self.shadow_settings));
+                },
+                Light::Spot { position, direction, attenuation, outer_angle, cast_shadows: true, .. } => {
+                    views.push(ShadowView {
+                        view_proj: shadow::spot_light_matrix(position, direction, *outer_angle, attenuation.range),
+                        split_far: std::f32::MAX
+                    });
+                },
+                _ => continue
+            }
+            light_data[data_index].shadow = [first as f32, (views.len() - first) as f32, 0.0, 0.0];
+        }
+
+        views
+    }
+
+    // lights and materials can change every frame, so the light data is uploaded per command buffer
+    fn create_command_buffer(&mut self, image_index: usize) -> Arc<AutoCommandBuffer> {
         let queue_family = self.graphics_queue.family();
-        self.command_buffers = self.swap_chain_framebuffers.iter()
-            .map(|framebuffer| {
-                Arc::new(AutoCommandBufferBuilder::primary_simultaneous_use(self.device.clone(), queue_family)
-                    .unwrap()
-                    .begin_render_pass(framebuffer.clone(), false, vec![[0.0, 0.0, 0.0, 1.0].into()])
-                    .unwrap()
-                    .draw(self.graphics_pipeline.clone(), &DynamicState::none(), self.vertex_buffers.clone(), (), ())
-                    .unwrap()
-                    .end_render_pass()
-                    .unwrap()
-                    .build()
-                    .unwrap())
+        let (ambient, mut light_data) = pack_lights(&self.lights);
+        let light_count = light_data.len() as u32;
+        let shadow_views = self.assign_shadows(&mut light_data);
+        if light_data.is_empty() {
+            // zero sized buffers aren't allowed, the shader won't read past light_count anyway
+            light_data.push(LightData {
+                position_kind: [0.0; 4],
+                direction: [0.0; 4],
+                color_intensity: [0.0; 4],
+                attenuation: [1.0, 0.0, 0.0, 0.0],
+                cone: [0.0; 4],
+                shadow: NO_SHADOW
+            });
+        }
+
+        if shadow_views.len() > self.shadow_atlas.capacity() || self.shadow_atlas.tile_size != self.shadow_settings.map_size {
+            self.shadow_atlas = ShadowAtlas::new(&self.device, &self.shadow_render_pass,
+                shadow_views.len(), self.shadow_settings.map_size);
+        }
+        let atlas = &self.shadow_atlas;
+        let mut shadow_data: Vec<ShadowData> = shadow_views.iter().enumerate()
+            .map(|(tile, view)| shadow::shadow_data(view, tile, atlas.tiles_per_row, &self.shadow_settings))
+            .collect();
+        if shadow_data.is_empty() {
+            shadow_data.push(shadow::shadow_data(&ShadowView { view_proj: Mat4::identity(), split_far: 0.0 },
+                0, 1, &self.shadow_settings));
+        }
+
+        let camera = &self.camera;
+        let frame_data = FrameData {
+            view_proj: camera.view_projection_matrix().to_gpu(),
+            camera_position: [camera.position.x, camera.position.y, camera.position.z, 1.0],
+            camera_forward: [camera.forward.x, camera.forward.y, camera.forward.z, 0.0],
+            ambient: [ambient.x, ambient.y, ambient.z, 1.0],
+            light_count: [light_count, 0, 0, 0],
+            shadow_params: [self.shadow_settings.pcf_radius as f32, 1.0 / atlas.size() as f32, 0.0, 0.0]
+        };
+        let frame_buffer = CpuAccessibleBuffer::from_data(self.device.clone(), BufferUsage::uniform_buffer(),
+            frame_data).unwrap();
+        let light_buffer = CpuAccessibleBuffer::from_iter(self.device.clone(), BufferUsage::storage_buffer(),
+            light_data.into_iter()).unwrap();
+        let shadow_buffer = CpuAccessibleBuffer::from_iter(self.device.clone(), BufferUsage::storage_buffer(),
+            shadow_data.into_iter()).unwrap();
+
+        // one instance buffer per batch, shared by the shadow and main passes
+        let instance_buffers = self.instances.batches().iter().enumerate()
+            .filter(|(_, batch)| !batch.instances.is_empty())
+            .map(|(batch_index, batch)| {
+                let buffer: Arc<BufferAccess + Send + Sync> = CpuAccessibleBuffer::from_iter(self.device.clone(),
+                    BufferUsage::vertex_buffer(), batch.instances.iter().cloned()).unwrap();
+                (batch_index, buffer)
             })
             .collect();
+        let joint_buffers = self.skinned_meshes.iter()
+            .map(|mesh| CpuAccessibleBuffer::from_iter(self.device.clone(), BufferUsage::storage_buffer(),
+                mesh.skinning_matrices.iter().map(|matrix| matrix.to_gpu())).unwrap())
+            .collect();
+
+        let buffers = FrameBuffers {
+            frame: frame_buffer,
+            lights: light_buffer,
+            shadows: shadow_buffer,
+            instances: instance_buffers,
+            joints: joint_buffers
+        };
+
+        // vulkano transitions the image layouts itself, the graph decides which passes run and in which order
+        let mut builder = AutoCommandBufferBuilder::primary_simultaneous_use(self.device.clone(), queue_family)
+            .unwrap();
+        // everything drawn through debug_draw since the last frame
+        let debug_lines = debug_draw::take();
+
+        let passes = &self.frame_passes;
+        for pass in self.frame_graph.passes.iter() {
+            if pass.id == passes.shadows {
+                builder = self.record_shadow_pass(builder, &shadow_views, &buffers);
+            } else if pass.id == passes.main {
+                builder = self.record_main_pass(builder, &buffers);
+            } else if pass.id == passes.composite {
+                builder = self.record_composite_pass(builder, image_index);
+            } else if Some(pass.id) == passes.fxaa {
+                let ldr = self.graph_image(passes.ldr.expect("fxaa pass without its input"));
+                builder = self.post_processor.record_fxaa(builder, ldr, self.swap_chain_framebuffers[image_index].clone(),
+                    self.swap_chain.dimensions());
+            } else if pass.id == passes.debug {
+                builder = self.record_debug_pass(builder, image_index, &debug_lines);
+            } else if let Some(&(_, source, target)) = passes.bloom_downsamples.iter().find(|step| step.0 == pass.id) {
+                // only the first step reads the scene, that's where the threshold is applied
+                builder = self.post_processor.record_downsample(builder, self.graph_image(source),
+                    self.graph_image(target), source == passes.hdr, &self.post_settings);
+            } else if let Some(&(_, source, target)) = passes.bloom_upsamples.iter().find(|step| step.0 == pass.id) {
+                builder = self.post_processor.record_upsample(builder, self.graph_image(source), self.graph_image(target));
+            }
+        }
+
+        Arc::new(builder.build().unwrap())
+    }
+
+    fn record_shadow_pass(&self, builder: AutoCommandBufferBuilder, shadow_views: &[ShadowView], buffers: &FrameBuffers)
+            -> AutoCommandBufferBuilder {
+        let atlas = &self.shadow_atlas;
+        let mut builder = builder
+            .begin_render_pass(atlas.framebuffer.clone(), false, vec![ClearValue::Depth(1.0)])
+            .unwrap();
+        for (tile, view) in shadow_views.iter().enumerate() {
+            let dynamic_state = DynamicState {
+                line_width: None,
+                viewports: Some(vec![atlas.tile_viewport(tile)]),
+                scissors: None
+            };
+            let constants = shadow_map::vs::ty::LightMatrix {
+                view_proj: view.view_proj.to_gpu()
+            };
+
+            for (vertex_buffer, flags) in self.vertex_buffers.iter().zip(self.shadow_flags.iter()) {
+                if flags.cast {
+                    builder = builder.draw(self.shadow_pipeline.clone(), &dynamic_state,
+                        vec![vertex_buffer.clone()], (), constants)
+                        .unwrap();
+                }
+            }
+            for (mesh, joints) in self.skinned_meshes.iter().zip(buffers.joints.iter()) {
+                if mesh.shadows.cast {
+                    let joint_set = Arc::new(PersistentDescriptorSet::start(self.skinned_shadow_pipeline.clone(), 0)
+                        .add_buffer(joints.clone()).unwrap()
+                        .build().unwrap());
+                    builder = builder.draw(self.skinned_shadow_pipeline.clone(), &dynamic_state,
+                        vec![mesh.vertex_buffer.clone()], joint_set, constants)
+                        .unwrap();
+                }
+            }
+            // shadows only follow the current level, a fade isn't worth drawing the caster twice
+            for lod_mesh in self.lod_meshes.iter().filter(|lod_mesh| lod_mesh.shadows.cast) {
+                builder = builder.draw(self.shadow_pipeline.clone(), &dynamic_state,
+                    vec![lod_mesh.levels[lod_mesh.group.level()].clone()], (), constants)
+                    .unwrap();
+            }
+            for drawable in self.pbr_meshes.iter().filter(|drawable| drawable.shadows.cast) {
+                builder = builder.draw(self.pbr_shadow_pipeline.clone(), &dynamic_state,
+                    vec![drawable.vertex_buffer.clone()], (), constants)
+                    .unwrap();
+            }
+            for (batch_index, instance_buffer) in buffers.instances.iter() {
+                let mesh = &self.instanced_meshes[self.instances.batches()[*batch_index].mesh];
+                if mesh.shadows.cast {
+                    builder = builder.draw(self.instanced_shadow_pipeline.clone(), &dynamic_state,
+                        vec![mesh.vertex_buffer.clone(), instance_buffer.clone()], (), constants)
+                        .unwrap();
+                }
+            }
+        }
+        builder.end_render_pass().unwrap()
+    }
+
+    fn record_main_pass(&self, builder: AutoCommandBufferBuilder, buffers: &FrameBuffers) -> AutoCommandBufferBuilder {
+        let atlas = &self.shadow_atlas;
+        let descriptor_set = Arc::new(PersistentDescriptorSet::start(self.graphics_pipeline.clone(), 0)
+            .add_buffer(buffers.frame.clone()).unwrap()
+            .add_buffer(buffers.lights.clone()).unwrap()
+            .add_buffer(buffers.shadows.clone()).unwrap()
+            .add_sampled_image(atlas.image.clone(), atlas.sampler.clone()).unwrap()
+            .build().unwrap());
+
+        let framebuffer: Arc<FramebufferAbstract + Send + Sync> = Arc::new(Framebuffer::start(self.render_pass.clone())
+            .add(self.graph_image(self.frame_passes.hdr).clone()).unwrap()
+            .add(self.graph_image(self.frame_passes.depth).clone()).unwrap()
+            .build().unwrap());
+        let mut builder = builder
+            .begin_render_pass(framebuffer, false, vec![[0.0, 0.0, 0.0, 1.0].into(), ClearValue::Depth(1.0)])
+            .unwrap();
+
+        let lit_meshes = self.vertex_buffers.iter().zip(self.materials.iter()).zip(self.shadow_flags.iter());
+        for ((vertex_buffer, material), flags) in lit_meshes {
+            let data = material.to_data();
+            let constants = MaterialConstants {
+                diffuse: data.diffuse,
+                specular: data.specular,
+                flags: [if flags.receive { 1.0 } else { 0.0 }, 0.0, 0.0, 0.0]
+            };
+            builder = builder.draw(self.graphics_pipeline.clone(), &DynamicState::none(),
+                vec![vertex_buffer.clone()], descriptor_set.clone(), constants)
+                .unwrap();
+        }
+
+        // every skinned mesh has its own joint matrices, so its own descriptor set
+        for (mesh, joints) in self.skinned_meshes.iter().zip(buffers.joints.iter()) {
+            let skinned_set = Arc::new(PersistentDescriptorSet::start(self.skinned_pipeline.clone(), 0)
+                .add_buffer(buffers.frame.clone()).unwrap()
+                .add_buffer(buffers.lights.clone()).unwrap()
+                .add_buffer(buffers.shadows.clone()).unwrap()
+                .add_sampled_image(atlas.image.clone(), atlas.sampler.clone()).unwrap()
+                .add_buffer(joints.clone()).unwrap()
+                .build().unwrap());
+            let data = mesh.material.to_data();
+            let constants = MaterialConstants {
+                diffuse: data.diffuse,
+                specular: data.specular,
+                flags: [if mesh.shadows.receive { 1.0 } else { 0.0 }, 0.0, 0.0, 0.0]
+            };
+            builder = builder.draw(self.skinned_pipeline.clone(), &DynamicState::none(),
+                vec![mesh.vertex_buffer.clone()], skinned_set, constants)
+                .unwrap();
+        }
+
+        for lod_mesh in self.lod_meshes.iter() {
+            let data = lod_mesh.material.to_data();
+            for (level, dither) in lod_mesh.group.draws(&self.lod_settings) {
+                let (fade, fading_out) = match dither {
+                    LodDither::None => (0.0, 0.0),
+                    LodDither::FadeIn(fade) => (fade, 0.0),
+                    LodDither::FadeOut(fade) => (fade, 1.0)
+                };
+                let constants = MaterialConstants {
+                    diffuse: data.diffuse,
+                    specular: data.specular,
+                    flags: [if lod_mesh.shadows.receive { 1.0 } else { 0.0 }, fade, fading_out, 0.0]
+                };
+                builder = builder.draw(self.graphics_pipeline.clone(), &DynamicState::none(),
+                    vec![lod_mesh.levels[level].clone()], descriptor_set.clone(), constants)
+                    .unwrap();
+            }
+        }
+
+        if !buffers.instances.is_empty() {
+            let instanced_set = Arc::new(PersistentDescriptorSet::start(self.instanced_pipeline.clone(), 0)
+                .add_buffer(buffers.frame.clone()).unwrap()
+                .add_buffer(buffers.lights.clone()).unwrap()
+                .add_buffer(buffers.shadows.clone()).unwrap()
+                .add_sampled_image(atlas.image.clone(), atlas.sampler.clone()).unwrap()
+                .build().unwrap());
+
+            // one draw per batch of identical mesh and material
+            for (batch_index, instance_buffer) in buffers.instances.iter() {
+                let batch = &self.instances.batches()[*batch_index];
+                let mesh = &self.instanced_meshes[batch.mesh];
+                let data = batch.material.to_data();
+                let constants = MaterialConstants {
+                    diffuse: data.diffuse,
+                    specular: data.specular,
+                    flags: [if mesh.shadows.receive { 1.0 } else { 0.0 }, 0.0, 0.0, 0.0]
+                };
+                builder = builder.draw(self.instanced_pipeline.clone(), &DynamicState::none(),
+                    vec![mesh.vertex_buffer.clone(), instance_buffer.clone()], instanced_set.clone(), constants)
+                    .unwrap();
+            }
+        }
+
+        if !self.pbr_meshes.is_empty() {
+            let environment = &self.environment;
+            let environment_set = Arc::new(PersistentDescriptorSet::start(self.pbr_pipeline.clone(), 0)
+                .add_buffer(buffers.frame.clone()).unwrap()
+                .add_buffer(buffers.lights.clone()).unwrap()
+                .add_sampled_image(environment.irradiance.clone(), environment.sampler.clone()).unwrap()
+                .add_sampled_image(environment.prefiltered.clone(), environment.sampler.clone()).unwrap()
+                .add_sampled_image(environment.brdf_lut.clone(), environment.sampler.clone()).unwrap()
+                .add_buffer(buffers.shadows.clone()).unwrap()
+                .add_sampled_image(atlas.image.clone(), atlas.sampler.clone()).unwrap()
+                .build().unwrap());
+
+            for drawable in self.pbr_meshes.iter() {
+                let maps = &drawable.maps;
+                let material_set = Arc::new(PersistentDescriptorSet::start(self.pbr_pipeline.clone(), 1)
+                    .add_sampled_image(maps[0].clone(), self.material_sampler.clone()).unwrap()
+                    .add_sampled_image(maps[1].clone(), self.material_sampler.clone()).unwrap()
+                    .add_sampled_image(maps[2].clone(), self.material_sampler.clone()).unwrap()
+                    .add_sampled_image(maps[3].clone(), self.material_sampler.clone()).unwrap()
+                    .add_sampled_image(maps[4].clone(), self.material_sampler.clone()).unwrap()
+                    .build().unwrap());
+
+                let material = &drawable.material;
+                let constants = pbr::PbrConstants {
+                    base_color: [material.base_color.x, material.base_color.y, material.base_color.z, material.opacity],
+                    emissive_metallic: [material.emissive.x, material.emissive.y, material.emissive.z, material.metallic],
+                    parameters: [material.roughness, material.occlusion_strength, material.normal_scale,
+                        (environment.prefiltered_levels - 1) as f32],
+                    flags: [if drawable.shadows.receive { 1.0 } else { 0.0 }, 0.0, 0.0, 0.0]
+                };
+                builder = builder.draw(self.pbr_pipeline.clone(), &DynamicState::none(),
+                    vec![drawable.vertex_buffer.clone()], (environment_set.clone(), material_set), constants)
+                    .unwrap();
+            }
+        }
+
+        builder.end_render_pass().unwrap()
+    }
+
+    fn record_composite_pass(&self, builder: AutoCommandBufferBuilder, image_index: usize) -> AutoCommandBufferBuilder {
+        let passes = &self.frame_passes;
+        let target = match passes.ldr {
+            Some(ldr) => CompositeTarget::Ldr(self.graph_image(ldr)),
+            None => CompositeTarget::Output(self.swap_chain_framebuffers[image_index].clone(), self.swap_chain.dimensions())
+        };
+        self.post_processor.record_composite(builder, self.graph_image(passes.hdr),
+            passes.bloom.map(|bloom| self.graph_image(bloom)), target, &self.post_settings)
+    }
+
+    fn record_debug_pass(&self, builder: AutoCommandBufferBuilder, image_index: usize, lines: &DebugDraw)
+            -> AutoCommandBufferBuilder {
+        self.debug_renderer.record(builder, &self.device, &self.swap_chain_images[image_index],
+            self.graph_image(self.frame_passes.depth), lines, &self.camera.view_projection_matrix())
+    }
+
+    fn graph_image(&self, resource: ResourceId) -> &Arc<AttachmentImage<Format>> {
+        let index = self.frame_graph.image_index(resource).expect("resource isn't a transient graph image");
+        &self.graph_images[index]
     }
 
     fn recreate_swap_chain(&mut self) {
@@ -575,10 +1579,17 @@ impl<'a> Core<'a> {
         self.swap_chain = swap_chain;
         self.swap_chain_images = images;
 
-        self.render_pass = Self::create_render_pass(&self.device, self.swap_chain.format());
-        self.graphics_pipeline = Self::create_graphics_pipeline(&self.device, self.swap_chain.dimensions(),
-            &self.render_pass, &self.fragment_shader, &self.vertex_shader);
-        self.swap_chain_framebuffers = Self::create_framebuffers(&self.swap_chain_images, &self.render_pass);
+        self.graphics_pipeline = self.create_target_pipeline(ShaderTarget::Lit, self.lit_program.as_ref())
+            .expect("failed to create graphics pipeline");
+        self.pbr_pipeline = self.create_target_pipeline(ShaderTarget::Pbr, self.pbr_program.as_ref())
+            .expect("failed to create graphics pipeline");
+        self.instanced_pipeline = Self::create_instanced_pipeline(&self.device, self.swap_chain.dimensions(),
+            &self.render_pass, &self.fragment_shader, &self.instanced_vertex_shader);
+        self.skinned_pipeline = Self::create_skinned_pipeline(&self.device, self.swap_chain.dimensions(),
+            &self.render_pass, &self.fragment_shader, &self.skinned_vertex_shader);
+        self.graph_images = Self::create_graph_images(&self.device, &self.frame_graph, self.swap_chain.dimensions());
+        self.swap_chain_framebuffers = Self::create_framebuffers(&self.swap_chain_images,
+            &self.post_processor.output_render_pass);
         self.create_command_buffers();
     }
 
@@ -599,6 +1610,10 @@ impl<'a> Core<'a> {
             Err(err) => panic!("unexpected error when acquiring next image: {:?}", err)
         };
 
+        for lod_mesh in self.lod_meshes.iter_mut() {
+            lod_mesh.group.update(&self.camera, &self.lod_settings);
+        }
+        self.command_buffers[image_index] = self.create_command_buffer(image_index);
         let command_buffer = self.command_buffers[image_index].clone();
 
         let future = self.previous_frame_end.take().unwrap()
@@ -630,12 +1645,21 @@ impl<'a> Core<'a> {
     *********************************/
 
     pub fn add_new(&mut self, n_mesh: &'a Mesh) {
+        self.add_new_with_material(n_mesh, Material::default());
+    }
+
+    pub fn add_new_with_material(&mut self, n_mesh: &'a Mesh, material: Material) {
         self.meshes.push(n_mesh);
+        self.materials.push(material);
+        self.shadow_flags.push(ShadowFlags::default());
 
+        // vertex buffers aren't indexed, triangles are expanded into their corners
         let mut vertices = vec![];
-        for i in 0..n_mesh.vertices.len() {
+        for i in n_mesh.triangle_list() {
+            let normal = n_mesh.vertex_normal(i);
             vertices.push(Vertex {
-                position: [n_mesh.vertices[i][0], n_mesh.vertices[i][1], n_mesh.vertices[i][2]]
+                position: [n_mesh.vertices[i][0], n_mesh.vertices[i][1], n_mesh.vertices[i][2]],
+                normal: [normal.x, normal.y, normal.z]
             });
         }
 
@@ -645,14 +1669,491 @@ impl<'a> Core<'a> {
         self.vertex_buffers.push(new_vertex_buffer);
     }
 
+    pub fn set_material(&mut self, mesh_index: usize, material: Material) {
+        self.materials[mesh_index] = material;
+    }
+
+    // uploads the mesh once for drawing many copies of it with add_instance, returns its index
+    pub fn add_instanced_mesh(&mut self, n_mesh: &'a Mesh) -> usize {
+        self.meshes.push(n_mesh);
+
+        let mut vertices = vec![];
+        for i in n_mesh.triangle_list() {
+            let normal = n_mesh.vertex_normal(i);
+            vertices.push(Vertex {
+                position: [n_mesh.vertices[i][0], n_mesh.vertices[i][1], n_mesh.vertices[i][2]],
+                normal: [normal.x, normal.y, normal.z]
+            });
+        }
+        let vertex_buffer = CpuAccessibleBuffer::from_iter(self.device.clone(), BufferUsage::vertex_buffer(),
+            vertices.iter().cloned()).unwrap();
+
+        self.instanced_meshes.push(InstancedMesh {
+            vertex_buffer,
+            shadows: ShadowFlags::default()
+        });
+        self.instanced_meshes.len() - 1
+    }
+
+    // instances with the same mesh and an equal material are batched into one draw call
+    pub fn add_instance(&mut self, instanced_mesh_index: usize, material: &Material, transform: &Mat4, color: &Vec3)
+            -> InstanceId {
+        assert!(instanced_mesh_index < self.instanced_meshes.len(), "no instanced mesh {}", instanced_mesh_index);
+        self.instances.add(instanced_mesh_index, material, InstanceData::new(transform, color))
+    }
+
+    // returns false if the instance was removed
+    pub fn set_instance(&mut self, id: InstanceId, transform: &Mat4, color: &Vec3) -> bool {
+        self.instances.set(id, InstanceData::new(transform, color))
+    }
+
+    pub fn remove_instance(&mut self, id: InstanceId) -> bool {
+        self.instances.remove(id).is_some()
+    }
+
+    pub fn instance_count(&self) -> usize {
+        self.instances.instance_count()
+    }
+
+    pub fn set_instanced_shadow_flags(&mut self, instanced_mesh_index: usize, flags: ShadowFlags) {
+        self.instanced_meshes[instanced_mesh_index].shadows = flags;
+    }
+
+    // a lit mesh whose vertices follow the joints of skeleton by their JOINTS and WEIGHTS, drawn in the rest
+    // pose until set_pose is called. returns its index for the other skinned functions, errors if the skeleton
+    // has no joints or the mesh's skinning data doesn't fit it
+    pub fn add_skinned_mesh(&mut self, n_mesh: &'a Mesh, skeleton: Skeleton, material: Material) -> Result<usize, String> {
+        let influences = vertex_influences(n_mesh, skeleton.joints.len())?;
+        self.meshes.push(n_mesh);
+
+        let mut vertices = vec![];
+        for i in n_mesh.triangle_list() {
+            let normal = n_mesh.vertex_normal(i);
+            let (joints, weights) = influences[i];
+            vertices.push(SkinnedVertex {
+                position: [n_mesh.vertices[i][0], n_mesh.vertices[i][1], n_mesh.vertices[i][2]],
+                normal: [normal.x, normal.y, normal.z],
+                joints: [u32::from(joints[0]), u32::from(joints[1]), u32::from(joints[2]), u32::from(joints[3])],
+                weights
+            });
+        }
+        let vertex_buffer = CpuAccessibleBuffer::from_iter(self.device.clone(), BufferUsage::vertex_buffer(),
+            vertices.iter().cloned()).unwrap();
+
+        let skinning_matrices = skeleton.skinning_matrices(&skeleton.rest_pose());
+        self.skinned_meshes.push(SkinnedMesh {
+            vertex_buffer,
+            material,
+            shadows: ShadowFlags::default(),
+            skeleton,
+            skinning_matrices
+        });
+        Ok(self.skinned_meshes.len() - 1)
+    }
+
+    pub fn skeleton(&self, skinned_mesh_index: usize) -> &Skeleton {
+        &self.skinned_meshes[skinned_mesh_index].skeleton
+    }
+
+    // usually once per frame with the pose of an AnimationPlayer
+    pub fn set_pose(&mut self, skinned_mesh_index: usize, pose: &Pose) {
+        let mesh = &mut self.skinned_meshes[skinned_mesh_index];
+        mesh.skinning_matrices = mesh.skeleton.skinning_matrices(pose);
+    }
+
+    pub fn set_skinned_material(&mut self, skinned_mesh_index: usize, material: Material) {
+        self.skinned_meshes[skinned_mesh_index].material = material;
+    }
+
+    pub fn set_skinned_shadow_flags(&mut self, skinned_mesh_index: usize, flags: ShadowFlags) {
+        self.skinned_meshes[skinned_mesh_index].shadows = flags;
+    }
+
+    // a lit mesh drawn from one of its levels, picked every frame by the size it has on screen. levels go
+    // from most to least detailed and transitions has the projected size where each next level takes over,
+    // see LodGroup. returns the index for the other lod functions
+    pub fn add_lod_mesh(&mut self, levels: &[&'a Mesh], transitions: Vec<f32>, material: Material) -> Result<usize, String> {
+        if levels.is_empty() {
+            return Err("a lod mesh needs at least one level".to_string());
+        }
+        if transitions.len() != levels.len() - 1 {
+            return Err(format!("{} levels need {} transitions, not {}", levels.len(), levels.len() - 1, transitions.len()));
+        }
+
+        let mut buffers = vec![];
+        for &n_mesh in levels.iter() {
+            self.meshes.push(n_mesh);
+
+            let mut vertices = vec![];
+            for i in n_mesh.triangle_list() {
+                let normal = n_mesh.vertex_normal(i);
+                vertices.push(Vertex {
+                    position: [n_mesh.vertices[i][0], n_mesh.vertices[i][1], n_mesh.vertices[i][2]],
+                    normal: [normal.x, normal.y, normal.z]
+                });
+            }
+            let vertex_buffer: Arc<BufferAccess + Send + Sync> = CpuAccessibleBuffer::from_iter(self.device.clone(),
+                BufferUsage::vertex_buffer(), vertices.iter().cloned()).unwrap();
+            buffers.push(vertex_buffer);
+        }
+
+        self.lod_meshes.push(LodMesh {
+            levels: buffers,
+            material,
+            shadows: ShadowFlags::default(),
+            group: LodGroup::from_mesh(transitions, levels[0])
+        });
+        Ok(self.lod_meshes.len() - 1)
+    }
+
+    pub fn set_lod_material(&mut self, lod_mesh_index: usize, material: Material) {
+        self.lod_meshes[lod_mesh_index].material = material;
+    }
+
+    pub fn set_lod_shadow_flags(&mut self, lod_mesh_index: usize, flags: ShadowFlags) {
+        self.lod_meshes[lod_mesh_index].shadows = flags;
+    }
+
+    // the level drawn last frame, the incoming one during a fade
+    pub fn lod_level(&self, lod_mesh_index: usize) -> usize {
+        self.lod_meshes[lod_mesh_index].group.level()
+    }
+
+    pub fn set_lod_settings(&mut self, settings: LodSettings) {
+        self.lod_settings = settings;
+    }
+
+    pub fn lod_settings(&self) -> &LodSettings {
+        &self.lod_settings
+    }
+
+    // pbr meshes are drawn after the Blinn-Phong ones, in their own pipeline
+    pub fn add_new_pbr(&mut self, n_mesh: &'a Mesh, material: PbrMaterial) {
+        self.meshes.push(n_mesh);
+
+        let mut vertices = vec![];
+        for i in n_mesh.triangle_list() {
+            let normal = n_mesh.vertex_normal(i);
+            vertices.push(PbrVertex {
+                position: [n_mesh.vertices[i][0], n_mesh.vertices[i][1], n_mesh.vertices[i][2]],
+                normal: [normal.x, normal.y, normal.z],
+                uv: n_mesh.vertex_uv(i)
+            });
+        }
+        let vertex_buffer = CpuAccessibleBuffer::from_iter(self.device.clone(), BufferUsage::vertex_buffer(),
+            vertices.iter().cloned()).unwrap();
+
+        let maps = {
+            let upload = |map: &Option<Arc<Texture>>, index: usize| match map {
+                Some(texture) => pbr::upload_texture(&self.graphics_queue, texture),
+                None => self.default_maps[index].clone()
+            };
+            [
+                upload(&material.base_color_map, 0),
+                upload(&material.metallic_roughness_map, 1),
+                upload(&material.normal_map, 2),
+                upload(&material.occlusion_map, 3),
+                upload(&material.emissive_map, 4)
+            ]
+        };
+
+        self.pbr_meshes.push(PbrDrawable {
+            vertex_buffer,
+            material,
+            shadows: ShadowFlags::default(),
+            maps
+        });
+    }
+
+    // maps are baked offline with ibl::bake
+    pub fn set_environment(&mut self, maps: &IblMaps) {
+        self.environment = GpuEnvironment::new(&self.device, &self.graphics_queue, maps);
+    }
+
+    pub fn add_light(&mut self, light: Light) -> usize {
+        self.lights.push(light);
+        self.lights.len() - 1
+    }
+
+    pub fn light_mut(&mut self, index: usize) -> Option<&mut Light> {
+        self.lights.get_mut(index)
+    }
+
+    pub fn remove_light(&mut self, index: usize) -> Light {
+        self.lights.remove(index)
+    }
+
+    pub fn clear_lights(&mut self) {
+        self.lights.clear();
+    }
+
+    pub fn set_camera_position(&mut self, position: Vec3) {
+        self.camera.position = position;
+    }
+
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.camera = camera;
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn set_shadow_settings(&mut self, settings: ShadowSettings) {
+        self.shadow_settings = settings;
+    }
+
+    // takes effect from the next frame. toggling bloom or fxaa rebuilds the frame graph
+    pub fn set_post_settings(&mut self, settings: PostSettings) {
+        self.post_processor.update_lut(&self.graphics_queue, &settings);
+        let rebuild = settings.bloom.enabled != self.post_settings.bloom.enabled
+            || settings.bloom.mip_count != self.post_settings.bloom.mip_count
+            || settings.fxaa != self.post_settings.fxaa;
+        self.post_settings = settings;
+
+        if rebuild {
+            let (frame_graph, frame_passes) = Self::create_frame_graph(&self.post_settings);
+            self.graph_images = Self::create_graph_images(&self.device, &frame_graph, self.swap_chain.dimensions());
+            self.frame_graph = frame_graph;
+            self.frame_passes = frame_passes;
+        }
+    }
+
+    pub fn post_settings(&self) -> &PostSettings {
+        &self.post_settings
+    }
+
+    pub fn set_shadow_flags(&mut self, mesh_index: usize, flags: ShadowFlags) {
+        self.shadow_flags[mesh_index] = flags;
+    }
+
+    pub fn set_pbr_shadow_flags(&mut self, pbr_mesh_index: usize, flags: ShadowFlags) {
+        self.pbr_meshes[pbr_mesh_index].shadows = flags;
+    }
+
     pub fn render(&mut self) {
+        if self.shader_hot_reload {
+            let errors = self.reload_changed_shaders();
+            self.shader_errors.extend(errors);
+        }
+
         self.draw_frame();
         let mut done = false;
+        let mut resized = None;
+        let mut moved = None;
+        let mut new_scale_factor = None;
         self.events_loop.poll_events(|ev| {
-            if let Event::WindowEvent { event: WindowEvent::CloseRequested, .. } = ev {
-                done = true
+            if let Event::WindowEvent { event, .. } = ev {
+                match event {
+                    WindowEvent::CloseRequested => done = true,
+                    WindowEvent::Resized(size) => resized = Some(size),
+                    WindowEvent::Moved(position) => moved = Some(position),
+                    WindowEvent::HiDpiFactorChanged(factor) => new_scale_factor = Some(factor),
+                    _ => {}
+                }
             }
         });
         self.done = done;
+
+        if let Some(factor) = new_scale_factor {
+            self.scale_factor = factor;
+            self.display_events.push(DisplayEvent::ScaleFactorChanged(factor));
+            // winit doesn't always send a resize along with the dpi change
+            if resized.is_none() {
+                resized = self.surface.window().get_inner_size();
+            }
+        }
+
+        if let Some(size) = resized {
+            if self.window_mode == WindowMode::Windowed {
+                self.windowed_placement.size = (size.width as u32, size.height as u32);
+            }
+            let physical_size = size.to_physical(self.scale_factor);
+            self.width = physical_size.width as u32;
+            self.height = physical_size.height as u32;
+            self.recreate_swap_chain = true;
+            self.display_events.push(DisplayEvent::Resized(self.width, self.height));
+        }
+
+        if let Some(position) = moved {
+            if self.window_mode == WindowMode::Windowed {
+                self.windowed_placement.position = (position.x as i32, position.y as i32);
+            }
+        }
+    }
+
+    /*********************************
+    *** DISPLAY MODE FUNCTIONS
+    *********************************/
+
+    pub fn monitors(&self) -> Vec<MonitorInfo> {
+        self.events_loop.get_available_monitors()
+            .enumerate()
+            .map(|(index, monitor)| MonitorInfo::from_winit(&monitor, index))
+            .collect()
+    }
+
+    pub fn window_mode(&self) -> WindowMode {
+        self.window_mode
+    }
+
+    pub fn windowed_placement(&self) -> WindowPlacement {
+        self.windowed_placement
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    pub fn extent(&self) -> [u32; 2] {
+        self.swap_chain.dimensions()
+    }
+
+    // returns and clears the display events gathered since the last call
+    pub fn poll_display_events(&mut self) -> Vec<DisplayEvent> {
+        self.display_events.drain(..).collect()
+    }
+
+    // winit can't change video modes, so exclusive fullscreen only goes to the monitor's current mode and
+    // asking for a specific one is an error
+    pub fn set_window_mode(&mut self, mode: WindowMode, monitor_index: usize) -> Result<(), String> {
+        let window = self.surface.window();
+        let monitor = window.get_available_monitors().nth(monitor_index)
+            .ok_or_else(|| format!("no monitor {}", monitor_index))?;
+        if let WindowMode::Fullscreen(Some(video_mode)) = mode {
+            return Err(format!("video mode {:?} can't be set, only the current mode of {} can be used",
+                video_mode, monitor.get_name().unwrap_or_else(|| format!("monitor {}", monitor_index))));
+        }
+
+        if self.window_mode == WindowMode::Windowed {
+            self.windowed_placement = Self::current_placement(window);
+        }
+
+        match mode {
+            WindowMode::Windowed => {
+                window.set_fullscreen(None);
+                window.set_decorations(true);
+                let (width, height) = self.windowed_placement.size;
+                window.set_inner_size(LogicalSize::new(f64::from(width), f64::from(height)));
+                let (x, y) = self.windowed_placement.position;
+                window.set_position(LogicalPosition::new(f64::from(x), f64::from(y)));
+            },
+            WindowMode::Borderless => {
+                window.set_fullscreen(None);
+                window.set_decorations(false);
+                let factor = monitor.get_hidpi_factor();
+                window.set_position(monitor.get_position().to_logical(factor));
+                window.set_inner_size(monitor.get_dimensions().to_logical(factor));
+            },
+            WindowMode::Fullscreen(_) => {
+                window.set_fullscreen(Some(monitor));
+            }
+        }
+
+        self.window_mode = mode;
+        self.recreate_swap_chain = true;
+        self.display_events.push(DisplayEvent::ModeChanged(mode));
+        Ok(())
+    }
+
+    fn current_placement(window: &Window) -> WindowPlacement {
+        let position = window.get_position().unwrap_or_else(|| LogicalPosition::new(0.0, 0.0));
+        let size = window.get_inner_size().unwrap_or_else(|| LogicalSize::new(0.0, 0.0));
+        WindowPlacement {
+            position: (position.x as i32, position.y as i32),
+            size: (size.width as u32, size.height as u32)
+        }
+    }
+
+    /*********************************
+    *** SHADER FUNCTIONS
+    *********************************/
+
+    // replaces the shaders of a built-in pipeline with GLSL or SPIR-V files. they have to keep the
+    // vertex inputs, descriptor sets and push constants of the shaders they replace
+    pub fn load_shaders(&mut self, target: ShaderTarget, vertex_path: &Path, fragment_path: &Path) -> Result<(), String> {
+        let program = ShaderProgram::load(&self.device, vertex_path, fragment_path)?;
+        let pipeline = self.create_target_pipeline(target, Some(&program))?;
+        self.set_target_pipeline(target, pipeline);
+        *self.program_mut(target) = Some(program);
+        Ok(())
+    }
+
+    // goes back to the built-in shaders
+    pub fn unload_shaders(&mut self, target: ShaderTarget) {
+        *self.program_mut(target) = None;
+        let pipeline = self.create_target_pipeline(target, None).expect("failed to create graphics pipeline");
+        self.set_target_pipeline(target, pipeline);
+    }
+
+    // when enabled, render() checks the loaded shader files for changes every frame. shaders that fail to
+    // reload keep their previous pipeline and their errors are returned by poll_shader_errors
+    pub fn set_shader_hot_reload(&mut self, enabled: bool) {
+        self.shader_hot_reload = enabled;
+    }
+
+    // returns and clears the hot reload failures gathered since the last call
+    pub fn poll_shader_errors(&mut self) -> Vec<(ShaderTarget, String)> {
+        self.shader_errors.drain(..).collect()
+    }
+
+    // rebuilds the pipelines whose shader files changed on disk. if a shader fails to compile or
+    // doesn't fit the pipeline, the previous pipeline is kept and the error is returned
+    pub fn reload_changed_shaders(&mut self) -> Vec<(ShaderTarget, String)> {
+        let device = self.device.clone();
+        let mut errors = vec![];
+        for target in [ShaderTarget::Lit, ShaderTarget::Pbr].iter().cloned() {
+            let reloaded = match self.program_mut(target) {
+                Some(program) => {
+                    if !program.changed() {
+                        continue;
+                    }
+                    program.reload(&device)
+                },
+                None => continue
+            };
+
+            let pipeline = reloaded.and_then(|program| {
+                let pipeline = self.create_target_pipeline(target, Some(&program))?;
+                Ok((program, pipeline))
+            });
+            match pipeline {
+                Ok((program, pipeline)) => {
+                    self.set_target_pipeline(target, pipeline);
+                    *self.program_mut(target) = Some(program);
+                },
+                Err(e) => errors.push((target, e))
+            }
+        }
+
+        errors
+    }
+
+    fn program_mut(&mut self, target: ShaderTarget) -> &mut Option<ShaderProgram> {
+        match target {
+            ShaderTarget::Lit => &mut self.lit_program,
+            ShaderTarget::Pbr => &mut self.pbr_program
+        }
+    }
+
+    fn set_target_pipeline(&mut self, target: ShaderTarget, pipeline: Arc<GraphicsPipelineAbstract + Send + Sync>) {
+        match target {
+            ShaderTarget::Lit => self.graphics_pipeline = pipeline,
+            ShaderTarget::Pbr => self.pbr_pipeline = pipeline
+        }
+    }
+
+    // uses the built-in shaders when no program is given
+    fn create_target_pipeline(&self, target: ShaderTarget, program: Option<&ShaderProgram>)
+            -> Result<Arc<GraphicsPipelineAbstract + Send + Sync>, String> {
+        let extent = self.swap_chain.dimensions();
+        match (target, program) {
+            (ShaderTarget::Lit, Some(program)) => program.create_pipeline::<Vertex>(&self.device, extent, &self.render_pass),
+            (ShaderTarget::Pbr, Some(program)) => program.create_pipeline::<PbrVertex>(&self.device, extent, &self.render_pass),
+            (ShaderTarget::Lit, None) => Ok(Self::create_graphics_pipeline(&self.device, extent, &self.render_pass,
+                &self.fragment_shader, &self.vertex_shader)),
+            (ShaderTarget::Pbr, None) => Ok(Self::create_pbr_pipeline(&self.device, extent, &self.render_pass,
+                &self.pbr_fragment_shader, &self.pbr_vertex_shader))
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,347 @@
+use crate::math::vec3::Vec3;
+use crate::math::mat4::Mat4;
+use crate::math::ray3d::Ray3d;
+use crate::math::deg2rad;
+
+use std::mem;
+use std::sync::Mutex;
+
+// immediate mode debug shapes. everything drawn during a frame is turned into lines, which the
+// renderer takes and draws on top of the final image before presenting it. the free functions at
+// the bottom draw into one instance shared by every thread, so gameplay code doesn't need access to
+// the renderer
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DebugVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4]
+}
+impl_vertex!(DebugVertex, position, color);
+
+const CIRCLE_SEGMENTS: usize = 24;
+// glyphs are drawn on a 4 x 6 grid, with 2 units between characters and lines
+const GLYPH_WIDTH: f32 = 4.0;
+const GLYPH_HEIGHT: f32 = 6.0;
+const GLYPH_SPACING: f32 = 2.0;
+
+#[derive(Debug, Clone)]
+pub struct DebugDraw {
+    // line list vertices, two per line
+    pub depth_tested: Vec<DebugVertex>,
+    pub overlay: Vec<DebugVertex>,
+    depth_test: bool
+}
+
+impl Default for DebugDraw {
+    fn default() -> DebugDraw {
+        DebugDraw {
+            depth_tested: vec![],
+            overlay: vec![],
+            depth_test: true
+        }
+    }
+}
+
+impl DebugDraw {
+    pub fn new() -> DebugDraw {
+        DebugDraw::default()
+    }
+
+    // whether the shapes drawn after this call are hidden behind scene geometry or drawn over it
+    pub fn set_depth_test(&mut self, enabled: bool) {
+        self.depth_test = enabled;
+    }
+
+    pub fn depth_test(&self) -> bool {
+        self.depth_test
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.depth_tested.is_empty() && self.overlay.is_empty()
+    }
+
+    // hands out the lines drawn so far and starts over, the depth test setting is kept
+    pub fn take(&mut self) -> DebugDraw {
+        DebugDraw {
+            depth_tested: mem::replace(&mut self.depth_tested, vec![]),
+            overlay: mem::replace(&mut self.overlay, vec![]),
+            depth_test: self.depth_test
+        }
+    }
+
+    pub fn line(&mut self, from: &Vec3, to: &Vec3, color: &Vec3) {
+        let color = [color.x, color.y, color.z, 1.0];
+        let lines = if self.depth_test { &mut self.depth_tested } else { &mut self.overlay };
+        lines.push(DebugVertex { position: [from.x, from.y, from.z], color });
+        lines.push(DebugVertex { position: [to.x, to.y, to.z], color });
+    }
+
+    // from the ray origin to the end of its range
+    pub fn ray(&mut self, ray: &Ray3d, color: &Vec3) {
+        self.line(&ray.origin, &ray.end(), color);
+    }
+
+    pub fn aabb(&mut self, min: &Vec3, max: &Vec3, color: &Vec3) {
+        let corner = |x: bool, y: bool, z: bool| Vec3 {
+            x: if x { max.x } else { min.x },
+            y: if y { max.y } else { min.y },
+            z: if z { max.z } else { min.z }
+        };
+        for &(a, b) in [(false, false), (true, false), (true, true), (false, true)].iter() {
+            // edges along x, then along y, then along z
+            self.line(&corner(false, a, b), &corner(true, a, b), color);
+            self.line(&corner(a, false, b), &corner(a, true, b), color);
+            self.line(&corner(a, b, false), &corner(a, b, true), color);
+        }
+    }
+
+    // three great circles, one around each axis
+    pub fn sphere(&mut self, center: &Vec3, radius: f32, color: &Vec3) {
+        let x = Vec3 { x: radius, y: 0.0, z: 0.0 };
+        let y = Vec3 { x: 0.0, y: radius, z: 0.0 };
+        let z = Vec3 { x: 0.0, y: 0.0, z: radius };
+        self.circle(center, &x, &y, color);
+        self.circle(center, &y, &z, color);
+        self.circle(center, &z, &x, color);
+    }
+
+    // circle through center + u and center + v
+    pub fn circle(&mut self, center: &Vec3, u: &Vec3, v: &Vec3, color: &Vec3) {
+        let point = |segment: usize| {
+            let angle = deg2rad(360.0 * segment as f32 / CIRCLE_SEGMENTS as f32);
+            center.clone() + u.clone() * angle.cos() + v.clone() * angle.sin()
+        };
+        for segment in 0..CIRCLE_SEGMENTS {
+            self.line(&point(segment), &point(segment + 1), color);
+        }
+    }
+
+    // outline of the volume a view projection matrix maps to clip space, with depth from 0 to 1
+    pub fn frustum(&mut self, view_proj: &Mat4, color: &Vec3) {
+        let inverse = match view_proj.invert() {
+            Some(inverse) => inverse,
+            None => return
+        };
+        let corner = |x: f32, y: f32, z: f32| inverse.clone() * &Vec3 { x, y, z };
+        let corners = [
+            corner(-1.0, -1.0, 0.0), corner(1.0, -1.0, 0.0), corner(1.0, 1.0, 0.0), corner(-1.0, 1.0, 0.0),
+            corner(-1.0, -1.0, 1.0), corner(1.0, -1.0, 1.0), corner(1.0, 1.0, 1.0), corner(-1.0, 1.0, 1.0)
+        ];
+        for i in 0..4 {
+            let next = (i + 1) % 4;
+            self.line(&corners[i], &corners[next], color);
+            self.line(&corners[i + 4], &corners[next + 4], color);
+            self.line(&corners[i], &corners[i + 4], color);
+        }
+    }
+
+    // the x, y and z axes of a transform in red, green and blue
+    pub fn axes(&mut self, transform: &Mat4, size: f32) {
+        let origin = transform.clone() * &Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+        let axes = [
+            (Vec3 { x: size, y: 0.0, z: 0.0 }, Vec3 { x: 1.0, y: 0.0, z: 0.0 }),
+            (Vec3 { x: 0.0, y: size, z: 0.0 }, Vec3 { x: 0.0, y: 1.0, z: 0.0 }),
+            (Vec3 { x: 0.0, y: 0.0, z: size }, Vec3 { x: 0.0, y: 0.0, z: 1.0 })
+        ];
+        for (axis, color) in axes.iter() {
+            self.line(&origin, &(origin.clone() + transform.transform_vector(axis)), color);
+        }
+    }
+
+    // square grid in the xz plane, size wide and split into divisions cells along each side
+    pub fn grid(&mut self, center: &Vec3, size: f32, divisions: u32, color: &Vec3) {
+        let divisions = divisions.max(1);
+        let half = size / 2.0;
+        for i in 0..=divisions {
+            let offset = -half + size * i as f32 / divisions as f32;
+            self.line(&(center.clone() + Vec3 { x: offset, y: 0.0, z: -half }),
+                &(center.clone() + Vec3 { x: offset, y: 0.0, z: half }), color);
+            self.line(&(center.clone() + Vec3 { x: -half, y: 0.0, z: offset }),
+                &(center.clone() + Vec3 { x: half, y: 0.0, z: offset }), color);
+        }
+    }
+
+    // stroke font text in the xy plane, starting with the bottom left of the first character at
+    // position. size is the height of a capital letter, \n starts a new line below
+    pub fn text3d(&mut self, position: &Vec3, text: &str, size: f32, color: &Vec3) {
+        let unit = size / GLYPH_HEIGHT;
+        let mut cursor_x = 0.0;
+        let mut cursor_y = 0.0;
+        for c in text.chars() {
+            if c == '\n' {
+                cursor_x = 0.0;
+                cursor_y -= GLYPH_HEIGHT + GLYPH_SPACING;
+                continue;
+            }
+
+            for stroke in glyph(c).iter() {
+                let points: Vec<Vec3> = stroke.split(' ')
+                    .map(|point| {
+                        let point = point.as_bytes();
+                        let x = f32::from(point[0] - b'0');
+                        let y = f32::from(point[1] - b'0');
+                        position.clone() + Vec3 { x: (cursor_x + x) * unit, y: (cursor_y + y) * unit, z: 0.0 }
+                    })
+                    .collect();
+                for pair in points.windows(2) {
+                    self.line(&pair[0], &pair[1], color);
+                }
+            }
+            cursor_x += GLYPH_WIDTH + GLYPH_SPACING;
+        }
+    }
+}
+
+// strokes of a character as polylines of grid points, each point an x and a y digit. lowercase
+// letters use the uppercase glyphs, characters without a glyph are drawn as a box
+fn glyph(c: char) -> &'static [&'static str] {
+    match c.to_ascii_uppercase() {
+        ' ' => &[],
+        '0' => &["10 01 05 16 36 45 41 30 10", "01 45"],
+        '1' => &["15 26 20", "10 30"],
+        '2' => &["05 16 36 45 44 00 40"],
+        '3' => &["05 16 36 45 44 33 13", "33 42 41 30 10 01"],
+        '4' => &["36 03 43", "36 30"],
+        '5' => &["46 06 03 33 42 41 30 00"],
+        '6' => &["36 16 05 01 10 30 41 42 33 03"],
+        '7' => &["06 46 20"],
+        '8' => &["13 04 05 16 36 45 44 33 13 02 01 10 30 41 42 33"],
+        '9' => &["43 13 04 05 16 36 45 41 30 10"],
+        'A' => &["00 04 26 44 40", "03 43"],
+        'B' => &["00 06 36 45 44 33 03", "33 42 41 30 00"],
+        'C' => &["45 36 16 05 01 10 30 41"],
+        'D' => &["00 06 26 44 42 20 00"],
+        'E' => &["46 06 00 40", "03 33"],
+        'F' => &["46 06 00", "03 33"],
+        'G' => &["45 36 16 05 01 10 30 41 43 23"],
+        'H' => &["00 06", "40 46", "03 43"],
+        'I' => &["06 46", "26 20", "00 40"],
+        'J' => &["46 41 30 10 01"],
+        'K' => &["00 06", "46 03 40"],
+        'L' => &["06 00 40"],
+        'M' => &["00 06 23 46 40"],
+        'N' => &["00 06 40 46"],
+        'O' => &["10 01 05 16 36 45 41 30 10"],
+        'P' => &["00 06 36 45 44 33 03"],
+        'Q' => &["10 01 05 16 36 45 41 30 10", "22 40"],
+        'R' => &["00 06 36 45 44 33 03", "23 40"],
+        'S' => &["45 36 16 05 04 13 33 42 41 30 10 01"],
+        'T' => &["06 46", "26 20"],
+        'U' => &["06 01 10 30 41 46"],
+        'V' => &["06 20 46"],
+        'W' => &["06 10 23 30 46"],
+        'X' => &["06 40", "00 46"],
+        'Y' => &["06 23 46", "23 20"],
+        'Z' => &["06 46 00 40"],
+        '.' => &["20 21"],
+        ',' => &["21 10"],
+        ':' => &["21 22", "24 25"],
+        ';' => &["21 10", "24 25"],
+        '-' => &["03 43"],
+        '+' => &["13 33", "22 24"],
+        '=' => &["02 42", "04 44"],
+        '*' => &["13 33", "12 34", "14 32"],
+        '/' => &["00 46"],
+        '_' => &["00 40"],
+        '(' => &["36 24 22 30"],
+        ')' => &["16 24 22 10"],
+        '[' => &["36 16 10 30"],
+        ']' => &["16 36 30 10"],
+        '<' => &["45 03 41"],
+        '>' => &["05 43 01"],
+        '!' => &["26 22", "20 21"],
+        '?' => &["05 16 36 45 44 23 22", "20 21"],
+        '%' => &["00 46", "05 06 16 15 05", "31 41 40 30 31"],
+        '\'' => &["26 25"],
+        '"' => &["16 15", "36 35"],
+        _ => &["00 40 46 06 00"]
+    }
+}
+
+static DEBUG_DRAW: Mutex<DebugDraw> = Mutex::new(DebugDraw {
+    depth_tested: Vec::new(),
+    overlay: Vec::new(),
+    depth_test: true
+});
+
+// runs f on the shared debug draw, for drawing several shapes with one lock. the depth test setting
+// is shared too, so threads changing it should draw through one call of this
+pub fn with<F: FnOnce(&mut DebugDraw) -> R, R>(f: F) -> R {
+    // a thread that panicked while drawing leaves at worst a few extra lines
+    let mut draw = DEBUG_DRAW.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut draw)
+}
+
+// the lines drawn on any thread since the last call, used by the renderer once per frame
+pub fn take() -> DebugDraw {
+    with(|draw| draw.take())
+}
+
+pub fn set_depth_test(enabled: bool) {
+    with(|draw| draw.set_depth_test(enabled))
+}
+
+pub fn line(from: &Vec3, to: &Vec3, color: &Vec3) {
+    with(|draw| draw.line(from, to, color))
+}
+
+pub fn ray(ray: &Ray3d, color: &Vec3) {
+    with(|draw| draw.ray(ray, color))
+}
+
+pub fn aabb(min: &Vec3, max: &Vec3, color: &Vec3) {
+    with(|draw| draw.aabb(min, max, color))
+}
+
+pub fn sphere(center: &Vec3, radius: f32, color: &Vec3) {
+    with(|draw| draw.sphere(center, radius, color))
+}
+
+pub fn frustum(view_proj: &Mat4, color: &Vec3) {
+    with(|draw| draw.frustum(view_proj, color))
+}
+
+pub fn axes(transform: &Mat4, size: f32) {
+    with(|draw| draw.axes(transform, size))
+}
+
+pub fn grid(center: &Vec3, size: f32, divisions: u32, color: &Vec3) {
+    with(|draw| draw.grid(center, size, divisions, color))
+}
+
+pub fn text3d(position: &Vec3, text: &str, size: f32, color: &Vec3) {
+    with(|draw| draw.text3d(position, text, size, color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn lines_from_other_threads_are_taken() {
+        let red = Vec3 { x: 1.0, y: 0.0, z: 0.0 };
+        let from = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+        let to = Vec3 { x: 0.0, y: 7.0, z: 0.0 };
+        thread::spawn(move || line(&from, &to, &red)).join().expect("failed to draw on another thread");
+
+        // other tests may draw into the shared instance at the same time
+        let lines = take();
+        assert!(lines.depth_tested.chunks(2).any(|pair| pair[1].position == [0.0, 7.0, 0.0]));
+    }
+
+    #[test]
+    fn depth_test_picks_the_list() {
+        let mut draw = DebugDraw::new();
+        let white = Vec3 { x: 1.0, y: 1.0, z: 1.0 };
+        draw.line(&Vec3 { x: 0.0, y: 0.0, z: 0.0 }, &white, &white);
+        draw.set_depth_test(false);
+        draw.aabb(&Vec3 { x: 0.0, y: 0.0, z: 0.0 }, &white, &white);
+        assert_eq!(draw.depth_tested.len(), 2);
+        assert_eq!(draw.overlay.len(), 24);
+
+        let taken = draw.take();
+        assert!(draw.is_empty() && !draw.depth_test());
+        assert_eq!(taken.overlay.len(), 24);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,178 @@
+use super::debug_draw::{DebugDraw, DebugVertex};
+use crate::math::mat4::Mat4;
+
+use std::sync::Arc;
+
+use winit::Window;
+
+use vulkano::device::Device;
+use vulkano::format::{Format, ClearValue};
+use vulkano::image::{AttachmentImage, swapchain::SwapchainImage};
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract, viewport::Viewport};
+use vulkano::pipeline::depth_stencil::{DepthStencil, Compare};
+use vulkano::pipeline::vertex::SingleBufferDefinition;
+use vulkano::framebuffer::{RenderPassAbstract, Subpass, FramebufferAbstract, Framebuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+
+// debug lines go through the same camera as the lit meshes, so they line up with the scene
+mod vs {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        src: "
+        #version 450
+        #extension GL_ARB_separate_shader_objects : enable
+
+        layout(location = 0) in vec3 position;
+        layout(location = 1) in vec4 color;
+
+        layout(location = 0) out vec4 line_color;
+
+        layout(push_constant) uniform Camera {
+            mat4 view_proj;
+        } camera;
+
+        void main() {
+            vec4 clip = camera.view_proj * vec4(position, 1.0);
+            gl_Position = vec4(clip.x, -clip.y, clip.z, clip.w);
+            line_color = color;
+        }"
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        src: "
+        #version 450
+        #extension GL_ARB_separate_shader_objects : enable
+
+        layout(location = 0) in vec4 line_color;
+        layout(location = 0) out vec4 f_color;
+
+        void main() {
+            f_color = line_color;
+        }"
+    }
+}
+
+// draws the debug lines of a frame over the presented image. the scene depth is kept from the main
+// pass so lines can be hidden behind geometry
+pub struct DebugRenderer {
+    vertex_shader: vs::Shader,
+    fragment_shader: fs::Shader,
+    render_pass: Arc<RenderPassAbstract + Send + Sync>,
+    depth_tested_pipeline: Arc<GraphicsPipelineAbstract + Send + Sync>,
+    overlay_pipeline: Arc<GraphicsPipelineAbstract + Send + Sync>
+}
+
+impl DebugRenderer {
+    pub fn new(device: &Arc<Device>, color_format: Format, depth_format: Format) -> DebugRenderer {
+        let vertex_shader = vs::Shader::load(device.clone()).expect("failed to create shader module");
+        let fragment_shader = fs::Shader::load(device.clone()).expect("failed to create shader module");
+
+        let render_pass: Arc<RenderPassAbstract + Send + Sync> = Arc::new(single_pass_renderpass!(device.clone(),
+            attachments: {
+                color: {
+                    load: Load,
+                    store: Store,
+                    format: color_format,
+                    samples: 1,
+                },
+                depth: {
+                    load: Load,
+                    store: DontCare,
+                    format: depth_format,
+                    samples: 1,
+                }
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth}
+            }
+        ).unwrap());
+
+        // depth tested lines don't write depth, lines in front of the scene never hide each other
+        let depth_tested = DepthStencil {
+            depth_compare: Compare::LessOrEqual,
+            depth_write: false,
+            .. DepthStencil::disabled()
+        };
+        let depth_tested_pipeline = Self::create_pipeline(device, &render_pass, &vertex_shader, &fragment_shader,
+            depth_tested);
+        let overlay_pipeline = Self::create_pipeline(device, &render_pass, &vertex_shader, &fragment_shader,
+            DepthStencil::disabled());
+
+        DebugRenderer {
+            vertex_shader,
+            fragment_shader,
+            render_pass,
+            depth_tested_pipeline,
+            overlay_pipeline
+        }
+    }
+
+    fn create_pipeline(
+        device: &Arc<Device>,
+        render_pass: &Arc<RenderPassAbstract + Send + Sync>,
+        vert_shader_module: &vs::Shader,
+        frag_shader_module: &fs::Shader,
+        depth_stencil: DepthStencil
+    ) -> Arc<GraphicsPipelineAbstract + Send + Sync> {
+        Arc::new(GraphicsPipeline::start()
+            .vertex_input(SingleBufferDefinition::<DebugVertex>::new())
+            .vertex_shader(vert_shader_module.main_entry_point(), ())
+            .line_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .fragment_shader(frag_shader_module.main_entry_point(), ())
+            .depth_stencil(depth_stencil)
+            .blend_alpha_blending()
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .unwrap())
+    }
+
+    pub fn record(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        device: &Arc<Device>,
+        target: &Arc<SwapchainImage<Window>>,
+        depth: &Arc<AttachmentImage<Format>>,
+        lines: &DebugDraw,
+        view_proj: &Mat4
+    ) -> AutoCommandBufferBuilder {
+        if lines.is_empty() {
+            return builder;
+        }
+
+        let dimensions = target.dimensions();
+        let framebuffer: Arc<FramebufferAbstract + Send + Sync> = Arc::new(Framebuffer::start(self.render_pass.clone())
+            .add(target.clone()).unwrap()
+            .add(depth.clone()).unwrap()
+            .build().unwrap());
+        let dynamic_state = DynamicState {
+            line_width: None,
+            viewports: Some(vec![Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+                depth_range: 0.0 .. 1.0,
+            }]),
+            scissors: None
+        };
+
+        let mut builder = builder
+            .begin_render_pass(framebuffer, false, vec![ClearValue::None, ClearValue::None])
+            .unwrap();
+        let constants = vs::ty::Camera {
+            view_proj: view_proj.to_gpu()
+        };
+        let batches = [(&lines.depth_tested, &self.depth_tested_pipeline), (&lines.overlay, &self.overlay_pipeline)];
+        for (vertices, pipeline) in batches.iter().filter(|(vertices, _)| !vertices.is_empty()) {
+            let vertex_buffer = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::vertex_buffer(),
+                vertices.iter().cloned()).unwrap();
+            builder = builder.draw((*pipeline).clone(), &dynamic_state, vertex_buffer, (), constants)
+                .unwrap();
+        }
+        builder.end_render_pass().unwrap()
+    }
+}
\ No newline at end of file
@@ -0,0 +1,388 @@
+use crate::math::vec3::Vec3;
+
+use std::f32::consts::PI;
+
+// offline image based lighting precomputation: irradiance convolution,
+// GGX prefiltered environment levels and the split-sum BRDF lookup table.
+// everything here runs on the cpu so the baked maps can be checked without a gpu
+
+pub struct IblSettings {
+    pub irradiance_size: u32,
+    pub irradiance_samples: u32,
+    pub prefiltered_size: u32,
+    pub prefiltered_levels: u32,
+    pub prefiltered_samples: u32,
+    pub brdf_lut_size: u32,
+    pub brdf_lut_samples: u32
+}
+
+impl Default for IblSettings {
+    fn default() -> IblSettings {
+        IblSettings {
+            irradiance_size: 32,
+            irradiance_samples: 512,
+            prefiltered_size: 128,
+            prefiltered_levels: 5,
+            prefiltered_samples: 256,
+            brdf_lut_size: 128,
+            brdf_lut_samples: 512
+        }
+    }
+}
+
+pub struct IblMaps {
+    pub irradiance: Cubemap,
+    // one cubemap per mip level, roughness goes linearly from 0 to 1 across levels
+    pub prefiltered: Vec<Cubemap>,
+    pub brdf_lut: BrdfLut
+}
+
+pub fn bake(environment: &Cubemap, settings: &IblSettings) -> IblMaps {
+    IblMaps {
+        irradiance: convolve_irradiance(environment, settings.irradiance_size, settings.irradiance_samples),
+        prefiltered: prefilter_environment(environment, settings.prefiltered_size,
+            settings.prefiltered_levels, settings.prefiltered_samples),
+        brdf_lut: generate_brdf_lut(settings.brdf_lut_size, settings.brdf_lut_samples)
+    }
+}
+
+/*********************************
+*** CUBEMAPS
+*********************************/
+
+// faces in vulkan order: +x, -x, +y, -y, +z, -z
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cubemap {
+    pub size: u32,
+    pub faces: Vec<Vec<Vec3>>
+}
+
+impl Cubemap {
+    pub fn new(size: u32) -> Cubemap {
+        let texels = (size * size) as usize;
+        Cubemap {
+            size,
+            faces: (0..6).map(|_| vec![Vec3 { x: 0.0, y: 0.0, z: 0.0 }; texels]).collect()
+        }
+    }
+
+    // builds a cubemap by evaluating f at the direction of every texel center
+    pub fn from_fn<F: Fn(&Vec3) -> Vec3>(size: u32, f: F) -> Cubemap {
+        let mut ret = Cubemap::new(size);
+        for face in 0..6 {
+            for y in 0..size {
+                for x in 0..size {
+                    let dir = ret.texel_direction(face, x, y);
+                    ret.faces[face][(y * size + x) as usize] = f(&dir);
+                }
+            }
+        }
+
+        ret
+    }
+
+    pub fn texel_direction(&self, face: usize, x: u32, y: u32) -> Vec3 {
+        let sc = 2.0 * (x as f32 + 0.5) / self.size as f32 - 1.0;
+        let tc = 2.0 * (y as f32 + 0.5) / self.size as f32 - 1.0;
+        face_direction(face, sc, tc)
+    }
+
+    // bilinear lookup inside the face the direction points at
+    pub fn sample(&self, dir: &Vec3) -> Vec3 {
+        let (face, u, v) = direction_to_face_uv(dir);
+        let fx = (u * self.size as f32 - 0.5).max(0.0).min(self.size as f32 - 1.0);
+        let fy = (v * self.size as f32 - 0.5).max(0.0).min(self.size as f32 - 1.0);
+        let x0 = fx.floor() as u32;
+        let y0 = fy.floor() as u32;
+        let x1 = (x0 + 1).min(self.size - 1);
+        let y1 = (y0 + 1).min(self.size - 1);
+        let tx = fx - x0 as f32;
+        let ty = fy - y0 as f32;
+
+        let texel = |x: u32, y: u32| self.faces[face][(y * self.size + x) as usize].clone();
+        let top = texel(x0, y0) * (1.0 - tx) + texel(x1, y0) * tx;
+        let bottom = texel(x0, y1) * (1.0 - tx) + texel(x1, y1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+
+    // texels face by face as rgba, the layout expected by a cubemap image upload
+    pub fn texels_rgba(&self) -> Vec<[f32; 4]> {
+        self.faces.iter()
+            .flat_map(|face| face.iter().map(|t| [t.x, t.y, t.z, 1.0]))
+            .collect()
+    }
+}
+
+fn face_direction(face: usize, sc: f32, tc: f32) -> Vec3 {
+    let dir = match face {
+        0 => Vec3 { x: 1.0, y: -tc, z: -sc },
+        1 => Vec3 { x: -1.0, y: -tc, z: sc },
+        2 => Vec3 { x: sc, y: 1.0, z: tc },
+        3 => Vec3 { x: sc, y: -1.0, z: -tc },
+        4 => Vec3 { x: sc, y: -tc, z: 1.0 },
+        5 => Vec3 { x: -sc, y: -tc, z: -1.0 },
+        _ => panic!("cubemap face index out of range: {}", face)
+    };
+    dir.normalized()
+}
+
+// returns the face index and uv in [0, 1] for a direction
+fn direction_to_face_uv(dir: &Vec3) -> (usize, f32, f32) {
+    let (ax, ay, az) = (dir.x.abs(), dir.y.abs(), dir.z.abs());
+    let (face, sc, tc, ma) = if ax >= ay && ax >= az {
+        if dir.x > 0.0 { (0, -dir.z, -dir.y, ax) } else { (1, dir.z, -dir.y, ax) }
+    } else if ay >= az {
+        if dir.y > 0.0 { (2, dir.x, dir.z, ay) } else { (3, dir.x, -dir.z, ay) }
+    } else {
+        if dir.z > 0.0 { (4, dir.x, -dir.y, az) } else { (5, -dir.x, -dir.y, az) }
+    };
+
+    (face, (sc / ma + 1.0) * 0.5, (tc / ma + 1.0) * 0.5)
+}
+
+/*********************************
+*** SAMPLING AND BRDF TERMS
+*********************************/
+
+pub fn radical_inverse_vdc(bits: u32) -> f32 {
+    bits.reverse_bits() as f32 * 2.328_306_4e-10 // / 2^32
+}
+
+pub fn hammersley(i: u32, n: u32) -> (f32, f32) {
+    (i as f32 / n as f32, radical_inverse_vdc(i))
+}
+
+// orthonormal tangent and bitangent around n
+fn tangent_basis(n: &Vec3) -> (Vec3, Vec3) {
+    let up = if n.z.abs() < 0.999 {
+        Vec3 { x: 0.0, y: 0.0, z: 1.0 }
+    } else {
+        Vec3 { x: 1.0, y: 0.0, z: 0.0 }
+    };
+    let tangent = up.cross(n.clone()).normalized();
+    let bitangent = n.cross(tangent.clone());
+    (tangent, bitangent)
+}
+
+fn to_world(local: &Vec3, n: &Vec3) -> Vec3 {
+    let (tangent, bitangent) = tangent_basis(n);
+    (tangent * local.x + bitangent * local.y + n.clone() * local.z).normalized()
+}
+
+// half vector distributed according to GGX around n
+pub fn importance_sample_ggx(xi: (f32, f32), n: &Vec3, roughness: f32) -> Vec3 {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.0;
+    let cos_theta = ((1.0 - xi.1) / (1.0 + (a * a - 1.0) * xi.1)).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+    let h = Vec3 { x: phi.cos() * sin_theta, y: phi.sin() * sin_theta, z: cos_theta };
+    to_world(&h, n)
+}
+
+pub fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+// k is remapped differently for direct and image based lighting
+pub fn geometry_schlick_ggx(n_dot_v: f32, k: f32) -> f32 {
+    n_dot_v / (n_dot_v * (1.0 - k) + k)
+}
+
+pub fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32, image_based: bool) -> f32 {
+    let k = if image_based {
+        roughness * roughness / 2.0
+    } else {
+        (roughness + 1.0) * (roughness + 1.0) / 8.0
+    };
+    geometry_schlick_ggx(n_dot_v, k) * geometry_schlick_ggx(n_dot_l, k)
+}
+
+pub fn fresnel_schlick(cos_theta: f32, f0: Vec3) -> Vec3 {
+    let factor = (1.0 - cos_theta).max(0.0).powi(5);
+    f0.clone() + (Vec3 { x: 1.0, y: 1.0, z: 1.0 } - f0) * factor
+}
+
+// full Cook-Torrance specular + lambert diffuse for a single light direction,
+// mirrors the math in the pbr fragment shader
+pub fn cook_torrance(n: &Vec3, v: &Vec3, l: &Vec3, base_color: &Vec3, metallic: f32, roughness: f32) -> Vec3 {
+    let h = (v.clone() + l.clone()).normalized();
+    let n_dot_v = n.dot(v.clone()).max(0.0001);
+    let n_dot_l = n.dot(l.clone()).max(0.0);
+    let n_dot_h = n.dot(h.clone()).max(0.0);
+    let h_dot_v = h.dot(v.clone()).max(0.0);
+
+    let dielectric = Vec3 { x: 0.04, y: 0.04, z: 0.04 };
+    let f0 = dielectric * (1.0 - metallic) + base_color.clone() * metallic;
+    let f = fresnel_schlick(h_dot_v, f0);
+    let specular = f.clone() * (distribution_ggx(n_dot_h, roughness)
+        * geometry_smith(n_dot_v, n_dot_l, roughness, false) / (4.0 * n_dot_v * n_dot_l).max(0.0001));
+    let k_d = (Vec3 { x: 1.0, y: 1.0, z: 1.0 } - f) * (1.0 - metallic);
+    (k_d * base_color.clone() / PI + specular) * n_dot_l
+}
+
+/*********************************
+*** BRDF LUT
+*********************************/
+
+// x axis is n dot v, y axis is roughness; values are the scale and bias applied to f0
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrdfLut {
+    pub size: u32,
+    pub data: Vec<[f32; 2]>
+}
+
+impl BrdfLut {
+    pub fn get(&self, x: u32, y: u32) -> [f32; 2] {
+        self.data[(y * self.size + x) as usize]
+    }
+
+    // nearest lookup, matching texel centers
+    pub fn sample(&self, n_dot_v: f32, roughness: f32) -> [f32; 2] {
+        let to_texel = |t: f32| ((t * self.size as f32 - 0.5).round().max(0.0) as u32).min(self.size - 1);
+        self.get(to_texel(n_dot_v), to_texel(roughness))
+    }
+}
+
+pub fn integrate_brdf(n_dot_v: f32, roughness: f32, samples: u32) -> [f32; 2] {
+    let v = Vec3 { x: (1.0 - n_dot_v * n_dot_v).sqrt(), y: 0.0, z: n_dot_v };
+    let n = Vec3 { x: 0.0, y: 0.0, z: 1.0 };
+
+    let mut a = 0.0;
+    let mut b = 0.0;
+    for i in 0..samples {
+        let h = importance_sample_ggx(hammersley(i, samples), &n, roughness);
+        let v_dot_h = v.dot(h.clone());
+        let l = h.clone() * (2.0 * v_dot_h) - v.clone();
+
+        let n_dot_l = l.z.max(0.0);
+        let n_dot_h = h.z.max(0.0);
+        let v_dot_h = v_dot_h.max(0.0);
+        if n_dot_l > 0.0 {
+            let g = geometry_smith(n_dot_v, n_dot_l, roughness, true);
+            let g_vis = g * v_dot_h / (n_dot_h * n_dot_v);
+            let fc = (1.0 - v_dot_h).powi(5);
+            a += (1.0 - fc) * g_vis;
+            b += fc * g_vis;
+        }
+    }
+
+    [a / samples as f32, b / samples as f32]
+}
+
+pub fn generate_brdf_lut(size: u32, samples: u32) -> BrdfLut {
+    let mut data = Vec::with_capacity((size * size) as usize);
+    for y in 0..size {
+        let roughness = (y as f32 + 0.5) / size as f32;
+        for x in 0..size {
+            let n_dot_v = (x as f32 + 0.5) / size as f32;
+            data.push(integrate_brdf(n_dot_v, roughness, samples));
+        }
+    }
+
+    BrdfLut { size, data }
+}
+
+/*********************************
+*** ENVIRONMENT CONVOLUTION
+*********************************/
+
+// cosine weighted hemisphere integral of the environment, used for diffuse ibl
+pub fn convolve_irradiance(environment: &Cubemap, size: u32, samples: u32) -> Cubemap {
+    Cubemap::from_fn(size, |n| {
+        let mut irradiance = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+        for i in 0..samples {
+            let (u, v) = hammersley(i, samples);
+            // cosine weighted sampling cancels the cos / pdf term
+            let r = u.sqrt();
+            let phi = 2.0 * PI * v;
+            let local = Vec3 { x: r * phi.cos(), y: r * phi.sin(), z: (1.0 - u).max(0.0).sqrt() };
+            irradiance += environment.sample(&to_world(&local, n));
+        }
+        irradiance / samples as f32
+    })
+}
+
+// GGX prefiltered specular radiance, one cubemap per roughness level.
+// uses the usual n = v = r assumption of the split-sum approximation
+pub fn prefilter_environment(environment: &Cubemap, base_size: u32, levels: u32, samples: u32) -> Vec<Cubemap> {
+    (0..levels).map(|level| {
+        let size = (base_size >> level).max(1);
+        let roughness = if levels > 1 { level as f32 / (levels - 1) as f32 } else { 0.0 };
+        if roughness == 0.0 {
+            return Cubemap::from_fn(size, |dir| environment.sample(dir));
+        }
+
+        Cubemap::from_fn(size, |n| {
+            let mut color = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+            let mut total_weight = 0.0;
+            for i in 0..samples {
+                let h = importance_sample_ggx(hammersley(i, samples), n, roughness);
+                let l = h.clone() * (2.0 * n.dot(h.clone())) - n.clone();
+                let n_dot_l = n.dot(l.clone());
+                if n_dot_l > 0.0 {
+                    color += environment.sample(&l) * n_dot_l;
+                    total_weight += n_dot_l;
+                }
+            }
+            if total_weight > 0.0 { color / total_weight } else { color }
+        })
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: &Vec3, b: &Vec3, epsilon: f32) -> bool {
+        (a.x - b.x).abs() < epsilon && (a.y - b.y).abs() < epsilon && (a.z - b.z).abs() < epsilon
+    }
+
+    #[test]
+    fn brdf_lut_stays_in_range() {
+        let lut = generate_brdf_lut(16, 128);
+        for value in lut.data.iter() {
+            assert!(value[0] >= 0.0 && value[1] >= 0.0, "{:?}", value);
+            assert!(value[0] + value[1] <= 1.01, "{:?}", value);
+        }
+        // smooth surfaces seen head on reflect f0 as it is
+        let smooth = integrate_brdf(1.0, 0.05, 256);
+        assert!((smooth[0] - 1.0).abs() < 0.05 && smooth[1] < 0.05, "{:?}", smooth);
+    }
+
+    #[test]
+    fn constant_environment_stays_constant() {
+        let color = Vec3 { x: 0.2, y: 0.5, z: 1.0 };
+        let environment = Cubemap::from_fn(8, |_| color.clone());
+        let maps = bake(&environment, &IblSettings {
+            irradiance_size: 4,
+            irradiance_samples: 64,
+            prefiltered_size: 8,
+            prefiltered_levels: 4,
+            prefiltered_samples: 64,
+            brdf_lut_size: 4,
+            brdf_lut_samples: 16
+        });
+        for face in maps.irradiance.faces.iter() {
+            assert!(face.iter().all(|texel| close(texel, &color, 1e-4)));
+        }
+        assert_eq!(maps.prefiltered.len(), 4);
+        for (level, cubemap) in maps.prefiltered.iter().enumerate() {
+            assert_eq!(cubemap.size, (8 >> level).max(1));
+            for face in cubemap.faces.iter() {
+                assert!(face.iter().all(|texel| close(texel, &color, 1e-4)));
+            }
+        }
+    }
+
+    #[test]
+    fn sample_finds_the_face() {
+        let cubemap = Cubemap::from_fn(4, |dir| dir.clone());
+        let dir = Vec3 { x: 0.0, y: 0.0, z: 1.0 };
+        assert!(cubemap.sample(&dir).z > 0.9);
+        let dir = Vec3 { x: -1.0, y: 0.0, z: 0.0 };
+        assert!(cubemap.sample(&dir).x < -0.9);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,224 @@
+use super::material::Material;
+use crate::math::mat4::Mat4;
+use crate::math::vec3::Vec3;
+
+// per instance vertex attributes, read from the second vertex buffer binding. the model matrix is
+// split into its columns since vertex inputs can't be matrices
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct InstanceData {
+    pub model_x: [f32; 4],
+    pub model_y: [f32; 4],
+    pub model_z: [f32; 4],
+    pub model_w: [f32; 4],
+    // multiplies the diffuse color of the material
+    pub color: [f32; 4]
+}
+impl_vertex!(InstanceData, model_x, model_y, model_z, model_w, color);
+
+impl InstanceData {
+    pub fn new(transform: &Mat4, color: &Vec3) -> InstanceData {
+        let columns = transform.to_gpu();
+        InstanceData {
+            model_x: columns[0],
+            model_y: columns[1],
+            model_z: columns[2],
+            model_w: columns[3],
+            color: [color.x, color.y, color.z, 1.0]
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InstanceId(usize);
+
+// instances sharing a mesh and a material, drawn with a single call
+#[derive(Debug, Clone)]
+pub struct InstanceBatch {
+    // index of the registered instanced mesh
+    pub mesh: usize,
+    pub material: Material,
+    pub instances: Vec<InstanceData>,
+    // owner of each entry in instances
+    ids: Vec<InstanceId>
+}
+
+// instances one batch takes by default, the instance buffer of a full batch is 5 MiB
+pub const MAX_BATCH_INSTANCES: usize = 65536;
+
+// groups instances by mesh and material as they're added. removing an instance moves the last one
+// of its batch into its slot, so the instance data stays packed for upload
+#[derive(Debug, Clone)]
+pub struct InstanceBatches {
+    // a full batch is followed by another of the same mesh and material, drawn with a call of its own
+    pub max_batch_size: usize,
+    batches: Vec<InstanceBatch>,
+    // batch and slot of every instance ever added, None once removed
+    locations: Vec<Option<(usize, usize)>>
+}
+
+impl InstanceBatches {
+    pub fn new() -> InstanceBatches {
+        InstanceBatches {
+            max_batch_size: MAX_BATCH_INSTANCES,
+            batches: vec![],
+            locations: vec![]
+        }
+    }
+
+    pub fn add(&mut self, mesh: usize, material: &Material, instance: InstanceData) -> InstanceId {
+        let max_batch_size = self.max_batch_size.max(1);
+        let batch_index = match self.batches.iter().position(|batch| {
+            batch.mesh == mesh && batch.material == *material && batch.instances.len() < max_batch_size
+        }) {
+            Some(index) => index,
+            None => {
+                self.batches.push(InstanceBatch {
+                    mesh,
+                    material: material.clone(),
+                    instances: vec![],
+                    ids: vec![]
+                });
+                self.batches.len() - 1
+            }
+        };
+
+        let id = InstanceId(self.locations.len());
+        let batch = &mut self.batches[batch_index];
+        self.locations.push(Some((batch_index, batch.instances.len())));
+        batch.instances.push(instance);
+        batch.ids.push(id);
+        id
+    }
+
+    pub fn get(&self, id: InstanceId) -> Option<&InstanceData> {
+        self.location(id).map(|(batch, slot)| &self.batches[batch].instances[slot])
+    }
+
+    // returns false if the instance was removed
+    pub fn set(&mut self, id: InstanceId, instance: InstanceData) -> bool {
+        match self.location(id) {
+            Some((batch, slot)) => {
+                self.batches[batch].instances[slot] = instance;
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn remove(&mut self, id: InstanceId) -> Option<InstanceData> {
+        let (batch_index, slot) = self.location(id)?;
+        self.locations[id.0] = None;
+
+        let batch = &mut self.batches[batch_index];
+        let instance = batch.instances.swap_remove(slot);
+        batch.ids.swap_remove(slot);
+        if let Some(moved) = batch.ids.get(slot) {
+            self.locations[moved.0] = Some((batch_index, slot));
+        }
+        Some(instance)
+    }
+
+    // empty batches are kept around for the next instance of their mesh and material
+    pub fn batches(&self) -> &[InstanceBatch] {
+        &self.batches
+    }
+
+    pub fn instance_count(&self) -> usize {
+        self.batches.iter().map(|batch| batch.instances.len()).sum()
+    }
+
+    fn location(&self, id: InstanceId) -> Option<(usize, usize)> {
+        self.locations.get(id.0).cloned().unwrap_or(None)
+    }
+}
+
+impl Default for InstanceBatches {
+    fn default() -> InstanceBatches {
+        InstanceBatches::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    // an instance told apart by its red
+    fn instance(red: f32) -> InstanceData {
+        InstanceData::new(&Mat4::identity(), &vec3(red, 0.0, 0.0))
+    }
+
+    fn red() -> Material {
+        Material::new(vec3(1.0, 0.0, 0.0), vec3(0.5, 0.5, 0.5), 32.0, 1.0)
+    }
+
+    fn reds(batch: &InstanceBatch) -> Vec<f32> {
+        batch.instances.iter().map(|instance| instance.color[0]).collect()
+    }
+
+    #[test]
+    fn instances_are_split_into_columns() {
+        let data = instance(0.5);
+        assert_eq!(data.model_x, [1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(data.model_w, [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(data.color, [0.5, 0.0, 0.0, 1.0]);
+        assert_eq!(std::mem::size_of::<InstanceData>(), 80);
+    }
+
+    #[test]
+    fn instances_batch_by_mesh_and_material() {
+        let mut batches = InstanceBatches::new();
+        batches.add(0, &Material::default(), instance(0.0));
+        batches.add(1, &Material::default(), instance(1.0));
+        batches.add(0, &red(), instance(2.0));
+        batches.add(0, &Material::default(), instance(3.0));
+        batches.add(1, &Material::default(), instance(4.0));
+
+        let batches = batches.batches();
+        assert_eq!(batches.len(), 3);
+        assert_eq!((batches[0].mesh, &batches[0].material), (0, &Material::default()));
+        assert_eq!(reds(&batches[0]), vec![0.0, 3.0]);
+        assert_eq!((batches[1].mesh, &batches[1].material), (1, &Material::default()));
+        assert_eq!(reds(&batches[1]), vec![1.0, 4.0]);
+        assert_eq!((batches[2].mesh, &batches[2].material), (0, &red()));
+        assert_eq!(reds(&batches[2]), vec![2.0]);
+    }
+
+    #[test]
+    fn full_batches_continue_in_another() {
+        let mut batches = InstanceBatches::new();
+        assert_eq!(batches.max_batch_size, MAX_BATCH_INSTANCES);
+        batches.max_batch_size = 2;
+        let ids: Vec<InstanceId> = (0..5).map(|i| batches.add(0, &red(), instance(i as f32))).collect();
+        let sizes: Vec<usize> = batches.batches().iter().map(|batch| batch.instances.len()).collect();
+        assert_eq!(sizes, vec![2, 2, 1]);
+        assert!(batches.batches().iter().all(|batch| batch.mesh == 0 && batch.material == red()));
+
+        // room made in a full batch is filled before the last one
+        batches.remove(ids[1]);
+        batches.add(0, &red(), instance(5.0));
+        assert_eq!(reds(&batches.batches()[0]), vec![0.0, 5.0]);
+        assert_eq!(batches.instance_count(), 5);
+    }
+
+    #[test]
+    fn removing_keeps_batches_packed() {
+        let mut batches = InstanceBatches::new();
+        let ids: Vec<InstanceId> = (0..4).map(|i| batches.add(0, &red(), instance(i as f32))).collect();
+
+        assert_eq!(batches.remove(ids[1]).map(|removed| removed.color[0]), Some(1.0));
+        assert_eq!(reds(&batches.batches()[0]), vec![0.0, 3.0, 2.0]);
+        // the moved instance is still found by its id
+        assert!(batches.set(ids[3], instance(7.0)));
+        assert_eq!(batches.get(ids[3]).map(|data| data.color[0]), Some(7.0));
+        assert_eq!(reds(&batches.batches()[0]), vec![0.0, 7.0, 2.0]);
+
+        assert!(batches.remove(ids[1]).is_none());
+        assert!(!batches.set(ids[1], instance(9.0)));
+        assert!(batches.get(ids[1]).is_none());
+        assert_eq!(batches.instance_count(), 3);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,254 @@
+use crate::math::vec3::Vec3;
+use crate::math::deg2rad;
+
+// must match the constants in the lit fragment shader
+const DIRECTIONAL_LIGHT: f32 = 0.0;
+const POINT_LIGHT: f32 = 1.0;
+const SPOT_LIGHT: f32 = 2.0;
+
+pub const NO_SHADOW: [f32; 4] = [-1.0, 0.0, 0.0, 0.0];
+
+// every lit fragment loops over every light, lights past this many are left out of the frame
+pub const MAX_LIGHTS: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+    // lights have no effect past this distance, 0 means unlimited
+    pub range: f32
+}
+
+impl Attenuation {
+    pub fn new(constant: f32, linear: f32, quadratic: f32, range: f32) -> Attenuation {
+        Attenuation {
+            constant,
+            linear,
+            quadratic,
+            range
+        }
+    }
+
+    pub fn factor(&self, distance: f32) -> f32 {
+        if self.range > 0.0 && distance > self.range {
+            return 0.0;
+        }
+        1.0 / (self.constant + self.linear * distance + self.quadratic * distance * distance)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Light {
+    Ambient {
+        color: Vec3,
+        intensity: f32
+    },
+    Directional {
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+        cast_shadows: bool
+    },
+    Point {
+        position: Vec3,
+        color: Vec3,
+        intensity: f32,
+        attenuation: Attenuation
+    },
+    // cone angles in degrees, measured from the spot direction
+    Spot {
+        position: Vec3,
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+        attenuation: Attenuation,
+        inner_angle: f32,
+        outer_angle: f32,
+        cast_shadows: bool
+    }
+}
+
+// std430 layout of a single light in the light storage buffer
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LightData {
+    pub position_kind: [f32; 4],
+    pub direction: [f32; 4],
+    pub color_intensity: [f32; 4],
+    pub attenuation: [f32; 4],
+    // cosines of the inner and outer cone angles
+    pub cone: [f32; 4],
+    // first shadow view and shadow view count, first is -1 for lights without shadows
+    pub shadow: [f32; 4]
+}
+
+impl Light {
+    // returns None for ambient lights, which are summed into a single term instead
+    pub fn to_data(&self) -> Option<LightData> {
+        match self {
+            Light::Ambient { .. } => None,
+            Light::Directional { direction, color, intensity, .. } => {
+                let direction = direction.normalized();
+                Some(LightData {
+                    position_kind: [0.0, 0.0, 0.0, DIRECTIONAL_LIGHT],
+                    direction: [direction.x, direction.y, direction.z, 0.0],
+                    color_intensity: [color.x, color.y, color.z, *intensity],
+                    attenuation: [1.0, 0.0, 0.0, 0.0],
+                    cone: [0.0, 0.0, 0.0, 0.0],
+                    shadow: NO_SHADOW
+                })
+            },
+            Light::Point { position, color, intensity, attenuation } => {
+                Some(LightData {
+                    position_kind: [position.x, position.y, position.z, POINT_LIGHT],
+                    direction: [0.0, 0.0, 0.0, 0.0],
+                    color_intensity: [color.x, color.y, color.z, *intensity],
+                    attenuation: [attenuation.constant, attenuation.linear, attenuation.quadratic, attenuation.range],
+                    cone: [0.0, 0.0, 0.0, 0.0],
+                    shadow: NO_SHADOW
+                })
+            },
+            Light::Spot { position, direction, color, intensity, attenuation, inner_angle, outer_angle, .. } => {
+                let direction = direction.normalized();
+                Some(LightData {
+                    position_kind: [position.x, position.y, position.z, SPOT_LIGHT],
+                    direction: [direction.x, direction.y, direction.z, 0.0],
+                    color_intensity: [color.x, color.y, color.z, *intensity],
+                    attenuation: [attenuation.constant, attenuation.linear, attenuation.quadratic, attenuation.range],
+                    cone: [deg2rad(*inner_angle).cos(), deg2rad(*outer_angle).cos(), 0.0, 0.0],
+                    shadow: NO_SHADOW
+                })
+            }
+        }
+    }
+}
+
+// splits a light list into the summed ambient color and the per-light gpu data, of the first
+// MAX_LIGHTS lights that aren't ambient
+pub fn pack_lights(lights: &[Light]) -> (Vec3, Vec<LightData>) {
+    let mut ambient = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+    let mut data = vec![];
+    for light in lights {
+        match light {
+            Light::Ambient { color, intensity } => ambient += color.clone() * *intensity,
+            _ if data.len() < MAX_LIGHTS => data.extend(light.to_data()),
+            _ => {}
+        }
+    }
+
+    (ambient, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn assert_near(a: &[f32], b: &[f32]) {
+        assert!(a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-5), "{:?} != {:?}", a, b);
+    }
+
+    fn point(x: f32) -> Light {
+        Light::Point {
+            position: vec3(x, 0.0, 0.0),
+            color: vec3(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            attenuation: Attenuation::new(1.0, 0.0, 0.0, 0.0)
+        }
+    }
+
+    #[test]
+    fn light_data_matches_the_std430_struct() {
+        let data = point(0.0).to_data().expect("failed to pack a point light");
+        let base = &data as *const LightData as usize;
+        let offsets = [
+            &data.position_kind as *const _ as usize - base,
+            &data.direction as *const _ as usize - base,
+            &data.color_intensity as *const _ as usize - base,
+            &data.attenuation as *const _ as usize - base,
+            &data.cone as *const _ as usize - base,
+            &data.shadow as *const _ as usize - base
+        ];
+        // vec4s back to back, so the array stride is the size without padding
+        assert_eq!(offsets, [0, 16, 32, 48, 64, 80]);
+        assert_eq!(std::mem::size_of::<LightData>(), 96);
+    }
+
+    #[test]
+    fn lights_pack_their_kind_and_parameters() {
+        let directional = Light::Directional {
+            direction: vec3(0.0, -2.0, 0.0),
+            color: vec3(1.0, 0.5, 0.25),
+            intensity: 3.0,
+            cast_shadows: true
+        }.to_data().expect("failed to pack a directional light");
+        assert_eq!(directional.position_kind[3], DIRECTIONAL_LIGHT);
+        assert_near(&directional.direction, &[0.0, -1.0, 0.0, 0.0]);
+        assert_eq!(directional.color_intensity, [1.0, 0.5, 0.25, 3.0]);
+        assert_eq!(directional.attenuation, [1.0, 0.0, 0.0, 0.0]);
+        // shadows are filled in by the renderer once it has the views
+        assert_eq!(directional.shadow, NO_SHADOW);
+
+        let point = Light::Point {
+            position: vec3(1.0, 2.0, 3.0),
+            color: vec3(1.0, 1.0, 1.0),
+            intensity: 2.0,
+            attenuation: Attenuation::new(1.0, 0.1, 0.01, 20.0)
+        }.to_data().expect("failed to pack a point light");
+        assert_eq!(point.position_kind, [1.0, 2.0, 3.0, POINT_LIGHT]);
+        assert_eq!(point.attenuation, [1.0, 0.1, 0.01, 20.0]);
+
+        let spot = Light::Spot {
+            position: vec3(0.0, 5.0, 0.0),
+            direction: vec3(0.0, 0.0, 3.0),
+            color: vec3(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            attenuation: Attenuation::new(1.0, 0.0, 0.0, 10.0),
+            inner_angle: 0.0,
+            outer_angle: 60.0,
+            cast_shadows: false
+        }.to_data().expect("failed to pack a spot light");
+        assert_eq!(spot.position_kind, [0.0, 5.0, 0.0, SPOT_LIGHT]);
+        assert_near(&spot.direction, &[0.0, 0.0, 1.0, 0.0]);
+        assert_near(&spot.cone, &[1.0, 0.5, 0.0, 0.0]);
+
+        assert!(Light::Ambient { color: vec3(1.0, 1.0, 1.0), intensity: 1.0 }.to_data().is_none());
+    }
+
+    #[test]
+    fn attenuation_falls_off_and_stops_at_the_range() {
+        let attenuation = Attenuation::new(1.0, 0.5, 0.25, 10.0);
+        assert_eq!(attenuation.factor(0.0), 1.0);
+        assert_eq!(attenuation.factor(2.0), 1.0 / 3.0);
+        assert_eq!(attenuation.factor(10.5), 0.0);
+        assert!(Attenuation::new(1.0, 0.0, 0.0, 0.0).factor(1e6) > 0.0);
+    }
+
+    #[test]
+    fn packing_sums_ambients_and_keeps_the_order() {
+        let lights = vec![
+            Light::Ambient { color: vec3(1.0, 0.0, 0.0), intensity: 0.5 },
+            point(1.0),
+            Light::Ambient { color: vec3(0.0, 1.0, 0.0), intensity: 0.25 },
+            point(2.0)
+        ];
+        let (ambient, data) = pack_lights(&lights);
+        assert_near(&[ambient.x, ambient.y, ambient.z], &[0.5, 0.25, 0.0]);
+        let positions: Vec<f32> = data.iter().map(|light| light.position_kind[0]).collect();
+        assert_eq!(positions, vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn packing_stops_at_the_light_limit() {
+        let mut lights: Vec<Light> = (0..MAX_LIGHTS + 10).map(|i| point(i as f32)).collect();
+        // ambients past the limit still count
+        lights.push(Light::Ambient { color: vec3(1.0, 1.0, 1.0), intensity: 0.5 });
+        let (ambient, data) = pack_lights(&lights);
+        assert_eq!(data.len(), MAX_LIGHTS);
+        assert_eq!(data[MAX_LIGHTS - 1].position_kind[0], (MAX_LIGHTS - 1) as f32);
+        assert_eq!(ambient.x, 0.5);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,225 @@
+use super::camera::Camera;
+use super::mesh::Mesh;
+use crate::math::vec3::Vec3;
+use crate::math::deg2rad;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LodSettings {
+    // fraction of a transition's screen size the projected size has to go past before the level changes,
+    // so a mesh sitting right at a transition doesn't flip between levels every frame
+    pub hysteresis: f32,
+    // frames the old and new level are dithered over when the level changes, 0 switches at once
+    pub fade_frames: u32,
+    // scales the projected sizes, above 1 keeps the detailed levels for longer
+    pub bias: f32
+}
+
+impl Default for LodSettings {
+    fn default() -> LodSettings {
+        LodSettings {
+            hysteresis: 0.1,
+            fade_frames: 0,
+            bias: 1.0
+        }
+    }
+}
+
+// how a level is drawn this frame. while fading the new level keeps the dither pattern's pixels below
+// the fade and the old level the rest, so together they cover every pixel exactly once
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LodDither {
+    None,
+    FadeIn(f32),
+    FadeOut(f32)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LodGroup {
+    // projected size below which level i + 1 takes over from level i, one less than there are levels
+    // and decreasing. sizes are the bounding sphere's diameter as a fraction of the viewport height
+    pub transitions: Vec<f32>,
+    pub center: Vec3,
+    pub radius: f32,
+    current: usize,
+    fading_from: Option<usize>,
+    fade_frame: u32
+}
+
+impl LodGroup {
+    pub fn new(transitions: Vec<f32>, center: Vec3, radius: f32) -> LodGroup {
+        LodGroup {
+            transitions,
+            center,
+            radius,
+            current: 0,
+            fading_from: None,
+            fade_frame: 0
+        }
+    }
+
+    // bounds taken from the most detailed level
+    pub fn from_mesh(transitions: Vec<f32>, mesh: &Mesh) -> LodGroup {
+        let (center, radius) = bounding_sphere(mesh);
+        LodGroup::new(transitions, center, radius)
+    }
+
+    pub fn level_count(&self) -> usize {
+        self.transitions.len() + 1
+    }
+
+    pub fn level(&self) -> usize {
+        self.current
+    }
+
+    pub fn is_fading(&self) -> bool {
+        self.fading_from.is_some()
+    }
+
+    // once per frame, picks the level for the camera and advances a running cross-fade
+    pub fn update(&mut self, camera: &Camera, settings: &LodSettings) {
+        let size = screen_size(camera, &self.center, self.radius) * settings.bias;
+        self.update_with_size(size, settings);
+    }
+
+    pub fn update_with_size(&mut self, size: f32, settings: &LodSettings) {
+        if self.fading_from.is_some() {
+            self.fade_frame += 1;
+            if self.fade_frame >= settings.fade_frames {
+                self.fading_from = None;
+            }
+        }
+
+        let level = select_level(&self.transitions, self.current, size, settings.hysteresis);
+        if level != self.current {
+            // a change in the middle of a fade starts over from the level that was fading in
+            self.fading_from = if settings.fade_frames > 0 { Some(self.current) } else { None };
+            self.fade_frame = 0;
+            self.current = level;
+        }
+    }
+
+    // the levels to draw this frame, the incoming level first
+    pub fn draws(&self, settings: &LodSettings) -> Vec<(usize, LodDither)> {
+        match self.fading_from {
+            Some(previous) => {
+                let fade = (self.fade_frame + 1) as f32 / (settings.fade_frames + 1) as f32;
+                vec![(self.current, LodDither::FadeIn(fade)), (previous, LodDither::FadeOut(fade))]
+            },
+            None => vec![(self.current, LodDither::None)]
+        }
+    }
+}
+
+// the level for a projected size, moving away from current only once the size is past a transition
+// by more than the hysteresis
+pub fn select_level(transitions: &[f32], current: usize, size: f32, hysteresis: f32) -> usize {
+    let mut level = current.min(transitions.len());
+    while level > 0 && size >= transitions[level - 1] * (1.0 + hysteresis) {
+        level -= 1;
+    }
+    while level < transitions.len() && size < transitions[level] * (1.0 - hysteresis) {
+        level += 1;
+    }
+    level
+}
+
+// diameter of the sphere on screen as a fraction of the viewport height, infinite with the camera inside
+pub fn screen_size(camera: &Camera, center: &Vec3, radius: f32) -> f32 {
+    let distance = center.distance_to(camera.position.clone());
+    if distance <= radius {
+        return std::f32::INFINITY;
+    }
+    let tan_half_fov = (deg2rad(camera.fov_y) / 2.0).tan();
+    radius / (distance * tan_half_fov)
+}
+
+// center of the axis aligned bounds and the distance to the farthest vertex from it
+pub fn bounding_sphere(mesh: &Mesh) -> (Vec3, f32) {
+    if mesh.vertices.is_empty() {
+        return (Vec3 { x: 0.0, y: 0.0, z: 0.0 }, 0.0);
+    }
+
+    let mut min = mesh.vertices[0].clone();
+    let mut max = mesh.vertices[0].clone();
+    for vertex in mesh.vertices.iter() {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(vertex[axis]);
+            max[axis] = max[axis].max(vertex[axis]);
+        }
+    }
+    let center = (min + max) / 2.0;
+    let radius = mesh.vertices.iter()
+        .map(|vertex| vertex.distance_to(center.clone()))
+        .fold(0.0, f32::max);
+    (center, radius)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRANSITIONS: [f32; 2] = [0.5, 0.2];
+
+    #[test]
+    fn levels_follow_the_projected_size() {
+        assert_eq!(select_level(&TRANSITIONS, 0, 0.8, 0.0), 0);
+        assert_eq!(select_level(&TRANSITIONS, 0, 0.3, 0.0), 1);
+        assert_eq!(select_level(&TRANSITIONS, 0, 0.1, 0.0), 2);
+        assert_eq!(select_level(&TRANSITIONS, 2, 0.8, 0.0), 0);
+        // a level past the last one is brought back in range
+        assert_eq!(select_level(&TRANSITIONS, 7, 0.1, 0.0), 2);
+    }
+
+    #[test]
+    fn hysteresis_keeps_the_level_near_a_transition() {
+        // within 10% of the 0.5 transition nothing changes, from either side
+        assert_eq!(select_level(&TRANSITIONS, 0, 0.47, 0.1), 0);
+        assert_eq!(select_level(&TRANSITIONS, 1, 0.53, 0.1), 1);
+        assert_eq!(select_level(&TRANSITIONS, 0, 0.44, 0.1), 1);
+        assert_eq!(select_level(&TRANSITIONS, 1, 0.56, 0.1), 0);
+    }
+
+    #[test]
+    fn screen_size_shrinks_with_distance() {
+        let camera = Camera::new(Vec3 { x: 0.0, y: 0.0, z: 0.0 }, Vec3 { x: 0.0, y: 0.0, z: -1.0 },
+            Vec3 { x: 0.0, y: 1.0, z: 0.0 }, 90.0, 1.0, 0.1, 100.0);
+        let near = screen_size(&camera, &Vec3 { x: 0.0, y: 0.0, z: -10.0 }, 1.0);
+        let far = screen_size(&camera, &Vec3 { x: 0.0, y: 0.0, z: -20.0 }, 1.0);
+        assert!((near - 0.1).abs() < 1e-5 && (far - 0.05).abs() < 1e-5);
+        assert_eq!(screen_size(&camera, &Vec3 { x: 0.0, y: 0.0, z: -0.5 }, 1.0), std::f32::INFINITY);
+    }
+
+    #[test]
+    fn level_changes_dither_over_the_fade() {
+        let settings = LodSettings { hysteresis: 0.0, fade_frames: 3, bias: 1.0 };
+        let mut group = LodGroup::new(TRANSITIONS.to_vec(), Vec3 { x: 0.0, y: 0.0, z: 0.0 }, 1.0);
+        group.update_with_size(1.0, &settings);
+        assert_eq!(group.draws(&settings), vec![(0, LodDither::None)]);
+
+        group.update_with_size(0.3, &settings);
+        assert_eq!(group.level(), 1);
+        let mut fades = vec![];
+        while group.is_fading() {
+            match group.draws(&settings).as_slice() {
+                [(1, LodDither::FadeIn(fade_in)), (0, LodDither::FadeOut(fade_out))] => {
+                    assert_eq!(fade_in, fade_out);
+                    fades.push(*fade_in);
+                },
+                draws => panic!("unexpected draws {:?}", draws)
+            }
+            group.update_with_size(0.3, &settings);
+        }
+        assert_eq!(fades, vec![0.25, 0.5, 0.75]);
+        assert_eq!(group.draws(&settings), vec![(1, LodDither::None)]);
+    }
+
+    #[test]
+    fn level_changes_are_immediate_without_fade_frames() {
+        let settings = LodSettings::default();
+        let mut group = LodGroup::new(TRANSITIONS.to_vec(), Vec3 { x: 0.0, y: 0.0, z: 0.0 }, 1.0);
+        group.update_with_size(0.1, &settings);
+        assert_eq!(group.level(), 2);
+        assert!(!group.is_fading());
+        assert_eq!(group.draws(&settings), vec![(2, LodDither::None)]);
+    }
+}
\ No newline at end of file
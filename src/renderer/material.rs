@@ -0,0 +1,126 @@
+use super::texture::Texture;
+use crate::math::vec3::Vec3;
+
+use std::sync::Arc;
+
+// Blinn-Phong material parameters, sent to the lit fragment shader as push constants
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    pub diffuse: Vec3,
+    pub specular: Vec3,
+    pub shininess: f32,
+    pub opacity: f32
+}
+
+// std140 layout of the material in the lit shader's MaterialConstants
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MaterialData {
+    // rgb and opacity
+    pub diffuse: [f32; 4],
+    // rgb and shininess
+    pub specular: [f32; 4]
+}
+
+impl Material {
+    pub fn new(diffuse: Vec3, specular: Vec3, shininess: f32, opacity: f32) -> Material {
+        Material {
+            diffuse,
+            specular,
+            shininess,
+            opacity
+        }
+    }
+
+    pub fn to_data(&self) -> MaterialData {
+        MaterialData {
+            diffuse: [self.diffuse.x, self.diffuse.y, self.diffuse.z, self.opacity],
+            specular: [self.specular.x, self.specular.y, self.specular.z, self.shininess]
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Material {
+        Material {
+            diffuse: Vec3 { x: 0.8, y: 0.8, z: 0.8 },
+            specular: Vec3 { x: 0.5, y: 0.5, z: 0.5 },
+            shininess: 32.0,
+            opacity: 1.0
+        }
+    }
+}
+
+// metallic-roughness material following the glTF conventions: the maps are multiplied by the factors,
+// metallic is read from the blue channel and roughness from the green channel of metallic_roughness_map
+#[derive(Debug, Clone, PartialEq)]
+pub struct PbrMaterial {
+    pub base_color: Vec3,
+    pub opacity: f32,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: Vec3,
+    pub occlusion_strength: f32,
+    pub normal_scale: f32,
+
+    pub base_color_map: Option<Arc<Texture>>,
+    pub metallic_roughness_map: Option<Arc<Texture>>,
+    pub normal_map: Option<Arc<Texture>>,
+    pub occlusion_map: Option<Arc<Texture>>,
+    pub emissive_map: Option<Arc<Texture>>
+}
+
+impl PbrMaterial {
+    pub fn new(base_color: Vec3, metallic: f32, roughness: f32) -> PbrMaterial {
+        PbrMaterial {
+            base_color,
+            metallic,
+            roughness,
+            .. PbrMaterial::default()
+        }
+    }
+}
+
+impl Default for PbrMaterial {
+    fn default() -> PbrMaterial {
+        PbrMaterial {
+            base_color: Vec3 { x: 1.0, y: 1.0, z: 1.0 },
+            opacity: 1.0,
+            metallic: 1.0,
+            roughness: 1.0,
+            emissive: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+            occlusion_strength: 1.0,
+            normal_scale: 1.0,
+
+            base_color_map: None,
+            metallic_roughness_map: None,
+            normal_map: None,
+            occlusion_map: None,
+            emissive_map: None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn material_data_matches_the_push_constants() {
+        let material = Material::new(Vec3 { x: 0.1, y: 0.2, z: 0.3 }, Vec3 { x: 0.4, y: 0.5, z: 0.6 }, 64.0, 0.75);
+        let data = material.to_data();
+        assert_eq!(data.diffuse, [0.1, 0.2, 0.3, 0.75]);
+        assert_eq!(data.specular, [0.4, 0.5, 0.6, 64.0]);
+
+        // two vec4s without padding, followed by the flags in MaterialConstants
+        let base = &data as *const MaterialData as usize;
+        assert_eq!(&data.specular as *const _ as usize - base, 16);
+        assert_eq!(std::mem::size_of::<MaterialData>(), 32);
+    }
+
+    #[test]
+    fn default_materials_are_opaque() {
+        let data = Material::default().to_data();
+        assert_eq!(data.diffuse[3], 1.0);
+        assert_eq!(data.specular[3], 32.0);
+    }
+}
\ No newline at end of file
@@ -1,12 +1,21 @@
 use crate::math::vec3::Vec3;
 
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Mesh {
     pub vertices: Vec<Vec3>,
     pub indices: Vec<u32>,
     pub uvs: Vec<Vec3>,
     pub uv_indices: Vec<u32>,
     pub normals: Vec<Vec3>,
-    pub normal_indices: Vec<u32>
+    pub normal_indices: Vec<u32>,
+    // optional per vertex streams, either empty or one entry per vertex.
+    // tangent w is the bitangent sign
+    pub tangents: Vec<[f32; 4]>,
+    pub colors: Vec<[f32; 4]>,
+    pub joints: Vec<[u16; 4]>,
+    pub weights: Vec<[f32; 4]>
 }
 
 impl Mesh {
@@ -17,7 +26,525 @@ impl Mesh {
             uvs: new_uvs,
             uv_indices: new_uv_indices,
             normals: new_normals,
-            normal_indices: new_normal_indices
+            normal_indices: new_normal_indices,
+            tangents: vec![],
+            colors: vec![],
+            joints: vec![],
+            weights: vec![]
+        }
+    }
+
+    // normal of vertex i, looked up through normal_indices when present.
+    // vertices without a normal face the default camera
+    pub fn vertex_normal(&self, i: usize) -> Vec3 {
+        let normal_index = self.normal_indices.get(i).map(|n| *n as usize).unwrap_or(i);
+        match self.normals.get(normal_index) {
+            Some(normal) => normal.clone(),
+            None => Vec3 { x: 0.0, y: 0.0, z: -1.0 }
+        }
+    }
+
+    // texture coordinate of vertex i, looked up through uv_indices when present
+    pub fn vertex_uv(&self, i: usize) -> [f32; 2] {
+        let uv_index = self.uv_indices.get(i).map(|n| *n as usize).unwrap_or(i);
+        match self.uvs.get(uv_index) {
+            Some(uv) => [uv.x, uv.y],
+            None => [0.0, 0.0]
+        }
+    }
+
+    // vertex index of every triangle corner in draw order, meshes without indices are drawn as they are
+    pub fn triangle_list(&self) -> Vec<usize> {
+        if self.indices.is_empty() {
+            (0..self.vertices.len()).collect()
+        } else {
+            self.indices.iter().map(|index| *index as usize).collect()
+        }
+    }
+}
+
+/*********************************
+*** PROCEDURAL SHAPES
+*********************************/
+
+// the shapes are centered on the origin with y up. front faces wind clockwise seen from outside, which
+// makes (b - a) x (c - a) of every triangle point outwards. normals and uvs are per vertex, vertices
+// are duplicated along uv seams and hard edges
+
+// point of a profile that's revolved around the y axis
+struct ProfilePoint {
+    radius: f32,
+    y: f32,
+    // normal in the plane of the profile, away from the axis and up
+    normal_radius: f32,
+    normal_y: f32,
+    // texture coordinate along the profile
+    v: f32
+}
+
+impl Mesh {
+    // flat grid in the xz plane facing up, segments along x and z
+    pub fn plane(width: f32, depth: f32, x_segments: u32, z_segments: u32) -> Mesh {
+        Mesh::heightfield(width, depth, x_segments, z_segments, |_, _| 0.0)
+    }
+
+    // grid in the xz plane with a height per grid point, normals from the slopes between neighbours
+    pub fn heightfield<F: Fn(f32, f32) -> f32>(width: f32, depth: f32, x_segments: u32, z_segments: u32, height: F) -> Mesh {
+        let x_segments = x_segments.max(1);
+        let z_segments = z_segments.max(1);
+        let step_x = width / x_segments as f32;
+        let step_z = depth / z_segments as f32;
+        // v grows towards -z, so the texture is upright seen from above with +z at the top
+        let position = |i: i64, j: i64| {
+            let x = -width / 2.0 + step_x * i as f32;
+            let z = depth / 2.0 - step_z * j as f32;
+            Vec3 { x, y: height(x, z), z }
+        };
+
+        Mesh::grid(x_segments, z_segments, |i, j| {
+            let (i, j) = (i as i64, j as i64);
+            // central differences, one sided on the borders
+            let (left, right) = (position((i - 1).max(0), j), position((i + 1).min(x_segments as i64), j));
+            let (top, bottom) = (position(i, (j - 1).max(0)), position(i, (j + 1).min(z_segments as i64)));
+            let normal = (right - left).cross(bottom - top).normalized();
+            (position(i, j), normal)
+        })
+    }
+
+    // axis aligned cube, every face split into subdivisions x subdivisions quads
+    pub fn cube(size: f32, subdivisions: u32) -> Mesh {
+        let unit = |x: f32, y: f32, z: f32| Vec3 { x, y, z };
+        // normal, then the directions u and v run in as seen from outside the face
+        let faces = [
+            (unit(1.0, 0.0, 0.0), unit(0.0, 0.0, 1.0), unit(0.0, -1.0, 0.0)),
+            (unit(-1.0, 0.0, 0.0), unit(0.0, 0.0, -1.0), unit(0.0, -1.0, 0.0)),
+            (unit(0.0, 1.0, 0.0), unit(1.0, 0.0, 0.0), unit(0.0, 0.0, -1.0)),
+            (unit(0.0, -1.0, 0.0), unit(1.0, 0.0, 0.0), unit(0.0, 0.0, 1.0)),
+            (unit(0.0, 0.0, 1.0), unit(-1.0, 0.0, 0.0), unit(0.0, -1.0, 0.0)),
+            (unit(0.0, 0.0, -1.0), unit(1.0, 0.0, 0.0), unit(0.0, -1.0, 0.0))
+        ];
+
+        let subdivisions = subdivisions.max(1);
+        let mut ret = Mesh::new(vec![], vec![], vec![], vec![], vec![], vec![]);
+        for (normal, u, v) in faces.iter() {
+            ret.append(&Mesh::grid(subdivisions, subdivisions, |i, j| {
+                let s = i as f32 / subdivisions as f32 - 0.5;
+                let t = j as f32 / subdivisions as f32 - 0.5;
+                let position = (normal.clone() * 0.5 + u.clone() * s + v.clone() * t) * size;
+                (position, normal.clone())
+            }));
+        }
+        ret
+    }
+
+    // sphere from rings of latitude and segments of longitude, uvs are the equirectangular mapping
+    pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> Mesh {
+        let rings = rings.max(2);
+        let profile: Vec<ProfilePoint> = (0..=rings)
+            .map(|ring| {
+                let (sin, cos) = polar(ring, rings, std::f32::consts::PI);
+                ProfilePoint {
+                    radius: radius * sin,
+                    y: radius * cos,
+                    normal_radius: sin,
+                    normal_y: cos,
+                    v: ring as f32 / rings as f32
+                }
+            })
+            .collect();
+
+        Mesh::revolve(&profile, segments)
+    }
+
+    // subdivided icosahedron, evenly spread triangles without the crowding at the poles of uv_sphere.
+    // uvs use the same mapping as uv_sphere. triangles across the seam get u past 1 on their far side,
+    // which needs a repeating sampler
+    pub fn icosphere(radius: f32, subdivisions: u32) -> Mesh {
+        let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+        let mut points: Vec<Vec3> = [
+            (-1.0, t, 0.0), (1.0, t, 0.0), (-1.0, -t, 0.0), (1.0, -t, 0.0),
+            (0.0, -1.0, t), (0.0, 1.0, t), (0.0, -1.0, -t), (0.0, 1.0, -t),
+            (t, 0.0, -1.0), (t, 0.0, 1.0), (-t, 0.0, -1.0), (-t, 0.0, 1.0)
+        ].iter().map(|&(x, y, z)| Vec3 { x, y, z }.normalized()).collect();
+        let mut triangles: Vec<[u32; 3]> = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+            [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+            [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1]
+        ];
+
+        // every subdivision splits each triangle in four, edge midpoints are shared between neighbours
+        for _ in 0..subdivisions {
+            let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+            let mut midpoint = |a: u32, b: u32, points: &mut Vec<Vec3>| {
+                let key = (a.min(b), a.max(b));
+                *midpoints.entry(key).or_insert_with(|| {
+                    points.push(((points[a as usize].clone() + points[b as usize].clone()) / 2.0).normalized());
+                    points.len() as u32 - 1
+                })
+            };
+            triangles = triangles.iter()
+                .flat_map(|&[a, b, c]| {
+                    let ab = midpoint(a, b, &mut points);
+                    let bc = midpoint(b, c, &mut points);
+                    let ca = midpoint(c, a, &mut points);
+                    vec![[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        let uv = |p: &Vec3| Vec3 {
+            x: (p.z.atan2(p.x) / (2.0 * std::f32::consts::PI) + 1.0) % 1.0,
+            y: p.y.max(-1.0).min(1.0).acos() / std::f32::consts::PI,
+            z: 0.0
+        };
+        let mut ret = Mesh::new(vec![], vec![], vec![], vec![], vec![], vec![]);
+        for triangle in triangles.iter() {
+            // the winding of the base triangles isn't uniform, flip the inward facing ones
+            let [a, b, c] = *triangle;
+            let (pa, pb, pc) = (&points[a as usize], &points[b as usize], &points[c as usize]);
+            let outward = (pb.clone() - pa.clone()).cross(pc.clone() - pa.clone())
+                .dot(pa.clone() + pb.clone() + pc.clone()) > 0.0;
+            let corners = if outward { [pa, pb, pc] } else { [pa, pc, pb] };
+
+            let mut uvs: Vec<Vec3> = corners.iter().map(|p| uv(p)).collect();
+            let poles: Vec<bool> = corners.iter().map(|p| p.y.abs() > 1.0 - 1e-6).collect();
+            // a triangle across the seam gets its small u moved past 1
+            let max_u = (0..3).filter(|corner| !poles[*corner]).map(|corner| uvs[corner].x).fold(0.0f32, f32::max);
+            for corner in (0..3).filter(|corner| !poles[*corner]) {
+                if max_u - uvs[corner].x > 0.5 {
+                    uvs[corner].x += 1.0;
+                }
+            }
+            // pole vertices have no longitude of their own, use the middle of the opposite edge
+            for corner in (0..3).filter(|corner| poles[*corner]) {
+                uvs[corner].x = (uvs[(corner + 1) % 3].x + uvs[(corner + 2) % 3].x) / 2.0;
+            }
+
+            for (corner, uv) in corners.iter().zip(uvs.into_iter()) {
+                ret.indices.push(ret.vertices.len() as u32);
+                ret.vertices.push((*corner).clone() * radius);
+                ret.normals.push((*corner).clone());
+                ret.uvs.push(uv);
+            }
+        }
+        ret.weld_exact();
+        ret
+    }
+
+    // closed cylinder along y
+    pub fn cylinder(radius: f32, height: f32, segments: u32, height_segments: u32) -> Mesh {
+        Mesh::truncated_cone(radius, radius, height, segments, height_segments)
+    }
+
+    // closed cone along y with the tip up
+    pub fn cone(radius: f32, height: f32, segments: u32, height_segments: u32) -> Mesh {
+        Mesh::truncated_cone(0.0, radius, height, segments, height_segments)
+    }
+
+    // cylinder with half spheres on its ends, height is the length of the straight part
+    pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32, height_segments: u32) -> Mesh {
+        let rings = rings.max(1);
+        let height_segments = height_segments.max(1);
+        let quarter = std::f32::consts::PI / 2.0;
+        let length = std::f32::consts::PI * radius + height;
+
+        // top cap down to its equator, the straight part, then the bottom cap
+        let mut profile = vec![];
+        for ring in 0..=rings {
+            let (sin, cos) = polar(ring, rings, quarter);
+            profile.push(ProfilePoint {
+                radius: radius * sin,
+                y: height / 2.0 + radius * cos,
+                normal_radius: sin,
+                normal_y: cos,
+                v: radius * quarter * ring as f32 / rings as f32 / length
+            });
+        }
+        for segment in 1..=height_segments {
+            let along = height * segment as f32 / height_segments as f32;
+            profile.push(ProfilePoint {
+                radius,
+                y: height / 2.0 - along,
+                normal_radius: 1.0,
+                normal_y: 0.0,
+                v: (radius * quarter + along) / length
+            });
+        }
+        for ring in 1..=rings {
+            // sin and cos swapped, the angle continues from the equator
+            let (cos, sin) = polar(ring, rings, quarter);
+            profile.push(ProfilePoint {
+                radius: radius * sin,
+                y: -height / 2.0 - radius * cos,
+                normal_radius: sin,
+                normal_y: -cos,
+                v: (radius * quarter * (1.0 + ring as f32 / rings as f32) + height) / length
+            });
+        }
+
+        Mesh::revolve(&profile, segments)
+    }
+
+    // ring around the y axis. sides is the number of segments around the tube
+    pub fn torus(major_radius: f32, minor_radius: f32, segments: u32, sides: u32) -> Mesh {
+        let sides = sides.max(3);
+        // starts at the outer equator and goes over the top, which keeps the winding outwards
+        let profile: Vec<ProfilePoint> = (0..=sides)
+            .map(|side| {
+                let angle = 2.0 * std::f32::consts::PI * (1.0 - side as f32 / sides as f32);
+                ProfilePoint {
+                    radius: major_radius + minor_radius * angle.cos(),
+                    y: minor_radius * angle.sin(),
+                    normal_radius: angle.cos(),
+                    normal_y: angle.sin(),
+                    v: side as f32 / sides as f32
+                }
+            })
+            .collect();
+
+        Mesh::revolve(&profile, segments)
+    }
+
+    fn truncated_cone(top_radius: f32, bottom_radius: f32, height: f32, segments: u32, height_segments: u32) -> Mesh {
+        let height_segments = height_segments.max(1);
+        let slope = Vec3 { x: height, y: bottom_radius - top_radius, z: 0.0 }.normalized();
+        let profile: Vec<ProfilePoint> = (0..=height_segments)
+            .map(|segment| {
+                let t = segment as f32 / height_segments as f32;
+                ProfilePoint {
+                    radius: top_radius + (bottom_radius - top_radius) * t,
+                    y: height / 2.0 - height * t,
+                    normal_radius: slope.x,
+                    normal_y: slope.y,
+                    v: t
+                }
+            })
+            .collect();
+
+        let mut ret = Mesh::revolve(&profile, segments);
+        if top_radius > 0.0 {
+            ret.append(&Mesh::disk(top_radius, height / 2.0, segments, true));
+        }
+        if bottom_radius > 0.0 {
+            ret.append(&Mesh::disk(bottom_radius, -height / 2.0, segments, false));
+        }
+        ret
+    }
+
+    // cap facing up or down at height y, uvs are planar
+    fn disk(radius: f32, y: f32, segments: u32, up: bool) -> Mesh {
+        let segments = segments.max(3);
+        let normal = Vec3 { x: 0.0, y: if up { 1.0 } else { -1.0 }, z: 0.0 };
+        let mut ret = Mesh::new(vec![], vec![], vec![], vec![], vec![], vec![]);
+        ret.vertices.push(Vec3 { x: 0.0, y, z: 0.0 });
+        ret.uvs.push(Vec3 { x: 0.5, y: 0.5, z: 0.0 });
+        ret.normals.push(normal.clone());
+        for segment in 0..=segments {
+            let angle = 2.0 * std::f32::consts::PI * segment as f32 / segments as f32;
+            let (x, z) = (radius * angle.cos(), radius * angle.sin());
+            ret.vertices.push(Vec3 { x, y, z });
+            // seen from outside, like the faces of the cube
+            let v = if up { 0.5 - z / (2.0 * radius) } else { 0.5 + z / (2.0 * radius) };
+            ret.uvs.push(Vec3 { x: 0.5 + x / (2.0 * radius), y: v, z: 0.0 });
+            ret.normals.push(normal.clone());
+        }
+        for segment in 1..=segments {
+            if up {
+                ret.indices.extend_from_slice(&[0, segment + 1, segment]);
+            } else {
+                ret.indices.extend_from_slice(&[0, segment, segment + 1]);
+            }
+        }
+        ret
+    }
+
+    // revolves the profile around the y axis. u goes around counterclockwise seen from above,
+    // starting at +x. the profile runs from top to bottom on the outside of the shape
+    fn revolve(profile: &[ProfilePoint], segments: u32) -> Mesh {
+        let segments = segments.max(3);
+        let mut ret = Mesh::grid(segments, profile.len() as u32 - 1, |i, j| {
+            let angle = 2.0 * std::f32::consts::PI * i as f32 / segments as f32;
+            let (cos, sin) = if i == segments { (1.0, 0.0) } else { (angle.cos(), angle.sin()) };
+            let point = &profile[j as usize];
+            (
+                Vec3 { x: point.radius * cos, y: point.y, z: point.radius * sin },
+                Vec3 { x: point.normal_radius * cos, y: point.normal_y, z: point.normal_radius * sin }.normalized()
+            )
+        });
+        for (vertex, uv) in ret.uvs.iter_mut().enumerate() {
+            uv.y = profile[vertex / (segments as usize + 1)].v;
+        }
+        ret
+    }
+
+    // (columns + 1) x (rows + 1) vertices from point(column, row), with u along the columns and v
+    // along the rows. point has to move right for growing columns and down for growing rows as seen
+    // from the side its normals face. triangles collapsed to a line, like the ones at the poles of
+    // a sphere, are left out
+    fn grid<F: Fn(u32, u32) -> (Vec3, Vec3)>(columns: u32, rows: u32, point: F) -> Mesh {
+        let mut ret = Mesh::new(vec![], vec![], vec![], vec![], vec![], vec![]);
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let (position, normal) = point(column, row);
+                ret.vertices.push(position);
+                ret.normals.push(normal);
+                ret.uvs.push(Vec3 { x: column as f32 / columns as f32, y: row as f32 / rows as f32, z: 0.0 });
+            }
+        }
+
+        let index = |column: u32, row: u32| row * (columns + 1) + column;
+        for row in 0..rows {
+            for column in 0..columns {
+                let (top_left, top_right) = (index(column, row), index(column + 1, row));
+                let (bottom_left, bottom_right) = (index(column, row + 1), index(column + 1, row + 1));
+                for triangle in [[top_left, top_right, bottom_left], [top_right, bottom_right, bottom_left]].iter() {
+                    let corners: Vec<&Vec3> = triangle.iter().map(|i| &ret.vertices[*i as usize]).collect();
+                    if corners[0] != corners[1] && corners[1] != corners[2] && corners[2] != corners[0] {
+                        ret.indices.extend_from_slice(triangle);
+                    }
+                }
+            }
+        }
+        ret
+    }
+
+    // adds the vertices and triangles of other, both meshes need per vertex normals and uvs
+    fn append(&mut self, other: &Mesh) {
+        let offset = self.vertices.len() as u32;
+        self.vertices.extend(other.vertices.iter().cloned());
+        self.normals.extend(other.normals.iter().cloned());
+        self.uvs.extend(other.uvs.iter().cloned());
+        self.indices.extend(other.indices.iter().map(|index| index + offset));
+    }
+
+    // merges vertices with identical position, normal and uv
+    fn weld_exact(&mut self) {
+        let mut unique: HashMap<[u32; 8], u32> = HashMap::new();
+        let mut vertices = vec![];
+        let mut normals = vec![];
+        let mut uvs = vec![];
+        let mut remap = Vec::with_capacity(self.vertices.len());
+        for i in 0..self.vertices.len() {
+            let (p, n, uv) = (&self.vertices[i], &self.normals[i], &self.uvs[i]);
+            let key = [p.x, p.y, p.z, n.x, n.y, n.z, uv.x, uv.y].iter()
+                .map(|c| c.to_bits())
+                .collect::<Vec<u32>>();
+            let key = [key[0], key[1], key[2], key[3], key[4], key[5], key[6], key[7]];
+            let index = *unique.entry(key).or_insert_with(|| {
+                vertices.push(p.clone());
+                normals.push(n.clone());
+                uvs.push(uv.clone());
+                vertices.len() as u32 - 1
+            });
+            remap.push(index);
+        }
+
+        self.indices = self.indices.iter().map(|index| remap[*index as usize]).collect();
+        self.vertices = vertices;
+        self.normals = normals;
+        self.uvs = uvs;
+    }
+}
+
+// sin and cos of step / steps of the way to angle, exact at the ends so the poles of a shape meet in a point
+fn polar(step: u32, steps: u32, angle: f32) -> (f32, f32) {
+    if step == 0 {
+        (0.0, 1.0)
+    } else if step == steps && angle == std::f32::consts::PI {
+        (0.0, -1.0)
+    } else if step == steps && angle == std::f32::consts::PI / 2.0 {
+        (1.0, 0.0)
+    } else {
+        let theta = angle * step as f32 / steps as f32;
+        (theta.sin(), theta.cos())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn closed_shapes() -> Vec<(&'static str, Mesh)> {
+        vec![
+            ("cube", Mesh::cube(2.0, 3)),
+            ("uv sphere", Mesh::uv_sphere(1.0, 16, 8)),
+            ("icosphere", Mesh::icosphere(1.0, 2)),
+            ("cylinder", Mesh::cylinder(0.5, 2.0, 12, 3)),
+            ("cone", Mesh::cone(0.5, 1.0, 12, 2)),
+            ("capsule", Mesh::capsule(0.5, 1.0, 12, 4, 2)),
+            ("torus", Mesh::torus(1.0, 0.25, 16, 8))
+        ]
+    }
+
+    // triangles as corners welded by position, vertices split along seams become one
+    fn welded_triangles(mesh: &Mesh) -> Vec<[usize; 3]> {
+        let mut ids: HashMap<(i64, i64, i64), usize> = HashMap::new();
+        let welded: Vec<usize> = mesh.vertices.iter().map(|p| {
+            let key = ((p.x * 1e4).round() as i64, (p.y * 1e4).round() as i64, (p.z * 1e4).round() as i64);
+            let next = ids.len();
+            *ids.entry(key).or_insert(next)
+        }).collect();
+        mesh.triangle_list().chunks(3)
+            .map(|t| [welded[t[0]], welded[t[1]], welded[t[2]]])
+            .filter(|[a, b, c]| a != b && b != c && c != a)
+            .collect()
+    }
+
+    #[test]
+    fn closed_shapes_are_watertight() {
+        for (name, mesh) in closed_shapes() {
+            let mut edges: HashMap<(usize, usize), u32> = HashMap::new();
+            for [a, b, c] in welded_triangles(&mesh) {
+                for edge in [(a, b), (b, c), (c, a)].iter() {
+                    *edges.entry(*edge).or_insert(0) += 1;
+                }
+            }
+            // every edge is used once in each direction, by two triangles wound the same way
+            for ((a, b), count) in edges.iter() {
+                assert_eq!(*count, 1, "{}: edge {}-{} used {} times", name, a, b, count);
+                assert_eq!(edges.get(&(*b, *a)), Some(&1), "{}: edge {}-{} has no opposite", name, a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn faces_and_normals_point_outwards() {
+        let mut shapes = closed_shapes();
+        shapes.push(("plane", Mesh::plane(2.0, 3.0, 4, 2)));
+        shapes.push(("heightfield", Mesh::heightfield(2.0, 2.0, 8, 8, |x, z| 0.2 * (x * 3.0).sin() * z)));
+
+        for (name, mesh) in shapes {
+            assert!(mesh.normals.iter().all(|n| (n.lenght() - 1.0).abs() < 1e-4), "{}: normals aren't unit length", name);
+            for t in mesh.triangle_list().chunks(3) {
+                let (a, b, c) = (&mesh.vertices[t[0]], &mesh.vertices[t[1]], &mesh.vertices[t[2]]);
+                let face = (b.clone() - a.clone()).cross(c.clone() - a.clone());
+                if face.lenght() < 1e-6 {
+                    continue;
+                }
+                for corner in t.iter() {
+                    assert!(face.dot(mesh.vertex_normal(*corner)) > 0.0, "{}: a normal faces away from its triangle", name);
+                }
+
+                // from the middle of the shape, or of the tube for the torus
+                let center = match name {
+                    "torus" => Vec3 { x: a.x, y: 0.0, z: a.z }.normalized(),
+                    "plane" | "heightfield" => Vec3 { x: a.x, y: a.y - 1.0, z: a.z },
+                    _ => Vec3 { x: 0.0, y: 0.0, z: 0.0 }
+                };
+                assert!(face.dot(a.clone() - center) > 0.0, "{}: a triangle faces inwards", name);
+            }
+        }
+    }
+
+    #[test]
+    fn generators_fill_every_stream() {
+        for (name, mesh) in closed_shapes() {
+            assert_eq!(mesh.normals.len(), mesh.vertices.len(), "{}", name);
+            assert_eq!(mesh.uvs.len(), mesh.vertices.len(), "{}", name);
+            assert!(mesh.indices.iter().all(|i| (*i as usize) < mesh.vertices.len()), "{}", name);
         }
+        // 6 faces of 2 x 2 quads
+        assert_eq!(Mesh::cube(1.0, 2).indices.len(), 6 * 4 * 6);
     }
 }
\ No newline at end of file
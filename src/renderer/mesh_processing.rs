@@ -0,0 +1,1082 @@
+use super::mesh::Mesh;
+use crate::math::vec3::Vec3;
+use crate::math::deg2rad;
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+// mesh cleanup and optimization. every function takes the mesh as it comes from a file or a generator
+// and returns an indexed mesh with one entry per vertex in each stream: normal_indices and uv_indices
+// are resolved, and meshes without indices get one per vertex. triangles follow the engine's winding,
+// with (b - a) x (c - a) pointing out of the front face
+
+/*********************************
+*** NORMALS AND TANGENTS
+*********************************/
+
+// smooth normals across edges where the faces meet at less than smoothing_angle degrees, hard edges
+// elsewhere. corners average the normals of their faces weighted by the angle at the corner, and
+// vertices are split where they end up with different normals. 0 gives flat shading and 180 smooth
+// shading everywhere. existing tangents are dropped, they depend on the normals
+pub fn compute_normals(mesh: &Mesh, smoothing_angle: f32) -> Mesh {
+    let mut mesh = resolved(mesh);
+    mesh.tangents.clear();
+    if mesh.normals.is_empty() {
+        mesh.normals = vec![Vec3 { x: 0.0, y: 0.0, z: 0.0 }; mesh.vertices.len()];
+    }
+
+    let triangles = triangles(&mesh);
+    let face_normals: Vec<Vec3> = triangles.iter().map(|triangle| face_normal(&mesh, triangle)).collect();
+    let mut corners_at_position: HashMap<[u32; 3], Vec<(usize, usize)>> = HashMap::new();
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        for corner in 0..3 {
+            corners_at_position.entry(position_key(&mesh.vertices[triangle[corner] as usize]))
+                .or_insert_with(Vec::new)
+                .push((triangle_index, corner));
+        }
+    }
+
+    let min_cos = deg2rad(smoothing_angle.max(0.0).min(180.0)).cos() - 1e-5;
+    let mut ret = empty_like();
+    let mut split: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        let face = &face_normals[triangle_index];
+        for corner in 0..3 {
+            let vertex = triangle[corner];
+            let mut normal = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+            for &(other, other_corner) in corners_at_position[&position_key(&mesh.vertices[vertex as usize])].iter() {
+                if face.dot(face_normals[other].clone()) >= min_cos {
+                    normal += face_normals[other].clone() * corner_angle(&mesh, &triangles[other], other_corner);
+                }
+            }
+            let normal = if normal.lenght() > 1e-12 {
+                normal.normalized()
+            } else if face.lenght() > 0.0 {
+                face.clone()
+            } else {
+                Vec3 { x: 0.0, y: 0.0, z: -1.0 }
+            };
+
+            let index = *split.entry((vertex, position_key(&normal))).or_insert_with(|| {
+                let index = push_vertex(&mut ret, &mesh, vertex as usize);
+                ret.normals[index as usize] = normal.clone();
+                index
+            });
+            ret.indices.push(index);
+        }
+    }
+    ret
+}
+
+pub fn compute_flat_normals(mesh: &Mesh) -> Mesh {
+    compute_normals(mesh, 0.0)
+}
+
+pub fn compute_smooth_normals(mesh: &Mesh) -> Mesh {
+    compute_normals(mesh, 180.0)
+}
+
+// tangents the way MikkTSpace builds them: per triangle directions from the uv gradients, weighted by
+// corner angle, orthogonalized against the vertex normal, and w the handedness so that the bitangent
+// is w * cross(normal, tangent). vertices used with both handednesses, like the ones on the mirror
+// line of mirrored uvs, are split. meshes without normals get smooth ones first
+pub fn compute_tangents(mesh: &Mesh) -> Result<Mesh, String> {
+    let mesh = if mesh.normals.is_empty() { compute_smooth_normals(mesh) } else { resolved(mesh) };
+    if mesh.uvs.is_empty() {
+        return Err("tangents need texture coordinates".to_string());
+    }
+
+    let triangles = triangles(&mesh);
+    // tangent and bitangent sums per vertex and handedness
+    let mut sums: HashMap<(u32, bool), (Vec3, Vec3)> = HashMap::new();
+    let mut corner_keys = Vec::with_capacity(triangles.len() * 3);
+    for triangle in triangles.iter() {
+        let p: Vec<&Vec3> = triangle.iter().map(|i| &mesh.vertices[*i as usize]).collect();
+        let uv: Vec<&Vec3> = triangle.iter().map(|i| &mesh.uvs[*i as usize]).collect();
+        let (edge1, edge2) = (p[1].clone() - p[0].clone(), p[2].clone() - p[0].clone());
+        let (du1, dv1) = (uv[1].x - uv[0].x, uv[1].y - uv[0].y);
+        let (du2, dv2) = (uv[2].x - uv[0].x, uv[2].y - uv[0].y);
+        let det = du1 * dv2 - du2 * dv1;
+        let (tangent, bitangent) = if det.abs() > 1e-12 {
+            ((edge1.clone() * dv2 - edge2.clone() * dv1) / det, (edge2 * du1 - edge1 * du2) / det)
+        } else {
+            (Vec3 { x: 0.0, y: 0.0, z: 0.0 }, Vec3 { x: 0.0, y: 0.0, z: 0.0 })
+        };
+        let positive = face_normal(&mesh, triangle).cross(tangent.clone()).dot(bitangent.clone()) >= 0.0;
+
+        for corner in 0..3 {
+            let key = (triangle[corner], positive);
+            let weight = corner_angle(&mesh, triangle, corner);
+            let normal = &mesh.normals[triangle[corner] as usize];
+            let projected = tangent.clone() - normal.clone() * normal.dot(tangent.clone());
+            let projected = if projected.lenght() > 1e-12 { projected.normalized() } else { projected };
+            let sum = sums.entry(key).or_insert_with(|| (Vec3 { x: 0.0, y: 0.0, z: 0.0 }, Vec3 { x: 0.0, y: 0.0, z: 0.0 }));
+            sum.0 += projected * weight;
+            sum.1 += bitangent.clone() * weight;
+            corner_keys.push(key);
+        }
+    }
+
+    let mut ret = empty_like();
+    let mut split: HashMap<(u32, bool), u32> = HashMap::new();
+    for key in corner_keys {
+        let index = *split.entry(key).or_insert_with(|| {
+            let (vertex, positive) = key;
+            let normal = &mesh.normals[vertex as usize];
+            let (tangent, bitangent) = &sums[&key];
+            let tangent = tangent.clone() - normal.clone() * normal.dot(tangent.clone());
+            let tangent = if tangent.lenght() > 1e-12 { tangent.normalized() } else { perpendicular(normal) };
+            let w = if bitangent.lenght() > 1e-12 {
+                if normal.cross(tangent.clone()).dot(bitangent.clone()) < 0.0 { -1.0 } else { 1.0 }
+            } else if positive { 1.0 } else { -1.0 };
+
+            let index = push_vertex(&mut ret, &mesh, vertex as usize);
+            if ret.tangents.len() <= index as usize {
+                ret.tangents.push([0.0; 4]);
+            }
+            ret.tangents[index as usize] = [tangent.x, tangent.y, tangent.z, w];
+            index
+        });
+        ret.indices.push(index);
+    }
+    Ok(ret)
+}
+
+/*********************************
+*** WELDING AND TRIANGULATION
+*********************************/
+
+// merges vertices whose positions and other attributes are all within tolerance of each other, 0
+// only merges exact duplicates. triangles collapsed by the merge are removed
+pub fn weld_vertices(mesh: &Mesh, tolerance: f32) -> Mesh {
+    let mesh = resolved(mesh);
+    let tolerance = tolerance.max(0.0);
+    let cell_size = if tolerance > 0.0 { tolerance } else { 1.0 };
+    let cell = |p: &Vec3| (
+        (p.x / cell_size).floor() as i64,
+        (p.y / cell_size).floor() as i64,
+        (p.z / cell_size).floor() as i64
+    );
+
+    let mut ret = empty_like();
+    let mut cells: HashMap<(i64, i64, i64), Vec<u32>> = HashMap::new();
+    let mut remap = Vec::with_capacity(mesh.vertices.len());
+    for vertex in 0..mesh.vertices.len() {
+        let attributes = vertex_attributes(&mesh, vertex);
+        let (x, y, z) = cell(&mesh.vertices[vertex]);
+        let mut found = None;
+        'search: for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    for &candidate in cells.get(&(x + dx, y + dy, z + dz)).map(|c| c.as_slice()).unwrap_or(&[]) {
+                        let other = vertex_attributes(&ret, candidate as usize);
+                        let same_joints = mesh.joints.is_empty() || mesh.joints[vertex] == ret.joints[candidate as usize];
+                        if same_joints && attributes.iter().zip(other.iter()).all(|(a, b)| (a - b).abs() <= tolerance) {
+                            found = Some(candidate);
+                            break 'search;
+                        }
+                    }
+                }
+            }
+        }
+
+        let index = match found {
+            Some(index) => index,
+            None => {
+                let index = push_vertex(&mut ret, &mesh, vertex);
+                cells.entry((x, y, z)).or_insert_with(Vec::new).push(index);
+                index
+            }
+        };
+        remap.push(index);
+    }
+
+    for triangle in triangles(&mesh) {
+        let [a, b, c] = [remap[triangle[0] as usize], remap[triangle[1] as usize], remap[triangle[2] as usize]];
+        if a != b && b != c && c != a {
+            ret.indices.extend_from_slice(&[a, b, c]);
+        }
+    }
+    ret
+}
+
+// ear clipping of a simple, roughly planar polygon, concave ones included. returns triangles as indices
+// into points, wound the same way as the polygon
+pub fn triangulate_polygon(points: &[Vec3]) -> Vec<u32> {
+    if points.len() < 3 {
+        return vec![];
+    }
+
+    // Newell's normal, its largest axis is dropped to work in 2d
+    let mut normal = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+    for i in 0..points.len() {
+        let (a, b) = (&points[i], &points[(i + 1) % points.len()]);
+        normal.x += (a.y - b.y) * (a.z + b.z);
+        normal.y += (a.z - b.z) * (a.x + b.x);
+        normal.z += (a.x - b.x) * (a.y + b.y);
+    }
+    let (ax, ay) = if normal.x.abs() >= normal.y.abs() && normal.x.abs() >= normal.z.abs() {
+        (1, 2)
+    } else if normal.y.abs() >= normal.z.abs() {
+        (2, 0)
+    } else {
+        (0, 1)
+    };
+    let flat: Vec<(f32, f32)> = points.iter().map(|p| (p[ax], p[ay])).collect();
+    let area: f32 = (0..flat.len())
+        .map(|i| {
+            let (a, b) = (flat[i], flat[(i + 1) % flat.len()]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum();
+    let orientation = if area >= 0.0 { 1.0 } else { -1.0 };
+    let cross = |a: (f32, f32), b: (f32, f32), c: (f32, f32)| ((b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)) * orientation;
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut ret = vec![];
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&i| {
+            let (prev, current, next) = (remaining[(i + count - 1) % count], remaining[i], remaining[(i + 1) % count]);
+            let (a, b, c) = (flat[prev], flat[current], flat[next]);
+            if cross(a, b, c) <= 0.0 {
+                return false;
+            }
+            // no other corner may lie in the ear
+            remaining.iter()
+                .filter(|&&other| other != prev && other != current && other != next)
+                .all(|&other| {
+                    let p = flat[other];
+                    cross(a, b, p) < 0.0 || cross(b, c, p) < 0.0 || cross(c, a, p) < 0.0
+                })
+        });
+
+        match ear {
+            Some(i) => {
+                ret.extend_from_slice(&[
+                    remaining[(i + count - 1) % count] as u32,
+                    remaining[i] as u32,
+                    remaining[(i + 1) % count] as u32
+                ]);
+                remaining.remove(i);
+            },
+            // self intersecting or degenerate, fan out what's left
+            None => break
+        }
+    }
+    for i in 1..remaining.len() - 1 {
+        ret.extend_from_slice(&[remaining[0] as u32, remaining[i] as u32, remaining[i + 1] as u32]);
+    }
+    ret
+}
+
+// triangle list of a polygon mesh, faces given as loops of indices into positions
+pub fn triangulate_faces(positions: &[Vec3], faces: &[Vec<u32>]) -> Vec<u32> {
+    let mut ret = vec![];
+    for face in faces.iter() {
+        let points: Vec<Vec3> = face.iter().map(|index| positions[*index as usize].clone()).collect();
+        ret.extend(triangulate_polygon(&points).into_iter().map(|corner| face[corner as usize]));
+    }
+    ret
+}
+
+/*********************************
+*** DRAW ORDER OPTIMIZATION
+*********************************/
+
+const FORSYTH_CACHE_SIZE: usize = 32;
+
+// reorders the triangles for the post transform vertex cache, with Tom Forsyth's linear speed
+// algorithm: vertices score higher the more recently they were used and the fewer triangles they
+// have left, and the triangle with the best total goes next
+pub fn optimize_vertex_cache(mesh: &Mesh) -> Mesh {
+    let mut ret = resolved(mesh);
+    let triangles = triangles(&ret);
+    let vertex_count = ret.vertices.len();
+
+    let mut vertex_triangles: Vec<Vec<usize>> = vec![vec![]; vertex_count];
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        for vertex in triangle.iter() {
+            vertex_triangles[*vertex as usize].push(triangle_index);
+        }
+    }
+    let mut remaining: Vec<usize> = vertex_triangles.iter().map(|triangles| triangles.len()).collect();
+    let mut cache_position: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut vertex_scores: Vec<f32> = (0..vertex_count).map(|vertex| forsyth_score(None, remaining[vertex])).collect();
+    let mut triangle_scores: Vec<f32> = triangles.iter()
+        .map(|triangle| triangle.iter().map(|vertex| vertex_scores[*vertex as usize]).sum())
+        .collect();
+    let mut emitted = vec![false; triangles.len()];
+    let mut cache: Vec<u32> = vec![];
+    let mut order = Vec::with_capacity(triangles.len());
+
+    let mut best = None;
+    while order.len() < triangles.len() {
+        // nothing left around the cache, start over from the best triangle anywhere
+        let next = match best {
+            Some(triangle) => triangle,
+            None => (0..triangles.len())
+                .filter(|triangle| !emitted[*triangle])
+                .max_by(|a, b| triangle_scores[*a].partial_cmp(&triangle_scores[*b]).unwrap_or(Ordering::Equal))
+                .unwrap()
+        };
+        emitted[next] = true;
+        order.push(next);
+
+        for vertex in triangles[next].iter() {
+            let vertex = *vertex as usize;
+            remaining[vertex] -= 1;
+            vertex_triangles[vertex].retain(|triangle| *triangle != next);
+        }
+        let mut new_cache: Vec<u32> = triangles[next].to_vec();
+        new_cache.extend(cache.iter().filter(|vertex| !triangles[next].contains(vertex)));
+
+        // vertices pushed out of the cache lose their cache score too
+        for (position, vertex) in new_cache.iter().enumerate() {
+            let vertex = *vertex as usize;
+            cache_position[vertex] = if position < FORSYTH_CACHE_SIZE { Some(position) } else { None };
+            vertex_scores[vertex] = forsyth_score(cache_position[vertex], remaining[vertex]);
+            for triangle in vertex_triangles[vertex].iter() {
+                triangle_scores[*triangle] = triangles[*triangle].iter().map(|v| vertex_scores[*v as usize]).sum();
+            }
+        }
+        new_cache.truncate(FORSYTH_CACHE_SIZE);
+        cache = new_cache;
+
+        best = cache.iter()
+            .flat_map(|vertex| vertex_triangles[*vertex as usize].iter().cloned())
+            .max_by(|a, b| triangle_scores[*a].partial_cmp(&triangle_scores[*b]).unwrap_or(Ordering::Equal));
+    }
+
+    ret.indices = order.iter().flat_map(|triangle| triangles[*triangle].iter().cloned()).collect();
+    ret
+}
+
+fn forsyth_score(cache_position: Option<usize>, remaining_triangles: usize) -> f32 {
+    if remaining_triangles == 0 {
+        return -1.0;
+    }
+
+    let cache_score = match cache_position {
+        // the last triangle's vertices get a fixed score, so the next one doesn't just reuse them
+        Some(position) if position < 3 => 0.75,
+        Some(position) => (1.0 - (position - 3) as f32 / (FORSYTH_CACHE_SIZE - 3) as f32).powf(1.5),
+        None => 0.0
+    };
+    // vertices with few triangles left are finished first, so they leave the working set
+    cache_score + 2.0 * (remaining_triangles as f32).powf(-0.5)
+}
+
+// average number of vertex shader runs per triangle with a FIFO cache of cache_size vertices,
+// 3 without reuse and about 0.5 at best
+pub fn cache_miss_ratio(indices: &[u32], cache_size: usize) -> f32 {
+    if indices.len() < 3 {
+        return 0.0;
+    }
+
+    let mut cache = FifoCache::new(cache_size);
+    let misses: usize = indices.chunks(3).map(|triangle| cache.add(triangle)).sum();
+    misses as f32 / (indices.len() / 3) as f32
+}
+
+// vertex cache as the simpler GPUs have it, for measuring how well an index order reuses vertices
+struct FifoCache {
+    entries: Vec<u32>,
+    size: usize
+}
+
+impl FifoCache {
+    fn new(size: usize) -> FifoCache {
+        FifoCache {
+            entries: vec![],
+            size
+        }
+    }
+
+    // returns the number of misses
+    fn add(&mut self, triangle: &[u32]) -> usize {
+        let mut misses = 0;
+        for vertex in triangle.iter() {
+            if !self.entries.contains(vertex) {
+                misses += 1;
+                self.entries.push(*vertex);
+                if self.entries.len() > self.size {
+                    self.entries.remove(0);
+                }
+            }
+        }
+        misses
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+const OVERDRAW_CACHE_SIZE: usize = 16;
+
+// reorders clusters of triangles so the ones facing away from the center of the mesh come first, and
+// hide more of what's drawn after them. the mesh should be cache optimized already: clusters are cut
+// where the order restarts the vertex cache, and where the cache miss ratio of a cluster is within
+// threshold times that of the triangles around it, so 1.05 trades at most 5% of cache efficiency
+pub fn optimize_overdraw(mesh: &Mesh, threshold: f32) -> Mesh {
+    let mut ret = resolved(mesh);
+    let triangles = triangles(&ret);
+    if triangles.is_empty() {
+        return ret;
+    }
+
+    // hard boundaries, where all three vertices of a triangle miss the cache anyway
+    let mut cache = FifoCache::new(OVERDRAW_CACHE_SIZE);
+    let mut hard = vec![0];
+    for (index, triangle) in triangles.iter().enumerate().skip(1) {
+        if cache.add(triangle) == 3 {
+            hard.push(index);
+        }
+    }
+    hard.push(triangles.len());
+
+    // soft boundaries split the hard clusters further, restarting the cache for every new cluster
+    let mut clusters = vec![];
+    for bounds in hard.windows(2) {
+        let (start, end) = (bounds[0], bounds[1]);
+        let cluster_ratio = {
+            let mut cache = FifoCache::new(OVERDRAW_CACHE_SIZE);
+            let misses: usize = triangles[start..end].iter().map(|triangle| cache.add(triangle)).sum();
+            misses as f32 / (end - start) as f32
+        };
+
+        cache.clear();
+        let mut cluster_start = start;
+        let mut misses = 0;
+        for index in start..end {
+            misses += cache.add(&triangles[index]);
+            let ratio = misses as f32 / (index + 1 - cluster_start) as f32;
+            if index + 1 < end && ratio <= cluster_ratio * threshold {
+                clusters.push((cluster_start, index + 1));
+                cluster_start = index + 1;
+                misses = 0;
+                cache.clear();
+            }
+        }
+        clusters.push((cluster_start, end));
+    }
+
+    let area_weighted = |range: &(usize, usize)| {
+        let mut center = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+        let mut normal = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+        let mut area = 0.0;
+        for triangle in triangles[range.0..range.1].iter() {
+            let p: Vec<&Vec3> = triangle.iter().map(|i| &ret.vertices[*i as usize]).collect();
+            let cross = (p[1].clone() - p[0].clone()).cross(p[2].clone() - p[0].clone());
+            let triangle_area = cross.lenght() / 2.0;
+            center += (p[0].clone() + p[1].clone() + p[2].clone()) * (triangle_area / 3.0);
+            normal += cross;
+            area += triangle_area;
+        }
+        (center, normal, area)
+    };
+    let (mesh_center, _, mesh_area) = area_weighted(&(0, triangles.len()));
+    let mesh_center = if mesh_area > 0.0 { mesh_center / mesh_area } else { mesh_center };
+
+    let mut sorted: Vec<(f32, (usize, usize))> = clusters.iter()
+        .map(|range| {
+            let (center, normal, area) = area_weighted(range);
+            if area <= 0.0 || normal.lenght() <= 0.0 {
+                return (std::f32::MIN, *range);
+            }
+            ((center / area - mesh_center.clone()).dot(normal.normalized()), *range)
+        })
+        .collect();
+    // stable, clusters facing the same way keep their cache friendly order
+    sorted.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+
+    ret.indices = sorted.iter()
+        .flat_map(|(_, (start, end))| triangles[*start..*end].iter().flat_map(|triangle| triangle.iter().cloned()))
+        .collect();
+    ret
+}
+
+/*********************************
+*** SIMPLIFICATION
+*********************************/
+
+// borders and uv or normal seams are held in place by planes this much stronger than the surface
+const BOUNDARY_WEIGHT: f64 = 10.0;
+// a collapse may not turn a triangle further than this, as the cosine between old and new normal
+const MAX_FLIP_COS: f32 = 0.2;
+
+// fewer triangles by collapsing edges, the cheapest first by quadric error metrics (Garland and
+// Heckbert). a vertex always moves onto its neighbour, so the remaining vertices keep their attributes.
+// collapses that would tear a seam or flip a triangle are skipped, which can leave the mesh above
+// target_triangles
+pub fn simplify(mesh: &Mesh, target_triangles: usize) -> Mesh {
+    let mesh = resolved(mesh);
+    let mut triangles = triangles(&mesh);
+    if triangles.len() <= target_triangles {
+        return mesh;
+    }
+
+    // the topology is worked on by position, vertices at one position are split by their attributes
+    let mut position_ids: HashMap<[u32; 3], usize> = HashMap::new();
+    let mut positions: Vec<Vec3> = vec![];
+    let vertex_position: Vec<usize> = mesh.vertices.iter()
+        .map(|vertex| *position_ids.entry(position_key(vertex)).or_insert_with(|| {
+            positions.push(vertex.clone());
+            positions.len() - 1
+        }))
+        .collect();
+    let mut position_triangles: Vec<Vec<usize>> = vec![vec![]; positions.len()];
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        for vertex in triangle.iter() {
+            let position = vertex_position[*vertex as usize];
+            if !position_triangles[position].contains(&triangle_index) {
+                position_triangles[position].push(triangle_index);
+            }
+        }
+    }
+
+    let mut quadrics = vec![Quadric::default(); positions.len()];
+    // triangles and vertex pairs along every edge, to find borders and seams
+    let mut edges: HashMap<(usize, usize), Vec<(usize, (u32, u32))>> = HashMap::new();
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        let p: Vec<&Vec3> = triangle.iter().map(|i| &mesh.vertices[*i as usize]).collect();
+        let cross = (p[1].clone() - p[0].clone()).cross(p[2].clone() - p[0].clone());
+        let area = cross.lenght() / 2.0;
+        if area > 0.0 {
+            let plane = Quadric::plane(&cross.normalized(), p[0], f64::from(area));
+            for vertex in triangle.iter() {
+                quadrics[vertex_position[*vertex as usize]].add(&plane);
+            }
+        }
+        for corner in 0..3 {
+            let (a, b) = (triangle[corner], triangle[(corner + 1) % 3]);
+            let (pa, pb) = (vertex_position[a as usize], vertex_position[b as usize]);
+            let key = if pa < pb { (pa, pb) } else { (pb, pa) };
+            let pair = if pa < pb { (a, b) } else { (b, a) };
+            edges.entry(key).or_insert_with(Vec::new).push((triangle_index, pair));
+        }
+    }
+    for (&(pa, pb), uses) in edges.iter() {
+        let first_pair = uses[0].1;
+        let constrained = uses.len() == 1 || uses.iter().any(|(_, pair)| *pair != first_pair);
+        if !constrained {
+            continue;
+        }
+        // a plane through the edge, perpendicular to each face along it
+        let edge = positions[pb].clone() - positions[pa].clone();
+        for (triangle_index, _) in uses.iter() {
+            let normal = face_normal(&mesh, &triangles[*triangle_index]);
+            let side = edge.cross(normal);
+            if side.lenght() > 0.0 {
+                let plane = Quadric::plane(&side.normalized(), &positions[pa], BOUNDARY_WEIGHT * f64::from(edge.lenght().powi(2)));
+                quadrics[pa].add(&plane);
+                quadrics[pb].add(&plane);
+            }
+        }
+    }
+
+    let mut alive = vec![true; triangles.len()];
+    let mut triangle_count = triangles.len();
+    let mut removed = vec![false; positions.len()];
+    let mut versions = vec![0u32; positions.len()];
+    let mut vertices_at: Vec<Vec<u32>> = vec![vec![]; positions.len()];
+    for (vertex, position) in vertex_position.iter().enumerate() {
+        vertices_at[*position].push(vertex as u32);
+    }
+
+    let mut heap = BinaryHeap::new();
+    {
+        let push = |from: usize, to: usize, heap: &mut BinaryHeap<Collapse>, versions: &[u32], quadrics: &[Quadric]| {
+            let mut quadric = quadrics[from].clone();
+            quadric.add(&quadrics[to]);
+            heap.push(Collapse { cost: quadric.error(&positions[to]), from, to, versions: (versions[from], versions[to]) });
+        };
+        for &(pa, pb) in edges.keys() {
+            push(pa, pb, &mut heap, &versions, &quadrics);
+            push(pb, pa, &mut heap, &versions, &quadrics);
+        }
+
+        while triangle_count > target_triangles {
+            let collapse = match heap.pop() {
+                Some(collapse) => collapse,
+                None => break
+            };
+            let (from, to) = (collapse.from, collapse.to);
+            if removed[from] || removed[to] || collapse.versions != (versions[from], versions[to]) {
+                continue;
+            }
+
+            let around: Vec<usize> = position_triangles[from].iter().cloned().filter(|t| alive[*t]).collect();
+            let position_of = |vertex: u32| vertex_position[vertex as usize];
+
+            // every vertex at from moves onto the vertex at to it shares an edge with, seams stay intact
+            let mut moves: HashMap<u32, u32> = HashMap::new();
+            let mut valid = true;
+            for vertex in vertices_at[from].iter() {
+                let used: Vec<&usize> = around.iter().filter(|t| triangles[**t].contains(vertex)).collect();
+                if used.is_empty() {
+                    continue;
+                }
+                let target = used.iter()
+                    .flat_map(|t| triangles[**t].iter())
+                    .find(|v| position_of(**v) == to);
+                match target {
+                    Some(target) => { moves.insert(*vertex, *target); },
+                    None => { valid = false; break; }
+                }
+            }
+
+            // the link condition: the only neighbours both ends share are the corners across the edge,
+            // anything else would pinch the surface into a non manifold one
+            if valid {
+                let neighbours = |position: usize| -> HashSet<usize> {
+                    position_triangles[position].iter()
+                        .filter(|t| alive[**t])
+                        .flat_map(|t| triangles[*t].iter().map(|v| vertex_position[*v as usize]))
+                        .filter(|other| *other != position)
+                        .collect()
+                };
+                let shared = neighbours(from).intersection(&neighbours(to)).count();
+                let across = around.iter().filter(|t| touches(&triangles[**t], to, &vertex_position)).count();
+                valid = shared == across;
+            }
+
+            // and no remaining triangle may fold over
+            if valid {
+                for triangle in around.iter().filter(|t| !touches(&triangles[**t], to, &vertex_position)) {
+                    let before: Vec<Vec3> = triangles[*triangle].iter().map(|v| positions[position_of(*v)].clone()).collect();
+                    let after: Vec<Vec3> = triangles[*triangle].iter()
+                        .map(|v| positions[if position_of(*v) == from { to } else { position_of(*v) }].clone())
+                        .collect();
+                    let old_normal = (before[1].clone() - before[0].clone()).cross(before[2].clone() - before[0].clone());
+                    let new_normal = (after[1].clone() - after[0].clone()).cross(after[2].clone() - after[0].clone());
+                    if new_normal.lenght() <= 0.0 || old_normal.lenght() <= 0.0
+                        || old_normal.normalized().dot(new_normal.normalized()) < MAX_FLIP_COS {
+                        valid = false;
+                        break;
+                    }
+                }
+            }
+            if !valid {
+                continue;
+            }
+
+            for triangle in around {
+                if touches(&triangles[triangle], to, &vertex_position) {
+                    alive[triangle] = false;
+                    triangle_count -= 1;
+                } else {
+                    for vertex in triangles[triangle].iter_mut() {
+                        if let Some(target) = moves.get(vertex) {
+                            *vertex = *target;
+                        }
+                    }
+                    position_triangles[to].push(triangle);
+                }
+            }
+            removed[from] = true;
+            position_triangles[from].clear();
+            vertices_at[from].clear();
+            let from_quadric = quadrics[from].clone();
+            quadrics[to].add(&from_quadric);
+            versions[to] += 1;
+
+            let neighbours: HashSet<usize> = position_triangles[to].iter()
+                .filter(|t| alive[**t])
+                .flat_map(|t| triangles[*t].iter().map(|v| vertex_position[*v as usize]))
+                .filter(|position| *position != to)
+                .collect();
+            for neighbour in neighbours {
+                push(to, neighbour, &mut heap, &versions, &quadrics);
+                push(neighbour, to, &mut heap, &versions, &quadrics);
+            }
+        }
+    }
+
+    // keep the surviving triangles and the vertices they use
+    let mut ret = empty_like();
+    let mut remap: HashMap<u32, u32> = HashMap::new();
+    for (triangle, _) in triangles.iter().zip(alive.iter()).filter(|(_, alive)| **alive) {
+        for vertex in triangle.iter() {
+            let index = *remap.entry(*vertex).or_insert_with(|| push_vertex(&mut ret, &mesh, *vertex as usize));
+            ret.indices.push(index);
+        }
+    }
+    ret
+}
+
+// level 0 is the mesh itself, every further level has ratio times the triangles of the one before,
+// simplified from the full mesh. stops early when the simplification can't go further
+pub fn generate_lods(mesh: &Mesh, levels: usize, ratio: f32) -> Vec<Mesh> {
+    let full = resolved(mesh);
+    let full_triangles = full.indices.len() / 3;
+    let mut ret = vec![full];
+    for level in 1..levels {
+        let target = (full_triangles as f32 * ratio.powi(level as i32)).round() as usize;
+        let lod = simplify(&ret[0], target.max(1));
+        if lod.indices.len() >= ret[ret.len() - 1].indices.len() {
+            break;
+        }
+        ret.push(lod);
+    }
+    ret
+}
+
+#[derive(Debug, Clone, Default)]
+struct Quadric {
+    // upper triangle of the symmetric 4x4 matrix, row by row
+    m: [f64; 10]
+}
+
+impl Quadric {
+    // squared distance to the plane through point with the given unit normal
+    fn plane(normal: &Vec3, point: &Vec3, weight: f64) -> Quadric {
+        let (a, b, c) = (f64::from(normal.x), f64::from(normal.y), f64::from(normal.z));
+        let d = -(a * f64::from(point.x) + b * f64::from(point.y) + c * f64::from(point.z));
+        Quadric {
+            m: [
+                a * a * weight, a * b * weight, a * c * weight, a * d * weight,
+                b * b * weight, b * c * weight, b * d * weight,
+                c * c * weight, c * d * weight,
+                d * d * weight
+            ]
+        }
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for i in 0..10 {
+            self.m[i] += other.m[i];
+        }
+    }
+
+    fn error(&self, p: &Vec3) -> f64 {
+        let (x, y, z) = (f64::from(p.x), f64::from(p.y), f64::from(p.z));
+        let m = &self.m;
+        m[0] * x * x + 2.0 * m[1] * x * y + 2.0 * m[2] * x * z + 2.0 * m[3] * x
+            + m[4] * y * y + 2.0 * m[5] * y * z + 2.0 * m[6] * y
+            + m[7] * z * z + 2.0 * m[8] * z
+            + m[9]
+    }
+}
+
+// candidate edge collapse, ordered so the heap pops the cheapest first
+struct Collapse {
+    cost: f64,
+    from: usize,
+    to: usize,
+    // versions of both positions when the cost was computed, stale entries are skipped
+    versions: (u32, u32)
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Collapse) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Collapse) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Collapse) -> Ordering {
+        other.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal)
+            .then_with(|| other.from.cmp(&self.from))
+            .then_with(|| other.to.cmp(&self.to))
+    }
+}
+
+/*********************************
+*** HELPERS
+*********************************/
+
+// copy of the mesh with normals and uvs per vertex and a triangle list in indices
+fn resolved(mesh: &Mesh) -> Mesh {
+    let mut ret = mesh.clone();
+    let vertex_count = mesh.vertices.len();
+    if !mesh.normals.is_empty() {
+        ret.normals = (0..vertex_count).map(|vertex| mesh.vertex_normal(vertex)).collect();
+    }
+    if !mesh.uvs.is_empty() {
+        ret.uvs = (0..vertex_count)
+            .map(|vertex| {
+                let uv_index = mesh.uv_indices.get(vertex).map(|n| *n as usize).unwrap_or(vertex);
+                mesh.uvs.get(uv_index).cloned().unwrap_or(Vec3 { x: 0.0, y: 0.0, z: 0.0 })
+            })
+            .collect();
+    }
+    ret.normal_indices.clear();
+    ret.uv_indices.clear();
+    ret.indices = mesh.triangle_list().into_iter().map(|index| index as u32).collect();
+    ret.indices.truncate(ret.indices.len() / 3 * 3);
+    ret
+}
+
+fn empty_like() -> Mesh {
+    Mesh::new(vec![], vec![], vec![], vec![], vec![], vec![])
+}
+
+fn triangles(mesh: &Mesh) -> Vec<[u32; 3]> {
+    mesh.indices.chunks(3).map(|triangle| [triangle[0], triangle[1], triangle[2]]).collect()
+}
+
+// copies vertex i of from with all its streams, returns its index in to
+fn push_vertex(to: &mut Mesh, from: &Mesh, i: usize) -> u32 {
+    to.vertices.push(from.vertices[i].clone());
+    if !from.normals.is_empty() {
+        to.normals.push(from.normals[i].clone());
+    }
+    if !from.uvs.is_empty() {
+        to.uvs.push(from.uvs[i].clone());
+    }
+    if !from.tangents.is_empty() {
+        to.tangents.push(from.tangents[i]);
+    }
+    if !from.colors.is_empty() {
+        to.colors.push(from.colors[i]);
+    }
+    if !from.joints.is_empty() {
+        to.joints.push(from.joints[i]);
+    }
+    if !from.weights.is_empty() {
+        to.weights.push(from.weights[i]);
+    }
+    to.vertices.len() as u32 - 1
+}
+
+// every float of a vertex, for comparing vertices. joints are compared separately
+fn vertex_attributes(mesh: &Mesh, i: usize) -> Vec<f32> {
+    let mut ret = vec![mesh.vertices[i].x, mesh.vertices[i].y, mesh.vertices[i].z];
+    if let Some(normal) = mesh.normals.get(i) {
+        ret.extend_from_slice(&[normal.x, normal.y, normal.z]);
+    }
+    if let Some(uv) = mesh.uvs.get(i) {
+        ret.extend_from_slice(&[uv.x, uv.y]);
+    }
+    for stream in [&mesh.tangents, &mesh.colors, &mesh.weights].iter() {
+        if let Some(value) = stream.get(i) {
+            ret.extend_from_slice(value);
+        }
+    }
+    ret
+}
+
+// exact position, with -0 and 0 the same
+fn position_key(p: &Vec3) -> [u32; 3] {
+    [(p.x + 0.0).to_bits(), (p.y + 0.0).to_bits(), (p.z + 0.0).to_bits()]
+}
+
+fn touches(triangle: &[u32; 3], position: usize, vertex_position: &[usize]) -> bool {
+    triangle.iter().any(|vertex| vertex_position[*vertex as usize] == position)
+}
+
+fn face_normal(mesh: &Mesh, triangle: &[u32; 3]) -> Vec3 {
+    let p: Vec<&Vec3> = triangle.iter().map(|i| &mesh.vertices[*i as usize]).collect();
+    let normal = (p[1].clone() - p[0].clone()).cross(p[2].clone() - p[0].clone());
+    if normal.lenght() > 0.0 { normal.normalized() } else { normal }
+}
+
+fn corner_angle(mesh: &Mesh, triangle: &[u32; 3], corner: usize) -> f32 {
+    let p = &mesh.vertices[triangle[corner] as usize];
+    let a = mesh.vertices[triangle[(corner + 1) % 3] as usize].clone() - p.clone();
+    let b = mesh.vertices[triangle[(corner + 2) % 3] as usize].clone() - p.clone();
+    let lengths = a.lenght() * b.lenght();
+    if lengths <= 0.0 {
+        return 0.0;
+    }
+    (a.dot(b) / lengths).max(-1.0).min(1.0).acos()
+}
+
+fn perpendicular(normal: &Vec3) -> Vec3 {
+    let axis = if normal.x.abs() < 0.9 { Vec3 { x: 1.0, y: 0.0, z: 0.0 } } else { Vec3 { x: 0.0, y: 1.0, z: 0.0 } };
+    let along = normal.dot(axis.clone());
+    let tangent = axis - normal.clone() * along;
+    tangent.normalized()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn assert_near(a: &Vec3, b: &Vec3) {
+        assert!((a.clone() - b.clone()).lenght() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    // twice the signed area of every triangle of a polygon in the xy plane
+    fn signed_areas(points: &[Vec3], indices: &[u32]) -> Vec<f32> {
+        indices.chunks(3)
+            .map(|t| {
+                let (a, b, c) = (&points[t[0] as usize], &points[t[1] as usize], &points[t[2] as usize]);
+                (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+            })
+            .collect()
+    }
+
+    fn area(mesh: &Mesh) -> f32 {
+        triangles(mesh).iter()
+            .map(|t| {
+                let (a, b, c) = (&mesh.vertices[t[0] as usize], &mesh.vertices[t[1] as usize], &mesh.vertices[t[2] as usize]);
+                (b.clone() - a.clone()).cross(c.clone() - a.clone()).lenght() / 2.0
+            })
+            .sum()
+    }
+
+    // unit cube with one vertex per corner, no normals or uvs
+    fn shared_cube() -> Mesh {
+        let mut cube = Mesh::cube(1.0, 1);
+        cube.normals.clear();
+        cube.uvs.clear();
+        weld_vertices(&cube, 0.0)
+    }
+
+    #[test]
+    fn cube_normals_are_hard_at_the_edges() {
+        let cube = shared_cube();
+        assert_eq!(cube.vertices.len(), 8);
+
+        let flat = compute_normals(&cube, 30.0);
+        assert_eq!(flat.vertices.len(), 24);
+        assert_eq!(flat.indices.len(), 36);
+        for t in triangles(&flat) {
+            let face = face_normal(&flat, &t);
+            // axis aligned and pointing out of the cube
+            assert_near(&face, &vec3(face.x.round(), face.y.round(), face.z.round()));
+            assert!(face.dot(flat.vertices[t[0] as usize].clone()) > 0.0);
+            for corner in t.iter() {
+                assert_near(&flat.normals[*corner as usize], &face);
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_cube_normals_point_along_the_diagonals() {
+        let smooth = compute_smooth_normals(&shared_cube());
+        assert_eq!(smooth.vertices.len(), 8);
+        for (position, normal) in smooth.vertices.iter().zip(smooth.normals.iter()) {
+            assert_near(normal, &position.normalized());
+        }
+    }
+
+    #[test]
+    fn quad_tangents_follow_the_uvs() {
+        // facing up, u along x and v along z
+        let positions = vec![vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(1.0, 0.0, 1.0), vec3(0.0, 0.0, 1.0)];
+        let uvs = positions.iter().map(|p| vec3(p.x, p.z, 0.0)).collect();
+        let normals = vec![vec3(0.0, 1.0, 0.0); 4];
+        let quad = Mesh::new(positions, vec![0, 2, 1, 0, 3, 2], uvs, vec![], normals, vec![]);
+
+        let with_tangents = compute_tangents(&quad).expect("failed to compute tangents");
+        assert_eq!(with_tangents.vertices.len(), 4);
+        for (tangent, normal) in with_tangents.tangents.iter().zip(with_tangents.normals.iter()) {
+            let direction = vec3(tangent[0], tangent[1], tangent[2]);
+            assert_near(&direction, &vec3(1.0, 0.0, 0.0));
+            assert_near(&(normal.cross(direction) * tangent[3]), &vec3(0.0, 0.0, 1.0));
+        }
+
+        let mut without_uvs = quad.clone();
+        without_uvs.uvs.clear();
+        assert!(compute_tangents(&without_uvs).is_err());
+    }
+
+    #[test]
+    fn welding_merges_duplicated_vertices() {
+        // two triangles of a quad with their own copies of the shared edge, one copy slightly off
+        let positions = vec![
+            vec3(0.0, 0.0, 0.0), vec3(1.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0), vec3(1.0, 1.0005, 0.0)
+        ];
+        let quad = Mesh::new(positions, vec![0, 1, 2, 3, 4, 5], vec![], vec![], vec![], vec![]);
+
+        let exact = weld_vertices(&quad, 0.0);
+        assert_eq!(exact.vertices.len(), 5);
+        assert_eq!(exact.indices, vec![0, 1, 2, 0, 3, 4]);
+
+        let close = weld_vertices(&quad, 0.001);
+        assert_eq!(close.vertices.len(), 4);
+        assert_eq!(close.indices, vec![0, 1, 2, 0, 3, 1]);
+
+        // triangles that collapse to an edge go away
+        let sliver = weld_vertices(&Mesh::new(vec![vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0)], vec![0, 1, 2], vec![], vec![], vec![], vec![]), 0.0);
+        assert!(sliver.indices.is_empty());
+    }
+
+    #[test]
+    fn welding_keeps_vertices_with_different_attributes() {
+        // the cube's faces meet at hard edges, every corner has three normals
+        let cube = Mesh::cube(1.0, 1);
+        let mut without_uvs = cube.clone();
+        without_uvs.uvs.clear();
+        assert_eq!(weld_vertices(&without_uvs, 0.0).vertices.len(), 24);
+    }
+
+    #[test]
+    fn concave_polygons_triangulate_inside() {
+        // an L with its reflex corner at (1, 1), counter clockwise
+        let points = vec![
+            vec3(0.0, 0.0, 0.0), vec3(2.0, 0.0, 0.0), vec3(2.0, 1.0, 0.0),
+            vec3(1.0, 1.0, 0.0), vec3(1.0, 2.0, 0.0), vec3(0.0, 2.0, 0.0)
+        ];
+        let indices = triangulate_polygon(&points);
+        assert_eq!(indices.len(), 12);
+        let areas = signed_areas(&points, &indices);
+        // same winding as the polygon, and together exactly its area
+        assert!(areas.iter().all(|area| *area > 0.0), "{:?}", areas);
+        assert!((areas.iter().sum::<f32>() / 2.0 - 3.0).abs() < 1e-5);
+
+        // the other way around keeps its winding too
+        let reversed: Vec<Vec3> = points.iter().rev().cloned().collect();
+        let areas = signed_areas(&reversed, &triangulate_polygon(&reversed));
+        assert!(areas.iter().all(|area| *area < 0.0), "{:?}", areas);
+
+        assert!(triangulate_polygon(&points[..2]).is_empty());
+    }
+
+    #[test]
+    fn faces_triangulate_into_the_shared_positions() {
+        let positions = vec![vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(1.0, 1.0, 0.0), vec3(0.0, 1.0, 0.0), vec3(2.0, 0.0, 0.0)];
+        let indices = triangulate_faces(&positions, &[vec![0, 1, 2, 3], vec![1, 4, 2]]);
+        assert_eq!(indices.len(), 9);
+        assert_eq!(&indices[6..], &[1, 4, 2]);
+        assert!(signed_areas(&positions, &indices).iter().all(|area| *area > 0.0));
+    }
+
+    #[test]
+    fn simplification_keeps_the_boundary() {
+        let plane = Mesh::plane(2.0, 2.0, 8, 8);
+        assert_eq!(plane.indices.len() / 3, 128);
+
+        let simple = simplify(&plane, 16);
+        let triangle_count = simple.indices.len() / 3;
+        assert!(triangle_count <= 32, "{} triangles", triangle_count);
+        // a flat plane can go down a lot without moving its border, which keeps the area and corners
+        assert!((area(&simple) - 4.0).abs() < 1e-4, "area {}", area(&simple));
+        for corner in [vec3(-1.0, 0.0, -1.0), vec3(1.0, 0.0, -1.0), vec3(1.0, 0.0, 1.0), vec3(-1.0, 0.0, 1.0)].iter() {
+            assert!(simple.vertices.iter().any(|vertex| (vertex.clone() - corner.clone()).lenght() < 1e-5), "{:?} lost", corner);
+        }
+        for t in triangles(&simple) {
+            assert!(face_normal(&simple, &t).y > 0.99);
+        }
+
+        // already at the target, nothing changes
+        assert_eq!(simplify(&simple, triangle_count).indices.len(), simple.indices.len());
+    }
+
+    #[test]
+    fn lods_get_fewer_triangles() {
+        let sphere = Mesh::uv_sphere(1.0, 16, 8);
+        let lods = generate_lods(&sphere, 3, 0.5);
+        assert!(lods.len() >= 2);
+        for pair in lods.windows(2) {
+            assert!(pair[1].indices.len() < pair[0].indices.len());
+        }
+    }
+}
\ No newline at end of file
@@ -1,3 +1,20 @@
+pub mod camera;
 pub mod core;
+pub mod debug_draw;
+pub mod debug_render;
+pub mod ibl;
+pub mod instancing;
+pub mod light;
+pub mod lod;
+pub mod material;
 pub mod mesh;
+pub mod mesh_processing;
+pub mod pbr;
+pub mod post;
+pub mod post_process;
+pub mod render_graph;
+pub mod shader;
+pub mod shadow;
+pub mod shadow_map;
+pub mod spirv;
 pub mod texture;
\ No newline at end of file
@@ -0,0 +1,328 @@
+use super::ibl::{Cubemap, BrdfLut, IblMaps, IblSettings};
+use super::texture::Texture;
+use crate::math::vec3::Vec3;
+
+use std::sync::Arc;
+
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::image::{Dimensions, ImageLayout, ImageUsage, MipmapsCount, ImmutableImage};
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBuffer};
+use vulkano::sampler::{Sampler, Filter, MipmapMode, SamplerAddressMode};
+use vulkano::sync::GpuFuture;
+
+pub mod vs {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        src: "
+        #version 450
+        #extension GL_ARB_separate_shader_objects : enable
+
+        layout(location = 0) in vec3 position;
+        layout(location = 1) in vec3 normal;
+        layout(location = 2) in vec2 uv;
+
+        layout(location = 0) out vec3 world_position;
+        layout(location = 1) out vec3 world_normal;
+        layout(location = 2) out vec2 frag_uv;
+
+        layout(set = 0, binding = 0) uniform FrameData {
+            mat4 view_proj;
+        } frame;
+
+        void main() {
+            vec4 clip = frame.view_proj * vec4(position, 1.0);
+            gl_Position = vec4(clip.x, -clip.y, clip.z, clip.w);
+            world_position = position;
+            world_normal = normal;
+            frag_uv = uv;
+        }"
+    }
+}
+
+// compiled when the renderer starts, so it can include the shadow sampling it shares with the lit shader
+pub const FS_GLSL: &str = r#"
+        #version 450
+        #extension GL_ARB_separate_shader_objects : enable
+
+        const float PI = 3.14159265359;
+
+        const uint DIRECTIONAL_LIGHT = 0;
+        const uint POINT_LIGHT = 1;
+        const uint SPOT_LIGHT = 2;
+
+        struct Light {
+            vec4 position_kind;
+            vec4 direction;
+            vec4 color_intensity;
+            vec4 attenuation; // constant, linear, quadratic, range
+            vec4 cone; // cos inner, cos outer
+            vec4 shadow; // first shadow, shadow count
+        };
+
+        struct Shadow {
+            mat4 view_proj;
+            vec4 atlas_rect; // uv offset, uv scale
+            vec4 params; // split far, constant bias, slope bias, normal offset
+        };
+
+        layout(location = 0) in vec3 world_position;
+        layout(location = 1) in vec3 world_normal;
+        layout(location = 2) in vec2 frag_uv;
+
+        layout(location = 0) out vec4 f_color;
+
+        layout(set = 0, binding = 0) uniform FrameData {
+            mat4 view_proj;
+            vec4 camera_position;
+            vec4 camera_forward;
+            vec4 ambient;
+            uvec4 light_count;
+            vec4 shadow_params; // pcf radius, atlas texel size
+        } frame;
+
+        layout(set = 0, binding = 1) readonly buffer LightBuffer {
+            Light lights[];
+        } light_buffer;
+
+        layout(set = 0, binding = 2) uniform samplerCube irradiance_map;
+        layout(set = 0, binding = 3) uniform samplerCube prefiltered_map;
+        layout(set = 0, binding = 4) uniform sampler2D brdf_lut;
+
+        layout(set = 0, binding = 5) readonly buffer ShadowBuffer {
+            Shadow shadows[];
+        } shadow_buffer;
+
+        layout(set = 0, binding = 6) uniform sampler2D shadow_atlas;
+
+        layout(set = 1, binding = 0) uniform sampler2D base_color_map;
+        layout(set = 1, binding = 1) uniform sampler2D metallic_roughness_map;
+        layout(set = 1, binding = 2) uniform sampler2D normal_map;
+        layout(set = 1, binding = 3) uniform sampler2D occlusion_map;
+        layout(set = 1, binding = 4) uniform sampler2D emissive_map;
+
+        layout(push_constant) uniform PbrConstants {
+            vec4 base_color; // rgb + opacity
+            vec4 emissive_metallic; // emissive rgb + metallic
+            vec4 parameters; // roughness, occlusion strength, normal scale, max reflection lod
+            vec4 flags; // receives shadows
+        } material;
+
+        #include "shadow.glsl"
+
+        // normal mapping without precomputed tangents, using screen space derivatives
+        vec3 perturb_normal(vec3 n, vec3 p, vec2 uv) {
+            vec3 tangent_normal = texture(normal_map, uv).xyz * 2.0 - 1.0;
+            tangent_normal.xy *= material.parameters.z;
+
+            vec3 dp1 = dFdx(p);
+            vec3 dp2 = dFdy(p);
+            vec2 duv1 = dFdx(uv);
+            vec2 duv2 = dFdy(uv);
+
+            vec3 dp2perp = cross(dp2, n);
+            vec3 dp1perp = cross(n, dp1);
+            vec3 t = dp2perp * duv1.x + dp1perp * duv2.x;
+            vec3 b = dp2perp * duv1.y + dp1perp * duv2.y;
+            float invmax = inversesqrt(max(dot(t, t), dot(b, b)));
+            if (isinf(invmax) || isnan(invmax)) {
+                return n;
+            }
+            mat3 tbn = mat3(t * invmax, b * invmax, n);
+            return normalize(tbn * tangent_normal);
+        }
+
+        float distribution_ggx(float n_dot_h, float roughness) {
+            float a = roughness * roughness;
+            float a2 = a * a;
+            float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+            return a2 / (PI * d * d);
+        }
+
+        float geometry_schlick_ggx(float n_dot_v, float k) {
+            return n_dot_v / (n_dot_v * (1.0 - k) + k);
+        }
+
+        float geometry_smith(float n_dot_v, float n_dot_l, float roughness) {
+            float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+            return geometry_schlick_ggx(n_dot_v, k) * geometry_schlick_ggx(n_dot_l, k);
+        }
+
+        vec3 fresnel_schlick(float cos_theta, vec3 f0) {
+            return f0 + (1.0 - f0) * pow(max(1.0 - cos_theta, 0.0), 5.0);
+        }
+
+        vec3 fresnel_schlick_roughness(float cos_theta, vec3 f0, float roughness) {
+            return f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(max(1.0 - cos_theta, 0.0), 5.0);
+        }
+
+        void main() {
+            vec4 base_color = texture(base_color_map, frag_uv) * material.base_color;
+            vec4 metallic_roughness = texture(metallic_roughness_map, frag_uv);
+            float metallic = clamp(material.emissive_metallic.a * metallic_roughness.b, 0.0, 1.0);
+            float roughness = clamp(material.parameters.x * metallic_roughness.g, 0.04, 1.0);
+            float occlusion = mix(1.0, texture(occlusion_map, frag_uv).r, material.parameters.y);
+            vec3 emissive = texture(emissive_map, frag_uv).rgb * material.emissive_metallic.rgb;
+
+            vec3 n = perturb_normal(normalize(world_normal), world_position, frag_uv);
+            vec3 v = normalize(frame.camera_position.xyz - world_position);
+            float n_dot_v = max(dot(n, v), 0.0001);
+            vec3 f0 = mix(vec3(0.04), base_color.rgb, metallic);
+
+            vec3 lo = vec3(0.0);
+            for (uint i = 0; i < frame.light_count.x; i++) {
+                Light light = light_buffer.lights[i];
+                uint kind = uint(light.position_kind.w);
+
+                vec3 l;
+                float attenuation = 1.0;
+                if (kind == DIRECTIONAL_LIGHT) {
+                    l = normalize(-light.direction.xyz);
+                } else {
+                    vec3 to_light = light.position_kind.xyz - world_position;
+                    float d = length(to_light);
+                    l = to_light / d;
+                    attenuation = 1.0 / (light.attenuation.x + light.attenuation.y * d + light.attenuation.z * d * d);
+                    if (light.attenuation.w > 0.0 && d > light.attenuation.w) {
+                        attenuation = 0.0;
+                    }
+                    if (kind == SPOT_LIGHT) {
+                        float theta = dot(-l, light.direction.xyz);
+                        float epsilon = max(light.cone.x - light.cone.y, 0.0001);
+                        attenuation *= clamp((theta - light.cone.y) / epsilon, 0.0, 1.0);
+                    }
+                }
+
+                if (material.flags.x > 0.0) {
+                    attenuation *= shadow_factor(light, world_position, n, l);
+                }
+
+                vec3 radiance = light.color_intensity.rgb * light.color_intensity.w * attenuation;
+                vec3 h = normalize(v + l);
+                float n_dot_l = max(dot(n, l), 0.0);
+                float n_dot_h = max(dot(n, h), 0.0);
+
+                vec3 f = fresnel_schlick(max(dot(h, v), 0.0), f0);
+                vec3 specular = distribution_ggx(n_dot_h, roughness) * geometry_smith(n_dot_v, n_dot_l, roughness) * f
+                    / max(4.0 * n_dot_v * n_dot_l, 0.0001);
+                vec3 k_d = (vec3(1.0) - f) * (1.0 - metallic);
+                lo += (k_d * base_color.rgb / PI + specular) * radiance * n_dot_l;
+            }
+
+            // image based lighting, split-sum approximation
+            vec3 f = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+            vec3 k_d = (vec3(1.0) - f) * (1.0 - metallic);
+            vec3 diffuse = texture(irradiance_map, n).rgb * base_color.rgb;
+            vec3 r = reflect(-v, n);
+            vec3 prefiltered = textureLod(prefiltered_map, r, roughness * material.parameters.w).rgb;
+            vec2 brdf = texture(brdf_lut, vec2(n_dot_v, roughness)).rg;
+            vec3 specular = prefiltered * (f * brdf.x + brdf.y);
+            vec3 ambient = (k_d * diffuse + specular + frame.ambient.rgb * base_color.rgb) * occlusion;
+
+            f_color = vec4(ambient + lo + emissive, base_color.a);
+        }"#;
+
+// std140 layout of the PbrConstants push constant block
+#[derive(Copy, Clone)]
+pub struct PbrConstants {
+    pub base_color: [f32; 4],
+    pub emissive_metallic: [f32; 4],
+    pub parameters: [f32; 4],
+    pub flags: [f32; 4]
+}
+
+#[derive(Copy, Clone)]
+pub struct PbrVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2]
+}
+impl_vertex!(PbrVertex, position, normal, uv);
+
+// ibl maps living on the gpu, bound to set 0 of the pbr pipeline
+pub struct GpuEnvironment {
+    pub irradiance: Arc<ImmutableImage<Format>>,
+    pub prefiltered: Arc<ImmutableImage<Format>>,
+    pub prefiltered_levels: u32,
+    pub brdf_lut: Arc<ImmutableImage<Format>>,
+    pub sampler: Arc<Sampler>
+}
+
+impl GpuEnvironment {
+    pub fn new(device: &Arc<Device>, queue: &Arc<Queue>, maps: &IblMaps) -> GpuEnvironment {
+        let sampler = Sampler::new(device.clone(), Filter::Linear, Filter::Linear, MipmapMode::Linear,
+            SamplerAddressMode::ClampToEdge, SamplerAddressMode::ClampToEdge, SamplerAddressMode::ClampToEdge,
+            0.0, 1.0, 0.0, maps.prefiltered.len() as f32).unwrap();
+
+        GpuEnvironment {
+            irradiance: upload_cubemap_levels(device, queue, &[maps.irradiance.clone()]),
+            prefiltered: upload_cubemap_levels(device, queue, &maps.prefiltered),
+            prefiltered_levels: maps.prefiltered.len() as u32,
+            brdf_lut: upload_brdf_lut(queue, &maps.brdf_lut),
+            sampler
+        }
+    }
+
+    // small uniformly lit environment, bound until the game provides its own
+    pub fn neutral(device: &Arc<Device>, queue: &Arc<Queue>) -> GpuEnvironment {
+        let settings = IblSettings {
+            irradiance_size: 1,
+            irradiance_samples: 1,
+            prefiltered_size: 1,
+            prefiltered_levels: 1,
+            prefiltered_samples: 1,
+            brdf_lut_size: 32,
+            brdf_lut_samples: 64
+        };
+        let environment = Cubemap::from_fn(1, |_| Vec3 { x: 0.03, y: 0.03, z: 0.03 });
+        GpuEnvironment::new(device, queue, &super::ibl::bake(&environment, &settings))
+    }
+}
+
+pub fn upload_texture(queue: &Arc<Queue>, texture: &Texture) -> Arc<ImmutableImage<Format>> {
+    let format = if texture.srgb { Format::R8G8B8A8Srgb } else { Format::R8G8B8A8Unorm };
+    let (image, future) = ImmutableImage::from_iter(texture.pixels.iter().cloned(),
+        Dimensions::Dim2d { width: texture.width, height: texture.height }, format, queue.clone())
+        .expect("failed to upload texture");
+    future.then_signal_fence_and_flush().unwrap().wait(None).unwrap();
+
+    image
+}
+
+pub fn upload_brdf_lut(queue: &Arc<Queue>, lut: &BrdfLut) -> Arc<ImmutableImage<Format>> {
+    let (image, future) = ImmutableImage::from_iter(lut.data.iter().cloned(),
+        Dimensions::Dim2d { width: lut.size, height: lut.size }, Format::R32G32Sfloat, queue.clone())
+        .expect("failed to upload brdf lut");
+    future.then_signal_fence_and_flush().unwrap().wait(None).unwrap();
+
+    image
+}
+
+// uploads each cubemap as one mip level of a single cubemap image
+pub fn upload_cubemap_levels(device: &Arc<Device>, queue: &Arc<Queue>, levels: &[Cubemap]) -> Arc<ImmutableImage<Format>> {
+    let usage = ImageUsage {
+        transfer_destination: true,
+        sampled: true,
+        .. ImageUsage::none()
+    };
+    let (image, initialization) = ImmutableImage::uninitialized(device.clone(), Dimensions::Cubemap { size: levels[0].size },
+        Format::R32G32B32A32Sfloat, MipmapsCount::Specific(levels.len() as u32), usage,
+        ImageLayout::ShaderReadOnlyOptimal, Some(queue.family()))
+        .expect("failed to create cubemap image");
+    let initialization = Arc::new(initialization);
+
+    let mut builder = AutoCommandBufferBuilder::new(device.clone(), queue.family()).unwrap();
+    for (level, cubemap) in levels.iter().enumerate() {
+        let buffer = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::transfer_source(),
+            cubemap.texels_rgba().into_iter()).unwrap();
+        builder = builder.copy_buffer_to_image_dimensions(buffer, initialization.clone(), [0, 0, 0],
+            [cubemap.size, cubemap.size, 1], 0, 6, level as u32).unwrap();
+    }
+    builder.build().unwrap()
+        .execute(queue.clone()).unwrap()
+        .then_signal_fence_and_flush().unwrap()
+        .wait(None).unwrap();
+
+    image
+}
\ No newline at end of file
@@ -0,0 +1,223 @@
+use super::texture::Texture;
+use crate::math::vec3::Vec3;
+
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tonemapper {
+    // clamps to [0, 1]
+    None,
+    Reinhard,
+    // Narkowicz's fit of the ACES filmic curve
+    Aces
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BloomSettings {
+    pub enabled: bool,
+    // brightness where pixels start to bloom, with a soft transition of `knee` around it
+    pub threshold: f32,
+    pub knee: f32,
+    pub intensity: f32,
+    // number of half resolution steps in the blur chain
+    pub mip_count: u32
+}
+
+impl Default for BloomSettings {
+    fn default() -> BloomSettings {
+        BloomSettings {
+            enabled: true,
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.05,
+            mip_count: 5
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VignetteSettings {
+    pub enabled: bool,
+    pub intensity: f32,
+    // distance from the center, 1 being the corners, where darkening starts and how far it fades in
+    pub radius: f32,
+    pub softness: f32
+}
+
+impl Default for VignetteSettings {
+    fn default() -> VignetteSettings {
+        VignetteSettings {
+            enabled: false,
+            intensity: 0.5,
+            radius: 0.75,
+            softness: 0.45
+        }
+    }
+}
+
+// effects run in this order: bloom, exposure, tonemapping, gamma, color grading, vignette, fxaa
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostSettings {
+    pub exposure: f32,
+    pub tonemapper: Tonemapper,
+    pub bloom: BloomSettings,
+    // applied to gamma corrected colors, None turns grading off
+    pub color_lut: Option<Arc<ColorLut>>,
+    pub vignette: VignetteSettings,
+    // the swap chain is UNORM, so without this the linear output shows too dark
+    pub gamma_correction: bool,
+    pub gamma: f32,
+    pub fxaa: bool
+}
+
+impl Default for PostSettings {
+    fn default() -> PostSettings {
+        PostSettings {
+            exposure: 1.0,
+            tonemapper: Tonemapper::Aces,
+            bloom: BloomSettings::default(),
+            color_lut: None,
+            vignette: VignetteSettings::default(),
+            gamma_correction: true,
+            gamma: 2.2,
+            fxaa: true
+        }
+    }
+}
+
+// 3d color lookup table, red along x, green along y and blue along z
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorLut {
+    pub size: u32,
+    pub data: Vec<Vec3>
+}
+
+impl ColorLut {
+    // at least 2 texels a side, so every color lies between two of them
+    pub fn identity(size: u32) -> Result<ColorLut, String> {
+        if size < 2 {
+            return Err(format!("a LUT needs at least 2 texels a side, not {}", size));
+        }
+
+        let step = 1.0 / (size - 1) as f32;
+        let mut data = Vec::with_capacity((size * size * size) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    data.push(Vec3 { x: r as f32 * step, y: g as f32 * step, z: b as f32 * step });
+                }
+            }
+        }
+
+        Ok(ColorLut {
+            size,
+            data
+        })
+    }
+
+    // the usual strip layout of grading LUTs exported from image editors: size slices of
+    // size x size side by side, blue increasing from slice to slice
+    pub fn from_strip(texture: &Texture) -> Result<ColorLut, String> {
+        let size = texture.height;
+        if size < 2 || texture.width != size * size {
+            return Err(format!("a {}x{} image is not a LUT strip, expected a width of height squared",
+                texture.width, texture.height));
+        }
+
+        let mut data = Vec::with_capacity((size * size * size) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    let texel = texture.get(b * size + r, g);
+                    data.push(Vec3 {
+                        x: f32::from(texel[0]) / 255.0,
+                        y: f32::from(texel[1]) / 255.0,
+                        z: f32::from(texel[2]) / 255.0
+                    });
+                }
+            }
+        }
+
+        Ok(ColorLut {
+            size,
+            data
+        })
+    }
+
+    fn texel(&self, r: u32, g: u32, b: u32) -> Vec3 {
+        self.data[((b * self.size + g) * self.size + r) as usize].clone()
+    }
+
+    // trilinear lookup, the same the grading shader gets from the sampler
+    pub fn sample(&self, color: &Vec3) -> Vec3 {
+        // only reachable by filling in the fields by hand, the constructors make at least 2 a side
+        if self.size < 2 {
+            return self.data.first().cloned().unwrap_or_else(|| color.clone());
+        }
+
+        let max = (self.size - 1) as f32;
+        let coordinates = [color.x, color.y, color.z].iter()
+            .map(|c| {
+                let c = c.max(0.0).min(1.0) * max;
+                let low = (c.floor() as u32).min(self.size - 2);
+                (low, c - low as f32)
+            })
+            .collect::<Vec<_>>();
+        let (r, tr) = coordinates[0];
+        let (g, tg) = coordinates[1];
+        let (b, tb) = coordinates[2];
+
+        let lerp = |a: Vec3, b: Vec3, t: f32| a.clone() + (b - a) * t;
+        let c00 = lerp(self.texel(r, g, b), self.texel(r + 1, g, b), tr);
+        let c10 = lerp(self.texel(r, g + 1, b), self.texel(r + 1, g + 1, b), tr);
+        let c01 = lerp(self.texel(r, g, b + 1), self.texel(r + 1, g, b + 1), tr);
+        let c11 = lerp(self.texel(r, g + 1, b + 1), self.texel(r + 1, g + 1, b + 1), tr);
+        lerp(lerp(c00, c10, tg), lerp(c01, c11, tg), tb)
+    }
+
+    pub fn texels_rgba(&self) -> Vec<[u8; 4]> {
+        let to_byte = |c: f32| (c.max(0.0).min(1.0) * 255.0).round() as u8;
+        self.data.iter().map(|c| [to_byte(c.x), to_byte(c.y), to_byte(c.z), 255]).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn luts_smaller_than_two_are_rejected() {
+        assert!(ColorLut::identity(0).is_err());
+        assert!(ColorLut::identity(1).is_err());
+        let texture = Texture::new(1, 1, vec![[0, 0, 0, 255]], false).expect("failed to create texture");
+        assert!(ColorLut::from_strip(&texture).is_err());
+    }
+
+    #[test]
+    fn identity_lut_keeps_colors() {
+        let lut = ColorLut::identity(4).expect("failed to create lut");
+        assert_eq!(lut.data.len(), 64);
+        for color in [Vec3 { x: 0.0, y: 0.0, z: 0.0 }, Vec3 { x: 0.3, y: 0.55, z: 1.0 }, Vec3 { x: 1.0, y: 0.1, z: 0.7 }].iter() {
+            let sampled = lut.sample(color);
+            assert!(sampled.distance_to(color.clone()) < 1e-5, "{:?} -> {:?}", color, sampled);
+        }
+        // out of range colors are clamped to the cube
+        assert_eq!(lut.sample(&Vec3 { x: 2.0, y: -1.0, z: 0.5 }), Vec3 { x: 1.0, y: 0.0, z: 0.5 });
+    }
+
+    #[test]
+    fn strip_matches_the_identity() {
+        let identity = ColorLut::identity(2).expect("failed to create lut");
+        // 2 slices of 2 x 2, blue from slice to slice
+        let mut pixels = vec![[0, 0, 0, 255]; 8];
+        for b in 0..2 {
+            for g in 0..2 {
+                for r in 0..2 {
+                    pixels[(g * 4 + b * 2 + r) as usize] = [r * 255, g * 255, b * 255, 255];
+                }
+            }
+        }
+        let strip = Texture::new(4, 2, pixels, false).expect("failed to create texture");
+        assert_eq!(ColorLut::from_strip(&strip), Ok(identity));
+    }
+}
\ No newline at end of file
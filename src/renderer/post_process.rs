@@ -0,0 +1,518 @@
+use super::post::{ColorLut, PostSettings, Tonemapper};
+
+use std::sync::Arc;
+
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::image::{AttachmentImage, Dimensions, ImmutableImage};
+use vulkano::sampler::{Sampler, Filter, MipmapMode, SamplerAddressMode};
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract, viewport::Viewport};
+use vulkano::pipeline::blend::{AttachmentBlend, BlendOp, BlendFactor};
+use vulkano::pipeline::vertex::{BufferlessDefinition, BufferlessVertices};
+use vulkano::framebuffer::{RenderPassAbstract, Subpass, FramebufferAbstract, Framebuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::sync::GpuFuture;
+
+// the scene is rendered into this, bloom runs at the same precision
+pub const HDR_FORMAT: Format = Format::R16G16B16A16Sfloat;
+// tonemapped and gamma corrected colors waiting for fxaa
+pub const LDR_FORMAT: Format = Format::R8G8B8A8Unorm;
+
+// single triangle covering the screen, no vertex buffer needed
+pub mod fullscreen_vs {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        src: "
+        #version 450
+        #extension GL_ARB_separate_shader_objects : enable
+
+        layout(location = 0) out vec2 uv;
+
+        void main() {
+            uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+            gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+        }"
+    }
+}
+
+// 2x2 box downsample, the first step also keeps only what's above the bloom threshold
+pub mod downsample_fs {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        src: "
+        #version 450
+        #extension GL_ARB_separate_shader_objects : enable
+
+        layout(location = 0) in vec2 uv;
+        layout(location = 0) out vec4 out_color;
+
+        layout(set = 0, binding = 0) uniform sampler2D source;
+
+        layout(push_constant) uniform DownsampleConstants {
+            // source texel size in xy, threshold, knee
+            vec4 params;
+            // prefilter
+            vec4 flags;
+        } constants;
+
+        vec3 soft_threshold(vec3 color) {
+            float threshold = constants.params.z;
+            float knee = constants.params.w;
+            float brightness = max(color.r, max(color.g, color.b));
+            float soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+            soft = soft * soft / (4.0 * knee + 0.00001);
+            return color * max(soft, brightness - threshold) / max(brightness, 0.00001);
+        }
+
+        void main() {
+            vec4 offset = constants.params.xyxy * vec4(-1.0, -1.0, 1.0, 1.0);
+            vec3 color = 0.25 * (texture(source, uv + offset.xy).rgb + texture(source, uv + offset.zy).rgb
+                + texture(source, uv + offset.xw).rgb + texture(source, uv + offset.zw).rgb);
+            if (constants.flags.x > 0.5) {
+                color = soft_threshold(color);
+            }
+            out_color = vec4(color, 1.0);
+        }"
+    }
+}
+
+// 3x3 tent upsample, added on top of the next larger mip by the blend state
+pub mod upsample_fs {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        src: "
+        #version 450
+        #extension GL_ARB_separate_shader_objects : enable
+
+        layout(location = 0) in vec2 uv;
+        layout(location = 0) out vec4 out_color;
+
+        layout(set = 0, binding = 0) uniform sampler2D source;
+
+        layout(push_constant) uniform UpsampleConstants {
+            // source texel size in xy
+            vec4 params;
+        } constants;
+
+        void main() {
+            vec2 texel = constants.params.xy;
+            vec3 color = texture(source, uv).rgb * 4.0;
+            color += (texture(source, uv + vec2(-texel.x, 0.0)).rgb + texture(source, uv + vec2(texel.x, 0.0)).rgb
+                + texture(source, uv + vec2(0.0, -texel.y)).rgb + texture(source, uv + vec2(0.0, texel.y)).rgb) * 2.0;
+            color += texture(source, uv - texel).rgb + texture(source, uv + texel).rgb
+                + texture(source, uv + vec2(-texel.x, texel.y)).rgb + texture(source, uv + vec2(texel.x, -texel.y)).rgb;
+            out_color = vec4(color / 16.0, 1.0);
+        }"
+    }
+}
+
+pub mod composite_fs {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        src: "
+        #version 450
+        #extension GL_ARB_separate_shader_objects : enable
+
+        layout(location = 0) in vec2 uv;
+        layout(location = 0) out vec4 out_color;
+
+        layout(set = 0, binding = 0) uniform sampler2D hdr;
+        layout(set = 0, binding = 1) uniform sampler2D bloom;
+        layout(set = 0, binding = 2) uniform sampler3D color_lut;
+
+        layout(push_constant) uniform CompositeConstants {
+            // exposure, tonemapper (0 none, 1 reinhard, 2 aces), bloom enabled, bloom intensity
+            vec4 tonemapping;
+            // gamma enabled, gamma, lut enabled, lut size
+            vec4 grading;
+            // enabled, intensity, radius, softness
+            vec4 vignette;
+        } settings;
+
+        vec3 aces(vec3 x) {
+            return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+        }
+
+        void main() {
+            vec3 color = texture(hdr, uv).rgb;
+            if (settings.tonemapping.z > 0.5) {
+                color += texture(bloom, uv).rgb * settings.tonemapping.w;
+            }
+
+            color *= settings.tonemapping.x;
+            if (settings.tonemapping.y > 1.5) {
+                color = aces(color);
+            } else if (settings.tonemapping.y > 0.5) {
+                color = color / (1.0 + color);
+            }
+            color = clamp(color, 0.0, 1.0);
+
+            if (settings.grading.x > 0.5) {
+                color = pow(color, vec3(1.0 / settings.grading.y));
+            }
+
+            if (settings.grading.z > 0.5) {
+                // sample texel centers so the ends of the table map to 0 and 1
+                float size = settings.grading.w;
+                color = texture(color_lut, color * ((size - 1.0) / size) + 0.5 / size).rgb;
+            }
+
+            if (settings.vignette.x > 0.5) {
+                float distance = length(uv - 0.5) * 1.41421356;
+                float falloff = smoothstep(settings.vignette.z, settings.vignette.z - settings.vignette.w, distance);
+                color *= mix(1.0, falloff, settings.vignette.y);
+            }
+
+            out_color = vec4(color, 1.0);
+        }"
+    }
+}
+
+// FXAA, the low quality console variant: one blur along the edge direction, rejected when it
+// leaves the local luma range
+pub mod fxaa_fs {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        src: "
+        #version 450
+        #extension GL_ARB_separate_shader_objects : enable
+
+        layout(location = 0) in vec2 uv;
+        layout(location = 0) out vec4 out_color;
+
+        layout(set = 0, binding = 0) uniform sampler2D source;
+
+        layout(push_constant) uniform FxaaConstants {
+            // source texel size in xy
+            vec4 params;
+        } constants;
+
+        const float REDUCE_MIN = 1.0 / 128.0;
+        const float REDUCE_MUL = 1.0 / 8.0;
+        const float SPAN_MAX = 8.0;
+
+        void main() {
+            vec2 texel = constants.params.xy;
+            vec3 luma = vec3(0.299, 0.587, 0.114);
+            vec3 rgb_m = texture(source, uv).rgb;
+            float luma_nw = dot(texture(source, uv + vec2(-1.0, -1.0) * texel).rgb, luma);
+            float luma_ne = dot(texture(source, uv + vec2(1.0, -1.0) * texel).rgb, luma);
+            float luma_sw = dot(texture(source, uv + vec2(-1.0, 1.0) * texel).rgb, luma);
+            float luma_se = dot(texture(source, uv + vec2(1.0, 1.0) * texel).rgb, luma);
+            float luma_m = dot(rgb_m, luma);
+            float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+            float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+            vec2 direction = vec2(-((luma_nw + luma_ne) - (luma_sw + luma_se)), (luma_nw + luma_sw) - (luma_ne + luma_se));
+            float reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+            float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+            direction = clamp(direction * scale, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * texel;
+
+            vec3 rgb_a = 0.5 * (texture(source, uv + direction * (1.0 / 3.0 - 0.5)).rgb
+                + texture(source, uv + direction * (2.0 / 3.0 - 0.5)).rgb);
+            vec3 rgb_b = rgb_a * 0.5 + 0.25 * (texture(source, uv - direction * 0.5).rgb
+                + texture(source, uv + direction * 0.5).rgb);
+            float luma_b = dot(rgb_b, luma);
+            out_color = vec4((luma_b < luma_min || luma_b > luma_max) ? rgb_a : rgb_b, 1.0);
+        }"
+    }
+}
+
+fn create_render_pass(device: &Arc<Device>, format: Format, keep_contents: bool) -> Arc<RenderPassAbstract + Send + Sync> {
+    // full screen passes overwrite every pixel, only the additive bloom upsample needs the old contents
+    if keep_contents {
+        Arc::new(single_pass_renderpass!(device.clone(),
+            attachments: {
+                color: {
+                    load: Load,
+                    store: Store,
+                    format: format,
+                    samples: 1,
+                }
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {}
+            }
+        ).unwrap())
+    } else {
+        Arc::new(single_pass_renderpass!(device.clone(),
+            attachments: {
+                color: {
+                    load: DontCare,
+                    store: Store,
+                    format: format,
+                    samples: 1,
+                }
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {}
+            }
+        ).unwrap())
+    }
+}
+
+// every pass draws the same full screen triangle, only the fragment shader and blending change.
+// a macro rather than a function since the pipeline builder's types follow the shaders
+macro_rules! fullscreen_pipeline {
+    ($device:expr, $render_pass:expr, $vertex_shader:expr, $fragment_shader:expr, $blend:expr) => {{
+        let pipeline: Arc<GraphicsPipelineAbstract + Send + Sync> = Arc::new(GraphicsPipeline::start()
+            .vertex_input(BufferlessDefinition)
+            .vertex_shader($vertex_shader.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .fragment_shader($fragment_shader.main_entry_point(), ())
+            .cull_mode_disabled()
+            .blend_collective($blend)
+            .render_pass(Subpass::from($render_pass.clone(), 0).unwrap())
+            .build($device.clone())
+            .unwrap());
+        pipeline
+    }};
+}
+
+fn additive_blend() -> AttachmentBlend {
+    AttachmentBlend {
+        enabled: true,
+        color_op: BlendOp::Add,
+        color_source: BlendFactor::One,
+        color_destination: BlendFactor::One,
+        alpha_op: BlendOp::Add,
+        alpha_source: BlendFactor::One,
+        alpha_destination: BlendFactor::Zero,
+        .. AttachmentBlend::pass_through()
+    }
+}
+
+fn viewport(dimensions: [u32; 2]) -> DynamicState {
+    DynamicState {
+        line_width: None,
+        viewports: Some(vec![Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+            depth_range: 0.0 .. 1.0,
+        }]),
+        scissors: None
+    }
+}
+
+fn texel_size(dimensions: [u32; 2]) -> [f32; 2] {
+    [1.0 / dimensions[0] as f32, 1.0 / dimensions[1] as f32]
+}
+
+pub fn upload_lut(queue: &Arc<Queue>, lut: &ColorLut) -> Arc<ImmutableImage<Format>> {
+    let (image, future) = ImmutableImage::from_iter(lut.texels_rgba().into_iter(),
+        Dimensions::Dim3d { width: lut.size, height: lut.size, depth: lut.size }, Format::R8G8B8A8Unorm, queue.clone())
+        .expect("failed to upload color lut");
+    future.then_signal_fence_and_flush().unwrap().wait(None).unwrap();
+
+    image
+}
+
+const FULLSCREEN_TRIANGLE: BufferlessVertices = BufferlessVertices { vertices: 3, instances: 1 };
+
+// pipelines and render passes of the post-processing chain. which steps run is decided by the frame graph
+pub struct PostProcessor {
+    vertex_shader: fullscreen_vs::Shader,
+    downsample_shader: downsample_fs::Shader,
+    upsample_shader: upsample_fs::Shader,
+    composite_shader: composite_fs::Shader,
+    fxaa_shader: fxaa_fs::Shader,
+
+    hdr_render_pass: Arc<RenderPassAbstract + Send + Sync>,
+    hdr_blend_render_pass: Arc<RenderPassAbstract + Send + Sync>,
+    ldr_render_pass: Arc<RenderPassAbstract + Send + Sync>,
+    // writes the swap chain image
+    pub output_render_pass: Arc<RenderPassAbstract + Send + Sync>,
+
+    downsample_pipeline: Arc<GraphicsPipelineAbstract + Send + Sync>,
+    upsample_pipeline: Arc<GraphicsPipelineAbstract + Send + Sync>,
+    composite_ldr_pipeline: Arc<GraphicsPipelineAbstract + Send + Sync>,
+    composite_output_pipeline: Arc<GraphicsPipelineAbstract + Send + Sync>,
+    fxaa_pipeline: Arc<GraphicsPipelineAbstract + Send + Sync>,
+
+    sampler: Arc<Sampler>,
+    identity_lut: Arc<ImmutableImage<Format>>,
+    // grading LUT currently on the gpu, with the settings value it was uploaded from
+    lut: Option<(Arc<ColorLut>, Arc<ImmutableImage<Format>>)>
+}
+
+impl PostProcessor {
+    pub fn new(device: &Arc<Device>, queue: &Arc<Queue>, output_format: Format) -> PostProcessor {
+        let vertex_shader = fullscreen_vs::Shader::load(device.clone()).expect("failed to create shader module");
+        let downsample_shader = downsample_fs::Shader::load(device.clone()).expect("failed to create shader module");
+        let upsample_shader = upsample_fs::Shader::load(device.clone()).expect("failed to create shader module");
+        let composite_shader = composite_fs::Shader::load(device.clone()).expect("failed to create shader module");
+        let fxaa_shader = fxaa_fs::Shader::load(device.clone()).expect("failed to create shader module");
+
+        let hdr_render_pass = create_render_pass(device, HDR_FORMAT, false);
+        let hdr_blend_render_pass = create_render_pass(device, HDR_FORMAT, true);
+        let ldr_render_pass = create_render_pass(device, LDR_FORMAT, false);
+        let output_render_pass = create_render_pass(device, output_format, false);
+
+        let downsample_pipeline = fullscreen_pipeline!(device, hdr_render_pass, vertex_shader,
+            downsample_shader, AttachmentBlend::pass_through());
+        let upsample_pipeline = fullscreen_pipeline!(device, hdr_blend_render_pass, vertex_shader,
+            upsample_shader, additive_blend());
+        let composite_ldr_pipeline = fullscreen_pipeline!(device, ldr_render_pass, vertex_shader,
+            composite_shader, AttachmentBlend::pass_through());
+        let composite_output_pipeline = fullscreen_pipeline!(device, output_render_pass, vertex_shader,
+            composite_shader, AttachmentBlend::pass_through());
+        let fxaa_pipeline = fullscreen_pipeline!(device, output_render_pass, vertex_shader,
+            fxaa_shader, AttachmentBlend::pass_through());
+
+        let sampler = Sampler::new(device.clone(), Filter::Linear, Filter::Linear, MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge, SamplerAddressMode::ClampToEdge, SamplerAddressMode::ClampToEdge,
+            0.0, 1.0, 0.0, 0.0).unwrap();
+        let identity_lut = upload_lut(queue, &ColorLut::identity(2).expect("failed to create identity lut"));
+
+        PostProcessor {
+            vertex_shader,
+            downsample_shader,
+            upsample_shader,
+            composite_shader,
+            fxaa_shader,
+
+            hdr_render_pass,
+            hdr_blend_render_pass,
+            ldr_render_pass,
+            output_render_pass,
+
+            downsample_pipeline,
+            upsample_pipeline,
+            composite_ldr_pipeline,
+            composite_output_pipeline,
+            fxaa_pipeline,
+
+            sampler,
+            identity_lut,
+            lut: None
+        }
+    }
+
+    // uploads the grading LUT of the settings if it isn't on the gpu yet
+    pub fn update_lut(&mut self, queue: &Arc<Queue>, settings: &PostSettings) {
+        let uploaded = match (&self.lut, &settings.color_lut) {
+            (Some((current, _)), Some(lut)) => Arc::ptr_eq(current, lut),
+            (None, None) => true,
+            _ => false
+        };
+        if !uploaded {
+            self.lut = settings.color_lut.as_ref().map(|lut| (lut.clone(), upload_lut(queue, lut)));
+        }
+    }
+
+    pub fn record_downsample(&self, builder: AutoCommandBufferBuilder, source: &Arc<AttachmentImage<Format>>,
+            target: &Arc<AttachmentImage<Format>>, prefilter: bool, settings: &PostSettings) -> AutoCommandBufferBuilder {
+        let texel = texel_size(source.dimensions());
+        let constants = downsample_fs::ty::DownsampleConstants {
+            params: [texel[0], texel[1], settings.bloom.threshold, settings.bloom.knee],
+            flags: [if prefilter { 1.0 } else { 0.0 }, 0.0, 0.0, 0.0]
+        };
+        self.record(builder, &self.hdr_render_pass, &self.downsample_pipeline, target, source, constants)
+    }
+
+    pub fn record_upsample(&self, builder: AutoCommandBufferBuilder, source: &Arc<AttachmentImage<Format>>,
+            target: &Arc<AttachmentImage<Format>>) -> AutoCommandBufferBuilder {
+        let texel = texel_size(source.dimensions());
+        let constants = upsample_fs::ty::UpsampleConstants {
+            params: [texel[0], texel[1], 0.0, 0.0]
+        };
+        self.record(builder, &self.hdr_blend_render_pass, &self.upsample_pipeline, target, source, constants)
+    }
+
+    // tonemaps into an ldr image for fxaa, or straight into the swap chain framebuffer
+    pub fn record_composite(&self, builder: AutoCommandBufferBuilder, hdr: &Arc<AttachmentImage<Format>>,
+            bloom: Option<&Arc<AttachmentImage<Format>>>, target: CompositeTarget, settings: &PostSettings)
+            -> AutoCommandBufferBuilder {
+        let tonemapper = match settings.tonemapper {
+            Tonemapper::None => 0.0,
+            Tonemapper::Reinhard => 1.0,
+            Tonemapper::Aces => 2.0
+        };
+        let lut = self.lut.as_ref().map(|(lut, image)| (lut.size, image));
+        let vignette = &settings.vignette;
+        let constants = composite_fs::ty::CompositeConstants {
+            tonemapping: [settings.exposure, tonemapper, if bloom.is_some() { 1.0 } else { 0.0 }, settings.bloom.intensity],
+            grading: [
+                if settings.gamma_correction { 1.0 } else { 0.0 },
+                settings.gamma,
+                if lut.is_some() { 1.0 } else { 0.0 },
+                lut.map(|(size, _)| size as f32).unwrap_or(2.0)
+            ],
+            vignette: [if vignette.enabled { 1.0 } else { 0.0 }, vignette.intensity, vignette.radius, vignette.softness]
+        };
+
+        // unused inputs still need something bound
+        let bloom = bloom.unwrap_or(hdr);
+        let lut_image = lut.map(|(_, image)| image.clone()).unwrap_or_else(|| self.identity_lut.clone());
+        let (pipeline, framebuffer, dimensions) = match target {
+            CompositeTarget::Ldr(image) => {
+                let framebuffer: Arc<FramebufferAbstract + Send + Sync> = Arc::new(Framebuffer::start(self.ldr_render_pass.clone())
+                    .add(image.clone()).unwrap()
+                    .build().unwrap());
+                (&self.composite_ldr_pipeline, framebuffer, image.dimensions())
+            },
+            CompositeTarget::Output(framebuffer, dimensions) => (&self.composite_output_pipeline, framebuffer, dimensions)
+        };
+
+        let descriptor_set = Arc::new(PersistentDescriptorSet::start(pipeline.clone(), 0)
+            .add_sampled_image(hdr.clone(), self.sampler.clone()).unwrap()
+            .add_sampled_image(bloom.clone(), self.sampler.clone()).unwrap()
+            .add_sampled_image(lut_image, self.sampler.clone()).unwrap()
+            .build().unwrap());
+
+        builder.begin_render_pass(framebuffer, false, vec![vulkano::format::ClearValue::None])
+            .unwrap()
+            .draw(pipeline.clone(), &viewport(dimensions), FULLSCREEN_TRIANGLE, descriptor_set, constants)
+            .unwrap()
+            .end_render_pass()
+            .unwrap()
+    }
+
+    pub fn record_fxaa(&self, builder: AutoCommandBufferBuilder, source: &Arc<AttachmentImage<Format>>,
+            framebuffer: Arc<FramebufferAbstract + Send + Sync>, dimensions: [u32; 2]) -> AutoCommandBufferBuilder {
+        let texel = texel_size(source.dimensions());
+        let constants = fxaa_fs::ty::FxaaConstants {
+            params: [texel[0], texel[1], 0.0, 0.0]
+        };
+        let descriptor_set = Arc::new(PersistentDescriptorSet::start(self.fxaa_pipeline.clone(), 0)
+            .add_sampled_image(source.clone(), self.sampler.clone()).unwrap()
+            .build().unwrap());
+
+        builder.begin_render_pass(framebuffer, false, vec![vulkano::format::ClearValue::None])
+            .unwrap()
+            .draw(self.fxaa_pipeline.clone(), &viewport(dimensions), FULLSCREEN_TRIANGLE, descriptor_set, constants)
+            .unwrap()
+            .end_render_pass()
+            .unwrap()
+    }
+
+    // one full screen triangle sampling source into target
+    fn record<Pc>(&self, builder: AutoCommandBufferBuilder, render_pass: &Arc<RenderPassAbstract + Send + Sync>,
+            pipeline: &Arc<GraphicsPipelineAbstract + Send + Sync>, target: &Arc<AttachmentImage<Format>>,
+            source: &Arc<AttachmentImage<Format>>, constants: Pc) -> AutoCommandBufferBuilder {
+        let framebuffer: Arc<FramebufferAbstract + Send + Sync> = Arc::new(Framebuffer::start(render_pass.clone())
+            .add(target.clone()).unwrap()
+            .build().unwrap());
+        let descriptor_set = Arc::new(PersistentDescriptorSet::start(pipeline.clone(), 0)
+            .add_sampled_image(source.clone(), self.sampler.clone()).unwrap()
+            .build().unwrap());
+
+        // load is DontCare or Load for these passes, neither takes a clear value
+        builder.begin_render_pass(framebuffer, false, vec![vulkano::format::ClearValue::None])
+            .unwrap()
+            .draw(pipeline.clone(), &viewport(target.dimensions()), FULLSCREEN_TRIANGLE, descriptor_set, constants)
+            .unwrap()
+            .end_render_pass()
+            .unwrap()
+    }
+}
+
+pub enum CompositeTarget<'a> {
+    Ldr(&'a Arc<AttachmentImage<Format>>),
+    // swap chain framebuffer and its size
+    Output(Arc<FramebufferAbstract + Send + Sync>, [u32; 2])
+}
\ No newline at end of file
@@ -0,0 +1,739 @@
+use std::error::Error;
+use std::fmt;
+
+use vulkano::format::Format;
+use vulkano::image::{ImageLayout, ImageUsage};
+
+// frame composition from passes that declare which images they read and write. compiling the graph
+// orders the passes, culls the ones nothing depends on, tracks image layouts and load/store ops,
+// and lets transient images with disjoint lifetimes share one allocation. it's all CPU side,
+// recording the passes is left to the renderer
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ResourceId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PassId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageSize {
+    // scale of the swap chain extent
+    SwapchainRelative(f32),
+    Absolute(u32, u32)
+}
+
+impl ImageSize {
+    pub fn resolve(&self, swap_chain_extent: [u32; 2]) -> [u32; 2] {
+        match *self {
+            ImageSize::SwapchainRelative(scale) => [
+                ((swap_chain_extent[0] as f32 * scale).round() as u32).max(1),
+                ((swap_chain_extent[1] as f32 * scale).round() as u32).max(1)
+            ],
+            ImageSize::Absolute(width, height) => [width, height]
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageDesc {
+    pub format: Format,
+    pub size: ImageSize
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceUsage {
+    ColorAttachment,
+    DepthStencilAttachment,
+    // depth test without depth writes
+    DepthStencilRead,
+    Sampled,
+    TransferSource,
+    TransferDestination
+}
+
+impl ResourceUsage {
+    pub fn layout(&self) -> ImageLayout {
+        match self {
+            ResourceUsage::ColorAttachment => ImageLayout::ColorAttachmentOptimal,
+            ResourceUsage::DepthStencilAttachment => ImageLayout::DepthStencilAttachmentOptimal,
+            ResourceUsage::DepthStencilRead => ImageLayout::DepthStencilReadOnlyOptimal,
+            ResourceUsage::Sampled => ImageLayout::ShaderReadOnlyOptimal,
+            ResourceUsage::TransferSource => ImageLayout::TransferSrcOptimal,
+            ResourceUsage::TransferDestination => ImageLayout::TransferDstOptimal
+        }
+    }
+
+    pub fn is_write(&self) -> bool {
+        match self {
+            ResourceUsage::ColorAttachment | ResourceUsage::DepthStencilAttachment | ResourceUsage::TransferDestination => true,
+            _ => false
+        }
+    }
+
+    pub fn is_attachment(&self) -> bool {
+        match self {
+            ResourceUsage::ColorAttachment | ResourceUsage::DepthStencilAttachment | ResourceUsage::DepthStencilRead => true,
+            _ => false
+        }
+    }
+
+    fn add_to(&self, usage: &mut ImageUsage) {
+        match self {
+            ResourceUsage::ColorAttachment => usage.color_attachment = true,
+            ResourceUsage::DepthStencilAttachment | ResourceUsage::DepthStencilRead => usage.depth_stencil_attachment = true,
+            ResourceUsage::Sampled => usage.sampled = true,
+            ResourceUsage::TransferSource => usage.transfer_source = true,
+            ResourceUsage::TransferDestination => usage.transfer_destination = true
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadOp {
+    Load,
+    Clear,
+    DontCare
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoreOp {
+    Store,
+    DontCare
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraphError {
+    Cycle { passes: Vec<String> },
+    // a transient image is read but no pass writes it
+    MissingInput { pass: String, resource: String },
+    // a read declared with a writing usage or the other way around
+    InvalidUsage { pass: String, resource: String, usage: ResourceUsage },
+    // the same image used more than once by a pass
+    ConflictingUsage { pass: String, resource: String }
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphError::Cycle { passes } => write!(f, "render graph has a cycle between passes {}", passes.join(", ")),
+            GraphError::MissingInput { pass, resource } =>
+                write!(f, "pass {} reads {}, which no pass writes", pass, resource),
+            GraphError::InvalidUsage { pass, resource, usage } =>
+                write!(f, "pass {} declares {} with mismatched usage {:?}", pass, resource, usage),
+            GraphError::ConflictingUsage { pass, resource } =>
+                write!(f, "pass {} uses {} more than once", pass, resource)
+        }
+    }
+}
+
+impl Error for GraphError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AccessKind {
+    Read,
+    // keeps the previous contents
+    Write,
+    // discards the previous contents
+    Clear
+}
+
+#[derive(Debug, Clone)]
+struct Access {
+    resource: ResourceId,
+    usage: ResourceUsage,
+    kind: AccessKind
+}
+
+#[derive(Debug, Clone)]
+struct Resource {
+    name: String,
+    // None for imported images, the graph doesn't allocate those
+    desc: Option<ImageDesc>,
+    initial_layout: ImageLayout,
+    final_layout: ImageLayout
+}
+
+#[derive(Debug, Clone)]
+struct Pass {
+    name: String,
+    accesses: Vec<Access>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Barrier {
+    pub resource: ResourceId,
+    pub old_layout: ImageLayout,
+    pub new_layout: ImageLayout,
+    // None when this is the first use of the image in the frame
+    pub src_usage: Option<ResourceUsage>,
+    pub dst_usage: ResourceUsage
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttachmentOps {
+    pub resource: ResourceId,
+    pub usage: ResourceUsage,
+    pub load: LoadOp,
+    pub store: StoreOp
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledPass {
+    pub id: PassId,
+    pub name: String,
+    // transitions to run before the pass
+    pub barriers: Vec<Barrier>,
+    pub attachments: Vec<AttachmentOps>
+}
+
+// one allocation shared by transient resources whose lifetimes don't overlap
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhysicalImage {
+    pub desc: ImageDesc,
+    pub usage: ImageUsage,
+    pub resources: Vec<ResourceId>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledGraph {
+    // in execution order
+    pub passes: Vec<CompiledPass>,
+    // passes that don't contribute to any imported image
+    pub culled: Vec<PassId>,
+    // transitions of imported images to their final layout after the last pass
+    pub final_barriers: Vec<Barrier>,
+    pub images: Vec<PhysicalImage>,
+    resource_images: Vec<Option<usize>>
+}
+
+impl CompiledGraph {
+    // index into images of the allocation backing a transient resource
+    pub fn image_index(&self, resource: ResourceId) -> Option<usize> {
+        self.resource_images.get(resource.0).cloned().unwrap_or(None)
+    }
+
+    pub fn pass(&self, id: PassId) -> Option<&CompiledPass> {
+        self.passes.iter().find(|pass| pass.id == id)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RenderGraph {
+    resources: Vec<Resource>,
+    passes: Vec<Pass>
+}
+
+impl RenderGraph {
+    pub fn new() -> RenderGraph {
+        RenderGraph::default()
+    }
+
+    // image allocated by the graph, its contents only live during the frame
+    pub fn create_image(&mut self, name: &str, desc: ImageDesc) -> ResourceId {
+        self.resources.push(Resource {
+            name: name.to_string(),
+            desc: Some(desc),
+            initial_layout: ImageLayout::Undefined,
+            final_layout: ImageLayout::Undefined
+        });
+        ResourceId(self.resources.len() - 1)
+    }
+
+    // image owned outside the graph, like a swap chain image. its contents outlive the frame
+    pub fn import_image(&mut self, name: &str, initial_layout: ImageLayout, final_layout: ImageLayout) -> ResourceId {
+        self.resources.push(Resource {
+            name: name.to_string(),
+            desc: None,
+            initial_layout,
+            final_layout
+        });
+        ResourceId(self.resources.len() - 1)
+    }
+
+    pub fn add_pass(&mut self, name: &str) -> PassId {
+        self.passes.push(Pass {
+            name: name.to_string(),
+            accesses: vec![]
+        });
+        PassId(self.passes.len() - 1)
+    }
+
+    pub fn read(&mut self, pass: PassId, resource: ResourceId, usage: ResourceUsage) {
+        self.passes[pass.0].accesses.push(Access { resource, usage, kind: AccessKind::Read });
+    }
+
+    // writes on top of what earlier passes wrote
+    pub fn write(&mut self, pass: PassId, resource: ResourceId, usage: ResourceUsage) {
+        self.passes[pass.0].accesses.push(Access { resource, usage, kind: AccessKind::Write });
+    }
+
+    // overwrites the image, whatever was in it before is discarded
+    pub fn clear(&mut self, pass: PassId, resource: ResourceId, usage: ResourceUsage) {
+        self.passes[pass.0].accesses.push(Access { resource, usage, kind: AccessKind::Clear });
+    }
+
+    pub fn compile(&self) -> Result<CompiledGraph, GraphError> {
+        self.validate()?;
+
+        let mut writers: Vec<Vec<usize>> = vec![vec![]; self.resources.len()];
+        let mut readers: Vec<Vec<usize>> = vec![vec![]; self.resources.len()];
+        for (pass_index, pass) in self.passes.iter().enumerate() {
+            for access in pass.accesses.iter() {
+                match access.kind {
+                    AccessKind::Read => readers[access.resource.0].push(pass_index),
+                    _ => writers[access.resource.0].push(pass_index)
+                }
+            }
+        }
+
+        // a read sees the writes added before it, or all of them when it was added first. writes run in
+        // the order they were added, after the passes reading what the image held before them
+        let mut dependencies: Vec<Vec<usize>> = vec![vec![]; self.passes.len()];
+        for (pass_index, pass) in self.passes.iter().enumerate() {
+            for access in pass.accesses.iter() {
+                let resource_writers = &writers[access.resource.0];
+                let earlier_writers: Vec<usize> = resource_writers.iter().cloned()
+                    .filter(|writer| *writer < pass_index)
+                    .collect();
+                let before: Vec<usize> = match access.kind {
+                    AccessKind::Read => {
+                        if resource_writers.is_empty() && self.resources[access.resource.0].desc.is_some() {
+                            return Err(GraphError::MissingInput {
+                                pass: pass.name.clone(),
+                                resource: self.resources[access.resource.0].name.clone()
+                            });
+                        }
+                        if earlier_writers.is_empty() {
+                            resource_writers.iter().cloned().filter(|writer| *writer != pass_index).collect()
+                        } else {
+                            earlier_writers
+                        }
+                    },
+                    _ => {
+                        let earlier_readers = readers[access.resource.0].iter().cloned()
+                            .filter(|reader| *reader < pass_index && resource_writers.iter().any(|writer| writer < reader));
+                        earlier_writers.iter().cloned().chain(earlier_readers).collect()
+                    }
+                };
+                for dependency in before {
+                    if !dependencies[pass_index].contains(&dependency) {
+                        dependencies[pass_index].push(dependency);
+                    }
+                }
+            }
+        }
+
+        let order = self.sort(&dependencies)?;
+
+        // passes writing imported images are kept, along with everything they depend on
+        let mut live = vec![false; self.passes.len()];
+        for (pass_index, pass) in self.passes.iter().enumerate() {
+            live[pass_index] = pass.accesses.iter()
+                .any(|access| access.kind != AccessKind::Read && self.resources[access.resource.0].desc.is_none());
+        }
+        for pass_index in order.iter().rev() {
+            if live[*pass_index] {
+                for dependency in dependencies[*pass_index].iter() {
+                    live[*dependency] = true;
+                }
+            }
+        }
+        let culled = order.iter().filter(|pass| !live[**pass]).map(|pass| PassId(*pass)).collect();
+        let order: Vec<usize> = order.into_iter().filter(|pass| live[*pass]).collect();
+
+        let passes = self.track_layouts(&order);
+        let final_barriers = self.final_barriers(&passes);
+        let (images, resource_images) = self.alias_images(&order);
+
+        Ok(CompiledGraph {
+            passes,
+            culled,
+            final_barriers,
+            images,
+            resource_images
+        })
+    }
+
+    fn validate(&self) -> Result<(), GraphError> {
+        for pass in self.passes.iter() {
+            for (index, access) in pass.accesses.iter().enumerate() {
+                let resource = self.resources[access.resource.0].name.clone();
+                if access.usage.is_write() != (access.kind != AccessKind::Read) {
+                    return Err(GraphError::InvalidUsage { pass: pass.name.clone(), resource, usage: access.usage });
+                }
+                if pass.accesses[..index].iter().any(|other| other.resource == access.resource) {
+                    return Err(GraphError::ConflictingUsage { pass: pass.name.clone(), resource });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // topological sort, ties go to the pass added first
+    fn sort(&self, dependencies: &[Vec<usize>]) -> Result<Vec<usize>, GraphError> {
+        let mut remaining: Vec<usize> = dependencies.iter().map(|d| d.len()).collect();
+        let mut done = vec![false; self.passes.len()];
+        let mut order = vec![];
+
+        while order.len() < self.passes.len() {
+            let next = match (0..self.passes.len()).find(|pass| !done[*pass] && remaining[*pass] == 0) {
+                Some(next) => next,
+                None => {
+                    let passes = (0..self.passes.len()).filter(|pass| !done[*pass])
+                        .map(|pass| self.passes[pass].name.clone())
+                        .collect();
+                    return Err(GraphError::Cycle { passes });
+                }
+            };
+
+            done[next] = true;
+            order.push(next);
+            for (pass, pass_dependencies) in dependencies.iter().enumerate() {
+                if pass_dependencies.contains(&next) {
+                    remaining[pass] -= 1;
+                }
+            }
+        }
+
+        Ok(order)
+    }
+
+    fn track_layouts(&self, order: &[usize]) -> Vec<CompiledPass> {
+        let mut layouts: Vec<ImageLayout> = self.resources.iter().map(|r| r.initial_layout).collect();
+        let mut last_usage: Vec<Option<ResourceUsage>> = vec![None; self.resources.len()];
+        // imported images come in with contents, transient ones get them from their first writer
+        let mut has_contents: Vec<bool> = self.resources.iter().map(|r| r.desc.is_none()).collect();
+
+        order.iter().enumerate().map(|(position, pass_index)| {
+            let pass = &self.passes[*pass_index];
+            let mut barriers = vec![];
+            let mut attachments = vec![];
+
+            for access in pass.accesses.iter() {
+                let resource = access.resource.0;
+                let new_layout = access.usage.layout();
+                // read after read in the same layout is the only case that needs no synchronization
+                let hazard = match last_usage[resource] {
+                    Some(previous) => previous.is_write() || access.usage.is_write(),
+                    None => false
+                };
+                if layouts[resource] != new_layout || hazard {
+                    barriers.push(Barrier {
+                        resource: access.resource,
+                        old_layout: layouts[resource],
+                        new_layout,
+                        src_usage: last_usage[resource],
+                        dst_usage: access.usage
+                    });
+                }
+
+                if access.usage.is_attachment() {
+                    let load = match access.kind {
+                        AccessKind::Clear => LoadOp::Clear,
+                        _ if has_contents[resource] => LoadOp::Load,
+                        _ => LoadOp::DontCare
+                    };
+                    let used_later = order[position + 1..].iter()
+                        .any(|later| self.passes[*later].accesses.iter().any(|a| a.resource == access.resource));
+                    let store = if used_later || self.resources[resource].desc.is_none() {
+                        StoreOp::Store
+                    } else {
+                        StoreOp::DontCare
+                    };
+                    attachments.push(AttachmentOps { resource: access.resource, usage: access.usage, load, store });
+                }
+
+                layouts[resource] = new_layout;
+                last_usage[resource] = Some(access.usage);
+                if access.kind != AccessKind::Read {
+                    has_contents[resource] = true;
+                }
+            }
+
+            CompiledPass {
+                id: PassId(*pass_index),
+                name: pass.name.clone(),
+                barriers,
+                attachments
+            }
+        }).collect()
+    }
+
+    fn final_barriers(&self, passes: &[CompiledPass]) -> Vec<Barrier> {
+        let mut ret = vec![];
+        for (index, resource) in self.resources.iter().enumerate() {
+            if resource.desc.is_some() {
+                continue;
+            }
+
+            // the last barrier into the image tells its layout and usage at the end of the frame
+            let last = passes.iter().rev()
+                .flat_map(|pass| pass.barriers.iter().rev())
+                .find(|barrier| barrier.resource.0 == index);
+            let last_usage = passes.iter().rev()
+                .flat_map(|pass| self.passes[pass.id.0].accesses.iter())
+                .find(|access| access.resource.0 == index)
+                .map(|access| access.usage);
+            let layout = last.map(|barrier| barrier.new_layout).unwrap_or(resource.initial_layout);
+
+            if layout != resource.final_layout {
+                if let Some(usage) = last_usage {
+                    ret.push(Barrier {
+                        resource: ResourceId(index),
+                        old_layout: layout,
+                        new_layout: resource.final_layout,
+                        src_usage: Some(usage),
+                        dst_usage: usage
+                    });
+                }
+            }
+        }
+
+        ret
+    }
+
+    // greedy interval allocation: a transient image reuses an allocation of the same description
+    // whose previous occupants are no longer used by the time it's first written
+    fn alias_images(&self, order: &[usize]) -> (Vec<PhysicalImage>, Vec<Option<usize>>) {
+        let mut lifetimes: Vec<Option<(usize, usize)>> = vec![None; self.resources.len()];
+        let mut usages: Vec<ImageUsage> = vec![ImageUsage::none(); self.resources.len()];
+        for (position, pass_index) in order.iter().enumerate() {
+            for access in self.passes[*pass_index].accesses.iter() {
+                let lifetime = &mut lifetimes[access.resource.0];
+                *lifetime = Some(match *lifetime {
+                    Some((first, _)) => (first, position),
+                    None => (position, position)
+                });
+                access.usage.add_to(&mut usages[access.resource.0]);
+            }
+        }
+
+        let mut transients: Vec<(usize, (usize, usize))> = (0..self.resources.len())
+            .filter(|index| self.resources[*index].desc.is_some())
+            .filter_map(|index| lifetimes[index].map(|lifetime| (index, lifetime)))
+            .collect();
+        transients.sort_by_key(|(_, (first, _))| *first);
+
+        let mut images: Vec<PhysicalImage> = vec![];
+        let mut image_last_use: Vec<usize> = vec![];
+        let mut resource_images = vec![None; self.resources.len()];
+        for (resource, (first, last)) in transients {
+            let desc = self.resources[resource].desc.unwrap();
+            let reusable = (0..images.len()).find(|image| images[*image].desc == desc && image_last_use[*image] < first);
+            let image = match reusable {
+                Some(image) => image,
+                None => {
+                    images.push(PhysicalImage { desc, usage: ImageUsage::none(), resources: vec![] });
+                    image_last_use.push(0);
+                    images.len() - 1
+                }
+            };
+
+            let usage = &mut images[image].usage;
+            let resource_usage = &usages[resource];
+            usage.color_attachment |= resource_usage.color_attachment;
+            usage.depth_stencil_attachment |= resource_usage.depth_stencil_attachment;
+            usage.sampled |= resource_usage.sampled;
+            usage.transfer_source |= resource_usage.transfer_source;
+            usage.transfer_destination |= resource_usage.transfer_destination;
+            images[image].resources.push(ResourceId(resource));
+            image_last_use[image] = last;
+            resource_images[resource] = Some(image);
+        }
+
+        (images, resource_images)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HDR: ImageDesc = ImageDesc { format: Format::R16G16B16A16Sfloat, size: ImageSize::SwapchainRelative(1.0) };
+    const BLUR: ImageDesc = ImageDesc { format: Format::R16G16B16A16Sfloat, size: ImageSize::Absolute(64, 64) };
+    const DEPTH: ImageDesc = ImageDesc { format: Format::D32Sfloat, size: ImageSize::SwapchainRelative(1.0) };
+
+    struct Frame {
+        graph: RenderGraph,
+        output: ResourceId,
+        resources: Vec<ResourceId>,
+        passes: Vec<PassId>
+    }
+
+    // scene -> three blur steps -> composite into the output, plus a pass nothing reads
+    fn frame() -> Frame {
+        let mut graph = RenderGraph::new();
+        let output = graph.import_image("output", ImageLayout::Undefined, ImageLayout::PresentSrc);
+        let hdr = graph.create_image("hdr", HDR);
+        let depth = graph.create_image("depth", DEPTH);
+        let blurs: Vec<ResourceId> = (0..3).map(|i| graph.create_image(&format!("blur {}", i), BLUR)).collect();
+        let scratch = graph.create_image("scratch", BLUR);
+
+        let scene = graph.add_pass("scene");
+        graph.clear(scene, hdr, ResourceUsage::ColorAttachment);
+        graph.clear(scene, depth, ResourceUsage::DepthStencilAttachment);
+        let mut passes = vec![scene];
+        let mut source = hdr;
+        for (i, blur) in blurs.iter().enumerate() {
+            let pass = graph.add_pass(&format!("blur {}", i));
+            graph.read(pass, source, ResourceUsage::Sampled);
+            graph.clear(pass, *blur, ResourceUsage::ColorAttachment);
+            passes.push(pass);
+            source = *blur;
+        }
+        let composite = graph.add_pass("composite");
+        graph.read(composite, hdr, ResourceUsage::Sampled);
+        graph.read(composite, source, ResourceUsage::Sampled);
+        graph.clear(composite, output, ResourceUsage::ColorAttachment);
+        passes.push(composite);
+        let unused = graph.add_pass("unused");
+        graph.clear(unused, scratch, ResourceUsage::ColorAttachment);
+        passes.push(unused);
+
+        let mut resources = vec![hdr, depth];
+        resources.extend(blurs);
+        resources.push(scratch);
+        Frame { graph, output, resources, passes }
+    }
+
+    #[test]
+    fn passes_run_in_dependency_order() {
+        let mut frame = frame();
+        // writes to the same image run in the order they were added
+        let late = frame.graph.add_pass("late");
+        frame.graph.write(late, frame.output, ResourceUsage::ColorAttachment);
+        let compiled = frame.graph.compile().expect("failed to compile the graph");
+        let names: Vec<&str> = compiled.passes.iter().map(|pass| pass.name.as_str()).collect();
+        assert_eq!(names, vec!["scene", "blur 0", "blur 1", "blur 2", "composite", "late"]);
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let mut graph = RenderGraph::new();
+        let output = graph.import_image("output", ImageLayout::Undefined, ImageLayout::PresentSrc);
+        let a = graph.create_image("a", BLUR);
+        let b = graph.create_image("b", BLUR);
+        let first = graph.add_pass("first");
+        graph.read(first, b, ResourceUsage::Sampled);
+        graph.clear(first, a, ResourceUsage::ColorAttachment);
+        let second = graph.add_pass("second");
+        graph.read(second, a, ResourceUsage::Sampled);
+        graph.clear(second, b, ResourceUsage::ColorAttachment);
+        graph.clear(second, output, ResourceUsage::ColorAttachment);
+
+        assert_eq!(graph.compile(), Err(GraphError::Cycle { passes: vec!["first".to_string(), "second".to_string()] }));
+    }
+
+    #[test]
+    fn reading_an_unwritten_image_is_an_error() {
+        let mut graph = RenderGraph::new();
+        let output = graph.import_image("output", ImageLayout::Undefined, ImageLayout::PresentSrc);
+        let missing = graph.create_image("missing", BLUR);
+        let pass = graph.add_pass("composite");
+        graph.read(pass, missing, ResourceUsage::Sampled);
+        graph.clear(pass, output, ResourceUsage::ColorAttachment);
+
+        assert_eq!(graph.compile(), Err(GraphError::MissingInput {
+            pass: "composite".to_string(),
+            resource: "missing".to_string()
+        }));
+    }
+
+    #[test]
+    fn mismatched_and_repeated_usages_are_errors() {
+        let mut graph = RenderGraph::new();
+        let image = graph.create_image("image", BLUR);
+        let pass = graph.add_pass("pass");
+        graph.read(pass, image, ResourceUsage::ColorAttachment);
+        assert!(match graph.compile() { Err(GraphError::InvalidUsage { .. }) => true, _ => false });
+
+        let mut graph = RenderGraph::new();
+        let image = graph.create_image("image", BLUR);
+        let pass = graph.add_pass("pass");
+        graph.clear(pass, image, ResourceUsage::ColorAttachment);
+        graph.read(pass, image, ResourceUsage::Sampled);
+        assert!(match graph.compile() { Err(GraphError::ConflictingUsage { .. }) => true, _ => false });
+    }
+
+    #[test]
+    fn passes_nothing_depends_on_are_culled() {
+        let frame = frame();
+        let compiled = frame.graph.compile().expect("failed to compile the graph");
+        assert_eq!(compiled.culled, vec![frame.passes[5]]);
+        assert!(compiled.pass(frame.passes[5]).is_none());
+        // the culled pass's image isn't allocated
+        assert_eq!(compiled.image_index(frame.resources[5]), None);
+    }
+
+    #[test]
+    fn disjoint_transients_share_an_image() {
+        let frame = frame();
+        let compiled = frame.graph.compile().expect("failed to compile the graph");
+        let image = |resource: usize| compiled.image_index(frame.resources[resource]).expect("failed to find an image");
+        let (hdr, depth, blur0, blur1, blur2) = (image(0), image(1), image(2), image(3), image(4));
+
+        // blur 0 is done by the time blur 2 is written, blur 1 overlaps both
+        assert_eq!(blur0, blur2);
+        assert_ne!(blur0, blur1);
+        // hdr lives through the whole frame and depth has another format
+        assert!(hdr != depth && hdr != blur0 && hdr != blur1);
+        assert_eq!(compiled.images.len(), 4);
+        assert_eq!(compiled.images[blur0].resources, vec![frame.resources[2], frame.resources[4]]);
+        assert!(compiled.images[blur0].usage.color_attachment && compiled.images[blur0].usage.sampled);
+    }
+
+    #[test]
+    fn attachments_get_load_and_store_ops() {
+        let mut frame = frame();
+        let overlay = frame.graph.add_pass("overlay");
+        frame.graph.write(overlay, frame.output, ResourceUsage::ColorAttachment);
+        let compiled = frame.graph.compile().expect("failed to compile the graph");
+        let ops = |pass: PassId, resource: ResourceId| {
+            let attachment = compiled.pass(pass).expect("failed to find a pass").attachments.iter()
+                .find(|attachment| attachment.resource == resource).expect("failed to find an attachment");
+            (attachment.load, attachment.store)
+        };
+
+        // hdr is sampled later, depth isn't used again
+        assert_eq!(ops(frame.passes[0], frame.resources[0]), (LoadOp::Clear, StoreOp::Store));
+        assert_eq!(ops(frame.passes[0], frame.resources[1]), (LoadOp::Clear, StoreOp::DontCare));
+        // imported images are always stored, a write keeps what the composite drew
+        assert_eq!(ops(frame.passes[4], frame.output), (LoadOp::Clear, StoreOp::Store));
+        assert_eq!(ops(overlay, frame.output), (LoadOp::Load, StoreOp::Store));
+
+        // a write into a transient nothing wrote yet has nothing to load
+        let mut graph = RenderGraph::new();
+        let output = graph.import_image("output", ImageLayout::Undefined, ImageLayout::PresentSrc);
+        let image = graph.create_image("image", BLUR);
+        let first = graph.add_pass("first");
+        graph.write(first, image, ResourceUsage::ColorAttachment);
+        let second = graph.add_pass("second");
+        graph.read(second, image, ResourceUsage::Sampled);
+        graph.clear(second, output, ResourceUsage::ColorAttachment);
+        let compiled = graph.compile().expect("failed to compile the graph");
+        assert_eq!(compiled.passes[0].attachments[0].load, LoadOp::DontCare);
+    }
+
+    #[test]
+    fn layouts_are_tracked_across_passes() {
+        let frame = frame();
+        let compiled = frame.graph.compile().expect("failed to compile the graph");
+        // the first blur samples what the scene drew, the composite's read after it needs no barrier
+        let blur = compiled.pass(frame.passes[1]).expect("failed to find the first blur");
+        let hdr = blur.barriers.iter().find(|barrier| barrier.resource == frame.resources[0])
+            .expect("failed to find the hdr barrier");
+        assert_eq!((hdr.old_layout, hdr.new_layout), (ImageLayout::ColorAttachmentOptimal, ImageLayout::ShaderReadOnlyOptimal));
+        assert_eq!(hdr.src_usage, Some(ResourceUsage::ColorAttachment));
+        let composite = compiled.pass(frame.passes[4]).expect("failed to find the composite");
+        assert!(composite.barriers.iter().all(|barrier| barrier.resource != frame.resources[0]));
+
+        assert_eq!(compiled.final_barriers, vec![Barrier {
+            resource: frame.output,
+            old_layout: ImageLayout::ColorAttachmentOptimal,
+            new_layout: ImageLayout::PresentSrc,
+            src_usage: Some(ResourceUsage::ColorAttachment),
+            dst_usage: ResourceUsage::ColorAttachment
+        }]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,515 @@
+use super::spirv::{self, ShaderStage, ShaderReflection, ScalarType, DescriptorKind, ImageDimensions, InterfaceVariable};
+use super::shadow_map;
+
+use std::borrow::Cow;
+use std::ffi::CString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::descriptor::descriptor::{
+    DescriptorDesc,
+    DescriptorDescTy,
+    DescriptorBufferDesc,
+    DescriptorImageDesc,
+    DescriptorImageDescDimensions,
+    DescriptorImageDescArray,
+    ShaderStages
+};
+use vulkano::descriptor::pipeline_layout::{PipelineLayoutDesc, PipelineLayoutDescPcRange};
+use vulkano::framebuffer::{RenderPassAbstract, Subpass};
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract, viewport::Viewport};
+use vulkano::pipeline::shader::{
+    ShaderModule,
+    ShaderInterfaceDef,
+    ShaderInterfaceDefEntry,
+    GraphicsShaderType,
+    GraphicsEntryPoint
+};
+use vulkano::pipeline::vertex::{SingleBufferDefinition, Vertex};
+
+// built-in pipelines whose shaders can be replaced at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShaderTarget {
+    Lit,
+    Pbr
+}
+
+// `.spv` files are loaded as they are, anything else is compiled as GLSL
+pub fn load_spirv(path: &Path, stage: ShaderStage) -> Result<Vec<u32>, String> {
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if path.extension().map(|extension| extension == "spv").unwrap_or(false) {
+        return spirv::words_from_bytes(&bytes).map_err(|e| format!("{}: {}", path.display(), e));
+    }
+
+    let source = String::from_utf8(bytes).map_err(|e| format!("{}: {}", path.display(), e))?;
+    compile_glsl(&source, stage, &path.display().to_string())
+}
+
+// glsl shared by the built-in shaders, which runtime shaders can include as well
+const INCLUDES: &[(&str, &str)] = &[
+    ("shadow.glsl", shadow_map::SHADOW_GLSL)
+];
+
+// `#include "name"` lines are replaced by the built-in glsl of that name
+pub fn compile_glsl(source: &str, stage: ShaderStage, file_name: &str) -> Result<Vec<u32>, String> {
+    let mut compiler = shaderc::Compiler::new().ok_or_else(|| "failed to create GLSL compiler".to_string())?;
+    let kind = match stage {
+        ShaderStage::Vertex => shaderc::ShaderKind::Vertex,
+        ShaderStage::Fragment => shaderc::ShaderKind::Fragment
+    };
+    let source = expand_includes(source, file_name)?;
+    let artifact = compiler.compile_into_spirv(&source, kind, file_name, "main", None)
+        .map_err(|e| e.to_string())?;
+    Ok(artifact.as_binary().to_vec())
+}
+
+fn expand_includes(source: &str, file_name: &str) -> Result<String, String> {
+    let mut ret = String::with_capacity(source.len());
+    for line in source.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("#include") {
+            let name = trimmed["#include".len()..].trim().trim_matches('"');
+            let (_, glsl) = INCLUDES.iter().find(|(include, _)| *include == name)
+                .ok_or_else(|| format!("{}: no built-in glsl named {}", file_name, name))?;
+            ret.push_str(glsl);
+        } else {
+            ret.push_str(line);
+        }
+        ret.push('\n');
+    }
+    Ok(ret)
+}
+
+fn shader_stages(stage: ShaderStage) -> ShaderStages {
+    ShaderStages {
+        vertex: stage == ShaderStage::Vertex,
+        fragment: stage == ShaderStage::Fragment,
+        .. ShaderStages::none()
+    }
+}
+
+/*********************************
+*** REFLECTED INTERFACES
+*********************************/
+
+// pipeline layout of a runtime shader, built from its reflection
+#[derive(Debug, Clone)]
+pub struct ReflectedLayout {
+    sets: Vec<Vec<Option<DescriptorDesc>>>,
+    push_constants: Option<PipelineLayoutDescPcRange>
+}
+
+impl ReflectedLayout {
+    fn new(reflection: &ShaderReflection) -> ReflectedLayout {
+        let stages = shader_stages(reflection.stage);
+        let mut sets: Vec<Vec<Option<DescriptorDesc>>> = vec![];
+        for descriptor in reflection.descriptors.iter() {
+            let (set, binding) = (descriptor.set as usize, descriptor.binding as usize);
+            if sets.len() <= set {
+                sets.resize(set + 1, vec![]);
+            }
+            if sets[set].len() <= binding {
+                sets[set].resize(binding + 1, None);
+            }
+
+            let readonly = match descriptor.kind {
+                DescriptorKind::StorageBuffer { readonly } => readonly,
+                DescriptorKind::StorageImage { .. } => false,
+                _ => true
+            };
+            sets[set][binding] = Some(DescriptorDesc {
+                ty: descriptor_ty(descriptor.kind),
+                array_count: descriptor.count,
+                stages: stages.clone(),
+                readonly
+            });
+        }
+
+        let push_constants = if reflection.push_constant_size > 0 {
+            Some(PipelineLayoutDescPcRange {
+                offset: 0,
+                size: reflection.push_constant_size as usize,
+                stages: stages.clone()
+            })
+        } else {
+            None
+        };
+
+        ReflectedLayout {
+            sets,
+            push_constants
+        }
+    }
+}
+
+fn descriptor_ty(kind: DescriptorKind) -> DescriptorDescTy {
+    let image = |dimensions: ImageDimensions, arrayed: bool, multisampled: bool, sampled: bool| DescriptorImageDesc {
+        sampled,
+        dimensions: match dimensions {
+            ImageDimensions::OneDimensional => DescriptorImageDescDimensions::OneDimensional,
+            ImageDimensions::TwoDimensional => DescriptorImageDescDimensions::TwoDimensional,
+            ImageDimensions::ThreeDimensional => DescriptorImageDescDimensions::ThreeDimensional,
+            ImageDimensions::Cube => DescriptorImageDescDimensions::Cube
+        },
+        format: None,
+        multisampled,
+        array_layers: if arrayed {
+            DescriptorImageDescArray::Arrayed { max_layers: None }
+        } else {
+            DescriptorImageDescArray::NonArrayed
+        }
+    };
+
+    match kind {
+        DescriptorKind::UniformBuffer => DescriptorDescTy::Buffer(DescriptorBufferDesc { dynamic: Some(false), storage: false }),
+        DescriptorKind::StorageBuffer { .. } => DescriptorDescTy::Buffer(DescriptorBufferDesc { dynamic: Some(false), storage: true }),
+        DescriptorKind::CombinedImageSampler { dimensions, arrayed, multisampled } =>
+            DescriptorDescTy::CombinedImageSampler(image(dimensions, arrayed, multisampled, true)),
+        DescriptorKind::SampledImage { dimensions, arrayed, multisampled } =>
+            DescriptorDescTy::Image(image(dimensions, arrayed, multisampled, true)),
+        DescriptorKind::StorageImage { dimensions, arrayed } =>
+            DescriptorDescTy::Image(image(dimensions, arrayed, false, false)),
+        DescriptorKind::Sampler => DescriptorDescTy::Sampler,
+        DescriptorKind::InputAttachment { multisampled } =>
+            DescriptorDescTy::InputAttachment { multisampled, array_layers: DescriptorImageDescArray::NonArrayed }
+    }
+}
+
+unsafe impl PipelineLayoutDesc for ReflectedLayout {
+    fn num_sets(&self) -> usize {
+        self.sets.len()
+    }
+
+    fn num_bindings_in_set(&self, set: usize) -> Option<usize> {
+        self.sets.get(set).map(|bindings| bindings.len())
+    }
+
+    fn descriptor(&self, set: usize, binding: usize) -> Option<DescriptorDesc> {
+        self.sets.get(set)?.get(binding)?.clone()
+    }
+
+    fn num_push_constants_ranges(&self) -> usize {
+        if self.push_constants.is_some() { 1 } else { 0 }
+    }
+
+    fn push_constants_range(&self, num: usize) -> Option<PipelineLayoutDescPcRange> {
+        if num == 0 { self.push_constants } else { None }
+    }
+}
+
+// stage inputs or outputs of a runtime shader
+#[derive(Debug, Clone)]
+pub struct ReflectedInterface {
+    entries: Vec<ShaderInterfaceDefEntry>
+}
+
+impl ReflectedInterface {
+    fn new(variables: &[InterfaceVariable]) -> Result<ReflectedInterface, String> {
+        let mut entries = vec![];
+        for variable in variables {
+            entries.push(ShaderInterfaceDefEntry {
+                location: variable.location .. variable.location + variable.location_count,
+                format: interface_format(variable.scalar, variable.components)
+                    .ok_or_else(|| format!("unsupported type for interface variable {}", variable.name))?,
+                name: Some(Cow::Owned(variable.name.clone()))
+            });
+        }
+
+        Ok(ReflectedInterface {
+            entries
+        })
+    }
+}
+
+fn interface_format(scalar: ScalarType, components: u32) -> Option<Format> {
+    match (scalar, components) {
+        (ScalarType::Float, 1) => Some(Format::R32Sfloat),
+        (ScalarType::Float, 2) => Some(Format::R32G32Sfloat),
+        (ScalarType::Float, 3) => Some(Format::R32G32B32Sfloat),
+        (ScalarType::Float, 4) => Some(Format::R32G32B32A32Sfloat),
+        (ScalarType::Int, 1) => Some(Format::R32Sint),
+        (ScalarType::Int, 2) => Some(Format::R32G32Sint),
+        (ScalarType::Int, 3) => Some(Format::R32G32B32Sint),
+        (ScalarType::Int, 4) => Some(Format::R32G32B32A32Sint),
+        (ScalarType::Uint, 1) => Some(Format::R32Uint),
+        (ScalarType::Uint, 2) => Some(Format::R32G32Uint),
+        (ScalarType::Uint, 3) => Some(Format::R32G32B32Uint),
+        (ScalarType::Uint, 4) => Some(Format::R32G32B32A32Uint),
+        _ => None
+    }
+}
+
+unsafe impl ShaderInterfaceDef for ReflectedInterface {
+    type Iter = ::std::vec::IntoIter<ShaderInterfaceDefEntry>;
+
+    fn elements(&self) -> Self::Iter {
+        self.entries.clone().into_iter()
+    }
+}
+
+/*********************************
+*** RUNTIME SHADERS
+*********************************/
+
+pub struct RuntimeShader {
+    pub module: Arc<ShaderModule>,
+    pub reflection: ShaderReflection,
+    entry_name: CString,
+    input: ReflectedInterface,
+    output: ReflectedInterface,
+    layout: ReflectedLayout
+}
+
+impl RuntimeShader {
+    pub fn from_spirv(device: &Arc<Device>, words: &[u32]) -> Result<RuntimeShader, String> {
+        let reflection = spirv::reflect(words)?;
+        let entry_name = CString::new(reflection.entry_point.clone()).map_err(|e| e.to_string())?;
+        let input = ReflectedInterface::new(&reflection.inputs)?;
+        let output = ReflectedInterface::new(&reflection.outputs)?;
+        let layout = ReflectedLayout::new(&reflection);
+        // the binary was parsed by the reflection above, so it is at least well formed
+        let module = unsafe { ShaderModule::new(device.clone(), &spirv::bytes_from_words(words)) }
+            .map_err(|e| format!("failed to create shader module: {:?}", e))?;
+
+        Ok(RuntimeShader {
+            module,
+            reflection,
+            entry_name,
+            input,
+            output,
+            layout
+        })
+    }
+
+    pub fn from_glsl(device: &Arc<Device>, source: &str, stage: ShaderStage, file_name: &str) -> Result<RuntimeShader, String> {
+        Self::from_spirv(device, &compile_glsl(source, stage, file_name)?)
+    }
+
+    pub fn load(device: &Arc<Device>, path: &Path, stage: ShaderStage) -> Result<RuntimeShader, String> {
+        let ret = Self::from_spirv(device, &load_spirv(path, stage)?)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        if ret.reflection.stage != stage {
+            return Err(format!("{}: expected a {:?} shader, found {:?}", path.display(), stage, ret.reflection.stage));
+        }
+
+        Ok(ret)
+    }
+
+    pub fn entry_point(&self) -> GraphicsEntryPoint<(), ReflectedInterface, ReflectedInterface, ReflectedLayout> {
+        let ty = match self.reflection.stage {
+            ShaderStage::Vertex => GraphicsShaderType::Vertex,
+            ShaderStage::Fragment => GraphicsShaderType::Fragment
+        };
+        // the interfaces and layout come from the module's own reflection
+        unsafe {
+            self.module.graphics_entry_point(&self.entry_name, self.input.clone(), self.output.clone(),
+                self.layout.clone(), ty)
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+// vertex and fragment shader pair loaded from files, which can be reloaded when the files change
+pub struct ShaderProgram {
+    pub vertex: RuntimeShader,
+    pub fragment: RuntimeShader,
+    vertex_path: PathBuf,
+    fragment_path: PathBuf,
+    modified: [Option<SystemTime>; 2]
+}
+
+impl ShaderProgram {
+    pub fn load(device: &Arc<Device>, vertex_path: &Path, fragment_path: &Path) -> Result<ShaderProgram, String> {
+        // read the times first so a write during loading is seen as a change
+        let modified = [modified_time(vertex_path), modified_time(fragment_path)];
+        let vertex = RuntimeShader::load(device, vertex_path, ShaderStage::Vertex)?;
+        let fragment = RuntimeShader::load(device, fragment_path, ShaderStage::Fragment)?;
+
+        Ok(ShaderProgram {
+            vertex,
+            fragment,
+            vertex_path: vertex_path.to_path_buf(),
+            fragment_path: fragment_path.to_path_buf(),
+            modified
+        })
+    }
+
+    // true once for every time one of the files is written
+    pub fn changed(&mut self) -> bool {
+        let modified = [modified_time(&self.vertex_path), modified_time(&self.fragment_path)];
+        let ret = modified != self.modified;
+        self.modified = modified;
+        ret
+    }
+
+    // loads the files again into a new program, leaving this one untouched if that fails
+    pub fn reload(&self, device: &Arc<Device>) -> Result<ShaderProgram, String> {
+        let mut ret = Self::load(device, &self.vertex_path, &self.fragment_path)?;
+        ret.modified = self.modified;
+        Ok(ret)
+    }
+
+    // same fixed function state as the built-in pipelines the program replaces
+    pub fn create_pipeline<V: Vertex>(
+        &self,
+        device: &Arc<Device>,
+        swap_chain_extent: [u32; 2],
+        render_pass: &Arc<RenderPassAbstract + Send + Sync>
+    ) -> Result<Arc<GraphicsPipelineAbstract + Send + Sync>, String> {
+        let dimensions = [swap_chain_extent[0] as f32, swap_chain_extent[1] as f32];
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions,
+            depth_range: 0.0 .. 1.0,
+        };
+
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input(SingleBufferDefinition::<V>::new())
+            .vertex_shader(self.vertex.entry_point(), ())
+            .triangle_list()
+            .viewports(vec![viewport])
+            .fragment_shader(self.fragment.entry_point(), ())
+            .cull_mode_back()
+            .front_face_clockwise()
+            .depth_stencil_simple_depth()
+            .blend_pass_through()
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .map_err(|e| format!("failed to create pipeline: {:?}", e))?;
+
+        Ok(Arc::new(pipeline))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::spirv::DescriptorBinding;
+
+    fn variable(location: u32, location_count: u32, scalar: ScalarType, components: u32, name: &str) -> InterfaceVariable {
+        InterfaceVariable { location, location_count, scalar, components, name: name.to_string() }
+    }
+
+    fn descriptor(set: u32, binding: u32, kind: DescriptorKind, count: u32) -> DescriptorBinding {
+        DescriptorBinding { set, binding, kind, count, name: String::new() }
+    }
+
+    fn reflection() -> ShaderReflection {
+        ShaderReflection {
+            stage: ShaderStage::Fragment,
+            entry_point: "main".to_string(),
+            inputs: vec![],
+            outputs: vec![],
+            descriptors: vec![
+                descriptor(0, 1, DescriptorKind::StorageBuffer { readonly: true }, 1),
+                descriptor(2, 0, DescriptorKind::CombinedImageSampler { dimensions: ImageDimensions::Cube, arrayed: false, multisampled: false }, 6),
+                descriptor(2, 1, DescriptorKind::StorageImage { dimensions: ImageDimensions::TwoDimensional, arrayed: true }, 1)
+            ],
+            push_constant_size: 80
+        }
+    }
+
+    #[test]
+    fn layouts_leave_gaps_for_unused_sets_and_bindings() {
+        let layout = ReflectedLayout::new(&reflection());
+        assert_eq!(layout.num_sets(), 3);
+        assert_eq!(layout.num_bindings_in_set(0), Some(2));
+        assert_eq!(layout.num_bindings_in_set(1), Some(0));
+        assert_eq!(layout.num_bindings_in_set(2), Some(2));
+        assert_eq!(layout.num_bindings_in_set(3), None);
+        assert!(layout.descriptor(0, 0).is_none());
+        assert!(layout.descriptor(1, 0).is_none());
+    }
+
+    #[test]
+    fn layouts_keep_the_kind_count_and_access_of_descriptors() {
+        let layout = ReflectedLayout::new(&reflection());
+        let stages = ShaderStages { fragment: true, .. ShaderStages::none() };
+
+        let storage = layout.descriptor(0, 1).expect("failed to find the storage buffer");
+        assert_eq!(storage.ty, DescriptorDescTy::Buffer(DescriptorBufferDesc { dynamic: Some(false), storage: true }));
+        assert!(storage.readonly);
+        assert_eq!(storage.stages, stages);
+
+        let cube = layout.descriptor(2, 0).expect("failed to find the cube maps");
+        assert_eq!(cube.array_count, 6);
+        assert!(cube.readonly);
+        match cube.ty {
+            DescriptorDescTy::CombinedImageSampler(image) => {
+                assert_eq!(image.dimensions, DescriptorImageDescDimensions::Cube);
+                assert_eq!(image.array_layers, DescriptorImageDescArray::NonArrayed);
+                assert!(image.sampled);
+            },
+            ty => panic!("expected a combined image sampler, found {:?}", ty)
+        }
+
+        let image = layout.descriptor(2, 1).expect("failed to find the storage image");
+        assert!(!image.readonly);
+        match image.ty {
+            DescriptorDescTy::Image(image) => {
+                assert!(!image.sampled);
+                assert_eq!(image.array_layers, DescriptorImageDescArray::Arrayed { max_layers: None });
+            },
+            ty => panic!("expected an image, found {:?}", ty)
+        }
+    }
+
+    #[test]
+    fn layouts_have_a_push_constant_range_when_the_shader_uses_one() {
+        let layout = ReflectedLayout::new(&reflection());
+        assert_eq!(layout.num_push_constants_ranges(), 1);
+        let range = layout.push_constants_range(0).expect("failed to find the push constants");
+        assert_eq!((range.offset, range.size), (0, 80));
+        assert!(range.stages.fragment && !range.stages.vertex);
+        assert!(layout.push_constants_range(1).is_none());
+
+        let layout = ReflectedLayout::new(&ShaderReflection { push_constant_size: 0, ..reflection() });
+        assert_eq!(layout.num_push_constants_ranges(), 0);
+    }
+
+    #[test]
+    fn interfaces_span_the_locations_of_their_variables() {
+        let interface = ReflectedInterface::new(&[
+            variable(0, 1, ScalarType::Float, 3, "position"),
+            variable(1, 4, ScalarType::Float, 4, "instance"),
+            variable(5, 1, ScalarType::Uint, 2, "ids")
+        ]).expect("failed to build the interface");
+        let entries: Vec<(std::ops::Range<u32>, Format)> = interface.elements().map(|entry| (entry.location, entry.format)).collect();
+        assert_eq!(entries, vec![(0 .. 1, Format::R32G32B32Sfloat), (1 .. 5, Format::R32G32B32A32Sfloat), (5 .. 6, Format::R32G32Uint)]);
+
+        assert!(ReflectedInterface::new(&[variable(0, 1, ScalarType::Int, 5, "wide")]).is_err());
+    }
+
+    #[test]
+    fn includes_are_replaced_by_the_built_in_glsl() {
+        let source = "#version 450\n  #include \"shadow.glsl\"\nvoid main() {}";
+        let expanded = expand_includes(source, "test.frag").expect("failed to expand");
+        assert_eq!(expanded, format!("#version 450\n{}\nvoid main() {{}}\n", shadow_map::SHADOW_GLSL));
+
+        let error = expand_includes("#include \"missing.glsl\"", "test.frag").expect_err("expanded a missing include");
+        assert!(error.contains("test.frag") && error.contains("missing.glsl"), "{}", error);
+    }
+
+    #[test]
+    fn spv_files_are_read_as_they_are() {
+        let path = std::env::temp_dir().join(format!("kitsune_shader_test_{}.spv", std::process::id()));
+        let words = vec![0x0723_0203, 0x0001_0000, 0, 1, 0];
+        fs::write(&path, spirv::bytes_from_words(&words)).expect("failed to write the test shader");
+        assert_eq!(load_spirv(&path, ShaderStage::Vertex), Ok(words));
+
+        fs::write(&path, [1, 2, 3]).expect("failed to write the test shader");
+        let error = load_spirv(&path, ShaderStage::Vertex).expect_err("read a broken binary");
+        assert!(error.starts_with(&path.display().to_string()), "{}", error);
+        fs::remove_file(&path).expect("failed to remove the test shader");
+
+        assert!(load_spirv(&path, ShaderStage::Vertex).is_err());
+    }
+}
\ No newline at end of file
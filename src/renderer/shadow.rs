@@ -0,0 +1,283 @@
+use super::camera::Camera;
+use crate::math::vec3::Vec3;
+use crate::math::mat4::Mat4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SplitScheme {
+    Uniform,
+    Logarithmic,
+    // blend between logarithmic (lambda 1) and uniform (lambda 0) splits
+    Practical(f32)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowSettings {
+    // size in texels of one shadow map tile in the atlas
+    pub map_size: u32,
+    pub cascade_count: u32,
+    pub split_scheme: SplitScheme,
+    // cascades stop at this view distance, or at the camera far plane if closer
+    pub max_distance: f32,
+    // casters up to this distance outside a cascade's bounds still cast into it
+    pub caster_margin: f32,
+    pub constant_bias: f32,
+    pub slope_bias: f32,
+    pub normal_offset: f32,
+    // pcf kernel is (2 * radius + 1)^2 taps
+    pub pcf_radius: u32
+}
+
+impl Default for ShadowSettings {
+    fn default() -> ShadowSettings {
+        ShadowSettings {
+            map_size: 2048,
+            cascade_count: 4,
+            split_scheme: SplitScheme::Practical(0.75),
+            max_distance: 100.0,
+            caster_margin: 50.0,
+            constant_bias: 0.0005,
+            slope_bias: 0.002,
+            normal_offset: 0.02,
+            pcf_radius: 1
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowView {
+    pub view_proj: Mat4,
+    // view distance where this cascade ends, infinite for spot lights
+    pub split_far: f32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowFlags {
+    pub cast: bool,
+    pub receive: bool
+}
+
+impl Default for ShadowFlags {
+    fn default() -> ShadowFlags {
+        ShadowFlags {
+            cast: true,
+            receive: true
+        }
+    }
+}
+
+// std430 layout of one entry in the shadow storage buffer
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ShadowData {
+    pub view_proj: [[f32; 4]; 4],
+    // atlas uv offset in xy, scale in zw
+    pub atlas_rect: [f32; 4],
+    // split far, constant bias, slope bias, normal offset
+    pub params: [f32; 4]
+}
+
+// far distance of each cascade, the first cascade starts at near
+pub fn cascade_splits(near: f32, far: f32, count: u32, scheme: SplitScheme) -> Vec<f32> {
+    let lambda = match scheme {
+        SplitScheme::Uniform => 0.0,
+        SplitScheme::Logarithmic => 1.0,
+        SplitScheme::Practical(lambda) => lambda.max(0.0).min(1.0)
+    };
+
+    // the logarithmic splits are undefined for a near plane at the camera
+    let log_near = near.max(far * 0.0001).max(0.0001);
+
+    (1..=count).map(|i| {
+        let t = i as f32 / count as f32;
+        let logarithmic = log_near * (far / log_near).powf(t);
+        let uniform = near + (far - near) * t;
+        lambda * logarithmic + (1.0 - lambda) * uniform
+    }).collect()
+}
+
+// orthographic light matrix fitted around a bounding sphere of the given corners.
+// the sphere keeps the projection size constant while the camera rotates, and the
+// origin is snapped to whole texels so the shadow edges don't shimmer when it moves
+pub fn directional_light_matrix(direction: &Vec3, corners: &[Vec3], map_size: u32, caster_margin: f32) -> Mat4 {
+    let mut center = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+    for corner in corners {
+        center += corner.clone();
+    }
+    center /= corners.len() as f32;
+
+    let mut radius: f32 = 0.0;
+    for corner in corners {
+        radius = radius.max(corner.distance_to(center.clone()));
+    }
+    radius = (radius * 16.0).ceil() / 16.0;
+
+    let direction = direction.normalized();
+    let eye = center.clone() - direction.clone() * (radius + caster_margin);
+    let view = Mat4::look_at(&eye, &center, &light_up(&direction));
+    let mut proj = Mat4::identity().orthographic_proj_zo(radius, -radius, -radius, radius, 0.0, 2.0 * radius + caster_margin);
+
+    let half_size = map_size as f32 / 2.0;
+    let origin = proj.clone() * &view * &Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+    let offset_x = ((origin.x * half_size).round() - origin.x * half_size) / half_size;
+    let offset_y = ((origin.y * half_size).round() - origin.y * half_size) / half_size;
+    proj[0][3] += offset_x;
+    proj[1][3] += offset_y;
+
+    proj * &view
+}
+
+// perspective light matrix covering the outer cone of a spot light
+pub fn spot_light_matrix(position: &Vec3, direction: &Vec3, outer_angle: f32, range: f32) -> Mat4 {
+    let far = if range > 0.0 { range } else { 100.0 };
+    let near = (far * 0.001).max(0.01);
+    let direction = direction.normalized();
+    let view = Mat4::look_at(position, &(position.clone() + direction.clone()), &light_up(&direction));
+    let fov = (2.0 * outer_angle).min(170.0);
+    Mat4::identity().perspective_fov_zo(fov, 1.0, near, far) * &view
+}
+
+fn light_up(direction: &Vec3) -> Vec3 {
+    if direction.y.abs() > 0.99 {
+        Vec3 { x: 0.0, y: 0.0, z: 1.0 }
+    } else {
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 }
+    }
+}
+
+pub fn directional_cascades(camera: &Camera, direction: &Vec3, settings: &ShadowSettings) -> Vec<ShadowView> {
+    let far = camera.far.min(settings.max_distance);
+    let splits = cascade_splits(camera.near, far, settings.cascade_count, settings.split_scheme);
+
+    let mut split_near = camera.near;
+    splits.iter().map(|split_far| {
+        let corners = camera.frustum_slice_corners(split_near, *split_far);
+        split_near = *split_far;
+        ShadowView {
+            view_proj: directional_light_matrix(direction, &corners, settings.map_size, settings.caster_margin),
+            split_far: *split_far
+        }
+    }).collect()
+}
+
+/*********************************
+*** ATLAS LAYOUT
+*********************************/
+
+// every shadow view gets a square tile in a single depth atlas
+pub fn atlas_tiles_per_row(tile_count: usize) -> u32 {
+    (tile_count as f32).sqrt().ceil().max(1.0) as u32
+}
+
+// offset and scale of a tile in atlas uv space
+pub fn atlas_rect(tile: usize, tiles_per_row: u32) -> [f32; 4] {
+    let scale = 1.0 / tiles_per_row as f32;
+    let x = (tile as u32 % tiles_per_row) as f32;
+    let y = (tile as u32 / tiles_per_row) as f32;
+    [x * scale, y * scale, scale, scale]
+}
+
+pub fn shadow_data(view: &ShadowView, tile: usize, tiles_per_row: u32, settings: &ShadowSettings) -> ShadowData {
+    ShadowData {
+        view_proj: view.view_proj.to_gpu(),
+        atlas_rect: atlas_rect(tile, tiles_per_row),
+        params: [view.split_far, settings.constant_bias, settings.slope_bias, settings.normal_offset]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_splits(splits: &[f32], expected: &[f32]) {
+        assert_eq!(splits.len(), expected.len());
+        for (split, expected) in splits.iter().zip(expected.iter()) {
+            assert!((split - expected).abs() < 1e-3, "{:?} != {:?}", splits, expected);
+        }
+    }
+
+    #[test]
+    fn uniform_splits_are_evenly_spaced() {
+        assert_splits(&cascade_splits(1.0, 9.0, 4, SplitScheme::Uniform), &[3.0, 5.0, 7.0, 9.0]);
+    }
+
+    #[test]
+    fn logarithmic_splits_grow_geometrically() {
+        assert_splits(&cascade_splits(1.0, 16.0, 4, SplitScheme::Logarithmic), &[2.0, 4.0, 8.0, 16.0]);
+    }
+
+    #[test]
+    fn practical_splits_blend_the_schemes() {
+        let uniform = cascade_splits(1.0, 16.0, 4, SplitScheme::Uniform);
+        let logarithmic = cascade_splits(1.0, 16.0, 4, SplitScheme::Logarithmic);
+        let expected: Vec<f32> = uniform.iter().zip(logarithmic.iter()).map(|(u, l)| 0.25 * u + 0.75 * l).collect();
+        assert_splits(&cascade_splits(1.0, 16.0, 4, SplitScheme::Practical(0.75)), &expected);
+        // lambda is clamped to the two schemes
+        assert_splits(&cascade_splits(1.0, 16.0, 4, SplitScheme::Practical(2.0)), &logarithmic);
+        assert_splits(&cascade_splits(1.0, 16.0, 4, SplitScheme::Practical(-1.0)), &uniform);
+    }
+
+    #[test]
+    fn splits_from_a_zero_near_plane_are_finite() {
+        for scheme in [SplitScheme::Uniform, SplitScheme::Logarithmic, SplitScheme::Practical(0.5)].iter() {
+            let splits = cascade_splits(0.0, 100.0, 4, *scheme);
+            assert!(splits.iter().all(|split| split.is_finite() && *split > 0.0), "{:?}", splits);
+            assert!(splits.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", splits);
+            assert!((splits[3] - 100.0).abs() < 1e-3);
+        }
+    }
+
+    fn assert_in_clip_volume(point: &Vec3) {
+        let epsilon = 1e-4;
+        assert!(point.x.abs() <= 1.0 + epsilon && point.y.abs() <= 1.0 + epsilon, "{:?}", point);
+        assert!(point.z >= -epsilon && point.z <= 1.0 + epsilon, "{:?}", point);
+    }
+
+    #[test]
+    fn directional_matrix_covers_the_corners() {
+        let camera = Camera::default();
+        let corners = camera.frustum_slice_corners(1.0, 20.0);
+        let direction = Vec3 { x: 0.3, y: -1.0, z: 0.2 };
+        let view_proj = directional_light_matrix(&direction, &corners, 1024, 10.0);
+        for corner in corners.iter() {
+            assert_in_clip_volume(&(view_proj.clone() * corner));
+        }
+
+        // casters within the margin towards the light still land in front of the near plane
+        let caster = corners[0].clone() - direction.normalized() * 9.0;
+        assert_in_clip_volume(&(view_proj.clone() * &caster));
+    }
+
+    #[test]
+    fn directional_matrix_snaps_to_texels() {
+        let corners = Camera::default().frustum_slice_corners(1.0, 20.0);
+        let direction = Vec3 { x: 0.0, y: -1.0, z: 0.5 };
+        let view_proj = directional_light_matrix(&direction, &corners, 1024, 10.0);
+        let origin = view_proj * &Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+        let texels = origin.x * 512.0;
+        assert!((texels - texels.round()).abs() < 1e-2, "{}", texels);
+    }
+
+    #[test]
+    fn spot_matrix_covers_the_cone() {
+        let position = Vec3 { x: 1.0, y: 4.0, z: 0.0 };
+        let direction = Vec3 { x: 0.0, y: -1.0, z: 0.0 };
+        let view_proj = spot_light_matrix(&position, &direction, 30.0, 10.0);
+
+        let on_axis = view_proj.clone() * &Vec3 { x: 1.0, y: -1.0, z: 0.0 };
+        assert!(on_axis.x.abs() < 1e-4 && on_axis.y.abs() < 1e-4, "{:?}", on_axis);
+        assert_in_clip_volume(&on_axis);
+
+        // just inside the outer cone, at the end of the range
+        let edge = 10.0 * (29.0f32).to_radians().tan();
+        assert_in_clip_volume(&(view_proj.clone() * &Vec3 { x: 1.0 + edge * 0.99, y: -5.9, z: 0.0 }));
+        let far = view_proj * &Vec3 { x: 1.0, y: -5.99, z: 0.0 };
+        assert!(far.z > 0.9 && far.z <= 1.0, "{:?}", far);
+    }
+
+    #[test]
+    fn atlas_tiles_cover_the_atlas() {
+        assert_eq!(atlas_tiles_per_row(0), 1);
+        assert_eq!(atlas_tiles_per_row(4), 2);
+        assert_eq!(atlas_tiles_per_row(5), 3);
+        assert_eq!(atlas_rect(4, 3), [1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,283 @@
+use std::sync::Arc;
+
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::image::AttachmentImage;
+use vulkano::sampler::{Sampler, Filter, MipmapMode, SamplerAddressMode};
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract, viewport::Viewport};
+use vulkano::pipeline::vertex::{SingleBufferDefinition, OneVertexOneInstanceDefinition, Vertex};
+use vulkano::framebuffer::{RenderPassAbstract, Subpass, FramebufferAbstract, Framebuffer};
+
+use super::shadow::atlas_tiles_per_row;
+
+// depth only shaders, positions are projected with the light matrix of the tile being drawn
+pub mod vs {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        src: "
+        #version 450
+        #extension GL_ARB_separate_shader_objects : enable
+
+        layout(location = 0) in vec3 position;
+
+        layout(push_constant) uniform LightMatrix {
+            mat4 view_proj;
+        } light;
+
+        void main() {
+            gl_Position = light.view_proj * vec4(position, 1.0);
+        }"
+    }
+}
+
+// same as vs, with the model matrix of the instance applied first
+pub mod instanced_vs {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        src: "
+        #version 450
+        #extension GL_ARB_separate_shader_objects : enable
+
+        layout(location = 0) in vec3 position;
+        layout(location = 2) in vec4 model_x;
+        layout(location = 3) in vec4 model_y;
+        layout(location = 4) in vec4 model_z;
+        layout(location = 5) in vec4 model_w;
+
+        layout(push_constant) uniform LightMatrix {
+            mat4 view_proj;
+        } light;
+
+        void main() {
+            mat4 model = mat4(model_x, model_y, model_z, model_w);
+            gl_Position = light.view_proj * model * vec4(position, 1.0);
+        }"
+    }
+}
+
+// same as vs, with the vertex moved by its weighted joint matrices first
+pub mod skinned_vs {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        src: "
+        #version 450
+        #extension GL_ARB_separate_shader_objects : enable
+
+        layout(location = 0) in vec3 position;
+        layout(location = 2) in uvec4 joints;
+        layout(location = 3) in vec4 weights;
+
+        layout(set = 0, binding = 0) readonly buffer JointMatrices {
+            mat4 matrices[];
+        } joint_matrices;
+
+        layout(push_constant) uniform LightMatrix {
+            mat4 view_proj;
+        } light;
+
+        void main() {
+            mat4 skin = weights.x * joint_matrices.matrices[joints.x]
+                + weights.y * joint_matrices.matrices[joints.y]
+                + weights.z * joint_matrices.matrices[joints.z]
+                + weights.w * joint_matrices.matrices[joints.w];
+            gl_Position = light.view_proj * skin * vec4(position, 1.0);
+        }"
+    }
+}
+
+pub mod fs {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        src: "
+        #version 450
+        #extension GL_ARB_separate_shader_objects : enable
+
+        void main() {
+        }"
+    }
+}
+
+// shadow atlas sampling of the lit and pbr fragment shaders, put in place of their `#include "shadow.glsl"`.
+// it needs the Light and Shadow structs and the frame, shadow_buffer and shadow_atlas bindings
+pub const SHADOW_GLSL: &str = "
+        float sample_shadow(uint index, vec3 p, vec3 n, vec3 l) {
+            Shadow shadow = shadow_buffer.shadows[index];
+            float n_dot_l = clamp(dot(n, l), 0.0, 1.0);
+            vec3 offset_position = p + n * shadow.params.w * (1.0 - n_dot_l);
+            vec4 clip = shadow.view_proj * vec4(offset_position, 1.0);
+            vec3 ndc = clip.xyz / clip.w;
+            vec2 uv = ndc.xy * 0.5 + 0.5;
+            if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0 || ndc.z > 1.0) {
+                return 1.0;
+            }
+
+            float bias = max(shadow.params.z * (1.0 - n_dot_l), shadow.params.y);
+            float texel = frame.shadow_params.y;
+            vec2 tile_min = shadow.atlas_rect.xy + vec2(texel * 0.5);
+            vec2 tile_max = shadow.atlas_rect.xy + shadow.atlas_rect.zw - vec2(texel * 0.5);
+            vec2 atlas_uv = shadow.atlas_rect.xy + uv * shadow.atlas_rect.zw;
+
+            int radius = int(frame.shadow_params.x);
+            float lit = 0.0;
+            for (int x = -radius; x <= radius; x++) {
+                for (int y = -radius; y <= radius; y++) {
+                    vec2 tap = clamp(atlas_uv + vec2(x, y) * texel, tile_min, tile_max);
+                    lit += ndc.z - bias <= texture(shadow_atlas, tap).r ? 1.0 : 0.0;
+                }
+            }
+            float taps = float((2 * radius + 1) * (2 * radius + 1));
+            return lit / taps;
+        }
+
+        // picks the cascade by view depth for directional lights
+        float shadow_factor(Light light, vec3 p, vec3 n, vec3 l) {
+            if (light.shadow.x < 0.0) {
+                return 1.0;
+            }
+            uint first = uint(light.shadow.x);
+            uint count = uint(light.shadow.y);
+            if (count == 1) {
+                return sample_shadow(first, p, n, l);
+            }
+
+            float depth = dot(p - frame.camera_position.xyz, frame.camera_forward.xyz);
+            for (uint c = 0; c < count; c++) {
+                if (depth < shadow_buffer.shadows[first + c].params.x) {
+                    return sample_shadow(first + c, p, n, l);
+                }
+            }
+            return 1.0;
+        }
+        ";
+
+const DEPTH_FORMAT: Format = Format::D32Sfloat;
+
+// single depth image holding the shadow maps of every shadow casting light, one square tile each
+pub struct ShadowAtlas {
+    pub image: Arc<AttachmentImage<Format>>,
+    pub framebuffer: Arc<FramebufferAbstract + Send + Sync>,
+    pub sampler: Arc<Sampler>,
+    pub tiles_per_row: u32,
+    pub tile_size: u32
+}
+
+impl ShadowAtlas {
+    pub fn new(device: &Arc<Device>, render_pass: &Arc<RenderPassAbstract + Send + Sync>,
+            tile_count: usize, tile_size: u32) -> ShadowAtlas {
+        let tiles_per_row = atlas_tiles_per_row(tile_count);
+        let size = tiles_per_row * tile_size;
+        let image = AttachmentImage::sampled(device.clone(), [size, size], DEPTH_FORMAT)
+            .expect("failed to create shadow atlas");
+        let framebuffer: Arc<FramebufferAbstract + Send + Sync> = Arc::new(Framebuffer::start(render_pass.clone())
+            .add(image.clone()).unwrap()
+            .build().unwrap());
+        // depth is compared manually in the shaders for pcf, so no comparison sampler
+        let sampler = Sampler::new(device.clone(), Filter::Nearest, Filter::Nearest, MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge, SamplerAddressMode::ClampToEdge, SamplerAddressMode::ClampToEdge,
+            0.0, 1.0, 0.0, 0.0).unwrap();
+
+        ShadowAtlas {
+            image,
+            framebuffer,
+            sampler,
+            tiles_per_row,
+            tile_size
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        (self.tiles_per_row * self.tiles_per_row) as usize
+    }
+
+    pub fn size(&self) -> u32 {
+        self.tiles_per_row * self.tile_size
+    }
+
+    pub fn tile_viewport(&self, tile: usize) -> Viewport {
+        let x = (tile as u32 % self.tiles_per_row) * self.tile_size;
+        let y = (tile as u32 / self.tiles_per_row) * self.tile_size;
+        Viewport {
+            origin: [x as f32, y as f32],
+            dimensions: [self.tile_size as f32, self.tile_size as f32],
+            depth_range: 0.0 .. 1.0,
+        }
+    }
+}
+
+pub fn create_render_pass(device: &Arc<Device>) -> Arc<RenderPassAbstract + Send + Sync> {
+    Arc::new(single_pass_renderpass!(device.clone(),
+        attachments: {
+            depth: {
+                load: Clear,
+                store: Store,
+                format: DEPTH_FORMAT,
+                samples: 1,
+            }
+        },
+        pass: {
+            color: [],
+            depth_stencil: {depth}
+        }
+    ).unwrap())
+}
+
+// one pipeline per vertex layout, the shaders only read the position attribute
+pub fn create_pipeline<V: Vertex>(
+    device: &Arc<Device>,
+    render_pass: &Arc<RenderPassAbstract + Send + Sync>,
+    frag_shader_module: &fs::Shader,
+    vert_shader_module: &vs::Shader
+) -> Arc<GraphicsPipelineAbstract + Send + Sync> {
+    Arc::new(GraphicsPipeline::start()
+        .vertex_input(SingleBufferDefinition::<V>::new())
+        .vertex_shader(vert_shader_module.main_entry_point(), ())
+        .triangle_list()
+        .viewports_dynamic_scissors_irrelevant(1)
+        .fragment_shader(frag_shader_module.main_entry_point(), ())
+        // casters are drawn double sided, the bias settings take care of acne
+        .cull_mode_disabled()
+        .depth_stencil_simple_depth()
+        .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+        .build(device.clone())
+        .unwrap())
+}
+
+// for instanced casters, the instance layout I provides the model matrix columns
+pub fn create_instanced_pipeline<V: Vertex, I: Vertex>(
+    device: &Arc<Device>,
+    render_pass: &Arc<RenderPassAbstract + Send + Sync>,
+    frag_shader_module: &fs::Shader,
+    vert_shader_module: &instanced_vs::Shader
+) -> Arc<GraphicsPipelineAbstract + Send + Sync> {
+    Arc::new(GraphicsPipeline::start()
+        .vertex_input(OneVertexOneInstanceDefinition::<V, I>::new())
+        .vertex_shader(vert_shader_module.main_entry_point(), ())
+        .triangle_list()
+        .viewports_dynamic_scissors_irrelevant(1)
+        .fragment_shader(frag_shader_module.main_entry_point(), ())
+        .cull_mode_disabled()
+        .depth_stencil_simple_depth()
+        .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+        .build(device.clone())
+        .unwrap())
+}
+
+// for skinned casters, V has to provide the joints and weights attributes
+pub fn create_skinned_pipeline<V: Vertex>(
+    device: &Arc<Device>,
+    render_pass: &Arc<RenderPassAbstract + Send + Sync>,
+    frag_shader_module: &fs::Shader,
+    vert_shader_module: &skinned_vs::Shader
+) -> Arc<GraphicsPipelineAbstract + Send + Sync> {
+    Arc::new(GraphicsPipeline::start()
+        .vertex_input(SingleBufferDefinition::<V>::new())
+        .vertex_shader(vert_shader_module.main_entry_point(), ())
+        .triangle_list()
+        .viewports_dynamic_scissors_irrelevant(1)
+        .fragment_shader(frag_shader_module.main_entry_point(), ())
+        .cull_mode_disabled()
+        .depth_stencil_simple_depth()
+        .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+        .build(device.clone())
+        .unwrap())
+}
\ No newline at end of file
@@ -0,0 +1,661 @@
+use std::collections::HashMap;
+
+// minimal SPIR-V reader, just enough to build pipeline layouts and interfaces for runtime loaded shaders
+
+const MAGIC: u32 = 0x0723_0203;
+
+// opcodes
+const OP_NAME: u32 = 5;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+// decorations
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_NON_WRITABLE: u32 = 24;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+// storage classes
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_OUTPUT: u32 = 3;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+// execution models
+const EXECUTION_VERTEX: u32 = 0;
+const EXECUTION_FRAGMENT: u32 = 4;
+
+// image dimensions
+const DIM_1D: u32 = 0;
+const DIM_2D: u32 = 1;
+const DIM_3D: u32 = 2;
+const DIM_CUBE: u32 = 3;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScalarType {
+    Float,
+    Int,
+    Uint
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageDimensions {
+    OneDimensional,
+    TwoDimensional,
+    ThreeDimensional,
+    Cube
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DescriptorKind {
+    UniformBuffer,
+    StorageBuffer { readonly: bool },
+    CombinedImageSampler { dimensions: ImageDimensions, arrayed: bool, multisampled: bool },
+    SampledImage { dimensions: ImageDimensions, arrayed: bool, multisampled: bool },
+    StorageImage { dimensions: ImageDimensions, arrayed: bool },
+    Sampler,
+    InputAttachment { multisampled: bool }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub kind: DescriptorKind,
+    // number of array elements, 1 for a single descriptor
+    pub count: u32,
+    pub name: String
+}
+
+// a vertex input or stage output. matrices and arrays take several consecutive locations
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterfaceVariable {
+    pub location: u32,
+    pub location_count: u32,
+    pub scalar: ScalarType,
+    // vector size of a single location
+    pub components: u32,
+    pub name: String
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShaderReflection {
+    pub stage: ShaderStage,
+    pub entry_point: String,
+    pub inputs: Vec<InterfaceVariable>,
+    pub outputs: Vec<InterfaceVariable>,
+    pub descriptors: Vec<DescriptorBinding>,
+    // bytes of push constant data, 0 when the shader has no push constant block
+    pub push_constant_size: u32
+}
+
+#[derive(Debug, Clone)]
+enum Type {
+    Bool,
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { dim: u32, arrayed: bool, multisampled: bool, sampled: u32 },
+    Sampler,
+    SampledImage { image: u32 },
+    Array { element: u32, length: u32 },
+    RuntimeArray,
+    Struct { members: Vec<u32> },
+    Pointer { pointee: u32 }
+}
+
+#[derive(Default)]
+struct Decorations {
+    location: Option<u32>,
+    binding: Option<u32>,
+    set: Option<u32>,
+    array_stride: Option<u32>,
+    built_in: bool,
+    buffer_block: bool,
+    non_writable: bool
+}
+
+#[derive(Default)]
+struct MemberDecorations {
+    offset: Option<u32>,
+    matrix_stride: Option<u32>,
+    non_writable: bool
+}
+
+pub fn words_from_bytes(bytes: &[u8]) -> Result<Vec<u32>, String> {
+    if bytes.len() % 4 != 0 || bytes.len() < 20 {
+        return Err("SPIR-V binary size must be a multiple of 4 and hold a header".to_string());
+    }
+
+    let words: Vec<u32> = bytes.chunks(4)
+        .map(|b| u32::from(b[0]) | u32::from(b[1]) << 8 | u32::from(b[2]) << 16 | u32::from(b[3]) << 24)
+        .collect();
+    // big endian modules have the magic number byte swapped
+    if words[0] == MAGIC {
+        Ok(words)
+    } else if words[0].swap_bytes() == MAGIC {
+        Ok(words.into_iter().map(|w| w.swap_bytes()).collect())
+    } else {
+        Err("not a SPIR-V binary (bad magic number)".to_string())
+    }
+}
+
+pub fn bytes_from_words(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|w| w.to_le_bytes().to_vec()).collect()
+}
+
+fn literal_string(words: &[u32]) -> String {
+    let bytes: Vec<u8> = words.iter()
+        .flat_map(|w| w.to_le_bytes().to_vec())
+        .take_while(|b| *b != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+// reflects the first vertex or fragment entry point of the module
+pub fn reflect(words: &[u32]) -> Result<ShaderReflection, String> {
+    if words.len() < 5 || words[0] != MAGIC {
+        return Err("not a SPIR-V binary (bad magic number)".to_string());
+    }
+
+    let mut names = HashMap::new();
+    let mut types = HashMap::new();
+    let mut constants = HashMap::new();
+    let mut decorations: HashMap<u32, Decorations> = HashMap::new();
+    let mut member_decorations: HashMap<(u32, u32), MemberDecorations> = HashMap::new();
+    // id, pointer type and storage class
+    let mut variables = vec![];
+    let mut entry_point = None;
+
+    let mut i = 5;
+    while i < words.len() {
+        let count = (words[i] >> 16) as usize;
+        let opcode = words[i] & 0xffff;
+        if count == 0 || i + count > words.len() {
+            return Err(format!("malformed instruction at word {}", i));
+        }
+        let op = &words[i + 1 .. i + count];
+        let operand = |n: usize| op.get(n).cloned()
+            .ok_or_else(|| format!("instruction at word {} is missing operands", i));
+
+        match opcode {
+            OP_NAME => { names.insert(operand(0)?, literal_string(&op[1..])); },
+            OP_ENTRY_POINT => {
+                let stage = match operand(0)? {
+                    EXECUTION_VERTEX => Some(ShaderStage::Vertex),
+                    EXECUTION_FRAGMENT => Some(ShaderStage::Fragment),
+                    _ => None
+                };
+                if let (Some(stage), None) = (stage, &entry_point) {
+                    entry_point = Some((stage, literal_string(&op[2..])));
+                }
+            },
+            OP_TYPE_BOOL => { types.insert(operand(0)?, Type::Bool); },
+            OP_TYPE_INT => { types.insert(operand(0)?, Type::Int { width: operand(1)?, signed: operand(2)? == 1 }); },
+            OP_TYPE_FLOAT => { types.insert(operand(0)?, Type::Float { width: operand(1)? }); },
+            OP_TYPE_VECTOR => { types.insert(operand(0)?, Type::Vector { component: operand(1)?, count: operand(2)? }); },
+            OP_TYPE_MATRIX => { types.insert(operand(0)?, Type::Matrix { column: operand(1)?, count: operand(2)? }); },
+            OP_TYPE_IMAGE => {
+                types.insert(operand(0)?, Type::Image {
+                    dim: operand(2)?,
+                    arrayed: operand(4)? == 1,
+                    multisampled: operand(5)? == 1,
+                    sampled: operand(6)?
+                });
+            },
+            OP_TYPE_SAMPLER => { types.insert(operand(0)?, Type::Sampler); },
+            OP_TYPE_SAMPLED_IMAGE => { types.insert(operand(0)?, Type::SampledImage { image: operand(1)? }); },
+            OP_TYPE_ARRAY => { types.insert(operand(0)?, Type::Array { element: operand(1)?, length: operand(2)? }); },
+            OP_TYPE_RUNTIME_ARRAY => { types.insert(operand(0)?, Type::RuntimeArray); },
+            OP_TYPE_STRUCT => { types.insert(operand(0)?, Type::Struct { members: op[1..].to_vec() }); },
+            OP_TYPE_POINTER => { types.insert(operand(0)?, Type::Pointer { pointee: operand(2)? }); },
+            // only 32 bit constants are needed, for array lengths
+            OP_CONSTANT => { constants.insert(operand(1)?, operand(2)?); },
+            OP_VARIABLE => variables.push((operand(1)?, operand(0)?, operand(2)?)),
+            OP_DECORATE => {
+                let entry = decorations.entry(operand(0)?).or_insert_with(Decorations::default);
+                match operand(1)? {
+                    DECORATION_LOCATION => entry.location = Some(operand(2)?),
+                    DECORATION_BINDING => entry.binding = Some(operand(2)?),
+                    DECORATION_DESCRIPTOR_SET => entry.set = Some(operand(2)?),
+                    DECORATION_ARRAY_STRIDE => entry.array_stride = Some(operand(2)?),
+                    DECORATION_BUILT_IN => entry.built_in = true,
+                    DECORATION_BUFFER_BLOCK => entry.buffer_block = true,
+                    DECORATION_NON_WRITABLE => entry.non_writable = true,
+                    _ => {}
+                }
+            },
+            OP_MEMBER_DECORATE => {
+                let entry = member_decorations.entry((operand(0)?, operand(1)?)).or_insert_with(MemberDecorations::default);
+                match operand(2)? {
+                    DECORATION_OFFSET => entry.offset = Some(operand(3)?),
+                    DECORATION_MATRIX_STRIDE => entry.matrix_stride = Some(operand(3)?),
+                    DECORATION_NON_WRITABLE => entry.non_writable = true,
+                    _ => {}
+                }
+            },
+            _ => {}
+        }
+
+        i += count;
+    }
+
+    let (stage, entry_point) = entry_point.ok_or_else(|| "no vertex or fragment entry point".to_string())?;
+    let module = Module { types, constants, decorations, member_decorations };
+
+    let mut ret = ShaderReflection {
+        stage,
+        entry_point,
+        inputs: vec![],
+        outputs: vec![],
+        descriptors: vec![],
+        push_constant_size: 0
+    };
+
+    for (id, pointer, storage) in variables {
+        let name = names.get(&id).cloned().unwrap_or_default();
+        let ty = match module.types.get(&pointer) {
+            Some(Type::Pointer { pointee }) => *pointee,
+            _ => return Err(format!("variable {} doesn't have a pointer type", name))
+        };
+        let decoration = module.decorations.get(&id);
+
+        match storage {
+            STORAGE_INPUT | STORAGE_OUTPUT => {
+                // built-ins and the gl_PerVertex block have no location
+                let location = match decoration {
+                    Some(d) if !d.built_in => match d.location {
+                        Some(location) => location,
+                        None => continue
+                    },
+                    _ => continue
+                };
+                let (scalar, components, location_count) = module.interface_type(ty)
+                    .ok_or_else(|| format!("unsupported type for interface variable {}", name))?;
+                let variable = InterfaceVariable { location, location_count, scalar, components, name };
+                if storage == STORAGE_INPUT {
+                    ret.inputs.push(variable);
+                } else {
+                    ret.outputs.push(variable);
+                }
+            },
+            STORAGE_UNIFORM | STORAGE_UNIFORM_CONSTANT | STORAGE_STORAGE_BUFFER => {
+                let (set, binding) = match decoration {
+                    Some(Decorations { set: Some(set), binding: Some(binding), .. }) => (*set, *binding),
+                    _ => return Err(format!("resource {} is missing a set or binding decoration", name))
+                };
+                let (element, count) = module.array_element(ty)?;
+                let kind = match module.descriptor_kind(element, storage) {
+                    // readonly can be declared on the variable instead of the block
+                    Some(DescriptorKind::StorageBuffer { readonly }) => DescriptorKind::StorageBuffer {
+                        readonly: readonly || decoration.map(|d| d.non_writable).unwrap_or(false)
+                    },
+                    Some(kind) => kind,
+                    None => return Err(format!("unsupported descriptor type for {}", name))
+                };
+                ret.descriptors.push(DescriptorBinding { set, binding, kind, count, name });
+            },
+            STORAGE_PUSH_CONSTANT => {
+                ret.push_constant_size = ret.push_constant_size.max(module.size_of(ty)?);
+            },
+            _ => {}
+        }
+    }
+
+    ret.inputs.sort_by_key(|v| v.location);
+    ret.outputs.sort_by_key(|v| v.location);
+    ret.descriptors.sort_by_key(|d| (d.set, d.binding));
+
+    Ok(ret)
+}
+
+struct Module {
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    decorations: HashMap<u32, Decorations>,
+    member_decorations: HashMap<(u32, u32), MemberDecorations>
+}
+
+impl Module {
+    fn scalar(&self, id: u32) -> Option<ScalarType> {
+        match self.types.get(&id)? {
+            Type::Float { width: 32 } => Some(ScalarType::Float),
+            Type::Int { width: 32, signed: true } => Some(ScalarType::Int),
+            Type::Int { width: 32, signed: false } => Some(ScalarType::Uint),
+            _ => None
+        }
+    }
+
+    // scalar type, components per location and number of locations
+    fn interface_type(&self, id: u32) -> Option<(ScalarType, u32, u32)> {
+        match self.types.get(&id)? {
+            Type::Vector { component, count } => Some((self.scalar(*component)?, *count, 1)),
+            Type::Matrix { column, count } => {
+                let (scalar, components, _) = self.interface_type(*column)?;
+                Some((scalar, components, *count))
+            },
+            Type::Array { element, length } => {
+                let (scalar, components, locations) = self.interface_type(*element)?;
+                Some((scalar, components, locations * self.constants.get(length)?))
+            },
+            _ => Some((self.scalar(id)?, 1, 1))
+        }
+    }
+
+    // arrays of descriptors take one binding with several elements
+    fn array_element(&self, id: u32) -> Result<(u32, u32), String> {
+        match self.types.get(&id) {
+            Some(Type::Array { element, length }) => {
+                let length = self.constants.get(length).cloned()
+                    .ok_or_else(|| "descriptor array length isn't a constant".to_string())?;
+                Ok((*element, length))
+            },
+            Some(Type::RuntimeArray) => Err("runtime sized descriptor arrays are not supported".to_string()),
+            _ => Ok((id, 1))
+        }
+    }
+
+    fn descriptor_kind(&self, id: u32, storage: u32) -> Option<DescriptorKind> {
+        let image_dimensions = |dim: u32| match dim {
+            DIM_1D => Some(ImageDimensions::OneDimensional),
+            DIM_2D => Some(ImageDimensions::TwoDimensional),
+            DIM_3D => Some(ImageDimensions::ThreeDimensional),
+            DIM_CUBE => Some(ImageDimensions::Cube),
+            _ => None
+        };
+
+        match self.types.get(&id)? {
+            Type::Struct { members } => {
+                let decoration = self.decorations.get(&id);
+                let buffer_block = decoration.map(|d| d.buffer_block).unwrap_or(false);
+                if storage == STORAGE_STORAGE_BUFFER || buffer_block {
+                    let readonly = decoration.map(|d| d.non_writable).unwrap_or(false) || (0..members.len() as u32)
+                        .all(|member| self.member_decorations.get(&(id, member)).map(|d| d.non_writable).unwrap_or(false));
+                    Some(DescriptorKind::StorageBuffer { readonly })
+                } else {
+                    Some(DescriptorKind::UniformBuffer)
+                }
+            },
+            Type::SampledImage { image } => match self.types.get(image)? {
+                Type::Image { dim, arrayed, multisampled, .. } => Some(DescriptorKind::CombinedImageSampler {
+                    dimensions: image_dimensions(*dim)?,
+                    arrayed: *arrayed,
+                    multisampled: *multisampled
+                }),
+                _ => None
+            },
+            Type::Image { dim: DIM_SUBPASS_DATA, multisampled, .. } =>
+                Some(DescriptorKind::InputAttachment { multisampled: *multisampled }),
+            Type::Image { dim, arrayed, multisampled, sampled } => match sampled {
+                2 => Some(DescriptorKind::StorageImage { dimensions: image_dimensions(*dim)?, arrayed: *arrayed }),
+                _ => Some(DescriptorKind::SampledImage {
+                    dimensions: image_dimensions(*dim)?,
+                    arrayed: *arrayed,
+                    multisampled: *multisampled
+                })
+            },
+            Type::Sampler => Some(DescriptorKind::Sampler),
+            _ => None
+        }
+    }
+
+    // size in bytes following the explicit layout decorations of the module
+    fn size_of(&self, id: u32) -> Result<u32, String> {
+        self.size_with_stride(id, None)
+    }
+
+    fn size_with_stride(&self, id: u32, matrix_stride: Option<u32>) -> Result<u32, String> {
+        match self.types.get(&id) {
+            Some(Type::Bool) => Ok(4),
+            Some(Type::Int { width, .. }) | Some(Type::Float { width }) => Ok(width / 8),
+            Some(Type::Vector { component, count }) => Ok(self.size_of(*component)? * count),
+            Some(Type::Matrix { column, count }) => match matrix_stride {
+                Some(stride) => Ok(stride * count),
+                None => Ok(self.size_of(*column)? * count)
+            },
+            Some(Type::Array { element, length }) => {
+                let length = self.constants.get(length).cloned()
+                    .ok_or_else(|| "array length isn't a constant".to_string())?;
+                let stride = match self.decorations.get(&id).and_then(|d| d.array_stride) {
+                    Some(stride) => stride,
+                    None => self.size_of(*element)?
+                };
+                Ok(stride * length)
+            },
+            Some(Type::Struct { members }) => {
+                let mut size = 0;
+                for (index, member) in members.iter().enumerate() {
+                    let decoration = self.member_decorations.get(&(id, index as u32));
+                    let offset = decoration.and_then(|d| d.offset).unwrap_or(size);
+                    let member_size = self.size_with_stride(*member, decoration.and_then(|d| d.matrix_stride))?;
+                    size = size.max(offset + member_size);
+                }
+                Ok(size)
+            },
+            _ => Err(format!("can't compute the size of type {}", id))
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OP_TYPE_VOID: u32 = 19;
+    const EXECUTION_GL_COMPUTE: u32 = 5;
+
+    fn instruction(opcode: u32, operands: &[u32]) -> Vec<u32> {
+        let mut ret = vec![((operands.len() as u32 + 1) << 16) | opcode];
+        ret.extend_from_slice(operands);
+        ret
+    }
+
+    // nul terminated and padded to whole words
+    fn string(text: &str) -> Vec<u32> {
+        let mut bytes = text.as_bytes().to_vec();
+        bytes.push(0);
+        while bytes.len() % 4 != 0 {
+            bytes.push(0);
+        }
+        bytes.chunks(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect()
+    }
+
+    fn module(instructions: &[Vec<u32>]) -> Vec<u32> {
+        let mut ret = vec![MAGIC, 0x0001_0000, 0, 100, 0];
+        for instruction in instructions {
+            ret.extend_from_slice(instruction);
+        }
+        ret
+    }
+
+    fn named(opcode: u32, id: u32, name: &str) -> Vec<u32> {
+        let mut operands = vec![id];
+        operands.extend(string(name));
+        instruction(opcode, &operands)
+    }
+
+    // the layout of a vertex shader like
+    //
+    //   layout(set = 0, binding = 0) uniform Camera { mat4 view_proj; vec4 eye; } camera;
+    //   layout(set = 0, binding = 1) readonly buffer Lights { vec4 colors; } lights;
+    //   layout(set = 1, binding = 2) uniform sampler2D textures[4];
+    //   layout(push_constant) uniform Push { mat4 model; vec3 tint; } push;
+    //   layout(location = 0) in vec3 position;
+    //   layout(location = 1) in mat4 instance;
+    //   layout(location = 0) out vec2 uv;
+    //
+    // with gl_VertexIndex as a built-in input
+    fn vertex_shader() -> Vec<u32> {
+        let mut entry_point = vec![EXECUTION_VERTEX, 50];
+        entry_point.extend(string("main"));
+        entry_point.extend(&[25, 27, 29, 31]);
+        module(&[
+            instruction(OP_ENTRY_POINT, &entry_point),
+            named(OP_NAME, 12, "camera"),
+            named(OP_NAME, 17, "textures"),
+            named(OP_NAME, 20, "lights"),
+            named(OP_NAME, 25, "position"),
+            named(OP_NAME, 27, "instance"),
+            named(OP_NAME, 31, "uv"),
+            // decorations come before the types in a real module
+            instruction(OP_MEMBER_DECORATE, &[10, 0, DECORATION_OFFSET, 0]),
+            instruction(OP_MEMBER_DECORATE, &[10, 0, DECORATION_MATRIX_STRIDE, 16]),
+            instruction(OP_MEMBER_DECORATE, &[10, 1, DECORATION_OFFSET, 64]),
+            instruction(OP_DECORATE, &[12, DECORATION_DESCRIPTOR_SET, 0]),
+            instruction(OP_DECORATE, &[12, DECORATION_BINDING, 0]),
+            instruction(OP_DECORATE, &[17, DECORATION_DESCRIPTOR_SET, 1]),
+            instruction(OP_DECORATE, &[17, DECORATION_BINDING, 2]),
+            instruction(OP_DECORATE, &[18, DECORATION_BUFFER_BLOCK]),
+            instruction(OP_MEMBER_DECORATE, &[18, 0, DECORATION_OFFSET, 0]),
+            instruction(OP_MEMBER_DECORATE, &[18, 0, DECORATION_NON_WRITABLE]),
+            instruction(OP_DECORATE, &[20, DECORATION_DESCRIPTOR_SET, 0]),
+            instruction(OP_DECORATE, &[20, DECORATION_BINDING, 1]),
+            instruction(OP_MEMBER_DECORATE, &[21, 0, DECORATION_OFFSET, 0]),
+            instruction(OP_MEMBER_DECORATE, &[21, 0, DECORATION_MATRIX_STRIDE, 16]),
+            instruction(OP_MEMBER_DECORATE, &[21, 1, DECORATION_OFFSET, 64]),
+            instruction(OP_DECORATE, &[25, DECORATION_LOCATION, 0]),
+            instruction(OP_DECORATE, &[27, DECORATION_LOCATION, 1]),
+            instruction(OP_DECORATE, &[29, DECORATION_BUILT_IN, 42]),
+            instruction(OP_DECORATE, &[31, DECORATION_LOCATION, 0]),
+
+            instruction(OP_TYPE_VOID, &[40]),
+            instruction(OP_TYPE_FLOAT, &[1, 32]),
+            instruction(OP_TYPE_VECTOR, &[2, 1, 2]),
+            instruction(OP_TYPE_VECTOR, &[3, 1, 3]),
+            instruction(OP_TYPE_VECTOR, &[4, 1, 4]),
+            instruction(OP_TYPE_MATRIX, &[5, 4, 4]),
+            instruction(OP_TYPE_INT, &[6, 32, 0]),
+            instruction(OP_TYPE_INT, &[8, 32, 1]),
+            instruction(OP_CONSTANT, &[6, 7, 4]),
+
+            instruction(OP_TYPE_STRUCT, &[10, 5, 4]),
+            instruction(OP_TYPE_POINTER, &[11, STORAGE_UNIFORM, 10]),
+            instruction(OP_VARIABLE, &[11, 12, STORAGE_UNIFORM]),
+
+            instruction(OP_TYPE_IMAGE, &[13, 1, DIM_2D, 0, 0, 0, 1, 0]),
+            instruction(OP_TYPE_SAMPLED_IMAGE, &[14, 13]),
+            instruction(OP_TYPE_ARRAY, &[15, 14, 7]),
+            instruction(OP_TYPE_POINTER, &[16, STORAGE_UNIFORM_CONSTANT, 15]),
+            instruction(OP_VARIABLE, &[16, 17, STORAGE_UNIFORM_CONSTANT]),
+
+            instruction(OP_TYPE_STRUCT, &[18, 4]),
+            instruction(OP_TYPE_POINTER, &[19, STORAGE_UNIFORM, 18]),
+            instruction(OP_VARIABLE, &[19, 20, STORAGE_UNIFORM]),
+
+            instruction(OP_TYPE_STRUCT, &[21, 5, 3]),
+            instruction(OP_TYPE_POINTER, &[22, STORAGE_PUSH_CONSTANT, 21]),
+            instruction(OP_VARIABLE, &[22, 23, STORAGE_PUSH_CONSTANT]),
+
+            instruction(OP_TYPE_POINTER, &[24, STORAGE_INPUT, 3]),
+            instruction(OP_VARIABLE, &[24, 25, STORAGE_INPUT]),
+            instruction(OP_TYPE_POINTER, &[26, STORAGE_INPUT, 5]),
+            instruction(OP_VARIABLE, &[26, 27, STORAGE_INPUT]),
+            instruction(OP_TYPE_POINTER, &[28, STORAGE_INPUT, 8]),
+            instruction(OP_VARIABLE, &[28, 29, STORAGE_INPUT]),
+            instruction(OP_TYPE_POINTER, &[30, STORAGE_OUTPUT, 2]),
+            instruction(OP_VARIABLE, &[30, 31, STORAGE_OUTPUT])
+        ])
+    }
+
+    #[test]
+    fn reflects_the_entry_point_and_interface() {
+        let reflection = reflect(&vertex_shader()).expect("failed to reflect");
+        assert_eq!(reflection.stage, ShaderStage::Vertex);
+        assert_eq!(reflection.entry_point, "main");
+        // gl_VertexIndex is left out
+        assert_eq!(reflection.inputs, vec![
+            InterfaceVariable { location: 0, location_count: 1, scalar: ScalarType::Float, components: 3, name: "position".to_string() },
+            InterfaceVariable { location: 1, location_count: 4, scalar: ScalarType::Float, components: 4, name: "instance".to_string() }
+        ]);
+        assert_eq!(reflection.outputs, vec![
+            InterfaceVariable { location: 0, location_count: 1, scalar: ScalarType::Float, components: 2, name: "uv".to_string() }
+        ]);
+    }
+
+    #[test]
+    fn reflects_descriptors_sorted_by_set_and_binding() {
+        let reflection = reflect(&vertex_shader()).expect("failed to reflect");
+        assert_eq!(reflection.descriptors, vec![
+            DescriptorBinding { set: 0, binding: 0, kind: DescriptorKind::UniformBuffer, count: 1, name: "camera".to_string() },
+            DescriptorBinding { set: 0, binding: 1, kind: DescriptorKind::StorageBuffer { readonly: true }, count: 1, name: "lights".to_string() },
+            DescriptorBinding {
+                set: 1,
+                binding: 2,
+                kind: DescriptorKind::CombinedImageSampler { dimensions: ImageDimensions::TwoDimensional, arrayed: false, multisampled: false },
+                count: 4,
+                name: "textures".to_string()
+            }
+        ]);
+    }
+
+    #[test]
+    fn push_constants_follow_the_offsets() {
+        // a mat4 then a vec3 at 64
+        assert_eq!(reflect(&vertex_shader()).expect("failed to reflect").push_constant_size, 76);
+    }
+
+    #[test]
+    fn resources_need_a_set_and_binding() {
+        let words = vertex_shader();
+        // drop the binding of the camera, decorations are 4 words long
+        let binding = instruction(OP_DECORATE, &[12, DECORATION_BINDING, 0]);
+        let at = words.windows(4).position(|window| window == &binding[..]).expect("failed to find the decoration");
+        let mut broken = words;
+        broken.drain(at..at + 4);
+        let error = reflect(&broken).expect_err("reflected without a binding");
+        assert!(error.contains("camera"), "{}", error);
+    }
+
+    #[test]
+    fn modules_need_a_vertex_or_fragment_entry_point() {
+        let mut entry_point = vec![EXECUTION_GL_COMPUTE, 50];
+        entry_point.extend(string("main"));
+        assert!(reflect(&module(&[instruction(OP_ENTRY_POINT, &entry_point)])).is_err());
+        assert!(reflect(&[0x1234_5678, 0, 0, 0, 0]).is_err());
+        // an instruction running past the end
+        assert!(reflect(&module(&[vec![(5 << 16) | OP_NAME, 1]])).is_err());
+    }
+
+    #[test]
+    fn bytes_of_either_endianness_are_read() {
+        let words = vertex_shader();
+        let bytes = bytes_from_words(&words);
+        assert_eq!(words_from_bytes(&bytes), Ok(words.clone()));
+
+        let swapped: Vec<u8> = words.iter().flat_map(|w| w.to_be_bytes().to_vec()).collect();
+        assert_eq!(words_from_bytes(&swapped), Ok(words));
+        assert!(words_from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(words_from_bytes(&[0; 20]).is_err());
+    }
+}
\ No newline at end of file
@@ -1 +1,48 @@
-// todo
\ No newline at end of file
+// cpu side rgba8 image, uploaded to the gpu when a material using it is added
+#[derive(Debug, Clone, PartialEq)]
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[u8; 4]>,
+    // color data (base color, emissive) is stored in sRGB, everything else is linear
+    pub srgb: bool
+}
+
+impl Texture {
+    pub fn new(width: u32, height: u32, pixels: Vec<[u8; 4]>, srgb: bool) -> Result<Texture, String> {
+        if pixels.len() != (width * height) as usize {
+            return Err(format!("{} pixels don't fill a {}x{} texture", pixels.len(), width, height));
+        }
+        Ok(Texture {
+            width,
+            height,
+            pixels,
+            srgb
+        })
+    }
+
+    // 1x1 texture, used in place of missing material maps
+    pub fn solid(color: [u8; 4], srgb: bool) -> Texture {
+        Texture {
+            width: 1,
+            height: 1,
+            pixels: vec![color],
+            srgb
+        }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> [u8; 4] {
+        self.pixels[(y * self.width + x) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_checks_the_pixel_count() {
+        assert!(Texture::new(2, 2, vec![[0; 4]; 4], true).is_ok());
+        assert!(Texture::new(2, 2, vec![[0; 4]; 3], true).is_err());
+    }
+}
\ No newline at end of file
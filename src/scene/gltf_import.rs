@@ -0,0 +1,829 @@
+use super::node::{Node, MeshInstance};
+use super::transform::Transform;
+use crate::renderer::mesh::Mesh;
+use crate::renderer::material::PbrMaterial;
+use crate::renderer::texture::Texture;
+use crate::renderer::camera::Camera;
+use crate::renderer::light::{Light, Attenuation};
+use crate::math::vec3::Vec3;
+use crate::math::mat4::Mat4;
+use crate::math::quaternion::Quat;
+use crate::math::rad2deg;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use gltf::buffer;
+use gltf::image;
+use gltf::mesh::Mode;
+use gltf::animation::{Property, Interpolation, util::ReadOutputs};
+use gltf::camera::Projection;
+use gltf::khr_lights_punctual::Kind;
+
+#[derive(Debug)]
+pub enum ImportError {
+    Gltf(gltf::Error),
+    MissingPositions { mesh: usize, primitive: usize },
+    AttributeCountMismatch { mesh: usize, primitive: usize, attribute: &'static str, expected: usize, found: usize },
+    IndexOutOfRange { mesh: usize, primitive: usize, index: u32, vertex_count: usize },
+    InvalidIndexCount { mesh: usize, primitive: usize, count: usize },
+    UnsupportedPrimitiveMode { mesh: usize, primitive: usize, mode: Mode },
+    JointOutOfRange { node: usize, joint: u16, joint_count: usize },
+    InverseBindMatrixCount { skin: usize, expected: usize, found: usize },
+    ImageSizeMismatch { image: usize, expected: usize, found: usize },
+    AnimationSamplerMismatch { animation: usize, channel: usize, inputs: usize, outputs: usize },
+    MissingAnimationData { animation: usize, channel: usize },
+    MultipleParents { node: usize },
+    HierarchyCycle { node: usize }
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::Gltf(e) => write!(f, "gltf error: {}", e),
+            ImportError::MissingPositions { mesh, primitive } =>
+                write!(f, "mesh {} primitive {} has no POSITION attribute", mesh, primitive),
+            ImportError::AttributeCountMismatch { mesh, primitive, attribute, expected, found } =>
+                write!(f, "mesh {} primitive {}: {} has {} elements, expected {}", mesh, primitive, attribute, found, expected),
+            ImportError::IndexOutOfRange { mesh, primitive, index, vertex_count } =>
+                write!(f, "mesh {} primitive {}: index {} out of range for {} vertices", mesh, primitive, index, vertex_count),
+            ImportError::InvalidIndexCount { mesh, primitive, count } =>
+                write!(f, "mesh {} primitive {}: {} indices don't form whole triangles", mesh, primitive, count),
+            ImportError::UnsupportedPrimitiveMode { mesh, primitive, mode } =>
+                write!(f, "mesh {} primitive {}: unsupported primitive mode {:?}", mesh, primitive, mode),
+            ImportError::JointOutOfRange { node, joint, joint_count } =>
+                write!(f, "node {}: joint index {} out of range for a skin with {} joints", node, joint, joint_count),
+            ImportError::InverseBindMatrixCount { skin, expected, found } =>
+                write!(f, "skin {} has {} inverse bind matrices, expected {}", skin, found, expected),
+            ImportError::ImageSizeMismatch { image, expected, found } =>
+                write!(f, "image {} has {} bytes of pixel data, expected {}", image, found, expected),
+            ImportError::AnimationSamplerMismatch { animation, channel, inputs, outputs } =>
+                write!(f, "animation {} channel {}: {} keyframe times for {} output values", animation, channel, inputs, outputs),
+            ImportError::MissingAnimationData { animation, channel } =>
+                write!(f, "animation {} channel {} has no keyframe data", animation, channel),
+            ImportError::MultipleParents { node } => write!(f, "node {} is the child of more than one node", node),
+            ImportError::HierarchyCycle { node } => write!(f, "node {} is its own ancestor", node)
+        }
+    }
+}
+
+impl Error for ImportError {}
+
+impl From<gltf::Error> for ImportError {
+    fn from(error: gltf::Error) -> ImportError {
+        ImportError::Gltf(error)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SceneCamera {
+    Perspective(Camera),
+    Orthographic {
+        x_mag: f32,
+        y_mag: f32,
+        near: f32,
+        far: f32
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedSkin {
+    pub name: String,
+    // node index of every joint, in the order the JOINTS_0 attribute refers to them
+    pub joints: Vec<usize>,
+    pub inverse_bind_matrices: Vec<Mat4>,
+    pub skeleton: Option<usize>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelProperty {
+    Translation,
+    Rotation,
+    Scale,
+    MorphWeights
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelInterpolation {
+    Step,
+    Linear,
+    // values hold in tangent, value and out tangent for every keyframe
+    CubicSpline
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedChannel {
+    pub node: usize,
+    pub property: ChannelProperty,
+    pub interpolation: ChannelInterpolation,
+    pub times: Vec<f32>,
+    // flattened keyframe values, `components` floats per value
+    pub values: Vec<f32>,
+    pub components: usize
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedAnimation {
+    pub name: String,
+    pub channels: Vec<ImportedChannel>,
+    pub duration: f32
+}
+
+// lights and cameras are stored in their node's local space, see world_lights and world_camera
+pub struct GltfScene {
+    pub nodes: Vec<Node>,
+    pub roots: Vec<usize>,
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<PbrMaterial>,
+    pub textures: Vec<Arc<Texture>>,
+    pub cameras: Vec<SceneCamera>,
+    pub lights: Vec<Light>,
+    pub skins: Vec<ImportedSkin>,
+    pub animations: Vec<ImportedAnimation>
+}
+
+impl GltfScene {
+    pub fn world_matrix(&self, node: usize) -> Mat4 {
+        let local = self.nodes[node].transform.to_matrix();
+        match self.nodes[node].parent {
+            Some(parent) => self.world_matrix(parent) * &local,
+            None => local
+        }
+    }
+
+    // every light placed at its node, lights point down their node's -z axis
+    pub fn world_lights(&self) -> Vec<Light> {
+        let mut ret = vec![];
+        for (index, node) in self.nodes.iter().enumerate() {
+            let light = match node.light {
+                Some(light) => self.lights[light].clone(),
+                None => continue
+            };
+            let world = self.world_matrix(index);
+            let world_position = world.clone() * &Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+            let world_direction = world.transform_vector(&Vec3 { x: 0.0, y: 0.0, z: -1.0 }).normalized();
+
+            ret.push(match light {
+                Light::Directional { color, intensity, cast_shadows, .. } =>
+                    Light::Directional { direction: world_direction, color, intensity, cast_shadows },
+                Light::Point { color, intensity, attenuation, .. } =>
+                    Light::Point { position: world_position, color, intensity, attenuation },
+                Light::Spot { color, intensity, attenuation, inner_angle, outer_angle, cast_shadows, .. } =>
+                    Light::Spot { position: world_position, direction: world_direction, color, intensity,
+                        attenuation, inner_angle, outer_angle, cast_shadows },
+                ambient => ambient
+            });
+        }
+
+        ret
+    }
+
+    // cameras look down their node's -z axis
+    pub fn world_camera(&self, node: usize) -> Option<SceneCamera> {
+        let camera = self.cameras[self.nodes[node].camera?].clone();
+        match camera {
+            SceneCamera::Perspective(mut camera) => {
+                let world = self.world_matrix(node);
+                camera.position = world.clone() * &Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+                camera.forward = world.transform_vector(&Vec3 { x: 0.0, y: 0.0, z: -1.0 }).normalized();
+                camera.up = world.transform_vector(&Vec3 { x: 0.0, y: 1.0, z: 0.0 }).normalized();
+                Some(SceneCamera::Perspective(camera))
+            },
+            orthographic => Some(orthographic)
+        }
+    }
+}
+
+// loads .gltf (with external or embedded buffers) and .glb files
+pub fn import<P: AsRef<Path>>(path: P) -> Result<GltfScene, ImportError> {
+    let (document, buffers, images) = gltf::import(path)?;
+    build_scene(&document, &buffers, &images)
+}
+
+pub fn import_slice(bytes: &[u8]) -> Result<GltfScene, ImportError> {
+    let (document, buffers, images) = gltf::import_slice(bytes)?;
+    build_scene(&document, &buffers, &images)
+}
+
+fn build_scene(document: &gltf::Document, buffers: &[buffer::Data], images: &[image::Data]) -> Result<GltfScene, ImportError> {
+    let mut textures = TextureCache::new(images);
+    let materials = document.materials()
+        .map(|material| import_material(&material, &mut textures))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // every gltf primitive becomes its own engine mesh
+    let mut meshes = vec![];
+    let mut mesh_instances = vec![];
+    for mesh in document.meshes() {
+        let mut instances = vec![];
+        for primitive in mesh.primitives() {
+            instances.push(MeshInstance {
+                mesh: meshes.len(),
+                material: primitive.material().index()
+            });
+            meshes.push(import_primitive(mesh.index(), &primitive, buffers)?);
+        }
+        mesh_instances.push(instances);
+    }
+
+    let cameras = document.cameras().map(|camera| import_camera(&camera)).collect();
+    let lights = match document.lights() {
+        Some(lights) => lights.map(|light| import_light(&light)).collect(),
+        None => vec![]
+    };
+
+    let mut nodes = vec![];
+    for node in document.nodes() {
+        let (translation, rotation, scale) = node.transform().decomposed();
+        let mut ret = Node::new(node.name().unwrap_or(""), Transform::new(
+            Vec3 { x: translation[0], y: translation[1], z: translation[2] },
+            Quat { x: rotation[0], y: rotation[1], z: rotation[2], w: rotation[3] },
+            Vec3 { x: scale[0], y: scale[1], z: scale[2] }
+        ));
+        ret.children = node.children().map(|child| child.index()).collect();
+        ret.meshes = node.mesh().map(|mesh| mesh_instances[mesh.index()].clone()).unwrap_or_default();
+        ret.camera = node.camera().map(|camera| camera.index());
+        ret.light = node.light().map(|light| light.index());
+        ret.skin = node.skin().map(|skin| skin.index());
+        nodes.push(ret);
+    }
+    for index in 0..nodes.len() {
+        for child in nodes[index].children.clone() {
+            if nodes[child].parent.is_some() {
+                return Err(ImportError::MultipleParents { node: child });
+            }
+            nodes[child].parent = Some(index);
+        }
+    }
+    validate_hierarchy(&nodes)?;
+
+    let skins = document.skins()
+        .map(|skin| import_skin(&skin, buffers))
+        .collect::<Result<Vec<_>, _>>()?;
+    validate_joints(&nodes, &meshes, &skins)?;
+
+    let animations = document.animations()
+        .map(|animation| import_animation(&animation, buffers))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let roots = match document.default_scene().or_else(|| document.scenes().next()) {
+        Some(scene) => scene.nodes().map(|node| node.index()).collect(),
+        None => (0..nodes.len()).filter(|i| nodes[*i].parent.is_none()).collect()
+    };
+
+    Ok(GltfScene {
+        nodes,
+        roots,
+        meshes,
+        materials,
+        textures: textures.into_textures(),
+        cameras,
+        lights,
+        skins,
+        animations
+    })
+}
+
+/*********************************
+*** MESHES
+*********************************/
+
+fn check_count(mesh: usize, primitive: usize, attribute: &'static str, expected: usize, found: usize) -> Result<(), ImportError> {
+    if expected == found {
+        Ok(())
+    } else {
+        Err(ImportError::AttributeCountMismatch { mesh, primitive, attribute, expected, found })
+    }
+}
+
+fn import_primitive(mesh_index: usize, primitive: &gltf::Primitive, buffers: &[buffer::Data]) -> Result<Mesh, ImportError> {
+    let primitive_index = primitive.index();
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+
+    let positions: Vec<Vec3> = reader.read_positions()
+        .ok_or(ImportError::MissingPositions { mesh: mesh_index, primitive: primitive_index })?
+        .map(|p| Vec3 { x: p[0], y: p[1], z: p[2] })
+        .collect();
+    let vertex_count = positions.len();
+
+    let normals: Vec<Vec3> = reader.read_normals()
+        .map(|normals| normals.map(|n| Vec3 { x: n[0], y: n[1], z: n[2] }).collect())
+        .unwrap_or_default();
+    let uvs: Vec<Vec3> = reader.read_tex_coords(0)
+        .map(|uvs| uvs.into_f32().map(|uv| Vec3 { x: uv[0], y: uv[1], z: 0.0 }).collect())
+        .unwrap_or_default();
+    let tangents: Vec<[f32; 4]> = reader.read_tangents().map(|t| t.collect()).unwrap_or_default();
+    let colors: Vec<[f32; 4]> = reader.read_colors(0).map(|c| c.into_rgba_f32().collect()).unwrap_or_default();
+    let joints: Vec<[u16; 4]> = reader.read_joints(0).map(|j| j.into_u16().collect()).unwrap_or_default();
+    let weights: Vec<[f32; 4]> = reader.read_weights(0).map(|w| w.into_f32().collect()).unwrap_or_default();
+
+    for (attribute, count) in [("NORMAL", normals.len()), ("TEXCOORD_0", uvs.len()), ("TANGENT", tangents.len()),
+            ("COLOR_0", colors.len()), ("JOINTS_0", joints.len()), ("WEIGHTS_0", weights.len())].iter() {
+        if *count > 0 {
+            check_count(mesh_index, primitive_index, attribute, vertex_count, *count)?;
+        }
+    }
+    if !joints.is_empty() || !weights.is_empty() {
+        check_count(mesh_index, primitive_index, "WEIGHTS_0", joints.len(), weights.len())?;
+    }
+
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..vertex_count as u32).collect()
+    };
+    if let Some(index) = indices.iter().find(|index| **index as usize >= vertex_count) {
+        return Err(ImportError::IndexOutOfRange { mesh: mesh_index, primitive: primitive_index, index: *index, vertex_count });
+    }
+    let indices = triangulate(mesh_index, primitive_index, primitive.mode(), indices)?;
+
+    // attributes are per vertex in gltf, so the separate uv and normal index streams stay empty
+    let mut ret = Mesh::new(positions, indices, uvs, vec![], normals, vec![]);
+    ret.tangents = tangents;
+    ret.colors = colors;
+    ret.joints = joints;
+    ret.weights = weights;
+
+    Ok(ret)
+}
+
+// converts strips and fans to triangle lists
+fn triangulate(mesh: usize, primitive: usize, mode: Mode, indices: Vec<u32>) -> Result<Vec<u32>, ImportError> {
+    match mode {
+        Mode::Triangles => {
+            if indices.len() % 3 != 0 {
+                return Err(ImportError::InvalidIndexCount { mesh, primitive, count: indices.len() });
+            }
+            Ok(indices)
+        },
+        Mode::TriangleStrip => {
+            let mut ret = vec![];
+            for i in 0..indices.len().saturating_sub(2) {
+                // every other triangle of a strip has flipped winding
+                if i % 2 == 0 {
+                    ret.extend_from_slice(&[indices[i], indices[i + 1], indices[i + 2]]);
+                } else {
+                    ret.extend_from_slice(&[indices[i + 1], indices[i], indices[i + 2]]);
+                }
+            }
+            Ok(ret)
+        },
+        Mode::TriangleFan => {
+            let mut ret = vec![];
+            for i in 1..indices.len().saturating_sub(1) {
+                ret.extend_from_slice(&[indices[0], indices[i], indices[i + 1]]);
+            }
+            Ok(ret)
+        },
+        mode => Err(ImportError::UnsupportedPrimitiveMode { mesh, primitive, mode })
+    }
+}
+
+/*********************************
+*** MATERIALS AND TEXTURES
+*********************************/
+
+// the same image can be used as color (sRGB) and as data (linear), so textures are keyed by both
+struct TextureCache<'a> {
+    images: &'a [image::Data],
+    indices: HashMap<(usize, bool), usize>,
+    textures: Vec<Arc<Texture>>
+}
+
+impl<'a> TextureCache<'a> {
+    fn new(images: &'a [image::Data]) -> TextureCache<'a> {
+        TextureCache {
+            images,
+            indices: HashMap::new(),
+            textures: vec![]
+        }
+    }
+
+    fn get(&mut self, texture: &gltf::Texture, srgb: bool) -> Result<Arc<Texture>, ImportError> {
+        let image = texture.source().index();
+        if let Some(index) = self.indices.get(&(image, srgb)) {
+            return Ok(self.textures[*index].clone());
+        }
+
+        let ret = Arc::new(convert_image(image, &self.images[image], srgb)?);
+        self.indices.insert((image, srgb), self.textures.len());
+        self.textures.push(ret.clone());
+        Ok(ret)
+    }
+
+    fn into_textures(self) -> Vec<Arc<Texture>> {
+        self.textures
+    }
+}
+
+fn convert_image(index: usize, data: &image::Data, srgb: bool) -> Result<Texture, ImportError> {
+    use gltf::image::Format;
+    let (channels, bytes_per_channel, bgr) = match data.format {
+        Format::R8 => (1, 1, false),
+        Format::R8G8 => (2, 1, false),
+        Format::R8G8B8 => (3, 1, false),
+        Format::R8G8B8A8 => (4, 1, false),
+        Format::B8G8R8 => (3, 1, true),
+        Format::B8G8R8A8 => (4, 1, true),
+        Format::R16 => (1, 2, false),
+        Format::R16G16 => (2, 2, false),
+        Format::R16G16B16 => (3, 2, false),
+        Format::R16G16B16A16 => (4, 2, false)
+    };
+
+    let pixel_count = (data.width * data.height) as usize;
+    let expected = pixel_count * channels * bytes_per_channel;
+    if data.pixels.len() != expected {
+        return Err(ImportError::ImageSizeMismatch { image: index, expected, found: data.pixels.len() });
+    }
+
+    // 16 bit channels are little endian, keep the high byte
+    let channel = |pixel: usize, c: usize| data.pixels[(pixel * channels + c) * bytes_per_channel + bytes_per_channel - 1];
+    let pixels = (0..pixel_count).map(|pixel| {
+        let mut rgba = match channels {
+            1 => [channel(pixel, 0), channel(pixel, 0), channel(pixel, 0), 255],
+            2 => [channel(pixel, 0), channel(pixel, 1), 0, 255],
+            3 => [channel(pixel, 0), channel(pixel, 1), channel(pixel, 2), 255],
+            _ => [channel(pixel, 0), channel(pixel, 1), channel(pixel, 2), channel(pixel, 3)]
+        };
+        if bgr {
+            rgba.swap(0, 2);
+        }
+        rgba
+    }).collect();
+
+    Texture::new(data.width, data.height, pixels, srgb)
+        .map_err(|_| ImportError::ImageSizeMismatch { image: index, expected, found: data.pixels.len() })
+}
+
+fn import_material(material: &gltf::Material, textures: &mut TextureCache) -> Result<PbrMaterial, ImportError> {
+    let pbr = material.pbr_metallic_roughness();
+    let base_color = pbr.base_color_factor();
+    let emissive = material.emissive_factor();
+
+    let mut ret = PbrMaterial {
+        base_color: Vec3 { x: base_color[0], y: base_color[1], z: base_color[2] },
+        opacity: base_color[3],
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+        emissive: Vec3 { x: emissive[0], y: emissive[1], z: emissive[2] },
+        .. PbrMaterial::default()
+    };
+
+    if let Some(info) = pbr.base_color_texture() {
+        ret.base_color_map = Some(textures.get(&info.texture(), true)?);
+    }
+    if let Some(info) = pbr.metallic_roughness_texture() {
+        ret.metallic_roughness_map = Some(textures.get(&info.texture(), false)?);
+    }
+    if let Some(normal) = material.normal_texture() {
+        ret.normal_scale = normal.scale();
+        ret.normal_map = Some(textures.get(&normal.texture(), false)?);
+    }
+    if let Some(occlusion) = material.occlusion_texture() {
+        ret.occlusion_strength = occlusion.strength();
+        ret.occlusion_map = Some(textures.get(&occlusion.texture(), false)?);
+    }
+    if let Some(info) = material.emissive_texture() {
+        ret.emissive_map = Some(textures.get(&info.texture(), true)?);
+    }
+
+    Ok(ret)
+}
+
+/*********************************
+*** CAMERAS AND LIGHTS
+*********************************/
+
+fn import_camera(camera: &gltf::Camera) -> SceneCamera {
+    match camera.projection() {
+        Projection::Perspective(perspective) => SceneCamera::Perspective(Camera::new(
+            Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+            Vec3 { x: 0.0, y: 0.0, z: -1.0 },
+            Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+            rad2deg(perspective.yfov()),
+            perspective.aspect_ratio().unwrap_or(Camera::default().aspect),
+            perspective.znear(),
+            // an infinite projection is approximated by a far plane
+            perspective.zfar().unwrap_or(1000.0)
+        )),
+        Projection::Orthographic(orthographic) => SceneCamera::Orthographic {
+            x_mag: orthographic.xmag(),
+            y_mag: orthographic.ymag(),
+            near: orthographic.znear(),
+            far: orthographic.zfar()
+        }
+    }
+}
+
+fn import_light(light: &gltf::khr_lights_punctual::Light) -> Light {
+    let c = light.color();
+    let color = Vec3 { x: c[0], y: c[1], z: c[2] };
+    let intensity = light.intensity();
+    // gltf lights fall off with the inverse square of the distance
+    let attenuation = Attenuation::new(1.0, 0.0, 1.0, light.range().unwrap_or(0.0));
+    let origin = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+    let forward = Vec3 { x: 0.0, y: 0.0, z: -1.0 };
+
+    match light.kind() {
+        Kind::Directional => Light::Directional { direction: forward, color, intensity, cast_shadows: false },
+        Kind::Point => Light::Point { position: origin, color, intensity, attenuation },
+        Kind::Spot { inner_cone_angle, outer_cone_angle } => Light::Spot {
+            position: origin,
+            direction: forward,
+            color,
+            intensity,
+            attenuation,
+            inner_angle: rad2deg(inner_cone_angle),
+            outer_angle: rad2deg(outer_cone_angle),
+            cast_shadows: false
+        }
+    }
+}
+
+/*********************************
+*** SKINS AND ANIMATIONS
+*********************************/
+
+fn import_skin(skin: &gltf::Skin, buffers: &[buffer::Data]) -> Result<ImportedSkin, ImportError> {
+    let joints: Vec<usize> = skin.joints().map(|joint| joint.index()).collect();
+    let reader = skin.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+
+    // gltf matrices are column major
+    let inverse_bind_matrices: Vec<Mat4> = match reader.read_inverse_bind_matrices() {
+        Some(matrices) => matrices.map(|m| Mat4 { mat: m }.transpose()).collect(),
+        None => vec![Mat4::identity(); joints.len()]
+    };
+    if inverse_bind_matrices.len() != joints.len() {
+        return Err(ImportError::InverseBindMatrixCount {
+            skin: skin.index(),
+            expected: joints.len(),
+            found: inverse_bind_matrices.len()
+        });
+    }
+
+    Ok(ImportedSkin {
+        name: skin.name().unwrap_or("").to_string(),
+        joints,
+        inverse_bind_matrices,
+        skeleton: skin.skeleton().map(|node| node.index())
+    })
+}
+
+// with one parent per node, a chain of parents longer than the node count goes around a cycle.
+// world_matrix walks these chains, so they have to end
+fn validate_hierarchy(nodes: &[Node]) -> Result<(), ImportError> {
+    for index in 0..nodes.len() {
+        let mut parent = nodes[index].parent;
+        for _ in 0..nodes.len() {
+            parent = match parent {
+                Some(parent) => nodes[parent].parent,
+                None => break
+            };
+        }
+        if parent.is_some() {
+            return Err(ImportError::HierarchyCycle { node: index });
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_joints(nodes: &[Node], meshes: &[Mesh], skins: &[ImportedSkin]) -> Result<(), ImportError> {
+    for (index, node) in nodes.iter().enumerate() {
+        let joint_count = match node.skin {
+            Some(skin) => skins[skin].joints.len(),
+            None => continue
+        };
+        for instance in node.meshes.iter() {
+            for joints in meshes[instance.mesh].joints.iter() {
+                if let Some(joint) = joints.iter().find(|joint| **joint as usize >= joint_count) {
+                    return Err(ImportError::JointOutOfRange { node: index, joint: *joint, joint_count });
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn import_animation(animation: &gltf::Animation, buffers: &[buffer::Data]) -> Result<ImportedAnimation, ImportError> {
+    let mut channels = vec![];
+    let mut duration: f32 = 0.0;
+
+    for (channel_index, channel) in animation.channels().enumerate() {
+        let reader = channel.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+        let missing = ImportError::MissingAnimationData { animation: animation.index(), channel: channel_index };
+        let times: Vec<f32> = match reader.read_inputs() {
+            Some(inputs) => inputs.collect(),
+            None => return Err(missing)
+        };
+
+        let interpolation = match channel.sampler().interpolation() {
+            Interpolation::Step => ChannelInterpolation::Step,
+            Interpolation::Linear => ChannelInterpolation::Linear,
+            Interpolation::CubicSpline => ChannelInterpolation::CubicSpline
+        };
+        let values_per_key = if interpolation == ChannelInterpolation::CubicSpline { 3 } else { 1 };
+
+        let (values, components): (Vec<f32>, usize) = match reader.read_outputs() {
+            Some(ReadOutputs::Translations(translations)) => (translations.flat_map(|t| t.to_vec()).collect(), 3),
+            Some(ReadOutputs::Rotations(rotations)) => (rotations.into_f32().flat_map(|r| r.to_vec()).collect(), 4),
+            Some(ReadOutputs::Scales(scales)) => (scales.flat_map(|s| s.to_vec()).collect(), 3),
+            Some(ReadOutputs::MorphTargetWeights(weights)) => {
+                // one weight per morph target of the node's mesh
+                let weights: Vec<f32> = weights.into_f32().collect();
+                let targets = channel.target().node().mesh()
+                    .and_then(|mesh| mesh.primitives().next())
+                    .map(|primitive| primitive.morph_targets().len());
+                let values = times.len() * values_per_key;
+                let per_value = match targets {
+                    Some(targets) => targets,
+                    None if values > 0 => weights.len() / values,
+                    None => 0
+                };
+                (weights, per_value)
+            },
+            None => return Err(missing)
+        };
+
+        let property = match channel.target().property() {
+            Property::Translation => ChannelProperty::Translation,
+            Property::Rotation => ChannelProperty::Rotation,
+            Property::Scale => ChannelProperty::Scale,
+            Property::MorphTargetWeights => ChannelProperty::MorphWeights
+        };
+
+        let output_count = if components > 0 { values.len() / components } else { 0 };
+        if output_count != times.len() * values_per_key || output_count * components != values.len() {
+            return Err(ImportError::AnimationSamplerMismatch {
+                animation: animation.index(),
+                channel: channel_index,
+                inputs: times.len(),
+                outputs: output_count
+            });
+        }
+
+        if let Some(last) = times.last() {
+            duration = duration.max(*last);
+        }
+        channels.push(ImportedChannel {
+            node: channel.target().node().index(),
+            property,
+            interpolation,
+            times,
+            values,
+            components
+        });
+    }
+
+    Ok(ImportedAnimation {
+        name: animation.name().unwrap_or("").to_string(),
+        channels,
+        duration
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a binary gltf with the json chunk and an optional buffer chunk
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        while json.len() % 4 != 0 {
+            json.push(b' ');
+        }
+        let mut bin = bin.to_vec();
+        while bin.len() % 4 != 0 {
+            bin.push(0);
+        }
+
+        let mut chunks = vec![];
+        chunks.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        chunks.extend_from_slice(b"JSON");
+        chunks.extend_from_slice(&json);
+        if !bin.is_empty() {
+            chunks.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+            chunks.extend_from_slice(b"BIN\0");
+            chunks.extend_from_slice(&bin);
+        }
+
+        let mut ret = vec![];
+        ret.extend_from_slice(b"glTF");
+        ret.extend_from_slice(&2u32.to_le_bytes());
+        ret.extend_from_slice(&(12 + chunks.len() as u32).to_le_bytes());
+        ret.extend_from_slice(&chunks);
+        ret
+    }
+
+    fn floats(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|value| value.to_le_bytes().to_vec()).collect()
+    }
+
+    // a triangle with two morph targets, animated by a sampler with the given interpolation
+    fn morph_scene(interpolation: &str, values_per_key: usize) -> Vec<u8> {
+        let triangle = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let mut bin = floats(&triangle);
+        bin.extend(floats(&triangle));
+        bin.extend(floats(&triangle));
+        bin.extend(floats(&[0.0, 1.0]));
+        let weights: Vec<f32> = (0..2 * values_per_key * 2).map(|i| i as f32).collect();
+        bin.extend(floats(&weights));
+
+        let json = format!(r#"{{
+            "asset": {{ "version": "2.0" }},
+            "buffers": [{{ "byteLength": {} }}],
+            "bufferViews": [
+                {{ "buffer": 0, "byteOffset": 0, "byteLength": 108 }},
+                {{ "buffer": 0, "byteOffset": 108, "byteLength": 8 }},
+                {{ "buffer": 0, "byteOffset": 116, "byteLength": {} }}
+            ],
+            "accessors": [
+                {{ "bufferView": 0, "byteOffset": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                    "min": [0, 0, 0], "max": [1, 1, 0] }},
+                {{ "bufferView": 0, "byteOffset": 36, "componentType": 5126, "count": 3, "type": "VEC3",
+                    "min": [0, 0, 0], "max": [1, 1, 0] }},
+                {{ "bufferView": 0, "byteOffset": 72, "componentType": 5126, "count": 3, "type": "VEC3",
+                    "min": [0, 0, 0], "max": [1, 1, 0] }},
+                {{ "bufferView": 1, "componentType": 5126, "count": 2, "type": "SCALAR", "min": [0], "max": [1] }},
+                {{ "bufferView": 2, "componentType": 5126, "count": {}, "type": "SCALAR" }}
+            ],
+            "meshes": [{{ "primitives": [{{
+                "attributes": {{ "POSITION": 0 }},
+                "targets": [{{ "POSITION": 1 }}, {{ "POSITION": 2 }}]
+            }}] }}],
+            "nodes": [{{ "mesh": 0 }}],
+            "animations": [{{
+                "channels": [{{ "sampler": 0, "target": {{ "node": 0, "path": "weights" }} }}],
+                "samplers": [{{ "input": 3, "output": 4, "interpolation": "{}" }}]
+            }}]
+        }}"#, bin.len(), weights.len() * 4, weights.len(), interpolation);
+        glb(&json, &bin)
+    }
+
+    #[test]
+    fn morph_weight_channels_have_a_weight_per_target() {
+        let scene = import_slice(&morph_scene("LINEAR", 1)).expect("failed to import linear morph weights");
+        let channel = &scene.animations[0].channels[0];
+        assert_eq!(channel.property, ChannelProperty::MorphWeights);
+        assert_eq!(channel.components, 2);
+        assert_eq!(channel.values.len(), 4);
+    }
+
+    #[test]
+    fn cubic_spline_morph_weights_are_imported() {
+        let scene = import_slice(&morph_scene("CUBICSPLINE", 3)).expect("failed to import cubic spline morph weights");
+        let channel = &scene.animations[0].channels[0];
+        assert_eq!(channel.interpolation, ChannelInterpolation::CubicSpline);
+        assert_eq!(channel.components, 2);
+        assert_eq!(channel.values.len(), 12);
+    }
+
+    #[test]
+    fn too_few_morph_weights_are_rejected() {
+        // linear keyframes with cubic spline sized outputs
+        match import_slice(&morph_scene("STEP", 3)) {
+            Err(ImportError::AnimationSamplerMismatch { inputs: 2, outputs: 6, .. }) => {}
+            other => panic!("expected a sampler mismatch, got {:?}", other.map(|_| ()))
+        }
+    }
+
+    fn node_scene(nodes: &str) -> Vec<u8> {
+        glb(&format!(r#"{{ "asset": {{ "version": "2.0" }}, "nodes": {} }}"#, nodes), &[])
+    }
+
+    #[test]
+    fn world_matrix_applies_the_parents() {
+        let scene = import_slice(&node_scene(r#"[
+            { "children": [1], "translation": [1, 0, 0] },
+            { "children": [2], "translation": [0, 2, 0] },
+            { "translation": [0, 0, 3] }
+        ]"#)).expect("failed to import a node chain");
+        assert_eq!(scene.roots, vec![0]);
+        let position = scene.world_matrix(2) * &Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+        assert_eq!(position, Vec3 { x: 1.0, y: 2.0, z: 3.0 });
+    }
+
+    #[test]
+    fn cyclic_hierarchies_are_rejected() {
+        match import_slice(&node_scene(r#"[{ "children": [1] }, { "children": [2] }, { "children": [0] }]"#)) {
+            Err(ImportError::HierarchyCycle { .. }) => {}
+            other => panic!("expected a cycle, got {:?}", other.map(|_| ()))
+        }
+        match import_slice(&node_scene(r#"[{ "children": [0] }]"#)) {
+            Err(ImportError::HierarchyCycle { node: 0 }) => {}
+            other => panic!("expected a cycle, got {:?}", other.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn nodes_with_two_parents_are_rejected() {
+        match import_slice(&node_scene(r#"[{ "children": [2] }, { "children": [2] }, {}]"#)) {
+            Err(ImportError::MultipleParents { node: 2 }) => {}
+            other => panic!("expected a node with two parents, got {:?}", other.map(|_| ()))
+        }
+    }
+}
\ No newline at end of file
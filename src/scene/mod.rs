@@ -0,0 +1,3 @@
+pub mod gltf_import;
+pub mod node;
+pub mod transform;
\ No newline at end of file
@@ -0,0 +1,36 @@
+use super::transform::Transform;
+
+// a mesh drawn by a node with the material it uses
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshInstance {
+    pub mesh: usize,
+    pub material: Option<usize>
+}
+
+// node of a scene hierarchy. everything it refers to is an index into the owning scene
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub name: String,
+    pub transform: Transform,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub meshes: Vec<MeshInstance>,
+    pub camera: Option<usize>,
+    pub light: Option<usize>,
+    pub skin: Option<usize>
+}
+
+impl Node {
+    pub fn new(name: &str, transform: Transform) -> Node {
+        Node {
+            name: name.to_string(),
+            transform,
+            parent: None,
+            children: vec![],
+            meshes: vec![],
+            camera: None,
+            light: None,
+            skin: None
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,49 @@
+use crate::math::vec3::Vec3;
+use crate::math::mat4::Mat4;
+use crate::math::quaternion::Quat;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3
+}
+
+impl Transform {
+    pub fn new(translation: Vec3, rotation: Quat, scale: Vec3) -> Transform {
+        Transform {
+            translation,
+            rotation,
+            scale
+        }
+    }
+
+    // scale first, then rotation, then translation
+    pub fn to_matrix(&self) -> Mat4 {
+        let s = &self.scale;
+        let scale = Mat4 {
+            mat: [
+                [s.x, 0.0, 0.0, 0.0],
+                [0.0, s.y, 0.0, 0.0],
+                [0.0, 0.0, s.z, 0.0],
+                [0.0, 0.0, 0.0, 1.0]
+            ]
+        };
+        let mut ret = self.rotation.to_mat4() * &scale;
+        ret[0][3] = self.translation.x;
+        ret[1][3] = self.translation.y;
+        ret[2][3] = self.translation.z;
+
+        ret
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform {
+            translation: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+            rotation: Quat::identity(),
+            scale: Vec3 { x: 1.0, y: 1.0, z: 1.0 }
+        }
+    }
+}
\ No newline at end of file